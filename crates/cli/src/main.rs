@@ -86,6 +86,7 @@ async fn main() -> std::io::Result<()> {
         Commands::Group(command) => command.exec(client).await,
         Commands::Queue(command) => command.exec(client).await,
         Commands::Report(command) => command.exec(client).await,
+        Commands::Quarantine(command) => command.exec(client).await,
     }
 
     Ok(())
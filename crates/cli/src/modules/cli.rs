@@ -65,7 +65,7 @@ pub enum Commands {
     #[clap(subcommand)]
     Import(ImportCommands),
 
-    /// Export JMAP accounts
+    /// Export JMAP accounts and Maildir/mbox mailboxes
     #[clap(subcommand)]
     Export(ExportCommands),
 
@@ -80,6 +80,10 @@ pub enum Commands {
     /// Manage SMTP DMARC/TLS report queue
     #[clap(subcommand)]
     Report(ReportCommands),
+
+    /// Manage quarantined messages
+    #[clap(subcommand)]
+    Quarantine(QuarantineCommands),
 }
 
 pub struct Client {
@@ -390,6 +394,22 @@ pub enum ImportCommands {
 
 #[derive(Subcommand)]
 pub enum ExportCommands {
+    /// Export messages and folders
+    Messages {
+        #[clap(value_enum)]
+        #[clap(short, long)]
+        format: MailboxFormat,
+
+        /// Number of concurrent blob downloads to perform, defaults to the number of CPUs.
+        #[clap(short, long)]
+        num_concurrent: Option<usize>,
+
+        /// Account name or email to export messages from
+        account: String,
+
+        /// Path to export the messages to
+        path: String,
+    },
     /// Export a JMAP account
     Account {
         /// Number of concurrent blob downloads to perform, defaults to the number of CPUs.
@@ -518,6 +538,52 @@ pub enum QueueCommands {
         // Cancel one or multiple message ids
         ids: Vec<String>,
     },
+
+    /// Edit a queued message
+    Edit {
+        /// Message id
+        id: String,
+        /// Remove one or multiple recipients
+        #[clap(short, long)]
+        remove_rcpt: Vec<String>,
+        /// Change the message priority
+        #[clap(short, long)]
+        priority: Option<i16>,
+        /// Route the message through a relay host (an empty value removes the override)
+        #[clap(short = 'l', long)]
+        relay: Option<String>,
+        /// Change the expiration time of pending domains
+        #[clap(short, long)]
+        #[arg(value_parser = parse_datetime)]
+        expires: Option<DateTime>,
+    },
+
+    /// Search the trace of received and delivered messages
+    Trace {
+        /// Filter by sender address
+        #[clap(short, long)]
+        sender: Option<String>,
+        /// Filter by recipient
+        #[clap(short, long)]
+        rcpt: Option<String>,
+        /// Filter by recipient domain
+        #[clap(short, long)]
+        domain: Option<String>,
+        /// Filter by Message-ID
+        #[clap(short, long)]
+        message_id: Option<String>,
+        /// Filter messages received before a certain datetime
+        #[clap(short, long)]
+        #[arg(value_parser = parse_datetime)]
+        before: Option<DateTime>,
+        /// Filter messages received after a certain datetime
+        #[clap(short, long)]
+        #[arg(value_parser = parse_datetime)]
+        after: Option<DateTime>,
+        /// Number of items to show per page
+        #[clap(short, long)]
+        page_size: Option<usize>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -549,6 +615,43 @@ pub enum ReportCommands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum QuarantineCommands {
+    /// Shows quarantined messages
+    List {
+        /// Filter by sender address
+        #[clap(short, long)]
+        sender: Option<String>,
+        /// Filter by recipient
+        #[clap(short, long)]
+        rcpt: Option<String>,
+        /// Number of items to show per page
+        #[clap(short, long)]
+        page_size: Option<usize>,
+    },
+
+    /// Displays details and a preview of quarantined messages
+    Show {
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+
+    /// Release quarantined messages into the delivery queue
+    Release {
+        /// Deliver through a specific relay host (use 'local' for local delivery)
+        #[clap(short, long)]
+        relay_host: Option<String>,
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+
+    /// Delete quarantined messages
+    Delete {
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Deserialize)]
 pub enum ReportFormat {
     /// DMARC report
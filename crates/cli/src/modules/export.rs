@@ -22,25 +22,30 @@
 */
 
 use std::{
+    collections::HashMap,
+    fs::File,
+    io::Write,
     path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, SystemTime},
 };
 
 use futures::{stream::FuturesUnordered, StreamExt};
 use jmap_client::{
     email::{self, Email},
     identity::{self, Identity},
-    mailbox::{self, Mailbox},
+    mailbox::{self, Mailbox, Role},
     sieve::{self, SieveScript},
     vacation_response::{self, VacationResponse},
 };
+use mail_parser::DateTime;
 use serde::Serialize;
 use tokio::io::AsyncWriteExt;
 
 use crate::modules::RETRY_ATTEMPTS;
 
 use super::{
-    cli::{Client, ExportCommands},
+    cli::{Client, ExportCommands, MailboxFormat},
     name_to_id, UnwrapResult,
 };
 
@@ -106,7 +111,7 @@ impl ExportCommands {
                             match client.download(&blob_id).await {
                                 Ok(bytes) => break bytes,
                                 Err(_) if retry_count < RETRY_ATTEMPTS => {
-                                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                                    tokio::time::sleep(Duration::from_secs(1)).await;
                                     retry_count += 1;
                                 }
                                 result => {
@@ -136,6 +141,94 @@ impl ExportCommands {
                 // Wait for remaining futures
                 while futures.next().await.is_some() {}
             }
+            ExportCommands::Messages {
+                format,
+                num_concurrent,
+                account,
+                path,
+            } => {
+                client.set_default_account_id(name_to_id(&client, &account).await);
+                let max_objects_in_get = client
+                    .session()
+                    .core_capabilities()
+                    .map(|c| c.max_objects_in_get())
+                    .unwrap_or(500);
+
+                // Create directory
+                let mut path = PathBuf::from(path);
+                if !path.is_dir() {
+                    eprintln!("Directory {} does not exist.", path.display());
+                    std::process::exit(1);
+                }
+                path.push(&account);
+                if !path.is_dir() {
+                    std::fs::create_dir(&path).unwrap_or_else(|_| {
+                        eprintln!("Failed to create directory: {}", path.display());
+                        std::process::exit(1);
+                    });
+                }
+
+                // Create folders
+                let mut folders = Folders::new(
+                    format,
+                    &path,
+                    &fetch_mailboxes(&client, max_objects_in_get).await,
+                );
+                eprintln!("Exported {} mailboxes.", folders.folders.len());
+
+                // Download and write messages
+                let emails = fetch_emails(&client, max_objects_in_get).await;
+                let client = Arc::new(client);
+                let num_concurrent = num_concurrent.unwrap_or_else(num_cpus::get);
+                let mut futures = FuturesUnordered::new();
+                let mut total_exported = 0;
+                eprintln!("Exporting {} messages...", emails.len());
+                for email in emails {
+                    let client = client.clone();
+                    futures.push(async move {
+                        let blob_id = if let Some(blob_id) = email.blob_id() {
+                            blob_id
+                        } else {
+                            eprintln!(
+                                "Warning: email {:?} has no blobId",
+                                email.id().unwrap_or_default()
+                            );
+                            return None;
+                        };
+                        let mut retry_count = 0;
+
+                        loop {
+                            match client.download(blob_id).await {
+                                Ok(bytes) => break (email, bytes).into(),
+                                Err(_) if retry_count < RETRY_ATTEMPTS => {
+                                    tokio::time::sleep(Duration::from_secs(1)).await;
+                                    retry_count += 1;
+                                }
+                                result => {
+                                    result.unwrap_result("download blob");
+                                    break None;
+                                }
+                            }
+                        }
+                    });
+
+                    if futures.len() == num_concurrent {
+                        if let Some((email, bytes)) = futures.next().await.unwrap() {
+                            total_exported += folders.write_message(&email, &bytes);
+                        }
+                    }
+                }
+
+                // Wait for remaining futures
+                while let Some(result) = futures.next().await {
+                    if let Some((email, bytes)) = result {
+                        total_exported += folders.write_message(&email, &bytes);
+                    }
+                }
+                folders.finish();
+
+                eprintln!("Exported {} messages.", total_exported);
+            }
         }
     }
 }
@@ -461,3 +554,294 @@ async fn write_file<T: Serialize>(path: &Path, name: &str, contents: Vec<T>) ->
         .unwrap_result(&format!("write to {}", path.display()));
     len
 }
+
+struct Folders {
+    folders: HashMap<String, Folder>,
+}
+
+struct Folder {
+    path: PathBuf,
+    keywords: Vec<String>,
+    mbox: Option<File>,
+}
+
+impl Folders {
+    fn new(format: MailboxFormat, path: &Path, mailboxes: &[Mailbox]) -> Self {
+        let mailbox_ids = mailboxes
+            .iter()
+            .filter_map(|m| m.id().map(|id| (id, m)))
+            .collect::<HashMap<_, _>>();
+        let mut folders = HashMap::with_capacity(mailboxes.len());
+
+        for mailbox in mailboxes {
+            let mailbox_id = if let Some(mailbox_id) = mailbox.id() {
+                mailbox_id
+            } else {
+                continue;
+            };
+
+            // Build folder hierarchy
+            let mut names = Vec::new();
+            let mut parent = Some(mailbox);
+            while let Some(mailbox) = parent {
+                if names.len() > mailbox_ids.len() {
+                    eprintln!("Warning: mailbox {mailbox_id:?} has a circular hierarchy");
+                    break;
+                }
+                names.push(mailbox.name().unwrap_or("Untitled"));
+                parent = mailbox
+                    .parent_id()
+                    .and_then(|parent_id| mailbox_ids.get(parent_id))
+                    .copied();
+            }
+            names.reverse();
+            let is_inbox = mailbox.role() == Role::Inbox && mailbox.parent_id().is_none();
+
+            // Create folder
+            let mut folder_path = path.to_path_buf();
+            let mut mbox = None;
+            match format {
+                MailboxFormat::Mbox => {
+                    let last = names.len() - 1;
+                    for (pos, name) in names.iter().enumerate() {
+                        let name = folder_name(name, None);
+                        if pos < last {
+                            folder_path.push(format!("{name}.sbd"));
+                        } else {
+                            folder_path.push(name);
+                        }
+                    }
+                    if let Some(parent_path) = folder_path.parent() {
+                        create_dir(parent_path);
+                    }
+                    mbox = File::create(&folder_path)
+                        .unwrap_result(&format!("create {}", folder_path.display()))
+                        .into();
+                }
+                MailboxFormat::Maildir | MailboxFormat::MaildirNested => {
+                    if !is_inbox {
+                        if format == MailboxFormat::Maildir {
+                            folder_path.push(format!(
+                                ".{}",
+                                names
+                                    .iter()
+                                    .map(|name| folder_name(name, Some('.')))
+                                    .collect::<Vec<_>>()
+                                    .join(".")
+                            ));
+                        } else {
+                            for name in &names {
+                                folder_path.push(folder_name(name, None));
+                            }
+                        }
+                    }
+                    for dir in ["cur", "new", "tmp"] {
+                        create_dir(&folder_path.join(dir));
+                    }
+                    if !is_inbox && format == MailboxFormat::Maildir {
+                        let path = folder_path.join("maildirfolder");
+                        File::create(&path).unwrap_result(&format!("create {}", path.display()));
+                    }
+                }
+            }
+
+            folders.insert(
+                mailbox_id.to_string(),
+                Folder {
+                    path: folder_path,
+                    keywords: Vec::new(),
+                    mbox,
+                },
+            );
+        }
+
+        Folders { folders }
+    }
+
+    fn write_message(&mut self, email: &Email, contents: &[u8]) -> usize {
+        let received_at = email.received_at().unwrap_or(0);
+        let keywords = email.keywords();
+        let mut total_written = 0;
+
+        for mailbox_id in email.mailbox_ids() {
+            let folder = if let Some(folder) = self.folders.get_mut(mailbox_id) {
+                folder
+            } else {
+                eprintln!(
+                    "Warning: email {:?} belongs to unknown mailbox {mailbox_id:?}",
+                    email.id().unwrap_or_default()
+                );
+                continue;
+            };
+
+            if let Some(mbox) = &mut folder.mbox {
+                let mut status = String::from("O");
+                let mut x_status = String::new();
+                let mut x_keywords = Vec::new();
+                for keyword in &keywords {
+                    match keyword.to_ascii_lowercase().as_str() {
+                        "$seen" => status.insert(0, 'R'),
+                        "$answered" => x_status.push('A'),
+                        "$flagged" => x_status.push('F'),
+                        "$draft" => x_status.push('T'),
+                        "$deleted" => x_status.push('D'),
+                        _ => x_keywords.push(*keyword),
+                    }
+                }
+
+                let mut message = Vec::with_capacity(contents.len() + 128);
+                let _ = write!(
+                    message,
+                    "From MAILER-DAEMON {}\nStatus: {status}\n",
+                    asctime(received_at)
+                );
+                if !x_status.is_empty() {
+                    let _ = writeln!(message, "X-Status: {x_status}");
+                }
+                if !x_keywords.is_empty() {
+                    let _ = writeln!(message, "X-Keywords: {}", x_keywords.join(" "));
+                }
+                for line in lines(contents) {
+                    // Escape "From " lines using the mboxrd convention
+                    if line
+                        .iter()
+                        .position(|&ch| ch != b'>')
+                        .is_some_and(|pos| line[pos..].starts_with(b"From "))
+                    {
+                        message.push(b'>');
+                    }
+                    message.extend_from_slice(line);
+                    message.push(b'\n');
+                }
+                message.push(b'\n');
+
+                mbox.write_all(&message)
+                    .unwrap_result(&format!("write to {}", folder.path.display()));
+            } else {
+                let mut flags = Vec::new();
+                for keyword in &keywords {
+                    let flag = match keyword.to_ascii_lowercase().as_str() {
+                        "$draft" => 'D',
+                        "$flagged" => 'F',
+                        "$forwarded" | "$passed" => 'P',
+                        "$answered" => 'R',
+                        "$seen" => 'S',
+                        "$deleted" => 'T',
+                        _ => {
+                            // Custom keywords are mapped to lowercase letters as in Dovecot
+                            let pos = if let Some(pos) =
+                                folder.keywords.iter().position(|k| k == keyword)
+                            {
+                                pos
+                            } else if folder.keywords.len() < 26 {
+                                folder.keywords.push(keyword.to_string());
+                                folder.keywords.len() - 1
+                            } else {
+                                eprintln!(
+                                    "Warning: too many keywords in {}, skipping {keyword:?}",
+                                    folder.path.display()
+                                );
+                                continue;
+                            };
+                            (b'a' + pos as u8) as char
+                        }
+                    };
+                    flags.push(flag);
+                }
+                flags.sort_unstable();
+                flags.dedup();
+
+                let mut message = Vec::with_capacity(contents.len());
+                for line in lines(contents) {
+                    message.extend_from_slice(line);
+                    message.push(b'\n');
+                }
+
+                let path = folder.path.join("cur").join(format!(
+                    "{received_at}.{}.stalwart-cli:2,{}",
+                    email.id().unwrap_or_default(),
+                    flags.into_iter().collect::<String>()
+                ));
+                let mut file =
+                    File::create(&path).unwrap_result(&format!("create {}", path.display()));
+                file.write_all(&message)
+                    .unwrap_result(&format!("write to {}", path.display()));
+                if received_at > 0 {
+                    file.set_modified(
+                        SystemTime::UNIX_EPOCH + Duration::from_secs(received_at as u64),
+                    )
+                    .unwrap_result(&format!("set modification time of {}", path.display()));
+                }
+            }
+
+            total_written += 1;
+        }
+
+        total_written
+    }
+
+    fn finish(self) {
+        for folder in self.folders.into_values() {
+            if !folder.keywords.is_empty() {
+                let path = folder.path.join("dovecot-keywords");
+                let contents = folder
+                    .keywords
+                    .iter()
+                    .enumerate()
+                    .map(|(pos, keyword)| format!("{pos} {keyword}\n"))
+                    .collect::<String>();
+                std::fs::write(&path, contents)
+                    .unwrap_result(&format!("write to {}", path.display()));
+            }
+        }
+    }
+}
+
+fn folder_name(name: &str, separator: Option<char>) -> String {
+    if matches!(name, "" | "." | "..") {
+        "_".repeat(name.len().max(1))
+    } else {
+        name.chars()
+            .map(|ch| {
+                if ch == '/' || Some(ch) == separator {
+                    '_'
+                } else {
+                    ch
+                }
+            })
+            .collect()
+    }
+}
+
+fn create_dir(path: &Path) {
+    std::fs::create_dir_all(path).unwrap_or_else(|_| {
+        eprintln!("Failed to create directory: {}", path.display());
+        std::process::exit(1);
+    });
+}
+
+fn lines(contents: &[u8]) -> impl Iterator<Item = &[u8]> {
+    contents
+        .strip_suffix(b"\n")
+        .unwrap_or(contents)
+        .split(|&ch| ch == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+}
+
+fn asctime(timestamp: i64) -> String {
+    const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let dt = DateTime::from_timestamp(timestamp);
+    format!(
+        "{} {} {:>2} {:02}:{:02}:{:02} {}",
+        DAYS[dt.day_of_week() as usize % 7],
+        MONTHS[(dt.month as usize).clamp(1, 12) - 1],
+        dt.day,
+        dt.hour,
+        dt.minute,
+        dt.second,
+        dt.year
+    )
+}
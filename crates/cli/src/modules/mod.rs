@@ -37,6 +37,7 @@ pub mod export;
 pub mod group;
 pub mod import;
 pub mod list;
+pub mod quarantine;
 pub mod queue;
 pub mod report;
 
@@ -0,0 +1,234 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::net::IpAddr;
+
+use super::{
+    cli::{Client, QuarantineCommands},
+    queue::{deserialize_datetime, parse_ids},
+    List,
+};
+use console::Term;
+use human_size::{Byte, SpecificSize};
+use mail_parser::DateTime;
+use prettytable::{Attr, Cell, Row, Table};
+use reqwest::Method;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct Quarantined {
+    pub id: u64,
+    pub return_path: String,
+    pub recipients: Vec<String>,
+    pub subject: Option<String>,
+    pub reason: String,
+    pub source: String,
+    pub remote_ip: IpAddr,
+    pub size: usize,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub quarantined_at: DateTime,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub expires: DateTime,
+    pub preview: Option<String>,
+}
+
+impl QuarantineCommands {
+    pub async fn exec(self, client: Client) {
+        match self {
+            QuarantineCommands::List {
+                sender,
+                rcpt,
+                page_size,
+            } => {
+                let mut query =
+                    form_urlencoded::Serializer::new("/api/queue/quarantine".to_string());
+
+                if let Some(sender) = &sender {
+                    query.append_pair("from", sender);
+                }
+                if let Some(rcpt) = &rcpt {
+                    query.append_pair("to", rcpt);
+                }
+
+                let stdout = Term::buffered_stdout();
+                let entries = client
+                    .http_request::<List<Quarantined>, String>(Method::GET, &query.finish(), None)
+                    .await
+                    .items;
+                let entries_len = entries.len();
+                let page_size = page_size.map(|p| std::cmp::max(p, 1)).unwrap_or(20);
+                let pages_total = (entries_len as f64 / page_size as f64).ceil() as usize;
+                for (page_num, chunk) in entries.chunks(page_size).enumerate() {
+                    // Build table
+                    let mut table = Table::new();
+                    table.add_row(Row::new(
+                        [
+                            "ID",
+                            "Quarantined",
+                            "Sender",
+                            "Recipients",
+                            "Subject",
+                            "Reason",
+                        ]
+                        .iter()
+                        .map(|p| Cell::new(p).with_style(Attr::Bold))
+                        .collect(),
+                    ));
+                    for entry in chunk {
+                        table.add_row(Row::new(vec![
+                            Cell::new(&format!("{:X}", entry.id)),
+                            Cell::new(&entry.quarantined_at.to_rfc822()),
+                            Cell::new(entry.sender()),
+                            Cell::new(&entry.recipients.join("\n")),
+                            Cell::new(entry.subject.as_deref().unwrap_or_default()),
+                            Cell::new(&entry.reason),
+                        ]));
+                    }
+
+                    eprintln!();
+                    table.printstd();
+                    eprintln!();
+                    if page_num + 1 != pages_total {
+                        eprintln!("\n--- Press any key to continue or 'q' to exit ---");
+                        if let Ok('q' | 'Q') = stdout.read_char() {
+                            break;
+                        }
+                    }
+                }
+                eprintln!("\n{entries_len} quarantined message(s) found.")
+            }
+            QuarantineCommands::Show { ids } => {
+                for (uid, id) in parse_ids(&ids).into_iter().zip(ids) {
+                    let entry = client
+                        .try_http_request::<Quarantined, String>(
+                            Method::GET,
+                            &format!("/api/queue/quarantine/{uid}"),
+                            None,
+                        )
+                        .await;
+                    let mut table = Table::new();
+                    table.add_row(Row::new(vec![
+                        Cell::new("ID").with_style(Attr::Bold),
+                        Cell::new(&id),
+                    ]));
+
+                    if let Some(entry) = entry {
+                        for (name, value) in [
+                            ("Sender", entry.sender().to_string()),
+                            ("Recipients", entry.recipients.join("\n")),
+                            ("Subject", entry.subject.clone().unwrap_or_default()),
+                            ("Reason", entry.reason.clone()),
+                            ("Source", entry.source.clone()),
+                            ("Remote IP", entry.remote_ip.to_string()),
+                            (
+                                "Size",
+                                SpecificSize::new(entry.size as u32, Byte)
+                                    .unwrap()
+                                    .to_string(),
+                            ),
+                            ("Quarantined", entry.quarantined_at.to_rfc822()),
+                            ("Expires", entry.expires.to_rfc822()),
+                            ("Preview", entry.preview.clone().unwrap_or_default()),
+                        ] {
+                            table.add_row(Row::new(vec![
+                                Cell::new(name).with_style(Attr::Bold),
+                                Cell::new(&value),
+                            ]));
+                        }
+                    } else {
+                        table.add_row(Row::new(vec![
+                            Cell::new("Status").with_style(Attr::Bold),
+                            Cell::new("-- Not found --"),
+                        ]));
+                    }
+
+                    eprintln!();
+                    table.printstd();
+                    eprintln!();
+                }
+            }
+            QuarantineCommands::Release { ids, relay_host } => {
+                let mut success_count = 0;
+                let mut failed_list = vec![];
+                for (uid, id) in parse_ids(&ids).into_iter().zip(ids) {
+                    let mut query =
+                        form_urlencoded::Serializer::new(format!("/api/queue/quarantine/{uid}"));
+                    if let Some(relay_host) = &relay_host {
+                        query.append_pair("relay-host", relay_host);
+                    }
+
+                    if client
+                        .try_http_request::<bool, String>(Method::PATCH, &query.finish(), None)
+                        .await
+                        .unwrap_or(false)
+                    {
+                        success_count += 1;
+                    } else {
+                        failed_list.push(id);
+                    }
+                }
+
+                eprint!("\nSuccessfully released {success_count} message(s).");
+                if !failed_list.is_empty() {
+                    eprint!(" Unable to release id(s): {}.", failed_list.join(", "));
+                }
+                eprintln!();
+            }
+            QuarantineCommands::Delete { ids } => {
+                let mut success_count = 0;
+                let mut failed_list = vec![];
+                for (uid, id) in parse_ids(&ids).into_iter().zip(ids) {
+                    if client
+                        .try_http_request::<bool, String>(
+                            Method::DELETE,
+                            &format!("/api/queue/quarantine/{uid}"),
+                            None,
+                        )
+                        .await
+                        .unwrap_or(false)
+                    {
+                        success_count += 1;
+                    } else {
+                        failed_list.push(id);
+                    }
+                }
+
+                eprint!("\nDeleted {success_count} quarantined message(s).");
+                if !failed_list.is_empty() {
+                    eprint!(" Unable to delete id(s): {}.", failed_list.join(", "));
+                }
+                eprintln!();
+            }
+        }
+    }
+}
+
+impl Quarantined {
+    fn sender(&self) -> &str {
+        if !self.return_path.is_empty() {
+            &self.return_path
+        } else {
+            "<>"
+        }
+    }
+}
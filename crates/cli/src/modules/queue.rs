@@ -31,6 +31,7 @@ use mail_parser::DateTime;
 use prettytable::{format::Alignment, Attr, Cell, Row, Table};
 use reqwest::Method;
 use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
 
 #[derive(Debug, Deserialize, PartialEq, Eq)]
 pub struct Message {
@@ -42,6 +43,10 @@ pub struct Message {
     #[serde(default)]
     pub priority: i16,
     pub env_id: Option<String>,
+    #[serde(default)]
+    pub relay_host: Option<String>,
+    #[serde(default)]
+    pub history: Vec<Attempt>,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq)]
@@ -66,6 +71,50 @@ pub struct Recipient {
     pub orcpt: Option<String>,
 }
 
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct Attempt {
+    pub domain: String,
+    pub retry_num: u32,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub attempted_at: DateTime,
+    pub duration: u64,
+    pub mx: Option<String>,
+    pub tls: Option<AttemptTls>,
+    #[serde(default)]
+    pub mta_sts: String,
+    #[serde(default)]
+    pub dane: String,
+    pub status: Status,
+    pub recipients: Vec<AttemptRecipient>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct AttemptTls {
+    pub version: String,
+    pub cipher: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct AttemptRecipient {
+    pub address: String,
+    pub hostname: Option<String>,
+    pub status: Status,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct Trace {
+    pub id: u64,
+    pub return_path: String,
+    pub message_id: Option<String>,
+    pub size: usize,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub received_at: DateTime,
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    pub completed_at: Option<DateTime>,
+    pub recipients: Vec<AttemptRecipient>,
+}
+
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub enum Status {
     #[serde(rename = "scheduled")]
@@ -220,6 +269,12 @@ impl QueueCommands {
                                 Cell::new(&message.priority.to_string()),
                             ]));
                         }
+                        if let Some(relay_host) = &message.relay_host {
+                            table.add_row(Row::new(vec![
+                                Cell::new("Relay Host").with_style(Attr::Bold),
+                                Cell::new(relay_host),
+                            ]));
+                        }
                         for domain in &message.domains {
                             table.add_row(Row::new(vec![Cell::new_align(
                                 &domain.name,
@@ -275,6 +330,60 @@ impl QueueCommands {
                                 Cell::from(&rcpts),
                             ]));
                         }
+                        if !message.history.is_empty() {
+                            let mut history = Table::new();
+                            history.add_row(Row::new(
+                                [
+                                    "Attempted",
+                                    "Domain",
+                                    "Retry #",
+                                    "Duration",
+                                    "MX",
+                                    "TLS",
+                                    "MTA-STS",
+                                    "DANE",
+                                    "Status",
+                                    "Details",
+                                ]
+                                .iter()
+                                .map(|p| Cell::new(p).with_style(Attr::Bold))
+                                .collect(),
+                            ));
+                            for attempt in &message.history {
+                                let mut details = attempt.status.details().to_string();
+                                for rcpt in &attempt.recipients {
+                                    if !details.is_empty() {
+                                        details.push('\n');
+                                    }
+                                    details.push_str(&rcpt.address);
+                                    if let Some(hostname) = &rcpt.hostname {
+                                        details.push_str(" via ");
+                                        details.push_str(hostname);
+                                    }
+                                    details.push_str(": ");
+                                    details.push_str(rcpt.status.details());
+                                }
+                                history.add_row(Row::new(vec![
+                                    Cell::new(&attempt.attempted_at.to_rfc822()),
+                                    Cell::new(&attempt.domain),
+                                    Cell::new(&attempt.retry_num.to_string()),
+                                    Cell::new(&format!("{}ms", attempt.duration)),
+                                    Cell::new(attempt.mx.as_deref().unwrap_or("")),
+                                    Cell::new(&attempt.tls.as_ref().map_or_else(
+                                        || "none".to_string(),
+                                        |tls| format!("{} {}", tls.version, tls.cipher),
+                                    )),
+                                    Cell::new(&attempt.mta_sts),
+                                    Cell::new(&attempt.dane),
+                                    Cell::new(attempt.status.status()),
+                                    Cell::new(&details),
+                                ]));
+                            }
+                            table.add_row(Row::new(vec![
+                                Cell::new("History").with_style(Attr::Bold),
+                                Cell::from(&history),
+                            ]));
+                        }
                     } else {
                         table.add_row(Row::new(vec![Cell::new_align(
                             "-- Not found --",
@@ -402,6 +511,146 @@ impl QueueCommands {
                 }
                 eprintln!();
             }
+            QueueCommands::Edit {
+                id,
+                remove_rcpt,
+                priority,
+                relay,
+                expires,
+            } => {
+                let mut updates = remove_rcpt
+                    .into_iter()
+                    .map(|address| json!({"action": "remove_recipient", "address": address}))
+                    .collect::<Vec<_>>();
+                if let Some(priority) = priority {
+                    updates.push(json!({"action": "set_priority", "priority": priority}));
+                }
+                if let Some(relay) = relay {
+                    updates.push(json!({
+                        "action": "set_relay_host",
+                        "relay_host": Some(relay).filter(|relay| !relay.is_empty()),
+                    }));
+                }
+                if let Some(expires) = expires {
+                    updates.push(json!({"action": "set_expires", "expires": expires.to_rfc3339()}));
+                }
+                if updates.is_empty() {
+                    eprintln!("No changes to apply.");
+                    std::process::exit(1);
+                }
+
+                let uid = parse_ids(std::slice::from_ref(&id))
+                    .pop()
+                    .unwrap_or_default();
+                if client
+                    .try_http_request::<Value, String>(
+                        Method::GET,
+                        &format!("/api/queue/messages/{uid}"),
+                        None,
+                    )
+                    .await
+                    .is_none()
+                {
+                    eprintln!("Message {id} not found.");
+                    std::process::exit(1);
+                }
+                client
+                    .http_request::<Value, _>(
+                        Method::POST,
+                        &format!("/api/queue/messages/{uid}/edit"),
+                        Some(updates),
+                    )
+                    .await;
+                eprintln!("Successfully updated message {id}.");
+            }
+            QueueCommands::Trace {
+                sender,
+                rcpt,
+                domain,
+                message_id,
+                before,
+                after,
+                page_size,
+            } => {
+                let mut query = form_urlencoded::Serializer::new("/api/queue/trace".to_string());
+
+                if let Some(sender) = &sender {
+                    query.append_pair("from", sender);
+                }
+                if let Some(rcpt) = &rcpt {
+                    query.append_pair("to", rcpt);
+                }
+                if let Some(domain) = &domain {
+                    query.append_pair("domain", domain);
+                }
+                if let Some(message_id) = &message_id {
+                    query.append_pair("message-id", message_id);
+                }
+                if let Some(before) = &before {
+                    query.append_pair("before", &before.to_rfc3339());
+                }
+                if let Some(after) = &after {
+                    query.append_pair("after", &after.to_rfc3339());
+                }
+
+                let stdout = Term::buffered_stdout();
+                let traces = client
+                    .http_request::<List<Trace>, String>(Method::GET, &query.finish(), None)
+                    .await
+                    .items;
+                let traces_len = traces.len();
+                let page_size = page_size.map(|p| std::cmp::max(p, 1)).unwrap_or(20);
+                let pages_total = (traces_len as f64 / page_size as f64).ceil() as usize;
+                for (page_num, chunk) in traces.chunks(page_size).enumerate() {
+                    // Build table
+                    let mut table = Table::new();
+                    table.add_row(Row::new(
+                        ["ID", "Received", "Sender", "Message-ID", "Recipients"]
+                            .iter()
+                            .map(|p| Cell::new(p).with_style(Attr::Bold))
+                            .collect(),
+                    ));
+                    for trace in chunk {
+                        let mut rcpts = String::new();
+                        for rcpt in &trace.recipients {
+                            if !rcpts.is_empty() {
+                                rcpts.push('\n');
+                            }
+                            rcpts.push_str(&rcpt.address);
+                            rcpts.push_str(" (");
+                            rcpts.push_str(rcpt.status.status_short());
+                            if let Some(hostname) = &rcpt.hostname {
+                                rcpts.push_str(" via ");
+                                rcpts.push_str(hostname);
+                            }
+                            rcpts.push(')');
+                        }
+
+                        table.add_row(Row::new(vec![
+                            Cell::new(&format!("{:X}", trace.id)),
+                            Cell::new(&trace.received_at.to_rfc822()),
+                            Cell::new(if !trace.return_path.is_empty() {
+                                &trace.return_path
+                            } else {
+                                "<>"
+                            }),
+                            Cell::new(trace.message_id.as_deref().unwrap_or("None")),
+                            Cell::new(&rcpts),
+                        ]));
+                    }
+
+                    eprintln!();
+                    table.printstd();
+                    eprintln!();
+                    if page_num + 1 != pages_total {
+                        eprintln!("\n--- Press any key to continue or 'q' to exit ---");
+                        if let Ok('q' | 'Q') = stdout.read_char() {
+                            break;
+                        }
+                    }
+                }
+                eprintln!("\n{traces_len} traced message(s) found.")
+            }
         }
     }
 }
@@ -465,7 +714,7 @@ where
     }
 }
 
-fn parse_ids(ids: &[String]) -> Vec<u64> {
+pub fn parse_ids(ids: &[String]) -> Vec<u64> {
     let mut result = Vec::with_capacity(ids.len());
     for id in ids {
         match u64::from_str_radix(id, 16) {
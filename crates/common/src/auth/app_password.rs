@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{
+    core::secret::{AppPassword, AppPasswordScope},
+    Directory, Principal, QueryBy,
+};
+use mail_send::Credentials;
+use store::write::now;
+
+use crate::{config::server::ServerProtocol, Core};
+
+impl Core {
+    pub async fn authenticate_app_password(
+        &self,
+        directory: &Directory,
+        credentials: &Credentials<String>,
+        protocol: ServerProtocol,
+        return_member_of: bool,
+    ) -> directory::Result<Option<Principal<u32>>> {
+        let (Credentials::Plain { username, secret }, Some(scope)) =
+            (credentials, app_password_scope(protocol))
+        else {
+            return Ok(None);
+        };
+
+        if let Some(principal) = directory
+            .query(QueryBy::Name(username), return_member_of)
+            .await?
+        {
+            for app_password in principal.app_passwords().collect::<Vec<_>>() {
+                if app_password.verify(scope, secret).await {
+                    self.set_app_password_last_used(principal.id, &app_password)
+                        .await;
+                    return Ok(Some(principal));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub async fn app_password_last_used(
+        &self,
+        account_id: u32,
+        app_password: &AppPassword,
+    ) -> Option<u64> {
+        self.storage
+            .lookup
+            .key_get::<String>(app_password_key(account_id, app_password))
+            .await
+            .unwrap_or_default()
+            .and_then(|last_used| last_used.parse().ok())
+    }
+
+    pub async fn remove_app_password_last_used(&self, account_id: u32, app_password: &AppPassword) {
+        let _ = self
+            .storage
+            .lookup
+            .key_delete(app_password_key(account_id, app_password))
+            .await;
+    }
+
+    async fn set_app_password_last_used(&self, account_id: u32, app_password: &AppPassword) {
+        if let Err(err) = self
+            .storage
+            .lookup
+            .key_set(
+                app_password_key(account_id, app_password),
+                now().to_string().into_bytes(),
+                None,
+            )
+            .await
+        {
+            tracing::warn!(
+                context = "auth",
+                event = "error",
+                account_id = account_id,
+                reason = %err,
+                "Failed to update app password last used time."
+            );
+        }
+    }
+}
+
+fn app_password_scope(protocol: ServerProtocol) -> Option<AppPasswordScope> {
+    match protocol {
+        ServerProtocol::Smtp => Some(AppPasswordScope::Smtp),
+        ServerProtocol::Imap => Some(AppPasswordScope::Imap),
+        ServerProtocol::Pop3 => Some(AppPasswordScope::Pop3),
+        ServerProtocol::Http => Some(AppPasswordScope::Jmap),
+        ServerProtocol::ManageSieve => Some(AppPasswordScope::ManageSieve),
+        ServerProtocol::Lmtp => None,
+    }
+}
+
+fn app_password_key(account_id: u32, app_password: &AppPassword) -> Vec<u8> {
+    format!(
+        "app-pw:{account_id}:{}:{}",
+        app_password.name, app_password.created
+    )
+    .into_bytes()
+}
@@ -0,0 +1,503 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::net::IpAddr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use directory::{
+    core::secret::{plain_secret, ScramAlgorithm, ScramSecret},
+    Directory, Principal, QueryBy,
+};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use crate::{
+    config::server::ServerProtocol,
+    webhooks::{WebhookPayload, WebhookType},
+    AuthResult, Core, Ipc,
+};
+
+pub const CHANNEL_BINDING: &str = "tls-exporter";
+
+pub struct ScramServer {
+    algorithm: ScramAlgorithm,
+    channel_binding: ChannelBinding,
+    state: State,
+}
+
+pub enum ScramResult<T> {
+    Challenge(Vec<u8>),
+    Done(AuthResult<T>),
+}
+
+enum ChannelBinding {
+    // Server does not support channel binding
+    Unsupported,
+    // Server supports channel binding but the client selected a non-PLUS mechanism
+    Supported,
+    // Client selected a PLUS mechanism
+    Required(Vec<u8>),
+}
+
+#[derive(Default)]
+enum State {
+    #[default]
+    ClientFirst,
+    ServerFirst {
+        username: String,
+        gs2_header: String,
+        client_first_bare: String,
+        nonce: String,
+    },
+    ClientFinal {
+        username: String,
+        gs2_header: String,
+        client_first_bare: String,
+        server_first: String,
+        nonce: String,
+        secret: ScramSecret,
+        principal: Option<Principal<u32>>,
+    },
+    ClientAck {
+        username: String,
+        principal: Principal<u32>,
+    },
+    Done,
+}
+
+impl ScramServer {
+    /// Creates a new SCRAM exchange. `tls_exporter` contains the channel binding
+    /// data of the underlying TLS connection, if any.
+    pub fn new(algorithm: ScramAlgorithm, is_plus: bool, tls_exporter: Option<Vec<u8>>) -> Self {
+        ScramServer {
+            algorithm,
+            channel_binding: match (is_plus, tls_exporter) {
+                (true, Some(data)) => ChannelBinding::Required(data),
+                (true, None) => ChannelBinding::Required(vec![]),
+                (false, Some(_)) => ChannelBinding::Supported,
+                (false, None) => ChannelBinding::Unsupported,
+            },
+            state: State::ClientFirst,
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        match &self.state {
+            State::ServerFirst { username, .. }
+            | State::ClientFinal { username, .. }
+            | State::ClientAck { username, .. } => username,
+            State::ClientFirst | State::Done => "",
+        }
+    }
+
+    /// Parses the client-first-message and returns the username to authenticate.
+    pub fn client_first(&mut self, message: &[u8]) -> Option<String> {
+        let message = std::str::from_utf8(message).ok()?;
+        let (cbind_flag, message) = message.split_once(',')?;
+        let (authzid, client_first_bare) = message.split_once(',')?;
+
+        // Validate channel binding flag
+        match (cbind_flag, &self.channel_binding) {
+            ("n", ChannelBinding::Unsupported | ChannelBinding::Supported)
+            | ("y", ChannelBinding::Unsupported) => {}
+            (flag, ChannelBinding::Required(data))
+                if !data.is_empty()
+                    && flag
+                        .strip_prefix("p=")
+                        .is_some_and(|name| name == CHANNEL_BINDING) => {}
+            _ => return None,
+        }
+
+        // Parse username and nonce
+        let mut attributes = client_first_bare.split(',');
+        let username = decode_saslname(attributes.next()?.strip_prefix("n=")?)?;
+        let nonce = attributes.next()?.strip_prefix("r=")?;
+        if username.is_empty() || nonce.is_empty() {
+            return None;
+        }
+
+        // Proxy authentication is not supported
+        if let Some(authzid) = authzid.strip_prefix("a=") {
+            if decode_saslname(authzid)? != username {
+                return None;
+            }
+        } else if !authzid.is_empty() {
+            return None;
+        }
+
+        self.state = State::ServerFirst {
+            username: username.clone(),
+            gs2_header: format!("{cbind_flag},{authzid},"),
+            client_first_bare: client_first_bare.to_string(),
+            nonce: nonce.to_string(),
+        };
+
+        Some(username)
+    }
+
+    /// Builds the server-first-message using the provided secret.
+    pub fn server_first(
+        &mut self,
+        secret: ScramSecret,
+        principal: Option<Principal<u32>>,
+        server_nonce: &str,
+    ) -> Vec<u8> {
+        if let State::ServerFirst {
+            username,
+            gs2_header,
+            client_first_bare,
+            nonce,
+        } = std::mem::take(&mut self.state)
+        {
+            let nonce = format!("{nonce}{server_nonce}");
+            let server_first = format!(
+                "r={nonce},s={},i={}",
+                STANDARD.encode(&secret.salt),
+                secret.iterations
+            );
+            let response = server_first.as_bytes().to_vec();
+            self.state = State::ClientFinal {
+                username,
+                gs2_header,
+                client_first_bare,
+                server_first,
+                nonce,
+                secret,
+                principal,
+            };
+            response
+        } else {
+            self.state = State::Done;
+            vec![]
+        }
+    }
+
+    /// Verifies the client-final-message and returns the server-final-message.
+    pub fn client_final(&mut self, message: &[u8]) -> Option<Vec<u8>> {
+        let State::ClientFinal {
+            username,
+            gs2_header,
+            client_first_bare,
+            server_first,
+            nonce,
+            secret,
+            principal,
+        } = std::mem::take(&mut self.state)
+        else {
+            return None;
+        };
+        self.state = State::Done;
+
+        let message = std::str::from_utf8(message).ok()?;
+        let (client_final_without_proof, proof) = message.rsplit_once(",p=")?;
+        let proof = STANDARD.decode(proof).ok()?;
+        let mut attributes = client_final_without_proof.split(',');
+
+        // Validate channel binding
+        let mut cbind_input = gs2_header.into_bytes();
+        if let ChannelBinding::Required(data) = &self.channel_binding {
+            cbind_input.extend_from_slice(data);
+        }
+        if STANDARD
+            .decode(attributes.next()?.strip_prefix("c=")?)
+            .ok()?
+            != cbind_input
+        {
+            return None;
+        }
+
+        // Validate nonce
+        if attributes.next()?.strip_prefix("r=")? != nonce {
+            return None;
+        }
+
+        // Verify proof
+        let auth_message =
+            format!("{client_first_bare},{server_first},{client_final_without_proof}");
+        let client_signature = self
+            .algorithm
+            .hmac(&secret.stored_key, auth_message.as_bytes());
+        if proof.len() != client_signature.len() {
+            return None;
+        }
+        let client_key = proof
+            .iter()
+            .zip(client_signature)
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<_>>();
+        if self.algorithm.hash(&client_key) != secret.stored_key {
+            return None;
+        }
+        let principal = principal?;
+
+        self.state = State::ClientAck {
+            username,
+            principal,
+        };
+
+        Some(
+            format!(
+                "v={}",
+                STANDARD.encode(
+                    self.algorithm
+                        .hmac(&secret.server_key, auth_message.as_bytes())
+                )
+            )
+            .into_bytes(),
+        )
+    }
+}
+
+impl Core {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn authenticate_scram(
+        &self,
+        directory: &Directory,
+        ipc: &Ipc,
+        server: &mut ScramServer,
+        response: &[u8],
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+        return_member_of: bool,
+    ) -> directory::Result<ScramResult<Principal<u32>>> {
+        match std::mem::take(&mut server.state) {
+            State::ClientFirst => {
+                if let Some(username) = server.client_first(response) {
+                    // SCRAM cannot carry a second factor and app passwords are stored
+                    // hashed, so accounts with two-factor authentication enabled have to
+                    // authenticate using a mechanism that sends their app password.
+                    let principal = directory
+                        .query(QueryBy::Name(&username), return_member_of)
+                        .await?
+                        .filter(|principal| !principal.has_totp());
+
+                    // Use stored SCRAM keys or derive them from a plain-text secret,
+                    // unknown users are given random keys to avoid disclosing their existence.
+                    // Derived keys use a salt obtained from the username so that it does
+                    // not change between attempts.
+                    let salt = self.scram_salt(server.algorithm, &username);
+                    let secret = principal
+                        .as_ref()
+                        .and_then(|principal| {
+                            principal
+                                .secrets
+                                .iter()
+                                .filter_map(|secret| ScramSecret::parse(secret))
+                                .find(|secret| secret.algorithm == server.algorithm)
+                                .or_else(|| {
+                                    principal
+                                        .secrets
+                                        .iter()
+                                        .find_map(|secret| plain_secret(secret))
+                                        .map(|secret| {
+                                            ScramSecret::derive(
+                                                server.algorithm,
+                                                secret,
+                                                salt.clone(),
+                                                ScramSecret::DEFAULT_ITERATIONS,
+                                            )
+                                        })
+                                })
+                        })
+                        .unwrap_or_else(|| {
+                            ScramSecret::derive(
+                                server.algorithm,
+                                &thread_rng()
+                                    .sample_iter(Alphanumeric)
+                                    .take(32)
+                                    .map(char::from)
+                                    .collect::<String>(),
+                                salt,
+                                ScramSecret::DEFAULT_ITERATIONS,
+                            )
+                        });
+                    let server_nonce = thread_rng()
+                        .sample_iter(Alphanumeric)
+                        .take(24)
+                        .map(char::from)
+                        .collect::<String>();
+
+                    Ok(ScramResult::Challenge(server.server_first(
+                        secret,
+                        principal,
+                        &server_nonce,
+                    )))
+                } else {
+                    self.authentication_failed(ipc, "", remote_ip, protocol)
+                        .await
+                        .map(ScramResult::Done)
+                }
+            }
+            state @ State::ClientFinal { .. } => {
+                server.state = state;
+                let username = server.username().to_string();
+                if let Some(server_final) = server.client_final(response) {
+                    Ok(ScramResult::Challenge(server_final))
+                } else {
+                    self.authentication_failed(ipc, &username, remote_ip, protocol)
+                        .await
+                        .map(ScramResult::Done)
+                }
+            }
+            State::ClientAck {
+                username,
+                principal,
+            } => {
+                server.state = State::Done;
+
+                // Send webhook event
+                if self.has_webhook_subscribers(WebhookType::AuthSuccess) {
+                    ipc.send_webhook(
+                        WebhookType::AuthSuccess,
+                        WebhookPayload::Authentication {
+                            login: username,
+                            protocol,
+                            remote_ip,
+                            typ: principal.typ.into(),
+                            as_master: None,
+                        },
+                    )
+                    .await;
+                }
+
+                Ok(ScramResult::Done(AuthResult::Success(principal)))
+            }
+            State::ServerFirst { .. } | State::Done => Ok(ScramResult::Done(AuthResult::Failure)),
+        }
+    }
+
+    fn scram_salt(&self, algorithm: ScramAlgorithm, username: &str) -> Vec<u8> {
+        let mut salt = algorithm.hmac(
+            self.jmap.oauth_key.as_bytes(),
+            format!("scram-salt:{username}").as_bytes(),
+        );
+        salt.truncate(16);
+        salt
+    }
+}
+
+fn decode_saslname(name: &str) -> Option<String> {
+    let mut result = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '=' => match (chars.next()?, chars.next()?) {
+                ('2', 'C') => result.push(','),
+                ('3', 'D') => result.push('='),
+                _ => return None,
+            },
+            ',' => return None,
+            _ => result.push(ch),
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use directory::{
+        core::secret::{ScramAlgorithm, ScramSecret},
+        Principal,
+    };
+
+    use super::ScramServer;
+
+    #[test]
+    fn scram_exchange() {
+        // RFC 5802 and RFC 7677 examples
+        for (algorithm, salt, server_nonce, messages) in [
+            (
+                ScramAlgorithm::Sha1,
+                "QSXCR+Q6sek8bf92",
+                "3rfcNHYJY1ZVvWVs7j",
+                [
+                    "n,,n=user,r=fyko+d2lbbFgONRv9qkxdawL",
+                    "r=fyko+d2lbbFgONRv9qkxdawL3rfcNHYJY1ZVvWVs7j,s=QSXCR+Q6sek8bf92,i=4096",
+                    "c=biws,r=fyko+d2lbbFgONRv9qkxdawL3rfcNHYJY1ZVvWVs7j,p=v0X8v3Bz2T0CJGbJQyF0X+HI4Ts=",
+                    "v=rmF9pqV8S7suAoZWja4dJRkFsKQ=",
+                ],
+            ),
+            (
+                ScramAlgorithm::Sha256,
+                "W22ZaJ0SNY7soEsUEjb6gQ==",
+                "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0",
+                [
+                    "n,,n=user,r=rOprNGfwEbeRWgbNEkqO",
+                    "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+                    "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=",
+                    "v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=",
+                ],
+            ),
+        ] {
+            let secret =
+                ScramSecret::derive(algorithm, "pencil", STANDARD.decode(salt).unwrap(), 4096);
+
+            // Successful exchange
+            let mut server = ScramServer::new(algorithm, false, None);
+            assert_eq!(
+                server.client_first(messages[0].as_bytes()).as_deref(),
+                Some("user")
+            );
+            assert_eq!(
+                server.server_first(secret.clone(), Principal::default().into(), server_nonce),
+                messages[1].as_bytes()
+            );
+            assert_eq!(
+                server.client_final(messages[2].as_bytes()),
+                Some(messages[3].as_bytes().to_vec())
+            );
+
+            // Invalid proof
+            let mut server = ScramServer::new(algorithm, false, None);
+            server.client_first(messages[0].as_bytes()).unwrap();
+            server.server_first(
+                ScramSecret::derive(algorithm, "pen", STANDARD.decode(salt).unwrap(), 4096),
+                Principal::default().into(),
+                server_nonce,
+            );
+            assert_eq!(server.client_final(messages[2].as_bytes()), None);
+
+            // Downgrade attacks are detected
+            let mut server = ScramServer::new(algorithm, false, Some(vec![1, 2, 3]));
+            assert_eq!(
+                server.client_first(messages[0].replacen('n', "y", 1).as_bytes()),
+                None
+            );
+
+            // Channel binding is required for PLUS mechanisms
+            let mut server = ScramServer::new(algorithm, true, Some(vec![1, 2, 3]));
+            assert_eq!(server.client_first(messages[0].as_bytes()), None);
+            let mut server = ScramServer::new(algorithm, true, Some(vec![1, 2, 3]));
+            assert_eq!(
+                server
+                    .client_first(
+                        messages[0]
+                            .replacen("n,,", "p=tls-exporter,,", 1)
+                            .as_bytes()
+                    )
+                    .as_deref(),
+                Some("user")
+            );
+        }
+    }
+}
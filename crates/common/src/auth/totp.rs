@@ -0,0 +1,103 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{
+    backend::internal::{manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue},
+    core::secret::TotpSecret,
+    Directory, Principal, QueryBy,
+};
+use mail_send::Credentials;
+
+use crate::Core;
+
+impl Core {
+    /// Authenticates web logins of accounts with two-factor authentication enabled,
+    /// which are expected to provide their TOTP or recovery code as `password$code`.
+    pub async fn authenticate_totp(
+        &self,
+        directory: &Directory,
+        credentials: &Credentials<String>,
+        return_member_of: bool,
+    ) -> directory::Result<Option<Principal<u32>>> {
+        let Some((username, password, code)) = (match credentials {
+            Credentials::Plain { username, secret } => secret
+                .rsplit_once('$')
+                .map(|(password, code)| (username, password, code)),
+            _ => None,
+        }) else {
+            return Ok(None);
+        };
+
+        let Some(principal) = directory
+            .query(
+                QueryBy::Credentials(&Credentials::Plain {
+                    username: username.to_string(),
+                    secret: password.to_string(),
+                }),
+                return_member_of,
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let Some(totp) = principal.totp_secret().filter(|totp| totp.enabled) else {
+            return Ok(None);
+        };
+
+        if totp.verify(code) {
+            // Codes are valid for a few periods, do not allow them to be replayed
+            let key = format!("totp:{}:{}", principal.id, code.trim()).into_bytes();
+            if self
+                .storage
+                .lookup
+                .counter_incr(key, 1, Some(3 * TotpSecret::PERIOD), true)
+                .await?
+                > 1
+            {
+                return Ok(None);
+            }
+
+            Ok(Some(principal))
+        } else if let Some(recovery_code) = principal
+            .verify_recovery_code(code)
+            .await
+            .map(|code| code.to_string())
+        {
+            // Recovery codes can only be used once
+            self.storage
+                .data
+                .update_account(
+                    QueryBy::Id(principal.id),
+                    vec![PrincipalUpdate::remove_item(
+                        PrincipalField::Secrets,
+                        PrincipalValue::String(recovery_code),
+                    )],
+                )
+                .await?;
+
+            Ok(Some(principal))
+        } else {
+            Ok(None)
+        }
+    }
+}
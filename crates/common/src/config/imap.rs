@@ -23,6 +23,7 @@
 
 use std::time::Duration;
 
+use ahash::AHashSet;
 use utils::config::{Config, Rate};
 
 #[derive(Default, Clone)]
@@ -37,10 +38,34 @@ pub struct ImapConfig {
 
     pub rate_requests: Option<Rate>,
     pub rate_concurrent: Option<u64>,
+
+    pub metadata_max_size: usize,
+    pub metadata_max_entries: usize,
+
+    pub compress_listeners: AHashSet<String>,
 }
 
 impl ImapConfig {
     pub fn parse(config: &mut Config) -> Self {
+        // Listeners that allow COMPRESS=DEFLATE
+        let mut compress_listeners = AHashSet::new();
+        for id in config
+            .sub_keys("server.listener", ".protocol")
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+        {
+            if config
+                .property_or_else(
+                    ("server.listener", id.as_str(), "imap.compress"),
+                    "imap.compress.enable",
+                    "true",
+                )
+                .unwrap_or(true)
+            {
+                compress_listeners.insert(id);
+            }
+        }
+
         ImapConfig {
             max_request_size: config
                 .property_or_default("imap.request.max-size", "52428800")
@@ -66,6 +91,13 @@ impl ImapConfig {
             allow_plain_auth: config
                 .property_or_default("imap.auth.allow-plain-text", "false")
                 .unwrap_or(false),
+            metadata_max_size: config
+                .property_or_default("imap.metadata.max-size", "4096")
+                .unwrap_or(4096),
+            metadata_max_entries: config
+                .property_or_default("imap.metadata.max-entries", "100")
+                .unwrap_or(100),
+            compress_listeners,
         }
     }
 }
@@ -1,18 +1,20 @@
 use ahash::AHashSet;
 use jmap_proto::{
     request::capability::{
-        BlobCapabilities, Capabilities, Capability, CoreCapabilities, EmptyCapabilities,
-        MailCapabilities, SieveAccountCapabilities, SieveSessionCapabilities,
-        SubmissionCapabilities,
+        BlobCapabilities, CalendarsCapabilities, Capabilities, Capability, ContactsCapabilities,
+        CoreCapabilities, EmptyCapabilities, MailCapabilities, SieveAccountCapabilities,
+        SieveSessionCapabilities, SubmissionCapabilities,
     },
     types::type_state::DataType,
 };
 use utils::{config::Config, map::vec_map::VecMap};
 
+use crate::config::scripts::Scripting;
+
 use super::settings::JmapConfig;
 
 impl JmapConfig {
-    pub fn add_capabilites(&mut self, config: &mut Config) {
+    pub fn add_capabilites(&mut self, config: &mut Config, sieve: &Scripting) {
         // Add core capabilities
         self.capabilities.session.append(
             Capability::Core,
@@ -93,15 +95,6 @@ impl JmapConfig {
         );
 
         // Add Sieve capabilities
-        let mut notification_methods = Vec::new();
-
-        for (_, uri) in config.values("sieve.untrusted.notification-uris") {
-            notification_methods.push(uri.to_string());
-        }
-        if notification_methods.is_empty() {
-            notification_methods.push("mailto".to_string());
-        }
-
         let mut capabilities: AHashSet<sieve::compiler::grammar::Capability> =
             AHashSet::from_iter(sieve::compiler::grammar::Capability::all().iter().cloned());
 
@@ -131,8 +124,8 @@ impl JmapConfig {
                     .property("sieve.untrusted.max-redirects")
                     .unwrap_or(1),
                 extensions,
-                notification_methods: if !notification_methods.is_empty() {
-                    notification_methods.into()
+                notification_methods: if !sieve.notify_methods.is_empty() {
+                    sieve.notify_methods.clone().into()
                 } else {
                     None
                 },
@@ -140,6 +133,33 @@ impl JmapConfig {
             }),
         );
 
+        // Add Contacts capabilities
+        self.capabilities.session.append(
+            Capability::Contacts,
+            Capabilities::Empty(EmptyCapabilities::default()),
+        );
+        self.capabilities.account.append(
+            Capability::Contacts,
+            Capabilities::Contacts(ContactsCapabilities {
+                max_address_books_per_card: self.contacts_max_address_books_per_card,
+                may_create_address_book: true,
+            }),
+        );
+
+        // Add Calendars capabilities
+        self.capabilities.session.append(
+            Capability::Calendars,
+            Capabilities::Empty(EmptyCapabilities::default()),
+        );
+        self.capabilities.account.append(
+            Capability::Calendars,
+            Capabilities::Calendars(CalendarsCapabilities {
+                max_calendars_per_event: self.calendars_max_calendars_per_event,
+                max_participants_per_event: self.calendars_max_participants_per_event,
+                may_create_calendar: true,
+            }),
+        );
+
         // Add Blob capabilities
         self.capabilities.session.append(
             Capability::Blob,
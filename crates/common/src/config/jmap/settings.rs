@@ -1,11 +1,15 @@
 use std::{str::FromStr, time::Duration};
 
+use ahash::AHashSet;
 use jmap_proto::request::capability::BaseCapabilities;
 use mail_parser::HeaderName;
 use nlp::language::Language;
+use sieve::Envelope;
 use store::rand::{distributions::Alphanumeric, thread_rng, Rng};
 use utils::config::{cron::SimpleCron, utils::ParseValue, Config, Rate};
 
+use crate::config::scripts::Scripting;
+
 #[derive(Default, Clone)]
 pub struct JmapConfig {
     pub default_language: Language,
@@ -38,6 +42,20 @@ pub struct JmapConfig {
 
     pub sieve_max_script_name: usize,
     pub sieve_max_scripts: usize,
+    pub sieve_notify_rate: Option<Rate>,
+    pub sieve_ext_lists: AHashSet<String>,
+    pub sieve_edit_policy: SieveEditPolicy,
+
+    pub contacts_max_address_books_per_card: Option<usize>,
+    pub contacts_max_card_size: usize,
+    pub contacts_default_address_book: String,
+    pub contacts_global_address_list: Option<String>,
+
+    pub calendars_max_calendars_per_event: Option<usize>,
+    pub calendars_max_participants_per_event: Option<usize>,
+    pub calendars_max_event_size: usize,
+    pub calendars_default_calendar: String,
+    pub calendars_imip: bool,
 
     pub session_cache_ttl: Duration,
     pub rate_authenticated: Option<Rate>,
@@ -68,6 +86,7 @@ pub struct JmapConfig {
     pub master_user: Option<(String, String)>,
 
     pub spam_header: Option<(HeaderName<'static>, String)>,
+    pub spam_train: Option<SpamTraining>,
     pub default_folders: Vec<DefaultFolder>,
     pub shared_folder: String,
 
@@ -84,6 +103,22 @@ pub struct JmapConfig {
     pub account_purge_frequency: SimpleCron,
 }
 
+#[derive(Clone, Debug)]
+pub struct SpamTraining {
+    pub lookup: Option<String>,
+    pub per_user: bool,
+    pub global: bool,
+    pub classify: bool,
+    pub history: Duration,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SieveEditPolicy {
+    pub add_headers: Vec<String>,
+    pub delete_headers: Vec<String>,
+    pub envelope: AHashSet<Envelope>,
+}
+
 #[derive(Clone, Debug)]
 pub struct DefaultFolder {
     pub name: String,
@@ -106,7 +141,7 @@ pub enum SpecialUse {
 }
 
 impl JmapConfig {
-    pub fn parse(config: &mut Config) -> Self {
+    pub fn parse(config: &mut Config, sieve: &Scripting) -> Self {
         // Parse HTTP headers
         let mut http_headers = config
             .values("server.http.headers")
@@ -203,6 +238,30 @@ impl JmapConfig {
             }
         }
 
+        // Parse Sieve edit policy
+        let mut sieve_edit_policy = SieveEditPolicy {
+            add_headers: header_patterns(config, "sieve.untrusted.editheader.allow-add"),
+            delete_headers: header_patterns(config, "sieve.untrusted.editheader.allow-delete"),
+            envelope: AHashSet::new(),
+        };
+        for (key, value) in config
+            .values("sieve.untrusted.setenvelope.allow")
+            .map(|(k, v)| (k.to_string(), v.to_ascii_lowercase()))
+            .collect::<Vec<_>>()
+        {
+            match value.as_str() {
+                "from" => {
+                    sieve_edit_policy.envelope.insert(Envelope::From);
+                }
+                "to" => {
+                    sieve_edit_policy.envelope.insert(Envelope::To);
+                }
+                _ => {
+                    config.new_parse_error(key, format!("Unsupported envelope field {value:?}"));
+                }
+            }
+        }
+
         // Add permissive CORS headers
         if config
             .property::<bool>("server.http.permissive-cors")
@@ -304,6 +363,46 @@ impl JmapConfig {
             sieve_max_scripts: config
                 .property("sieve.untrusted.limits.max-scripts")
                 .unwrap_or(256),
+            sieve_notify_rate: config
+                .property_or_default::<Option<Rate>>("sieve.untrusted.limits.notify-rate", "25/1h")
+                .unwrap_or_default(),
+            sieve_ext_lists: config
+                .values("sieve.untrusted.ext-lists")
+                .map(|(_, v)| v.to_string())
+                .collect(),
+            sieve_edit_policy,
+            contacts_max_address_books_per_card: config
+                .property("jmap.contacts.max-address-books-per-card"),
+            contacts_max_card_size: config
+                .property("jmap.contacts.max-card-size")
+                .unwrap_or(512 * 1024),
+            contacts_default_address_book: config
+                .value("jmap.contacts.default-address-book")
+                .unwrap_or("Contacts")
+                .to_string(),
+            contacts_global_address_list: config
+                .property::<bool>("jmap.contacts.global-address-list.enable")
+                .unwrap_or(false)
+                .then(|| {
+                    config
+                        .value("jmap.contacts.global-address-list.name")
+                        .unwrap_or("Directory")
+                        .to_string()
+                }),
+            calendars_max_calendars_per_event: config
+                .property("jmap.calendars.max-calendars-per-event"),
+            calendars_max_participants_per_event: config
+                .property("jmap.calendars.max-participants-per-event"),
+            calendars_max_event_size: config
+                .property("jmap.calendars.max-event-size")
+                .unwrap_or(512 * 1024),
+            calendars_default_calendar: config
+                .value("jmap.calendars.default-calendar")
+                .unwrap_or("Calendar")
+                .to_string(),
+            calendars_imip: config
+                .property_or_default("jmap.calendars.imip.enable", "true")
+                .unwrap_or(true),
             capabilities: BaseCapabilities::default(),
             session_cache_ttl: config
                 .property("cache.session.ttl")
@@ -385,6 +484,27 @@ impl JmapConfig {
                         )
                     })
                 }),
+            spam_train: config
+                .property_or_default("spam.train.enable", "false")
+                .unwrap_or(false)
+                .then(|| SpamTraining {
+                    lookup: config
+                        .value("spam.train.lookup")
+                        .filter(|v| !v.is_empty())
+                        .map(|v| v.to_string()),
+                    per_user: config
+                        .property_or_default("spam.train.per-user", "false")
+                        .unwrap_or(false),
+                    global: config
+                        .property_or_default("spam.train.global", "true")
+                        .unwrap_or(true),
+                    classify: config
+                        .property_or_default("spam.train.classify", "false")
+                        .unwrap_or(false),
+                    history: config
+                        .property_or_default("spam.train.history", "30d")
+                        .unwrap_or_else(|| Duration::from_secs(30 * 86400)),
+                }),
             http_use_forwarded: config
                 .property("server.http.use-x-forwarded")
                 .unwrap_or(false),
@@ -430,11 +550,49 @@ impl JmapConfig {
         };
 
         // Add capabilities
-        jmap.add_capabilites(config);
+        jmap.add_capabilites(config, sieve);
         jmap
     }
 }
 
+impl SieveEditPolicy {
+    // Added by the server to record edits, scripts are never allowed to modify it
+    pub const TRACE_HEADER: &'static str = "X-Sieve-Trace";
+
+    pub fn can_add_header(&self, name: &str) -> bool {
+        !name.eq_ignore_ascii_case(Self::TRACE_HEADER) && matches_header(&self.add_headers, name)
+    }
+
+    pub fn can_delete_header(&self, name: &str) -> bool {
+        !name.eq_ignore_ascii_case(Self::TRACE_HEADER) && matches_header(&self.delete_headers, name)
+    }
+}
+
+fn header_patterns(config: &mut Config, key: &str) -> Vec<String> {
+    let patterns = config
+        .values(key)
+        .map(|(_, v)| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>();
+    if !patterns.is_empty() {
+        patterns
+    } else {
+        vec!["*".to_string()]
+    }
+}
+
+fn matches_header(patterns: &[String], name: &str) -> bool {
+    patterns.iter().any(|pattern| {
+        if let Some(prefix) = pattern.strip_suffix('*') {
+            name.as_bytes()
+                .get(..prefix.len())
+                .is_some_and(|name| name.eq_ignore_ascii_case(prefix.as_bytes()))
+        } else {
+            pattern.eq_ignore_ascii_case(name)
+        }
+    })
+}
+
 impl ParseValue for SpecialUse {
     fn parse_value(value: &str) -> utils::config::Result<Self> {
         match value {
@@ -0,0 +1,59 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use utils::config::Config;
+
+#[derive(Default, Clone)]
+pub struct Metrics {
+    pub prometheus: Option<PrometheusMetrics>,
+}
+
+#[derive(Default, Clone)]
+pub struct PrometheusMetrics {
+    pub auth: Option<String>,
+}
+
+impl Metrics {
+    pub fn parse(config: &mut Config) -> Self {
+        let prometheus = if config
+            .property_or_default("metrics.prometheus.enable", "false")
+            .unwrap_or(false)
+        {
+            PrometheusMetrics {
+                auth: config
+                    .value("metrics.prometheus.auth.username")
+                    .and_then(|user| {
+                        config
+                            .value("metrics.prometheus.auth.secret")
+                            .map(|secret| STANDARD.encode(format!("{user}:{secret}")))
+                    }),
+            }
+            .into()
+        } else {
+            None
+        };
+
+        Metrics { prometheus }
+    }
+}
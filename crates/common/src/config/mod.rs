@@ -34,12 +34,13 @@ use crate::{
 };
 
 use self::{
-    imap::ImapConfig, jmap::settings::JmapConfig, scripts::Scripting, smtp::SmtpConfig,
-    storage::Storage,
+    imap::ImapConfig, jmap::settings::JmapConfig, metrics::Metrics, scripts::Scripting,
+    smtp::SmtpConfig, storage::Storage,
 };
 
 pub mod imap;
 pub mod jmap;
+pub mod metrics;
 pub mod network;
 pub mod scripts;
 pub mod server;
@@ -149,14 +150,17 @@ impl Core {
             )
         }
 
+        let sieve = Scripting::parse(config, &stores).await;
+
         Self {
-            sieve: Scripting::parse(config, &stores).await,
             network: Network::parse(config),
             smtp: SmtpConfig::parse(config).await,
-            jmap: JmapConfig::parse(config),
+            jmap: JmapConfig::parse(config, &sieve),
             imap: ImapConfig::parse(config),
             tls: TlsManager::parse(config),
             web_hooks: Webhooks::parse(config),
+            metrics: Metrics::parse(config),
+            sieve,
             storage: Storage {
                 data,
                 blob,
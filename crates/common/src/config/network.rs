@@ -24,7 +24,7 @@
 use std::{str::FromStr, time::Duration};
 
 use crate::{
-    expr::{if_block::IfBlock, tokenizer::TokenMap},
+    expr::{if_block::IfBlock, tokenizer::TokenMap, V_ACCOUNT_TYPE, V_AUTHENTICATED_AS},
     listener::blocked::{AllowedIps, BlockedIps},
     webhooks::{Webhook, WebhookType, Webhooks},
     Network,
@@ -49,6 +49,7 @@ impl Default for Network {
                 [],
                 "protocol + '://' + key_get('default', 'hostname') + ':' + local_port",
             ),
+            totp_required: IfBlock::new::<()>("authentication.totp.require", [], "false"),
         }
     }
 }
@@ -68,6 +69,16 @@ impl Network {
             }
         }
 
+        if let Some(if_block) = IfBlock::try_parse(
+            config,
+            "authentication.totp.require",
+            &TokenMap::default()
+                .with_variables(CONNECTION_VARS)
+                .with_variables(&[V_AUTHENTICATED_AS, V_ACCOUNT_TYPE]),
+        ) {
+            network.totp_required = if_block;
+        }
+
         network
     }
 }
@@ -196,6 +207,7 @@ impl FromStr for WebhookType {
             "report.incoming.tls" => Ok(Self::IncomingTlsReport),
             "report.incoming.arf" => Ok(Self::IncomingArfReport),
             "report.outgoing" => Ok(Self::OutgoingReport),
+            "sieve.notify" => Ok(Self::SieveNotify),
             _ => Err(s.to_string()),
         }
     }
@@ -47,6 +47,7 @@ pub struct Scripting {
     pub return_path: IfBlock,
     pub sign: IfBlock,
     pub scripts: AHashMap<String, Arc<Sieve>>,
+    pub notify_methods: Vec<String>,
 }
 
 pub struct ScriptCache {
@@ -115,6 +116,15 @@ impl Scripting {
                     .unwrap_or(3),
             );
 
+        // Parse notification methods available to untrusted scripts
+        let mut notify_methods = config
+            .values("sieve.untrusted.notification-uris")
+            .map(|(_, v)| v.to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        if notify_methods.is_empty() {
+            notify_methods.push("mailto".to_string());
+        }
+
         // Parse untrusted runtime
         let untrusted_runtime = Runtime::new()
             .with_max_nested_includes(
@@ -169,17 +179,18 @@ impl Scripting {
                     .values("sieve.untrusted.disable-capabilities")
                     .map(|(_, v)| v),
             )
-            .with_valid_notification_uris({
-                let values = config
-                    .values("sieve.untrusted.notification-uris")
-                    .map(|(_, v)| v.to_string())
-                    .collect::<Vec<_>>();
-                if !values.is_empty() {
-                    values
-                } else {
-                    vec!["mailto".to_string()]
-                }
-            })
+            .with_valid_notification_uris(notify_methods.clone())
+            .with_valid_ext_lists(
+                [":addrbook:personal", ":addrbook:default"]
+                    .into_iter()
+                    .map(String::from)
+                    .chain(
+                        config
+                            .values("sieve.untrusted.ext-lists")
+                            .map(|(_, v)| v.to_string()),
+                    )
+                    .collect::<Vec<_>>(),
+            )
             .with_protected_headers({
                 let values = config
                     .values("sieve.untrusted.protected-headers")
@@ -352,6 +363,7 @@ impl Scripting {
                 },
             ),
             scripts,
+            notify_methods,
         }
     }
 }
@@ -397,6 +409,7 @@ impl Default for Scripting {
                 ),
             ),
             scripts: AHashMap::new(),
+            notify_methods: vec!["mailto".to_string()],
         }
     }
 }
@@ -425,6 +438,7 @@ impl Clone for Scripting {
             return_path: self.return_path.clone(),
             sign: self.sign.clone(),
             scripts: self.scripts.clone(),
+            notify_methods: self.notify_methods.clone(),
         }
     }
 }
@@ -1,7 +1,11 @@
+use std::{net::IpAddr, time::Duration};
+
 use ahash::AHashMap;
 use mail_auth::IpLookupStrategy;
+use mail_parser::DateTime;
 use mail_send::Credentials;
 use utils::config::{
+    cron::SimpleCron,
     utils::{AsKey, ParseValue},
     Config,
 };
@@ -29,6 +33,7 @@ pub struct QueueConfig {
     pub max_multihomed: IfBlock,
     pub ip_strategy: IfBlock,
     pub source_ip: QueueOutboundSourceIp,
+    pub source_pool: IfBlock,
     pub tls: QueueOutboundTls,
     pub dsn: Dsn,
 
@@ -37,10 +42,34 @@ pub struct QueueConfig {
 
     // Throttle and Quotas
     pub throttle: QueueThrottle,
+    pub adaptive_throttle: Option<QueueAdaptiveThrottle>,
     pub quota: QueueQuotas,
 
     // Relay hosts
     pub relay_hosts: AHashMap<String, RelayHost>,
+
+    // Source IP pools
+    pub ip_pools: AHashMap<String, IpPool>,
+
+    // Message trace
+    pub trace_retention: Option<Duration>,
+
+    // Quarantine
+    pub quarantine: QueueQuarantine,
+}
+
+#[derive(Clone)]
+pub struct QueueQuarantine {
+    pub retention: Duration,
+    pub digest: Option<QuarantineDigest>,
+}
+
+#[derive(Clone)]
+pub struct QuarantineDigest {
+    pub schedule: SimpleCron,
+    pub from_name: String,
+    pub from_address: String,
+    pub subject: String,
 }
 
 #[derive(Clone)]
@@ -49,6 +78,27 @@ pub struct QueueOutboundSourceIp {
     pub ipv6: IfBlock,
 }
 
+#[derive(Clone)]
+pub struct IpPool {
+    pub addresses: Vec<IpPoolAddress>,
+    pub block_patterns: Vec<String>,
+    pub block_duration: Duration,
+}
+
+#[derive(Clone)]
+pub struct IpPoolAddress {
+    pub ip: IpAddr,
+    pub weight: u32,
+    pub daily_limit: Option<u64>,
+    pub warmup: Option<IpWarmUp>,
+}
+
+#[derive(Clone)]
+pub struct IpWarmUp {
+    pub start: u64,
+    pub schedule: Vec<u64>,
+}
+
 #[derive(Clone)]
 pub struct Dsn {
     pub name: IfBlock,
@@ -83,6 +133,16 @@ pub struct QueueThrottle {
     pub host: Vec<Throttle>,
 }
 
+#[derive(Debug, Clone)]
+pub struct QueueAdaptiveThrottle {
+    pub max_concurrency: u64,
+    pub min_concurrency: u64,
+    pub backoff_factor: f64,
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+    pub recovery_threshold: u64,
+}
+
 #[derive(Clone)]
 pub struct QueueQuotas {
     pub sender: Vec<QueueQuota>,
@@ -150,6 +210,7 @@ impl Default for QueueConfig {
                 ipv4: IfBlock::empty("queue.outbound.source-ip.v4"),
                 ipv6: IfBlock::empty("queue.outbound.source-ip.v6"),
             },
+            source_pool: IfBlock::empty("queue.outbound.source-pool"),
             tls: QueueOutboundTls {
                 dane: IfBlock::new::<RequireOptional>("queue.outbound.tls.dane", [], "optional"),
                 mta_sts: IfBlock::new::<RequireOptional>(
@@ -202,12 +263,19 @@ impl Default for QueueConfig {
                 rcpt: Default::default(),
                 host: Default::default(),
             },
+            adaptive_throttle: None,
             quota: QueueQuotas {
                 sender: Default::default(),
                 rcpt: Default::default(),
                 rcpt_domain: Default::default(),
             },
             relay_hosts: Default::default(),
+            ip_pools: Default::default(),
+            trace_retention: Some(Duration::from_secs(30 * 86400)),
+            quarantine: QueueQuarantine {
+                retention: Duration::from_secs(30 * 86400),
+                digest: None,
+            },
         }
     }
 }
@@ -249,6 +317,11 @@ impl QueueConfig {
                 "queue.outbound.source-ip.v6",
                 &mx_vars,
             ),
+            (
+                &mut queue.source_pool,
+                "queue.outbound.source-pool",
+                &mx_vars,
+            ),
             (&mut queue.next_hop, "queue.outbound.next-hop", &rcpt_vars),
             (&mut queue.tls.dane, "queue.outbound.tls.dane", &dane_vars),
             (
@@ -322,6 +395,15 @@ impl QueueConfig {
         // Parse queue quotas and throttles
         queue.throttle = parse_queue_throttle(config);
         queue.quota = parse_queue_quota(config);
+        queue.adaptive_throttle = parse_queue_adaptive_throttle(config);
+
+        // Parse message trace retention
+        queue.trace_retention = config
+            .property_or_default::<Option<Duration>>("queue.trace.retention", "30d")
+            .unwrap_or(queue.trace_retention);
+
+        // Parse quarantine settings
+        queue.quarantine = parse_queue_quarantine(config);
 
         // Parse relay hosts
         queue.relay_hosts = config
@@ -332,6 +414,15 @@ impl QueueConfig {
             .filter_map(|id| parse_relay_host(config, &id).map(|host| (id, host)))
             .collect();
 
+        // Parse source IP pools
+        queue.ip_pools = config
+            .sub_keys("queue.outbound.ip-pool", "")
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|id| parse_ip_pool(config, &id).map(|pool| (id, pool)))
+            .collect();
+
         // Add local delivery host
         queue.relay_hosts.insert(
             "local".to_string(),
@@ -375,6 +466,110 @@ fn parse_relay_host(config: &mut Config, id: &str) -> Option<RelayHost> {
     })
 }
 
+fn parse_ip_pool(config: &mut Config, id: &str) -> Option<IpPool> {
+    let prefix = ("queue.outbound.ip-pool", id);
+    let mut addresses = Vec::new();
+
+    for addr_id in config
+        .sub_keys(("queue.outbound.ip-pool", id, "address"), ".ip")
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+    {
+        let addr_prefix = format!("queue.outbound.ip-pool.{id}.address.{addr_id}");
+        let addr_prefix = addr_prefix.as_str();
+        let Some(ip) = config.property_require::<IpAddr>((addr_prefix, "ip")) else {
+            continue;
+        };
+        let warmup = if let Some(start) = config.value((addr_prefix, "warmup.start")) {
+            let Some(start) = DateTime::parse_rfc3339(start) else {
+                config.new_parse_error((addr_prefix, "warmup.start"), "Invalid RFC3339 timestamp");
+                continue;
+            };
+            let schedule = config
+                .properties::<u64>((addr_prefix, "warmup.schedule"))
+                .into_iter()
+                .map(|(_, limit)| limit)
+                .collect::<Vec<_>>();
+            if schedule.is_empty() {
+                config.new_parse_error(
+                    (addr_prefix, "warmup.schedule"),
+                    "Warm-up schedule cannot be empty",
+                );
+                continue;
+            }
+
+            Some(IpWarmUp {
+                start: start.to_timestamp() as u64,
+                schedule,
+            })
+        } else {
+            None
+        };
+
+        addresses.push(IpPoolAddress {
+            ip,
+            weight: config
+                .property_or_default::<u32>((addr_prefix, "weight"), "1")
+                .unwrap_or(1)
+                .max(1),
+            daily_limit: config.property::<u64>((addr_prefix, "daily-limit")),
+            warmup,
+        });
+    }
+
+    if addresses.is_empty() {
+        config.new_build_error(prefix, "IP pool has no addresses");
+        return None;
+    }
+
+    let mut block_patterns = config
+        .values(("queue.outbound.ip-pool", id, "block-detection.patterns"))
+        .map(|(_, pattern)| pattern.to_lowercase())
+        .collect::<Vec<_>>();
+    if block_patterns.is_empty() {
+        block_patterns = [
+            "blocklist",
+            "blacklist",
+            "blocked",
+            "spamhaus",
+            "spamcop",
+            "barracuda",
+            "listed",
+            "reputation",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    }
+
+    Some(IpPool {
+        addresses,
+        block_patterns,
+        block_duration: config
+            .property_or_default::<Duration>(
+                ("queue.outbound.ip-pool", id, "block-detection.duration"),
+                "1d",
+            )
+            .unwrap_or_else(|| Duration::from_secs(86400)),
+    })
+}
+
+impl IpPoolAddress {
+    pub fn daily_limit_at(&self, timestamp: u64) -> Option<u64> {
+        if let Some(warmup) = &self.warmup {
+            let day = timestamp.saturating_sub(warmup.start) / 86400;
+            if let Some(limit) = warmup.schedule.get(day as usize) {
+                return Some(
+                    self.daily_limit
+                        .map_or(*limit, |daily_limit| daily_limit.min(*limit)),
+                );
+            }
+        }
+
+        self.daily_limit
+    }
+}
+
 fn parse_queue_throttle(config: &mut Config) -> QueueThrottle {
     // Parse throttle
     let mut throttle = QueueThrottle {
@@ -417,6 +612,77 @@ fn parse_queue_throttle(config: &mut Config) -> QueueThrottle {
     throttle
 }
 
+fn parse_queue_adaptive_throttle(config: &mut Config) -> Option<QueueAdaptiveThrottle> {
+    if !config
+        .property_or_default::<bool>("queue.outbound.adaptive-throttle.enable", "false")
+        .unwrap_or(false)
+    {
+        return None;
+    }
+
+    let max_concurrency = config
+        .property_or_default::<u64>("queue.outbound.adaptive-throttle.concurrency.max", "10")
+        .unwrap_or(10)
+        .max(1);
+    let min_interval = config
+        .property_or_default::<Duration>("queue.outbound.adaptive-throttle.interval.min", "1s")
+        .unwrap_or_else(|| Duration::from_secs(1));
+
+    Some(QueueAdaptiveThrottle {
+        max_concurrency,
+        min_concurrency: config
+            .property_or_default::<u64>("queue.outbound.adaptive-throttle.concurrency.min", "1")
+            .unwrap_or(1)
+            .clamp(1, max_concurrency),
+        backoff_factor: config
+            .property_or_default::<f64>("queue.outbound.adaptive-throttle.backoff-factor", "0.5")
+            .unwrap_or(0.5)
+            .clamp(0.0, 1.0),
+        max_interval: config
+            .property_or_default::<Duration>("queue.outbound.adaptive-throttle.interval.max", "5m")
+            .unwrap_or_else(|| Duration::from_secs(300))
+            .max(min_interval),
+        min_interval,
+        recovery_threshold: config
+            .property_or_default::<u64>("queue.outbound.adaptive-throttle.recovery", "10")
+            .unwrap_or(10)
+            .max(1),
+    })
+}
+
+fn parse_queue_quarantine(config: &mut Config) -> QueueQuarantine {
+    QueueQuarantine {
+        retention: config
+            .property_or_default::<Duration>("queue.quarantine.retention", "30d")
+            .unwrap_or_else(|| Duration::from_secs(30 * 86400)),
+        digest: if config
+            .property_or_default::<bool>("queue.quarantine.digest.enable", "false")
+            .unwrap_or(false)
+        {
+            QuarantineDigest {
+                schedule: config
+                    .property_or_default::<SimpleCron>("queue.quarantine.digest.schedule", "0 8 *")
+                    .unwrap_or_else(|| SimpleCron::parse_value("0 8 *").unwrap()),
+                from_name: config
+                    .value("queue.quarantine.digest.from-name")
+                    .unwrap_or("Mail Quarantine")
+                    .to_string(),
+                from_address: config
+                    .value("queue.quarantine.digest.from-address")
+                    .unwrap_or("postmaster@localhost")
+                    .to_string(),
+                subject: config
+                    .value("queue.quarantine.digest.subject")
+                    .unwrap_or("Quarantined messages summary")
+                    .to_string(),
+            }
+            .into()
+        } else {
+            None
+        },
+    }
+}
+
 fn parse_queue_quota(config: &mut Config) -> QueueQuotas {
     let mut capacities = QueueQuotas {
         sender: Vec::new(),
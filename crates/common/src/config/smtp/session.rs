@@ -75,6 +75,7 @@ pub struct Extensions {
     pub future_release: IfBlock,
     pub deliver_by: IfBlock,
     pub mt_priority: IfBlock,
+    pub burl: IfBlock,
 }
 
 #[derive(Clone)]
@@ -137,6 +138,9 @@ pub struct Data {
     pub add_auth_results: IfBlock,
     pub add_message_id: IfBlock,
     pub add_date: IfBlock,
+
+    // Journaling
+    pub journal: IfBlock,
 }
 
 // Ceci n'est pas une pipe
@@ -303,6 +307,11 @@ impl SessionConfig {
                 "session.extensions.mt-priority",
                 &mt_priority_vars,
             ),
+            (
+                &mut session.extensions.burl,
+                "session.extensions.burl",
+                &has_sender_vars,
+            ),
             (
                 &mut session.ehlo.script,
                 "session.ehlo.script",
@@ -443,6 +452,11 @@ impl SessionConfig {
                 "session.data.add-headers.date",
                 &has_rcpt_vars,
             ),
+            (
+                &mut session.data.journal,
+                "session.data.journal",
+                &has_rcpt_vars,
+            ),
         ] {
             if let Some(if_block) = IfBlock::try_parse(config, key, token_map) {
                 *value = if_block;
@@ -821,6 +835,7 @@ impl Default for SessionConfig {
                     [("local_port == 25", "true")],
                     "false",
                 ),
+                journal: IfBlock::empty("session.data.journal"),
             },
             extensions: Extensions {
                 pipelining: IfBlock::new::<()>("session.extensions.pipelining", [], "true"),
@@ -857,6 +872,11 @@ impl Default for SessionConfig {
                     [("!is_empty(authenticated_as)", "mixer")],
                     "false",
                 ),
+                burl: IfBlock::new::<()>(
+                    "session.extensions.burl",
+                    [("!is_empty(authenticated_as)", "true")],
+                    "false",
+                ),
             },
             mta_sts_policy: None,
             milters: Default::default(),
@@ -875,11 +895,11 @@ impl ParseValue for Mechanism {
             "PLAIN" => AUTH_PLAIN,
             "XOAUTH2" => AUTH_XOAUTH2,
             "OAUTHBEARER" => AUTH_OAUTHBEARER,
-            /*"SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
+            "SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
             "SCRAM-SHA-256" => AUTH_SCRAM_SHA_256,
             "SCRAM-SHA-1-PLUS" => AUTH_SCRAM_SHA_1_PLUS,
             "SCRAM-SHA-1" => AUTH_SCRAM_SHA_1,
-            "XOAUTH" => AUTH_XOAUTH,
+            /*"XOAUTH" => AUTH_XOAUTH,
             "9798-M-DSA-SHA1" => AUTH_9798_M_DSA_SHA1,
             "9798-M-ECDSA-SHA1" => AUTH_9798_M_ECDSA_SHA1,
             "9798-M-RSA-SHA1-ENC" => AUTH_9798_M_RSA_SHA1_ENC,
@@ -953,7 +973,11 @@ impl ConstantValue for Mechanism {
             .add_constant("login", Mechanism(AUTH_LOGIN))
             .add_constant("plain", Mechanism(AUTH_PLAIN))
             .add_constant("xoauth2", Mechanism(AUTH_XOAUTH2))
-            .add_constant("oauthbearer", Mechanism(AUTH_OAUTHBEARER));
+            .add_constant("oauthbearer", Mechanism(AUTH_OAUTHBEARER))
+            .add_constant("scram_sha_256_plus", Mechanism(AUTH_SCRAM_SHA_256_PLUS))
+            .add_constant("scram_sha_256", Mechanism(AUTH_SCRAM_SHA_256))
+            .add_constant("scram_sha_1_plus", Mechanism(AUTH_SCRAM_SHA_1_PLUS))
+            .add_constant("scram_sha_1", Mechanism(AUTH_SCRAM_SHA_1));
     }
 }
 
@@ -27,6 +27,7 @@ use arc_swap::ArcSwap;
 use config::{
     imap::ImapConfig,
     jmap::settings::JmapConfig,
+    metrics::Metrics,
     scripts::Scripting,
     server::ServerProtocol,
     smtp::{
@@ -37,7 +38,9 @@ use config::{
     storage::Storage,
     tracers::{OtelTracer, Tracer, Tracers},
 };
-use directory::{core::secret::verify_secret_hash, Directory, Principal, QueryBy, Type};
+use directory::{
+    core::secret::verify_secret_hash, Directory, DirectoryError, Principal, QueryBy, Type,
+};
 use expr::if_block::IfBlock;
 use listener::{
     blocked::{AllowedIps, BlockedIps},
@@ -61,6 +64,7 @@ use utils::{config::Config, BlobHash};
 use webhooks::{manager::WebhookEvent, WebhookPayload, WebhookType, Webhooks};
 
 pub mod addresses;
+pub mod auth;
 pub mod config;
 pub mod expr;
 pub mod listener;
@@ -85,6 +89,7 @@ pub struct Core {
     pub jmap: JmapConfig,
     pub imap: ImapConfig,
     pub web_hooks: Webhooks,
+    pub metrics: Metrics,
 }
 
 #[derive(Clone)]
@@ -92,6 +97,7 @@ pub struct Network {
     pub blocked_ips: BlockedIps,
     pub allowed_ips: AllowedIps,
     pub url: IfBlock,
+    pub totp_required: IfBlock,
 }
 
 pub enum AuthResult<T> {
@@ -106,6 +112,11 @@ pub enum DeliveryEvent {
         message: IngestMessage,
         result_tx: oneshot::Sender<Vec<DeliveryResult>>,
     },
+    FetchUrl {
+        url: String,
+        requester: String,
+        result_tx: oneshot::Sender<Option<Vec<u8>>>,
+    },
     Stop,
 }
 
@@ -120,6 +131,7 @@ pub struct IngestMessage {
     pub recipients: Vec<String>,
     pub message_blob: BlobHash,
     pub message_size: usize,
+    pub sender_verified: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -238,6 +250,7 @@ impl Core {
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn authenticate(
         &self,
         directory: &Directory,
@@ -246,12 +259,40 @@ impl Core {
         remote_ip: IpAddr,
         protocol: ServerProtocol,
         return_member_of: bool,
+        allow_app_passwords: bool,
     ) -> directory::Result<AuthResult<Principal<u32>>> {
         // First try to authenticate the user against the default directory
         let result = match directory
             .query(QueryBy::Credentials(credentials), return_member_of)
             .await
         {
+            // Accounts with two-factor authentication enabled have to use
+            // app passwords with legacy protocols and a TOTP code for web logins
+            Ok(Some(principal)) if principal.has_totp() => {
+                if allow_app_passwords {
+                    self.authenticate_app_password(
+                        directory,
+                        credentials,
+                        protocol,
+                        return_member_of,
+                    )
+                    .await
+                } else {
+                    return Err(DirectoryError::MissingTotpCode);
+                }
+            }
+            // Then try the application passwords issued for this protocol
+            Ok(None) if allow_app_passwords => {
+                self.authenticate_app_password(directory, credentials, protocol, return_member_of)
+                    .await
+            }
+            Ok(None) => {
+                self.authenticate_totp(directory, credentials, return_member_of)
+                    .await
+            }
+            result => result,
+        };
+        let result = match result {
             Ok(Some(principal)) => {
                 // Send webhook event
                 if self.has_webhook_subscribers(WebhookType::AuthSuccess) {
@@ -366,8 +407,20 @@ impl Core {
             }
 
             Err(err)
-        } else if self.has_fail2ban() {
-            let login = credentials.login();
+        } else {
+            self.authentication_failed(ipc, credentials.login(), remote_ip, protocol)
+                .await
+        }
+    }
+
+    pub async fn authentication_failed<T>(
+        &self,
+        ipc: &Ipc,
+        login: &str,
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+    ) -> directory::Result<AuthResult<T>> {
+        if self.has_fail2ban() {
             if self.is_fail2banned(remote_ip, login.to_string()).await? {
                 tracing::info!(
                     context = "directory",
@@ -382,7 +435,7 @@ impl Core {
                     ipc.send_webhook(
                         WebhookType::AuthBanned,
                         WebhookPayload::Authentication {
-                            login: credentials.login().to_string(),
+                            login: login.to_string(),
                             protocol,
                             remote_ip,
                             typ: None,
@@ -399,7 +452,7 @@ impl Core {
                     ipc.send_webhook(
                         WebhookType::AuthFailure,
                         WebhookPayload::Authentication {
-                            login: credentials.login().to_string(),
+                            login: login.to_string(),
                             protocol,
                             remote_ip,
                             typ: None,
@@ -417,7 +470,7 @@ impl Core {
                 ipc.send_webhook(
                     WebhookType::AuthFailure,
                     WebhookPayload::Authentication {
-                        login: credentials.login().to_string(),
+                        login: login.to_string(),
                         protocol,
                         remote_ip,
                         typ: None,
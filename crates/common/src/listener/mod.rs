@@ -92,6 +92,7 @@ pub struct SessionData<T: SessionStream> {
 pub trait SessionStream: AsyncRead + AsyncWrite + Unpin + 'static + Sync + Send {
     fn is_tls(&self) -> bool;
     fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>);
+    fn tls_exporter(&self) -> Option<Vec<u8>>;
 }
 
 pub trait SessionManager: Sync + Send + 'static + Clone {
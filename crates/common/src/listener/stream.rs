@@ -40,6 +40,10 @@ impl SessionStream for TcpStream {
     fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>) {
         (Cow::Borrowed(""), Cow::Borrowed(""))
     }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 impl<T: SessionStream> SessionStream for TlsStream<T> {
@@ -79,6 +83,17 @@ impl<T: SessionStream> SessionStream for TlsStream<T> {
             .into(),
         )
     }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        // RFC 9266 channel binding, only defined for TLS 1.3
+        let (_, conn) = self.get_ref();
+        if conn.protocol_version() == Some(rustls::ProtocolVersion::TLSv1_3) {
+            conn.export_keying_material(vec![0u8; 32], b"EXPORTER-Channel-Binding", None)
+                .ok()
+        } else {
+            None
+        }
+    }
 }
 
 impl SessionStream for ProxiedStream<TcpStream> {
@@ -99,6 +114,10 @@ impl SessionStream for ProxiedStream<TcpStream> {
             })
             .unwrap_or((Cow::Borrowed("unknown"), Cow::Borrowed("unknown")))
     }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 #[derive(Default)]
@@ -157,4 +176,8 @@ impl SessionStream for NullIo {
             std::borrow::Cow::Borrowed(""),
         )
     }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
@@ -31,6 +31,8 @@ use nlp::{
 use sieve::{runtime::Variable, FunctionMap};
 use store::{write::key::KeySerializer, LookupStore, U64_LEN};
 
+use crate::Core;
+
 use super::PluginContext;
 
 pub fn register_train(plugin_id: u32, fnc_map: &mut FunctionMap) {
@@ -89,83 +91,80 @@ async fn train(ctx: PluginContext<'_>, is_train: bool) -> Variable {
     }
 
     // Train the model
+    match bayes_train(
+        ctx.core,
+        store,
+        &ctx.cache.bayes_cache,
+        text.as_ref(),
+        is_spam,
+        is_train,
+        None,
+    )
+    .await
+    {
+        Ok(0) => {
+            tracing::debug!(
+                parent: span,
+                context = "sieve:bayes_train",
+                event = "failed",
+                reason = "No weights found",
+            );
+            false.into()
+        }
+        Ok(num_tokens) => {
+            tracing::debug!(
+                parent: span,
+                context = "sieve:bayes_train",
+                event = if is_train { "train" } else { "untrain" },
+                is_spam = is_spam,
+                num_tokens = num_tokens,
+            );
+            true.into()
+        }
+        Err(_) => false.into(),
+    }
+}
+
+/// Trains or untrains the Bayes model stored in `store` with the provided text,
+/// returning the number of tokens updated. When an account id is provided, the
+/// per-account model is updated instead of the global one.
+pub async fn bayes_train(
+    core: &Core,
+    store: &LookupStore,
+    cache: &BayesTokenCache,
+    text: &str,
+    is_spam: bool,
+    is_train: bool,
+    account_id: Option<u32>,
+) -> store::Result<usize> {
     let mut model = BayesModel::default();
     model.train(
-        OsbTokenizer::new(
-            BayesTokenizer::new(text.as_ref(), &ctx.core.smtp.resolvers.psl),
-            5,
-        ),
+        OsbTokenizer::new(BayesTokenizer::new(text, &core.smtp.resolvers.psl), 5),
         is_spam,
     );
     if model.weights.is_empty() {
-        tracing::debug!(
-            parent: span,
-            context = "sieve:bayes_train",
-            event = "failed",
-            reason = "No weights found",
-        );
-        return false.into();
+        return Ok(0);
     }
 
-    tracing::debug!(
-        parent: span,
-        context = "sieve:bayes_train",
-        event = "train",
-        is_spam = is_spam,
-        num_tokens = model.weights.len(),
-    );
-
-    // Update weight and invalidate cache
-    let bayes_cache = &ctx.cache.bayes_cache;
-    if is_train {
-        for (hash, weights) in model.weights {
-            if store
-                .counter_incr(
-                    KeySerializer::new(U64_LEN)
-                        .write(hash.h1)
-                        .write(hash.h2)
-                        .finalize(),
-                    weights.into(),
-                    None,
-                    false,
-                )
-                .await
-                .is_err()
-            {
-                return false.into();
-            }
-            bayes_cache.invalidate(&hash);
-        }
-
-        // Update training counts
-        let weights = if is_spam {
-            Weights { spam: 1, ham: 0 }
-        } else {
-            Weights { spam: 0, ham: 1 }
-        };
-        if store
-            .counter_incr(
-                KeySerializer::new(U64_LEN)
-                    .write(0u64)
-                    .write(0u64)
-                    .finalize(),
-                weights.into(),
-                None,
-                false,
-            )
-            .await
-            .is_err()
-        {
-            return false.into();
-        }
-    } else {
-        //TODO: Implement untrain
-        return false.into();
+    // Update weights and invalidate cache
+    let num_tokens = model.weights.len();
+    for (hash, weights) in model.weights {
+        let hash = scoped_hash(hash, account_id);
+        update_weights(store, &hash, weights, is_train).await?;
+        cache.invalidate(&hash);
     }
 
-    bayes_cache.invalidate(&TokenHash::default());
+    // Update training counts
+    let weights = if is_spam {
+        Weights { spam: 1, ham: 0 }
+    } else {
+        Weights { spam: 0, ham: 1 }
+    };
+    let hash = scoped_hash(TokenHash::default(), account_id);
+    update_weights(store, &hash, weights, is_train).await?;
+    cache.invalidate(&hash);
 
-    true.into()
+    Ok(num_tokens)
 }
 
 pub async fn exec_classify(ctx: PluginContext<'_>) -> Variable {
@@ -208,10 +207,35 @@ pub async fn exec_classify(ctx: PluginContext<'_>) -> Variable {
         }
     }
 
+    bayes_classify(
+        ctx.core,
+        store,
+        &ctx.cache.bayes_cache,
+        span,
+        text.as_ref(),
+        &classifier,
+        None,
+    )
+    .await
+    .map(Variable::from)
+    .unwrap_or_default()
+}
+
+/// Classifies the provided text using the global or, when an account id is
+/// provided, the per-account Bayes model. Returns `None` when the model does
+/// not have enough training data.
+pub async fn bayes_classify(
+    core: &Core,
+    store: &LookupStore,
+    cache: &BayesTokenCache,
+    span: &tracing::Span,
+    text: &str,
+    classifier: &BayesClassifier,
+    account_id: Option<u32>,
+) -> Option<f64> {
     // Obtain training counts
-    let bayes_cache = &ctx.cache.bayes_cache;
     let (spam_learns, ham_learns) =
-        if let Some(weights) = bayes_cache.get_or_update(TokenHash::default(), store).await {
+        if let Some(weights) = bayes_learns(store, cache, account_id).await {
             (weights.spam, weights.ham)
         } else {
             tracing::warn!(
@@ -220,7 +244,7 @@ pub async fn exec_classify(ctx: PluginContext<'_>) -> Variable {
                 event = "failed",
                 reason = "Failed to obtain training counts",
             );
-            return Variable::default();
+            return None;
         };
 
     // Make sure we have enough training data
@@ -233,26 +257,37 @@ pub async fn exec_classify(ctx: PluginContext<'_>) -> Variable {
             min_learns = classifier.min_learns,
             spam_learns = %spam_learns,
             ham_learns = %ham_learns);
-        return Variable::default();
+        return None;
     }
 
     // Classify the text
     let mut tokens = Vec::new();
-    for token in OsbTokenizer::<_, TokenHash>::new(
-        BayesTokenizer::new(text.as_ref(), &ctx.core.smtp.resolvers.psl),
-        5,
-    ) {
-        if let Some(weights) = bayes_cache.get_or_update(token.inner, store).await {
+    for token in
+        OsbTokenizer::<_, TokenHash>::new(BayesTokenizer::new(text, &core.smtp.resolvers.psl), 5)
+    {
+        if let Some(weights) = cache
+            .get_or_update(scoped_hash(token.inner, account_id), store)
+            .await
+        {
             tokens.push(OsbToken {
                 inner: weights,
                 idx: token.idx,
             });
         }
     }
-    classifier
-        .classify(tokens.into_iter(), ham_learns, spam_learns)
-        .map(Variable::from)
-        .unwrap_or_default()
+    classifier.classify(tokens.into_iter(), ham_learns, spam_learns)
+}
+
+/// Returns the number of spam and ham messages the global or per-account
+/// model was trained with.
+pub async fn bayes_learns(
+    store: &LookupStore,
+    cache: &BayesTokenCache,
+    account_id: Option<u32>,
+) -> Option<Weights> {
+    cache
+        .get_or_update(scoped_hash(TokenHash::default(), account_id), store)
+        .await
 }
 
 pub async fn exec_is_balanced(ctx: PluginContext<'_>) -> Variable {
@@ -331,15 +366,7 @@ impl LookupOrInsert for BayesTokenCache {
     async fn get_or_update(&self, hash: TokenHash, get_token: &LookupStore) -> Option<Weights> {
         if let Some(weights) = self.get(&hash) {
             weights.unwrap_or_default().into()
-        } else if let Ok(num) = get_token
-            .counter_get(
-                KeySerializer::new(U64_LEN)
-                    .write(hash.h1)
-                    .write(hash.h2)
-                    .finalize(),
-            )
-            .await
-        {
+        } else if let Ok(num) = get_token.counter_get(token_key(&hash)).await {
             if num != 0 {
                 let weights = Weights::from(num);
                 self.insert_positive(hash, weights);
@@ -355,3 +382,50 @@ impl LookupOrInsert for BayesTokenCache {
         }
     }
 }
+
+fn token_key(hash: &TokenHash) -> Vec<u8> {
+    KeySerializer::new(U64_LEN * 2)
+        .write(hash.h1)
+        .write(hash.h2)
+        .finalize()
+}
+
+// Spam and ham counts are packed into a single counter, untraining never
+// subtracts more than what is stored so that neither half can underflow.
+async fn update_weights(
+    store: &LookupStore,
+    hash: &TokenHash,
+    weights: Weights,
+    is_train: bool,
+) -> store::Result<()> {
+    let key = token_key(hash);
+    let delta = if is_train {
+        i64::from(weights)
+    } else {
+        let current = Weights::from(store.counter_get(key.clone()).await?);
+        -i64::from(Weights {
+            spam: weights.spam.min(current.spam),
+            ham: weights.ham.min(current.ham),
+        })
+    };
+
+    if delta != 0 {
+        store.counter_incr(key, delta, None, false).await?;
+    }
+
+    Ok(())
+}
+
+// Per-account models share the store with the global model, their token
+// hashes are mixed with the account id so they never collide.
+fn scoped_hash(hash: TokenHash, account_id: Option<u32>) -> TokenHash {
+    if let Some(account_id) = account_id {
+        let seed = (account_id as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        TokenHash {
+            h1: hash.h1 ^ seed,
+            h2: hash.h2 ^ seed.rotate_left(32),
+        }
+    } else {
+        hash
+    }
+}
@@ -28,6 +28,7 @@ pub mod headers;
 pub mod http;
 pub mod lookup;
 pub mod pyzor;
+pub mod quarantine;
 pub mod query;
 pub mod text;
 
@@ -49,7 +50,7 @@ pub struct PluginContext<'x> {
     pub arguments: Vec<Variable>,
 }
 
-const PLUGINS_REGISTER: [RegisterPluginFnc; 18] = [
+const PLUGINS_REGISTER: [RegisterPluginFnc; 19] = [
     query::register,
     exec::register,
     lookup::register,
@@ -68,6 +69,7 @@ const PLUGINS_REGISTER: [RegisterPluginFnc; 18] = [
     headers::register,
     text::register_tokenize,
     text::register_domain_part,
+    quarantine::register,
 ];
 
 pub trait RegisterSievePlugins {
@@ -114,6 +116,7 @@ impl Core {
             15 => headers::exec(ctx),
             16 => text::exec_tokenize(ctx),
             17 => text::exec_domain_part(ctx),
+            18 => quarantine::exec(ctx),
             _ => unreachable!(),
         }
         .into()
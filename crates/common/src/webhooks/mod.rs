@@ -100,6 +100,8 @@ pub enum WebhookType {
     IncomingArfReport,
     #[serde(rename = "report.outgoing")]
     OutgoingReport,
+    #[serde(rename = "sieve.notify")]
+    SieveNotify,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -262,6 +264,17 @@ pub enum WebhookPayload {
         #[serde(rename = "objectSize")]
         object_size: usize,
     },
+    SieveNotify {
+        #[serde(rename = "accountId")]
+        account_id: u32,
+        method: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        from: Option<String>,
+        importance: WebhookNotifyImportance,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        options: Vec<String>,
+        message: String,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -312,6 +325,14 @@ pub enum WebhookDSNType {
     PermanentFailure,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum WebhookNotifyImportance {
+    High,
+    Normal,
+    Low,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 #[serde(rename_all = "camelCase")]
 pub enum WebhookMessageFailure {
@@ -30,7 +30,10 @@ use store::{
     Deserialize, IterateParams, Serialize, Store, ValueKey, U32_LEN,
 };
 
-use crate::{DirectoryError, ManagementError, Principal, QueryBy, Type};
+use crate::{
+    core::secret::{plain_secret, with_scram_secrets, ScramSecret},
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
 
 use super::{
     lookup::DirectoryStore, PrincipalAction, PrincipalField, PrincipalIdType, PrincipalUpdate,
@@ -177,6 +180,9 @@ impl ManageDirectory for Store {
         let mut principal = self.map_principal(principal, false).await?;
         let members = self.map_group_names(members, false).await?;
 
+        // Store salted SCRAM keys alongside plain-text secrets
+        principal.secrets = with_scram_secrets(principal.secrets);
+
         // Make sure new name is not taken
         principal.name = principal.name.to_lowercase();
         if self.get_account_id(&principal.name).await?.is_some() {
@@ -429,7 +435,32 @@ impl ManageDirectory for Store {
                     PrincipalField::Secrets,
                     PrincipalValue::StringList(secrets),
                 ) => {
-                    principal.inner.secrets = secrets;
+                    principal.inner.secrets = with_scram_secrets(secrets);
+                }
+                (
+                    PrincipalAction::AddItem,
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(secret),
+                ) => {
+                    if !principal.inner.secrets.contains(&secret) {
+                        principal.inner.secrets.push(secret);
+                        principal.inner.secrets =
+                            with_scram_secrets(std::mem::take(&mut principal.inner.secrets));
+                    }
+                }
+                (
+                    PrincipalAction::RemoveItem,
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(secret),
+                ) => {
+                    // Also remove any SCRAM keys derived from the secret
+                    let plain = plain_secret(&secret);
+                    principal.inner.secrets.retain(|v| {
+                        *v != secret
+                            && !plain.is_some_and(|plain| {
+                                ScramSecret::parse(v).is_some_and(|scram| scram.verify(plain))
+                            })
+                    });
                 }
                 (
                     PrincipalAction::Set,
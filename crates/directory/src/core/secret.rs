@@ -21,17 +21,23 @@
  * for more details.
 */
 
+use std::fmt::Display;
+
 use argon2::Argon2;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
 use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use password_hash::PasswordHash;
-use pbkdf2::Pbkdf2;
+use pbkdf2::{pbkdf2_hmac, Pbkdf2};
 use pwhash::{bcrypt, bsdi_crypt, md5_crypt, sha1_crypt, sha256_crypt, sha512_crypt, unix_crypt};
+use rand::{thread_rng, Rng};
 use scrypt::Scrypt;
 use sha1::Digest;
 use sha1::Sha1;
 use sha2::Sha256;
 use sha2::Sha512;
+use store::write::now;
 use tokio::sync::oneshot;
 
 use crate::Principal;
@@ -39,12 +45,56 @@ use crate::Principal;
 impl<T: serde::Serialize + serde::de::DeserializeOwned> Principal<T> {
     pub async fn verify_secret(&self, secret: &str) -> bool {
         for hashed_secret in &self.secrets {
-            if verify_secret_hash(hashed_secret, secret).await {
+            // App passwords are only valid for the protocols they were issued for,
+            // TOTP secrets and recovery codes are only used as a second factor
+            if is_password_secret(hashed_secret) && verify_secret_hash(hashed_secret, secret).await
+            {
                 return true;
             }
         }
         false
     }
+
+    pub fn app_passwords(&self) -> impl Iterator<Item = AppPassword> + '_ {
+        self.secrets
+            .iter()
+            .filter_map(|secret| AppPassword::parse(secret))
+    }
+
+    pub fn totp_secret(&self) -> Option<TotpSecret> {
+        self.secrets
+            .iter()
+            .find_map(|secret| TotpSecret::parse(secret))
+    }
+
+    pub fn has_totp(&self) -> bool {
+        self.totp_secret().is_some_and(|totp| totp.enabled)
+    }
+
+    pub fn recovery_codes(&self) -> impl Iterator<Item = &str> + '_ {
+        self.secrets
+            .iter()
+            .filter(|secret| secret.starts_with(RECOVERY_CODE_PREFIX))
+            .map(|secret| secret.as_str())
+    }
+
+    /// Returns the stored entry matching the recovery code, so it can be removed once used.
+    pub async fn verify_recovery_code(&self, code: &str) -> Option<&str> {
+        let code = code.trim().to_ascii_lowercase();
+        for secret in self.recovery_codes() {
+            if verify_secret_hash(&secret[RECOVERY_CODE_PREFIX.len()..], &code).await {
+                return Some(secret);
+            }
+        }
+        None
+    }
+}
+
+pub fn is_password_secret(secret: &str) -> bool {
+    !secret.starts_with(AppPassword::PREFIX)
+        && !secret.starts_with(TotpSecret::PREFIX)
+        && !secret.starts_with(TotpSecret::PENDING_PREFIX)
+        && !secret.starts_with(RECOVERY_CODE_PREFIX)
 }
 
 async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> bool {
@@ -189,6 +239,12 @@ pub async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> bool {
                         unix_crypt::verify(secret, hashed_secret)
                     }
                 }
+                "SCRAM-SHA-1" | "SCRAM-SHA-256" => {
+                    // Salted SCRAM keys
+                    ScramAlgorithm::parse(algo)
+                        .and_then(|algorithm| ScramSecret::parse_value(algorithm, hashed_secret))
+                        .is_some_and(|scram| scram.verify(secret))
+                }
                 "PLAIN" | "plain" | "CLEAR" | "clear" => hashed_secret == secret,
                 _ => {
                     tracing::warn!(
@@ -213,3 +269,592 @@ pub async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> bool {
         hashed_secret == secret
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScramAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScramSecret {
+    pub algorithm: ScramAlgorithm,
+    pub iterations: u32,
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+}
+
+impl ScramAlgorithm {
+    pub fn parse(value: &str) -> Option<Self> {
+        if value.eq_ignore_ascii_case("SCRAM-SHA-1") {
+            Some(ScramAlgorithm::Sha1)
+        } else if value.eq_ignore_ascii_case("SCRAM-SHA-256") {
+            Some(ScramAlgorithm::Sha256)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScramAlgorithm::Sha1 => "SCRAM-SHA-1",
+            ScramAlgorithm::Sha256 => "SCRAM-SHA-256",
+        }
+    }
+
+    pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+            ScramAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        }
+    }
+
+    pub fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramAlgorithm::Sha1 => {
+                let mut mac = Hmac::<Sha1>::new_from_slice(key).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            ScramAlgorithm::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+
+    pub fn salted_password(&self, password: &[u8], salt: &[u8], iterations: u32) -> Vec<u8> {
+        match self {
+            ScramAlgorithm::Sha1 => {
+                let mut result = vec![0u8; 20];
+                pbkdf2_hmac::<Sha1>(password, salt, iterations, &mut result);
+                result
+            }
+            ScramAlgorithm::Sha256 => {
+                let mut result = vec![0u8; 32];
+                pbkdf2_hmac::<Sha256>(password, salt, iterations, &mut result);
+                result
+            }
+        }
+    }
+}
+
+impl ScramSecret {
+    pub const DEFAULT_ITERATIONS: u32 = 4096;
+
+    pub fn derive(
+        algorithm: ScramAlgorithm,
+        password: &str,
+        salt: Vec<u8>,
+        iterations: u32,
+    ) -> Self {
+        let salted_password = algorithm.salted_password(password.as_bytes(), &salt, iterations);
+        ScramSecret {
+            algorithm,
+            iterations,
+            stored_key: algorithm.hash(&algorithm.hmac(&salted_password, b"Client Key")),
+            server_key: algorithm.hmac(&salted_password, b"Server Key"),
+            salt,
+        }
+    }
+
+    pub fn generate(algorithm: ScramAlgorithm, password: &str) -> Self {
+        ScramSecret::derive(
+            algorithm,
+            password,
+            thread_rng().gen::<[u8; 16]>().to_vec(),
+            Self::DEFAULT_ITERATIONS,
+        )
+    }
+
+    pub fn parse(hashed_secret: &str) -> Option<Self> {
+        let (algo, value) = hashed_secret.strip_prefix('{')?.split_once('}')?;
+        ScramSecret::parse_value(ScramAlgorithm::parse(algo)?, value)
+    }
+
+    pub fn parse_value(algorithm: ScramAlgorithm, value: &str) -> Option<Self> {
+        // Dovecot compatible format: iterations,salt,stored_key,server_key
+        let mut parts = value.split(',');
+        let iterations = parts.next()?.parse().ok().filter(|&i| i > 0)?;
+        let salt = base64_decode(parts.next()?.as_bytes())?;
+        let stored_key = base64_decode(parts.next()?.as_bytes())?;
+        let server_key = base64_decode(parts.next()?.as_bytes())?;
+
+        if parts.next().is_none() && !salt.is_empty() {
+            Some(ScramSecret {
+                algorithm,
+                iterations,
+                salt,
+                stored_key,
+                server_key,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        ScramSecret::derive(self.algorithm, password, self.salt.clone(), self.iterations).stored_key
+            == self.stored_key
+    }
+}
+
+impl Display for ScramSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{{{}}}{},{},{},{}",
+            self.algorithm.as_str(),
+            self.iterations,
+            std::str::from_utf8(&base64_encode(&self.salt).unwrap_or_default()).unwrap_or_default(),
+            std::str::from_utf8(&base64_encode(&self.stored_key).unwrap_or_default())
+                .unwrap_or_default(),
+            std::str::from_utf8(&base64_encode(&self.server_key).unwrap_or_default())
+                .unwrap_or_default()
+        )
+    }
+}
+
+pub fn plain_secret(secret: &str) -> Option<&str> {
+    if !secret.starts_with(['$', '_', '{']) {
+        Some(secret)
+    } else {
+        secret
+            .strip_prefix('{')
+            .and_then(|secret| secret.split_once('}'))
+            .and_then(|(algo, secret)| {
+                matches!(algo, "PLAIN" | "plain" | "CLEAR" | "clear").then_some(secret)
+            })
+    }
+}
+
+/// Adds salted SCRAM keys for each plain-text secret that does not have them already.
+pub fn with_scram_secrets(mut secrets: Vec<String>) -> Vec<String> {
+    let mut scram_secrets = Vec::new();
+    for secret in secrets.iter().filter_map(|secret| plain_secret(secret)) {
+        for algorithm in [ScramAlgorithm::Sha1, ScramAlgorithm::Sha256] {
+            if !secrets
+                .iter()
+                .filter_map(|secret| ScramSecret::parse(secret))
+                .any(|scram| scram.algorithm == algorithm && scram.verify(secret))
+            {
+                scram_secrets.push(ScramSecret::generate(algorithm, secret).to_string());
+            }
+        }
+    }
+    secrets.extend(scram_secrets);
+    secrets
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppPasswordScope {
+    Imap,
+    Smtp,
+    Pop3,
+    ManageSieve,
+    Jmap,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppPassword {
+    pub name: String,
+    pub scopes: Vec<AppPasswordScope>,
+    pub created: u64,
+    pub expires: Option<u64>,
+    pub hashed_secret: String,
+}
+
+impl AppPasswordScope {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "imap" => Some(AppPasswordScope::Imap),
+            "smtp" => Some(AppPasswordScope::Smtp),
+            "pop3" => Some(AppPasswordScope::Pop3),
+            "managesieve" => Some(AppPasswordScope::ManageSieve),
+            "jmap" => Some(AppPasswordScope::Jmap),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AppPasswordScope::Imap => "imap",
+            AppPasswordScope::Smtp => "smtp",
+            AppPasswordScope::Pop3 => "pop3",
+            AppPasswordScope::ManageSieve => "managesieve",
+            AppPasswordScope::Jmap => "jmap",
+        }
+    }
+}
+
+impl AppPassword {
+    pub const PREFIX: &'static str = "$app$";
+
+    pub fn new(
+        name: String,
+        scopes: Vec<AppPasswordScope>,
+        expires: Option<u64>,
+        password: &str,
+    ) -> Self {
+        AppPassword {
+            name,
+            scopes,
+            created: now(),
+            expires,
+            hashed_secret: salted_sha512(password),
+        }
+    }
+
+    pub fn parse(secret: &str) -> Option<Self> {
+        // Format: $app$name$scopes$created$expires$hashed_secret
+        let mut parts = secret.strip_prefix(Self::PREFIX)?.splitn(5, '$');
+        let name = parts.next().filter(|name| Self::is_valid_name(name))?;
+        let scopes = parts
+            .next()?
+            .split(',')
+            .map(AppPasswordScope::parse)
+            .collect::<Option<Vec<_>>>()?;
+        let created = parts.next()?.parse().ok()?;
+        let expires = parts.next()?.parse::<u64>().ok()?;
+        let hashed_secret = parts.next().filter(|secret| !secret.is_empty())?;
+
+        Some(AppPassword {
+            name: name.to_string(),
+            scopes,
+            created,
+            expires: (expires != 0).then_some(expires),
+            hashed_secret: hashed_secret.to_string(),
+        })
+    }
+
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= 64
+            && name
+                .chars()
+                .all(|ch| ch.is_alphanumeric() || matches!(ch, ' ' | '-' | '_' | '.'))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= now())
+    }
+
+    pub async fn verify(&self, scope: AppPasswordScope, secret: &str) -> bool {
+        self.scopes.contains(&scope)
+            && !self.is_expired()
+            && verify_secret_hash(&self.hashed_secret, secret).await
+    }
+}
+
+impl Display for AppPassword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}$", Self::PREFIX, self.name)?;
+        for (pos, scope) in self.scopes.iter().enumerate() {
+            if pos > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(scope.as_str())?;
+        }
+        write!(
+            f,
+            "${}${}${}",
+            self.created,
+            self.expires.unwrap_or_default(),
+            self.hashed_secret
+        )
+    }
+}
+
+pub const RECOVERY_CODE_PREFIX: &str = "$recovery$";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TotpSecret {
+    pub secret: Vec<u8>,
+    pub enabled: bool,
+}
+
+impl TotpSecret {
+    pub const PREFIX: &'static str = "$totp$";
+    pub const PENDING_PREFIX: &'static str = "$totp-pending$";
+    pub const DIGITS: u32 = 6;
+    pub const PERIOD: u64 = 30;
+
+    pub fn generate() -> Self {
+        TotpSecret {
+            secret: thread_rng().gen::<[u8; 20]>().to_vec(),
+            enabled: false,
+        }
+    }
+
+    pub fn parse(secret: &str) -> Option<Self> {
+        let (secret, enabled) = if let Some(secret) = secret.strip_prefix(Self::PREFIX) {
+            (secret, true)
+        } else {
+            (secret.strip_prefix(Self::PENDING_PREFIX)?, false)
+        };
+
+        BASE32_NOPAD
+            .decode(secret.as_bytes())
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .map(|secret| TotpSecret { secret, enabled })
+    }
+
+    pub fn encoded_secret(&self) -> String {
+        BASE32_NOPAD.encode(&self.secret)
+    }
+
+    pub fn provisioning_uri(&self, issuer: &str, account: &str) -> String {
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            uri_encode(issuer),
+            uri_encode(account),
+            self.encoded_secret(),
+            uri_encode(issuer),
+            Self::DIGITS,
+            Self::PERIOD
+        )
+    }
+
+    pub fn code_at(&self, time: u64) -> String {
+        // RFC 6238 using HMAC-SHA1 and RFC 4226 dynamic truncation
+        let hash = ScramAlgorithm::Sha1.hmac(&self.secret, &(time / Self::PERIOD).to_be_bytes());
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let code = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]) % 10u32.pow(Self::DIGITS);
+
+        format!("{code:0width$}", width = Self::DIGITS as usize)
+    }
+
+    pub fn verify(&self, code: &str) -> bool {
+        self.verify_at(code, now())
+    }
+
+    pub fn verify_at(&self, code: &str, time: u64) -> bool {
+        // Allow one step of clock skew in either direction
+        let code = code.trim();
+        code.len() == Self::DIGITS as usize
+            && code.chars().all(|ch| ch.is_ascii_digit())
+            && [time.saturating_sub(Self::PERIOD), time, time + Self::PERIOD]
+                .into_iter()
+                .any(|time| self.code_at(time) == code)
+    }
+
+    pub fn enable(self) -> Self {
+        TotpSecret {
+            enabled: true,
+            ..self
+        }
+    }
+}
+
+impl Display for TotpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            if self.enabled {
+                Self::PREFIX
+            } else {
+                Self::PENDING_PREFIX
+            },
+            self.encoded_secret()
+        )
+    }
+}
+
+pub fn recovery_code_secret(code: &str) -> String {
+    format!(
+        "{RECOVERY_CODE_PREFIX}{}",
+        salted_sha512(&code.trim().to_ascii_lowercase())
+    )
+}
+
+fn salted_sha512(secret: &str) -> String {
+    // Generated secrets are random, a salted SHA-512 is enough
+    let salt = thread_rng().gen::<[u8; 16]>();
+    let mut hasher = Sha512::new();
+    hasher.update(secret.as_bytes());
+    hasher.update(salt);
+    let mut hash = hasher.finalize().to_vec();
+    hash.extend_from_slice(&salt);
+
+    format!(
+        "{{SSHA512}}{}",
+        std::str::from_utf8(&base64_encode(&hash).unwrap_or_default()).unwrap_or_default()
+    )
+}
+
+fn uri_encode(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'@') {
+            result.push(char::from(byte));
+        } else {
+            result.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Principal;
+
+    use super::{
+        recovery_code_secret, verify_secret_hash, with_scram_secrets, AppPassword,
+        AppPasswordScope, ScramAlgorithm, ScramSecret, TotpSecret,
+    };
+
+    #[tokio::test]
+    async fn scram_secrets() {
+        // RFC 5802 and RFC 7677 test vectors
+        for (algorithm, salt, stored_key, server_key) in [
+            (
+                ScramAlgorithm::Sha1,
+                "QSXCR+Q6sek8bf92",
+                "6dlGYMOdZcOPutkcNY8U2g7vK9Y=",
+                "D+CSWLOshSulAsxiupA+qs2/fTE=",
+            ),
+            (
+                ScramAlgorithm::Sha256,
+                "W22ZaJ0SNY7soEsUEjb6gQ==",
+                "WG5d8oPm3OtcPnkdi4Uo7BkeZkBFzpcXkuLmtbsT4qY=",
+                "wfPLwcE6nTWhTAmQ7tl2KeoiWGPlZqQxSrmfPwDl2dU=",
+            ),
+        ] {
+            let hashed_secret = format!(
+                "{{{}}}4096,{salt},{stored_key},{server_key}",
+                algorithm.as_str()
+            );
+            let secret = ScramSecret::parse(&hashed_secret).unwrap();
+            assert_eq!(secret.algorithm, algorithm);
+            assert_eq!(secret.to_string(), hashed_secret);
+            assert!(secret.verify("pencil"));
+            assert!(!secret.verify("pen"));
+            assert!(verify_secret_hash(&hashed_secret, "pencil").await);
+            assert!(!verify_secret_hash(&hashed_secret, "pen").await);
+        }
+
+        // Derive SCRAM secrets from plain-text secrets only once
+        let secrets = with_scram_secrets(vec!["secret".to_string(), "$6$hash".to_string()]);
+        assert_eq!(secrets.len(), 4);
+        assert_eq!(with_scram_secrets(secrets.clone()), secrets);
+        for secret in &secrets[2..] {
+            assert!(verify_secret_hash(secret, "secret").await);
+        }
+    }
+
+    #[tokio::test]
+    async fn app_passwords() {
+        let app_password = AppPassword::new(
+            "Phone mail".to_string(),
+            vec![AppPasswordScope::Imap, AppPasswordScope::Smtp],
+            None,
+            "app-secret",
+        );
+        let secret = app_password.to_string();
+        assert!(secret.starts_with("$app$Phone mail$imap,smtp$"), "{secret}");
+        assert_eq!(AppPassword::parse(&secret).unwrap(), app_password);
+        assert!(
+            app_password
+                .verify(AppPasswordScope::Imap, "app-secret")
+                .await
+        );
+        assert!(!app_password.verify(AppPasswordScope::Imap, "wrong").await);
+        assert!(
+            !app_password
+                .verify(AppPasswordScope::Jmap, "app-secret")
+                .await
+        );
+
+        // Expired app passwords are rejected
+        let expired = AppPassword {
+            expires: Some(1),
+            ..app_password.clone()
+        };
+        assert_eq!(AppPassword::parse(&expired.to_string()).unwrap(), expired);
+        assert!(!expired.verify(AppPasswordScope::Imap, "app-secret").await);
+
+        // App passwords are not accepted as regular secrets
+        let principal = Principal::<u32> {
+            secrets: vec!["secret".to_string(), secret],
+            ..Default::default()
+        };
+        assert!(principal.verify_secret("secret").await);
+        assert!(!principal.verify_secret("app-secret").await);
+        assert_eq!(principal.app_passwords().count(), 1);
+        assert_eq!(with_scram_secrets(principal.secrets).len(), 4);
+    }
+
+    #[tokio::test]
+    async fn totp() {
+        // RFC 6238 test vectors (SHA-1, truncated to 6 digits)
+        let totp = TotpSecret {
+            secret: b"12345678901234567890".to_vec(),
+            enabled: true,
+        };
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            assert_eq!(totp.code_at(time), code);
+            assert!(totp.verify_at(code, time));
+            assert!(totp.verify_at(code, time + TotpSecret::PERIOD));
+            assert!(!totp.verify_at(code, time + 3 * TotpSecret::PERIOD));
+        }
+        assert!(!totp.verify_at("28708", 59));
+        assert!(!totp.verify_at("abcdef", 59));
+
+        // Serialization and provisioning
+        let secret = totp.to_string();
+        assert_eq!(secret, "$totp$GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(TotpSecret::parse(&secret).unwrap(), totp);
+        let pending = TotpSecret::generate();
+        assert!(!pending.enabled);
+        assert!(pending.to_string().starts_with(TotpSecret::PENDING_PREFIX));
+        assert_eq!(TotpSecret::parse(&pending.to_string()).unwrap(), pending);
+        assert_eq!(
+            totp.provisioning_uri("Example Mail", "jdoe@example.com"),
+            concat!(
+                "otpauth://totp/Example%20Mail:jdoe@example.com?",
+                "secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Example%20Mail",
+                "&algorithm=SHA1&digits=6&period=30"
+            )
+        );
+
+        // TOTP secrets and recovery codes are not accepted as passwords
+        let principal = Principal::<u32> {
+            secrets: vec![
+                "secret".to_string(),
+                secret,
+                recovery_code_secret("abcd-efgh"),
+            ],
+            ..Default::default()
+        };
+        assert!(principal.has_totp());
+        assert!(principal.verify_secret("secret").await);
+        assert!(!principal.verify_secret("abcd-efgh").await);
+        assert!(
+            !principal
+                .verify_secret("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")
+                .await
+        );
+        assert_eq!(
+            principal.verify_recovery_code("ABCD-EFGH").await,
+            Some(principal.secrets[2].as_str())
+        );
+        assert_eq!(principal.verify_recovery_code("abcd-efgx").await, None);
+        assert_eq!(with_scram_secrets(principal.secrets).len(), 5);
+    }
+}
@@ -98,6 +98,7 @@ pub enum DirectoryError {
     Management(ManagementError),
     TimedOut,
     Unsupported,
+    MissingTotpCode,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -326,6 +327,7 @@ impl Display for DirectoryError {
             Self::Management(error) => write!(f, "Management error: {:?}", error),
             Self::TimedOut => write!(f, "Directory timed out"),
             Self::Unsupported => write!(f, "Method not supported by directory"),
+            Self::MissingTotpCode => write!(f, "Missing TOTP code"),
         }
     }
 }
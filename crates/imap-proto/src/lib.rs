@@ -89,6 +89,29 @@ pub enum Command {
 
     // RFC 2971
     Id,
+
+    // RFC 9208
+    GetQuota,
+    GetQuotaRoot,
+    SetQuota,
+
+    // RFC 5464
+    GetMetadata,
+    SetMetadata,
+
+    // RFC 4978
+    Compress,
+
+    // RFC 5465
+    Notify,
+
+    // RFC 8508
+    Replace(bool),
+
+    // RFC 4467
+    GenUrlAuth,
+    ResetKey,
+    UrlFetch,
 }
 
 impl Command {
@@ -103,6 +126,7 @@ impl Command {
                 | Command::Expunge(true)
                 | Command::Sort(true)
                 | Command::Thread(true)
+                | Command::Replace(true)
         )
     }
 }
@@ -167,6 +191,34 @@ pub enum ResponseCode {
 
     // USEATTR
     UseAttr,
+
+    // COMPRESS
+    CompressionActive,
+
+    // METADATA
+    Metadata {
+        code: MetadataCode,
+    },
+
+    // NOTIFY
+    BadEvent,
+    NotificationOverflow,
+
+    // APPENDLIMIT
+    TooBig,
+
+    // CATENATE
+    BadUrl {
+        url: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataCode {
+    LongEntries(u32),
+    MaxSize(u32),
+    TooMany,
+    NoPrivate,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -23,7 +23,7 @@
 
 use crate::{
     protocol::{
-        append::{self, Message},
+        append::{self, CatenatePart, Message},
         Flag, ProtocolVersion,
     },
     receiver::{Request, Token},
@@ -38,6 +38,8 @@ enum State {
     Flags,
     UTF8,
     UTF8Data,
+    Catenate,
+    CatenateData,
 }
 
 impl Request<Command> {
@@ -63,6 +65,7 @@ impl Request<Command> {
                         message: vec![],
                         flags: vec![],
                         received_at: None,
+                        catenate: vec![],
                     };
                     let mut state = State::None;
                     let mut seen_flags = false;
@@ -76,6 +79,7 @@ impl Request<Command> {
                                         State::Flags
                                     }
                                     State::UTF8 => State::UTF8Data,
+                                    State::Catenate => State::CatenateData,
                                     _ => {
                                         return Err((
                                             self.tag.as_str(),
@@ -86,7 +90,7 @@ impl Request<Command> {
                                 };
                             }
                             Token::ParenthesisClose => match state {
-                                State::None | State::UTF8 => {
+                                State::None | State::UTF8 | State::Catenate => {
                                     return Err((
                                         self.tag.as_str(),
                                         "Invalid closing parenthesis found.",
@@ -99,11 +103,24 @@ impl Request<Command> {
                                 State::UTF8Data => {
                                     break;
                                 }
+                                State::CatenateData => {
+                                    if !message.catenate.is_empty() {
+                                        break;
+                                    } else {
+                                        return Err((
+                                            self.tag.as_str(),
+                                            "CATENATE requires at least one part.",
+                                        )
+                                            .into());
+                                    }
+                                }
                             },
                             Token::Argument(value) => match state {
                                 State::None => {
                                     if value.eq_ignore_ascii_case(b"utf8") {
                                         state = State::UTF8;
+                                    } else if value.eq_ignore_ascii_case(b"catenate") {
+                                        state = State::Catenate;
                                     } else if matches!(tokens.peek(), Some(Token::Argument(_)))
                                         && value.len() <= 28
                                         && !value.contains(&b'\n')
@@ -135,6 +152,38 @@ impl Request<Command> {
                                     )
                                         .into());
                                 }
+                                State::Catenate => {
+                                    return Err((
+                                        self.tag.as_str(),
+                                        "Expected parenthesis after CATENATE.",
+                                    )
+                                        .into());
+                                }
+                                State::CatenateData => {
+                                    let part =
+                                        match tokens.next() {
+                                            Some(Token::Argument(part))
+                                                if value.eq_ignore_ascii_case(b"text") =>
+                                            {
+                                                CatenatePart::Text(part)
+                                            }
+                                            Some(Token::Argument(part))
+                                                if value.eq_ignore_ascii_case(b"url") =>
+                                            {
+                                                CatenatePart::Url(String::from_utf8(part).map_err(
+                                                    |_| (self.tag.as_str(), "Invalid URL."),
+                                                )?)
+                                            }
+                                            _ => {
+                                                return Err((
+                                                    self.tag.as_str(),
+                                                    "Invalid CATENATE part.",
+                                                )
+                                                    .into());
+                                            }
+                                        };
+                                    message.catenate.push(part);
+                                }
                                 State::UTF8Data => {
                                     if message.message.is_empty() {
                                         message.message = value;
@@ -169,7 +218,7 @@ mod tests {
 
     use crate::{
         protocol::{
-            append::{self, Message},
+            append::{self, CatenatePart, Message},
             Flag, ProtocolVersion,
         },
         receiver::{Error, Receiver},
@@ -189,6 +238,7 @@ mod tests {
                         message: vec![b'a'],
                         flags: vec![Flag::Seen],
                         received_at: None,
+                        catenate: vec![],
                     }],
                 },
             ),
@@ -201,6 +251,7 @@ mod tests {
                         message: vec![b'a'],
                         flags: vec![Flag::Seen, Flag::Draft, Flag::MDNSent],
                         received_at: None,
+                        catenate: vec![],
                     }],
                 },
             ),
@@ -213,6 +264,7 @@ mod tests {
                         message: vec![b'a'],
                         flags: vec![Flag::Junk],
                         received_at: Some(760689784),
+                        catenate: vec![],
                     }],
                 },
             ),
@@ -225,6 +277,7 @@ mod tests {
                         message: vec![b'a'],
                         flags: vec![],
                         received_at: Some(1668977999),
+                        catenate: vec![],
                     }],
                 },
             ),
@@ -237,6 +290,7 @@ mod tests {
                         message: vec![b'a'],
                         flags: vec![],
                         received_at: Some(1668977999),
+                        catenate: vec![],
                     }],
                 },
             ),
@@ -249,6 +303,7 @@ mod tests {
                         message: vec![b'h', b'e', b'l', b'l', b'o'],
                         flags: vec![Flag::Draft],
                         received_at: None,
+                        catenate: vec![],
                     }],
                 },
             ),
@@ -261,6 +316,7 @@ mod tests {
                         message: vec![b'h', b'e', b'l', b'l', b'o'],
                         flags: vec![Flag::Draft],
                         received_at: Some(1668977999),
+                        catenate: vec![],
                     }],
                 },
             ),
@@ -277,6 +333,47 @@ mod tests {
             );
         }
 
+        // Catenate
+        for line in [
+            "A003 APPEND Drafts (\\Seen \\Draft) CATENATE (URL \"/Drafts;UIDVALIDITY=385759045/;UID=20/;SECTION=HEADER\" TEXT {42}\r\n",
+            "\r\n--------------030308070208000400050907\r\n",
+            " URL \"/Drafts;UIDVALIDITY=385759045/;UID=20/;SECTION=1.MIME\")\r\n",
+        ] {
+            match receiver.parse(&mut line.as_bytes().iter()) {
+                Ok(request) => {
+                    assert_eq!(
+                        request.parse_append(ProtocolVersion::Rev1).unwrap(),
+                        append::Arguments {
+                            tag: "A003".to_string(),
+                            mailbox_name: "Drafts".to_string(),
+                            messages: vec![Message {
+                                message: vec![],
+                                flags: vec![Flag::Seen, Flag::Draft],
+                                received_at: None,
+                                catenate: vec![
+                                    CatenatePart::Url(
+                                        "/Drafts;UIDVALIDITY=385759045/;UID=20/;SECTION=HEADER"
+                                            .to_string()
+                                    ),
+                                    CatenatePart::Text(
+                                        b"\r\n--------------030308070208000400050907\r\n".to_vec()
+                                    ),
+                                    CatenatePart::Url(
+                                        "/Drafts;UIDVALIDITY=385759045/;UID=20/;SECTION=1.MIME"
+                                            .to_string()
+                                    ),
+                                ],
+                            }],
+                        },
+                    );
+                }
+                Err(err) => match err {
+                    Error::NeedsMoreData | Error::NeedsLiteral { .. } => (),
+                    Error::Error { response } => panic!("{:?}", response),
+                },
+            }
+        }
+
         // Multiappend
         for line in [
             "A003 APPEND saved-messages (\\Seen) UTF8 ({329}\r\n",
@@ -323,6 +420,7 @@ mod tests {
                                     .to_vec(),
                                     flags: vec![Flag::Seen],
                                     received_at: None,
+                                    catenate: vec![],
                                 },
                                 Message {
                                     message: concat!(
@@ -339,6 +437,7 @@ mod tests {
                                     .to_vec(),
                                     flags: vec![Flag::Seen],
                                     received_at: Some(760689784),
+                                    catenate: vec![],
                                 }
                             ],
                         },
@@ -31,7 +31,7 @@ use crate::{
     Command,
 };
 
-use super::{parse_number, parse_sequence_set, PushUnique};
+use super::{parse_number, parse_partial_range, parse_sequence_set, PushUnique};
 
 impl Request<Command> {
     #[allow(clippy::while_let_on_iterator)]
@@ -336,6 +336,8 @@ impl Request<Command> {
                         attributes.push_unique(Attribute::EmailId);
                     } else if value.eq_ignore_ascii_case(b"THREADID") {
                         attributes.push_unique(Attribute::ThreadId);
+                    } else if value.eq_ignore_ascii_case(b"SAVEDATE") {
+                        attributes.push_unique(Attribute::SaveDate);
                     } else {
                         return Err((
                             self.tag,
@@ -372,9 +374,10 @@ impl Request<Command> {
             }
         }
 
-        // CONDSTORE parameters
+        // CONDSTORE and PARTIAL parameters
         let mut changed_since = None;
         let mut include_vanished = false;
+        let mut partial = None;
         if let Some(Token::ParenthesisOpen) = tokens.peek() {
             tokens.next();
             while let Some(token) = tokens.next() {
@@ -392,6 +395,16 @@ impl Request<Command> {
                     Token::Argument(param) if param.eq_ignore_ascii_case(b"VANISHED") => {
                         include_vanished = true;
                     }
+                    Token::Argument(param) if param.eq_ignore_ascii_case(b"PARTIAL") => {
+                        partial = parse_partial_range(
+                            &tokens
+                                .next()
+                                .ok_or((self.tag.as_str(), "Missing PARTIAL parameter."))?
+                                .unwrap_bytes(),
+                        )
+                        .map_err(|v| (self.tag.as_str(), v))?
+                        .into();
+                    }
                     Token::ParenthesisClose => {
                         break;
                     }
@@ -413,6 +426,7 @@ impl Request<Command> {
                 attributes,
                 changed_since,
                 include_vanished,
+                partial,
             })
         } else {
             Err((self.tag, "No data items to fetch specified.").into())
@@ -502,7 +516,7 @@ mod tests {
     use crate::{
         protocol::{
             fetch::{self, Attribute, Section},
-            Sequence,
+            PartialRange, Sequence,
         },
         receiver::Receiver,
     };
@@ -530,6 +544,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -544,6 +559,7 @@ mod tests {
                     }],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -558,6 +574,7 @@ mod tests {
                     }],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -578,6 +595,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -599,6 +617,7 @@ mod tests {
                     }],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -621,6 +640,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -637,6 +657,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -652,6 +673,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -725,6 +747,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -740,6 +763,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -756,6 +780,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -770,6 +795,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -780,6 +806,7 @@ mod tests {
                     attributes: vec![Attribute::Flags, Attribute::ModSeq],
                     changed_since: 12345.into(),
                     include_vanished: true,
+                    partial: None,
                 },
             ),
             (
@@ -790,6 +817,18 @@ mod tests {
                     attributes: vec![Attribute::Uid],
                     changed_since: 1.into(),
                     include_vanished: true,
+                    partial: None,
+                },
+            ),
+            (
+                "10 UID FETCH 1:* (UID SAVEDATE) (PARTIAL -1:-30)\r\n",
+                fetch::Arguments {
+                    tag: "10".to_string(),
+                    sequence_set: Sequence::range(1.into(), None),
+                    attributes: vec![Attribute::Uid, Attribute::SaveDate],
+                    changed_since: None,
+                    include_vanished: false,
+                    partial: PartialRange::Last { start: 1, end: 30 }.into(),
                 },
             ),
         ] {
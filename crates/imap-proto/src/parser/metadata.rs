@@ -0,0 +1,329 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::{
+    protocol::{
+        metadata::{self, Depth, Entry},
+        ProtocolVersion,
+    },
+    receiver::{Request, Token},
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+use super::parse_number;
+
+/*
+
+   getmetadata     = "GETMETADATA" [SP getmetadata-options]
+                     SP mailbox SP entries
+
+   getmetadata-options = "(" getmetadata-option
+                         *(SP getmetadata-option) ")"
+
+   getmetadata-option  = maxsize-opt / scope-opt
+
+   maxsize-opt     = "MAXSIZE" SP number
+
+   scope-opt       = "DEPTH" SP ("0" / "1" / "infinity")
+
+   entries         = entry / "(" entry *(SP entry) ")"
+
+   setmetadata     = "SETMETADATA" SP mailbox SP entry-values
+
+   entry-values    = "(" entry-value *(SP entry-value) ")"
+
+   entry-value     = entry SP value
+
+   value           = nstring / literal8
+
+*/
+
+impl Request<Command> {
+    pub fn parse_metadata(self, version: ProtocolVersion) -> crate::Result<metadata::Arguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+        let mut max_size = None;
+        let mut depth = Depth::Zero;
+
+        // Parse options
+        if self.command == Command::GetMetadata
+            && tokens
+                .peek()
+                .is_some_and(|token| token.is_parenthesis_open())
+        {
+            tokens.next();
+            loop {
+                match tokens.next() {
+                    Some(Token::ParenthesisClose) => break,
+                    Some(Token::Argument(option)) if option.eq_ignore_ascii_case(b"maxsize") => {
+                        max_size = parse_number::<u32>(
+                            &tokens
+                                .next()
+                                .ok_or((self.tag.as_str(), "Missing MAXSIZE value."))?
+                                .unwrap_bytes(),
+                        )
+                        .map_err(|v| (self.tag.as_str(), v))?
+                        .into();
+                    }
+                    Some(Token::Argument(option)) if option.eq_ignore_ascii_case(b"depth") => {
+                        let value = tokens
+                            .next()
+                            .ok_or((self.tag.as_str(), "Missing DEPTH value."))?
+                            .unwrap_bytes();
+                        depth = if value.eq(b"0") {
+                            Depth::Zero
+                        } else if value.eq(b"1") {
+                            Depth::One
+                        } else if value.eq_ignore_ascii_case(b"infinity") {
+                            Depth::Infinity
+                        } else {
+                            return Err((self.tag.as_str(), "Invalid DEPTH value.").into());
+                        };
+                    }
+                    _ => {
+                        return Err((self.tag.as_str(), "Invalid GETMETADATA option.").into());
+                    }
+                }
+            }
+        }
+
+        // Parse mailbox name
+        let mailbox_name = utf7_maybe_decode(
+            tokens
+                .next()
+                .ok_or((self.tag.as_str(), "Missing mailbox name."))?
+                .unwrap_string()
+                .map_err(|v| (self.tag.as_str(), v))?,
+            version,
+        );
+
+        // Parse entries
+        let mut entries = Vec::new();
+        match self.command {
+            Command::GetMetadata => match tokens.next() {
+                Some(Token::ParenthesisOpen) => loop {
+                    match tokens.next() {
+                        Some(Token::ParenthesisClose) => break,
+                        Some(token) => {
+                            entries.push(Entry {
+                                name: parse_entry_name(token, false)
+                                    .map_err(|v| (self.tag.as_str(), v))?,
+                                value: None,
+                            });
+                        }
+                        None => {
+                            return Err((self.tag.as_str(), "Unterminated entry list.").into());
+                        }
+                    }
+                },
+                Some(token) => {
+                    entries.push(Entry {
+                        name: parse_entry_name(token, false).map_err(|v| (self.tag.as_str(), v))?,
+                        value: None,
+                    });
+                }
+                None => {
+                    return Err((self.tag.as_str(), "Missing entry names.").into());
+                }
+            },
+            Command::SetMetadata => {
+                if !tokens
+                    .next()
+                    .is_some_and(|token| token.is_parenthesis_open())
+                {
+                    return Err((self.tag.as_str(), "Expected entry list.").into());
+                }
+
+                loop {
+                    match tokens.next() {
+                        Some(Token::ParenthesisClose) => break,
+                        Some(token) => {
+                            let name = parse_entry_name(token, true)
+                                .map_err(|v| (self.tag.as_str(), v))?;
+                            let value = match tokens
+                                .next()
+                                .ok_or((self.tag.as_str(), "Missing entry value."))?
+                            {
+                                Token::Argument(value) if value.eq_ignore_ascii_case(b"nil") => {
+                                    None
+                                }
+                                Token::Argument(value) => Some(value),
+                                Token::Nil => Some(Vec::new()),
+                                _ => {
+                                    return Err((self.tag.as_str(), "Invalid entry value.").into());
+                                }
+                            };
+                            entries.push(Entry { name, value });
+                        }
+                        None => {
+                            return Err((self.tag.as_str(), "Unterminated entry list.").into());
+                        }
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        if !entries.is_empty() {
+            Ok(metadata::Arguments {
+                tag: self.tag,
+                mailbox_name,
+                entries,
+                max_size,
+                depth,
+            })
+        } else {
+            Err((self.tag.as_str(), "At least one entry must be specified.").into())
+        }
+    }
+}
+
+fn parse_entry_name(token: Token, is_set: bool) -> super::Result<String> {
+    let name = token.unwrap_string()?.to_ascii_lowercase();
+    let mut parts = name.split('/');
+
+    if parts.next() != Some("")
+        || !matches!(parts.next(), Some("private" | "shared"))
+        || (is_set && parts.next().is_none())
+        || name.ends_with('/')
+        || name.contains("//")
+        || name
+            .chars()
+            .any(|ch| ch.is_ascii_control() || ['*', '%'].contains(&ch))
+    {
+        Err(format!("Invalid entry name '{name}'.").into())
+    } else {
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            metadata::{self, Depth, Entry},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_metadata() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "a GETMETADATA \"\" /private/vendor/vendor.dovecot/webmail-settings\r\n",
+                metadata::Arguments {
+                    tag: "a".to_string(),
+                    mailbox_name: "".to_string(),
+                    entries: vec![Entry {
+                        name: "/private/vendor/vendor.dovecot/webmail-settings".to_string(),
+                        value: None,
+                    }],
+                    max_size: None,
+                    depth: Depth::Zero,
+                },
+            ),
+            (
+                "a GETMETADATA (MAXSIZE 1024 DEPTH infinity) INBOX (/Shared/Comment /private)\r\n",
+                metadata::Arguments {
+                    tag: "a".to_string(),
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![
+                        Entry {
+                            name: "/shared/comment".to_string(),
+                            value: None,
+                        },
+                        Entry {
+                            name: "/private".to_string(),
+                            value: None,
+                        },
+                    ],
+                    max_size: Some(1024),
+                    depth: Depth::Infinity,
+                },
+            ),
+            (
+                "a SETMETADATA INBOX (/private/comment \"My comment\" /shared/comment NIL)\r\n",
+                metadata::Arguments {
+                    tag: "a".to_string(),
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![
+                        Entry {
+                            name: "/private/comment".to_string(),
+                            value: Some(b"My comment".to_vec()),
+                        },
+                        Entry {
+                            name: "/shared/comment".to_string(),
+                            value: None,
+                        },
+                    ],
+                    max_size: None,
+                    depth: Depth::Zero,
+                },
+            ),
+            (
+                "a SETMETADATA \"\" (/shared/admin {14+}\r\nmailto:a@b.com)\r\n",
+                metadata::Arguments {
+                    tag: "a".to_string(),
+                    mailbox_name: "".to_string(),
+                    entries: vec![Entry {
+                        name: "/shared/admin".to_string(),
+                        value: Some(b"mailto:a@b.com".to_vec()),
+                    }],
+                    max_size: None,
+                    depth: Depth::Zero,
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_metadata(ProtocolVersion::Rev2)
+                    .unwrap(),
+                arguments,
+                "{:?}",
+                command
+            );
+        }
+
+        for command in [
+            "a SETMETADATA INBOX (/private \"value\")\r\n",
+            "a SETMETADATA INBOX (/other/comment \"value\")\r\n",
+            "a GETMETADATA INBOX (/shared/*)\r\n",
+            "a GETMETADATA INBOX\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_metadata(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{:?}",
+                command
+            );
+        }
+    }
+}
@@ -24,6 +24,7 @@
 pub mod acl;
 pub mod append;
 pub mod authenticate;
+pub mod compress;
 pub mod copy_move;
 pub mod create;
 pub mod delete;
@@ -32,7 +33,11 @@ pub mod fetch;
 pub mod list;
 pub mod login;
 pub mod lsub;
+pub mod metadata;
+pub mod notify;
+pub mod quota;
 pub mod rename;
+pub mod replace;
 pub mod search;
 pub mod select;
 pub mod sort;
@@ -40,13 +45,14 @@ pub mod status;
 pub mod store;
 pub mod subscribe;
 pub mod thread;
+pub mod urlauth;
 
 use std::{borrow::Cow, str::FromStr};
 
 use chrono::{DateTime, NaiveDate};
 
 use crate::{
-    protocol::{Flag, Sequence},
+    protocol::{Flag, PartialRange, Sequence},
     receiver::CommandParser,
     Command,
 };
@@ -94,6 +100,17 @@ impl CommandParser for Command {
             b"MYRIGHTS" => Some(Command::MyRights),
             b"UNAUTHENTICATE" => Some(Command::Unauthenticate),
             b"ID" => Some(Command::Id),
+            b"GETQUOTA" => Some(Command::GetQuota),
+            b"GETQUOTAROOT" => Some(Command::GetQuotaRoot),
+            b"SETQUOTA" => Some(Command::SetQuota),
+            b"GETMETADATA" => Some(Command::GetMetadata),
+            b"SETMETADATA" => Some(Command::SetMetadata),
+            b"COMPRESS" => Some(Command::Compress),
+            b"NOTIFY" => Some(Command::Notify),
+            b"REPLACE" => Some(Command::Replace(uid)),
+            b"GENURLAUTH" => Some(Command::GenUrlAuth),
+            b"RESETKEY" => Some(Command::ResetKey),
+            b"URLFETCH" => Some(Command::UrlFetch),
             _ => None,
         }
     }
@@ -220,6 +237,35 @@ pub fn parse_number<T: FromStr>(value: &[u8]) -> Result<T> {
         .map_err(|_| Cow::from(format!("Expected a number, found {:?}.", string)))
 }
 
+pub fn parse_partial_range(value: &[u8]) -> Result<PartialRange> {
+    let err = || {
+        Cow::from(format!(
+            "Invalid partial range {:?}.",
+            String::from_utf8_lossy(value)
+        ))
+    };
+    let (start, end) = std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.split_once(':'))
+        .ok_or_else(err)?;
+    let range = match (start.strip_prefix('-'), end.strip_prefix('-')) {
+        (Some(start), Some(end)) => (start.parse::<u32>(), end.parse::<u32>(), true),
+        (None, None) => (start.parse::<u32>(), end.parse::<u32>(), false),
+        _ => return Err(err()),
+    };
+    match range {
+        (Ok(start), Ok(end), is_last) if start > 0 && end > 0 => {
+            let (start, end) = (start.min(end), start.max(end));
+            Ok(if is_last {
+                PartialRange::Last { start, end }
+            } else {
+                PartialRange::First { start, end }
+            })
+        }
+        _ => Err(err()),
+    }
+}
+
 pub fn parse_sequence_set(value: &[u8]) -> Result<Sequence> {
     let mut sequence_set = Vec::new();
 
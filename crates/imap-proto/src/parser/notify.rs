@@ -0,0 +1,394 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{borrow::Cow, iter::Peekable, vec::IntoIter};
+
+use crate::{
+    protocol::{
+        notify::{self, Event, EventGroup, Filter},
+        ProtocolVersion,
+    },
+    receiver::{Receiver, Request, Token},
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+/*
+
+   notify          = "NOTIFY" SP
+                     (notify-set / notify-none)
+
+   notify-set      = "SET" [status-indicator] SP event-groups
+
+   status-indicator = SP "STATUS"
+
+   notify-none     = "NONE"
+
+   event-group     = "(" filter-mailboxes SP events ")"
+
+   filter-mailboxes = filter-mailboxes-selected /
+                      filter-mailboxes-other
+
+   filter-mailboxes-selected = "selected" / "selected-delayed"
+
+   filter-mailboxes-other = "inboxes" / "personal" / "subscribed" /
+                            ( "subtree" SP one-or-more-mailbox ) /
+                            ( "mailboxes" SP one-or-more-mailbox )
+
+   events          = ( "(" event *(SP event) ")" ) / "NONE"
+
+   message-event   = ( "MessageNew" [SP
+                           "(" fetch-att *(SP fetch-att) ")" ] )
+                         / "MessageExpunge" / "FlagChange"
+                         / "AnnotationChange"
+
+*/
+
+impl Request<Command> {
+    pub fn parse_notify(self, version: ProtocolVersion) -> crate::Result<notify::Arguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+        let mut status = false;
+        let mut groups = Vec::new();
+
+        match tokens.next() {
+            Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"NONE") => {}
+            Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"SET") => {
+                if tokens
+                    .peek()
+                    .is_some_and(|token| token.eq_ignore_ascii_case(b"STATUS"))
+                {
+                    tokens.next();
+                    status = true;
+                }
+
+                while let Some(token) = tokens.next() {
+                    if !token.is_parenthesis_open() {
+                        return Err((self.tag.as_str(), "Expected event group.").into());
+                    }
+                    let filter =
+                        parse_filter(&mut tokens, version).map_err(|v| (self.tag.as_str(), v))?;
+                    let events = parse_events(&mut tokens).map_err(|v| (self.tag.as_str(), v))?;
+                    if !tokens
+                        .next()
+                        .is_some_and(|token| token.is_parenthesis_close())
+                    {
+                        return Err((self.tag.as_str(), "Expected ')' after events.").into());
+                    }
+
+                    // Validate event combinations
+                    let has_new = events.iter().any(|e| matches!(e, Event::MessageNew { .. }));
+                    let has_expunge = events.contains(&Event::MessageExpunge);
+                    if has_new != has_expunge
+                        || (!has_new
+                            && events.iter().any(|e| {
+                                matches!(e, Event::FlagChange | Event::AnnotationChange)
+                            }))
+                    {
+                        return Err((
+                            self.tag.as_str(),
+                            concat!(
+                                "MessageNew and MessageExpunge must be specified together ",
+                                "and are required by FlagChange and AnnotationChange."
+                            ),
+                        )
+                            .into());
+                    } else if !filter.is_selected()
+                        && events.iter().any(|e| {
+                            matches!(e, Event::MessageNew { attributes } if !attributes.is_empty())
+                        })
+                    {
+                        return Err((
+                            self.tag.as_str(),
+                            "MessageNew fetch attributes are only allowed for the selected mailbox.",
+                        )
+                            .into());
+                    }
+
+                    groups.push(EventGroup { filter, events });
+                }
+
+                if groups.is_empty() {
+                    return Err((self.tag.as_str(), "Missing event groups.").into());
+                }
+            }
+            _ => {
+                return Err((self.tag.as_str(), "Expected SET or NONE.").into());
+            }
+        }
+
+        Ok(notify::Arguments {
+            tag: self.tag,
+            status,
+            groups,
+        })
+    }
+}
+
+fn parse_filter(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    version: ProtocolVersion,
+) -> super::Result<Filter> {
+    let filter = tokens
+        .next()
+        .ok_or("Missing mailbox filter.")?
+        .unwrap_bytes();
+    if filter.eq_ignore_ascii_case(b"selected") {
+        Ok(Filter::Selected)
+    } else if filter.eq_ignore_ascii_case(b"selected-delayed") {
+        Ok(Filter::SelectedDelayed)
+    } else if filter.eq_ignore_ascii_case(b"inboxes") {
+        Ok(Filter::Inboxes)
+    } else if filter.eq_ignore_ascii_case(b"personal") {
+        Ok(Filter::Personal)
+    } else if filter.eq_ignore_ascii_case(b"subscribed") {
+        Ok(Filter::Subscribed)
+    } else if filter.eq_ignore_ascii_case(b"subtree") || filter.eq_ignore_ascii_case(b"mailboxes") {
+        let mut mailboxes = Vec::new();
+        match tokens.next().ok_or("Missing mailbox names.")? {
+            Token::ParenthesisOpen => loop {
+                match tokens.next().ok_or("Unterminated mailbox list.")? {
+                    Token::ParenthesisClose => break,
+                    token => {
+                        mailboxes.push(utf7_maybe_decode(token.unwrap_string()?, version));
+                    }
+                }
+            },
+            token => {
+                mailboxes.push(utf7_maybe_decode(token.unwrap_string()?, version));
+            }
+        }
+        if mailboxes.is_empty() {
+            Err("Missing mailbox names.".into())
+        } else if filter.eq_ignore_ascii_case(b"subtree") {
+            Ok(Filter::Subtree(mailboxes))
+        } else {
+            Ok(Filter::Mailboxes(mailboxes))
+        }
+    } else {
+        Err(format!(
+            "Invalid mailbox filter '{}'.",
+            String::from_utf8_lossy(&filter)
+        )
+        .into())
+    }
+}
+
+fn parse_events(tokens: &mut Peekable<IntoIter<Token>>) -> super::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    match tokens.next().ok_or("Missing events.")? {
+        Token::ParenthesisOpen => loop {
+            let event = match tokens.next().ok_or("Unterminated event list.")? {
+                Token::ParenthesisClose => break,
+                token => token.unwrap_bytes(),
+            };
+            events.push(if event.eq_ignore_ascii_case(b"MessageNew") {
+                let mut attributes = Vec::new();
+                if tokens
+                    .peek()
+                    .is_some_and(|token| token.is_parenthesis_open())
+                {
+                    // Re-tokenize the attribute list using the FETCH rules
+                    let mut buf = b"N FETCH 1 ".to_vec();
+                    let mut depth = 0;
+                    for token in tokens.by_ref() {
+                        match token {
+                            Token::ParenthesisOpen => depth += 1,
+                            Token::ParenthesisClose => depth -= 1,
+                            _ => (),
+                        }
+                        match token {
+                            Token::Argument(value)
+                                if value.iter().any(|ch| {
+                                    ch.is_ascii_whitespace() || matches!(ch, b'"' | b'\\')
+                                }) =>
+                            {
+                                buf.push(b'"');
+                                for ch in value {
+                                    if matches!(ch, b'"' | b'\\') {
+                                        buf.push(b'\\');
+                                    }
+                                    buf.push(ch);
+                                }
+                                buf.push(b'"');
+                            }
+                            token => buf.extend_from_slice(token.to_string().as_bytes()),
+                        }
+                        buf.push(b' ');
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    buf.extend_from_slice(b"\r\n");
+                    attributes = Receiver::new()
+                        .parse(&mut buf.iter())
+                        .map_err(|_| Cow::from("Invalid MessageNew fetch attributes."))?
+                        .parse_fetch()
+                        .map_err(|err| err.message)?
+                        .attributes;
+                }
+                Event::MessageNew { attributes }
+            } else if event.eq_ignore_ascii_case(b"MessageExpunge") {
+                Event::MessageExpunge
+            } else if event.eq_ignore_ascii_case(b"FlagChange") {
+                Event::FlagChange
+            } else if event.eq_ignore_ascii_case(b"AnnotationChange") {
+                Event::AnnotationChange
+            } else if event.eq_ignore_ascii_case(b"MailboxName") {
+                Event::MailboxName
+            } else if event.eq_ignore_ascii_case(b"SubscriptionChange") {
+                Event::SubscriptionChange
+            } else if event.eq_ignore_ascii_case(b"MailboxMetadataChange") {
+                Event::MailboxMetadataChange
+            } else if event.eq_ignore_ascii_case(b"ServerMetadataChange") {
+                Event::ServerMetadataChange
+            } else {
+                return Err(format!("Invalid event '{}'.", String::from_utf8_lossy(&event)).into());
+            });
+        },
+        Token::Argument(value) if value.eq_ignore_ascii_case(b"NONE") => (),
+        _ => return Err("Invalid event list.".into()),
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            fetch::{self, Section},
+            notify::{self, Event, EventGroup, Filter},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_notify() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A01 NOTIFY NONE\r\n",
+                notify::Arguments {
+                    tag: "A01".to_string(),
+                    status: false,
+                    groups: vec![],
+                },
+            ),
+            (
+                concat!(
+                    "A02 NOTIFY SET STATUS (selected (MessageNew (uid ",
+                    "body.peek[header.fields (from to subject)]) MessageExpunge)) ",
+                    "(subtree Lists (MessageNew MessageExpunge FlagChange)) ",
+                    "(mailboxes (INBOX Sent) (MessageNew MessageExpunge)) ",
+                    "(personal (MailboxName SubscriptionChange)) ",
+                    "(subscribed NONE)\r\n"
+                ),
+                notify::Arguments {
+                    tag: "A02".to_string(),
+                    status: true,
+                    groups: vec![
+                        EventGroup {
+                            filter: Filter::Selected,
+                            events: vec![
+                                Event::MessageNew {
+                                    attributes: vec![
+                                        fetch::Attribute::Uid,
+                                        fetch::Attribute::BodySection {
+                                            peek: true,
+                                            sections: vec![Section::HeaderFields {
+                                                not: false,
+                                                fields: vec![
+                                                    "from".to_string(),
+                                                    "to".to_string(),
+                                                    "subject".to_string(),
+                                                ],
+                                            }],
+                                            partial: None,
+                                        },
+                                    ],
+                                },
+                                Event::MessageExpunge,
+                            ],
+                        },
+                        EventGroup {
+                            filter: Filter::Subtree(vec!["Lists".to_string()]),
+                            events: vec![
+                                Event::MessageNew { attributes: vec![] },
+                                Event::MessageExpunge,
+                                Event::FlagChange,
+                            ],
+                        },
+                        EventGroup {
+                            filter: Filter::Mailboxes(vec![
+                                "INBOX".to_string(),
+                                "Sent".to_string(),
+                            ]),
+                            events: vec![
+                                Event::MessageNew { attributes: vec![] },
+                                Event::MessageExpunge,
+                            ],
+                        },
+                        EventGroup {
+                            filter: Filter::Personal,
+                            events: vec![Event::MailboxName, Event::SubscriptionChange],
+                        },
+                        EventGroup {
+                            filter: Filter::Subscribed,
+                            events: vec![],
+                        },
+                    ],
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_notify(ProtocolVersion::Rev2)
+                    .unwrap(),
+                arguments,
+                "{:?}",
+                command
+            );
+        }
+
+        for command in [
+            "A03 NOTIFY SET (personal (MessageNew))\r\n",
+            "A04 NOTIFY SET (personal (FlagChange))\r\n",
+            "A05 NOTIFY SET (personal (MessageNew (uid) MessageExpunge))\r\n",
+            "A06 NOTIFY SET (unknown (MailboxName))\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_notify(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{:?}",
+                command
+            );
+        }
+    }
+}
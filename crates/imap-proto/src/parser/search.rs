@@ -34,7 +34,7 @@ use crate::protocol::{Flag, ProtocolVersion};
 use crate::receiver::{Request, Token};
 use crate::Command;
 
-use super::{parse_date, parse_number, parse_sequence_set};
+use super::{parse_date, parse_number, parse_partial_range, parse_sequence_set};
 
 impl Request<Command> {
     #[allow(clippy::while_let_on_iterator)]
@@ -95,9 +95,17 @@ pub fn parse_result_options(
         return Err(Cow::from("Invalid result option, expected parenthesis."));
     }
 
-    for token in tokens {
+    while let Some(token) = tokens.next() {
         match token {
             Token::ParenthesisClose => break,
+            Token::Argument(value) if value.eq_ignore_ascii_case(b"partial") => {
+                result_options.push(ResultOption::Partial(parse_partial_range(
+                    &tokens
+                        .next()
+                        .ok_or_else(|| Cow::from("Expected partial range."))?
+                        .unwrap_bytes(),
+                )?));
+            }
             Token::Argument(value) => {
                 result_options.push(ResultOption::parse(&value)?);
             }
@@ -105,6 +113,14 @@ pub fn parse_result_options(
         }
     }
 
+    if result_options.contains(&ResultOption::All)
+        && result_options
+            .iter()
+            .any(|option| matches!(option, ResultOption::Partial(_)))
+    {
+        return Err(Cow::from("PARTIAL and ALL are mutually exclusive."));
+    }
+
     Ok(result_options)
 }
 
@@ -256,6 +272,29 @@ pub fn parse_filters(
                             .ok_or_else(|| Cow::from("Expected integer"))?
                             .unwrap_bytes(),
                     )?));
+                } else if value.eq_ignore_ascii_case(b"SAVEDBEFORE") {
+                    filters.push(Filter::SavedBefore(parse_date(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected date"))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"SAVEDON") {
+                    filters.push(Filter::SavedOn(parse_date(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected date"))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"SAVEDSINCE") {
+                    filters.push(Filter::SavedSince(parse_date(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected date"))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"SAVEDATESUPPORTED") {
+                    filters.push(Filter::SaveDateSupported);
                 } else if value.eq_ignore_ascii_case(b"OLD") {
                     filters.push(Filter::Old);
                 } else if value.eq_ignore_ascii_case(b"NEW") {
@@ -446,7 +485,7 @@ mod tests {
     use crate::{
         protocol::{
             search::{self, Filter, ModSeqEntry, ResultOption},
-            Flag, ProtocolVersion, Sequence,
+            Flag, PartialRange, ProtocolVersion, Sequence,
         },
         receiver::Receiver,
     };
@@ -525,6 +564,20 @@ mod tests {
                     sort: None,
                 },
             ),
+            (
+                b"F283 SEARCH RETURN (COUNT PARTIAL -1:-100) SAVEDSINCE 1-Feb-1994 SAVEDATESUPPORTED\r\n"
+                    .to_vec(),
+                search::Arguments {
+                    tag: "F283".to_string(),
+                    result_options: vec![
+                        ResultOption::Count,
+                        ResultOption::Partial(PartialRange::Last { start: 1, end: 100 }),
+                    ],
+                    filter: vec![Filter::SavedSince(760060800), Filter::SaveDateSupported],
+                    is_esearch: true,
+                    sort: None,
+                },
+            ),
             (
                 b"F282 SEARCH RETURN (SAVE) KEYWORD $Junk\r\n".to_vec(),
                 search::Arguments {
@@ -97,6 +97,8 @@ impl Status {
             Ok(Self::Unseen)
         } else if value.eq_ignore_ascii_case(b"deleted") {
             Ok(Self::Deleted)
+        } else if value.eq_ignore_ascii_case(b"deleted-storage") {
+            Ok(Self::DeletedStorage)
         } else if value.eq_ignore_ascii_case(b"size") {
             Ok(Self::Size)
         } else if value.eq_ignore_ascii_case(b"highestmodseq") {
@@ -105,6 +107,8 @@ impl Status {
             Ok(Self::MailboxId)
         } else if value.eq_ignore_ascii_case(b"recent") {
             Ok(Self::Recent)
+        } else if value.eq_ignore_ascii_case(b"appendlimit") {
+            Ok(Self::AppendLimit)
         } else {
             Err(format!(
                 "Invalid status option '{}'.",
@@ -0,0 +1,199 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::{
+    protocol::{
+        urlauth::{self, Mechanism},
+        ProtocolVersion,
+    },
+    receiver::Request,
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+/*
+
+   genurlauth      = "GENURLAUTH" 1*(SP url-rump SP mechanism)
+
+   resetkey        = "RESETKEY" [SP mailbox *(SP mechanism)]
+
+   urlfetch        = "URLFETCH" 1*(SP url-full)
+
+*/
+
+impl Request<Command> {
+    pub fn parse_urlauth(self, version: ProtocolVersion) -> crate::Result<urlauth::Arguments> {
+        let mut tokens = self.tokens.into_iter();
+        let mut mailbox_name = None;
+        let mut urls = Vec::new();
+        let mut mechanisms = Vec::new();
+
+        match self.command {
+            Command::GenUrlAuth => {
+                while let Some(url) = tokens.next() {
+                    urls.push(url.unwrap_string().map_err(|v| (self.tag.as_str(), v))?);
+                    mechanisms.push(
+                        Mechanism::parse(
+                            &tokens
+                                .next()
+                                .ok_or((self.tag.as_str(), "Missing URLAUTH mechanism."))?
+                                .unwrap_bytes(),
+                        )
+                        .map_err(|v| (self.tag.as_str(), v))?,
+                    );
+                }
+                if urls.is_empty() {
+                    return Err((self.tag.as_str(), "Missing URL.").into());
+                }
+            }
+            Command::ResetKey => {
+                if let Some(name) = tokens.next() {
+                    mailbox_name = Some(utf7_maybe_decode(
+                        name.unwrap_string().map_err(|v| (self.tag.as_str(), v))?,
+                        version,
+                    ));
+                    for mechanism in tokens {
+                        mechanisms.push(
+                            Mechanism::parse(&mechanism.unwrap_bytes())
+                                .map_err(|v| (self.tag.as_str(), v))?,
+                        );
+                    }
+                }
+            }
+            Command::UrlFetch => {
+                for url in tokens {
+                    urls.push(url.unwrap_string().map_err(|v| (self.tag.as_str(), v))?);
+                }
+                if urls.is_empty() {
+                    return Err((self.tag.as_str(), "Missing URL.").into());
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(urlauth::Arguments {
+            tag: self.tag,
+            mailbox_name,
+            urls,
+            mechanisms,
+        })
+    }
+}
+
+impl Mechanism {
+    pub fn parse(value: &[u8]) -> super::Result<Self> {
+        if value.eq_ignore_ascii_case(b"INTERNAL") {
+            Ok(Mechanism::Internal)
+        } else {
+            Err(format!(
+                "Unsupported URLAUTH mechanism '{}'.",
+                String::from_utf8_lossy(value)
+            )
+            .into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            urlauth::{self, Mechanism},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_urlauth() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A001 GENURLAUTH \"imap://joe@example.com/INBOX/;uid=20/;section=1.2;urlauth=submit+fred\" INTERNAL\r\n",
+                urlauth::Arguments {
+                    tag: "A001".to_string(),
+                    mailbox_name: None,
+                    urls: vec![
+                        "imap://joe@example.com/INBOX/;uid=20/;section=1.2;urlauth=submit+fred"
+                            .to_string(),
+                    ],
+                    mechanisms: vec![Mechanism::Internal],
+                },
+            ),
+            (
+                "A002 RESETKEY INBOX INTERNAL\r\n",
+                urlauth::Arguments {
+                    tag: "A002".to_string(),
+                    mailbox_name: Some("INBOX".to_string()),
+                    urls: vec![],
+                    mechanisms: vec![Mechanism::Internal],
+                },
+            ),
+            (
+                "A003 RESETKEY\r\n",
+                urlauth::Arguments {
+                    tag: "A003".to_string(),
+                    mailbox_name: None,
+                    urls: vec![],
+                    mechanisms: vec![],
+                },
+            ),
+            (
+                "A004 URLFETCH \"imap://joe@example.com/INBOX/;uid=20\" \"/INBOX/;uid=21\"\r\n",
+                urlauth::Arguments {
+                    tag: "A004".to_string(),
+                    mailbox_name: None,
+                    urls: vec![
+                        "imap://joe@example.com/INBOX/;uid=20".to_string(),
+                        "/INBOX/;uid=21".to_string(),
+                    ],
+                    mechanisms: vec![],
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_urlauth(ProtocolVersion::Rev2)
+                    .unwrap(),
+                arguments
+            );
+        }
+
+        for command in [
+            "A005 GENURLAUTH \"imap://joe@example.com/INBOX/;uid=20;urlauth=anonymous\" XSAMPLE\r\n",
+            "A006 URLFETCH\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_urlauth(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+}
@@ -35,4 +35,11 @@ pub struct Message {
     pub message: Vec<u8>,
     pub flags: Vec<Flag>,
     pub received_at: Option<i64>,
+    pub catenate: Vec<CatenatePart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatenatePart {
+    Text(Vec<u8>),
+    Url(String),
 }
@@ -64,6 +64,19 @@ pub enum Capability {
     ObjectId,
     Preview,
     Utf8Accept,
+    Quota,
+    QuotaResStorage, //QUOTA=RES-STORAGE
+    QuotaSet,
+    Metadata,
+    MetadataServer,  //METADATA-SERVER
+    CompressDeflate, //COMPRESS=DEFLATE
+    Notify,
+    Replace,
+    SaveDate,
+    AppendLimit(u32), //APPENDLIMIT=n
+    Partial,
+    Catenate,
+    UrlAuth,
     Auth(Mechanism),
 }
 
@@ -109,10 +122,32 @@ impl Capability {
             Capability::CreateSpecialUse => b"CREATE-SPECIAL-USE",
             Capability::Move => b"MOVE",
             Capability::Utf8Accept => b"UTF8=ACCEPT",
+            Capability::Quota => b"QUOTA",
+            Capability::QuotaResStorage => b"QUOTA=RES-STORAGE",
+            Capability::QuotaSet => b"QUOTASET",
+            Capability::Metadata => b"METADATA",
+            Capability::MetadataServer => b"METADATA-SERVER",
+            Capability::CompressDeflate => b"COMPRESS=DEFLATE",
+            Capability::Notify => b"NOTIFY",
+            Capability::Replace => b"REPLACE",
+            Capability::SaveDate => b"SAVEDATE",
+            Capability::AppendLimit(limit) => {
+                buf.extend_from_slice(b"APPENDLIMIT=");
+                buf.extend_from_slice(limit.to_string().as_bytes());
+                return;
+            }
+            Capability::Partial => b"PARTIAL",
+            Capability::Catenate => b"CATENATE",
+            Capability::UrlAuth => b"URLAUTH",
         });
     }
 
-    pub fn all_capabilities(is_authenticated: bool, is_tls: bool) -> Vec<Capability> {
+    pub fn all_capabilities(
+        is_authenticated: bool,
+        is_tls: bool,
+        can_compress: bool,
+        append_limit: u32,
+    ) -> Vec<Capability> {
         let mut capabilties = vec![
             Capability::IMAP4rev2,
             Capability::IMAP4rev1,
@@ -150,12 +185,35 @@ impl Capability {
                 Capability::StatusSize,
                 Capability::ObjectId,
                 Capability::Preview,
+                Capability::Quota,
+                Capability::QuotaResStorage,
+                Capability::QuotaSet,
+                Capability::Metadata,
+                Capability::MetadataServer,
+                Capability::Notify,
+                Capability::Replace,
+                Capability::SaveDate,
+                Capability::AppendLimit(append_limit),
+                Capability::Partial,
+                Capability::Catenate,
+                Capability::UrlAuth,
             ]);
+            if can_compress {
+                capabilties.push(Capability::CompressDeflate);
+            }
         } else {
             capabilties.extend([
                 Capability::Auth(Mechanism::OAuthBearer),
                 Capability::Auth(Mechanism::Plain),
+                Capability::Auth(Mechanism::ScramSha256),
+                Capability::Auth(Mechanism::ScramSha1),
             ]);
+            if is_tls {
+                capabilties.extend([
+                    Capability::Auth(Mechanism::ScramSha256Plus),
+                    Capability::Auth(Mechanism::ScramSha1Plus),
+                ]);
+            }
         }
         if !is_tls {
             capabilties.push(Capability::StartTLS);
@@ -0,0 +1,161 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::utf7::utf7_encode;
+
+use super::{literal_string, quoted_string};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    pub mailbox_name: String,
+    pub entries: Vec<Entry>,
+    pub max_size: Option<u32>,
+    pub depth: Depth,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub value: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Depth {
+    #[default]
+    Zero,
+    One,
+    Infinity,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataResponse {
+    pub mailbox_name: String,
+    pub entries: Vec<Entry>,
+}
+
+impl MetadataResponse {
+    pub fn into_bytes(self, is_rev2: bool) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(
+            self.mailbox_name.len()
+                + self
+                    .entries
+                    .iter()
+                    .map(|e| e.name.len() + e.value.as_ref().map_or(3, |v| v.len() + 8))
+                    .sum::<usize>()
+                + 16,
+        );
+        buf.extend_from_slice(b"* METADATA ");
+        if is_rev2 {
+            quoted_string(&mut buf, &self.mailbox_name);
+        } else {
+            quoted_string(&mut buf, &utf7_encode(&self.mailbox_name));
+        }
+        buf.extend_from_slice(b" (");
+        for (pos, entry) in self.entries.iter().enumerate() {
+            if pos > 0 {
+                buf.push(b' ');
+            }
+            quoted_string(&mut buf, &entry.name);
+            buf.push(b' ');
+            match &entry.value {
+                Some(value) if value.contains(&0) => {
+                    buf.push(b'~');
+                    literal_string(&mut buf, value);
+                }
+                Some(value)
+                    if value.len() > 1024
+                        || value.iter().any(|ch| {
+                            !ch.is_ascii() || [b'\\', b'"', b'\r', b'\n'].contains(ch)
+                        }) =>
+                {
+                    literal_string(&mut buf, value);
+                }
+                Some(value) => {
+                    buf.push(b'"');
+                    buf.extend_from_slice(value);
+                    buf.push(b'"');
+                }
+                None => {
+                    buf.extend_from_slice(b"NIL");
+                }
+            }
+        }
+        buf.extend_from_slice(b")\r\n");
+        buf
+    }
+}
+
+impl Entry {
+    pub fn is_private(&self) -> bool {
+        self.name.starts_with("/private")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::metadata::{Entry, MetadataResponse};
+
+    #[test]
+    fn serialize_metadata() {
+        for (response, expected) in [
+            (
+                MetadataResponse {
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![
+                        Entry {
+                            name: "/shared/comment".to_string(),
+                            value: Some(b"My comment".to_vec()),
+                        },
+                        Entry {
+                            name: "/private/comment".to_string(),
+                            value: None,
+                        },
+                    ],
+                },
+                "* METADATA \"INBOX\" (\"/shared/comment\" \"My comment\" \"/private/comment\" NIL)\r\n",
+            ),
+            (
+                MetadataResponse {
+                    mailbox_name: "".to_string(),
+                    entries: vec![
+                        Entry {
+                            name: "/shared/admin".to_string(),
+                            value: Some(b"line 1\r\nline 2".to_vec()),
+                        },
+                        Entry {
+                            name: "/private/vendor/x-bin".to_string(),
+                            value: Some(b"a\0b".to_vec()),
+                        },
+                    ],
+                },
+                concat!(
+                    "* METADATA \"\" (\"/shared/admin\" {14}\r\nline 1\r\nline 2 ",
+                    "\"/private/vendor/x-bin\" ~{3}\r\na\0b)\r\n"
+                ),
+            ),
+        ] {
+            assert_eq!(String::from_utf8(response.into_bytes(true)).unwrap(), expected);
+        }
+    }
+}
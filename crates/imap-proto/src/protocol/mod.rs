@@ -27,12 +27,13 @@ use ahash::AHashSet;
 use chrono::{DateTime, Utc};
 use jmap_proto::types::keyword::Keyword;
 
-use crate::{Command, ResponseCode, ResponseType, StatusResponse};
+use crate::{Command, MetadataCode, ResponseCode, ResponseType, StatusResponse};
 
 pub mod acl;
 pub mod append;
 pub mod authenticate;
 pub mod capability;
+pub mod compress;
 pub mod copy_move;
 pub mod create;
 pub mod delete;
@@ -41,14 +42,19 @@ pub mod expunge;
 pub mod fetch;
 pub mod list;
 pub mod login;
+pub mod metadata;
 pub mod namespace;
+pub mod notify;
+pub mod quota;
 pub mod rename;
+pub mod replace;
 pub mod search;
 pub mod select;
 pub mod status;
 pub mod store;
 pub mod subscribe;
 pub mod thread;
+pub mod urlauth;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProtocolVersion {
@@ -164,6 +170,37 @@ impl Sequence {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartialRange {
+    First { start: u32, end: u32 },
+    Last { start: u32, end: u32 },
+}
+
+impl PartialRange {
+    pub fn apply<T>(&self, items: Vec<T>) -> Vec<T> {
+        let (start, end) = match *self {
+            PartialRange::First { start, end } => ((start - 1) as usize, end as usize),
+            PartialRange::Last { start, end } => (
+                items.len().saturating_sub(end as usize),
+                items.len().saturating_sub((start - 1) as usize),
+            ),
+        };
+        items
+            .into_iter()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect()
+    }
+
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        let (prefix, start, end) = match self {
+            PartialRange::First { start, end } => ("", start, end),
+            PartialRange::Last { start, end } => ("-", start, end),
+        };
+        buf.extend_from_slice(format!("{prefix}{start}:{prefix}{end}").as_bytes());
+    }
+}
+
 pub trait ImapResponse {
     fn serialize(self) -> Vec<u8>;
 }
@@ -396,6 +433,33 @@ impl ResponseCode {
                 return;
             }
             ResponseCode::UseAttr => b"USEATTR",
+            ResponseCode::CompressionActive => b"COMPRESSIONACTIVE",
+            ResponseCode::Metadata { code } => {
+                buf.extend_from_slice(b"METADATA ");
+                match code {
+                    MetadataCode::LongEntries(size) => {
+                        buf.extend_from_slice(b"LONGENTRIES ");
+                        buf.extend_from_slice(size.to_string().as_bytes());
+                    }
+                    MetadataCode::MaxSize(size) => {
+                        buf.extend_from_slice(b"MAXSIZE ");
+                        buf.extend_from_slice(size.to_string().as_bytes());
+                    }
+                    MetadataCode::TooMany => buf.extend_from_slice(b"TOOMANY"),
+                    MetadataCode::NoPrivate => buf.extend_from_slice(b"NOPRIVATE"),
+                }
+                return;
+            }
+            ResponseCode::BadEvent => {
+                b"BADEVENT (MessageNew MessageExpunge FlagChange MailboxName SubscriptionChange)"
+            }
+            ResponseCode::NotificationOverflow => b"NOTIFICATIONOVERFLOW",
+            ResponseCode::TooBig => b"TOOBIG",
+            ResponseCode::BadUrl { url } => {
+                buf.extend_from_slice(b"BADURL ");
+                buf.extend_from_slice(url.as_bytes());
+                return;
+            }
         });
     }
 }
@@ -524,6 +588,18 @@ impl Display for Command {
             Command::MyRights => write!(f, "MYRIGHTS"),
             Command::Unauthenticate => write!(f, "UNAUTHENTICATE"),
             Command::Id => write!(f, "ID"),
+            Command::GetQuota => write!(f, "GETQUOTA"),
+            Command::GetQuotaRoot => write!(f, "GETQUOTAROOT"),
+            Command::SetQuota => write!(f, "SETQUOTA"),
+            Command::GetMetadata => write!(f, "GETMETADATA"),
+            Command::SetMetadata => write!(f, "SETMETADATA"),
+            Command::Compress => write!(f, "COMPRESS"),
+            Command::Notify => write!(f, "NOTIFY"),
+            Command::Replace(false) => write!(f, "REPLACE"),
+            Command::Replace(true) => write!(f, "UID REPLACE"),
+            Command::GenUrlAuth => write!(f, "GENURLAUTH"),
+            Command::ResetKey => write!(f, "RESETKEY"),
+            Command::UrlFetch => write!(f, "URLFETCH"),
         }
     }
 }
@@ -0,0 +1,150 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::utf7::utf7_encode;
+
+use super::quoted_string;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    pub name: String,
+    pub limits: Vec<(Resource, u64)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Storage,
+    Message,
+    Mailbox,
+    AnnotationStorage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaResponse {
+    pub root: String,
+    pub resources: Vec<QuotaResource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaResource {
+    pub resource: Resource,
+    pub usage: u64,
+    pub limit: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaRootResponse {
+    pub mailbox_name: String,
+    pub roots: Vec<String>,
+}
+
+impl QuotaResponse {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(b"* QUOTA ");
+        quoted_string(buf, &self.root);
+        buf.extend_from_slice(b" (");
+        for (pos, resource) in self.resources.iter().enumerate() {
+            if pos > 0 {
+                buf.push(b' ');
+            }
+            buf.extend_from_slice(resource.resource.as_str().as_bytes());
+            buf.push(b' ');
+            buf.extend_from_slice(resource.usage.to_string().as_bytes());
+            buf.push(b' ');
+            buf.extend_from_slice(resource.limit.to_string().as_bytes());
+        }
+        buf.extend_from_slice(b")\r\n");
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.root.len() + 32);
+        self.serialize(&mut buf);
+        buf
+    }
+}
+
+impl QuotaRootResponse {
+    pub fn into_bytes(self, is_rev2: bool) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.mailbox_name.len() + 16);
+        buf.extend_from_slice(b"* QUOTAROOT ");
+        if is_rev2 {
+            quoted_string(&mut buf, &self.mailbox_name);
+        } else {
+            quoted_string(&mut buf, &utf7_encode(&self.mailbox_name));
+        }
+        for root in &self.roots {
+            buf.push(b' ');
+            quoted_string(&mut buf, root);
+        }
+        buf.extend_from_slice(b"\r\n");
+        buf
+    }
+}
+
+impl Resource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resource::Storage => "STORAGE",
+            Resource::Message => "MESSAGE",
+            Resource::Mailbox => "MAILBOX",
+            Resource::AnnotationStorage => "ANNOTATION-STORAGE",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::quota::{QuotaResource, QuotaResponse, QuotaRootResponse, Resource};
+
+    #[test]
+    fn serialize_quota() {
+        assert_eq!(
+            String::from_utf8(
+                QuotaRootResponse {
+                    mailbox_name: "INBOX".to_string(),
+                    roots: vec!["".to_string()],
+                }
+                .into_bytes(true)
+            )
+            .unwrap(),
+            "* QUOTAROOT \"INBOX\" \"\"\r\n"
+        );
+
+        assert_eq!(
+            String::from_utf8(
+                QuotaResponse {
+                    root: "".to_string(),
+                    resources: vec![QuotaResource {
+                        resource: Resource::Storage,
+                        usage: 10,
+                        limit: 512
+                    }],
+                }
+                .into_bytes()
+            )
+            .unwrap(),
+            "* QUOTA \"\" (STORAGE 10 512)\r\n"
+        );
+    }
+}
@@ -23,7 +23,7 @@
 
 use store::fts::{FilterItem, FilterType};
 
-use super::{quoted_string, serialize_sequence, Flag, Sequence};
+use super::{quoted_string, serialize_sequence, Flag, PartialRange, Sequence};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Arguments {
@@ -63,6 +63,7 @@ pub struct Response {
     pub max: Option<u32>,
     pub count: Option<u32>,
     pub highest_modseq: Option<u64>,
+    pub partial: Option<PartialRange>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -73,6 +74,7 @@ pub enum ResultOption {
     Count,
     Save,
     Context,
+    Partial(PartialRange),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -129,6 +131,12 @@ pub enum Filter {
     // RFC 8474 - ObjectID
     EmailId(String),
     ThreadId(String),
+
+    // RFC 8514 - SAVEDATE
+    SavedBefore(i64),
+    SavedOn(i64),
+    SavedSince(i64),
+    SaveDateSupported,
 }
 
 impl FilterItem for Filter {
@@ -203,7 +211,17 @@ impl Response {
                 buf.extend_from_slice(b" MAX ");
                 buf.extend_from_slice(max.to_string().as_bytes());
             }
-            if !self.ids.is_empty() {
+            if let Some(partial) = &self.partial {
+                buf.extend_from_slice(b" PARTIAL (");
+                partial.serialize(&mut buf);
+                if !self.ids.is_empty() {
+                    buf.push(b' ');
+                    serialize_sequence(&mut buf, &self.ids);
+                } else {
+                    buf.extend_from_slice(b" NIL");
+                }
+                buf.push(b')');
+            } else if !self.ids.is_empty() {
                 buf.extend_from_slice(b" ALL ");
                 serialize_sequence(&mut buf, &self.ids);
             }
@@ -250,6 +268,7 @@ mod tests {
                     max: 11.into(),
                     count: 3.into(),
                     highest_modseq: None,
+                    partial: None,
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\") COUNT 3 MIN 2 MAX 11 ALL 2,10:11\r\n",),
@@ -267,6 +286,7 @@ mod tests {
                     max: None,
                     count: None,
                     highest_modseq: None,
+                    partial: None,
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\") ALL 1:3,5,10:13,90,92:99\r\n",),
@@ -282,6 +302,7 @@ mod tests {
                     max: None,
                     count: None,
                     highest_modseq: None,
+                    partial: None,
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\")\r\n",),
@@ -297,11 +318,28 @@ mod tests {
                     max: None,
                     count: None,
                     highest_modseq: 12345.into(),
+                    partial: None,
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\") ALL 10:13,21 MODSEQ 12345\r\n",),
                 concat!("* SEARCH 10 11 12 13 21 (MODSEQ 12345)\r\n",),
             ),
+            (
+                super::Response {
+                    is_uid: false,
+                    is_esearch: true,
+                    is_sort: false,
+                    ids: vec![5, 6, 7],
+                    min: None,
+                    max: None,
+                    count: 3.into(),
+                    highest_modseq: None,
+                    partial: super::PartialRange::First { start: 1, end: 100 }.into(),
+                },
+                "A284",
+                "* ESEARCH (TAG \"A284\") COUNT 3 PARTIAL (1:100 5:7)\r\n",
+                "* SEARCH 5 6 7\r\n",
+            ),
         ] {
             let response_v2 = String::from_utf8(response.clone().serialize(tag)).unwrap();
             response.is_esearch = false;
@@ -39,10 +39,12 @@ pub enum Status {
     UidValidity,
     Unseen,
     Deleted,
+    DeletedStorage,
     Size,
     Recent,
     HighestModSeq,
     MailboxId,
+    AppendLimit,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -77,10 +79,12 @@ impl StatusItem {
                 Status::UidValidity => b"UIDVALIDITY ",
                 Status::Unseen => b"UNSEEN ",
                 Status::Deleted => b"DELETED ",
+                Status::DeletedStorage => b"DELETED-STORAGE ",
                 Status::Size => b"SIZE ",
                 Status::HighestModSeq => b"HIGHESTMODSEQ ",
                 Status::MailboxId => b"MAILBOXID ",
                 Status::Recent => b"RECENT ",
+                Status::AppendLimit => b"APPENDLIMIT ",
             });
 
             match value {
@@ -0,0 +1,131 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use super::{literal_string, quoted_string};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    pub mailbox_name: Option<String>,
+    pub urls: Vec<String>,
+    pub mechanisms: Vec<Mechanism>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mechanism {
+    Internal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenUrlAuthResponse {
+    pub urls: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlFetchResponse {
+    pub items: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl GenUrlAuthResponse {
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(b"* GENURLAUTH");
+        for url in &self.urls {
+            buf.push(b' ');
+            quoted_string(&mut buf, url);
+        }
+        buf.extend_from_slice(b"\r\n");
+        buf
+    }
+}
+
+impl UrlFetchResponse {
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(
+            self.items
+                .iter()
+                .map(|(url, contents)| url.len() + contents.as_ref().map_or(0, |c| c.len()) + 16)
+                .sum::<usize>()
+                + 16,
+        );
+        buf.extend_from_slice(b"* URLFETCH");
+        for (url, contents) in &self.items {
+            buf.push(b' ');
+            quoted_string(&mut buf, url);
+            buf.push(b' ');
+            if let Some(contents) = contents {
+                literal_string(&mut buf, contents);
+            } else {
+                buf.extend_from_slice(b"NIL");
+            }
+        }
+        buf.extend_from_slice(b"\r\n");
+        buf
+    }
+}
+
+impl Mechanism {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mechanism::Internal => "internal",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::urlauth::{GenUrlAuthResponse, UrlFetchResponse};
+
+    #[test]
+    fn serialize_urlauth() {
+        assert_eq!(
+            String::from_utf8(
+                GenUrlAuthResponse {
+                    urls: vec![
+                        "imap://joe@example.com/INBOX/;uid=20/;section=1.2;urlauth=submit+fred:internal:91354a473744909de610943775f92038".to_string()
+                    ],
+                }
+                .into_bytes()
+            )
+            .unwrap(),
+            concat!(
+                "* GENURLAUTH \"imap://joe@example.com/INBOX/;uid=20/;section=1.2;",
+                "urlauth=submit+fred:internal:91354a473744909de610943775f92038\"\r\n"
+            )
+        );
+
+        assert_eq!(
+            String::from_utf8(
+                UrlFetchResponse {
+                    items: vec![
+                        ("/INBOX/;uid=20".to_string(), Some(b"Hello".to_vec())),
+                        ("/INBOX/;uid=21".to_string(), None),
+                    ],
+                }
+                .into_bytes()
+            )
+            .unwrap(),
+            "* URLFETCH \"/INBOX/;uid=20\" {5}\r\nHello \"/INBOX/;uid=21\" NIL\r\n"
+        );
+    }
+}
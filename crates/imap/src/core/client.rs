@@ -29,11 +29,12 @@ use imap_proto::{
     Command, ResponseCode, StatusResponse,
 };
 use jmap::auth::rate_limit::ConcurrencyLimiters;
+use utils::metrics::{IMAP_COMMANDS, IMAP_COMMAND_DURATION};
 
-use super::{SelectedMailbox, Session, SessionData, State};
+use super::{SelectedMailbox, Session, SessionData, State, Upgrade};
 
 impl<T: SessionStream> Session<T> {
-    pub async fn ingest(&mut self, bytes: &[u8]) -> crate::Result<bool> {
+    pub async fn ingest(&mut self, bytes: &[u8]) -> crate::Result<Option<Upgrade>> {
         /*for line in String::from_utf8_lossy(bytes).split("\r\n") {
             let c = println!("{}", line);
         }*/
@@ -73,6 +74,9 @@ impl<T: SessionStream> Session<T> {
 
         let mut requests = requests.into_iter().peekable();
         while let Some(request) = requests.next() {
+            let _timer = IMAP_COMMAND_DURATION.start_timer();
+            IMAP_COMMANDS.increment();
+
             match request.command {
                 Command::List | Command::Lsub => {
                     self.handle_list(request).await?;
@@ -121,6 +125,18 @@ impl<T: SessionStream> Session<T> {
                 Command::Move(is_uid) => {
                     self.handle_copy_move(request, true, is_uid).await?;
                 }
+                Command::Replace(is_uid) => {
+                    self.handle_replace(request, is_uid).await?;
+                }
+                Command::GenUrlAuth => {
+                    self.handle_genurlauth(request).await?;
+                }
+                Command::ResetKey => {
+                    self.handle_resetkey(request).await?;
+                }
+                Command::UrlFetch => {
+                    self.handle_urlfetch(request).await?;
+                }
                 Command::Sort(is_uid) => {
                     self.handle_search(request, true, is_uid).await?;
                 }
@@ -159,7 +175,7 @@ impl<T: SessionStream> Session<T> {
                                 .into_bytes(),
                         )
                         .await
-                        .map(|_| true);
+                        .map(|_| Some(Upgrade::Tls));
                 }
                 Command::Noop => {
                     self.handle_noop(request).await?;
@@ -191,6 +207,29 @@ impl<T: SessionStream> Session<T> {
                 Command::Id => {
                     self.handle_id(request).await?;
                 }
+                Command::GetQuota => {
+                    self.handle_get_quota(request).await?;
+                }
+                Command::GetQuotaRoot => {
+                    self.handle_get_quota_root(request).await?;
+                }
+                Command::SetQuota => {
+                    self.handle_set_quota(request).await?;
+                }
+                Command::GetMetadata => {
+                    self.handle_get_metadata(request).await?;
+                }
+                Command::SetMetadata => {
+                    self.handle_set_metadata(request).await?;
+                }
+                Command::Compress => {
+                    if self.handle_compress(request).await? {
+                        return Ok(Some(Upgrade::Compress));
+                    }
+                }
+                Command::Notify => {
+                    self.handle_notify(request).await?;
+                }
             }
         }
 
@@ -199,7 +238,7 @@ impl<T: SessionStream> Session<T> {
                 .await?;
         }
 
-        Ok(false)
+        Ok(None)
     }
 }
 
@@ -254,7 +293,12 @@ impl<T: SessionStream> Session<T> {
         match &request.command {
             Command::Capability | Command::Noop | Command::Logout | Command::Id => Ok(request),
             Command::StartTls => {
-                if !self.is_tls {
+                if self.is_compressed {
+                    Err(
+                        StatusResponse::no("STARTTLS is not allowed after COMPRESS.")
+                            .with_tag(request.tag),
+                    )
+                } else if !self.is_tls {
                     if self.instance.acceptor.is_tls() {
                         Ok(request)
                     } else {
@@ -304,7 +348,17 @@ impl<T: SessionStream> Session<T> {
             | Command::GetAcl
             | Command::ListRights
             | Command::MyRights
-            | Command::Unauthenticate => {
+            | Command::Unauthenticate
+            | Command::GetQuota
+            | Command::GetQuotaRoot
+            | Command::SetQuota
+            | Command::GetMetadata
+            | Command::SetMetadata
+            | Command::Compress
+            | Command::Notify
+            | Command::GenUrlAuth
+            | Command::ResetKey
+            | Command::UrlFetch => {
                 if let State::Authenticated { .. } | State::Selected { .. } = state {
                     Ok(request)
                 } else {
@@ -319,6 +373,7 @@ impl<T: SessionStream> Session<T> {
             | Command::Store(_)
             | Command::Copy(_)
             | Command::Move(_)
+            | Command::Replace(_)
             | Command::Check
             | Command::Sort(_)
             | Command::Thread(_) => match state {
@@ -326,7 +381,10 @@ impl<T: SessionStream> Session<T> {
                     if mailbox.is_select
                         || !matches!(
                             request.command,
-                            Command::Store(_) | Command::Expunge(_) | Command::Move(_),
+                            Command::Store(_)
+                                | Command::Expunge(_)
+                                | Command::Move(_)
+                                | Command::Replace(_),
                         )
                     {
                         Ok(request)
@@ -401,6 +459,15 @@ impl<T: SessionStream> State<T> {
         }
     }
 
+    pub fn is_in_use(&self) -> bool {
+        match self {
+            State::Authenticated { data } | State::Selected { data, .. } => {
+                Arc::strong_count(data) > 1
+            }
+            State::NotAuthenticated { .. } => false,
+        }
+    }
+
     pub fn is_authenticated(&self) -> bool {
         matches!(self, State::Authenticated { .. } | State::Selected { .. })
     }
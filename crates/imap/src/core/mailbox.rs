@@ -464,6 +464,7 @@ impl<T: SessionStream> SessionData<T> {
                         if account.account_id == account_id {
                             account.mailbox_state.values_mut().for_each(|v| {
                                 v.total_deleted = None;
+                                v.total_deleted_storage = None;
                                 v.total_unseen = None;
                                 v.total_messages = None;
                                 v.size = None;
@@ -488,6 +489,7 @@ impl<T: SessionStream> SessionData<T> {
                             let mut cached_account = cached_account_.as_ref().clone();
                             cached_account.mailbox_state.values_mut().for_each(|v| {
                                 v.total_deleted = None;
+                                v.total_deleted_storage = None;
                                 v.total_unseen = None;
                                 v.total_messages = None;
                                 v.size = None;
@@ -558,6 +560,9 @@ impl<T: SessionStream> SessionData<T> {
                                     {
                                         changes.changed.push(mailbox_name.to_string());
                                     }
+                                    if mailbox.is_subscribed != old_mailbox.is_subscribed {
+                                        changes.subscriptions.push(mailbox_name.to_string());
+                                    }
                                 }
                             } else {
                                 changes.added.push(mailbox_name.to_string());
@@ -28,10 +28,13 @@ use std::{
 };
 
 use ahash::AHashMap;
-use common::listener::{limiter::InFlight, ServerInstance, SessionStream};
+use common::{
+    auth::scram::ScramServer,
+    listener::{limiter::InFlight, ServerInstance, SessionStream},
+};
 use dashmap::DashMap;
 use imap_proto::{
-    protocol::{list::Attribute, ProtocolVersion},
+    protocol::{list::Attribute, notify::EventGroup, ProtocolVersion},
     receiver::Receiver,
     Command, ResponseCode, StatusResponse,
 };
@@ -39,13 +42,15 @@ use jmap::{
     auth::{rate_limit::ConcurrencyLimiters, AccessToken},
     JmapInstance, JMAP,
 };
+use jmap_proto::types::state::StateChange;
 use tokio::{
     io::{ReadHalf, WriteHalf},
-    sync::watch,
+    sync::{mpsc, watch},
 };
 use utils::lru_cache::LruCache;
 
 pub mod client;
+pub mod compress;
 pub mod mailbox;
 pub mod message;
 pub mod session;
@@ -79,6 +84,12 @@ pub struct Inner {
 
 pub struct IMAP {}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upgrade {
+    Tls,
+    Compress,
+}
+
 pub struct Session<T: SessionStream> {
     pub jmap: JMAP,
     pub imap: Arc<Inner>,
@@ -87,8 +98,12 @@ pub struct Session<T: SessionStream> {
     pub version: ProtocolVersion,
     pub state: State<T>,
     pub is_tls: bool,
+    pub is_compressed: bool,
     pub is_condstore: bool,
     pub is_qresync: bool,
+    pub notify: Option<NotifyState>,
+    pub scram: Option<ScramServer>,
+    pub tls_exporter: Option<Vec<u8>>,
     pub stream_rx: ReadHalf<T>,
     pub stream_tx: Arc<tokio::sync::Mutex<WriteHalf<T>>>,
     pub in_flight: InFlight,
@@ -115,6 +130,7 @@ pub struct Mailbox {
     pub total_messages: Option<u32>,
     pub total_unseen: Option<u32>,
     pub total_deleted: Option<u32>,
+    pub total_deleted_storage: Option<u32>,
     pub uid_validity: Option<u32>,
     pub uid_next: Option<u32>,
     pub size: Option<u32>,
@@ -130,6 +146,12 @@ pub struct Account {
     pub state_mailbox: Option<u64>,
 }
 
+pub struct NotifyState {
+    pub groups: Vec<EventGroup>,
+    pub change_rx: mpsc::Receiver<StateChange>,
+    pub pending_selected: bool,
+}
+
 pub struct SelectedMailbox {
     pub id: MailboxId,
     pub state: parking_lot::Mutex<MailboxState>,
@@ -173,6 +195,7 @@ pub struct MailboxSync {
     pub added: Vec<String>,
     pub changed: Vec<String>,
     pub deleted: Vec<String>,
+    pub subscriptions: Vec<String>,
 }
 
 pub enum SavedSearch {
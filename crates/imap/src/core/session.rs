@@ -21,7 +21,7 @@
  * for more details.
 */
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use common::listener::{stream::NullIo, SessionData, SessionManager, SessionStream};
 use imap_proto::{protocol::ProtocolVersion, receiver::Receiver};
@@ -29,7 +29,12 @@ use jmap::JMAP;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_rustls::server::TlsStream;
 
-use super::{ImapSessionManager, Session, State};
+use crate::op::idle::recv_notify_changes;
+
+use super::{compress::DeflateStream, ImapSessionManager, Session, State, Upgrade};
+
+// How often to check whether deferred NOTIFY changes can be sent
+const NOTIFY_PENDING_INTERVAL: Duration = Duration::from_millis(100);
 
 impl SessionManager for ImapSessionManager {
     #[allow(clippy::manual_async_fn)]
@@ -39,10 +44,22 @@ impl SessionManager for ImapSessionManager {
     ) -> impl std::future::Future<Output = ()> + Send {
         async move {
             if let Ok(mut session) = Session::new(session, self).await {
-                if session.handle_conn().await && session.instance.acceptor.is_tls() {
-                    if let Ok(mut session) = session.into_tls().await {
-                        session.handle_conn().await;
+                match session.handle_conn().await {
+                    Some(Upgrade::Tls) if session.instance.acceptor.is_tls() => {
+                        if let Ok(mut session) = session.into_tls().await {
+                            if session.handle_conn().await == Some(Upgrade::Compress) {
+                                if let Ok(mut session) = session.into_compressed() {
+                                    session.handle_conn().await;
+                                }
+                            }
+                        }
+                    }
+                    Some(Upgrade::Compress) => {
+                        if let Ok(mut session) = session.into_compressed() {
+                            session.handle_conn().await;
+                        }
                     }
+                    _ => (),
                 }
             }
         }
@@ -55,11 +72,16 @@ impl SessionManager for ImapSessionManager {
 }
 
 impl<T: SessionStream> Session<T> {
-    pub async fn handle_conn(&mut self) -> bool {
+    pub async fn handle_conn(&mut self) -> Option<Upgrade> {
         let mut buf = vec![0; 8192];
         let mut shutdown_rx = self.instance.shutdown_rx.clone();
 
         loop {
+            let has_pending_notify = self
+                .notify
+                .as_ref()
+                .is_some_and(|notify| notify.pending_selected);
+
             tokio::select! {
                 result = tokio::time::timeout(
                     if !matches!(self.state, State::NotAuthenticated {..}) {
@@ -72,9 +94,9 @@ impl<T: SessionStream> Session<T> {
                         Ok(Ok(bytes_read)) => {
                             if bytes_read > 0 {
                                 match self.ingest(&buf[..bytes_read]).await {
-                                    Ok(false) => (),
-                                    Ok(true) => {
-                                        return true;
+                                    Ok(None) => (),
+                                    Ok(upgrade) => {
+                                        return upgrade;
                                     }
                                     Err(_) => {
                                         tracing::debug!(parent: &self.span, event = "disconnect", "Disconnecting client.");
@@ -97,6 +119,17 @@ impl<T: SessionStream> Session<T> {
                         }
                     }
                 },
+                state_change = recv_notify_changes(&mut self.notify) => {
+                    if let Some(state_change) = state_change {
+                        self.write_notify_changes(state_change).await;
+                    } else {
+                        tracing::debug!(parent: &self.span, "NOTIFY channel closed.");
+                        self.notify = None;
+                    }
+                },
+                _ = tokio::time::sleep(NOTIFY_PENDING_INTERVAL), if has_pending_notify => {
+                    self.write_notify_pending().await;
+                },
                 _ = shutdown_rx.changed() => {
                     self.write_bytes(&b"* BYE Server shutting down.\r\n"[..]).await.ok();
                     tracing::debug!(parent: &self.span, event = "shutdown", "IMAP server shutting down.");
@@ -105,7 +138,7 @@ impl<T: SessionStream> Session<T> {
             };
         }
 
-        false
+        None
     }
 
     pub async fn new(
@@ -125,6 +158,7 @@ impl<T: SessionStream> Session<T> {
         let _ = session.stream.flush().await;
 
         // Split stream into read and write halves
+        let tls_exporter = session.stream.tls_exporter();
         let (stream_rx, stream_tx) = tokio::io::split(session.stream);
         let jmap = JMAP::from(manager.imap.jmap_instance);
 
@@ -133,8 +167,12 @@ impl<T: SessionStream> Session<T> {
             version: ProtocolVersion::Rev1,
             state: State::NotAuthenticated { auth_failures: 0 },
             is_tls,
+            is_compressed: false,
             is_condstore: false,
             is_qresync: false,
+            notify: None,
+            scram: None,
+            tls_exporter,
             jmap,
             imap: manager.imap.imap_inner,
             instance: session.instance,
@@ -170,8 +208,9 @@ impl<T: SessionStream> Session<T> {
         };
 
         // Upgrade to TLS
-        let (stream_rx, stream_tx) =
-            tokio::io::split(self.instance.tls_accept(stream, &self.span).await?);
+        let stream = self.instance.tls_accept(stream, &self.span).await?;
+        let tls_exporter = stream.tls_exporter();
+        let (stream_rx, stream_tx) = tokio::io::split(stream);
         let stream_tx = Arc::new(tokio::sync::Mutex::new(stream_tx));
 
         Ok(Session {
@@ -182,8 +221,63 @@ impl<T: SessionStream> Session<T> {
             version: self.version,
             state: state.try_replace_stream_tx(stream_tx.clone()).unwrap(),
             is_tls: true,
+            is_compressed: self.is_compressed,
+            is_condstore: self.is_condstore,
+            is_qresync: self.is_qresync,
+            notify: self.notify,
+            scram: None,
+            tls_exporter,
+            span: self.span,
+            in_flight: self.in_flight,
+            remote_addr: self.remote_addr,
+            stream_rx,
+            stream_tx,
+        })
+    }
+}
+
+impl<T: SessionStream> Session<T> {
+    fn into_compressed(self) -> Result<Session<DeflateStream<T>>, ()> {
+        // Drop references to write half from state
+        let state = if let Some(state) =
+            self.state
+                .try_replace_stream_tx(Arc::new(tokio::sync::Mutex::new(
+                    tokio::io::split(NullIo::default()).1,
+                ))) {
+            state
+        } else {
+            tracing::debug!("Failed to obtain write half state.");
+            return Err(());
+        };
+
+        // Take ownership of WriteHalf and unsplit it from ReadHalf
+        let stream = if let Ok(stream_tx) =
+            Arc::try_unwrap(self.stream_tx).map(|mutex| mutex.into_inner())
+        {
+            self.stream_rx.unsplit(stream_tx)
+        } else {
+            tracing::debug!("Failed to take ownership of write half.");
+            return Err(());
+        };
+
+        // Wrap stream in a DEFLATE compressor
+        let (stream_rx, stream_tx) = tokio::io::split(DeflateStream::new(stream));
+        let stream_tx = Arc::new(tokio::sync::Mutex::new(stream_tx));
+
+        Ok(Session {
+            jmap: self.jmap,
+            imap: self.imap,
+            instance: self.instance,
+            receiver: self.receiver,
+            version: self.version,
+            state: state.try_replace_stream_tx(stream_tx.clone()).unwrap(),
+            is_tls: self.is_tls,
+            is_compressed: true,
             is_condstore: self.is_condstore,
             is_qresync: self.is_qresync,
+            notify: self.notify,
+            scram: self.scram,
+            tls_exporter: self.tls_exporter,
             span: self.span,
             in_flight: self.in_flight,
             remote_addr: self.remote_addr,
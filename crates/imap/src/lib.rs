@@ -48,12 +48,12 @@ impl IMAP {
         let inner = Inner {
             greeting_plain: StatusResponse::ok(SERVER_GREETING)
                 .with_code(ResponseCode::Capability {
-                    capabilities: Capability::all_capabilities(false, false),
+                    capabilities: Capability::all_capabilities(false, false, false, 0),
                 })
                 .into_bytes(),
             greeting_tls: StatusResponse::ok(SERVER_GREETING)
                 .with_code(ResponseCode::Capability {
-                    capabilities: Capability::all_capabilities(false, true),
+                    capabilities: Capability::all_capabilities(false, true, false, 0),
                 })
                 .into_bytes(),
             rate_limiter: DashMap::with_capacity_and_hasher_and_shard_amount(
@@ -24,14 +24,20 @@
 use std::sync::Arc;
 
 use imap_proto::{
-    protocol::{append::Arguments, select::HighestModSeq},
+    protocol::{
+        append::{Arguments, CatenatePart},
+        select::HighestModSeq,
+    },
     receiver::Request,
     Command, ResponseCode, StatusResponse,
 };
 
 use crate::core::{ImapUidToId, MailboxId, SelectedMailbox, Session, SessionData};
 use common::listener::SessionStream;
-use jmap::email::ingest::{IngestEmail, IngestSource};
+use jmap::{
+    email::ingest::{IngestEmail, IngestSource},
+    JMAP,
+};
 use jmap_proto::types::{acl::Acl, keyword::Keyword, state::StateChange, type_state::DataType};
 use mail_parser::MessageParser;
 
@@ -72,7 +78,7 @@ impl<T: SessionStream> Session<T> {
                             .append_messages(arguments, selected_mailbox, mailbox, is_qresync)
                             .await
                         {
-                            Ok(response) => response,
+                            Ok((response, _)) => response,
                             Err(response) => response,
                         }
                         .into_bytes(),
@@ -87,13 +93,13 @@ impl<T: SessionStream> Session<T> {
 }
 
 impl<T: SessionStream> SessionData<T> {
-    async fn append_messages(
+    pub(crate) async fn append_messages(
         &self,
-        arguments: Arguments,
+        mut arguments: Arguments,
         selected_mailbox: Option<Arc<SelectedMailbox>>,
         mailbox: MailboxId,
         is_qresync: bool,
-    ) -> crate::op::Result<StatusResponse> {
+    ) -> crate::op::Result<(StatusResponse, Vec<u32>)> {
         // Verify ACLs
         let account_id = mailbox.account_id;
         let mailbox_id = mailbox.mailbox_id;
@@ -102,19 +108,53 @@ impl<T: SessionStream> SessionData<T> {
             .await
             .map_err(|r| r.with_tag(&arguments.tag))?
         {
-            return Ok(StatusResponse::no(
-                "You do not have the required permissions to append messages to this mailbox.",
-            )
-            .with_tag(arguments.tag)
-            .with_code(ResponseCode::NoPerm));
+            return Ok((
+                StatusResponse::no(
+                    "You do not have the required permissions to append messages to this mailbox.",
+                )
+                .with_tag(arguments.tag)
+                .with_code(ResponseCode::NoPerm),
+                vec![],
+            ));
+        }
+
+        // Compose CATENATE messages
+        let limit = append_limit(&self.jmap) as usize;
+        for message in &mut arguments.messages {
+            if !message.catenate.is_empty() {
+                message.message = self
+                    .catenate_message(std::mem::take(&mut message.catenate), limit)
+                    .await
+                    .map_err(|r| r.with_tag(&arguments.tag))?;
+            }
+        }
+
+        // Enforce APPENDLIMIT
+        if arguments
+            .messages
+            .iter()
+            .any(|message| message.message.len() > limit)
+        {
+            return Ok((
+                StatusResponse::no(format!(
+                    "Message exceeds the maximum size of {limit} bytes."
+                ))
+                .with_tag(arguments.tag)
+                .with_code(ResponseCode::TooBig),
+                vec![],
+            ));
         }
 
         // Obtain quota
-        let account_quota = self
+        let access_token = self
             .get_access_token()
             .await
-            .map_err(|r| r.with_tag(&arguments.tag))?
-            .quota as i64;
+            .map_err(|r| r.with_tag(&arguments.tag))?;
+        let account_quota = self
+            .jmap
+            .get_quota(&access_token, account_id)
+            .await
+            .map_err(|r| StatusResponse::from(r).with_tag(&arguments.tag))?;
 
         // Append messages
         let mut response = StatusResponse::completed(Command::Append);
@@ -132,6 +172,8 @@ impl<T: SessionStream> SessionData<T> {
                     keywords: message.flags.into_iter().map(Keyword::from).collect(),
                     received_at: message.received_at.map(|d| d as u64),
                     source: IngestSource::Imap,
+                    envelope_from: None,
+                    sender_verified: false,
                     encrypt: self.jmap.core.jmap.encrypt && self.jmap.core.jmap.encrypt_append,
                 })
                 .await
@@ -173,6 +215,7 @@ impl<T: SessionStream> SessionData<T> {
                 .await;
         }
 
+        let document_ids = created_ids.iter().map(|id| id.id).collect();
         if !created_ids.is_empty() {
             let uids = created_ids.iter().map(|id| id.uid).collect();
             let uid_validity = match selected_mailbox {
@@ -196,6 +239,55 @@ impl<T: SessionStream> SessionData<T> {
             response = response.with_code(ResponseCode::AppendUid { uid_validity, uids });
         }
 
-        Ok(response.with_tag(arguments.tag))
+        // Send updated quota
+        if account_quota > 0 {
+            if let Ok(Some(quota)) = self.get_quota_response(account_id).await {
+                self.write_bytes(quota.into_bytes()).await;
+            }
+        }
+
+        Ok((response.with_tag(arguments.tag), document_ids))
+    }
+
+    async fn catenate_message(
+        &self,
+        parts: Vec<CatenatePart>,
+        limit: usize,
+    ) -> crate::op::Result<Vec<u8>> {
+        let mut message = Vec::new();
+        for part in parts {
+            match part {
+                CatenatePart::Text(text) => {
+                    message.extend_from_slice(&text);
+                }
+                CatenatePart::Url(url) => match self.fetch_url(&url).await? {
+                    Some(contents) => {
+                        message.extend_from_slice(&contents);
+                    }
+                    None => {
+                        return Err(StatusResponse::no(format!("Unable to fetch URL {url:?}."))
+                            .with_code(ResponseCode::BadUrl { url }));
+                    }
+                },
+            }
+
+            if message.len() > limit {
+                return Err(StatusResponse::no(format!(
+                    "Message exceeds the maximum size of {limit} bytes."
+                ))
+                .with_code(ResponseCode::TooBig));
+            }
+        }
+
+        Ok(message)
     }
 }
+
+pub fn append_limit(jmap: &JMAP) -> u32 {
+    jmap.core
+        .imap
+        .max_request_size
+        .min(jmap.core.jmap.mail_max_size)
+        .try_into()
+        .unwrap_or(u32::MAX)
+}
@@ -21,18 +21,31 @@
  * for more details.
 */
 
-use common::{config::server::ServerProtocol, listener::SessionStream, AuthResult};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{
+    auth::scram::{ScramResult, ScramServer},
+    config::server::ServerProtocol,
+    listener::SessionStream,
+    AuthResult,
+};
+use directory::core::secret::ScramAlgorithm;
 use imap_proto::{
-    protocol::{authenticate::Mechanism, capability::Capability},
+    protocol::{
+        authenticate::{Arguments, Mechanism},
+        capability::Capability,
+    },
     receiver::{self, Request},
     Command, ResponseCode, StatusResponse,
 };
+use jmap::auth::AccessToken;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use std::sync::Arc;
 
 use crate::core::{Session, SessionData, State};
 
+use super::append::append_limit;
+
 impl<T: SessionStream> Session<T> {
     pub async fn handle_authenticate(&mut self, request: Request<Command>) -> crate::OpResult {
         match request.parse_authenticate() {
@@ -79,6 +92,10 @@ impl<T: SessionStream> Session<T> {
                         self.write_bytes(b"+ \"\"\r\n".to_vec()).await
                     }
                 }
+                Mechanism::ScramSha1
+                | Mechanism::ScramSha1Plus
+                | Mechanism::ScramSha256
+                | Mechanism::ScramSha256Plus => self.handle_scram(args).await,
                 _ => {
                     self.write_bytes(
                         StatusResponse::no("Authentication mechanism not supported.")
@@ -93,12 +110,100 @@ impl<T: SessionStream> Session<T> {
         }
     }
 
-    pub async fn authenticate(
+    async fn handle_scram(&mut self, mut args: Arguments) -> crate::OpResult {
+        let mut server = if let Some(server) = self.scram.take() {
+            server
+        } else {
+            // Throttle authentication requests
+            self.is_auth_allowed().await?;
+
+            let (algorithm, is_plus) = match args.mechanism {
+                Mechanism::ScramSha1 => (ScramAlgorithm::Sha1, false),
+                Mechanism::ScramSha1Plus => (ScramAlgorithm::Sha1, true),
+                Mechanism::ScramSha256 => (ScramAlgorithm::Sha256, false),
+                _ => (ScramAlgorithm::Sha256, true),
+            };
+            if is_plus && self.tls_exporter.is_none() {
+                return self
+                    .write_bytes(
+                        StatusResponse::no("Channel binding is not available.")
+                            .with_tag(args.tag)
+                            .with_code(ResponseCode::Cannot)
+                            .into_bytes(),
+                    )
+                    .await;
+            }
+            let server = ScramServer::new(algorithm, is_plus, self.tls_exporter.clone());
+
+            if args.params.is_empty() {
+                return self.scram_challenge(server, args, b"").await;
+            }
+            server
+        };
+
+        let response = args.params.pop().unwrap_or_default();
+        let response = if !response.is_empty() {
+            if let Some(response) = base64_decode(response.as_bytes()) {
+                response
+            } else {
+                return self
+                    .write_bytes(
+                        StatusResponse::no("Failed to decode challenge.")
+                            .with_tag(args.tag)
+                            .with_code(ResponseCode::Parse)
+                            .into_bytes(),
+                    )
+                    .await;
+            }
+        } else {
+            vec![]
+        };
+
+        match self
+            .jmap
+            .authenticate_scram(
+                &mut server,
+                &response,
+                self.remote_addr,
+                ServerProtocol::Imap,
+            )
+            .await
+        {
+            ScramResult::Challenge(challenge) => {
+                self.scram_challenge(server, args, &challenge).await
+            }
+            ScramResult::Done(AuthResult::Success(access_token)) => {
+                self.complete_authentication(Some(access_token), args.tag)
+                    .await
+            }
+            ScramResult::Done(AuthResult::Failure) => {
+                self.complete_authentication(None, args.tag).await
+            }
+            ScramResult::Done(AuthResult::Banned) => Err(()),
+        }
+    }
+
+    async fn scram_challenge(
         &mut self,
-        credentials: Credentials<String>,
-        tag: String,
-    ) -> crate::Result<()> {
-        // Throttle authentication requests
+        server: ScramServer,
+        args: Arguments,
+        challenge: &[u8],
+    ) -> crate::OpResult {
+        self.scram = Some(server);
+        self.receiver.request = receiver::Request {
+            tag: args.tag,
+            command: Command::Authenticate,
+            tokens: vec![receiver::Token::Argument(args.mechanism.into_bytes())],
+        };
+        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
+        let mut response = Vec::with_capacity(challenge.len() * 2 + 4);
+        response.extend_from_slice(b"+ ");
+        response.extend_from_slice(STANDARD.encode(challenge).as_bytes());
+        response.extend_from_slice(b"\r\n");
+        self.write_bytes(response).await
+    }
+
+    async fn is_auth_allowed(&mut self) -> crate::Result<()> {
         if self
             .jmap
             .is_auth_allowed_soft(&self.remote_addr)
@@ -114,8 +219,19 @@ impl<T: SessionStream> Session<T> {
                 event = "disconnect",
                 "Too many authentication attempts, disconnecting.",
             );
-            return Err(());
+            Err(())
+        } else {
+            Ok(())
         }
+    }
+
+    pub async fn authenticate(
+        &mut self,
+        credentials: Credentials<String>,
+        tag: String,
+    ) -> crate::Result<()> {
+        // Throttle authentication requests
+        self.is_auth_allowed().await?;
 
         // Authenticate
         let access_token = match credentials {
@@ -150,6 +266,14 @@ impl<T: SessionStream> Session<T> {
             }
         };
 
+        self.complete_authentication(access_token, tag).await
+    }
+
+    async fn complete_authentication(
+        &mut self,
+        access_token: Option<AccessToken>,
+        tag: String,
+    ) -> crate::Result<()> {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = match self
@@ -182,7 +306,12 @@ impl<T: SessionStream> Session<T> {
             self.write_bytes(
                 StatusResponse::ok("Authentication successful")
                     .with_code(ResponseCode::Capability {
-                        capabilities: Capability::all_capabilities(true, self.is_tls),
+                        capabilities: Capability::all_capabilities(
+                            true,
+                            self.is_tls,
+                            self.is_compress_allowed(),
+                            append_limit(&self.jmap),
+                        ),
                     })
                     .with_tag(tag)
                     .into_bytes(),
@@ -221,6 +350,7 @@ impl<T: SessionStream> Session<T> {
 
     pub async fn handle_unauthenticate(&mut self, request: Request<Command>) -> crate::OpResult {
         self.state = State::NotAuthenticated { auth_failures: 0 };
+        self.notify = None;
 
         self.write_bytes(
             StatusResponse::completed(Command::Unauthenticate)
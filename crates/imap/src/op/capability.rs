@@ -32,6 +32,8 @@ use imap_proto::{
     Command, StatusResponse,
 };
 
+use super::append::append_limit;
+
 impl<T: SessionStream> Session<T> {
     pub async fn handle_capability(&mut self, request: Request<Command>) -> crate::OpResult {
         self.write_bytes(
@@ -42,6 +44,8 @@ impl<T: SessionStream> Session<T> {
                         capabilities: Capability::all_capabilities(
                             self.state.is_authenticated(),
                             self.is_tls,
+                            self.is_compress_allowed(),
+                            append_limit(&self.jmap),
                         ),
                     }
                     .serialize(),
@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use common::listener::SessionStream;
+use imap_proto::{receiver::Request, Command, ResponseCode, StatusResponse};
+
+use crate::core::Session;
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_compress(&mut self, request: Request<Command>) -> crate::Result<bool> {
+        let arguments = match request.parse_compress() {
+            Ok(arguments) => arguments,
+            Err(response) => {
+                self.write_bytes(response.into_bytes()).await?;
+                return Ok(false);
+            }
+        };
+
+        let response = if self.is_compressed {
+            StatusResponse::no("Compression is already active.")
+                .with_code(ResponseCode::CompressionActive)
+        } else if !self.is_compress_allowed() {
+            StatusResponse::no("Compression is not available.").with_code(ResponseCode::Cannot)
+        } else if self.state.is_in_use() {
+            StatusResponse::no("Other commands are still in progress.")
+                .with_code(ResponseCode::InUse)
+        } else {
+            // The tagged response is the last uncompressed data sent
+            self.write_bytes(
+                StatusResponse::ok("DEFLATE active")
+                    .with_tag(arguments.tag)
+                    .into_bytes(),
+            )
+            .await?;
+            return Ok(true);
+        };
+
+        self.write_bytes(response.with_tag(arguments.tag).into_bytes())
+            .await
+            .map(|_| false)
+    }
+
+    pub fn is_compress_allowed(&self) -> bool {
+        !self.is_compressed
+            && self
+                .jmap
+                .core
+                .imap
+                .compress_listeners
+                .contains(&self.instance.id)
+    }
+}
@@ -30,7 +30,10 @@ use imap_proto::{
 
 use crate::core::{MailboxId, SelectedMailbox, Session, SessionData};
 use common::listener::SessionStream;
-use jmap::{email::set::TagManager, mailbox::UidMailbox};
+use jmap::{
+    email::{set::TagManager, spam::SpamTrainAction},
+    mailbox::{UidMailbox, JUNK_ID},
+};
 use jmap_proto::{
     error::{method::MethodError, set::SetErrorType},
     types::{
@@ -40,7 +43,9 @@ use jmap_proto::{
 };
 use store::{
     roaring::RoaringBitmap,
-    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder, F_VALUE},
+    write::{
+        assert::HashedValue, log::ChangeLogBuilder, now, BatchBuilder, F_CLEAR, F_INDEX, F_VALUE,
+    },
 };
 
 impl<T: SessionStream> Session<T> {
@@ -183,6 +188,7 @@ impl<T: SessionStream> SessionData<T> {
         let mut changelog = ChangeLogBuilder::new();
         let mut did_move = false;
         let mut copied_ids = Vec::with_capacity(ids.len());
+        let mut train_ids = Vec::new();
         if src_mailbox.id.account_id == dest_mailbox.account_id {
             // Mailboxes are in the same account
             let account_id = src_mailbox.id.account_id;
@@ -218,6 +224,11 @@ impl<T: SessionStream> SessionData<T> {
                 if is_move {
                     mailboxes.update(UidMailbox::new_unassigned(src_mailbox.id.mailbox_id), false);
                 }
+                let train_action = SpamTrainAction::from_mailbox_ids(
+                    mailboxes.added().iter().map(|m| &m.mailbox_id),
+                    mailboxes.removed().iter().map(|m| &m.mailbox_id),
+                    mailboxes.current().iter().map(|m| &m.mailbox_id),
+                );
 
                 // Assign IMAP UIDs
                 for uid_mailbox in mailboxes.inner_tags_mut() {
@@ -242,6 +253,13 @@ impl<T: SessionStream> SessionData<T> {
                     }
                 }
 
+                // Obtain current save date
+                let save_date = self
+                    .jmap
+                    .get_property::<u64>(account_id, Collection::Email, id, Property::SaveDate)
+                    .await
+                    .map_err(|_| StatusResponse::database_failure().with_tag(&arguments.tag))?;
+
                 // Write changes
                 let mut batch = BatchBuilder::new();
                 batch
@@ -249,6 +267,18 @@ impl<T: SessionStream> SessionData<T> {
                     .with_collection(Collection::Email)
                     .update_document(id);
                 mailboxes.update_batch(&mut batch, Property::MailboxIds);
+                // A copy within the same account shares the message with the
+                // source mailbox, only moves update an existing save date.
+                match save_date {
+                    Some(save_date) if is_move => {
+                        batch.value(Property::SaveDate, save_date, F_INDEX | F_CLEAR);
+                        batch.value(Property::SaveDate, now(), F_VALUE | F_INDEX);
+                    }
+                    Some(_) => {}
+                    None => {
+                        batch.value(Property::SaveDate, now(), F_VALUE | F_INDEX);
+                    }
+                }
                 if changelog.change_id == u64::MAX {
                     changelog.change_id =
                         self.jmap.assign_change_id(account_id).await.map_err(|_| {
@@ -260,6 +290,9 @@ impl<T: SessionStream> SessionData<T> {
                     Ok(_) => {
                         changelog.log_update(Collection::Email, Id::from_parts(thread_id, id));
                         changelog.log_child_update(Collection::Mailbox, dest_mailbox_id.mailbox_id);
+                        if let Some(train_action) = train_action {
+                            train_ids.push((account_id, id, train_action));
+                        }
                         if is_move {
                             changelog
                                 .log_child_update(Collection::Mailbox, src_mailbox.id.mailbox_id);
@@ -306,6 +339,13 @@ impl<T: SessionStream> SessionData<T> {
                 {
                     Ok(Ok(email)) => {
                         dest_change_id = email.change_id.into();
+                        if dest_mailbox_id == JUNK_ID {
+                            train_ids.push((
+                                dest_account_id,
+                                email.id.document_id(),
+                                SpamTrainAction::Spam,
+                            ));
+                        }
                         if let Some(assigned_uid) = email.imap_uids.first() {
                             debug_assert!(*assigned_uid > 0);
                             copied_ids.push((imap_id.uid, *assigned_uid));
@@ -432,6 +472,11 @@ impl<T: SessionStream> SessionData<T> {
 
         self.write_bytes(response).await;
 
+        // Train the spam classifier on messages moved into or out of Junk
+        for (account_id, id, train_action) in train_ids {
+            self.jmap.spam_train(account_id, id, train_action).await;
+        }
+
         Ok(())
     }
 
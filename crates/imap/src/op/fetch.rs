@@ -278,6 +278,9 @@ impl<T: SessionStream> SessionData<T> {
             .map(|(id, imap_id)| (imap_id.seqnum, imap_id.uid, id))
             .collect::<Vec<_>>();
         ids.sort_unstable_by_key(|(seqnum, _, _)| *seqnum);
+        if let Some(partial) = &arguments.partial {
+            ids = partial.apply(ids);
+        }
         for (seqnum, uid, id) in ids {
             // Obtain attributes and keywords
             let (email, keywords) = if let (Ok(Some(email)), Ok(Some(keywords))) = (
@@ -485,6 +488,22 @@ impl<T: SessionStream> SessionData<T> {
                             thread_id: Id::from_parts(account_id, thread_id).to_string(),
                         });
                     }
+                    Attribute::SaveDate => {
+                        items.push(DataItem::SaveDate {
+                            date: self
+                                .jmap
+                                .get_property::<u64>(
+                                    account_id,
+                                    Collection::Email,
+                                    id,
+                                    Property::SaveDate,
+                                )
+                                .await
+                                .ok()
+                                .flatten()
+                                .map(|date| date as i64),
+                        });
+                    }
                 }
             }
 
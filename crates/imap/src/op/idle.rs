@@ -36,12 +36,12 @@ use imap_proto::{
 };
 
 use common::listener::SessionStream;
-use jmap_proto::types::{collection::Collection, type_state::DataType};
+use jmap_proto::types::{collection::Collection, state::StateChange, type_state::DataType};
 use store::query::log::Query;
-use tokio::io::AsyncReadExt;
+use tokio::{io::AsyncReadExt, sync::mpsc};
 use utils::map::bitmap::Bitmap;
 
-use crate::core::{SelectedMailbox, Session, SessionData, State};
+use crate::core::{NotifyState, SelectedMailbox, Session, SessionData, State};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_idle(&mut self, request: Request<Command>) -> crate::OpResult {
@@ -59,13 +59,15 @@ impl<T: SessionStream> Session<T> {
         let is_rev2 = self.version.is_rev2();
         let is_qresync = self.is_qresync;
 
-        // Register with state manager
-        let mut change_rx = if let Some(change_rx) = self
+        // Register with state manager, unless NOTIFY is already active
+        let mut change_rx = if self.notify.is_some() {
+            None
+        } else if let Some(change_rx) = self
             .jmap
             .subscribe_state_manager(data.account_id, types)
             .await
         {
-            change_rx
+            Some(change_rx)
         } else {
             return self
                 .write_bytes(
@@ -110,8 +112,10 @@ impl<T: SessionStream> Session<T> {
                         }
                     }
                 }
-                state_change = change_rx.recv() => {
-                    if let Some(state_change) = state_change {
+                state_change = recv_changes(&mut change_rx, &mut self.notify) => {
+                    if let (Some(state_change), Some(notify)) = (&state_change, &self.notify) {
+                        data.write_notify_changes(&notify.groups, &mailbox, state_change, true, is_qresync, is_rev2).await;
+                    } else if let Some(state_change) = state_change {
                         let mut has_mailbox_changes = false;
                         let mut has_email_changes = false;
 
@@ -141,6 +145,25 @@ impl<T: SessionStream> Session<T> {
     }
 }
 
+pub(crate) async fn recv_changes(
+    change_rx: &mut Option<mpsc::Receiver<StateChange>>,
+    notify: &mut Option<NotifyState>,
+) -> Option<StateChange> {
+    if let Some(change_rx) = change_rx {
+        change_rx.recv().await
+    } else {
+        recv_notify_changes(notify).await
+    }
+}
+
+pub(crate) async fn recv_notify_changes(notify: &mut Option<NotifyState>) -> Option<StateChange> {
+    if let Some(notify) = notify {
+        notify.change_rx.recv().await
+    } else {
+        std::future::pending().await
+    }
+}
+
 impl<T: SessionStream> SessionData<T> {
     pub async fn write_changes(
         &self,
@@ -206,74 +229,109 @@ impl<T: SessionStream> SessionData<T> {
 
         // Fetch selected mailbox changes
         if check_emails {
-            // Synchronize emails
             if let Some(mailbox) = mailbox {
-                // Obtain changes since last sync
-                let modseq = mailbox.state.lock().modseq;
-                match self.write_mailbox_changes(mailbox, is_qresync).await {
-                    Ok(new_state) => {
-                        if new_state == modseq {
-                            return;
-                        }
-                    }
-                    Err(response) => {
-                        self.write_bytes(response.into_bytes()).await;
-                        return;
-                    }
-                }
+                self.write_email_changes(mailbox, &[], is_qresync, is_rev2)
+                    .await;
+            }
+        }
+    }
 
-                // Obtain changed messages
-                let changed_ids = match self
-                    .jmap
-                    .changes_(
-                        mailbox.id.account_id,
-                        Collection::Email,
-                        modseq.map(Query::Since).unwrap_or(Query::All),
-                    )
-                    .await
-                {
-                    Ok(changelog) => {
-                        let state = mailbox.state.lock();
-                        changelog
-                            .changes
-                            .into_iter()
-                            .filter_map(|change| {
-                                state
-                                    .id_to_imap
-                                    .get(&((change.unwrap_id() & u32::MAX as u64) as u32))
-                                    .map(|id| id.uid)
-                            })
-                            .collect::<AHashSet<_>>()
-                    }
-                    Err(_) => {
-                        self.write_bytes(StatusResponse::database_failure().into_bytes())
-                            .await;
-                        return;
-                    }
-                };
+    pub async fn write_email_changes(
+        &self,
+        mailbox: &Arc<SelectedMailbox>,
+        new_attributes: &[fetch::Attribute],
+        is_qresync: bool,
+        is_rev2: bool,
+    ) {
+        // Obtain changes since last sync
+        let (modseq, uid_max) = {
+            let state = mailbox.state.lock();
+            (state.modseq, state.uid_max)
+        };
+        match self.write_mailbox_changes(mailbox, is_qresync).await {
+            Ok(new_state) => {
+                if new_state == modseq {
+                    return;
+                }
+            }
+            Err(response) => {
+                self.write_bytes(response.into_bytes()).await;
+                return;
+            }
+        }
 
-                if !changed_ids.is_empty() {
-                    self.fetch(
-                        fetch::Arguments {
-                            tag: String::new(),
-                            sequence_set: Sequence::List {
-                                items: changed_ids
-                                    .into_iter()
-                                    .map(|uid| Sequence::Number { value: uid })
-                                    .collect(),
-                            },
-                            attributes: vec![fetch::Attribute::Flags, fetch::Attribute::Uid],
-                            changed_since: None,
-                            include_vanished: false,
-                        },
-                        mailbox.clone(),
-                        true,
-                        is_qresync,
-                        is_rev2,
-                        false,
-                    )
+        // Obtain changed messages
+        let changed_ids = match self
+            .jmap
+            .changes_(
+                mailbox.id.account_id,
+                Collection::Email,
+                modseq.map(Query::Since).unwrap_or(Query::All),
+            )
+            .await
+        {
+            Ok(changelog) => {
+                let state = mailbox.state.lock();
+                changelog
+                    .changes
+                    .into_iter()
+                    .filter_map(|change| {
+                        state
+                            .id_to_imap
+                            .get(&((change.unwrap_id() & u32::MAX as u64) as u32))
+                            .map(|id| id.uid)
+                    })
+                    .collect::<AHashSet<_>>()
+            }
+            Err(_) => {
+                self.write_bytes(StatusResponse::database_failure().into_bytes())
                     .await;
+                return;
+            }
+        };
+
+        // Split new messages when additional attributes were requested
+        let (new_ids, changed_ids): (Vec<_>, Vec<_>) = changed_ids
+            .into_iter()
+            .partition(|uid| !new_attributes.is_empty() && *uid > uid_max);
+
+        for (uids, attributes) in [
+            (
+                changed_ids,
+                vec![fetch::Attribute::Flags, fetch::Attribute::Uid],
+            ),
+            (new_ids, {
+                let mut attributes = vec![fetch::Attribute::Flags, fetch::Attribute::Uid];
+                for attribute in new_attributes {
+                    if !attributes.contains(attribute) {
+                        attributes.push(attribute.clone());
+                    }
                 }
+                attributes
+            }),
+        ] {
+            if !uids.is_empty() {
+                self.fetch(
+                    fetch::Arguments {
+                        tag: String::new(),
+                        sequence_set: Sequence::List {
+                            items: uids
+                                .into_iter()
+                                .map(|uid| Sequence::Number { value: uid })
+                                .collect(),
+                        },
+                        attributes,
+                        changed_since: None,
+                        include_vanished: false,
+                        partial: None,
+                    },
+                    mailbox.clone(),
+                    true,
+                    is_qresync,
+                    is_rev2,
+                    false,
+                )
+                .await;
             }
         }
     }
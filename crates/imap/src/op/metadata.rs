@@ -0,0 +1,446 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::slice::Iter;
+
+use common::listener::SessionStream;
+use imap_proto::{
+    protocol::{
+        acl::Rights,
+        metadata::{Arguments, Depth, Entry, MetadataResponse},
+    },
+    receiver::Request,
+    Command, MetadataCode, ResponseCode, StatusResponse,
+};
+use jmap_proto::types::{collection::Collection, property::Property};
+use store::write::{
+    assert::HashedValue, BatchBuilder, DeserializeFrom, Operation, SerializeInto, ToBitmaps,
+    F_CLEAR, F_VALUE,
+};
+use utils::codec::leb128::Leb128Iterator;
+
+use crate::core::{MailboxId, Session, SessionData};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub name: String,
+    // Owner of /private entries, unused for /shared entries
+    pub account_id: u32,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    account_id: u32,
+    collection: Collection,
+    document_id: u32,
+}
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_get_metadata(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_metadata(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+                let is_rev2 = self.version.is_rev2();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    let response = match data.get_metadata(arguments, is_rev2).await {
+                        Ok((response, long_entries)) => {
+                            let mut status =
+                                StatusResponse::completed(Command::GetMetadata).with_tag(tag);
+                            if let Some(size) = long_entries {
+                                status = status.with_code(ResponseCode::Metadata {
+                                    code: MetadataCode::LongEntries(size),
+                                });
+                            }
+                            status.serialize(response)
+                        }
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    };
+                    data.write_bytes(response).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_set_metadata(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_metadata(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    let response = match data.set_metadata(arguments).await {
+                        Ok(_) => StatusResponse::completed(Command::SetMetadata).with_tag(tag),
+                        Err(response) => response.with_tag(tag),
+                    };
+                    data.write_bytes(response.into_bytes()).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn get_metadata(
+        &self,
+        arguments: Arguments,
+        is_rev2: bool,
+    ) -> crate::op::Result<(Vec<u8>, Option<u32>)> {
+        // Obtain stored entries
+        let mut annotations = Vec::new();
+        match self.get_metadata_mailbox(&arguments.mailbox_name).await? {
+            Some(mailbox) => {
+                if !self
+                    .check_mailbox_acl(mailbox.account_id, mailbox.mailbox_id, Rights::Read.into())
+                    .await?
+                {
+                    return Err(StatusResponse::no(
+                        "You do not have enough permissions to read metadata on this mailbox.",
+                    )
+                    .with_code(ResponseCode::NoPerm));
+                }
+                annotations = self
+                    .get_annotations(Location::mailbox(mailbox))
+                    .await?
+                    .map(|a| a.inner)
+                    .unwrap_or_default();
+            }
+            None => {
+                for location in [
+                    Location::server_private(self.account_id),
+                    Location::server_shared(),
+                ] {
+                    if let Some(values) = self.get_annotations(location).await? {
+                        annotations.extend(values.inner);
+                    }
+                }
+            }
+        }
+        annotations.retain(|a| !a.is_private() || a.account_id == self.account_id);
+
+        // Build response
+        let mut entries: Vec<Entry> = Vec::with_capacity(arguments.entries.len());
+        let mut long_entries = None;
+        for entry in arguments.entries {
+            let mut has_match = false;
+            for annotation in annotations
+                .iter()
+                .filter(|a| a.matches(&entry.name, arguments.depth))
+            {
+                has_match = true;
+                if entries.iter().any(|e| e.name == annotation.name) {
+                    continue;
+                } else if arguments
+                    .max_size
+                    .is_none_or(|max_size| annotation.value.len() <= max_size as usize)
+                {
+                    entries.push(Entry {
+                        name: annotation.name.clone(),
+                        value: annotation.value.clone().into(),
+                    });
+                } else {
+                    long_entries = Some(std::cmp::max(
+                        long_entries.unwrap_or_default(),
+                        annotation.value.len() as u32,
+                    ));
+                }
+            }
+
+            // Entries that do not exist are returned as NIL
+            if !has_match
+                && arguments.depth == Depth::Zero
+                && !entries.iter().any(|e| e.name == entry.name)
+            {
+                entries.push(entry);
+            }
+        }
+
+        Ok((
+            if !entries.is_empty() {
+                MetadataResponse {
+                    mailbox_name: arguments.mailbox_name,
+                    entries,
+                }
+                .into_bytes(is_rev2)
+            } else {
+                Vec::new()
+            },
+            long_entries,
+        ))
+    }
+
+    async fn set_metadata(&self, arguments: Arguments) -> crate::op::Result<()> {
+        // Enforce size limits
+        let max_size = self.jmap.core.imap.metadata_max_size;
+        if arguments
+            .entries
+            .iter()
+            .any(|entry| entry.value.as_ref().is_some_and(|v| v.len() > max_size))
+        {
+            return Err(
+                StatusResponse::no("Metadata value is too large.").with_code(
+                    ResponseCode::Metadata {
+                        code: MetadataCode::MaxSize(max_size as u32),
+                    },
+                ),
+            );
+        }
+
+        // Validate permissions and group entries by location
+        let mut updates: Vec<(Location, Vec<Entry>)> = Vec::with_capacity(2);
+        match self.get_metadata_mailbox(&arguments.mailbox_name).await? {
+            Some(mailbox) => {
+                for (is_private, right) in [(true, Rights::Lookup), (false, Rights::Write)] {
+                    if arguments
+                        .entries
+                        .iter()
+                        .any(|e| e.is_private() == is_private)
+                        && !self
+                            .check_mailbox_acl(mailbox.account_id, mailbox.mailbox_id, right.into())
+                            .await?
+                    {
+                        return Err(StatusResponse::no(
+                            "You do not have enough permissions to set metadata on this mailbox.",
+                        )
+                        .with_code(ResponseCode::NoPerm));
+                    }
+                }
+                updates.push((Location::mailbox(mailbox), arguments.entries));
+            }
+            None => {
+                let (private, shared): (Vec<_>, Vec<_>) =
+                    arguments.entries.into_iter().partition(|e| e.is_private());
+                if !shared.is_empty() {
+                    if !self.get_access_token().await?.is_super_user() {
+                        return Err(StatusResponse::no(
+                            "Only administrators can set shared server metadata.",
+                        )
+                        .with_code(ResponseCode::NoPerm));
+                    }
+                    updates.push((Location::server_shared(), shared));
+                }
+                if !private.is_empty() {
+                    updates.push((Location::server_private(self.account_id), private));
+                }
+            }
+        }
+
+        // Apply changes
+        let max_entries = self.jmap.core.imap.metadata_max_entries;
+        for (location, entries) in updates {
+            let current = self.get_annotations(location).await?;
+            let mut annotations = current
+                .as_ref()
+                .map(|a| a.inner.clone())
+                .unwrap_or_default();
+            let num_annotations = annotations.len();
+
+            for entry in entries {
+                let account_id = if entry.is_private() {
+                    self.account_id
+                } else {
+                    0
+                };
+                let pos = annotations
+                    .iter()
+                    .position(|a| a.name == entry.name && a.account_id == account_id);
+                match (entry.value, pos) {
+                    (Some(value), Some(pos)) => {
+                        annotations[pos].value = value;
+                    }
+                    (Some(value), None) => {
+                        annotations.push(Annotation {
+                            name: entry.name,
+                            account_id,
+                            value,
+                        });
+                    }
+                    (None, Some(pos)) => {
+                        annotations.swap_remove(pos);
+                    }
+                    (None, None) => (),
+                }
+            }
+
+            if annotations.len() > num_annotations && annotations.len() > max_entries {
+                return Err(StatusResponse::no("Too many metadata entries.").with_code(
+                    ResponseCode::Metadata {
+                        code: MetadataCode::TooMany,
+                    },
+                ));
+            }
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(location.account_id)
+                .with_collection(location.collection)
+                .update_document(location.document_id);
+            if let Some(current) = &current {
+                batch.assert_value(Property::Annotations, current);
+            }
+            if !annotations.is_empty() {
+                batch.value(Property::Annotations, annotations, F_VALUE);
+            } else {
+                batch.value(Property::Annotations, (), F_VALUE | F_CLEAR);
+            }
+
+            self.jmap
+                .core
+                .storage
+                .data
+                .write(batch.build())
+                .await
+                .map_err(|err| match err {
+                    store::Error::AssertValueFailed => StatusResponse::no(
+                        "Another process modified this metadata, please try again.",
+                    ),
+                    err => {
+                        tracing::warn!(parent: &self.span,
+                            event = "error",
+                            context = "set_metadata",
+                            account_id = location.account_id,
+                            document_id = location.document_id,
+                            error = ?err,
+                            "Failed to write metadata.");
+                        StatusResponse::database_failure()
+                    }
+                })?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_metadata_mailbox(
+        &self,
+        mailbox_name: &str,
+    ) -> crate::op::Result<Option<MailboxId>> {
+        if !mailbox_name.is_empty() {
+            // Refresh mailboxes
+            self.synchronize_mailboxes(false).await?;
+
+            self.get_mailbox_by_name(mailbox_name)
+                .ok_or_else(|| {
+                    StatusResponse::no("Mailbox does not exist.")
+                        .with_code(ResponseCode::NonExistent)
+                })
+                .map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn get_annotations(
+        &self,
+        location: Location,
+    ) -> crate::op::Result<Option<HashedValue<Vec<Annotation>>>> {
+        self.jmap
+            .get_property::<HashedValue<Vec<Annotation>>>(
+                location.account_id,
+                location.collection,
+                location.document_id,
+                Property::Annotations,
+            )
+            .await
+            .map_err(Into::into)
+    }
+}
+
+impl Location {
+    fn mailbox(mailbox: MailboxId) -> Self {
+        Location {
+            account_id: mailbox.account_id,
+            collection: Collection::Mailbox,
+            document_id: mailbox.mailbox_id,
+        }
+    }
+
+    fn server_private(account_id: u32) -> Self {
+        Location {
+            account_id,
+            collection: Collection::Principal,
+            document_id: 0,
+        }
+    }
+
+    fn server_shared() -> Self {
+        Location {
+            account_id: u32::MAX,
+            collection: Collection::Principal,
+            document_id: 0,
+        }
+    }
+}
+
+impl Annotation {
+    pub fn is_private(&self) -> bool {
+        self.name.starts_with("/private")
+    }
+
+    fn matches(&self, name: &str, depth: Depth) -> bool {
+        if self.name == name {
+            true
+        } else if let Some(child) = self
+            .name
+            .strip_prefix(name)
+            .and_then(|child| child.strip_prefix('/'))
+        {
+            match depth {
+                Depth::Zero => false,
+                Depth::One => !child.contains('/'),
+                Depth::Infinity => true,
+            }
+        } else {
+            false
+        }
+    }
+}
+
+impl SerializeInto for Annotation {
+    fn serialize_into(&self, buf: &mut Vec<u8>) {
+        self.name.serialize_into(buf);
+        self.account_id.serialize_into(buf);
+        self.value.serialize_into(buf);
+    }
+}
+
+impl DeserializeFrom for Annotation {
+    fn deserialize_from(bytes: &mut Iter<'_, u8>) -> Option<Self> {
+        Some(Annotation {
+            name: String::deserialize_from(bytes)?,
+            account_id: bytes.next_leb128()?,
+            value: <Vec<u8>>::deserialize_from(bytes)?,
+        })
+    }
+}
+
+impl ToBitmaps for Annotation {
+    fn to_bitmaps(&self, _: &mut Vec<Operation>, _: u8, _: bool) {
+        unreachable!()
+    }
+}
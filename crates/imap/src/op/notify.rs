@@ -0,0 +1,422 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use common::listener::SessionStream;
+use imap_proto::{
+    protocol::{
+        list::{Attribute, ListItem},
+        notify::{Event, EventGroup, Filter},
+        status::Status,
+    },
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+use jmap_proto::types::{state::StateChange, type_state::DataType};
+use utils::map::bitmap::Bitmap;
+
+use crate::core::{MailboxId, NotifyState, SelectedMailbox, Session, SessionData, State};
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_notify(&mut self, request: Request<Command>) -> crate::OpResult {
+        let arguments = match request.parse_notify(self.version) {
+            Ok(arguments) => arguments,
+            Err(response) => return self.write_bytes(response.into_bytes()).await,
+        };
+
+        // Metadata and annotation change events are not supported
+        if arguments.groups.iter().any(|group| {
+            group.events.iter().any(|event| {
+                matches!(
+                    event,
+                    Event::AnnotationChange
+                        | Event::MailboxMetadataChange
+                        | Event::ServerMetadataChange
+                )
+            })
+        }) {
+            return self
+                .write_bytes(
+                    StatusResponse::no("Unsupported NOTIFY event.")
+                        .with_tag(arguments.tag)
+                        .with_code(ResponseCode::BadEvent)
+                        .into_bytes(),
+                )
+                .await;
+        }
+
+        // NOTIFY NONE
+        self.notify = None;
+        if arguments.groups.is_empty() {
+            return self
+                .write_bytes(
+                    StatusResponse::completed(Command::Notify)
+                        .with_tag(arguments.tag)
+                        .into_bytes(),
+                )
+                .await;
+        }
+
+        // Register with state manager
+        let (data, mailbox) = self.state.session_mailbox_state();
+        let change_rx = if let Some(change_rx) = self
+            .jmap
+            .subscribe_state_manager(
+                data.account_id,
+                Bitmap::from_iter([DataType::Email, DataType::Mailbox, DataType::EmailDelivery]),
+            )
+            .await
+        {
+            change_rx
+        } else {
+            return self
+                .write_bytes(
+                    StatusResponse::no("It was not possible to start NOTIFY.")
+                        .with_tag(arguments.tag)
+                        .with_code(ResponseCode::ContactAdmin)
+                        .into_bytes(),
+                )
+                .await;
+        };
+
+        // Send the initial status of all monitored mailboxes
+        if arguments.status {
+            if let Err(response) = data.synchronize_mailboxes(false).await {
+                return self
+                    .write_bytes(response.with_tag(arguments.tag).into_bytes())
+                    .await;
+            }
+            let mailbox_names = data
+                .mailboxes
+                .lock()
+                .iter()
+                .flat_map(|account| account.mailbox_names.keys().cloned())
+                .collect::<Vec<_>>();
+            let mut buf = Vec::with_capacity(64);
+            for mailbox_name in mailbox_names {
+                if data
+                    .notify_group(&arguments.groups, &mailbox_name, &mailbox)
+                    .is_some_and(|group| {
+                        !group.filter.is_selected()
+                            && group.events.iter().any(|event| event.is_message_event())
+                    })
+                {
+                    data.write_notify_status(&mut buf, mailbox_name, self.version.is_rev2())
+                        .await;
+                }
+            }
+            if !buf.is_empty() {
+                self.write_bytes(buf).await?;
+            }
+        }
+
+        self.notify = Some(NotifyState {
+            groups: arguments.groups,
+            change_rx,
+            pending_selected: false,
+        });
+
+        self.write_bytes(
+            StatusResponse::completed(Command::Notify)
+                .with_tag(arguments.tag)
+                .into_bytes(),
+        )
+        .await
+    }
+
+    pub async fn write_notify_changes(&mut self, state_change: StateChange) {
+        let (data, mailbox) = match &self.state {
+            State::Authenticated { data } => (data.clone(), None),
+            State::Selected { data, mailbox } => (data.clone(), Some(mailbox.clone())),
+            State::NotAuthenticated { .. } => {
+                self.notify = None;
+                return;
+            }
+        };
+
+        if let Some(notify) = &mut self.notify {
+            if self.state.is_in_use() {
+                // Untagged responses about the selected mailbox cannot be sent
+                // while a command is running, defer them until it completes.
+                let has_email_changes = data
+                    .write_notify_mailbox_changes(
+                        &notify.groups,
+                        &mailbox,
+                        &state_change,
+                        self.version.is_rev2(),
+                    )
+                    .await;
+                notify.pending_selected |= has_email_changes && mailbox.is_some();
+            } else {
+                notify.pending_selected = false;
+                data.write_notify_changes(
+                    &notify.groups,
+                    &mailbox,
+                    &state_change,
+                    false,
+                    self.is_qresync,
+                    self.version.is_rev2(),
+                )
+                .await;
+            }
+        }
+    }
+
+    pub async fn write_notify_pending(&mut self) {
+        if self.state.is_in_use() {
+            return;
+        }
+
+        if let Some(notify) = &mut self.notify {
+            notify.pending_selected = false;
+            if let State::Selected { data, mailbox } = &self.state {
+                data.write_notify_selected_changes(
+                    &notify.groups,
+                    mailbox,
+                    false,
+                    self.is_qresync,
+                    self.version.is_rev2(),
+                )
+                .await;
+            }
+        }
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    pub async fn write_notify_changes(
+        &self,
+        groups: &[EventGroup],
+        selected: &Option<Arc<SelectedMailbox>>,
+        state_change: &StateChange,
+        is_idle: bool,
+        is_qresync: bool,
+        is_rev2: bool,
+    ) {
+        if self
+            .write_notify_mailbox_changes(groups, selected, state_change, is_rev2)
+            .await
+        {
+            if let Some(mailbox) = selected {
+                self.write_notify_selected_changes(groups, mailbox, is_idle, is_qresync, is_rev2)
+                    .await;
+            }
+        }
+    }
+
+    // Reports changes to mailboxes other than the selected one, returns
+    // whether the state change included any email changes.
+    pub async fn write_notify_mailbox_changes(
+        &self,
+        groups: &[EventGroup],
+        selected: &Option<Arc<SelectedMailbox>>,
+        state_change: &StateChange,
+        is_rev2: bool,
+    ) -> bool {
+        let mut has_mailbox_changes = false;
+        let mut has_email_changes = false;
+        for (type_state, _) in &state_change.types {
+            match type_state {
+                DataType::Email | DataType::EmailDelivery => {
+                    has_email_changes = true;
+                }
+                DataType::Mailbox => {
+                    has_mailbox_changes = true;
+                }
+                _ => {}
+            }
+        }
+
+        if has_mailbox_changes || has_email_changes {
+            match self.synchronize_mailboxes(true).await {
+                Ok(Some(changes)) => {
+                    let mut buf = Vec::with_capacity(64);
+
+                    for (mailbox_name, is_deleted) in changes
+                        .deleted
+                        .into_iter()
+                        .map(|name| (name, true))
+                        .chain(changes.added.into_iter().map(|name| (name, false)))
+                    {
+                        if self
+                            .notify_group(groups, &mailbox_name, selected)
+                            .is_some_and(|group| group.events.contains(&Event::MailboxName))
+                        {
+                            ListItem {
+                                mailbox_name,
+                                attributes: if is_deleted {
+                                    vec![Attribute::NonExistent]
+                                } else {
+                                    vec![]
+                                },
+                                tags: vec![],
+                            }
+                            .serialize(&mut buf, is_rev2, false);
+                        }
+                    }
+
+                    for mailbox_name in changes.subscriptions {
+                        if self
+                            .notify_group(groups, &mailbox_name, selected)
+                            .is_some_and(|group| group.events.contains(&Event::SubscriptionChange))
+                        {
+                            let is_subscribed = self.is_subscribed(&mailbox_name);
+                            ListItem {
+                                mailbox_name,
+                                attributes: if is_subscribed {
+                                    vec![Attribute::Subscribed]
+                                } else {
+                                    vec![]
+                                },
+                                tags: vec![],
+                            }
+                            .serialize(&mut buf, is_rev2, false);
+                        }
+                    }
+
+                    for mailbox_name in changes.changed {
+                        if self
+                            .notify_group(groups, &mailbox_name, selected)
+                            .is_some_and(|group| {
+                                !group.filter.is_selected()
+                                    && group.events.iter().any(|event| event.is_message_event())
+                            })
+                        {
+                            self.write_notify_status(&mut buf, mailbox_name, is_rev2)
+                                .await;
+                        }
+                    }
+
+                    if !buf.is_empty() {
+                        self.write_bytes(buf).await;
+                    }
+                }
+                Err(_) => {
+                    tracing::debug!(parent: &self.span, "Failed to refresh mailboxes.");
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        has_email_changes
+    }
+
+    // Reports changes to the selected mailbox, SELECTED-DELAYED
+    // changes are only sent while idling.
+    pub async fn write_notify_selected_changes(
+        &self,
+        groups: &[EventGroup],
+        mailbox: &Arc<SelectedMailbox>,
+        is_idle: bool,
+        is_qresync: bool,
+        is_rev2: bool,
+    ) {
+        if let Some(group) = groups.iter().find(|group| {
+            matches!(group.filter, Filter::Selected)
+                || (is_idle && matches!(group.filter, Filter::SelectedDelayed))
+        }) {
+            if group.events.iter().any(|event| event.is_message_event()) {
+                let new_attributes = group
+                    .events
+                    .iter()
+                    .find_map(|event| match event {
+                        Event::MessageNew { attributes } => Some(attributes.as_slice()),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                self.write_email_changes(mailbox, new_attributes, is_qresync, is_rev2)
+                    .await;
+            }
+        }
+    }
+
+    pub fn notify_group<'x>(
+        &self,
+        groups: &'x [EventGroup],
+        mailbox_name: &str,
+        selected: &Option<Arc<SelectedMailbox>>,
+    ) -> Option<&'x EventGroup> {
+        let mailbox_id = self.get_mailbox_by_name(mailbox_name);
+        if let (Some(mailbox_id), Some(selected)) = (mailbox_id, selected) {
+            if mailbox_id == selected.id {
+                return groups.iter().find(|group| group.filter.is_selected());
+            }
+        }
+
+        let is_inbox = mailbox_name.eq_ignore_ascii_case("INBOX");
+        let is_personal = mailbox_id.map_or_else(
+            || !mailbox_name.starts_with(&self.jmap.core.jmap.shared_folder),
+            |mailbox_id| mailbox_id.account_id == self.account_id,
+        );
+        groups.iter().find(|group| match &group.filter {
+            Filter::Selected | Filter::SelectedDelayed => false,
+            Filter::Inboxes => is_inbox && is_personal,
+            Filter::Personal => is_personal,
+            Filter::Subscribed => {
+                mailbox_id.is_some_and(|mailbox_id| self.is_subscribed_id(mailbox_id))
+            }
+            Filter::Subtree(names) => names.iter().any(|name| {
+                mailbox_name == name
+                    || (is_inbox && name.eq_ignore_ascii_case("INBOX"))
+                    || mailbox_name
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|child| child.starts_with('/'))
+            }),
+            Filter::Mailboxes(names) => names.iter().any(|name| {
+                mailbox_name == name || (is_inbox && name.eq_ignore_ascii_case("INBOX"))
+            }),
+        })
+    }
+
+    fn is_subscribed(&self, mailbox_name: &str) -> bool {
+        self.get_mailbox_by_name(mailbox_name)
+            .is_some_and(|mailbox_id| self.is_subscribed_id(mailbox_id))
+    }
+
+    fn is_subscribed_id(&self, mailbox_id: MailboxId) -> bool {
+        self.mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.account_id == mailbox_id.account_id)
+            .and_then(|account| account.mailbox_state.get(&mailbox_id.mailbox_id))
+            .is_some_and(|mailbox| mailbox.is_subscribed)
+    }
+
+    async fn write_notify_status(&self, buf: &mut Vec<u8>, mailbox_name: String, is_rev2: bool) {
+        if let Ok(status) = self
+            .status(
+                mailbox_name,
+                &[
+                    Status::Messages,
+                    Status::Unseen,
+                    Status::UidNext,
+                    Status::UidValidity,
+                ],
+            )
+            .await
+        {
+            status.serialize(buf, is_rev2);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use common::listener::SessionStream;
+use directory::{
+    backend::internal::{manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue},
+    DirectoryInner, QueryBy,
+};
+use imap_proto::{
+    protocol::quota::{Arguments, QuotaResource, QuotaResponse, QuotaRootResponse, Resource},
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+
+use crate::core::{Session, SessionData};
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_get_quota(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_quota(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    let response = match data.get_quota(&arguments.name).await {
+                        Ok(quota) => StatusResponse::completed(Command::GetQuota)
+                            .with_tag(arguments.tag)
+                            .serialize(quota.into_bytes()),
+                        Err(response) => response.with_tag(arguments.tag).into_bytes(),
+                    };
+                    data.write_bytes(response).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_get_quota_root(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_quota(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+                let is_rev2 = self.version.is_rev2();
+
+                tokio::spawn(async move {
+                    let response = match data.get_quota_root(arguments.name, is_rev2).await {
+                        Ok(response) => StatusResponse::completed(Command::GetQuotaRoot)
+                            .with_tag(arguments.tag)
+                            .serialize(response),
+                        Err(response) => response.with_tag(arguments.tag).into_bytes(),
+                    };
+                    data.write_bytes(response).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_set_quota(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_quota(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    let response = match data.set_quota(arguments).await {
+                        Ok(quota) => StatusResponse::completed(Command::SetQuota)
+                            .with_tag(tag)
+                            .serialize(quota.map(|q| q.into_bytes()).unwrap_or_default()),
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    };
+                    data.write_bytes(response).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn get_quota(&self, root: &str) -> crate::op::Result<QuotaResponse> {
+        if let Some(account_id) = self.get_quota_root_account(root) {
+            if let Some(quota) = self.get_quota_response(account_id).await? {
+                return Ok(quota);
+            }
+        }
+
+        Err(StatusResponse::no("Quota root does not exist.").with_code(ResponseCode::NonExistent))
+    }
+
+    async fn get_quota_root(
+        &self,
+        mailbox_name: String,
+        is_rev2: bool,
+    ) -> crate::op::Result<Vec<u8>> {
+        // Refresh mailboxes
+        self.synchronize_mailboxes(false).await?;
+
+        // Obtain mailbox
+        let account_id = self
+            .get_mailbox_by_name(&mailbox_name)
+            .ok_or_else(|| {
+                StatusResponse::no("Mailbox does not exist.").with_code(ResponseCode::NonExistent)
+            })?
+            .account_id;
+        let quota = self.get_quota_response(account_id).await?;
+
+        let mut response = QuotaRootResponse {
+            mailbox_name,
+            roots: quota.iter().map(|q| q.root.clone()).collect(),
+        }
+        .into_bytes(is_rev2);
+        if let Some(quota) = quota {
+            quota.serialize(&mut response);
+        }
+
+        Ok(response)
+    }
+
+    async fn set_quota(&self, arguments: Arguments) -> crate::op::Result<Option<QuotaResponse>> {
+        // Only administrators can change quotas
+        let access_token = self.get_access_token().await?;
+        if !access_token.is_super_user() {
+            return Err(StatusResponse::no("Only administrators can change quotas.")
+                .with_code(ResponseCode::NoPerm));
+        }
+        let account_id = self
+            .get_quota_root_account(&arguments.name)
+            .ok_or_else(|| {
+                StatusResponse::no("Quota root does not exist.")
+                    .with_code(ResponseCode::NonExistent)
+            })?;
+
+        // Only storage limits are supported
+        let mut quota = 0;
+        for (resource, limit) in arguments.limits {
+            if resource == Resource::Storage {
+                quota = limit.saturating_mul(1024);
+            } else {
+                return Err(StatusResponse::no(format!(
+                    "Resource {} is not supported.",
+                    resource.as_str()
+                ))
+                .with_code(ResponseCode::Cannot));
+            }
+        }
+
+        // Update principal
+        if let DirectoryInner::Internal(store) = &self.jmap.core.storage.directory.store {
+            store
+                .update_account(
+                    QueryBy::Id(account_id),
+                    vec![PrincipalUpdate::set(
+                        PrincipalField::Quota,
+                        PrincipalValue::Integer(quota),
+                    )],
+                )
+                .await
+                .map_err(|err| {
+                    tracing::warn!(parent: &self.span,
+                        event = "error",
+                        context = "set_quota",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to update quota.");
+                    StatusResponse::database_failure()
+                })?;
+        } else {
+            return Err(
+                StatusResponse::no("Quotas are managed by an external directory.")
+                    .with_code(ResponseCode::Cannot),
+            );
+        }
+
+        Ok((quota > 0).then(|| QuotaResponse {
+            root: arguments.name,
+            resources: vec![QuotaResource {
+                resource: Resource::Storage,
+                usage: 0,
+                limit: quota / 1024,
+            }],
+        }))
+    }
+
+    pub async fn get_quota_response(
+        &self,
+        account_id: u32,
+    ) -> crate::op::Result<Option<QuotaResponse>> {
+        let access_token = self.get_access_token().await?;
+        let limit = self.jmap.get_quota(&access_token, account_id).await?;
+        if limit > 0 {
+            let usage = self.jmap.get_used_quota(account_id).await?;
+            Ok(Some(QuotaResponse {
+                root: self.get_quota_root_name(account_id),
+                resources: vec![QuotaResource {
+                    resource: Resource::Storage,
+                    usage: (usage.max(0) as u64).div_ceil(1024),
+                    limit: limit as u64 / 1024,
+                }],
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn get_quota_root_name(&self, account_id: u32) -> String {
+        // The root of the primary account is "", shared accounts use their prefix
+        self.mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.account_id == account_id)
+            .and_then(|account| account.prefix.clone())
+            .unwrap_or_default()
+    }
+
+    fn get_quota_root_account(&self, root: &str) -> Option<u32> {
+        if root.is_empty() {
+            Some(self.account_id)
+        } else {
+            self.mailboxes
+                .lock()
+                .iter()
+                .find(|account| account.prefix.as_deref() == Some(root))
+                .map(|account| account.account_id)
+        }
+    }
+}
@@ -0,0 +1,244 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use imap_proto::{
+    protocol::{append, replace::Arguments},
+    receiver::Request,
+    Command, ResponseCode, ResponseType, StatusResponse,
+};
+
+use crate::core::{MailboxId, SavedSearch, SelectedMailbox, Session, SessionData};
+use common::listener::SessionStream;
+use jmap_proto::types::{acl::Acl, state::StateChange, type_state::DataType};
+use store::{roaring::RoaringBitmap, write::log::ChangeLogBuilder};
+
+use super::ToModSeq;
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_replace(
+        &mut self,
+        request: Request<Command>,
+        is_uid: bool,
+    ) -> crate::OpResult {
+        match request.parse_replace(self.version) {
+            Ok(arguments) => {
+                let (data, src_mailbox) = self.state.mailbox_state();
+
+                // Refresh mailboxes
+                if let Err(err) = data.synchronize_mailboxes(false).await {
+                    return self
+                        .write_bytes(err.with_tag(arguments.tag).into_bytes())
+                        .await;
+                }
+
+                // Obtain destination mailbox
+                let dest_mailbox =
+                    if let Some(mailbox) = data.get_mailbox_by_name(&arguments.mailbox_name) {
+                        mailbox
+                    } else {
+                        return self
+                            .write_bytes(
+                                StatusResponse::no("Mailbox does not exist.")
+                                    .with_tag(arguments.tag)
+                                    .with_code(ResponseCode::TryCreate)
+                                    .into_bytes(),
+                            )
+                            .await;
+                    };
+                let is_qresync = self.is_qresync;
+                let is_condstore = self.is_condstore;
+
+                tokio::spawn(async move {
+                    data.write_bytes(
+                        match data
+                            .replace_message(
+                                arguments,
+                                src_mailbox,
+                                dest_mailbox,
+                                is_uid,
+                                is_qresync,
+                                is_condstore,
+                            )
+                            .await
+                        {
+                            Ok(response) => response,
+                            Err(response) => response,
+                        }
+                        .into_bytes(),
+                    )
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn replace_message(
+        &self,
+        arguments: Arguments,
+        src_mailbox: Arc<SelectedMailbox>,
+        dest_mailbox: MailboxId,
+        is_uid: bool,
+        is_qresync: bool,
+        is_condstore: bool,
+    ) -> crate::op::Result<StatusResponse> {
+        // Verify ACLs
+        let account_id = src_mailbox.id.account_id;
+        if !self
+            .check_mailbox_acl(account_id, src_mailbox.id.mailbox_id, Acl::RemoveItems)
+            .await
+            .map_err(|r| r.with_tag(&arguments.tag))?
+        {
+            return Ok(StatusResponse::no(
+                "You do not have the required permissions to remove messages from this mailbox.",
+            )
+            .with_tag(arguments.tag)
+            .with_code(ResponseCode::NoPerm));
+        }
+
+        // Obtain the message to replace
+        let ids = src_mailbox
+            .sequence_to_ids(&arguments.sequence_set, is_uid)
+            .await
+            .map_err(|r| r.with_tag(&arguments.tag))?;
+        let replaced_id = match ids.keys().next() {
+            Some(id) if ids.len() == 1 => *id,
+            _ => {
+                return Ok(StatusResponse::no("No such message.").with_tag(arguments.tag));
+            }
+        };
+
+        // Append the replacement message
+        let (mut response, appended_ids) = self
+            .append_messages(
+                append::Arguments {
+                    tag: arguments.tag.clone(),
+                    mailbox_name: arguments.mailbox_name,
+                    messages: vec![arguments.message],
+                },
+                src_mailbox.clone().into(),
+                dest_mailbox,
+                is_qresync,
+            )
+            .await?;
+        if response.rtype != ResponseType::Ok {
+            return Ok(response);
+        }
+
+        // Expunge the replaced message, removing the replacement on failure
+        let mut changelog = ChangeLogBuilder::new();
+        if let Err(err) = self
+            .email_untag_or_delete(
+                account_id,
+                src_mailbox.id.mailbox_id,
+                RoaringBitmap::from_iter([replaced_id]),
+                &mut changelog,
+            )
+            .await
+        {
+            self.rollback_replace(dest_mailbox, appended_ids).await;
+            return Err(err.with_tag(&arguments.tag));
+        }
+        response.tag = None;
+        response.message = "Replacement Message ID".into();
+        self.write_bytes(response.into_bytes()).await;
+        if !changelog.is_empty() {
+            let change_id = self
+                .jmap
+                .commit_changes(account_id, changelog)
+                .await
+                .map_err(|r| StatusResponse::from(r).with_tag(&arguments.tag))?;
+            self.jmap
+                .broadcast_state_change(
+                    StateChange::new(account_id)
+                        .with_change(DataType::Email, change_id)
+                        .with_change(DataType::Mailbox, change_id)
+                        .with_change(DataType::Thread, change_id),
+                )
+                .await;
+        }
+
+        // Clear saved searches
+        *src_mailbox.saved_search.lock() = SavedSearch::None;
+
+        // Synchronize messages
+        let modseq = self
+            .write_mailbox_changes(&src_mailbox, is_qresync)
+            .await
+            .map_err(|r| r.with_tag(&arguments.tag))?;
+        let mut response = StatusResponse::completed(Command::Replace(is_uid));
+        if is_condstore {
+            response = response.with_code(ResponseCode::HighestModseq {
+                modseq: modseq.to_modseq(),
+            });
+        }
+
+        Ok(response.with_tag(arguments.tag))
+    }
+
+    async fn rollback_replace(&self, mailbox: MailboxId, appended_ids: Vec<u32>) {
+        let mut changelog = ChangeLogBuilder::new();
+        if self
+            .email_untag_or_delete(
+                mailbox.account_id,
+                mailbox.mailbox_id,
+                RoaringBitmap::from_iter(appended_ids),
+                &mut changelog,
+            )
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                parent: &self.span,
+                context = "replace",
+                event = "error",
+                account_id = mailbox.account_id,
+                mailbox_id = mailbox.mailbox_id,
+                "Failed to remove replacement message."
+            );
+            return;
+        }
+
+        if !changelog.is_empty() {
+            if let Ok(change_id) = self
+                .jmap
+                .commit_changes(mailbox.account_id, changelog)
+                .await
+            {
+                self.jmap
+                    .broadcast_state_change(
+                        StateChange::new(mailbox.account_id)
+                            .with_change(DataType::Email, change_id)
+                            .with_change(DataType::Mailbox, change_id)
+                            .with_change(DataType::Thread, change_id),
+                    )
+                    .await;
+            }
+        }
+    }
+}
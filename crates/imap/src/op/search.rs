@@ -224,6 +224,18 @@ impl<T: SessionStream> SessionData<T> {
             results_tx.send(saved_results).ok();
         }
 
+        // Apply partial range
+        let partial = arguments
+            .result_options
+            .iter()
+            .find_map(|option| match option {
+                ResultOption::Partial(range) => Some(*range),
+                _ => None,
+            });
+        if let Some(partial) = &partial {
+            imap_ids = partial.apply(imap_ids);
+        }
+
         // Build response
         Ok(Response {
             is_uid,
@@ -236,6 +248,7 @@ impl<T: SessionStream> SessionData<T> {
             },
             ids: if arguments.result_options.is_empty()
                 || arguments.result_options.contains(&ResultOption::All)
+                || partial.is_some()
             {
                 imap_ids
             } else {
@@ -244,6 +257,7 @@ impl<T: SessionStream> SessionData<T> {
             is_sort,
             is_esearch: arguments.is_esearch,
             highest_modseq,
+            partial,
         })
     }
 
@@ -438,7 +452,7 @@ impl<T: SessionStream> SessionData<T> {
                         }
                         filters.push(query::Filter::is_in_set(set));
                     }
-                    search::Filter::All => {
+                    search::Filter::All | search::Filter::SaveDateSupported => {
                         filters.push(query::Filter::is_in_set(message_ids.clone()));
                     }
                     search::Filter::Answered => {
@@ -632,6 +646,18 @@ impl<T: SessionStream> SessionData<T> {
                             )));
                         }
                     }
+                    search::Filter::SavedBefore(date) => {
+                        filters.push(query::Filter::lt(Property::SaveDate, date as u64));
+                    }
+                    search::Filter::SavedOn(date) => {
+                        filters.push(query::Filter::And);
+                        filters.push(query::Filter::ge(Property::SaveDate, date as u64));
+                        filters.push(query::Filter::lt(Property::SaveDate, (date + 86400) as u64));
+                        filters.push(query::Filter::End);
+                    }
+                    search::Filter::SavedSince(date) => {
+                        filters.push(query::Filter::ge(Property::SaveDate, date as u64));
+                    }
                     search::Filter::ThreadId(id) => {
                         if let Some(id) = Id::from_bytes(id.as_bytes()) {
                             filters.push(query::Filter::is_in_bitmap(
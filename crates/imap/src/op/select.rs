@@ -143,6 +143,7 @@ impl<T: SessionStream> Session<T> {
                                     attributes: vec![fetch::Attribute::Flags],
                                     changed_since: qresync.modseq.into(),
                                     include_vanished: true,
+                                    partial: None,
                                 },
                                 mailbox.clone(),
                                 true,
@@ -169,6 +170,12 @@ impl<T: SessionStream> Session<T> {
                             .to_string(),
                     };
 
+                    // Add quota
+                    let mut response = response.serialize();
+                    if let Ok(Some(quota)) = data.get_quota_response(mailbox.id.account_id).await {
+                        quota.serialize(&mut response);
+                    }
+
                     // Update state
                     self.state = State::Selected { data, mailbox };
 
@@ -180,7 +187,7 @@ impl<T: SessionStream> Session<T> {
                             } else {
                                 ResponseCode::ReadOnly
                             })
-                            .serialize(response.serialize()),
+                            .serialize(response),
                     )
                     .await
                 } else {
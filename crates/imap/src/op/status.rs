@@ -42,7 +42,7 @@ use store::{
 };
 use store::{Deserialize, U32_LEN};
 
-use super::ToModSeq;
+use super::{append::append_limit, ToModSeq};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_status(&mut self, request: Request<Command>) -> crate::OpResult {
@@ -114,11 +114,13 @@ impl<T: SessionStream> SessionData<T> {
                                     | Status::Unseen
                                     | Status::Recent
                                     | Status::Deleted
+                                    | Status::DeletedStorage
                                     | Status::HighestModSeq => StatusItemType::Number(0),
                                     Status::UidNext | Status::UidValidity => {
                                         StatusItemType::Number(1)
                                     }
                                     Status::MailboxId => StatusItemType::String("none".to_string()),
+                                    Status::AppendLimit => StatusItemType::Number(0),
                                 },
                             )
                         })
@@ -178,6 +180,13 @@ impl<T: SessionStream> SessionData<T> {
                                 items_update.push_unique(*item);
                             }
                         }
+                        Status::DeletedStorage => {
+                            if let Some(value) = mailbox_state.total_deleted_storage {
+                                items_response.push((*item, StatusItemType::Number(value as u64)));
+                            } else {
+                                items_update.push_unique(*item);
+                            }
+                        }
                         Status::Size => {
                             if let Some(value) = mailbox_state.size {
                                 items_response.push((*item, StatusItemType::Number(value as u64)));
@@ -200,6 +209,12 @@ impl<T: SessionStream> SessionData<T> {
                                 ),
                             ));
                         }
+                        Status::AppendLimit => {
+                            items_response.push((
+                                *item,
+                                StatusItemType::Number(append_limit(&self.jmap) as u64),
+                            ));
+                        }
                         Status::Recent => {
                             if !update_recent {
                                 items_response.push((*item, StatusItemType::Number(0)));
@@ -320,6 +335,25 @@ impl<T: SessionStream> SessionData<T> {
                             0
                         }
                     }
+                    Status::DeletedStorage => {
+                        if let (Some(mailbox_message_ids), Some(mut deleted)) = (
+                            &mailbox_message_ids,
+                            self.jmap
+                                .get_tag(
+                                    mailbox.account_id,
+                                    Collection::Email,
+                                    Property::Keywords,
+                                    Keyword::Deleted,
+                                )
+                                .await?,
+                        ) {
+                            deleted &= mailbox_message_ids.as_ref();
+                            self.calculate_mailbox_size(mailbox.account_id, &Arc::new(deleted))
+                                .await? as u64
+                        } else {
+                            0
+                        }
+                    }
                     Status::Size => {
                         if let Some(mailbox_message_ids) = &mailbox_message_ids {
                             self.calculate_mailbox_size(mailbox.account_id, mailbox_message_ids)
@@ -332,7 +366,7 @@ impl<T: SessionStream> SessionData<T> {
                         self.fetch_messages(&mailbox).await?;
                         0
                     }
-                    Status::HighestModSeq | Status::MailboxId => {
+                    Status::HighestModSeq | Status::MailboxId | Status::AppendLimit => {
                         unreachable!()
                     }
                 };
@@ -356,6 +390,9 @@ impl<T: SessionStream> SessionData<T> {
                             Status::UidValidity => mailbox_state.uid_validity = value.into(),
                             Status::Unseen => mailbox_state.total_unseen = value.into(),
                             Status::Deleted => mailbox_state.total_deleted = value.into(),
+                            Status::DeletedStorage => {
+                                mailbox_state.total_deleted_storage = value.into()
+                            }
                             Status::Size => mailbox_state.size = value.into(),
                             Status::Recent => {
                                 items_response
@@ -364,7 +401,7 @@ impl<T: SessionStream> SessionData<T> {
                                     .unwrap()
                                     .1 = StatusItemType::Number(0);
                             }
-                            Status::HighestModSeq | Status::MailboxId => {
+                            Status::HighestModSeq | Status::MailboxId | Status::AppendLimit => {
                                 unreachable!()
                             }
                         }
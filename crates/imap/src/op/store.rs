@@ -35,7 +35,10 @@ use imap_proto::{
     receiver::Request,
     Command, ResponseCode, ResponseType, StatusResponse,
 };
-use jmap::{email::set::TagManager, mailbox::UidMailbox};
+use jmap::{
+    email::{set::TagManager, spam::SpamTrainAction},
+    mailbox::UidMailbox,
+};
 use jmap_proto::{
     error::method::MethodError,
     types::{
@@ -196,6 +199,7 @@ impl<T: SessionStream> SessionData<T> {
             .collect::<Vec<_>>();
         let mut changelog = ChangeLogBuilder::new();
         let mut changed_mailboxes = AHashSet::new();
+        let mut train_ids = Vec::new();
         'outer: for (id, imap_id) in ids {
             let mut try_count = 0;
             loop {
@@ -248,6 +252,8 @@ impl<T: SessionStream> SessionData<T> {
                     let seen_changed = keywords
                         .changed_tags()
                         .any(|keyword| keyword == &Keyword::Seen);
+                    let train_action =
+                        SpamTrainAction::from_keywords(keywords.added(), keywords.removed());
                     let flags = if !arguments.is_silent {
                         keywords
                             .current()
@@ -298,6 +304,9 @@ impl<T: SessionStream> SessionData<T> {
                                 }
                             }
                             changelog.log_update(Collection::Email, Id::from_parts(thread_id, id));
+                            if let Some(train_action) = train_action {
+                                train_ids.push((id, train_action));
+                            }
 
                             // Add item to response
                             let modseq = changelog.change_id + 1;
@@ -371,6 +380,11 @@ impl<T: SessionStream> SessionData<T> {
                 .await;
         }
 
+        // Train the spam classifier on $Junk/$NotJunk changes
+        for (id, train_action) in train_ids {
+            self.jmap.spam_train(account_id, id, train_action).await;
+        }
+
         // Send response
         Ok(response.serialize(items.serialize()))
     }
@@ -0,0 +1,222 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use common::listener::SessionStream;
+use imap_proto::{
+    protocol::urlauth::{Arguments, GenUrlAuthResponse, UrlFetchResponse},
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+use jmap::{email::urlauth::ImapUrl, JMAP};
+use jmap_proto::types::{acl::Acl, collection::Collection};
+
+use crate::core::{MailboxId, Session, SessionData};
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_genurlauth(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_urlauth(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    let response = match data.generate_urls(arguments).await {
+                        Ok(response) => StatusResponse::completed(Command::GenUrlAuth)
+                            .with_tag(tag)
+                            .serialize(response.into_bytes()),
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    };
+                    data.write_bytes(response).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_resetkey(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_urlauth(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    let response = match data.reset_keys(arguments).await {
+                        Ok(_) => StatusResponse::completed(Command::ResetKey).with_tag(tag),
+                        Err(response) => response.with_tag(tag),
+                    };
+                    data.write_bytes(response.into_bytes()).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_urlfetch(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_urlauth(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    let response = match data.fetch_urls(arguments).await {
+                        Ok(response) => StatusResponse::completed(Command::UrlFetch)
+                            .with_tag(tag)
+                            .serialize(response.into_bytes()),
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    };
+                    data.write_bytes(response).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn generate_urls(&self, arguments: Arguments) -> crate::op::Result<GenUrlAuthResponse> {
+        // Refresh mailboxes
+        self.synchronize_mailboxes(false).await?;
+
+        let access_token = self.get_access_token().await?;
+        let mut urls = Vec::with_capacity(arguments.urls.len());
+        for (url, mechanism) in arguments.urls.into_iter().zip(arguments.mechanisms) {
+            // Only authorization rumps for messages owned by the user can be signed
+            let parsed = ImapUrl::parse(&url)
+                .filter(|parsed| {
+                    parsed.verifier.is_none()
+                        && parsed
+                            .user
+                            .as_ref()
+                            .is_some_and(|user| user.eq_ignore_ascii_case(&access_token.name))
+                })
+                .ok_or_else(|| {
+                    StatusResponse::no(format!("Invalid URLAUTH rump {url:?}."))
+                        .with_code(ResponseCode::BadUrl { url: url.clone() })
+                })?;
+            let rump = parsed.rump.as_deref().ok_or_else(|| {
+                StatusResponse::no("URL does not contain an access identifier.")
+                    .with_code(ResponseCode::BadUrl { url: url.clone() })
+            })?;
+            let mailbox = self.get_own_mailbox(&parsed.mailbox)?;
+
+            // Sign URL
+            let key = self
+                .jmap
+                .urlauth_key(mailbox.account_id, mailbox.mailbox_id, true)
+                .await?
+                .unwrap_or_default();
+            urls.push(format!(
+                "{rump}:{}:{}",
+                mechanism.as_str(),
+                JMAP::urlauth_token(&key, rump)
+            ));
+        }
+
+        Ok(GenUrlAuthResponse { urls })
+    }
+
+    async fn reset_keys(&self, arguments: Arguments) -> crate::op::Result<()> {
+        if let Some(mailbox_name) = &arguments.mailbox_name {
+            // Refresh mailboxes
+            self.synchronize_mailboxes(false).await?;
+
+            let mailbox = self.get_own_mailbox(mailbox_name)?;
+            self.jmap
+                .urlauth_reset_keys(mailbox.account_id, [mailbox.mailbox_id])
+                .await?;
+        } else if let Some(mailbox_ids) = self
+            .jmap
+            .get_document_ids(self.account_id, Collection::Mailbox)
+            .await?
+        {
+            self.jmap
+                .urlauth_reset_keys(self.account_id, mailbox_ids)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn fetch_urls(&self, arguments: Arguments) -> crate::op::Result<UrlFetchResponse> {
+        let access_token = self.get_access_token().await?;
+        let mut items = Vec::with_capacity(arguments.urls.len());
+        for url in arguments.urls {
+            let contents = self
+                .jmap
+                .urlauth_fetch(&url, &access_token.name, false)
+                .await?;
+            items.push((url, contents));
+        }
+
+        Ok(UrlFetchResponse { items })
+    }
+
+    pub(crate) async fn fetch_url(&self, url: &str) -> crate::op::Result<Option<Vec<u8>>> {
+        let parsed = if let Some(parsed) = ImapUrl::parse(url) {
+            parsed
+        } else {
+            return Ok(None);
+        };
+        let access_token = self.get_access_token().await?;
+
+        if parsed.access.is_some() {
+            // URLAUTH-authorized URL
+            return self
+                .jmap
+                .urlauth_fetch(url, &access_token.name, false)
+                .await
+                .map_err(Into::into);
+        } else if parsed
+            .user
+            .as_ref()
+            .is_some_and(|user| !user.eq_ignore_ascii_case(&access_token.name))
+        {
+            return Ok(None);
+        }
+
+        match self.get_mailbox_by_name(&parsed.mailbox) {
+            Some(mailbox)
+                if self
+                    .check_mailbox_acl(mailbox.account_id, mailbox.mailbox_id, Acl::ReadItems)
+                    .await? =>
+            {
+                self.jmap
+                    .imap_url_contents(mailbox.account_id, mailbox.mailbox_id, &parsed)
+                    .await
+                    .map_err(Into::into)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn get_own_mailbox(&self, mailbox_name: &str) -> crate::op::Result<MailboxId> {
+        self.get_mailbox_by_name(mailbox_name)
+            .filter(|mailbox| mailbox.account_id == self.account_id)
+            .ok_or_else(|| {
+                StatusResponse::no("Mailbox does not exist.").with_code(ResponseCode::NonExistent)
+            })
+    }
+}
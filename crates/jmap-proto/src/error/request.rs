@@ -143,6 +143,14 @@ impl RequestError {
         )
     }
 
+    pub fn totp_required() -> Self {
+        RequestError::blank(
+            402,
+            "TOTP Code Required",
+            "A TOTP code is required to authenticate this account.",
+        )
+    }
+
     pub fn limit(limit_type: RequestLimitError) -> Self {
         RequestError {
             p_type: RequestErrorType::Limit,
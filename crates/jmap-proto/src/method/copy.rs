@@ -102,6 +102,7 @@ pub struct CopyBlobResponse {
 #[derive(Debug, Clone)]
 pub enum RequestArguments {
     Email,
+    ContactCard,
 }
 
 impl JsonObjectParser for CopyRequest<RequestArguments> {
@@ -112,6 +113,7 @@ impl JsonObjectParser for CopyRequest<RequestArguments> {
         let mut request = CopyRequest {
             arguments: match &parser.ctx {
                 MethodObject::Email => RequestArguments::Email,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/copy",
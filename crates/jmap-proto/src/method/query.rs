@@ -117,6 +117,15 @@ pub enum Filter {
     IsActive(bool),
     Scope(String),
     ResourceType(String),
+    InAddressBook(Id),
+    Uid(String),
+    Kind(String),
+    HasMember(String),
+    Phone(String),
+    Organization(String),
+    InCalendars(Vec<Id>),
+    Title(String),
+    Attendee(String),
     _T(String),
 
     And,
@@ -154,6 +163,9 @@ pub enum SortProperty {
     AllInThreadHaveKeyword,
     SomeInThreadHaveKeyword,
     Used,
+    Created,
+    Updated,
+    Start,
     _T(String),
 }
 
@@ -165,6 +177,8 @@ pub enum RequestArguments {
     SieveScript,
     Principal,
     Quota,
+    ContactCard,
+    CalendarEvent,
 }
 
 impl JsonObjectParser for QueryRequest<RequestArguments> {
@@ -180,6 +194,8 @@ impl JsonObjectParser for QueryRequest<RequestArguments> {
                 MethodObject::SieveScript => RequestArguments::SieveScript,
                 MethodObject::Principal => RequestArguments::Principal,
                 MethodObject::Quota => RequestArguments::Quota,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/query",
@@ -443,6 +459,35 @@ pub fn parse_filter(parser: &mut Parser) -> crate::parser::Result<Vec<Filter>> {
                                 .next_token::<String>()?
                                 .unwrap_string("resourceType")?,
                         ),
+                        (0x006b_6f6f_4273_7365_7264_6441_6e69, _) => Filter::InAddressBook(
+                            parser.next_token::<Id>()?.unwrap_string("inAddressBook")?,
+                        ),
+                        (0x0064_6975, _) => {
+                            Filter::Uid(parser.next_token::<String>()?.unwrap_string("uid")?)
+                        }
+                        (0x646e_696b, _) => {
+                            Filter::Kind(parser.next_token::<String>()?.unwrap_string("kind")?)
+                        }
+                        (0x0072_6562_6d65_4d73_6168, _) => Filter::HasMember(
+                            parser.next_token::<String>()?.unwrap_string("hasMember")?,
+                        ),
+                        (0x0065_6e6f_6870, _) => {
+                            Filter::Phone(parser.next_token::<String>()?.unwrap_string("phone")?)
+                        }
+                        (0x6e6f_6974_617a_696e_6167_726f, _) => Filter::Organization(
+                            parser
+                                .next_token::<String>()?
+                                .unwrap_string("organization")?,
+                        ),
+                        (0x0073_7261_646e_656c_6143_6e69, _) => {
+                            Filter::InCalendars(<Vec<Id>>::parse(parser)?)
+                        }
+                        (0x0065_6c74_6974, _) => {
+                            Filter::Title(parser.next_token::<String>()?.unwrap_string("title")?)
+                        }
+                        (0x6565_646e_6574_7461, _) => Filter::Attendee(
+                            parser.next_token::<String>()?.unwrap_string("attendee")?,
+                        ),
                         _ => {
                             if parser.is_eof || parser.skip_string() {
                                 let filter = Filter::_T(
@@ -585,6 +630,9 @@ impl JsonObjectParser for SortProperty {
             0x4b65_7661_4864_6165_7268_546e_496c_6c61 => Ok(SortProperty::AllInThreadHaveKeyword),
             0x6576_6148_6461_6572_6854_6e49_656d_6f73 => Ok(SortProperty::SomeInThreadHaveKeyword),
             0x6465_7375 => Ok(SortProperty::Used),
+            0x0064_6574_6165_7263 => Ok(SortProperty::Created),
+            0x0064_6574_6164_7075 => Ok(SortProperty::Updated),
+            0x0074_7261_7473 => Ok(SortProperty::Start),
             _ => {
                 if parser.is_eof || parser.skip_string() {
                     Ok(SortProperty::_T(
@@ -647,6 +695,15 @@ impl Display for Filter {
             Filter::IsActive(_) => "isActive",
             Filter::ResourceType(_) => "resourceType",
             Filter::Scope(_) => "scope",
+            Filter::InAddressBook(_) => "inAddressBook",
+            Filter::Uid(_) => "uid",
+            Filter::Kind(_) => "kind",
+            Filter::HasMember(_) => "hasMember",
+            Filter::Phone(_) => "phone",
+            Filter::Organization(_) => "organization",
+            Filter::InCalendars(_) => "inCalendars",
+            Filter::Title(_) => "title",
+            Filter::Attendee(_) => "attendee",
             Filter::_T(v) => v.as_str(),
             Filter::And => "and",
             Filter::Or => "or",
@@ -678,6 +735,9 @@ impl Display for SortProperty {
             SortProperty::AllInThreadHaveKeyword => "allInThreadHaveKeyword",
             SortProperty::SomeInThreadHaveKeyword => "someInThreadHaveKeyword",
             SortProperty::Used => "used",
+            SortProperty::Created => "created",
+            SortProperty::Updated => "updated",
+            SortProperty::Start => "start",
             SortProperty::_T(s) => s,
         })
     }
@@ -29,7 +29,11 @@ use crate::{
         method::MethodError,
         set::{InvalidProperty, SetError},
     },
-    object::{email_submission, mailbox, sieve, Object},
+    object::{
+        calendar,
+        contact::{self, parse_jscontact},
+        email_submission, mailbox, sieve, Object,
+    },
     parser::{json::Parser, Error, JsonObjectParser, Token},
     request::{
         method::MethodObject,
@@ -71,6 +75,10 @@ pub enum RequestArguments {
     PushSubscription,
     SieveScript(sieve::SetArguments),
     VacationResponse,
+    AddressBook(contact::SetArguments),
+    ContactCard,
+    Calendar(calendar::SetArguments),
+    CalendarEvent(calendar::EventSetArguments),
 }
 
 #[derive(Debug, Clone, Default, serde::Serialize)]
@@ -131,6 +139,10 @@ impl JsonObjectParser for SetRequest<RequestArguments> {
                 MethodObject::PushSubscription => RequestArguments::PushSubscription,
                 MethodObject::VacationResponse => RequestArguments::VacationResponse,
                 MethodObject::SieveScript => RequestArguments::SieveScript(Default::default()),
+                MethodObject::AddressBook => RequestArguments::AddressBook(Default::default()),
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::Calendar => RequestArguments::Calendar(Default::default()),
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent(Default::default()),
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/set",
@@ -205,6 +217,23 @@ impl JsonObjectParser for Object<SetValue> {
                         .unwrap_string_or_null("")?
                         .map(|id| SetValue::Value(Value::Id(id)))
                         .unwrap_or(SetValue::Value(Value::Null)),
+                    Property::AddressBookIds | Property::CalendarIds => {
+                        if key.patch.is_empty() {
+                            SetValue::from(
+                                <SetValueMap<MaybeReference<Id, String>>>::parse(parser)?.values,
+                            )
+                        } else {
+                            key.patch.push(Value::Bool(bool::parse(parser)?));
+                            SetValue::Patch(key.patch)
+                        }
+                    }
+                    _ if matches!(
+                        parser.ctx,
+                        MethodObject::ContactCard | MethodObject::CalendarEvent
+                    ) =>
+                    {
+                        SetValue::Value(parse_jscontact(parser.next_token()?, parser)?)
+                    }
                     Property::BlobId | Property::Picture => parser
                         .next_token::<MaybeReference<BlobId, String>>()?
                         .unwrap_string_or_null("")?
@@ -238,7 +267,9 @@ impl JsonObjectParser for Object<SetValue> {
                     | Property::Location
                     | Property::Cid
                     | Property::Role
-                    | Property::PartId => parser
+                    | Property::PartId
+                    | Property::Color
+                    | Property::TimeZone => parser
                         .next_token::<String>()?
                         .unwrap_string_or_null("")?
                         .map(|text| SetValue::Value(Value::Text(text)))
@@ -260,7 +291,9 @@ impl JsonObjectParser for Object<SetValue> {
                     Property::HasAttachment
                     | Property::IsSubscribed
                     | Property::IsEnabled
-                    | Property::IsActive => parser
+                    | Property::IsActive
+                    | Property::IsDefault
+                    | Property::IsVisible => parser
                         .next_token::<String>()?
                         .unwrap_bool_or_null("")?
                         .map(|bool| SetValue::Value(Value::Bool(bool)))
@@ -411,6 +444,9 @@ impl RequestPropertyParser for RequestArguments {
             RequestArguments::Mailbox(args) => args.parse(parser, property),
             RequestArguments::EmailSubmission(args) => args.parse(parser, property),
             RequestArguments::SieveScript(args) => args.parse(parser, property),
+            RequestArguments::AddressBook(args) => args.parse(parser, property),
+            RequestArguments::Calendar(args) => args.parse(parser, property),
+            RequestArguments::CalendarEvent(args) => args.parse(parser, property),
             _ => Ok(false),
         }
     }
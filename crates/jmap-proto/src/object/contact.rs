@@ -0,0 +1,101 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::{
+    parser::{json::Parser, Ignore, Token},
+    request::{reference::MaybeReference, RequestProperty, RequestPropertyParser},
+    types::{id::Id, property::Property, value::Value},
+};
+
+use super::Object;
+
+#[derive(Debug, Clone, Default)]
+pub struct SetArguments {
+    pub on_destroy_remove_contents: Option<bool>,
+    pub on_success_set_is_default: Option<MaybeReference<Id, String>>,
+}
+
+impl RequestPropertyParser for SetArguments {
+    fn parse(
+        &mut self,
+        parser: &mut Parser,
+        property: RequestProperty,
+    ) -> crate::parser::Result<bool> {
+        if property.hash[0] == 0x4365_766f_6d65_5279_6f72_7473_6544_6e6f
+            && property.hash[1] == 0x0073_746e_6574_6e6f
+        {
+            self.on_destroy_remove_contents = parser
+                .next_token::<Ignore>()?
+                .unwrap_bool_or_null("onDestroyRemoveContents")?;
+            Ok(true)
+        } else if property.hash[0] == 0x6544_7349_7465_5373_7365_6363_7553_6e6f
+            && property.hash[1] == 0x0074_6c75_6166
+        {
+            self.on_success_set_is_default = parser
+                .next_token::<MaybeReference<Id, String>>()?
+                .unwrap_string_or_null("onSuccessSetIsDefault")?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+// JSContact objects are stored as-is, keeping the client supplied member names
+// at every nesting level so they can be returned without loss.
+pub fn parse_jscontact(
+    token: Token<String>,
+    parser: &mut Parser<'_>,
+) -> crate::parser::Result<Value> {
+    Ok(match token {
+        Token::String(text) => Value::Text(text),
+        Token::DictStart => {
+            let mut properties = Object::with_capacity(4);
+            while let Some(key) = parser.next_dict_key::<String>()? {
+                let value = parse_jscontact(parser.next_token()?, parser)?;
+                properties.append(Property::_T(key), value);
+            }
+            Value::Object(properties)
+        }
+        Token::ArrayStart => {
+            let mut values = Vec::with_capacity(4);
+            loop {
+                match parser.next_token::<String>()? {
+                    Token::Comma => (),
+                    Token::ArrayEnd => break,
+                    token => {
+                        values.push(parse_jscontact(token, parser)?);
+                    }
+                }
+            }
+            Value::List(values)
+        }
+        Token::Integer(v) if v >= 0 => Value::UnsignedInt(v as u64),
+        Token::Integer(v) => Value::Int(v),
+        Token::Float(v) if v >= 0.0 => Value::UnsignedInt(v as u64),
+        Token::Float(v) => Value::Int(v as i64),
+        Token::Boolean(v) => Value::Bool(v),
+        Token::Null => Value::Null,
+        token => return Err(token.error("", "value")),
+    })
+}
@@ -22,6 +22,8 @@
 */
 
 pub mod blob;
+pub mod calendar;
+pub mod contact;
 pub mod email;
 pub mod email_submission;
 pub mod index;
@@ -129,6 +131,7 @@ const LIST: u8 = 9;
 const OBJECT: u8 = 10;
 const ACL: u8 = 11;
 const NULL: u8 = 12;
+const INT: u8 = 13;
 
 impl Serialize for Value {
     fn serialize(self) -> Vec<u8> {
@@ -222,6 +225,10 @@ impl SerializeInto for Value {
                 buf.push(UNSIGNED_INT);
                 v.serialize_into(buf);
             }
+            Value::Int(v) => {
+                buf.push(INT);
+                (*v as u64).serialize_into(buf);
+            }
             Value::Bool(v) => {
                 buf.push(if *v { BOOL_TRUE } else { BOOL_FALSE });
             }
@@ -275,6 +282,7 @@ impl DeserializeFrom for Value {
         match *bytes.next()? {
             TEXT => Some(Value::Text(String::deserialize_from(bytes)?)),
             UNSIGNED_INT => Some(Value::UnsignedInt(bytes.next_leb128()?)),
+            INT => Some(Value::Int(bytes.next_leb128::<u64>()? as i64)),
             BOOL_TRUE => Some(Value::Bool(true)),
             BOOL_FALSE => Some(Value::Bool(false)),
             ID => Some(Value::Id(Id::new(bytes.next_leb128()?))),
@@ -143,7 +143,11 @@ impl Request {
                                 | MethodObject::SieveScript
                                 | MethodObject::Principal
                                 | MethodObject::Quota
-                                | MethodObject::Blob,
+                                | MethodObject::Blob
+                                | MethodObject::AddressBook
+                                | MethodObject::ContactCard
+                                | MethodObject::Calendar
+                                | MethodObject::CalendarEvent,
                             ) => GetRequest::parse(parser).map(RequestMethod::Get),
                             (MethodFunction::Get, MethodObject::SearchSnippet) => {
                                 GetSearchSnippetRequest::parse(parser)
@@ -161,9 +165,10 @@ impl Request {
                             (MethodFunction::QueryChanges, _) => {
                                 QueryChangesRequest::parse(parser).map(RequestMethod::QueryChanges)
                             }
-                            (MethodFunction::Copy, MethodObject::Email) => {
-                                CopyRequest::parse(parser).map(RequestMethod::Copy)
-                            }
+                            (
+                                MethodFunction::Copy,
+                                MethodObject::Email | MethodObject::ContactCard,
+                            ) => CopyRequest::parse(parser).map(RequestMethod::Copy),
                             (MethodFunction::Copy, MethodObject::Blob) => {
                                 CopyBlobRequest::parse(parser).map(RequestMethod::CopyBlob)
                             }
@@ -38,7 +38,11 @@ pub enum Collection {
     SieveScript = 5,
     PushSubscription = 6,
     Principal = 7,
-    None = 8,
+    AddressBook = 8,
+    ContactCard = 9,
+    Calendar = 10,
+    CalendarEvent = 11,
+    None = 12,
 }
 
 impl From<u8> for Collection {
@@ -52,6 +56,10 @@ impl From<u8> for Collection {
             5 => Collection::SieveScript,
             6 => Collection::PushSubscription,
             7 => Collection::Principal,
+            8 => Collection::AddressBook,
+            9 => Collection::ContactCard,
+            10 => Collection::Calendar,
+            11 => Collection::CalendarEvent,
             _ => Collection::None,
         }
     }
@@ -68,6 +76,10 @@ impl From<u64> for Collection {
             5 => Collection::SieveScript,
             6 => Collection::PushSubscription,
             7 => Collection::Principal,
+            8 => Collection::AddressBook,
+            9 => Collection::ContactCard,
+            10 => Collection::Calendar,
+            11 => Collection::CalendarEvent,
             _ => Collection::None,
         }
     }
@@ -97,6 +109,10 @@ impl TryFrom<Collection> for DataType {
             Collection::EmailSubmission => Ok(DataType::EmailSubmission),
             Collection::SieveScript => Ok(DataType::SieveScript),
             Collection::PushSubscription => Ok(DataType::PushSubscription),
+            Collection::AddressBook => Ok(DataType::AddressBook),
+            Collection::ContactCard => Ok(DataType::ContactCard),
+            Collection::Calendar => Ok(DataType::Calendar),
+            Collection::CalendarEvent => Ok(DataType::CalendarEvent),
             _ => Err(()),
         }
     }
@@ -113,6 +129,10 @@ impl Display for Collection {
             Collection::EmailSubmission => write!(f, "emailSubmission"),
             Collection::SieveScript => write!(f, "sieveScript"),
             Collection::Principal => write!(f, "principal"),
+            Collection::AddressBook => write!(f, "addressBook"),
+            Collection::ContactCard => write!(f, "contactCard"),
+            Collection::Calendar => write!(f, "calendar"),
+            Collection::CalendarEvent => write!(f, "calendarEvent"),
             Collection::None => write!(f, ""),
         }
     }
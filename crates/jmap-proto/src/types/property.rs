@@ -27,7 +27,10 @@ use mail_parser::HeaderName;
 use serde::Serialize;
 use store::write::{DeserializeFrom, SerializeInto};
 
-use crate::parser::{json::Parser, Error, JsonObjectParser};
+use crate::{
+    parser::{json::Parser, Error, JsonObjectParser},
+    request::method::MethodObject,
+};
 
 use super::{acl::Acl, id::Id, keyword::Keyword, value::Value};
 
@@ -136,6 +139,30 @@ pub enum Property {
     WarnLimit,
     SoftLimit,
     Scope,
+    Annotations,
+    SaveDate,
+    UrlAuthKey,
+    AddressBookIds,
+    IsDefault,
+    Uid,
+    Kind,
+    Created,
+    Updated,
+    MayRead,
+    MayWrite,
+    MayShare,
+    CalendarIds,
+    Color,
+    IsVisible,
+    TimeZone,
+    UtcStart,
+    UtcEnd,
+    MayReadFreeBusy,
+    MayWriteAll,
+    MayWriteOwn,
+    MayUpdatePrivate,
+    MayRsvp,
+    MayAdmin,
     Digest(DigestProperty),
     Data(DataProperty),
     _T(String),
@@ -264,7 +291,18 @@ impl JsonObjectParser for SetProperty {
 
         if is_patch {
             match &property {
-                Property::MailboxIds | Property::Members => match Id::parse(parser) {
+                // JSContact and JSCalendar patches are JSON pointers resolved on update
+                _ if matches!(
+                    parser.ctx,
+                    MethodObject::ContactCard | MethodObject::CalendarEvent
+                ) && !matches!(property, Property::AddressBookIds | Property::CalendarIds) =>
+                {
+                    property = parser.invalid_property()?;
+                }
+                Property::MailboxIds
+                | Property::Members
+                | Property::AddressBookIds
+                | Property::CalendarIds => match Id::parse(parser) {
                     Ok(id) => {
                         patch.push(Value::Id(id));
                     }
@@ -350,6 +388,7 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
     Some(match first_char {
         b'a' => match hash {
             0x6c63 => Property::Acl,
+            0x0073_6449_6b6f_6f42_7373_6572_6464 => Property::AddressBookIds,
             0x7365_7361_696c => Property::Aliases,
             0x7374_6e65_6d68_6361_7474 => Property::Attachments,
             _ => return None,
@@ -362,10 +401,13 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             _ => return None,
         },
         b'c' => match hash {
+            0x726f_6c6f => Property::Color,
+            0x7364_4972_6164_6e65_6c61 => Property::CalendarIds,
             0x0073_6569_7469_6c69_6261_7061 => Property::Capabilities,
             0x63 => Property::Cc,
             0x7465_7372_6168 => Property::Charset,
             0x6469 => Property::Cid,
+            0x6465_7461_6572 => Property::Created,
             _ => return None,
         },
         b'd' => match hash {
@@ -398,17 +440,20 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             _ => return None,
         },
         b'i' => match hash {
+            0x656c_6269_7369_5673 => Property::IsVisible,
             0x64 => Property::Id,
             0x0064_4979_7469_746e_6564 => Property::IdentityId,
             0x6f54_796c_7065_526e => Property::InReplyTo,
             0x0065_7669_7463_4173 => Property::IsActive,
             0x6465_6c62_616e_4573 => Property::IsEnabled,
             0x0064_6562_6972_6373_6275_5373 => Property::IsSubscribed,
+            0x746c_7561_6665_4473 => Property::IsDefault,
             _ => return None,
         },
         b'k' => match hash {
             0x0073_7965 => Property::Keys,
             0x0073_6472_6f77_7965 => Property::Keywords,
+            0x0064_6e69 => Property::Kind,
             _ => return None,
         },
         b'l' => match hash {
@@ -417,12 +462,21 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             _ => return None,
         },
         b'm' => match hash {
+            0x006e_696d_6441_7961 => Property::MayAdmin,
+            0x5056_5352_7961 => Property::MayRsvp,
+            0x0065_7461_7669_7250_6574_6164_7055_7961 => Property::MayUpdatePrivate,
+            0x6e77_4f65_7469_7257_7961 => Property::MayWriteOwn,
+            0x6c6c_4165_7469_7257_7961 => Property::MayWriteAll,
+            0x7973_7542_6565_7246_6461_6552_7961 => Property::MayReadFreeBusy,
             0x0073_6449_786f_626c_6961 => Property::MailboxIds,
             0x6574_656c_6544_7961 => Property::MayDelete,
             0x0073_6449_626f_6c42_6e64 => Property::MdnBlobIds,
             0x7372_6562_6d65 => Property::Members,
             0x6449_6567_6173_7365 => Property::MessageId,
             0x0073_7468_6769_5279 => Property::MyRights,
+            0x6461_6552_7961 => Property::MayRead,
+            0x0065_7469_7257_7961 => Property::MayWrite,
+            0x0065_7261_6853_7961 => Property::MayShare,
             _ => return None,
         },
         b'n' => match hash {
@@ -459,6 +513,7 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             _ => return None,
         },
         b't' => match hash {
+            0x0065_6e6f_5a65_6d69 => Property::TimeZone,
             0x0079_646f_4274_7865 => Property::TextBody,
             0x6572_7574_616e_6769_5374_7865 => Property::TextSignature,
             0x0064_4964_6165_7268 => Property::ThreadId,
@@ -472,10 +527,14 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             _ => return None,
         },
         b'u' => match hash {
+            0x0064_6e45_6374 => Property::UtcEnd,
+            0x0074_7261_7453_6374 => Property::UtcStart,
             0x0073_7574_6174_536f_646e => Property::UndoStatus,
             0x0073_6c69_616d_4564_6165_726e => Property::UnreadEmails,
             0x7364_6165_7268_5464_6165_726e => Property::UnreadThreads,
             0x6c72 => Property::Url,
+            0x6469 => Property::Uid,
+            0x6465_7461_6470 => Property::Updated,
             _ => return None,
         },
         b'v' => match hash {
@@ -639,6 +698,7 @@ impl JsonObjectParser for ObjectProperty {
             b'c' => match hash {
                 0x7465_7372_6168 => Property::Charset,
                 0x6469 => Property::Cid,
+                0x6465_7461_6572 => Property::Created,
                 _ => parser.invalid_property()?,
             },
             b'd' => match hash {
@@ -898,6 +958,30 @@ impl Display for Property {
             Property::Used => write!(f, "used"),
             Property::HardLimit => write!(f, "hardLimit"),
             Property::Scope => write!(f, "scope"),
+            Property::Annotations => write!(f, "annotations"),
+            Property::SaveDate => write!(f, "saveDate"),
+            Property::UrlAuthKey => write!(f, "urlAuthKey"),
+            Property::AddressBookIds => write!(f, "addressBookIds"),
+            Property::IsDefault => write!(f, "isDefault"),
+            Property::Uid => write!(f, "uid"),
+            Property::Kind => write!(f, "kind"),
+            Property::Created => write!(f, "created"),
+            Property::Updated => write!(f, "updated"),
+            Property::MayRead => write!(f, "mayRead"),
+            Property::MayWrite => write!(f, "mayWrite"),
+            Property::MayShare => write!(f, "mayShare"),
+            Property::CalendarIds => write!(f, "calendarIds"),
+            Property::Color => write!(f, "color"),
+            Property::IsVisible => write!(f, "isVisible"),
+            Property::TimeZone => write!(f, "timeZone"),
+            Property::UtcStart => write!(f, "utcStart"),
+            Property::UtcEnd => write!(f, "utcEnd"),
+            Property::MayReadFreeBusy => write!(f, "mayReadFreeBusy"),
+            Property::MayWriteAll => write!(f, "mayWriteAll"),
+            Property::MayWriteOwn => write!(f, "mayWriteOwn"),
+            Property::MayUpdatePrivate => write!(f, "mayUpdatePrivate"),
+            Property::MayRsvp => write!(f, "mayRSVP"),
+            Property::MayAdmin => write!(f, "mayAdmin"),
             Property::WarnLimit => write!(f, "warnLimit"),
             Property::SoftLimit => write!(f, "softLimit"),
             Property::_T(s) => write!(f, "{s}"),
@@ -1080,6 +1164,30 @@ impl From<&Property> for u8 {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::Annotations => 104,
+            Property::SaveDate => 105,
+            Property::UrlAuthKey => 106,
+            Property::AddressBookIds => 107,
+            Property::IsDefault => 108,
+            Property::Uid => 109,
+            Property::Kind => 110,
+            Property::Created => 111,
+            Property::Updated => 112,
+            Property::MayRead => 113,
+            Property::MayWrite => 114,
+            Property::MayShare => 115,
+            Property::CalendarIds => 116,
+            Property::Color => 117,
+            Property::IsVisible => 118,
+            Property::TimeZone => 119,
+            Property::UtcStart => 120,
+            Property::UtcEnd => 121,
+            Property::MayReadFreeBusy => 122,
+            Property::MayWriteAll => 123,
+            Property::MayWriteOwn => 124,
+            Property::MayUpdatePrivate => 125,
+            Property::MayRsvp => 126,
+            Property::MayAdmin => 127,
             Property::Digest(_) | Property::Data(_) => unreachable!("invalid property"),
         }
     }
@@ -1222,6 +1330,30 @@ impl SerializeInto for Property {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::Annotations => 104,
+            Property::SaveDate => 105,
+            Property::UrlAuthKey => 106,
+            Property::AddressBookIds => 107,
+            Property::IsDefault => 108,
+            Property::Uid => 109,
+            Property::Kind => 110,
+            Property::Created => 111,
+            Property::Updated => 112,
+            Property::MayRead => 113,
+            Property::MayWrite => 114,
+            Property::MayShare => 115,
+            Property::CalendarIds => 116,
+            Property::Color => 117,
+            Property::IsVisible => 118,
+            Property::TimeZone => 119,
+            Property::UtcStart => 120,
+            Property::UtcEnd => 121,
+            Property::MayReadFreeBusy => 122,
+            Property::MayWriteAll => 123,
+            Property::MayWriteOwn => 124,
+            Property::MayUpdatePrivate => 125,
+            Property::MayRsvp => 126,
+            Property::MayAdmin => 127,
             Property::Digest(_) | Property::Data(_) => {
                 unreachable!("Property::Digest and Property::Data are not serializable")
             }
@@ -1340,6 +1472,30 @@ impl DeserializeFrom for Property {
             101 => Some(Property::WarnLimit),
             102 => Some(Property::SoftLimit),
             103 => Some(Property::Scope),
+            104 => Some(Property::Annotations),
+            105 => Some(Property::SaveDate),
+            106 => Some(Property::UrlAuthKey),
+            107 => Some(Property::AddressBookIds),
+            108 => Some(Property::IsDefault),
+            109 => Some(Property::Uid),
+            110 => Some(Property::Kind),
+            111 => Some(Property::Created),
+            112 => Some(Property::Updated),
+            113 => Some(Property::MayRead),
+            114 => Some(Property::MayWrite),
+            115 => Some(Property::MayShare),
+            116 => Some(Property::CalendarIds),
+            117 => Some(Property::Color),
+            118 => Some(Property::IsVisible),
+            119 => Some(Property::TimeZone),
+            120 => Some(Property::UtcStart),
+            121 => Some(Property::UtcEnd),
+            122 => Some(Property::MayReadFreeBusy),
+            123 => Some(Property::MayWriteAll),
+            124 => Some(Property::MayWriteOwn),
+            125 => Some(Property::MayUpdatePrivate),
+            126 => Some(Property::MayRsvp),
+            127 => Some(Property::MayAdmin),
             _ => None,
         }
     }
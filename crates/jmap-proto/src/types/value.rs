@@ -48,6 +48,7 @@ use super::{
 pub enum Value {
     Text(String),
     UnsignedInt(u64),
+    Int(i64),
     Bool(bool),
     Id(Id),
     Date(UTCDate),
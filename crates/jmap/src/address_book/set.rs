@@ -0,0 +1,615 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::set::{SetRequest, SetResponse},
+    object::{
+        contact::SetArguments,
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    request::reference::MaybeReference,
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        id::Id,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{MaybePatchValue, SetValue, Value},
+    },
+};
+use store::{
+    query::Filter,
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder},
+};
+
+use crate::{
+    auth::{acl::EffectiveAcl, AccessToken},
+    mailbox::set::MailboxSubscribe,
+    JMAP,
+};
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::Name)
+        .index_as(IndexAs::Text {
+            tokenize: true,
+            index: true,
+        })
+        .required(),
+    IndexProperty::new(Property::SortOrder).index_as(IndexAs::Integer),
+    IndexProperty::new(Property::IsSubscribed).index_as(IndexAs::IntegerList),
+    IndexProperty::new(Property::Acl).index_as(IndexAs::Acl),
+];
+
+impl JMAP {
+    pub async fn address_book_set(
+        &self,
+        mut request: SetRequest<SetArguments>,
+        access_token: &AccessToken,
+    ) -> Result<SetResponse, MethodError> {
+        // Prepare response
+        let account_id = request.account_id.document_id();
+        let on_destroy_remove_contents = request
+            .arguments
+            .on_destroy_remove_contents
+            .unwrap_or(false);
+        let is_shared = access_token.is_shared(account_id);
+        let mut response = self
+            .prepare_set_response(&request, Collection::AddressBook)
+            .await?;
+        let mut address_book_ids = self.address_book_get_or_create(account_id).await?;
+        let will_destroy = request.unwrap_destroy();
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        for (id, object) in request.unwrap_create() {
+            if is_shared {
+                response.not_created.append(
+                    id,
+                    SetError::forbidden().with_description(
+                        "You are not allowed to create address books in this account.",
+                    ),
+                );
+                continue;
+            }
+
+            match self
+                .address_book_set_item(object, None, &response, access_token)
+                .await?
+            {
+                Ok(builder) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::AddressBook)
+                        .create_document()
+                        .custom(builder);
+                    let document_id = self.write_batch_expect_id(batch).await?;
+                    changes.log_insert(Collection::AddressBook, document_id);
+                    address_book_ids.insert(document_id);
+                    response.created(id, document_id);
+                }
+                Err(err) => {
+                    response.not_created.append(id, err);
+                }
+            }
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain address book
+            let document_id = id.document_id();
+            let address_book = if let Some(address_book) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::AddressBook,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                address_book
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+
+            // Validate ACL
+            if is_shared {
+                let acl = address_book.inner.effective_acl(access_token);
+                if !acl.contains(Acl::Modify) {
+                    response.not_updated.append(
+                        id,
+                        SetError::forbidden()
+                            .with_description("You are not allowed to modify this address book."),
+                    );
+                    continue 'update;
+                } else if object.properties.contains_key(&Property::Acl)
+                    && !acl.contains(Acl::Administer)
+                {
+                    response.not_updated.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "You are not allowed to change the permissions of this address book.",
+                        ),
+                    );
+                    continue 'update;
+                }
+            }
+
+            match self
+                .address_book_set_item(object, address_book.into(), &response, access_token)
+                .await?
+            {
+                Ok(builder) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::AddressBook)
+                        .update_document(document_id)
+                        .custom(builder);
+                    if !batch.is_empty() {
+                        match self.core.storage.data.write(batch.build()).await {
+                            Ok(_) => {
+                                changes.log_update(Collection::AddressBook, document_id);
+                            }
+                            Err(store::Error::AssertValueFailed) => {
+                                response.not_updated.append(
+                                    id,
+                                    SetError::forbidden().with_description(
+                                        "Another process modified this address book, please try again.",
+                                    ),
+                                );
+                                continue 'update;
+                            }
+                            Err(err) => {
+                                tracing::error!(
+                                    event = "error",
+                                    context = "address_book_set",
+                                    account_id = account_id,
+                                    error = ?err,
+                                    "Failed to update address book(s).");
+                                return Err(MethodError::ServerPartialFail);
+                            }
+                        }
+                    }
+                    response.updated.append(id, None);
+                }
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                }
+            }
+        }
+
+        // Process default address book changes
+        if let Some(default_id) = request.arguments.on_success_set_is_default {
+            let default_id = match default_id {
+                MaybeReference::Value(id) => Some(id),
+                MaybeReference::Reference(id_ref) => {
+                    response.get_id(&id_ref).and_then(|id| id.try_unwrap_id())
+                }
+            };
+
+            match default_id {
+                Some(default_id)
+                    if address_book_ids.contains(default_id.document_id())
+                        && !will_destroy.contains(&default_id) =>
+                {
+                    if !is_shared {
+                        self.address_book_set_default(
+                            account_id,
+                            default_id.document_id(),
+                            &mut changes,
+                            &mut response,
+                        )
+                        .await?;
+                    } else {
+                        response.not_updated.append(
+                            default_id,
+                            SetError::forbidden().with_description(
+                                "You are not allowed to change the default address book.",
+                            ),
+                        );
+                    }
+                }
+                _ => {
+                    return Err(MethodError::InvalidArguments(
+                        "Invalid onSuccessSetIsDefault address book id.".to_string(),
+                    ));
+                }
+            }
+        }
+
+        // Process deletions
+        let mut did_remove_cards = false;
+        for id in will_destroy {
+            match self
+                .address_book_destroy(
+                    account_id,
+                    id.document_id(),
+                    &mut changes,
+                    access_token,
+                    on_destroy_remove_contents,
+                )
+                .await?
+            {
+                Ok(removed_cards) => {
+                    did_remove_cards |= removed_cards;
+                    response.destroyed.push(id);
+                }
+                Err(err) => {
+                    response.not_destroyed.append(id, err);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            let change_id = self.commit_changes(account_id, changes).await?;
+            let state_change =
+                StateChange::new(account_id).with_change(DataType::AddressBook, change_id);
+            response.state_change = if did_remove_cards {
+                state_change.with_change(DataType::ContactCard, change_id)
+            } else {
+                state_change
+            }
+            .into();
+            response.new_state = Some(change_id.into());
+        }
+
+        Ok(response)
+    }
+
+    async fn address_book_set_item(
+        &self,
+        changes_: Object<SetValue>,
+        current: Option<HashedValue<Object<Value>>>,
+        response: &SetResponse,
+        access_token: &AccessToken,
+    ) -> Result<Result<ObjectIndexBuilder, SetError>, MethodError> {
+        // Parse properties
+        let mut changes = Object::with_capacity(changes_.properties.len());
+        for (property, value) in changes_.properties {
+            let value = match response.eval_object_references(value) {
+                Ok(value) => value,
+                Err(err) => {
+                    return Ok(Err(err));
+                }
+            };
+            let value = match (&property, value) {
+                (Property::Name, MaybePatchValue::Value(Value::Text(value))) => {
+                    let value = value.trim();
+                    if !value.is_empty() && value.len() < self.core.jmap.mailbox_name_max_len {
+                        Value::Text(value.to_string())
+                    } else {
+                        return Ok(Err(SetError::invalid_properties()
+                            .with_property(Property::Name)
+                            .with_description(
+                                if !value.is_empty() {
+                                    "Address book name is too long."
+                                } else {
+                                    "Address book name cannot be empty."
+                                }
+                                .to_string(),
+                            )));
+                    }
+                }
+                (Property::Description, MaybePatchValue::Value(Value::Text(value))) => {
+                    Value::Text(value)
+                }
+                (Property::Description, MaybePatchValue::Value(Value::Null)) => Value::Null,
+                (Property::SortOrder, MaybePatchValue::Value(Value::UnsignedInt(value))) => {
+                    Value::UnsignedInt(value)
+                }
+                (Property::IsSubscribed, MaybePatchValue::Value(Value::Bool(subscribe))) => {
+                    if let Some(current) = current.as_ref() {
+                        if let Some(value) = current
+                            .inner
+                            .mailbox_subscribe(access_token.primary_id(), subscribe)
+                        {
+                            value
+                        } else {
+                            continue;
+                        }
+                    } else if subscribe {
+                        Value::List(vec![Value::Id(access_token.primary_id().into())])
+                    } else {
+                        continue;
+                    }
+                }
+                (Property::Acl, value) => {
+                    match self.acl_set(&mut changes, current.as_ref(), value).await {
+                        Ok(_) => continue,
+                        Err(err) => {
+                            return Ok(Err(err));
+                        }
+                    }
+                }
+                _ => {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value.".to_string())))
+                }
+            };
+
+            changes.append(property, value);
+        }
+
+        // Refresh ACLs
+        if changes.properties.contains_key(&Property::Acl) {
+            self.refresh_acls(&changes, &current);
+        }
+
+        // Validate
+        Ok(ObjectIndexBuilder::new(SCHEMA)
+            .with_changes(changes)
+            .with_current_opt(current)
+            .validate())
+    }
+
+    async fn address_book_set_default(
+        &self,
+        account_id: u32,
+        default_id: u32,
+        changes: &mut ChangeLogBuilder,
+        response: &mut SetResponse,
+    ) -> Result<(), MethodError> {
+        let address_book_ids = self
+            .get_document_ids(account_id, Collection::AddressBook)
+            .await?
+            .unwrap_or_default();
+
+        for (document_id, address_book) in self
+            .get_properties::<HashedValue<Object<Value>>, _, _>(
+                account_id,
+                Collection::AddressBook,
+                &address_book_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            let is_default = document_id == default_id;
+            if matches!(
+                address_book.inner.get(&Property::IsDefault),
+                Value::Bool(true)
+            ) == is_default
+            {
+                continue;
+            }
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::AddressBook)
+                .update_document(document_id)
+                .custom(
+                    ObjectIndexBuilder::new(SCHEMA)
+                        .with_current(address_book)
+                        .with_changes(Object::with_capacity(1).with_property(
+                            Property::IsDefault,
+                            if is_default {
+                                Value::Bool(true)
+                            } else {
+                                Value::Null
+                            },
+                        )),
+                );
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => {
+                    changes.log_update(Collection::AddressBook, document_id);
+                    let id = Id::from(document_id);
+                    if !response.updated.contains_key(&id) {
+                        response.updated.append(id, None);
+                    }
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_updated.append(
+                        Id::from(document_id),
+                        SetError::forbidden().with_description(
+                            "Another process modified this address book, please try again.",
+                        ),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "address_book_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to update default address book.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn address_book_destroy(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        changes: &mut ChangeLogBuilder,
+        access_token: &AccessToken,
+        remove_contents: bool,
+    ) -> Result<Result<bool, SetError>, MethodError> {
+        // Obtain address book
+        let address_book = if let Some(address_book) = self
+            .get_property::<HashedValue<Object<Value>>>(
+                account_id,
+                Collection::AddressBook,
+                document_id,
+                Property::Value,
+            )
+            .await?
+        {
+            address_book
+        } else {
+            return Ok(Err(SetError::not_found()));
+        };
+
+        // Validate ACLs
+        if access_token.is_shared(account_id) {
+            let acl = address_book.inner.effective_acl(access_token);
+            if !acl.contains(Acl::Administer) {
+                if !acl.contains(Acl::Delete) {
+                    return Ok(Err(SetError::forbidden().with_description(
+                        "You are not allowed to delete this address book.",
+                    )));
+                } else if remove_contents && !acl.contains(Acl::RemoveItems) {
+                    return Ok(Err(SetError::forbidden().with_description(
+                        "You are not allowed to delete contacts from this address book.",
+                    )));
+                }
+            }
+        }
+
+        // The default address book cannot be deleted
+        if matches!(
+            address_book.inner.get(&Property::IsDefault),
+            Value::Bool(true)
+        ) && !access_token.is_super_user()
+        {
+            return Ok(Err(SetError::forbidden().with_description(
+                "You are not allowed to delete the default address book.",
+            )));
+        }
+
+        // Verify that the address book is empty
+        let mut did_remove_cards = false;
+        let card_ids = self
+            .filter(
+                account_id,
+                Collection::ContactCard,
+                vec![Filter::eq(Property::AddressBookIds, document_id)],
+            )
+            .await?
+            .results;
+        if !card_ids.is_empty() {
+            if remove_contents {
+                if let Err(err) = self
+                    .contact_card_remove_address_book(account_id, document_id, &card_ids, changes)
+                    .await?
+                {
+                    return Ok(Err(err));
+                }
+                did_remove_cards = true;
+            } else {
+                return Ok(Err(SetError::new(SetErrorType::AddressBookHasContents)
+                    .with_description("Address book is not empty.")));
+            }
+        }
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::AddressBook)
+            .delete_document(document_id)
+            .custom(ObjectIndexBuilder::new(SCHEMA).with_current(address_book));
+
+        match self.core.storage.data.write(batch.build()).await {
+            Ok(_) => {
+                changes.log_delete(Collection::AddressBook, document_id);
+                Ok(Ok(did_remove_cards))
+            }
+            Err(store::Error::AssertValueFailed) => Ok(Err(SetError::forbidden()
+                .with_description(concat!(
+                    "Another process modified this address book ",
+                    "while deleting it, please try again."
+                )))),
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "address_book_set",
+                    account_id = account_id,
+                    document_id = document_id,
+                    error = ?err,
+                    "Failed to delete address book.");
+                Err(MethodError::ServerPartialFail)
+            }
+        }
+    }
+
+    pub async fn address_book_get_or_create(
+        &self,
+        account_id: u32,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let mut address_book_ids = self
+            .get_document_ids(account_id, Collection::AddressBook)
+            .await?
+            .unwrap_or_default();
+        if !address_book_ids.is_empty() {
+            return Ok(address_book_ids);
+        }
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::AddressBook)
+            .create_document_with_id(0)
+            .custom(
+                ObjectIndexBuilder::new(SCHEMA).with_changes(
+                    Object::with_capacity(3)
+                        .with_property(
+                            Property::Name,
+                            self.core.jmap.contacts_default_address_book.clone(),
+                        )
+                        .with_property(Property::IsDefault, Value::Bool(true))
+                        .with_property(
+                            Property::IsSubscribed,
+                            Value::List(vec![Value::Id(account_id.into())]),
+                        ),
+                ),
+            );
+        address_book_ids.insert(0);
+
+        self.core
+            .storage
+            .data
+            .write(batch.build())
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                event = "error",
+                context = "address_book_get_or_create",
+                error = ?err,
+                "Failed to create address book.");
+                MethodError::ServerPartialFail
+            })?;
+
+        Ok(address_book_ids)
+    }
+}
@@ -48,6 +48,7 @@ use jmap_proto::{
 use crate::{
     auth::oauth::OAuthMetadata,
     blob::{DownloadResponse, UploadResponse},
+    dav::DavResponse,
     services::state,
     JmapInstance, JMAP,
 };
@@ -281,11 +282,24 @@ impl JMAP {
                         return self.handle_autoconfig_request(&req).await;
                     }
                 }
+                ("caldav", _) => {
+                    return DavResponse::new(StatusCode::MOVED_PERMANENTLY)
+                        .with_header(header::LOCATION, "/dav/cal/")
+                        .into_http_response();
+                }
+                ("carddav", _) => {
+                    return DavResponse::new(StatusCode::MOVED_PERMANENTLY)
+                        .with_header(header::LOCATION, "/dav/card/")
+                        .into_http_response();
+                }
                 (_, &Method::OPTIONS) => {
                     return ().into_http_response();
                 }
                 _ => (),
             },
+            "dav" => {
+                return self.handle_dav_request(req, &session).await;
+            }
             "auth" => match (path.next().unwrap_or_default(), req.method()) {
                 ("device", &Method::POST) => {
                     return match self.is_anonymous_allowed(&session.remote_ip).await {
@@ -316,6 +330,21 @@ impl JMAP {
                 // Authenticate user
                 return match self.authenticate_headers(&req, session.remote_ip).await {
                     Ok(Some((_, access_token))) => {
+                        // Accounts required to use two-factor authentication
+                        // can only sign in and enroll until they do so
+                        if !matches!(path.next(), Some("totp" | "oauth"))
+                            && self
+                                .is_totp_enrollment_required(&session, &access_token)
+                                .await
+                        {
+                            return RequestError::blank(
+                                403,
+                                "Two-Factor Authentication Required",
+                                "Two-factor authentication has to be enabled for this account.",
+                            )
+                            .into_http_response();
+                        }
+
                         let body = fetch_body(&mut req, 1024 * 1024).await;
                         self.handle_api_manage_request(&req, body, access_token)
                             .await
@@ -340,6 +369,11 @@ impl JMAP {
                         .await;
                 }
             }
+            "metrics" => {
+                if req.method() == Method::GET {
+                    return self.handle_metrics_request(&req).await;
+                }
+            }
             "robots.txt" => {
                 return Resource {
                     content_type: "text/plain",
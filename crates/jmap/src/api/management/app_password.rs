@@ -0,0 +1,230 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use directory::{
+    backend::internal::{
+        lookup::DirectoryStore, manage::ManageDirectory, PrincipalField, PrincipalUpdate,
+        PrincipalValue,
+    },
+    core::secret::{AppPassword, AppPasswordScope},
+    QueryBy,
+};
+use hyper::Method;
+use jmap_proto::error::request::RequestError;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde_json::json;
+use store::write::now;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    auth::AccessToken,
+    JMAP,
+};
+
+use super::{decode_path_element, ManagementApiError};
+
+const APP_PASSWORD_LEN: usize = 24;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct AppPasswordRequest {
+    pub name: String,
+    pub scopes: Vec<AppPasswordScope>,
+    #[serde(default)]
+    pub expires: Option<u64>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct AppPasswordResponse {
+    pub name: String,
+    pub scopes: Vec<AppPasswordScope>,
+    pub created: u64,
+    pub expires: Option<u64>,
+    #[serde(rename = "lastUsed")]
+    pub last_used: Option<u64>,
+}
+
+impl JMAP {
+    pub async fn handle_manage_app_password(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        access_token: Arc<AccessToken>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        // Make sure the current directory supports updates
+        if let Some(response) = self.assert_supported_directory() {
+            return response;
+        }
+
+        // App passwords are not available for the fallback administrator
+        let account_id = access_token.primary_id();
+        if account_id == u32::MAX {
+            return ManagementApiError::Unsupported {
+                details: "App passwords are not available for this account".into(),
+            }
+            .into_http_response();
+        }
+
+        // Obtain the app passwords issued to this account
+        let app_passwords = match self
+            .core
+            .storage
+            .data
+            .query(QueryBy::Id(account_id), false)
+            .await
+        {
+            Ok(Some(principal)) => principal.app_passwords().collect::<Vec<_>>(),
+            Ok(None) => return RequestError::not_found().into_http_response(),
+            Err(err) => return err.into_http_response(),
+        };
+
+        match (path.get(1), req.method()) {
+            (None, &Method::GET) => {
+                let mut items = Vec::with_capacity(app_passwords.len());
+                for app_password in app_passwords {
+                    items.push(AppPasswordResponse {
+                        last_used: self
+                            .core
+                            .app_password_last_used(account_id, &app_password)
+                            .await,
+                        name: app_password.name,
+                        scopes: app_password.scopes,
+                        created: app_password.created,
+                        expires: app_password.expires,
+                    });
+                }
+
+                JsonResponse::new(json!({
+                    "data": items,
+                }))
+                .into_http_response()
+            }
+            (None, &Method::POST) => {
+                let request = match serde_json::from_slice::<AppPasswordRequest>(
+                    body.as_deref().unwrap_or_default(),
+                ) {
+                    Ok(request) => request,
+                    Err(err) => return err.into_http_response(),
+                };
+
+                // Validate request
+                let name = request.name.trim().to_string();
+                if !AppPassword::is_valid_name(&name) {
+                    return ManagementApiError::Other {
+                        details: "Invalid app password name".into(),
+                    }
+                    .into_http_response();
+                } else if app_passwords.iter().any(|p| p.name == name) {
+                    return ManagementApiError::FieldAlreadyExists {
+                        field: "name".into(),
+                        value: name.into(),
+                    }
+                    .into_http_response();
+                } else if request.scopes.is_empty() {
+                    return ManagementApiError::FieldMissing {
+                        field: "scopes".into(),
+                    }
+                    .into_http_response();
+                } else if request.expires.is_some_and(|expires| expires <= now()) {
+                    return ManagementApiError::Other {
+                        details: "Expiration date is in the past".into(),
+                    }
+                    .into_http_response();
+                }
+
+                // Generate password
+                let password = thread_rng()
+                    .sample_iter(Alphanumeric)
+                    .take(APP_PASSWORD_LEN)
+                    .map(char::from)
+                    .collect::<String>();
+                let mut scopes = request.scopes;
+                scopes.sort_unstable_by_key(|scope| scope.as_str());
+                scopes.dedup();
+                let app_password =
+                    AppPassword::new(name.clone(), scopes, request.expires, &password);
+
+                match self
+                    .core
+                    .storage
+                    .data
+                    .update_account(
+                        QueryBy::Id(account_id),
+                        vec![PrincipalUpdate::add_item(
+                            PrincipalField::Secrets,
+                            PrincipalValue::String(app_password.to_string()),
+                        )],
+                    )
+                    .await
+                {
+                    Ok(_) => JsonResponse::new(json!({
+                        "data": {
+                            "name": name,
+                            "password": password,
+                        },
+                    }))
+                    .into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            (Some(name), &Method::DELETE) => {
+                let name = decode_path_element(name);
+                let Some(app_password) = app_passwords.into_iter().find(|p| p.name == name) else {
+                    return ManagementApiError::NotFound {
+                        item: name.into_owned().into(),
+                    }
+                    .into_http_response();
+                };
+
+                match self
+                    .core
+                    .storage
+                    .data
+                    .update_account(
+                        QueryBy::Id(account_id),
+                        vec![PrincipalUpdate::remove_item(
+                            PrincipalField::Secrets,
+                            PrincipalValue::String(app_password.to_string()),
+                        )],
+                    )
+                    .await
+                {
+                    Ok(_) => {
+                        self.core
+                            .remove_app_password_last_used(account_id, &app_password)
+                            .await;
+
+                        JsonResponse::new(json!({
+                            "data": (),
+                        }))
+                        .into_http_response()
+                    }
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            _ => RequestError::not_found().into_http_response(),
+        }
+    }
+}
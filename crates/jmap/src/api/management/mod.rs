@@ -21,6 +21,7 @@
  * for more details.
 */
 
+pub mod app_password;
 pub mod dkim;
 pub mod domain;
 pub mod log;
@@ -31,6 +32,7 @@ pub mod report;
 pub mod settings;
 pub mod sieve;
 pub mod stores;
+pub mod totp;
 
 use std::{borrow::Cow, sync::Arc};
 
@@ -78,7 +80,7 @@ impl JMAP {
         let is_superuser = access_token.is_super_user();
 
         match path.first().copied().unwrap_or_default() {
-            "queue" if is_superuser => self.handle_manage_queue(req, path).await,
+            "queue" if is_superuser => self.handle_manage_queue(req, path, body).await,
             "settings" if is_superuser => self.handle_manage_settings(req, path, body).await,
             "reports" if is_superuser => self.handle_manage_reports(req, path).await,
             "principal" if is_superuser => self.handle_manage_principal(req, path, body).await,
@@ -106,6 +108,11 @@ impl JMAP {
             "password" if req.method() == Method::POST => {
                 self.handle_change_password(req, access_token, body).await
             }
+            "app-password" => {
+                self.handle_manage_app_password(req, path, access_token, body)
+                    .await
+            }
+            "totp" => self.handle_manage_totp(req, path, access_token, body).await,
             _ => RequestError::not_found().into_http_response(),
         }
     }
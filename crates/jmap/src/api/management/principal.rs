@@ -28,6 +28,7 @@ use directory::{
         lookup::DirectoryStore, manage::ManageDirectory, PrincipalField, PrincipalUpdate,
         PrincipalValue,
     },
+    core::secret::is_password_secret,
     DirectoryError, DirectoryInner, ManagementError, Principal, QueryBy, Type,
 };
 
@@ -341,6 +342,27 @@ impl JMAP {
             return response;
         }
 
+        // Keep any app passwords, TOTP secrets and recovery codes issued to the account
+        let mut secrets = vec![new_password];
+        match self
+            .core
+            .storage
+            .data
+            .query(QueryBy::Id(access_token.primary_id()), false)
+            .await
+        {
+            Ok(Some(principal)) => {
+                secrets.extend(
+                    principal
+                        .secrets
+                        .into_iter()
+                        .filter(|secret| !is_password_secret(secret)),
+                );
+            }
+            Ok(None) => (),
+            Err(err) => return err.into_http_response(),
+        }
+
         // Update password
         match self
             .core
@@ -350,7 +372,7 @@ impl JMAP {
                 QueryBy::Id(access_token.primary_id()),
                 vec![PrincipalUpdate::set(
                     PrincipalField::Secrets,
-                    PrincipalValue::StringList(vec![new_password]),
+                    PrincipalValue::StringList(secrets),
                 )],
             )
             .await
@@ -21,22 +21,29 @@
  * for more details.
 */
 
-use std::str::FromStr;
+use std::{net::IpAddr, str::FromStr, sync::atomic::Ordering};
 
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-use hyper::Method;
+use hyper::{Method, StatusCode};
 use jmap_proto::error::request::RequestError;
 use mail_auth::{
     dmarc::URI,
     mta_sts::ReportUri,
     report::{self, tlsrpt::TlsReport},
 };
-use mail_parser::DateTime;
+use mail_parser::{DateTime, MessageParser};
 use serde::{Deserializer, Serializer};
 use serde_json::json;
-use smtp::queue::{self, ErrorDetails, HostResponse, QueueId, Status};
+use smtp::queue::{
+    self,
+    quarantine::{QuarantineSource, QuarantinedMessage},
+    spool::LockedMessage,
+    ErrorDetails, HostResponse, PolicyResult, QueueId, Status, MAIL_RELAY_OVERRIDE,
+};
 use store::{
-    write::{key::DeserializeBigEndian, now, Bincode, QueueClass, ReportEvent, ValueClass},
+    write::{
+        key::DeserializeBigEndian, now, BatchBuilder, Bincode, QueueClass, ReportEvent, ValueClass,
+    },
     Deserialize, IterateParams, ValueKey,
 };
 use utils::url_params::UrlParams;
@@ -62,7 +69,13 @@ pub struct Message {
     pub priority: i16,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub env_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub relay_host: Option<String>,
     pub blob_hash: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub history: Vec<Attempt>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
@@ -91,6 +104,124 @@ pub struct Recipient {
     pub orcpt: Option<String>,
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct Attempt {
+    pub domain: String,
+    pub retry_num: u32,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub attempted_at: DateTime,
+    pub duration: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<AttemptTls>,
+    #[serde(default)]
+    pub mta_sts: PolicyResult,
+    #[serde(default)]
+    pub dane: PolicyResult,
+    pub status: Status<String, String>,
+    pub recipients: Vec<AttemptRecipient>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct AttemptTls {
+    pub version: String,
+    pub cipher: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct AttemptRecipient {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub status: Status<String, String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct Trace {
+    pub id: QueueId,
+    pub return_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub message_id: Option<String>,
+    pub size: usize,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub received_at: DateTime,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    #[serde(serialize_with = "serialize_maybe_datetime")]
+    #[serde(default)]
+    pub completed_at: Option<DateTime>,
+    pub recipients: Vec<AttemptRecipient>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct Quarantined {
+    pub id: QueueId,
+    pub return_path: String,
+    pub recipients: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub subject: Option<String>,
+    pub reason: String,
+    pub source: QuarantineSource,
+    pub remote_ip: IpAddr,
+    pub size: usize,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub quarantined_at: DateTime,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub expires: DateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub preview: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[serde(tag = "action")]
+#[serde(rename_all = "snake_case")]
+pub enum MessageUpdate {
+    RemoveRecipient {
+        address: String,
+    },
+    SetPriority {
+        priority: i16,
+    },
+    SetRelayHost {
+        #[serde(default)]
+        relay_host: Option<String>,
+    },
+    SetExpires {
+        #[serde(deserialize_with = "deserialize_datetime")]
+        #[serde(serialize_with = "serialize_datetime")]
+        expires: DateTime,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        domain: Option<String>,
+    },
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct HostThrottle {
+    pub mx: String,
+    pub concurrency: u64,
+    pub in_flight: u64,
+    pub interval: u64,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    #[serde(serialize_with = "serialize_maybe_datetime")]
+    #[serde(default)]
+    pub next_delivery: Option<DateTime>,
+    pub deferral_rate: f64,
+    pub attempts: u64,
+    pub deferrals: u64,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    #[serde(serialize_with = "serialize_maybe_datetime")]
+    #[serde(default)]
+    pub last_deferral: Option<DateTime>,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 pub enum Report {
@@ -121,7 +252,12 @@ pub enum Report {
 }
 
 impl JMAP {
-    pub async fn handle_manage_queue(&self, req: &HttpRequest, path: Vec<&str>) -> HttpResponse {
+    pub async fn handle_manage_queue(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
         let params = UrlParams::new(req.uri().query());
 
         match (
@@ -235,14 +371,37 @@ impl JMAP {
                     .read_message(queue_id.parse().unwrap_or_default())
                     .await
                 {
+                    let mut result = Message::from(&message);
+                    if (message.flags & MAIL_RELAY_OVERRIDE) != 0 {
+                        result.relay_host = self.smtp.read_message_relay(message.id).await;
+                    }
+                    result.history = self
+                        .smtp
+                        .read_message_history(message.id)
+                        .await
+                        .iter()
+                        .map(Attempt::from)
+                        .collect();
+
                     JsonResponse::new(json!({
-                            "data": Message::from(&message),
+                            "data": result,
                     }))
                     .into_http_response()
                 } else {
                     RequestError::not_found().into_http_response()
                 }
             }
+            ("messages", Some(queue_id), &Method::POST) if path.get(3) == Some(&"edit") => {
+                match serde_json::from_slice::<Vec<MessageUpdate>>(
+                    body.as_deref().unwrap_or_default(),
+                ) {
+                    Ok(updates) => {
+                        self.handle_update_message(queue_id.parse().unwrap_or_default(), updates)
+                            .await
+                    }
+                    Err(err) => err.into_http_response(),
+                }
+            }
             ("messages", Some(queue_id), &Method::PATCH) => {
                 let time = params
                     .parse::<Timestamp>("at")
@@ -370,6 +529,244 @@ impl JMAP {
                     RequestError::not_found().into_http_response()
                 }
             }
+            ("trace", None, &Method::GET) => {
+                let text = params.get("text").map(|t| t.to_lowercase());
+                let from = params.get("from").map(|f| f.to_lowercase());
+                let to = params.get("to").map(|t| t.to_lowercase());
+                let domain = params.get("domain").map(|d| d.to_lowercase());
+                let message_id = params
+                    .get("message-id")
+                    .map(|id| id.trim_start_matches('<').trim_end_matches('>'));
+                let before = params
+                    .get("before")
+                    .and_then(DateTime::parse_rfc3339)
+                    .map(|dt| dt.to_timestamp() as u64);
+                let after = params
+                    .get("after")
+                    .and_then(DateTime::parse_rfc3339)
+                    .map(|dt| dt.to_timestamp() as u64);
+                let page = params.parse::<usize>("page").unwrap_or_default();
+                let limit = params.parse::<usize>("limit").unwrap_or_default();
+                let max_total = params.parse::<usize>("max-total").unwrap_or_default();
+
+                let mut traces = Vec::new();
+                let from_key = ValueKey::from(ValueClass::Queue(QueueClass::MessageTrace {
+                    id: 0,
+                    expires: 0,
+                }));
+                let to_key = ValueKey::from(ValueClass::Queue(QueueClass::MessageTrace {
+                    id: u64::MAX,
+                    expires: u64::MAX,
+                }));
+                let _ =
+                    self.core
+                        .storage
+                        .data
+                        .iterate(IterateParams::new(from_key, to_key), |_, value| {
+                            let trace =
+                                Bincode::<queue::trace::MessageTrace>::deserialize(value)?.inner;
+                            let matches =
+                                text.as_ref().is_none_or(|text| {
+                                    trace.matches(text)
+                                        || trace
+                                            .message_id
+                                            .as_ref()
+                                            .is_some_and(|id| id.to_lowercase().contains(text))
+                                }) && from.as_ref().is_none_or(|from| {
+                                    trace.return_path.to_lowercase().contains(from)
+                                }) && to.as_ref().is_none_or(|to| {
+                                    trace
+                                        .recipients
+                                        .iter()
+                                        .any(|r| r.address.to_lowercase().contains(to))
+                                }) && domain.as_ref().is_none_or(|domain| {
+                                    trace.recipients.iter().any(|r| {
+                                        r.address
+                                            .rsplit_once('@')
+                                            .is_some_and(|(_, d)| d.eq_ignore_ascii_case(domain))
+                                    })
+                                }) && message_id.is_none_or(|message_id| {
+                                    trace.message_id.as_deref() == Some(message_id)
+                                }) && before.is_none_or(|before| trace.received_at < before)
+                                    && after.is_none_or(|after| trace.received_at > after);
+
+                            if matches {
+                                traces.push(trace);
+                            }
+
+                            Ok(true)
+                        })
+                        .await;
+
+                // Traces are keyed by expiration, return them in reception order
+                traces.sort_unstable_by_key(|trace| std::cmp::Reverse(trace.received_at));
+                if max_total > 0 {
+                    traces.truncate(max_total);
+                }
+                let total = traces.len();
+                let result = traces
+                    .iter()
+                    .skip(page.saturating_sub(1) * limit)
+                    .take(if limit > 0 { limit } else { total })
+                    .map(Trace::from)
+                    .collect::<Vec<_>>();
+
+                JsonResponse::new(json!({
+                        "data": {
+                            "items": result,
+                            "total": total,
+                        },
+                }))
+                .into_http_response()
+            }
+            ("quarantine", None, &Method::GET) => {
+                let text = params.get("text").map(|t| t.to_lowercase());
+                let from = params.get("from").map(|f| f.to_lowercase());
+                let to = params.get("to").map(|t| t.to_lowercase());
+                let page = params.parse::<usize>("page").unwrap_or_default();
+                let limit = params.parse::<usize>("limit").unwrap_or_default();
+
+                let entries = self
+                    .smtp
+                    .list_quarantined()
+                    .await
+                    .into_iter()
+                    .filter(|entry| {
+                        text.as_ref().is_none_or(|text| {
+                            entry.message.return_path_lcase.contains(text)
+                                || entry
+                                    .message
+                                    .recipients
+                                    .iter()
+                                    .any(|r| r.address_lcase.contains(text))
+                                || entry.reason.to_lowercase().contains(text)
+                                || entry
+                                    .subject
+                                    .as_ref()
+                                    .is_some_and(|s| s.to_lowercase().contains(text))
+                        }) && from
+                            .as_ref()
+                            .is_none_or(|from| entry.message.return_path_lcase.contains(from))
+                            && to.as_ref().is_none_or(|to| {
+                                entry
+                                    .message
+                                    .recipients
+                                    .iter()
+                                    .any(|r| r.address_lcase.contains(to))
+                            })
+                    })
+                    .collect::<Vec<_>>();
+                let total = entries.len();
+                let items = entries
+                    .iter()
+                    .skip(page.saturating_sub(1) * limit)
+                    .take(if limit > 0 { limit } else { total })
+                    .map(Quarantined::from)
+                    .collect::<Vec<_>>();
+
+                JsonResponse::new(json!({
+                        "data": {
+                            "items": items,
+                            "total": total,
+                        },
+                }))
+                .into_http_response()
+            }
+            ("quarantine", Some(id), &Method::GET) => {
+                if let Some(entry) = self
+                    .smtp
+                    .read_quarantined(id.parse().unwrap_or_default())
+                    .await
+                {
+                    let mut result = Quarantined::from(&entry);
+                    result.preview =
+                        self.smtp
+                            .read_quarantined_blob(&entry)
+                            .await
+                            .and_then(|blob| {
+                                MessageParser::new()
+                                    .parse(&blob)
+                                    .and_then(|message| message.body_preview(1024).map(Into::into))
+                            });
+
+                    JsonResponse::new(json!({
+                            "data": result,
+                    }))
+                    .into_http_response()
+                } else {
+                    RequestError::not_found().into_http_response()
+                }
+            }
+            ("quarantine", Some(id), &Method::PATCH) => {
+                let relay_host = params.get("relay-host").map(|r| r.to_string());
+                if relay_host
+                    .as_ref()
+                    .is_some_and(|r| self.core.get_relay_host(r).is_none())
+                {
+                    return RequestError::blank(
+                        StatusCode::BAD_REQUEST.as_u16(),
+                        "Invalid parameters",
+                        "Relay host does not exist.",
+                    )
+                    .into_http_response();
+                }
+
+                if let Some(entry) = self
+                    .smtp
+                    .read_quarantined(id.parse().unwrap_or_default())
+                    .await
+                {
+                    JsonResponse::new(json!({
+                            "data": self.smtp.release_quarantined(entry, relay_host).await,
+                    }))
+                    .into_http_response()
+                } else {
+                    RequestError::not_found().into_http_response()
+                }
+            }
+            ("quarantine", Some(id), &Method::DELETE) => {
+                if let Some(entry) = self
+                    .smtp
+                    .read_quarantined(id.parse().unwrap_or_default())
+                    .await
+                {
+                    JsonResponse::new(json!({
+                            "data": self.smtp.delete_quarantined(&entry).await,
+                    }))
+                    .into_http_response()
+                } else {
+                    RequestError::not_found().into_http_response()
+                }
+            }
+            ("throttle", None, &Method::GET) => {
+                let now = now();
+                let items = self
+                    .smtp
+                    .adaptive_throttle_status()
+                    .into_iter()
+                    .map(|(mx, host)| HostThrottle {
+                        mx,
+                        concurrency: host.concurrency,
+                        in_flight: host.in_flight.load(Ordering::Relaxed),
+                        interval: host.interval,
+                        next_delivery: (host.next_delivery > now)
+                            .then(|| DateTime::from_timestamp(host.next_delivery as i64)),
+                        deferral_rate: host.deferral_rate,
+                        attempts: host.attempts,
+                        deferrals: host.deferrals,
+                        last_deferral: (host.last_deferral != 0)
+                            .then(|| DateTime::from_timestamp(host.last_deferral as i64)),
+                    })
+                    .collect::<Vec<_>>();
+
+                JsonResponse::new(json!({
+                        "data": {
+                            "total": items.len(),
+                            "items": items,
+                        },
+                }))
+                .into_http_response()
+            }
             ("reports", None, &Method::GET) => {
                 let domain = params.get("domain").map(|d| d.to_lowercase());
                 let type_ = params.get("type").and_then(|t| match t {
@@ -511,6 +908,134 @@ impl JMAP {
     }
 }
 
+impl JMAP {
+    async fn handle_update_message(
+        &self,
+        queue_id: QueueId,
+        updates: Vec<MessageUpdate>,
+    ) -> HttpResponse {
+        let (mut message, event) = match self.smtp.lock_message(queue_id).await {
+            LockedMessage::Locked { message, event } => (message, event),
+            LockedMessage::Busy => {
+                return RequestError::blank(
+                    StatusCode::CONFLICT.as_u16(),
+                    "Message locked",
+                    "The message is currently being delivered, try again later.",
+                )
+                .into_http_response();
+            }
+            LockedMessage::NotFound => return RequestError::not_found().into_http_response(),
+        };
+
+        // Apply changes
+        let mut relay_host = None;
+        let now = now();
+        for update in updates {
+            let result = match update {
+                MessageUpdate::RemoveRecipient { address } => {
+                    let address = address.to_lowercase();
+                    if let Some(pos) = message
+                        .recipients
+                        .iter()
+                        .position(|rcpt| rcpt.address_lcase == address)
+                    {
+                        let domain_idx = message.recipients.remove(pos).domain_idx;
+                        if message.recipients.is_empty() {
+                            Err("Cannot remove all recipients, cancel the message instead.")
+                        } else {
+                            // Remove the domain if it has no recipients left
+                            if !message
+                                .recipients
+                                .iter()
+                                .any(|rcpt| rcpt.domain_idx == domain_idx)
+                            {
+                                message.domains.remove(domain_idx);
+                                for rcpt in &mut message.recipients {
+                                    if rcpt.domain_idx > domain_idx {
+                                        rcpt.domain_idx -= 1;
+                                    }
+                                }
+                            }
+                            Ok(())
+                        }
+                    } else {
+                        Err("Recipient not found.")
+                    }
+                }
+                MessageUpdate::SetPriority { priority } => {
+                    message.priority = priority;
+                    Ok(())
+                }
+                MessageUpdate::SetRelayHost {
+                    relay_host: Some(name),
+                } if self.core.get_relay_host(&name).is_none() => Err("Relay host does not exist."),
+                MessageUpdate::SetRelayHost { relay_host: name } => {
+                    relay_host = Some(name);
+                    Ok(())
+                }
+                MessageUpdate::SetExpires { expires, domain } => {
+                    let expires = expires.to_timestamp() as u64;
+                    if expires > now {
+                        let mut found = false;
+                        for queue_domain in &mut message.domains {
+                            if matches!(
+                                queue_domain.status,
+                                Status::Scheduled | Status::TemporaryFailure(_)
+                            ) && domain
+                                .as_ref()
+                                .is_none_or(|domain| queue_domain.domain == *domain)
+                            {
+                                queue_domain.expires = expires;
+                                found = true;
+                            }
+                        }
+                        if found {
+                            Ok(())
+                        } else {
+                            Err("No pending domains found.")
+                        }
+                    } else {
+                        Err("Expiration time must be in the future.")
+                    }
+                }
+            };
+
+            if let Err(details) = result {
+                self.smtp.unlock_event(event).await;
+                return RequestError::blank(
+                    StatusCode::BAD_REQUEST.as_u16(),
+                    "Invalid parameters",
+                    details,
+                )
+                .into_http_response();
+            }
+        }
+
+        // Save changes along with the relay host, which also releases the lock
+        let mut result = Message::from(message.as_ref());
+        let mut batch = BatchBuilder::new();
+        if let Some(relay_host) = relay_host {
+            result.relay_host = relay_host.clone();
+            message.set_relay_host(&mut batch, relay_host);
+        } else if (message.flags & MAIL_RELAY_OVERRIDE) != 0 {
+            result.relay_host = self.smtp.read_message_relay(message.id).await;
+        }
+        let next_event = message.next_event().unwrap_or_default();
+        if message
+            .save_changes_with(&self.smtp, batch, event.due.into(), next_event.into())
+            .await
+        {
+            let _ = self.smtp.inner.queue_tx.send(queue::Event::Reload).await;
+            JsonResponse::new(json!({
+                    "data": result,
+            }))
+            .into_http_response()
+        } else {
+            RequestError::internal_server_error().into_http_response()
+        }
+    }
+}
+
 impl From<&queue::Message> for Message {
     fn from(message: &queue::Message) -> Self {
         let now = now();
@@ -521,6 +1046,7 @@ impl From<&queue::Message> for Message {
             created: DateTime::from_timestamp(message.created as i64),
             size: message.size,
             priority: message.priority,
+            relay_host: None,
             env_id: message.env_id.clone(),
             domains: message
                 .domains
@@ -528,16 +1054,7 @@ impl From<&queue::Message> for Message {
                 .enumerate()
                 .map(|(idx, domain)| Domain {
                     name: domain.domain.clone(),
-                    status: match &domain.status {
-                        Status::Scheduled => Status::Scheduled,
-                        Status::Completed(_) => Status::Completed(String::new()),
-                        Status::TemporaryFailure(status) => {
-                            Status::TemporaryFailure(status.to_string())
-                        }
-                        Status::PermanentFailure(status) => {
-                            Status::PermanentFailure(status.to_string())
-                        }
-                    },
+                    status: domain_status(&domain.status),
                     retry_num: domain.retry.inner,
                     next_retry: Some(DateTime::from_timestamp(domain.retry.due as i64)),
                     next_notify: if domain.notify.due > now {
@@ -551,18 +1068,7 @@ impl From<&queue::Message> for Message {
                         .filter(|rcpt| rcpt.domain_idx == idx)
                         .map(|rcpt| Recipient {
                             address: rcpt.address.clone(),
-                            status: match &rcpt.status {
-                                Status::Scheduled => Status::Scheduled,
-                                Status::Completed(status) => {
-                                    Status::Completed(status.response.to_string())
-                                }
-                                Status::TemporaryFailure(status) => {
-                                    Status::TemporaryFailure(status.response.to_string())
-                                }
-                                Status::PermanentFailure(status) => {
-                                    Status::PermanentFailure(status.response.to_string())
-                                }
-                            },
+                            status: rcpt_status(&rcpt.status),
                             orcpt: rcpt.orcpt.clone(),
                         })
                         .collect(),
@@ -570,10 +1076,120 @@ impl From<&queue::Message> for Message {
                 })
                 .collect(),
             blob_hash: URL_SAFE_NO_PAD.encode::<&[u8]>(message.blob_hash.as_ref()),
+            history: Vec::new(),
         }
     }
 }
 
+impl From<&queue::HistoryEntry> for Attempt {
+    fn from(entry: &queue::HistoryEntry) -> Self {
+        Attempt {
+            domain: entry.domain.clone(),
+            retry_num: entry.retry_num,
+            attempted_at: DateTime::from_timestamp(entry.attempted_at as i64),
+            duration: entry.duration,
+            mx: entry.mx.clone(),
+            tls: entry.tls.as_ref().map(|tls| AttemptTls {
+                version: tls.version.clone(),
+                cipher: tls.cipher.clone(),
+            }),
+            mta_sts: entry.mta_sts,
+            dane: entry.dane,
+            status: domain_status(&entry.status),
+            recipients: entry
+                .recipients
+                .iter()
+                .map(|rcpt| AttemptRecipient {
+                    address: rcpt.address.clone(),
+                    hostname: rcpt_hostname(&rcpt.status),
+                    status: rcpt_status(&rcpt.status),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&QuarantinedMessage> for Quarantined {
+    fn from(entry: &QuarantinedMessage) -> Self {
+        Quarantined {
+            id: entry.message.id,
+            return_path: entry.message.return_path.clone(),
+            recipients: entry
+                .message
+                .recipients
+                .iter()
+                .map(|rcpt| rcpt.address.clone())
+                .collect(),
+            subject: entry.subject.clone(),
+            reason: entry.reason.clone(),
+            source: entry.source,
+            remote_ip: entry.remote_ip,
+            size: entry.message.size,
+            quarantined_at: DateTime::from_timestamp(entry.quarantined_at as i64),
+            expires: DateTime::from_timestamp(entry.expires as i64),
+            preview: None,
+        }
+    }
+}
+
+impl From<&queue::trace::MessageTrace> for Trace {
+    fn from(trace: &queue::trace::MessageTrace) -> Self {
+        Trace {
+            id: trace.id,
+            return_path: trace.return_path.clone(),
+            message_id: trace.message_id.clone(),
+            size: trace.size,
+            received_at: DateTime::from_timestamp(trace.received_at as i64),
+            completed_at: trace
+                .completed_at
+                .map(|completed_at| DateTime::from_timestamp(completed_at as i64)),
+            recipients: trace
+                .recipients
+                .iter()
+                .map(|rcpt| AttemptRecipient {
+                    address: rcpt.address.clone(),
+                    hostname: rcpt_hostname(&rcpt.status),
+                    status: rcpt_status(&rcpt.status),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn domain_status(status: &Status<(), queue::Error>) -> Status<String, String> {
+    match status {
+        Status::Scheduled => Status::Scheduled,
+        Status::Completed(_) => Status::Completed(String::new()),
+        Status::TemporaryFailure(status) => Status::TemporaryFailure(status.to_string()),
+        Status::PermanentFailure(status) => Status::PermanentFailure(status.to_string()),
+    }
+}
+
+fn rcpt_hostname(
+    status: &Status<HostResponse<String>, HostResponse<ErrorDetails>>,
+) -> Option<String> {
+    match status {
+        Status::Completed(status) => Some(status.hostname.clone()),
+        Status::TemporaryFailure(status) | Status::PermanentFailure(status)
+            if !status.hostname.entity.is_empty() =>
+        {
+            Some(status.hostname.entity.clone())
+        }
+        _ => None,
+    }
+}
+
+fn rcpt_status(
+    status: &Status<HostResponse<String>, HostResponse<ErrorDetails>>,
+) -> Status<String, String> {
+    match status {
+        Status::Scheduled => Status::Scheduled,
+        Status::Completed(status) => Status::Completed(status.response.to_string()),
+        Status::TemporaryFailure(status) => Status::TemporaryFailure(status.response.to_string()),
+        Status::PermanentFailure(status) => Status::PermanentFailure(status.response.to_string()),
+    }
+}
+
 impl Report {
     fn dmarc(event: ReportEvent, report: report::Report, rua: Vec<URI>) -> Self {
         Self::Dmarc {
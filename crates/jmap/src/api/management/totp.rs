@@ -0,0 +1,301 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use common::expr::{functions::ResolveVariable, V_ACCOUNT_TYPE, V_AUTHENTICATED_AS};
+use directory::{
+    backend::internal::{
+        lookup::DirectoryStore, manage::ManageDirectory, PrincipalField, PrincipalUpdate,
+        PrincipalValue,
+    },
+    core::secret::{recovery_code_secret, TotpSecret},
+    QueryBy,
+};
+use hyper::Method;
+use jmap_proto::error::request::RequestError;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde_json::json;
+
+use crate::{
+    api::{
+        http::{HttpSessionData, ToHttpResponse},
+        HttpRequest, HttpResponse, JsonResponse,
+    },
+    auth::AccessToken,
+    JMAP,
+};
+
+use super::ManagementApiError;
+
+const RECOVERY_CODES: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TotpConfirmRequest {
+    pub code: String,
+}
+
+struct TotpPolicy<'x> {
+    session: &'x HttpSessionData,
+    access_token: &'x AccessToken,
+}
+
+impl JMAP {
+    pub async fn handle_manage_totp(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        access_token: Arc<AccessToken>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        // Make sure the current directory supports updates
+        if let Some(response) = self.assert_supported_directory() {
+            return response;
+        }
+
+        // Two-factor authentication is not available for the fallback administrator
+        let account_id = access_token.primary_id();
+        if account_id == u32::MAX {
+            return ManagementApiError::Unsupported {
+                details: "Two-factor authentication is not available for this account".into(),
+            }
+            .into_http_response();
+        }
+
+        // Obtain the account's current TOTP settings
+        let principal = match self
+            .core
+            .storage
+            .data
+            .query(QueryBy::Id(account_id), false)
+            .await
+        {
+            Ok(Some(principal)) => principal,
+            Ok(None) => return RequestError::not_found().into_http_response(),
+            Err(err) => return err.into_http_response(),
+        };
+        let totp = principal.totp_secret();
+
+        match (path.get(1).copied(), req.method()) {
+            (None, &Method::GET) => JsonResponse::new(json!({
+                "data": {
+                    "enabled": totp.as_ref().is_some_and(|totp| totp.enabled),
+                    "recoveryCodes": principal.recovery_codes().count(),
+                },
+            }))
+            .into_http_response(),
+            (None, &Method::POST) => {
+                if totp.as_ref().is_some_and(|totp| totp.enabled) {
+                    return ManagementApiError::Other {
+                        details: "Two-factor authentication is already enabled".into(),
+                    }
+                    .into_http_response();
+                }
+
+                // Replace any pending enrollment with a new secret
+                let new_totp = TotpSecret::generate();
+                let issuer = self
+                    .core
+                    .storage
+                    .config
+                    .get("lookup.default.hostname")
+                    .await
+                    .ok()
+                    .flatten()
+                    .unwrap_or_else(|| "Stalwart Mail Server".to_string());
+                let response = json!({
+                    "data": {
+                        "secret": new_totp.encoded_secret(),
+                        "uri": new_totp.provisioning_uri(&issuer, &principal.name),
+                    },
+                });
+                let mut updates = totp
+                    .map(|totp| {
+                        vec![PrincipalUpdate::remove_item(
+                            PrincipalField::Secrets,
+                            PrincipalValue::String(totp.to_string()),
+                        )]
+                    })
+                    .unwrap_or_default();
+                updates.push(PrincipalUpdate::add_item(
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(new_totp.to_string()),
+                ));
+
+                match self
+                    .core
+                    .storage
+                    .data
+                    .update_account(QueryBy::Id(account_id), updates)
+                    .await
+                {
+                    Ok(_) => JsonResponse::new(response).into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            (Some("confirm"), &Method::POST) => {
+                let request = match serde_json::from_slice::<TotpConfirmRequest>(
+                    body.as_deref().unwrap_or_default(),
+                ) {
+                    Ok(request) => request,
+                    Err(err) => return err.into_http_response(),
+                };
+                let Some(totp) = totp.filter(|totp| !totp.enabled) else {
+                    return ManagementApiError::Other {
+                        details: "No pending two-factor authentication enrollment".into(),
+                    }
+                    .into_http_response();
+                };
+                if !totp.verify(&request.code) {
+                    return ManagementApiError::Other {
+                        details: "Invalid TOTP code".into(),
+                    }
+                    .into_http_response();
+                }
+
+                // Enable TOTP and issue a new set of recovery codes
+                let mut updates = vec![PrincipalUpdate::remove_item(
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(totp.to_string()),
+                )];
+                for code in principal.recovery_codes() {
+                    updates.push(PrincipalUpdate::remove_item(
+                        PrincipalField::Secrets,
+                        PrincipalValue::String(code.to_string()),
+                    ));
+                }
+                updates.push(PrincipalUpdate::add_item(
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(totp.enable().to_string()),
+                ));
+                let mut recovery_codes = Vec::with_capacity(RECOVERY_CODES);
+                for _ in 0..RECOVERY_CODES {
+                    let code = thread_rng()
+                        .sample_iter(Alphanumeric)
+                        .take(RECOVERY_CODE_LEN)
+                        .map(|ch| char::from(ch).to_ascii_lowercase())
+                        .collect::<String>();
+                    updates.push(PrincipalUpdate::add_item(
+                        PrincipalField::Secrets,
+                        PrincipalValue::String(recovery_code_secret(&code)),
+                    ));
+                    recovery_codes.push(code);
+                }
+
+                match self
+                    .core
+                    .storage
+                    .data
+                    .update_account(QueryBy::Id(account_id), updates)
+                    .await
+                {
+                    Ok(_) => JsonResponse::new(json!({
+                        "data": {
+                            "recoveryCodes": recovery_codes,
+                        },
+                    }))
+                    .into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            (None, &Method::DELETE) => {
+                // Remove the TOTP secret along with any unused recovery codes
+                let mut updates = totp
+                    .map(|totp| {
+                        vec![PrincipalUpdate::remove_item(
+                            PrincipalField::Secrets,
+                            PrincipalValue::String(totp.to_string()),
+                        )]
+                    })
+                    .unwrap_or_default();
+                for code in principal.recovery_codes() {
+                    updates.push(PrincipalUpdate::remove_item(
+                        PrincipalField::Secrets,
+                        PrincipalValue::String(code.to_string()),
+                    ));
+                }
+                if !updates.is_empty() {
+                    if let Err(err) = self
+                        .core
+                        .storage
+                        .data
+                        .update_account(QueryBy::Id(account_id), updates)
+                        .await
+                    {
+                        return err.into_http_response();
+                    }
+                }
+
+                JsonResponse::new(json!({
+                    "data": (),
+                }))
+                .into_http_response()
+            }
+            _ => RequestError::not_found().into_http_response(),
+        }
+    }
+
+    pub async fn is_totp_enrollment_required(
+        &self,
+        session: &HttpSessionData,
+        access_token: &AccessToken,
+    ) -> bool {
+        // The fallback administrator is not stored in the directory and cannot enroll
+        access_token.primary_id() != u32::MAX
+            && self
+                .core
+                .eval_if(
+                    &self.core.network.totp_required,
+                    &TotpPolicy {
+                        session,
+                        access_token,
+                    },
+                )
+                .await
+                .unwrap_or(false)
+            && !matches!(
+                self.core
+                    .storage
+                    .directory
+                    .query(QueryBy::Id(access_token.primary_id()), false)
+                    .await,
+                Ok(Some(principal)) if principal.has_totp()
+            )
+    }
+}
+
+impl ResolveVariable for TotpPolicy<'_> {
+    fn resolve_variable(&self, variable: u32) -> common::expr::Variable<'_> {
+        match variable {
+            V_AUTHENTICATED_AS => self.access_token.name.as_str().into(),
+            V_ACCOUNT_TYPE => if self.access_token.is_super_user() {
+                "superuser"
+            } else {
+                "individual"
+            }
+            .into(),
+            _ => self.session.resolve_variable(variable),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use common::manager::webadmin::Resource;
+use hyper::header;
+use jmap_proto::error::request::RequestError;
+use store::{
+    write::{QueueClass, ValueClass},
+    IterateParams, ValueKey,
+};
+use subtle::ConstantTimeEq;
+use utils::metrics::{export_prometheus, QUEUE_MESSAGES};
+
+use crate::JMAP;
+
+use super::{http::ToHttpResponse, HttpRequest, HttpResponse};
+
+impl JMAP {
+    pub async fn handle_metrics_request(&self, req: &HttpRequest) -> HttpResponse {
+        let prometheus = match &self.core.metrics.prometheus {
+            Some(prometheus) => prometheus,
+            None => return RequestError::not_found().into_http_response(),
+        };
+
+        // Validate credentials
+        if let Some(auth) = &prometheus.auth {
+            let is_authorized = req
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|h| h.strip_prefix("Basic "))
+                .is_some_and(|h| bool::from(h.trim().as_bytes().ct_eq(auth.as_bytes())));
+            if !is_authorized {
+                return RequestError::unauthorized().into_http_response();
+            }
+        }
+
+        // Count queued messages
+        let mut total = 0;
+        let from_key = ValueKey::from(ValueClass::Queue(QueueClass::Message(0)));
+        let to_key = ValueKey::from(ValueClass::Queue(QueueClass::Message(u64::MAX)));
+        if let Err(err) = self
+            .core
+            .storage
+            .data
+            .iterate(
+                IterateParams::new(from_key, to_key).ascending().no_values(),
+                |_, _| {
+                    total += 1;
+                    Ok(true)
+                },
+            )
+            .await
+        {
+            tracing::warn!(
+                event = "error",
+                context = "metrics",
+                error = ?err,
+                "Failed to count queued messages."
+            );
+        } else {
+            QUEUE_MESSAGES.set(total);
+        }
+
+        Resource {
+            content_type: "text/plain; version=0.0.4",
+            contents: export_prometheus().into_bytes(),
+        }
+        .into_http_response()
+    }
+}
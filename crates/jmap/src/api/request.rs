@@ -27,7 +27,7 @@ use common::listener::ServerInstance;
 use jmap_proto::{
     error::{method::MethodError, request::RequestError},
     method::{
-        get, query,
+        copy, get, query,
         set::{self},
     },
     request::{method::MethodName, Call, Request, RequestMethod},
@@ -35,6 +35,8 @@ use jmap_proto::{
     types::collection::Collection,
 };
 
+use utils::metrics::{JMAP_METHOD_CALLS, JMAP_REQUESTS, JMAP_REQUEST_DURATION};
+
 use crate::{auth::AccessToken, JMAP};
 
 impl JMAP {
@@ -44,6 +46,9 @@ impl JMAP {
         access_token: Arc<AccessToken>,
         instance: &Arc<ServerInstance>,
     ) -> Result<Response, RequestError> {
+        let _timer = JMAP_REQUEST_DURATION.start_timer();
+        JMAP_REQUESTS.increment();
+
         let mut response = Response::new(
             access_token.state(),
             request.created_ids.unwrap_or_default(),
@@ -60,6 +65,7 @@ impl JMAP {
 
             loop {
                 let mut next_call = None;
+                JMAP_METHOD_CALLS.increment();
 
                 // Add response
                 match self
@@ -187,6 +193,26 @@ impl JMAP {
 
                     self.quota_get(req, access_token).await?.into()
                 }
+                get::RequestArguments::AddressBook => {
+                    access_token.assert_has_access(req.account_id, Collection::AddressBook)?;
+
+                    self.address_book_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::ContactCard => {
+                    access_token.assert_has_access(req.account_id, Collection::ContactCard)?;
+
+                    self.contact_card_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::Calendar => {
+                    access_token.assert_has_access(req.account_id, Collection::Calendar)?;
+
+                    self.calendar_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::CalendarEvent => {
+                    access_token.assert_has_access(req.account_id, Collection::CalendarEvent)?;
+
+                    self.calendar_event_get(req, access_token).await?.into()
+                }
                 get::RequestArguments::Blob(arguments) => {
                     access_token.assert_is_member(req.account_id)?;
 
@@ -234,6 +260,16 @@ impl JMAP {
 
                     self.quota_query(req, access_token).await?.into()
                 }
+                query::RequestArguments::ContactCard => {
+                    access_token.assert_has_access(req.account_id, Collection::ContactCard)?;
+
+                    self.contact_card_query(req, access_token).await?.into()
+                }
+                query::RequestArguments::CalendarEvent => {
+                    access_token.assert_has_access(req.account_id, Collection::CalendarEvent)?;
+
+                    self.calendar_event_query(req, access_token).await?.into()
+                }
             },
             RequestMethod::Set(mut req) => match req.take_arguments() {
                 set::RequestArguments::Email => {
@@ -275,15 +311,52 @@ impl JMAP {
 
                     self.vacation_response_set(req).await?.into()
                 }
+                set::RequestArguments::AddressBook(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::AddressBook)?;
+
+                    self.address_book_set(req.with_arguments(arguments), access_token)
+                        .await?
+                        .into()
+                }
+                set::RequestArguments::ContactCard => {
+                    access_token.assert_has_access(req.account_id, Collection::ContactCard)?;
+
+                    self.contact_card_set(req, access_token).await?.into()
+                }
+                set::RequestArguments::Calendar(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::Calendar)?;
+
+                    self.calendar_set(req.with_arguments(arguments), access_token)
+                        .await?
+                        .into()
+                }
+                set::RequestArguments::CalendarEvent(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::CalendarEvent)?;
+
+                    self.calendar_event_set(req.with_arguments(arguments), access_token)
+                        .await?
+                        .into()
+                }
             },
             RequestMethod::Changes(req) => self.changes(req, access_token).await?.into(),
-            RequestMethod::Copy(req) => {
-                access_token
-                    .assert_has_access(req.account_id, Collection::Email)?
-                    .assert_has_access(req.from_account_id, Collection::Email)?;
+            RequestMethod::Copy(req) => match req.arguments {
+                copy::RequestArguments::Email => {
+                    access_token
+                        .assert_has_access(req.account_id, Collection::Email)?
+                        .assert_has_access(req.from_account_id, Collection::Email)?;
 
-                self.email_copy(req, access_token, next_call).await?.into()
-            }
+                    self.email_copy(req, access_token, next_call).await?.into()
+                }
+                copy::RequestArguments::ContactCard => {
+                    access_token
+                        .assert_has_access(req.account_id, Collection::ContactCard)?
+                        .assert_has_access(req.from_account_id, Collection::ContactCard)?;
+
+                    self.contact_card_copy(req, access_token, next_call)
+                        .await?
+                        .into()
+                }
+            },
             RequestMethod::ImportEmail(req) => {
                 access_token.assert_has_access(req.account_id, Collection::Email)?;
 
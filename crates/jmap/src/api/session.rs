@@ -72,7 +72,13 @@ impl JMAP {
                     .unwrap_or_else(|| Id::from(*id).to_string()),
                 is_personal,
                 is_readonly,
-                Some(&[Capability::Mail, Capability::Quota, Capability::Blob]),
+                Some(&[
+                    Capability::Mail,
+                    Capability::Quota,
+                    Capability::Blob,
+                    Capability::Contacts,
+                    Capability::Calendars,
+                ]),
                 &self.core.jmap.capabilities.account,
             );
         }
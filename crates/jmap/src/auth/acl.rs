@@ -33,7 +33,7 @@ use jmap_proto::{
     },
 };
 use store::{
-    query::acl::AclQuery,
+    query::{acl::AclQuery, Filter},
     roaring::RoaringBitmap,
     write::{assert::HashedValue, ValueClass},
     ValueKey,
@@ -87,6 +87,16 @@ impl JMAP {
                     {
                         collections.insert(Collection::Email);
                     }
+                    if collection == Collection::AddressBook
+                        && (acl.contains(Acl::ReadItems) || acl.contains(Acl::Administer))
+                    {
+                        collections.insert(Collection::ContactCard);
+                    }
+                    if collection == Collection::Calendar
+                        && (acl.contains(Acl::ReadItems) || acl.contains(Acl::Administer))
+                    {
+                        collections.insert(Collection::CalendarEvent);
+                    }
 
                     if !collections.is_empty() {
                         if let Some((_, sharing)) = access_token
@@ -222,6 +232,104 @@ impl JMAP {
         Ok(document_ids)
     }
 
+    pub async fn shared_contacts(
+        &self,
+        access_token: &AccessToken,
+        to_account_id: u32,
+        check_acls: impl Into<Bitmap<Acl>>,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let check_acls = check_acls.into();
+        let shared_address_books = self
+            .shared_documents(
+                access_token,
+                to_account_id,
+                Collection::AddressBook,
+                check_acls,
+            )
+            .await?;
+        if shared_address_books.is_empty() {
+            return Ok(shared_address_books);
+        }
+        let mut filters = Vec::with_capacity(shared_address_books.len() as usize + 2);
+        filters.push(Filter::Or);
+        for address_book_id in shared_address_books {
+            filters.push(Filter::eq(Property::AddressBookIds, address_book_id));
+        }
+        filters.push(Filter::End);
+
+        self.filter(to_account_id, Collection::ContactCard, filters)
+            .await
+            .map(|result_set| result_set.results)
+    }
+
+    pub async fn owned_or_shared_contacts(
+        &self,
+        access_token: &AccessToken,
+        account_id: u32,
+        check_acls: impl Into<Bitmap<Acl>>,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let check_acls = check_acls.into();
+        let mut document_ids = self
+            .get_document_ids(account_id, Collection::ContactCard)
+            .await?
+            .unwrap_or_default();
+        if !document_ids.is_empty() && !access_token.is_member(account_id) {
+            document_ids &= self
+                .shared_contacts(access_token, account_id, check_acls)
+                .await?;
+        }
+        Ok(document_ids)
+    }
+
+    pub async fn shared_calendar_events(
+        &self,
+        access_token: &AccessToken,
+        to_account_id: u32,
+        check_acls: impl Into<Bitmap<Acl>>,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let check_acls = check_acls.into();
+        let shared_calendars = self
+            .shared_documents(
+                access_token,
+                to_account_id,
+                Collection::Calendar,
+                check_acls,
+            )
+            .await?;
+        if shared_calendars.is_empty() {
+            return Ok(shared_calendars);
+        }
+        let mut filters = Vec::with_capacity(shared_calendars.len() as usize + 2);
+        filters.push(Filter::Or);
+        for calendar_id in shared_calendars {
+            filters.push(Filter::eq(Property::CalendarIds, calendar_id));
+        }
+        filters.push(Filter::End);
+
+        self.filter(to_account_id, Collection::CalendarEvent, filters)
+            .await
+            .map(|result_set| result_set.results)
+    }
+
+    pub async fn owned_or_shared_calendar_events(
+        &self,
+        access_token: &AccessToken,
+        account_id: u32,
+        check_acls: impl Into<Bitmap<Acl>>,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let check_acls = check_acls.into();
+        let mut document_ids = self
+            .get_document_ids(account_id, Collection::CalendarEvent)
+            .await?
+            .unwrap_or_default();
+        if !document_ids.is_empty() && !access_token.is_member(account_id) {
+            document_ids &= self
+                .shared_calendar_events(access_token, account_id, check_acls)
+                .await?;
+        }
+        Ok(document_ids)
+    }
+
     pub async fn has_access_to_document(
         &self,
         access_token: &AccessToken,
@@ -23,8 +23,13 @@
 
 use std::{net::IpAddr, sync::Arc, time::Instant};
 
-use common::{config::server::ServerProtocol, listener::limiter::InFlight, AuthResult};
-use directory::{Principal, QueryBy};
+use common::{
+    auth::scram::{ScramResult, ScramServer},
+    config::server::ServerProtocol,
+    listener::limiter::InFlight,
+    AuthResult,
+};
+use directory::{DirectoryError, Principal, QueryBy};
 use hyper::header;
 use jmap_proto::error::request::RequestError;
 use mail_parser::decoders::base64::base64_decode;
@@ -47,7 +52,15 @@ impl JMAP {
             .and_then(|h| h.to_str().ok())
             .and_then(|h| h.split_once(' ').map(|(l, t)| (l, t.trim().to_string())))
         {
-            let session = if let Some(account_id) = self.inner.sessions.get_with_ttl(&token) {
+            // App passwords are not valid for the management API
+            let allow_app_passwords = !req.uri().path().starts_with("/api/");
+            let session_id = if allow_app_passwords {
+                token.clone()
+            } else {
+                format!("api:{token}")
+            };
+
+            let session = if let Some(account_id) = self.inner.sessions.get_with_ttl(&session_id) {
                 self.get_cached_access_token(account_id).await
             } else {
                 if mechanism.eq_ignore_ascii_case("basic") {
@@ -63,13 +76,32 @@ impl JMAP {
                             })
                         })
                     {
-                        if let AuthResult::Success(access_token) = self
-                            .authenticate_plain(&account, &secret, remote_ip, ServerProtocol::Http)
+                        match self
+                            .core
+                            .authenticate(
+                                &self.core.storage.directory,
+                                &self.smtp.inner.ipc,
+                                &Credentials::Plain {
+                                    username: account,
+                                    secret,
+                                },
+                                remote_ip,
+                                ServerProtocol::Http,
+                                true,
+                                allow_app_passwords,
+                            )
                             .await
                         {
-                            Some(access_token)
-                        } else {
-                            None
+                            Ok(AuthResult::Success(principal)) => Some(AccessToken::new(principal)),
+                            Ok(AuthResult::Failure) => {
+                                let _ = self.is_auth_allowed_hard(&remote_ip).await;
+                                None
+                            }
+                            Ok(AuthResult::Banned) => None,
+                            Err(DirectoryError::MissingTotpCode) => {
+                                return Err(RequestError::totp_required())
+                            }
+                            Err(_) => None,
                         }
                     } else {
                         tracing::debug!(
@@ -101,7 +133,7 @@ impl JMAP {
                 }
                 .map(|access_token| {
                     let access_token = Arc::new(access_token);
-                    self.cache_session(token, &access_token);
+                    self.cache_session(session_id, &access_token);
                     self.cache_access_token(access_token.clone());
                     access_token
                 })
@@ -169,6 +201,7 @@ impl JMAP {
                 remote_ip,
                 protocol,
                 true,
+                true,
             )
             .await
         {
@@ -182,6 +215,39 @@ impl JMAP {
         }
     }
 
+    pub async fn authenticate_scram(
+        &self,
+        server: &mut ScramServer,
+        response: &[u8],
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+    ) -> ScramResult<AccessToken> {
+        match self
+            .core
+            .authenticate_scram(
+                &self.core.storage.directory,
+                &self.smtp.inner.ipc,
+                server,
+                response,
+                remote_ip,
+                protocol,
+                true,
+            )
+            .await
+        {
+            Ok(ScramResult::Challenge(challenge)) => ScramResult::Challenge(challenge),
+            Ok(ScramResult::Done(AuthResult::Success(principal))) => {
+                ScramResult::Done(AuthResult::Success(AccessToken::new(principal)))
+            }
+            Ok(ScramResult::Done(AuthResult::Failure)) => {
+                let _ = self.is_auth_allowed_hard(&remote_ip).await;
+                ScramResult::Done(AuthResult::Failure)
+            }
+            Ok(ScramResult::Done(AuthResult::Banned)) => ScramResult::Done(AuthResult::Banned),
+            Err(_) => ScramResult::Done(AuthResult::Failure),
+        }
+    }
+
     pub async fn get_access_token(&self, account_id: u32) -> Option<AccessToken> {
         match self
             .core
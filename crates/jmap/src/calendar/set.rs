@@ -0,0 +1,615 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::set::{SetRequest, SetResponse},
+    object::{
+        calendar::SetArguments,
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    request::reference::MaybeReference,
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        id::Id,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{MaybePatchValue, SetValue, Value},
+    },
+};
+use store::{
+    query::Filter,
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder},
+};
+
+use crate::{
+    auth::{acl::EffectiveAcl, AccessToken},
+    mailbox::set::MailboxSubscribe,
+    JMAP,
+};
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::Name)
+        .index_as(IndexAs::Text {
+            tokenize: true,
+            index: true,
+        })
+        .required(),
+    IndexProperty::new(Property::SortOrder).index_as(IndexAs::Integer),
+    IndexProperty::new(Property::IsSubscribed).index_as(IndexAs::IntegerList),
+    IndexProperty::new(Property::Acl).index_as(IndexAs::Acl),
+];
+
+impl JMAP {
+    pub async fn calendar_set(
+        &self,
+        mut request: SetRequest<SetArguments>,
+        access_token: &AccessToken,
+    ) -> Result<SetResponse, MethodError> {
+        // Prepare response
+        let account_id = request.account_id.document_id();
+        let on_destroy_remove_events = request.arguments.on_destroy_remove_events.unwrap_or(false);
+        let is_shared = access_token.is_shared(account_id);
+        let mut response = self
+            .prepare_set_response(&request, Collection::Calendar)
+            .await?;
+        let mut calendar_ids = self.calendar_get_or_create(account_id).await?;
+        let will_destroy = request.unwrap_destroy();
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        for (id, object) in request.unwrap_create() {
+            if is_shared {
+                response.not_created.append(
+                    id,
+                    SetError::forbidden().with_description(
+                        "You are not allowed to create calendars in this account.",
+                    ),
+                );
+                continue;
+            }
+
+            match self
+                .calendar_set_item(object, None, &response, access_token)
+                .await?
+            {
+                Ok(builder) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::Calendar)
+                        .create_document()
+                        .custom(builder);
+                    let document_id = self.write_batch_expect_id(batch).await?;
+                    changes.log_insert(Collection::Calendar, document_id);
+                    calendar_ids.insert(document_id);
+                    response.created(id, document_id);
+                }
+                Err(err) => {
+                    response.not_created.append(id, err);
+                }
+            }
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain calendar
+            let document_id = id.document_id();
+            let calendar = if let Some(calendar) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::Calendar,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                calendar
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+
+            // Validate ACL
+            if is_shared {
+                let acl = calendar.inner.effective_acl(access_token);
+                if !acl.contains(Acl::Modify) {
+                    response.not_updated.append(
+                        id,
+                        SetError::forbidden()
+                            .with_description("You are not allowed to modify this calendar."),
+                    );
+                    continue 'update;
+                } else if object.properties.contains_key(&Property::Acl)
+                    && !acl.contains(Acl::Administer)
+                {
+                    response.not_updated.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "You are not allowed to change the permissions of this calendar.",
+                        ),
+                    );
+                    continue 'update;
+                }
+            }
+
+            match self
+                .calendar_set_item(object, calendar.into(), &response, access_token)
+                .await?
+            {
+                Ok(builder) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::Calendar)
+                        .update_document(document_id)
+                        .custom(builder);
+                    if !batch.is_empty() {
+                        match self.core.storage.data.write(batch.build()).await {
+                            Ok(_) => {
+                                changes.log_update(Collection::Calendar, document_id);
+                            }
+                            Err(store::Error::AssertValueFailed) => {
+                                response.not_updated.append(
+                                    id,
+                                    SetError::forbidden().with_description(
+                                        "Another process modified this calendar, please try again.",
+                                    ),
+                                );
+                                continue 'update;
+                            }
+                            Err(err) => {
+                                tracing::error!(
+                                    event = "error",
+                                    context = "calendar_set",
+                                    account_id = account_id,
+                                    error = ?err,
+                                    "Failed to update calendar(s).");
+                                return Err(MethodError::ServerPartialFail);
+                            }
+                        }
+                    }
+                    response.updated.append(id, None);
+                }
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                }
+            }
+        }
+
+        // Process default calendar changes
+        if let Some(default_id) = request.arguments.on_success_set_is_default {
+            let default_id = match default_id {
+                MaybeReference::Value(id) => Some(id),
+                MaybeReference::Reference(id_ref) => {
+                    response.get_id(&id_ref).and_then(|id| id.try_unwrap_id())
+                }
+            };
+
+            match default_id {
+                Some(default_id)
+                    if calendar_ids.contains(default_id.document_id())
+                        && !will_destroy.contains(&default_id) =>
+                {
+                    if !is_shared {
+                        self.calendar_set_default(
+                            account_id,
+                            default_id.document_id(),
+                            &mut changes,
+                            &mut response,
+                        )
+                        .await?;
+                    } else {
+                        response.not_updated.append(
+                            default_id,
+                            SetError::forbidden().with_description(
+                                "You are not allowed to change the default calendar.",
+                            ),
+                        );
+                    }
+                }
+                _ => {
+                    return Err(MethodError::InvalidArguments(
+                        "Invalid onSuccessSetIsDefault calendar id.".to_string(),
+                    ));
+                }
+            }
+        }
+
+        // Process deletions
+        let mut did_remove_events = false;
+        for id in will_destroy {
+            match self
+                .calendar_destroy(
+                    account_id,
+                    id.document_id(),
+                    &mut changes,
+                    access_token,
+                    on_destroy_remove_events,
+                )
+                .await?
+            {
+                Ok(removed_events) => {
+                    did_remove_events |= removed_events;
+                    response.destroyed.push(id);
+                }
+                Err(err) => {
+                    response.not_destroyed.append(id, err);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            let change_id = self.commit_changes(account_id, changes).await?;
+            let state_change =
+                StateChange::new(account_id).with_change(DataType::Calendar, change_id);
+            response.state_change = if did_remove_events {
+                state_change.with_change(DataType::CalendarEvent, change_id)
+            } else {
+                state_change
+            }
+            .into();
+            response.new_state = Some(change_id.into());
+        }
+
+        Ok(response)
+    }
+
+    async fn calendar_set_item(
+        &self,
+        changes_: Object<SetValue>,
+        current: Option<HashedValue<Object<Value>>>,
+        response: &SetResponse,
+        access_token: &AccessToken,
+    ) -> Result<Result<ObjectIndexBuilder, SetError>, MethodError> {
+        // Parse properties
+        let mut changes = Object::with_capacity(changes_.properties.len());
+        for (property, value) in changes_.properties {
+            let value = match response.eval_object_references(value) {
+                Ok(value) => value,
+                Err(err) => {
+                    return Ok(Err(err));
+                }
+            };
+            let value = match (&property, value) {
+                (Property::Name, MaybePatchValue::Value(Value::Text(value))) => {
+                    let value = value.trim();
+                    if !value.is_empty() && value.len() < self.core.jmap.mailbox_name_max_len {
+                        Value::Text(value.to_string())
+                    } else {
+                        return Ok(Err(SetError::invalid_properties()
+                            .with_property(Property::Name)
+                            .with_description(
+                                if !value.is_empty() {
+                                    "Calendar name is too long."
+                                } else {
+                                    "Calendar name cannot be empty."
+                                }
+                                .to_string(),
+                            )));
+                    }
+                }
+                (Property::Description, MaybePatchValue::Value(Value::Text(value))) => {
+                    Value::Text(value)
+                }
+                (Property::Description, MaybePatchValue::Value(Value::Null)) => Value::Null,
+                (
+                    Property::Color | Property::TimeZone,
+                    MaybePatchValue::Value(Value::Text(value)),
+                ) if !value.is_empty() && value.len() < 128 => Value::Text(value),
+                (Property::Color | Property::TimeZone, MaybePatchValue::Value(Value::Null)) => {
+                    Value::Null
+                }
+                (Property::IsVisible, MaybePatchValue::Value(Value::Bool(value))) => {
+                    Value::Bool(value)
+                }
+                (Property::SortOrder, MaybePatchValue::Value(Value::UnsignedInt(value))) => {
+                    Value::UnsignedInt(value)
+                }
+                (Property::IsSubscribed, MaybePatchValue::Value(Value::Bool(subscribe))) => {
+                    if let Some(current) = current.as_ref() {
+                        if let Some(value) = current
+                            .inner
+                            .mailbox_subscribe(access_token.primary_id(), subscribe)
+                        {
+                            value
+                        } else {
+                            continue;
+                        }
+                    } else if subscribe {
+                        Value::List(vec![Value::Id(access_token.primary_id().into())])
+                    } else {
+                        continue;
+                    }
+                }
+                (Property::Acl, value) => {
+                    match self.acl_set(&mut changes, current.as_ref(), value).await {
+                        Ok(_) => continue,
+                        Err(err) => {
+                            return Ok(Err(err));
+                        }
+                    }
+                }
+                _ => {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value.".to_string())))
+                }
+            };
+
+            changes.append(property, value);
+        }
+
+        // Refresh ACLs
+        if changes.properties.contains_key(&Property::Acl) {
+            self.refresh_acls(&changes, &current);
+        }
+
+        // Validate
+        Ok(ObjectIndexBuilder::new(SCHEMA)
+            .with_changes(changes)
+            .with_current_opt(current)
+            .validate())
+    }
+
+    async fn calendar_set_default(
+        &self,
+        account_id: u32,
+        default_id: u32,
+        changes: &mut ChangeLogBuilder,
+        response: &mut SetResponse,
+    ) -> Result<(), MethodError> {
+        let calendar_ids = self
+            .get_document_ids(account_id, Collection::Calendar)
+            .await?
+            .unwrap_or_default();
+
+        for (document_id, calendar) in self
+            .get_properties::<HashedValue<Object<Value>>, _, _>(
+                account_id,
+                Collection::Calendar,
+                &calendar_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            let is_default = document_id == default_id;
+            if matches!(calendar.inner.get(&Property::IsDefault), Value::Bool(true)) == is_default {
+                continue;
+            }
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Calendar)
+                .update_document(document_id)
+                .custom(
+                    ObjectIndexBuilder::new(SCHEMA)
+                        .with_current(calendar)
+                        .with_changes(Object::with_capacity(1).with_property(
+                            Property::IsDefault,
+                            if is_default {
+                                Value::Bool(true)
+                            } else {
+                                Value::Null
+                            },
+                        )),
+                );
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => {
+                    changes.log_update(Collection::Calendar, document_id);
+                    let id = Id::from(document_id);
+                    if !response.updated.contains_key(&id) {
+                        response.updated.append(id, None);
+                    }
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_updated.append(
+                        Id::from(document_id),
+                        SetError::forbidden().with_description(
+                            "Another process modified this calendar, please try again.",
+                        ),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "calendar_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to update default calendar.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn calendar_destroy(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        changes: &mut ChangeLogBuilder,
+        access_token: &AccessToken,
+        remove_events: bool,
+    ) -> Result<Result<bool, SetError>, MethodError> {
+        // Obtain calendar
+        let calendar = if let Some(calendar) = self
+            .get_property::<HashedValue<Object<Value>>>(
+                account_id,
+                Collection::Calendar,
+                document_id,
+                Property::Value,
+            )
+            .await?
+        {
+            calendar
+        } else {
+            return Ok(Err(SetError::not_found()));
+        };
+
+        // Validate ACLs
+        if access_token.is_shared(account_id) {
+            let acl = calendar.inner.effective_acl(access_token);
+            if !acl.contains(Acl::Administer) {
+                if !acl.contains(Acl::Delete) {
+                    return Ok(Err(SetError::forbidden()
+                        .with_description("You are not allowed to delete this calendar.")));
+                } else if remove_events && !acl.contains(Acl::RemoveItems) {
+                    return Ok(Err(SetError::forbidden().with_description(
+                        "You are not allowed to delete events from this calendar.",
+                    )));
+                }
+            }
+        }
+
+        // The default calendar cannot be deleted
+        if matches!(calendar.inner.get(&Property::IsDefault), Value::Bool(true))
+            && !access_token.is_super_user()
+        {
+            return Ok(Err(SetError::forbidden().with_description(
+                "You are not allowed to delete the default calendar.",
+            )));
+        }
+
+        // Verify that the calendar is empty
+        let mut did_remove_events = false;
+        let event_ids = self
+            .filter(
+                account_id,
+                Collection::CalendarEvent,
+                vec![Filter::eq(Property::CalendarIds, document_id)],
+            )
+            .await?
+            .results;
+        if !event_ids.is_empty() {
+            if remove_events {
+                if let Err(err) = self
+                    .calendar_event_remove_calendar(account_id, document_id, &event_ids, changes)
+                    .await?
+                {
+                    return Ok(Err(err));
+                }
+                did_remove_events = true;
+            } else {
+                return Ok(Err(SetError::new(SetErrorType::CalendarHasEvent)
+                    .with_description("Calendar is not empty.")));
+            }
+        }
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Calendar)
+            .delete_document(document_id)
+            .custom(ObjectIndexBuilder::new(SCHEMA).with_current(calendar));
+
+        match self.core.storage.data.write(batch.build()).await {
+            Ok(_) => {
+                changes.log_delete(Collection::Calendar, document_id);
+                Ok(Ok(did_remove_events))
+            }
+            Err(store::Error::AssertValueFailed) => Ok(Err(SetError::forbidden()
+                .with_description(concat!(
+                    "Another process modified this calendar ",
+                    "while deleting it, please try again."
+                )))),
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "calendar_set",
+                    account_id = account_id,
+                    document_id = document_id,
+                    error = ?err,
+                    "Failed to delete calendar.");
+                Err(MethodError::ServerPartialFail)
+            }
+        }
+    }
+
+    pub async fn calendar_get_or_create(
+        &self,
+        account_id: u32,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let mut calendar_ids = self
+            .get_document_ids(account_id, Collection::Calendar)
+            .await?
+            .unwrap_or_default();
+        if !calendar_ids.is_empty() {
+            return Ok(calendar_ids);
+        }
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Calendar)
+            .create_document_with_id(0)
+            .custom(
+                ObjectIndexBuilder::new(SCHEMA).with_changes(
+                    Object::with_capacity(3)
+                        .with_property(
+                            Property::Name,
+                            self.core.jmap.calendars_default_calendar.clone(),
+                        )
+                        .with_property(Property::IsDefault, Value::Bool(true))
+                        .with_property(
+                            Property::IsSubscribed,
+                            Value::List(vec![Value::Id(account_id.into())]),
+                        ),
+                ),
+            );
+        calendar_ids.insert(0);
+
+        self.core
+            .storage
+            .data
+            .write(batch.build())
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                event = "error",
+                context = "calendar_get_or_create",
+                error = ?err,
+                "Failed to create calendar.");
+                MethodError::ServerPartialFail
+            })?;
+
+        Ok(calendar_ids)
+    }
+}
@@ -0,0 +1,130 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::get::{GetRequest, GetResponse, RequestArguments},
+    object::Object,
+    types::{acl::Acl, collection::Collection, id::Id, property::Property, value::Value},
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::{format_utc_date_time, set::CalendarEventCalendars};
+
+impl JMAP {
+    pub async fn calendar_event_get(
+        &self,
+        mut request: GetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<GetResponse, MethodError> {
+        let ids = request.unwrap_ids(self.core.jmap.get_max_objects)?;
+        let properties = request.unwrap_properties(&[]);
+        let account_id = request.account_id.document_id();
+        let event_ids = self
+            .owned_or_shared_calendar_events(access_token, account_id, Acl::ReadItems)
+            .await?;
+        let ids = if let Some(ids) = ids {
+            ids
+        } else {
+            event_ids
+                .iter()
+                .take(self.core.jmap.get_max_objects)
+                .map(Into::into)
+                .collect::<Vec<_>>()
+        };
+        let mut response = GetResponse {
+            account_id: request.account_id.into(),
+            state: self
+                .get_state(account_id, Collection::CalendarEvent)
+                .await?
+                .into(),
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for id in ids {
+            // Obtain the event object
+            let document_id = id.document_id();
+            if !event_ids.contains(document_id) {
+                response.not_found.push(id.into());
+                continue;
+            }
+            let mut values = if let Some(values) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    &Property::Value,
+                )
+                .await?
+            {
+                values
+            } else {
+                response.not_found.push(id.into());
+                continue;
+            };
+
+            let mut calendar_ids = Object::with_capacity(1);
+            for calendar_id in values.calendar_ids() {
+                calendar_ids.append(Property::_T(Id::from(calendar_id).to_string()), true);
+            }
+            values.properties.remove(&Property::CalendarIds);
+
+            // The indexed time range is exposed as UTC date-times
+            for property in [Property::UtcStart, Property::UtcEnd] {
+                if let Some(timestamp) = values.properties.get_mut(&property) {
+                    *timestamp = Value::Text(format_utc_date_time(
+                        timestamp.as_uint().unwrap_or_default() as i64,
+                    ));
+                }
+            }
+
+            // Return all properties when none are requested
+            let event = if properties.is_empty() {
+                let mut event = Object::with_capacity(values.properties.len() + 2)
+                    .with_property(Property::Id, id)
+                    .with_property(Property::CalendarIds, calendar_ids);
+                for (property, value) in values.properties {
+                    event.append(property, value);
+                }
+                event
+            } else {
+                let mut event = Object::with_capacity(properties.len());
+                for property in &properties {
+                    let value = match property {
+                        Property::Id => Value::Id(id),
+                        Property::CalendarIds => Value::Object(std::mem::take(&mut calendar_ids)),
+                        property => values.remove(property),
+                    };
+                    event.append(property.clone(), value);
+                }
+                event
+            };
+
+            response.list.push(event);
+        }
+
+        Ok(response)
+    }
+}
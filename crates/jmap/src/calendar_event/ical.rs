@@ -0,0 +1,1252 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+use crate::contact::{parse_pointer, JSObjectPatch};
+
+use super::{
+    format_duration, format_local_date_time, parse_duration, parse_local_date_time,
+    JSCalendarEvent, JSCalendarParticipant,
+};
+
+pub struct ICalendar {
+    pub method: Option<String>,
+    pub events: Vec<Object<Value>>,
+}
+
+type RecurrenceInstance = (String, Object<Value>);
+
+#[derive(Debug, Default)]
+struct Component {
+    name: String,
+    lines: Vec<ContentLine>,
+    components: Vec<Component>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ContentLine {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+struct DateTimeValue {
+    local: NaiveDateTime,
+    time_zone: Option<String>,
+    is_date: bool,
+}
+
+const UTC_TIME_ZONES: &[&str] = &["Etc/UTC", "UTC", "GMT", "Etc/GMT", "Z"];
+
+impl ICalendar {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut stack: Vec<Component> = Vec::new();
+        let mut root = None;
+
+        for line in unfold(text) {
+            let line = parse_line(&line).ok_or_else(|| format!("Invalid content line {line:?}"))?;
+            match line.name.as_str() {
+                "BEGIN" => {
+                    stack.push(Component {
+                        name: line.value.to_ascii_uppercase(),
+                        ..Default::default()
+                    });
+                }
+                "END" => {
+                    let component = stack
+                        .pop()
+                        .filter(|c| c.name.eq_ignore_ascii_case(&line.value))
+                        .ok_or_else(|| format!("Unexpected END:{}", line.value))?;
+                    if let Some(parent) = stack.last_mut() {
+                        parent.components.push(component);
+                    } else {
+                        root = Some(component);
+                        break;
+                    }
+                }
+                _ => {
+                    stack
+                        .last_mut()
+                        .ok_or_else(|| "Content line outside of a component".to_string())?
+                        .lines
+                        .push(line);
+                }
+            }
+        }
+
+        let root = root
+            .filter(|root| root.name == "VCALENDAR")
+            .ok_or_else(|| "Missing VCALENDAR component".to_string())?;
+        let method = root
+            .line("METHOD")
+            .map(|line| line.value.to_ascii_uppercase());
+
+        // Group components by UID, recurrence instances become overrides of the master
+        let mut events: Vec<(String, Option<Object<Value>>, Vec<RecurrenceInstance>)> = Vec::new();
+        for component in root.components.iter().filter(|c| c.name == "VEVENT") {
+            let (recurrence_id, event) = component.to_jscalendar()?;
+            let uid = event
+                .get(&Property::Uid)
+                .as_string()
+                .unwrap_or_default()
+                .to_string();
+            let idx = if let Some(idx) = events.iter().position(|(id, _, _)| id == &uid) {
+                idx
+            } else {
+                events.push((uid, None, Vec::new()));
+                events.len() - 1
+            };
+            if let Some(recurrence_id) = recurrence_id {
+                events[idx].2.push((recurrence_id, event));
+            } else {
+                events[idx].1 = Some(event);
+            }
+        }
+
+        let mut result = Vec::with_capacity(events.len());
+        for (uid, master, instances) in events {
+            let mut master = if let Some(master) = master {
+                master
+            } else {
+                // Only some instances were sent, such as in an iTIP reply,
+                // the resulting event has no start and only contains overrides
+                let mut master = Object::with_capacity(3);
+                master.append(property("@type"), Value::Text("Event".to_string()));
+                master.append(Property::Uid, Value::Text(uid));
+                master
+            };
+            if !instances.is_empty() {
+                let mut overrides = match master.properties.remove(&property("recurrenceOverrides"))
+                {
+                    Some(Value::Object(overrides)) => overrides,
+                    _ => Object::with_capacity(instances.len()),
+                };
+                for (recurrence_id, instance) in instances {
+                    overrides.set(
+                        Property::_T(recurrence_id),
+                        Value::Object(instance_patch(&master, instance)),
+                    );
+                }
+                master.set(property("recurrenceOverrides"), Value::Object(overrides));
+            }
+            result.push(master);
+        }
+
+        Ok(ICalendar {
+            method,
+            events: result,
+        })
+    }
+
+    pub fn build(event: &Object<Value>, method: Option<&str>) -> String {
+        let mut ical = String::with_capacity(1024);
+        write_line(&mut ical, "BEGIN", &[], "VCALENDAR");
+        write_line(&mut ical, "VERSION", &[], "2.0");
+        write_line(
+            &mut ical,
+            "PRODID",
+            &[],
+            "-//Stalwart Labs Ltd.//Stalwart Mail Server//EN",
+        );
+        if let Some(method) = method {
+            write_line(&mut ical, "METHOD", &[], method);
+        }
+
+        let mut excluded = Vec::new();
+        let mut included = Vec::new();
+        let mut instances = Vec::new();
+        if let Some(overrides) = event.member("recurrenceOverrides").as_obj() {
+            for (recurrence_id, patch) in &overrides.properties {
+                let recurrence_id = recurrence_id.to_string();
+                let patch = patch.as_obj();
+                if patch.is_some_and(|patch| matches!(patch.field("excluded"), Value::Bool(true))) {
+                    excluded.push(recurrence_id);
+                } else if patch.is_none_or(|patch| patch.properties.is_empty()) {
+                    included.push(recurrence_id);
+                } else if let Some(patch) = patch {
+                    instances.push((recurrence_id, apply_patch(event, patch)));
+                }
+            }
+        }
+
+        write_event(&mut ical, event, None, &excluded, &included);
+        for (recurrence_id, instance) in instances {
+            write_event(&mut ical, &instance, Some(&recurrence_id), &[], &[]);
+        }
+
+        write_line(&mut ical, "END", &[], "VCALENDAR");
+        ical
+    }
+}
+
+impl Component {
+    fn line(&self, name: &str) -> Option<&ContentLine> {
+        self.lines.iter().find(|line| line.name == name)
+    }
+
+    fn to_jscalendar(&self) -> Result<(Option<String>, Object<Value>), String> {
+        let mut event = Object::with_capacity(self.lines.len() + 2);
+        event.append(property("@type"), Value::Text("Event".to_string()));
+        let mut start = None;
+        let mut end = None;
+        let mut recurrence_id = None;
+        let mut participants: Vec<(String, Object<Value>)> = Vec::new();
+        let mut recurrence_rules = Vec::new();
+        let mut overrides = Object::with_capacity(0);
+        let mut keywords = Object::with_capacity(0);
+
+        for line in &self.lines {
+            match line.name.as_str() {
+                "UID" => {
+                    event.append(Property::Uid, Value::Text(line.value.trim().to_string()));
+                }
+                "SUMMARY" => {
+                    event.append(property("title"), Value::Text(line.text()));
+                }
+                "DESCRIPTION" => {
+                    event.append(property("description"), Value::Text(line.text()));
+                }
+                "LOCATION" => {
+                    event.append(
+                        property("locations"),
+                        Value::Object(
+                            Object::with_capacity(1).with_property(
+                                Property::_T("1".to_string()),
+                                Value::Object(
+                                    Object::with_capacity(2)
+                                        .with_property(field("@type"), "Location")
+                                        .with_property(field("name"), line.text()),
+                                ),
+                            ),
+                        ),
+                    );
+                }
+                "DTSTART" => {
+                    start = line.date_time();
+                }
+                "DTEND" => {
+                    end = line.date_time();
+                }
+                "DURATION" => {
+                    if let Some(duration) = parse_duration(&line.value) {
+                        event.append(property("duration"), Value::Text(format_duration(duration)));
+                    }
+                }
+                "RECURRENCE-ID" => {
+                    recurrence_id = line
+                        .date_time()
+                        .map(|value| format_local_date_time(&value.local));
+                }
+                "STATUS" => {
+                    event.append(
+                        property("status"),
+                        Value::Text(line.value.trim().to_lowercase()),
+                    );
+                }
+                "SEQUENCE" => {
+                    if let Ok(sequence) = line.value.trim().parse::<u64>() {
+                        event.append(property("sequence"), Value::UnsignedInt(sequence));
+                    }
+                }
+                "PRIORITY" => {
+                    if let Ok(priority) = line.value.trim().parse::<u64>() {
+                        event.append(property("priority"), Value::UnsignedInt(priority));
+                    }
+                }
+                "CREATED" => {
+                    if let Some(value) = line.date_time() {
+                        event.append(Property::Created, Value::Text(format_utc(&value.local)));
+                    }
+                }
+                "LAST-MODIFIED" => {
+                    if let Some(value) = line.date_time() {
+                        event.set(Property::Updated, Value::Text(format_utc(&value.local)));
+                    }
+                }
+                "DTSTAMP" => {
+                    if let (Some(value), Value::Null) =
+                        (line.date_time(), event.get(&Property::Updated))
+                    {
+                        event.set(Property::Updated, Value::Text(format_utc(&value.local)));
+                    }
+                }
+                "TRANSP" => {
+                    event.append(
+                        property("freeBusyStatus"),
+                        Value::Text(
+                            if line.value.eq_ignore_ascii_case("TRANSPARENT") {
+                                "free"
+                            } else {
+                                "busy"
+                            }
+                            .to_string(),
+                        ),
+                    );
+                }
+                "CLASS" => {
+                    event.append(
+                        property("privacy"),
+                        Value::Text(
+                            match line.value.to_ascii_uppercase().as_str() {
+                                "PRIVATE" => "private",
+                                "CONFIDENTIAL" => "secret",
+                                _ => "public",
+                            }
+                            .to_string(),
+                        ),
+                    );
+                }
+                "COLOR" => {
+                    event.append(Property::Color, Value::Text(line.value.trim().to_string()));
+                }
+                "CATEGORIES" => {
+                    for keyword in split_list(&line.value) {
+                        keywords.set(Property::_T(keyword), Value::Bool(true));
+                    }
+                }
+                "RRULE" => {
+                    if let Some(rule) = parse_rrule(&line.value) {
+                        recurrence_rules.push(Value::Object(rule));
+                    }
+                }
+                "EXDATE" | "RDATE" => {
+                    for value in line.value.split(',') {
+                        if let Some(value) = parse_date_time_value(value, &line.params) {
+                            let patch = if line.name == "EXDATE" {
+                                Object::with_capacity(1).with_property(field("excluded"), true)
+                            } else {
+                                Object::with_capacity(0)
+                            };
+                            overrides.set(
+                                Property::_T(format_local_date_time(&value.local)),
+                                Value::Object(patch),
+                            );
+                        }
+                    }
+                }
+                "ORGANIZER" => {
+                    let address = line.value.trim().to_string();
+                    event.append(
+                        Property::ReplyTo,
+                        Value::Object(
+                            Object::with_capacity(1).with_property(field("imip"), address.clone()),
+                        ),
+                    );
+                    let participant = participant_entry(&mut participants, &address);
+                    participant.set(field("sendTo"), send_to(&address));
+                    if let Some(name) = line.param("CN") {
+                        participant.set(field("name"), name.to_string());
+                    }
+                    participant_role(participant, "owner");
+                }
+                "ATTENDEE" => {
+                    let address = line.value.trim().to_string();
+                    let participant = participant_entry(&mut participants, &address);
+                    participant.set(field("sendTo"), send_to(&address));
+                    if let Some(name) = line.param("CN") {
+                        participant.set(field("name"), name.to_string());
+                    }
+                    if let Some(kind) = line.param("CUTYPE") {
+                        let kind = kind.to_lowercase();
+                        if kind != "individual" {
+                            participant.set(field("kind"), kind);
+                        }
+                    }
+                    match line
+                        .param("ROLE")
+                        .map(|role| role.to_ascii_uppercase())
+                        .as_deref()
+                    {
+                        Some("CHAIR") => {
+                            participant_role(participant, "attendee");
+                            participant_role(participant, "chair");
+                        }
+                        Some("OPT-PARTICIPANT") => {
+                            participant_role(participant, "attendee");
+                            participant_role(participant, "optional");
+                        }
+                        Some("NON-PARTICIPANT") => {
+                            participant_role(participant, "informational");
+                        }
+                        _ => {
+                            participant_role(participant, "attendee");
+                        }
+                    }
+                    participant.set(
+                        field("participationStatus"),
+                        line.param("PARTSTAT")
+                            .unwrap_or("NEEDS-ACTION")
+                            .to_lowercase(),
+                    );
+                    if line
+                        .param("RSVP")
+                        .is_some_and(|rsvp| rsvp.eq_ignore_ascii_case("TRUE"))
+                    {
+                        participant.set(field("expectReply"), true);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // Start and duration
+        if let Some(start) = &start {
+            event.append(
+                property("start"),
+                Value::Text(format_local_date_time(&start.local)),
+            );
+            if start.is_date {
+                event.append(property("showWithoutTime"), Value::Bool(true));
+            } else if let Some(time_zone) = &start.time_zone {
+                event.append(Property::TimeZone, Value::Text(time_zone.clone()));
+            }
+            if let (Some(end), Value::Null) = (&end, event.member("duration")) {
+                let duration = (end.local - start.local).num_seconds();
+                if duration > 0 {
+                    event.append(property("duration"), Value::Text(format_duration(duration)));
+                }
+            } else if start.is_date && matches!(event.member("duration"), Value::Null) {
+                event.append(property("duration"), Value::Text("P1D".to_string()));
+            }
+        } else if recurrence_id.is_none() {
+            return Err("Event is missing DTSTART".to_string());
+        }
+
+        if !participants.is_empty() {
+            let mut list = Object::with_capacity(participants.len());
+            for (id, mut participant) in participants {
+                participant.set(field("@type"), "Participant");
+                list.append(Property::_T(id), Value::Object(participant));
+            }
+            event.append(property("participants"), Value::Object(list));
+        }
+        if !recurrence_rules.is_empty() {
+            event.append(property("recurrenceRules"), Value::List(recurrence_rules));
+        }
+        if !overrides.properties.is_empty() {
+            event.append(property("recurrenceOverrides"), Value::Object(overrides));
+        }
+        if !keywords.properties.is_empty() {
+            event.append(Property::Keywords, Value::Object(keywords));
+        }
+
+        // Alerts
+        let mut alerts = Object::with_capacity(0);
+        for alarm in self.components.iter().filter(|c| c.name == "VALARM") {
+            if let Some(trigger) = alarm.line("TRIGGER") {
+                let trigger = if let Some(offset) = parse_duration(&trigger.value) {
+                    let mut trigger_obj = Object::with_capacity(3)
+                        .with_property(field("@type"), "OffsetTrigger")
+                        .with_property(field("offset"), format_duration(offset));
+                    if trigger
+                        .param("RELATED")
+                        .is_some_and(|related| related.eq_ignore_ascii_case("END"))
+                    {
+                        trigger_obj.set(field("relativeTo"), "end");
+                    }
+                    trigger_obj
+                } else if let Some(when) = trigger.date_time() {
+                    Object::with_capacity(2)
+                        .with_property(field("@type"), "AbsoluteTrigger")
+                        .with_property(field("when"), format_utc(&when.local))
+                } else {
+                    continue;
+                };
+                let action = match alarm.line("ACTION").map(|a| a.value.to_ascii_uppercase()) {
+                    Some(action) if action == "EMAIL" => "email",
+                    _ => "display",
+                };
+                alerts.append(
+                    Property::_T((alerts.properties.len() + 1).to_string()),
+                    Value::Object(
+                        Object::with_capacity(3)
+                            .with_property(field("@type"), "Alert")
+                            .with_property(field("trigger"), Value::Object(trigger))
+                            .with_property(field("action"), action),
+                    ),
+                );
+            }
+        }
+        if !alerts.properties.is_empty() {
+            event.append(property("alerts"), Value::Object(alerts));
+        }
+
+        Ok((recurrence_id, event))
+    }
+}
+
+impl ContentLine {
+    pub(crate) fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn text(&self) -> String {
+        unescape_text(&self.value)
+    }
+
+    fn date_time(&self) -> Option<DateTimeValue> {
+        parse_date_time_value(&self.value, &self.params)
+    }
+}
+
+fn write_event(
+    ical: &mut String,
+    event: &Object<Value>,
+    recurrence_id: Option<&str>,
+    excluded: &[String],
+    included: &[String],
+) {
+    write_line(ical, "BEGIN", &[], "VEVENT");
+    if let Some(uid) = event.get(&Property::Uid).as_string() {
+        write_line(ical, "UID", &[], uid);
+    }
+    let updated = event
+        .get(&Property::Updated)
+        .as_string()
+        .and_then(parse_local_date_time)
+        .unwrap_or_else(|| Utc::now().naive_utc());
+    write_line(ical, "DTSTAMP", &[], &ical_utc(&updated));
+    if let Some(created) = event
+        .get(&Property::Created)
+        .as_string()
+        .and_then(parse_local_date_time)
+    {
+        write_line(ical, "CREATED", &[], &ical_utc(&created));
+    }
+    if event.get(&Property::Updated).as_string().is_some() {
+        write_line(ical, "LAST-MODIFIED", &[], &ical_utc(&updated));
+    }
+
+    // Start and duration
+    let show_without_time = matches!(event.member("showWithoutTime"), Value::Bool(true));
+    let time_zone = event.get(&Property::TimeZone).as_string();
+    if let Some(start) = event.member_text("start").and_then(parse_local_date_time) {
+        write_date_time(ical, "DTSTART", &start, time_zone, show_without_time);
+    }
+    if let Some(recurrence_id) = recurrence_id.and_then(parse_local_date_time) {
+        write_date_time(
+            ical,
+            "RECURRENCE-ID",
+            &recurrence_id,
+            time_zone,
+            show_without_time,
+        );
+    }
+    if let Some(duration) = event.member_text("duration").and_then(parse_duration) {
+        if !show_without_time || duration % 86400 == 0 {
+            write_line(ical, "DURATION", &[], &format_duration(duration));
+        }
+    }
+    for (name, list) in [("EXDATE", excluded), ("RDATE", included)] {
+        for date in list.iter().filter_map(|date| parse_local_date_time(date)) {
+            write_date_time(ical, name, &date, time_zone, show_without_time);
+        }
+    }
+    if let Some(rules) = event.member("recurrenceRules").as_list() {
+        for rule in rules.iter().filter_map(|rule| rule.as_obj()) {
+            if let Some(rule) = build_rrule(rule) {
+                write_line(ical, "RRULE", &[], &rule);
+            }
+        }
+    }
+
+    // Descriptive properties
+    if let Some(title) = event.member_text("title") {
+        write_line(ical, "SUMMARY", &[], &escape_text(title));
+    }
+    if let Some(description) = event.member_text("description") {
+        write_line(ical, "DESCRIPTION", &[], &escape_text(description));
+    }
+    if let Some(location) = event.member("locations").as_obj().and_then(|locations| {
+        locations
+            .properties
+            .iter()
+            .find_map(|(_, location)| location.as_obj()?.field("name").as_string())
+    }) {
+        write_line(ical, "LOCATION", &[], &escape_text(location));
+    }
+    if let Some(status) = event.member_text("status") {
+        write_line(ical, "STATUS", &[], &status.to_ascii_uppercase());
+    }
+    if let Some(sequence) = event.member("sequence").as_uint() {
+        write_line(ical, "SEQUENCE", &[], &sequence.to_string());
+    }
+    if let Some(priority) = event.member("priority").as_uint() {
+        write_line(ical, "PRIORITY", &[], &priority.to_string());
+    }
+    if let Some(privacy) = event.member_text("privacy") {
+        write_line(
+            ical,
+            "CLASS",
+            &[],
+            match privacy {
+                "private" => "PRIVATE",
+                "secret" => "CONFIDENTIAL",
+                _ => "PUBLIC",
+            },
+        );
+    }
+    if let Some(status) = event.member_text("freeBusyStatus") {
+        write_line(
+            ical,
+            "TRANSP",
+            &[],
+            if status == "free" {
+                "TRANSPARENT"
+            } else {
+                "OPAQUE"
+            },
+        );
+    }
+    if let Some(color) = event.get(&Property::Color).as_string() {
+        write_line(ical, "COLOR", &[], color);
+    }
+    if let Some(keywords) = event.get(&Property::Keywords).as_obj() {
+        let keywords = keywords
+            .properties
+            .iter()
+            .filter(|(_, value)| matches!(value, Value::Bool(true)))
+            .map(|(keyword, _)| escape_text(&keyword.to_string()))
+            .collect::<Vec<_>>();
+        if !keywords.is_empty() {
+            write_line(ical, "CATEGORIES", &[], &keywords.join(","));
+        }
+    }
+
+    // Participants
+    if let Some(organizer) = event.organizer_address() {
+        let name = event
+            .member("participants")
+            .as_obj()
+            .and_then(|participants| {
+                participants.properties.iter().find_map(|(_, participant)| {
+                    let participant = participant.as_obj()?;
+                    if participant.participant_address().as_ref() == Some(&organizer) {
+                        participant.field("name").as_string()
+                    } else {
+                        None
+                    }
+                })
+            });
+        let mut params = Vec::new();
+        if let Some(name) = name {
+            params.push(("CN", name.to_string()));
+        }
+        write_line(ical, "ORGANIZER", &params, &format!("mailto:{organizer}"));
+    }
+    if let Some(participants) = event.member("participants").as_obj() {
+        let organizer = event.organizer_address();
+        for (_, participant) in &participants.properties {
+            let participant = if let Some(participant) = participant.as_obj() {
+                participant
+            } else {
+                continue;
+            };
+            let address = if let Some(address) = participant.participant_address() {
+                address
+            } else {
+                continue;
+            };
+            if organizer.as_ref() == Some(&address) && !participant.has_role("attendee") {
+                continue;
+            }
+            let mut params = Vec::new();
+            if let Some(name) = participant.field("name").as_string() {
+                params.push(("CN", name.to_string()));
+            }
+            if let Some(kind) = participant.field("kind").as_string() {
+                params.push(("CUTYPE", kind.to_ascii_uppercase()));
+            }
+            params.push((
+                "ROLE",
+                if participant.has_role("chair") {
+                    "CHAIR"
+                } else if participant.has_role("optional") {
+                    "OPT-PARTICIPANT"
+                } else if participant.has_role("informational") {
+                    "NON-PARTICIPANT"
+                } else {
+                    "REQ-PARTICIPANT"
+                }
+                .to_string(),
+            ));
+            params.push((
+                "PARTSTAT",
+                participant
+                    .field("participationStatus")
+                    .as_string()
+                    .unwrap_or("needs-action")
+                    .to_ascii_uppercase(),
+            ));
+            if matches!(participant.field("expectReply"), Value::Bool(true)) {
+                params.push(("RSVP", "TRUE".to_string()));
+            }
+            write_line(ical, "ATTENDEE", &params, &format!("mailto:{address}"));
+        }
+    }
+
+    // Alerts
+    if let Some(alerts) = event.member("alerts").as_obj() {
+        for (_, alert) in &alerts.properties {
+            let alert = if let Some(alert) = alert.as_obj() {
+                alert
+            } else {
+                continue;
+            };
+            let trigger = if let Some(trigger) = alert.field("trigger").as_obj() {
+                trigger
+            } else {
+                continue;
+            };
+            write_line(ical, "BEGIN", &[], "VALARM");
+            if let Some(offset) = trigger.field("offset").as_string() {
+                let mut params = Vec::new();
+                if trigger.field("relativeTo").as_string() == Some("end") {
+                    params.push(("RELATED", "END".to_string()));
+                }
+                write_line(ical, "TRIGGER", &params, offset);
+            } else if let Some(when) = trigger
+                .field("when")
+                .as_string()
+                .and_then(parse_local_date_time)
+            {
+                write_line(
+                    ical,
+                    "TRIGGER",
+                    &[("VALUE", "DATE-TIME".to_string())],
+                    &ical_utc(&when),
+                );
+            }
+            let is_email = alert.field("action").as_string() == Some("email");
+            write_line(
+                ical,
+                "ACTION",
+                &[],
+                if is_email { "EMAIL" } else { "DISPLAY" },
+            );
+            write_line(
+                ical,
+                "DESCRIPTION",
+                &[],
+                &escape_text(event.member_text("title").unwrap_or("Reminder")),
+            );
+            write_line(ical, "END", &[], "VALARM");
+        }
+    }
+
+    write_line(ical, "END", &[], "VEVENT");
+}
+
+fn write_date_time(
+    ical: &mut String,
+    name: &str,
+    value: &NaiveDateTime,
+    time_zone: Option<&str>,
+    is_date: bool,
+) {
+    if is_date {
+        write_line(
+            ical,
+            name,
+            &[("VALUE", "DATE".to_string())],
+            &value.format("%Y%m%d").to_string(),
+        );
+    } else {
+        match time_zone {
+            Some(tz) if UTC_TIME_ZONES.contains(&tz) => {
+                write_line(ical, name, &[], &ical_utc(value));
+            }
+            Some(tz) => {
+                write_line(
+                    ical,
+                    name,
+                    &[("TZID", tz.to_string())],
+                    &value.format("%Y%m%dT%H%M%S").to_string(),
+                );
+            }
+            None => {
+                write_line(ical, name, &[], &value.format("%Y%m%dT%H%M%S").to_string());
+            }
+        }
+    }
+}
+
+pub(crate) fn write_line(ical: &mut String, name: &str, params: &[(&str, String)], value: &str) {
+    let mut line = String::with_capacity(name.len() + value.len() + 16);
+    line.push_str(name);
+    for (param, param_value) in params {
+        line.push(';');
+        line.push_str(param);
+        line.push('=');
+        if param_value.contains([':', ';', ',']) {
+            line.push('"');
+            line.push_str(&param_value.replace('"', "'"));
+            line.push('"');
+        } else {
+            line.push_str(param_value);
+        }
+    }
+    line.push(':');
+    line.push_str(value);
+
+    // Fold lines longer than 75 octets
+    let mut line_len = 0;
+    for ch in line.chars() {
+        let ch_len = ch.len_utf8();
+        if line_len + ch_len > 75 {
+            ical.push_str("\r\n ");
+            line_len = 1;
+        }
+        ical.push(ch);
+        line_len += ch_len;
+    }
+    ical.push_str("\r\n");
+}
+
+pub(crate) fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let Some(folded) = line.strip_prefix([' ', '\t']) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(folded);
+                continue;
+            }
+        }
+        if !line.trim().is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+pub(crate) fn parse_line(line: &str) -> Option<ContentLine> {
+    let mut chars = line.char_indices().peekable();
+    let mut name_end = line.len();
+    let mut params = Vec::new();
+
+    // Property name
+    for (pos, ch) in chars.by_ref() {
+        if ch == ';' || ch == ':' {
+            name_end = pos;
+            if ch == ':' {
+                return Some(ContentLine {
+                    name: line[..name_end].trim().to_ascii_uppercase(),
+                    params,
+                    value: line[pos + 1..].to_string(),
+                });
+            }
+            break;
+        }
+    }
+    let name = line.get(..name_end)?.trim().to_ascii_uppercase();
+
+    // Parameters
+    loop {
+        let mut param_name = String::new();
+        for (_, ch) in chars.by_ref() {
+            if ch == '=' {
+                break;
+            }
+            param_name.push(ch);
+        }
+        let mut param_value = String::new();
+        let mut in_quotes = false;
+        loop {
+            let (pos, ch) = chars.next()?;
+            match ch {
+                '"' => in_quotes = !in_quotes,
+                ';' if !in_quotes => break,
+                ':' if !in_quotes => {
+                    params.push((param_name.trim().to_ascii_uppercase(), param_value));
+                    return Some(ContentLine {
+                        name,
+                        params,
+                        value: line[pos + 1..].to_string(),
+                    });
+                }
+                _ => param_value.push(ch),
+            }
+        }
+        params.push((param_name.trim().to_ascii_uppercase(), param_value));
+    }
+}
+
+fn parse_date_time_value(value: &str, params: &[(String, String)]) -> Option<DateTimeValue> {
+    let value = value.trim();
+    let is_date = params
+        .iter()
+        .any(|(name, value)| name == "VALUE" && value.eq_ignore_ascii_case("DATE"))
+        || value.len() == 8;
+    if is_date {
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|local| DateTimeValue {
+                local,
+                time_zone: None,
+                is_date: true,
+            })
+    } else if let Some(value) = value.strip_suffix(['Z', 'z']) {
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|local| DateTimeValue {
+                local,
+                time_zone: Some("Etc/UTC".to_string()),
+                is_date: false,
+            })
+    } else {
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|local| DateTimeValue {
+                local,
+                time_zone: params
+                    .iter()
+                    .find(|(name, _)| name == "TZID")
+                    .map(|(_, tz)| tz.trim_start_matches('/').to_string()),
+                is_date: false,
+            })
+    }
+}
+
+fn parse_rrule(value: &str) -> Option<Object<Value>> {
+    let mut rule = Object::with_capacity(4).with_property(field("@type"), "RecurrenceRule");
+    for part in value.split(';') {
+        let (name, value) = part.split_once('=')?;
+        let value = value.trim();
+        match name.trim().to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                rule.set(field("frequency"), value.to_lowercase());
+            }
+            "INTERVAL" => {
+                rule.set(field("interval"), Value::UnsignedInt(value.parse().ok()?));
+            }
+            "COUNT" => {
+                rule.set(field("count"), Value::UnsignedInt(value.parse().ok()?));
+            }
+            "UNTIL" => {
+                let until = parse_date_time_value(value, &[])?;
+                rule.set(field("until"), format_local_date_time(&until.local));
+            }
+            "WKST" => {
+                rule.set(field("firstDayOfWeek"), value.to_lowercase());
+            }
+            "BYDAY" => {
+                let mut days = Vec::new();
+                for day in value.split(',') {
+                    let day = day.trim();
+                    let split = day.len().checked_sub(2)?;
+                    let mut nday = Object::with_capacity(3)
+                        .with_property(field("@type"), "NDay")
+                        .with_property(field("day"), day.get(split..)?.to_lowercase());
+                    if split > 0 {
+                        nday.set(field("nthOfPeriod"), int_value(day[..split].parse().ok()?));
+                    }
+                    days.push(Value::Object(nday));
+                }
+                rule.set(field("byDay"), Value::List(days));
+            }
+            "BYMONTH" => {
+                rule.set(
+                    field("byMonth"),
+                    Value::List(
+                        value
+                            .split(',')
+                            .map(|month| Value::Text(month.trim().to_string()))
+                            .collect(),
+                    ),
+                );
+            }
+            name @ ("BYMONTHDAY" | "BYYEARDAY" | "BYWEEKNO" | "BYHOUR" | "BYMINUTE"
+            | "BYSECOND" | "BYSETPOS") => {
+                let mut values = Vec::new();
+                for value in value.split(',') {
+                    values.push(int_value(value.trim().parse().ok()?));
+                }
+                let name = RRULE_LISTS.iter().find(|(n, _)| *n == name)?.1;
+                rule.set(field(name), Value::List(values));
+            }
+            _ => (),
+        }
+    }
+    Some(rule)
+}
+
+const RRULE_LISTS: &[(&str, &str)] = &[
+    ("BYMONTHDAY", "byMonthDay"),
+    ("BYYEARDAY", "byYearDay"),
+    ("BYWEEKNO", "byWeekNo"),
+    ("BYHOUR", "byHour"),
+    ("BYMINUTE", "byMinute"),
+    ("BYSECOND", "bySecond"),
+    ("BYSETPOS", "bySetPosition"),
+];
+
+fn build_rrule(rule: &Object<Value>) -> Option<String> {
+    let mut result = format!(
+        "FREQ={}",
+        rule.field("frequency").as_string()?.to_ascii_uppercase()
+    );
+    if let Some(interval) = rule.field("interval").as_uint() {
+        result.push_str(&format!(";INTERVAL={interval}"));
+    }
+    if let Some(count) = rule.field("count").as_uint() {
+        result.push_str(&format!(";COUNT={count}"));
+    }
+    if let Some(until) = rule
+        .field("until")
+        .as_string()
+        .and_then(parse_local_date_time)
+    {
+        result.push_str(&format!(";UNTIL={}", ical_utc(&until)));
+    }
+    if let Some(days) = rule.field("byDay").as_list() {
+        let days = days
+            .iter()
+            .filter_map(|day| {
+                let day = day.as_obj()?;
+                let name = day.field("day").as_string()?.to_ascii_uppercase();
+                Some(match int_of(day.field("nthOfPeriod")) {
+                    Some(nth) => format!("{nth}{name}"),
+                    None => name,
+                })
+            })
+            .collect::<Vec<_>>();
+        if !days.is_empty() {
+            result.push_str(&format!(";BYDAY={}", days.join(",")));
+        }
+    }
+    if let Some(months) = rule.field("byMonth").as_list() {
+        let months = months
+            .iter()
+            .filter_map(|month| month.as_string())
+            .collect::<Vec<_>>();
+        if !months.is_empty() {
+            result.push_str(&format!(";BYMONTH={}", months.join(",")));
+        }
+    }
+    for (name, property) in RRULE_LISTS {
+        if let Some(values) = rule.field(property).as_list() {
+            let values = values
+                .iter()
+                .filter_map(|value| int_of(value).map(|v| v.to_string()))
+                .collect::<Vec<_>>();
+            if !values.is_empty() {
+                result.push_str(&format!(";{name}={}", values.join(",")));
+            }
+        }
+    }
+    if let Some(wkst) = rule.field("firstDayOfWeek").as_string() {
+        result.push_str(&format!(";WKST={}", wkst.to_ascii_uppercase()));
+    }
+    Some(result)
+}
+
+// Builds a recurrence override patch with the members that differ from the master
+pub(super) fn instance_patch(master: &Object<Value>, instance: Object<Value>) -> Object<Value> {
+    let mut patch = Object::with_capacity(instance.properties.len());
+    for (property, value) in instance.properties {
+        if !matches!(
+            &property,
+            Property::Uid | Property::Updated | Property::Created
+        ) && !matches!(&property, Property::_T(name) if IGNORE_IN_PATCH.contains(&name.as_str()))
+            && master.get(&property) != &value
+        {
+            patch.append(Property::_T(property.to_string()), value);
+        }
+    }
+    patch
+}
+
+const IGNORE_IN_PATCH: &[&str] = &[
+    "@type",
+    "recurrenceRules",
+    "recurrenceOverrides",
+    "sequence",
+];
+
+// Applies a recurrence override patch to a copy of the master event
+fn apply_patch(master: &Object<Value>, patch: &Object<Value>) -> Object<Value> {
+    let mut instance = master.clone();
+    for name in ["recurrenceRules", "recurrenceOverrides"] {
+        instance.properties.remove(&property(name));
+    }
+    for (pointer, value) in &patch.properties {
+        let path = parse_pointer(&pointer.to_string());
+        let member = Property::parse(&path[0]);
+        if path.len() == 1 {
+            if value != &Value::Null {
+                instance.set(member, value.clone());
+            } else {
+                instance.properties.remove(&member);
+            }
+        } else if let Some(item) = instance.properties.get_mut(&member) {
+            item.patch_pointer(&path[1..], value.clone());
+        }
+    }
+    instance
+}
+
+fn participant_entry<'x>(
+    participants: &'x mut Vec<(String, Object<Value>)>,
+    address: &str,
+) -> &'x mut Object<Value> {
+    let address = super::strip_mailto(address);
+    let idx = if let Some(idx) = participants
+        .iter()
+        .position(|(_, participant)| participant.participant_address() == address)
+    {
+        idx
+    } else {
+        participants.push((
+            (participants.len() + 1).to_string(),
+            Object::with_capacity(6),
+        ));
+        participants.len() - 1
+    };
+    &mut participants[idx].1
+}
+
+fn participant_role(participant: &mut Object<Value>, role: &str) {
+    let roles = match participant.properties.get_mut(&field("roles")) {
+        Some(Value::Object(roles)) => roles,
+        _ => {
+            participant.set(field("roles"), Value::Object(Object::with_capacity(2)));
+            participant
+                .properties
+                .get_mut(&field("roles"))
+                .unwrap()
+                .as_obj_mut()
+                .unwrap()
+        }
+    };
+    roles.set(field(role), Value::Bool(true));
+}
+
+fn send_to(address: &str) -> Value {
+    let address = if address.len() > 7 && address[..7].eq_ignore_ascii_case("mailto:") {
+        format!("mailto:{}", &address[7..])
+    } else {
+        format!("mailto:{address}")
+    };
+    Value::Object(Object::with_capacity(1).with_property(field("imip"), address))
+}
+
+pub(crate) fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                if let Some(ch) = chars.next() {
+                    item.push(ch);
+                }
+            }
+            ',' => {
+                if !item.trim().is_empty() {
+                    items.push(item.trim().to_string());
+                }
+                item.clear();
+            }
+            _ => item.push(ch),
+        }
+    }
+    if !item.trim().is_empty() {
+        items.push(item.trim().to_string());
+    }
+    items
+}
+
+pub(crate) fn unescape_text(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => result.push('\n'),
+                Some(ch) => result.push(ch),
+                None => (),
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+    result
+}
+
+pub(crate) fn escape_text(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' | ';' | ',' => {
+                result.push('\\');
+                result.push(ch);
+            }
+            '\n' => result.push_str("\\n"),
+            '\r' => (),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+fn ical_utc(value: &NaiveDateTime) -> String {
+    value.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn format_utc(value: &NaiveDateTime) -> String {
+    value.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn int_value(value: i64) -> Value {
+    if value >= 0 {
+        Value::UnsignedInt(value as u64)
+    } else {
+        Value::Int(value)
+    }
+}
+
+fn int_of(value: &Value) -> Option<i64> {
+    match value {
+        Value::UnsignedInt(value) => Some(*value as i64),
+        Value::Int(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn property(name: &str) -> Property {
+    Property::parse(name)
+}
+
+fn field(name: &str) -> Property {
+    Property::_T(name.to_string())
+}
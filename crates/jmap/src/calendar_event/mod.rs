@@ -0,0 +1,288 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+pub mod get;
+pub mod ical;
+pub mod query;
+pub mod scheduling;
+pub mod set;
+
+pub trait JSCalendarEvent {
+    fn member(&self, name: &str) -> &Value;
+    fn member_text(&self, name: &str) -> Option<&str>;
+    fn utc_range(&self) -> Option<(i64, i64)>;
+    fn organizer_address(&self) -> Option<String>;
+    fn attendees(&self) -> Vec<(&str, String, &Object<Value>)>;
+}
+
+impl JSCalendarEvent for Object<Value> {
+    fn member(&self, name: &str) -> &Value {
+        self.get(&Property::parse(name))
+    }
+
+    fn member_text(&self, name: &str) -> Option<&str> {
+        self.member(name).as_string()
+    }
+
+    // Time zone rules are not available to the server, local times in named
+    // time zones are therefore indexed as if they were expressed in UTC.
+    fn utc_range(&self) -> Option<(i64, i64)> {
+        let start = parse_local_date_time(self.member_text("start")?)?
+            .and_utc()
+            .timestamp();
+        let duration = self
+            .member_text("duration")
+            .and_then(parse_duration)
+            .unwrap_or_default();
+        let mut end = start + duration.max(0);
+
+        // Recurring events are indexed until their last occurrence
+        if let Some(rules) = self.member("recurrenceRules").as_list() {
+            for rule in rules.iter().filter_map(|rule| rule.as_obj()) {
+                end = match rule
+                    .field("until")
+                    .as_string()
+                    .and_then(parse_local_date_time)
+                {
+                    Some(until) => end.max(until.and_utc().timestamp() + duration.max(0)),
+                    None => i64::MAX,
+                };
+            }
+        }
+        if let Some(overrides) = self.member("recurrenceOverrides").as_obj() {
+            for (recurrence_id, _) in &overrides.properties {
+                if let Some(recurrence_id) = parse_local_date_time(recurrence_id.as_str_key()) {
+                    end = end.max(recurrence_id.and_utc().timestamp() + duration.max(0));
+                }
+            }
+        }
+
+        Some((start, end))
+    }
+
+    fn organizer_address(&self) -> Option<String> {
+        if let Some(address) = self
+            .member("replyTo")
+            .as_obj()
+            .and_then(|reply_to| reply_to.field("imip").as_string())
+            .and_then(strip_mailto)
+        {
+            return Some(address);
+        }
+
+        self.member("participants")
+            .as_obj()?
+            .properties
+            .iter()
+            .filter_map(|(_, participant)| participant.as_obj())
+            .find(|participant| participant.has_role("owner"))
+            .and_then(|participant| participant.participant_address())
+    }
+
+    fn attendees(&self) -> Vec<(&str, String, &Object<Value>)> {
+        let organizer = self.organizer_address();
+        self.member("participants")
+            .as_obj()
+            .map(|participants| {
+                participants
+                    .properties
+                    .iter()
+                    .filter_map(|(id, participant)| {
+                        let participant = participant.as_obj()?;
+                        let address = participant.participant_address()?;
+                        if organizer.as_ref() != Some(&address)
+                            && (participant.has_role("attendee") || !participant.has_role("owner"))
+                        {
+                            Some((id.as_str_key(), address, participant))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub trait JSCalendarParticipant {
+    fn field(&self, name: &str) -> &Value;
+    fn has_role(&self, role: &str) -> bool;
+    fn participant_address(&self) -> Option<String>;
+}
+
+impl JSCalendarParticipant for Object<Value> {
+    fn field(&self, name: &str) -> &Value {
+        self.get(&Property::_T(name.to_string()))
+    }
+
+    fn has_role(&self, role: &str) -> bool {
+        self.field("roles")
+            .as_obj()
+            .is_some_and(|roles| matches!(roles.field(role), Value::Bool(true)))
+    }
+
+    fn participant_address(&self) -> Option<String> {
+        self.field("sendTo")
+            .as_obj()
+            .and_then(|send_to| send_to.field("imip").as_string())
+            .and_then(strip_mailto)
+            .or_else(|| {
+                self.field("email")
+                    .as_string()
+                    .map(|email| email.trim().to_lowercase())
+            })
+    }
+}
+
+trait PropertyKey {
+    fn as_str_key(&self) -> &str;
+}
+
+impl PropertyKey for Property {
+    fn as_str_key(&self) -> &str {
+        match self {
+            Property::_T(key) => key.as_str(),
+            _ => "",
+        }
+    }
+}
+
+pub fn strip_mailto(uri: &str) -> Option<String> {
+    let uri = uri.trim();
+    let address = if uri.len() > 7 && uri[..7].eq_ignore_ascii_case("mailto:") {
+        &uri[7..]
+    } else {
+        uri
+    };
+    if address.contains('@') {
+        Some(address.to_lowercase())
+    } else {
+        None
+    }
+}
+
+// Parses an RFC 8984 LocalDateTime, or a UTCDateTime when it ends with 'Z'
+pub fn parse_local_date_time(value: &str) -> Option<NaiveDateTime> {
+    let value = value.strip_suffix('Z').unwrap_or(value);
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+}
+
+pub fn format_local_date_time(value: &NaiveDateTime) -> String {
+    value.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+pub fn format_utc_date_time(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string()
+}
+
+// Parses an ISO 8601 duration as used by both RFC 8984 and RFC 5545
+pub fn parse_duration(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (sign, value) = match value.as_bytes().first()? {
+        b'-' => (-1, &value[1..]),
+        b'+' => (1, &value[1..]),
+        _ => (1, value),
+    };
+    let value = value.strip_prefix(['P', 'p'])?;
+    let mut seconds = 0i64;
+    let mut number = None;
+    let mut in_time = false;
+
+    for ch in value.chars() {
+        match ch {
+            '0'..='9' => {
+                number = Some(
+                    number
+                        .unwrap_or(0i64)
+                        .checked_mul(10)?
+                        .checked_add(ch as i64 - '0' as i64)?,
+                );
+            }
+            'T' | 't' if !in_time && number.is_none() => {
+                in_time = true;
+            }
+            _ => {
+                let multiplier = match (ch.to_ascii_uppercase(), in_time) {
+                    ('W', false) => 7 * 86400,
+                    ('D', false) => 86400,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+                seconds = seconds.checked_add(number.take()?.checked_mul(multiplier)?)?;
+            }
+        }
+    }
+
+    if number.is_none() {
+        Some(sign * seconds)
+    } else {
+        None
+    }
+}
+
+pub fn format_duration(seconds: i64) -> String {
+    let mut result = String::with_capacity(12);
+    if seconds < 0 {
+        result.push('-');
+    }
+    result.push('P');
+    let seconds = seconds.unsigned_abs();
+    let (days, rem) = (seconds / 86400, seconds % 86400);
+    if days > 0 && days % 7 == 0 && rem == 0 {
+        result.push_str(&format!("{}W", days / 7));
+        return result;
+    } else if days > 0 {
+        result.push_str(&format!("{days}D"));
+    }
+    if rem > 0 || days == 0 {
+        result.push('T');
+        let (hours, minutes, seconds) = (rem / 3600, (rem % 3600) / 60, rem % 60);
+        if hours > 0 {
+            result.push_str(&format!("{hours}H"));
+        }
+        if minutes > 0 {
+            result.push_str(&format!("{minutes}M"));
+        }
+        if seconds > 0 || rem == 0 {
+            result.push_str(&format!("{seconds}S"));
+        }
+    }
+    result
+}
@@ -0,0 +1,714 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use common::listener::stream::NullIo;
+use directory::QueryBy;
+use jmap_proto::{
+    error::method::MethodError,
+    method::set::SetRequest,
+    object::{calendar::EventSetArguments, Object},
+    types::{
+        collection::Collection,
+        id::Id,
+        property::Property,
+        value::{SetValue, Value},
+    },
+};
+use mail_builder::{
+    headers::{content_type::ContentType, HeaderType},
+    mime::{make_boundary, BodyPart, MimePart},
+    MessageBuilder,
+};
+use mail_parser::{Message, MimeHeaders};
+use smtp::core::{Session, SessionAddress};
+use store::{query::Filter, write::assert::HashedValue};
+use utils::map::vec_map::VecMap;
+
+use crate::JMAP;
+
+use super::{
+    ical::{instance_patch, ICalendar},
+    set::SetContext,
+    JSCalendarEvent, JSCalendarParticipant,
+};
+
+// Members that are specific to each calendar user and are kept on updates
+const LOCAL_PROPERTIES: &[&str] = &["alerts", "color", "keywords", "useDefaultAlerts"];
+
+impl JMAP {
+    pub async fn calendar_account_addresses(
+        &self,
+        account_id: u32,
+    ) -> Result<Vec<String>, MethodError> {
+        Ok(self
+            .core
+            .storage
+            .directory
+            .query(QueryBy::Id(account_id), false)
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    event = "error",
+                    context = "calendar_account_addresses",
+                    error = ?err,
+                    "Failed to query directory.");
+                MethodError::ServerPartialFail
+            })?
+            .map(|principal| {
+                principal
+                    .emails
+                    .into_iter()
+                    .map(|email| email.to_lowercase())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    // Sends the iTIP messages resulting from a calendar event change (RFC 6047)
+    pub async fn calendar_event_schedule(
+        &self,
+        ctx: &SetContext<'_>,
+        previous: Option<&Object<Value>>,
+        current: Option<&Object<Value>>,
+    ) {
+        let event = if let Some(event) = current.or(previous) {
+            event
+        } else {
+            return;
+        };
+        let organizer = if let Some(organizer) = event.organizer_address() {
+            organizer
+        } else {
+            return;
+        };
+
+        if ctx.addresses.contains(&organizer) {
+            // Organizer: notify attendees
+            let previous_attendees = previous.map(scheduled_attendees).unwrap_or_default();
+            match current {
+                Some(current) if !current.is_cancelled() => {
+                    let attendees = scheduled_attendees(current);
+                    let removed = previous_attendees
+                        .into_iter()
+                        .filter(|address| !attendees.contains(address))
+                        .collect::<Vec<_>>();
+                    self.calendar_send_itip(&organizer, attendees, "REQUEST", current)
+                        .await;
+                    if !removed.is_empty() {
+                        self.calendar_send_itip(
+                            &organizer,
+                            removed,
+                            "CANCEL",
+                            &cancelled_event(current),
+                        )
+                        .await;
+                    }
+                }
+                Some(current) => {
+                    if !previous.is_some_and(|previous| previous.is_cancelled()) {
+                        self.calendar_send_itip(
+                            &organizer,
+                            scheduled_attendees(current),
+                            "CANCEL",
+                            current,
+                        )
+                        .await;
+                    }
+                }
+                None => {
+                    if !event.is_cancelled() {
+                        self.calendar_send_itip(
+                            &organizer,
+                            previous_attendees,
+                            "CANCEL",
+                            &cancelled_event(event),
+                        )
+                        .await;
+                    }
+                }
+            }
+        } else if let Some(address) = event
+            .attendees()
+            .into_iter()
+            .map(|(_, address, _)| address)
+            .find(|address| ctx.addresses.contains(address))
+        {
+            // Attendee: reply to the organizer when the participation status changes
+            let previous_status = previous.and_then(|event| event.participation_status(&address));
+            let reply = match current {
+                Some(current) => {
+                    let status = current.participation_status(&address);
+                    if status != previous_status && status.is_some() && previous.is_some() {
+                        reply_event(current, &organizer, &address, None)
+                    } else {
+                        return;
+                    }
+                }
+                None if previous_status.as_deref() != Some("declined") => {
+                    reply_event(event, &organizer, &address, Some("declined"))
+                }
+                None => return,
+            };
+            self.calendar_send_itip(&address, vec![organizer], "REPLY", &reply)
+                .await;
+        }
+    }
+
+    async fn calendar_send_itip(
+        &self,
+        from: &str,
+        recipients: Vec<String>,
+        method: &str,
+        event: &Object<Value>,
+    ) {
+        if recipients.is_empty() {
+            return;
+        }
+        let title = event.member_text("title").unwrap_or("(no title)");
+        let subject = match method {
+            "REQUEST" if event.member("sequence").as_uint().unwrap_or(0) > 0 => {
+                format!("Updated invitation: {title}")
+            }
+            "REQUEST" => format!("Invitation: {title}"),
+            "CANCEL" => format!("Cancelled: {title}"),
+            _ => {
+                let status = event
+                    .attendees()
+                    .first()
+                    .and_then(|(_, _, participant)| {
+                        participant.field("participationStatus").as_string()
+                    })
+                    .unwrap_or("needs-action");
+                match status {
+                    "accepted" => format!("Accepted: {title}"),
+                    "declined" => format!("Declined: {title}"),
+                    "tentative" => format!("Tentatively accepted: {title}"),
+                    _ => format!("Reply: {title}"),
+                }
+            }
+        };
+        let mut text = format!("{title}\r\n\r\n");
+        if let Some(start) = event.member_text("start") {
+            text.push_str(&format!(
+                "When: {} ({})\r\n",
+                start.replace('T', " "),
+                event
+                    .get(&Property::TimeZone)
+                    .as_string()
+                    .unwrap_or("floating")
+            ));
+        }
+        if let Some(organizer) = event.organizer_address() {
+            text.push_str(&format!("Organizer: {organizer}\r\n"));
+        }
+        if let Some(description) = event.member_text("description") {
+            text.push_str(&format!("\r\n{description}\r\n"));
+        }
+        let ical = ICalendar::build(event, Some(method));
+        let raw_message = MessageBuilder::new()
+            .from(from)
+            .to(recipients
+                .iter()
+                .map(|address| address.as_str())
+                .collect::<Vec<_>>())
+            .header("Auto-Submitted", HeaderType::Text("auto-generated".into()))
+            .message_id(format!(
+                "<{}@{}>",
+                make_boundary("."),
+                from.rsplit_once('@')
+                    .map_or("localhost", |(_, domain)| domain)
+            ))
+            .subject(subject)
+            .body(MimePart::new(
+                ContentType::new("multipart/alternative"),
+                BodyPart::Multipart(vec![
+                    MimePart::new(
+                        ContentType::new("text/plain").attribute("charset", "utf-8"),
+                        BodyPart::Text(text.into()),
+                    ),
+                    MimePart::new(
+                        ContentType::new("text/calendar")
+                            .attribute("charset", "utf-8")
+                            .attribute("method", method),
+                        BodyPart::Text(ical.into()),
+                    ),
+                ]),
+            ))
+            .write_to_vec()
+            .unwrap_or_default();
+
+        // Queue the message on a separate task to keep request futures small
+        let smtp = self.smtp.clone();
+        let from = from.to_string();
+        let method = method.to_string();
+        tokio::spawn(async move {
+            // The server sends on behalf of the organizer or attendee, so
+            // recipients on this server can trust the From address
+            let mut session = Session::<NullIo>::sieve(
+                smtp,
+                SessionAddress::new(from.clone()),
+                recipients.into_iter().map(SessionAddress::new).collect(),
+                raw_message,
+            );
+            session.data.authenticated_emails = vec![from.to_lowercase()];
+            let result = session.queue_message().await;
+
+            tracing::debug!(
+                context = "calendar_scheduling",
+                event = "send_message",
+                method = method,
+                smtp_response = std::str::from_utf8(&result).unwrap_or_default()
+            );
+        });
+    }
+
+    // Processes an iMIP message received by an account (RFC 6047)
+    pub async fn calendar_imip_ingest(
+        &self,
+        account_id: u32,
+        sender: &str,
+        ical: &str,
+    ) -> Result<bool, MethodError> {
+        let ical = match ICalendar::parse(ical) {
+            Ok(ical) => ical,
+            Err(reason) => {
+                tracing::debug!(
+                    context = "calendar_scheduling",
+                    event = "parse-failed",
+                    account_id = account_id,
+                    reason = reason,
+                    "Failed to parse iMIP message."
+                );
+                return Ok(false);
+            }
+        };
+        let method = ical.method.as_deref().unwrap_or_default();
+        if !matches!(method, "REQUEST" | "ADD" | "CANCEL" | "REPLY") {
+            return Ok(false);
+        }
+        let access_token = if let Some(access_token) = self.get_access_token(account_id).await {
+            access_token
+        } else {
+            return Ok(false);
+        };
+        let addresses = self.calendar_account_addresses(account_id).await?;
+        let sender = sender.to_lowercase();
+        let mut create = VecMap::new();
+        let mut update = VecMap::new();
+
+        for event in ical.events {
+            let uid = if let Some(uid) = event.get(&Property::Uid).as_string() {
+                uid.to_string()
+            } else {
+                continue;
+            };
+            let existing = if let Some(document_id) = self
+                .filter(
+                    account_id,
+                    Collection::CalendarEvent,
+                    vec![Filter::eq(Property::Uid, uid.clone())],
+                )
+                .await?
+                .results
+                .min()
+            {
+                self.get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+                .map(|event| (document_id, event.inner))
+            } else {
+                None
+            };
+            let has_master = !matches!(event.member("start"), Value::Null);
+
+            match (method, existing) {
+                ("REQUEST" | "ADD", Some((document_id, existing))) => {
+                    if existing.organizer_address().as_deref() != Some(sender.as_str())
+                        || event.organizer_address().is_some_and(|o| o != sender)
+                    {
+                        continue;
+                    }
+                    let mut changes = Object::with_capacity(event.properties.len());
+                    if has_master {
+                        if event.member("sequence").as_uint().unwrap_or(0)
+                            < existing.member("sequence").as_uint().unwrap_or(0)
+                        {
+                            continue;
+                        }
+                        for (property, _) in &existing.properties {
+                            if !event.properties.contains_key(property)
+                                && !is_local_property(property)
+                            {
+                                changes.append(property.clone(), Value::Null);
+                            }
+                        }
+                        for (property, value) in event.properties {
+                            if !is_local_property(&property) {
+                                changes.append(property, value);
+                            }
+                        }
+                    } else {
+                        add_overrides(&existing, event, &mut changes, |patch| patch);
+                    }
+                    update.append(Id::from(document_id), into_set_object(changes));
+                }
+                ("REQUEST" | "ADD", None) if has_master => {
+                    if event.organizer_address().as_deref() != Some(sender.as_str()) {
+                        continue;
+                    }
+                    let calendar_id = self.calendar_default_id(account_id).await?;
+                    let mut changes = Object::with_capacity(event.properties.len() + 1);
+                    changes.append(
+                        Property::CalendarIds,
+                        Value::List(vec![Value::Id(calendar_id.into())]),
+                    );
+                    for (property, value) in event.properties {
+                        changes.append(property, value);
+                    }
+                    create.append(format!("c{}", create.len()), into_set_object(changes));
+                }
+                ("CANCEL", Some((document_id, existing))) => {
+                    if existing.organizer_address().as_deref() != Some(sender.as_str()) {
+                        continue;
+                    }
+                    let mut changes = Object::with_capacity(2);
+                    if has_master {
+                        changes.append(
+                            Property::_T("status".to_string()),
+                            Value::Text("cancelled".to_string()),
+                        );
+                        if let Some(sequence) = event.member("sequence").as_uint() {
+                            changes.append(
+                                Property::_T("sequence".to_string()),
+                                Value::UnsignedInt(sequence),
+                            );
+                        }
+                    } else {
+                        add_overrides(&existing, event, &mut changes, |_| {
+                            Object::with_capacity(1)
+                                .with_property(Property::_T("excluded".to_string()), true)
+                        });
+                    }
+                    update.append(Id::from(document_id), into_set_object(changes));
+                }
+                ("REPLY", Some((document_id, existing))) => {
+                    if !existing
+                        .organizer_address()
+                        .is_some_and(|organizer| addresses.contains(&organizer))
+                    {
+                        continue;
+                    }
+                    // Locate the replying attendee
+                    let participant_id = if let Some((id, _, _)) = existing
+                        .attendees()
+                        .into_iter()
+                        .find(|(_, address, _)| address == &sender)
+                    {
+                        id.to_string()
+                    } else {
+                        continue;
+                    };
+                    let mut changes = Object::with_capacity(1);
+                    if has_master {
+                        if let Some(status) = event.participation_status(&sender) {
+                            changes.append(
+                                Property::_T(format!(
+                                    "participants/{participant_id}/participationStatus"
+                                )),
+                                Value::Text(status),
+                            );
+                        }
+                    } else if let Some(overrides) = event.member("recurrenceOverrides").as_obj() {
+                        let mut existing_overrides = existing_overrides(&existing);
+                        for (recurrence_id, instance) in &overrides.properties {
+                            if let Some(status) = instance.as_obj().and_then(|instance| {
+                                instance_participation_status(instance, &sender)
+                            }) {
+                                let patch =
+                                    match existing_overrides.properties.get_mut(recurrence_id) {
+                                        Some(Value::Object(patch)) => patch,
+                                        _ => {
+                                            existing_overrides.set(
+                                                recurrence_id.clone(),
+                                                Value::Object(Object::with_capacity(1)),
+                                            );
+                                            existing_overrides
+                                                .properties
+                                                .get_mut(recurrence_id)
+                                                .unwrap()
+                                                .as_obj_mut()
+                                                .unwrap()
+                                        }
+                                    };
+                                patch.set(
+                                    Property::_T(format!(
+                                        "participants/{participant_id}/participationStatus"
+                                    )),
+                                    Value::Text(status),
+                                );
+                            }
+                        }
+                        changes.append(
+                            Property::_T("recurrenceOverrides".to_string()),
+                            Value::Object(existing_overrides),
+                        );
+                    }
+                    if !changes.properties.is_empty() {
+                        update.append(Id::from(document_id), into_set_object(changes));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if create.is_empty() && update.is_empty() {
+            return Ok(false);
+        }
+
+        let mut response = self
+            .calendar_event_set(
+                SetRequest {
+                    account_id: Id::from(account_id),
+                    if_in_state: None,
+                    create: (!create.is_empty()).then_some(create),
+                    update: (!update.is_empty()).then_some(update),
+                    destroy: None,
+                    arguments: EventSetArguments::default(),
+                },
+                &access_token,
+            )
+            .await?;
+        for (id, err) in response
+            .not_created
+            .iter()
+            .map(|(id, err)| (id.to_string(), err))
+            .chain(
+                response
+                    .not_updated
+                    .iter()
+                    .map(|(id, err)| (id.to_string(), err)),
+            )
+        {
+            tracing::debug!(
+                context = "calendar_scheduling",
+                event = "error",
+                account_id = account_id,
+                id = id,
+                error = ?err,
+                "Failed to apply iMIP message."
+            );
+        }
+        if let Some(state_change) = response.state_change.take() {
+            self.broadcast_state_change(state_change).await;
+        }
+
+        Ok(!response.created.is_empty() || !response.updated.is_empty())
+    }
+
+    pub async fn calendar_default_id(&self, account_id: u32) -> Result<u32, MethodError> {
+        let calendar_ids = self.calendar_get_or_create(account_id).await?;
+        Ok(self
+            .filter(
+                account_id,
+                Collection::Calendar,
+                vec![Filter::eq(Property::IsDefault, 1u32)],
+            )
+            .await
+            .ok()
+            .and_then(|result_set| result_set.results.min())
+            .or_else(|| calendar_ids.min())
+            .unwrap_or_default())
+    }
+}
+
+// Returns the sender and the iCalendar object of an iMIP message
+pub fn imip_part<'x>(message: &'x Message<'x>) -> Option<(&'x str, &'x str)> {
+    let ical = message.parts.iter().find_map(|part| {
+        part.content_type()
+            .filter(|ct| {
+                ct.ctype().eq_ignore_ascii_case("text")
+                    && ct
+                        .subtype()
+                        .is_some_and(|st| st.eq_ignore_ascii_case("calendar"))
+                    && ct.attribute("method").is_some()
+            })
+            .and_then(|_| part.text_contents())
+    })?;
+    let sender = message
+        .from()
+        .and_then(|addr| addr.first())
+        .and_then(|addr| addr.address())?;
+    Some((sender, ical))
+}
+
+trait CalendarEventScheduling {
+    fn is_cancelled(&self) -> bool;
+    fn participation_status(&self, address: &str) -> Option<String>;
+}
+
+impl CalendarEventScheduling for Object<Value> {
+    fn is_cancelled(&self) -> bool {
+        self.member_text("status") == Some("cancelled")
+    }
+
+    fn participation_status(&self, address: &str) -> Option<String> {
+        instance_participation_status(self, address)
+    }
+}
+
+fn instance_participation_status(event: &Object<Value>, address: &str) -> Option<String> {
+    event
+        .member("participants")
+        .as_obj()?
+        .properties
+        .iter()
+        .filter_map(|(_, participant)| participant.as_obj())
+        .find(|participant| participant.participant_address().as_deref() == Some(address))
+        .map(|participant| {
+            participant
+                .field("participationStatus")
+                .as_string()
+                .unwrap_or("needs-action")
+                .to_string()
+        })
+}
+
+fn scheduled_attendees(event: &Object<Value>) -> Vec<String> {
+    event
+        .attendees()
+        .into_iter()
+        .filter(|(_, _, participant)| {
+            !matches!(
+                participant.field("scheduleAgent").as_string(),
+                Some("client" | "none")
+            )
+        })
+        .map(|(_, address, _)| address)
+        .collect()
+}
+
+fn cancelled_event(event: &Object<Value>) -> Object<Value> {
+    let mut event = event.clone();
+    event.set(
+        Property::_T("status".to_string()),
+        Value::Text("cancelled".to_string()),
+    );
+    event
+}
+
+fn reply_event(
+    event: &Object<Value>,
+    organizer: &str,
+    address: &str,
+    status: Option<&str>,
+) -> Object<Value> {
+    let mut event = event.clone();
+    let participants = Property::_T("participants".to_string());
+    if let Some(Value::Object(participants)) = event.properties.get_mut(&participants) {
+        let mut retained = Object::with_capacity(2);
+        for (id, participant) in std::mem::take(&mut participants.properties) {
+            if participant.as_obj().is_some_and(|participant| {
+                participant
+                    .participant_address()
+                    .is_some_and(|participant| participant == organizer || participant == address)
+            }) {
+                retained.append(id, participant);
+            }
+        }
+        *participants = retained;
+        if let Some(status) = status {
+            for (_, participant) in participants.properties.iter_mut() {
+                if let Value::Object(participant) = participant {
+                    if participant.participant_address().as_deref() == Some(address) {
+                        participant.set(
+                            Property::_T("participationStatus".to_string()),
+                            Value::Text(status.to_string()),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    for name in LOCAL_PROPERTIES {
+        event.properties.remove(&Property::parse(name));
+    }
+    event
+}
+
+fn existing_overrides(existing: &Object<Value>) -> Object<Value> {
+    existing
+        .member("recurrenceOverrides")
+        .as_obj()
+        .cloned()
+        .unwrap_or_else(|| Object::with_capacity(1))
+}
+
+fn add_overrides(
+    existing: &Object<Value>,
+    event: Object<Value>,
+    changes: &mut Object<Value>,
+    patch_fn: impl Fn(Object<Value>) -> Object<Value>,
+) {
+    let mut overrides = existing_overrides(existing);
+    if let Some(Value::Object(instances)) = event
+        .properties
+        .into_iter()
+        .find(|(property, _)| property == &Property::_T("recurrenceOverrides".to_string()))
+        .map(|(_, value)| value)
+    {
+        for (recurrence_id, instance) in instances.properties {
+            let patch = match instance {
+                Value::Object(instance) => instance_patch(existing, instance),
+                _ => Object::with_capacity(0),
+            };
+            overrides.set(recurrence_id, Value::Object(patch_fn(patch)));
+        }
+    }
+    changes.append(
+        Property::_T("recurrenceOverrides".to_string()),
+        Value::Object(overrides),
+    );
+}
+
+pub(crate) fn into_set_object(object: Object<Value>) -> Object<SetValue> {
+    let mut result: Object<SetValue> = Object {
+        properties: Default::default(),
+    };
+    for (property, value) in object.properties {
+        result.properties.append(property, SetValue::Value(value));
+    }
+    result
+}
+
+fn is_local_property(property: &Property) -> bool {
+    matches!(
+        property,
+        Property::CalendarIds | Property::UtcStart | Property::UtcEnd
+    ) || LOCAL_PROPERTIES
+        .iter()
+        .any(|name| &Property::parse(name) == property)
+}
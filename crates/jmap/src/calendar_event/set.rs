@@ -0,0 +1,768 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::{method::MethodError, set::SetError},
+    method::set::{SetRequest, SetResponse},
+    object::{
+        calendar::EventSetArguments,
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{MaybePatchValue, SetValue, Value},
+    },
+};
+use store::{
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder},
+    Serialize,
+};
+
+use crate::{
+    auth::AccessToken,
+    contact::{parse_pointer, set::generate_uuid, JSObjectPatch},
+    JMAP,
+};
+
+use super::JSCalendarEvent;
+
+pub struct SetContext<'x> {
+    pub account_id: u32,
+    pub access_token: &'x AccessToken,
+    pub calendar_ids: RoaringBitmap,
+    pub can_add_ids: Option<RoaringBitmap>,
+    pub can_modify_ids: Option<RoaringBitmap>,
+    pub can_remove_ids: Option<RoaringBitmap>,
+    pub send_scheduling_messages: bool,
+    pub addresses: Vec<String>,
+}
+
+pub struct EventChanges {
+    pub builder: ObjectIndexBuilder,
+    pub event: Object<Value>,
+    pub server_set: Object<Value>,
+}
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::CalendarIds)
+        .index_as(IndexAs::IntegerList)
+        .required(),
+    IndexProperty::new(Property::Uid).index_as(IndexAs::Text {
+        tokenize: false,
+        index: true,
+    }),
+    IndexProperty::new(Property::UtcStart).index_as(IndexAs::LongInteger),
+    IndexProperty::new(Property::UtcEnd).index_as(IndexAs::LongInteger),
+    IndexProperty::new(Property::Created).index_as(IndexAs::Text {
+        tokenize: false,
+        index: true,
+    }),
+    IndexProperty::new(Property::Updated).index_as(IndexAs::Text {
+        tokenize: false,
+        index: true,
+    }),
+];
+
+impl JMAP {
+    pub async fn calendar_event_set(
+        &self,
+        mut request: SetRequest<EventSetArguments>,
+        access_token: &AccessToken,
+    ) -> Result<SetResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut response = self
+            .prepare_set_response(&request, Collection::CalendarEvent)
+            .await?;
+        let send_scheduling_messages = request.arguments.send_scheduling_messages.unwrap_or(false);
+        let ctx = self
+            .calendar_event_set_context(account_id, access_token, send_scheduling_messages)
+            .await?;
+        let event_ids = self
+            .owned_or_shared_calendar_events(access_token, account_id, Acl::ReadItems)
+            .await?;
+        let will_destroy = request.unwrap_destroy();
+        let mut scheduling = Vec::new();
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        for (id, object) in request.unwrap_create() {
+            match self
+                .calendar_event_set_item(object, None, &ctx, &response)
+                .await?
+            {
+                Ok(event_changes) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::CalendarEvent)
+                        .create_document()
+                        .custom(event_changes.builder);
+                    let document_id = self.write_batch_expect_id(batch).await?;
+                    changes.log_insert(Collection::CalendarEvent, document_id);
+                    response.created(id.clone(), document_id);
+
+                    // Return server-set properties
+                    if let Some(created) = response.created.get_mut(&id) {
+                        for (property, value) in event_changes.server_set.properties {
+                            created.append(property, value);
+                        }
+                    }
+                    if ctx.send_scheduling_messages {
+                        scheduling.push((None, Some(event_changes.event)));
+                    }
+                }
+                Err(err) => {
+                    response.not_created.append(id, err);
+                }
+            }
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain event
+            let document_id = id.document_id();
+            let event = if let Some(event) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+                .filter(|_| event_ids.contains(document_id))
+            {
+                event
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+            let previous = ctx.send_scheduling_messages.then(|| event.inner.clone());
+
+            match self
+                .calendar_event_set_item(object, event.into(), &ctx, &response)
+                .await?
+            {
+                Ok(event_changes) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::CalendarEvent)
+                        .update_document(document_id)
+                        .custom(event_changes.builder);
+                    if !batch.is_empty() {
+                        match self.core.storage.data.write(batch.build()).await {
+                            Ok(_) => {
+                                changes.log_update(Collection::CalendarEvent, document_id);
+                            }
+                            Err(store::Error::AssertValueFailed) => {
+                                response.not_updated.append(
+                                    id,
+                                    SetError::forbidden().with_description(
+                                        "Another process modified this event, please try again.",
+                                    ),
+                                );
+                                continue 'update;
+                            }
+                            Err(err) => {
+                                tracing::error!(
+                                    event = "error",
+                                    context = "calendar_event_set",
+                                    account_id = account_id,
+                                    error = ?err,
+                                    "Failed to update calendar event(s).");
+                                return Err(MethodError::ServerPartialFail);
+                            }
+                        }
+                        if previous.is_some() {
+                            scheduling.push((previous, Some(event_changes.event)));
+                        }
+                    }
+                    response.updated.append(
+                        id,
+                        if !event_changes.server_set.properties.is_empty() {
+                            Some(event_changes.server_set)
+                        } else {
+                            None
+                        },
+                    );
+                }
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                }
+            }
+        }
+
+        // Process deletions
+        for id in will_destroy {
+            let document_id = id.document_id();
+            let event = if let Some(event) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+                .filter(|_| event_ids.contains(document_id))
+            {
+                event
+            } else {
+                response.not_destroyed.append(id, SetError::not_found());
+                continue;
+            };
+
+            // Validate ACLs
+            if let Some(can_remove_ids) = &ctx.can_remove_ids {
+                if !event
+                    .inner
+                    .calendar_ids()
+                    .all(|calendar_id| can_remove_ids.contains(calendar_id))
+                {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "You are not allowed to delete events from this calendar.",
+                        ),
+                    );
+                    continue;
+                }
+            }
+            let previous = ctx.send_scheduling_messages.then(|| event.inner.clone());
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::CalendarEvent)
+                .delete_document(document_id)
+                .custom(ObjectIndexBuilder::new(SCHEMA).with_current(event));
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => {
+                    changes.log_delete(Collection::CalendarEvent, document_id);
+                    response.destroyed.push(id);
+                    if previous.is_some() {
+                        scheduling.push((previous, None));
+                    }
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden().with_description(concat!(
+                            "Another process modified this event ",
+                            "while deleting it, please try again."
+                        )),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "calendar_event_set",
+                        account_id = account_id,
+                        document_id = document_id,
+                        error = ?err,
+                        "Failed to delete calendar event.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            let change_id = self.commit_changes(account_id, changes).await?;
+            response.new_state = Some(change_id.into());
+            response.state_change = StateChange::new(account_id)
+                .with_change(DataType::CalendarEvent, change_id)
+                .into();
+        }
+
+        // Send iTIP messages
+        for (previous, current) in scheduling {
+            self.calendar_event_schedule(&ctx, previous.as_ref(), current.as_ref())
+                .await;
+        }
+
+        Ok(response)
+    }
+
+    pub async fn calendar_event_set_context<'x>(
+        &self,
+        account_id: u32,
+        access_token: &'x AccessToken,
+        send_scheduling_messages: bool,
+    ) -> Result<SetContext<'x>, MethodError> {
+        let calendar_ids = self.calendar_get_or_create(account_id).await?;
+        let addresses = self.calendar_account_addresses(account_id).await?;
+        Ok(if access_token.is_shared(account_id) {
+            SetContext {
+                account_id,
+                access_token,
+                calendar_ids,
+                can_add_ids: self
+                    .shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::Calendar,
+                        Acl::AddItems,
+                    )
+                    .await?
+                    .into(),
+                can_modify_ids: self
+                    .shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::Calendar,
+                        Acl::ModifyItems,
+                    )
+                    .await?
+                    .into(),
+                can_remove_ids: self
+                    .shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::Calendar,
+                        Acl::RemoveItems,
+                    )
+                    .await?
+                    .into(),
+                send_scheduling_messages,
+                addresses,
+            }
+        } else {
+            SetContext {
+                account_id,
+                access_token,
+                calendar_ids,
+                can_add_ids: None,
+                can_modify_ids: None,
+                can_remove_ids: None,
+                send_scheduling_messages,
+                addresses,
+            }
+        })
+    }
+
+    pub async fn calendar_event_set_item(
+        &self,
+        changes_: Object<SetValue>,
+        current: Option<HashedValue<Object<Value>>>,
+        ctx: &SetContext<'_>,
+        response: &impl EvalObjectReferences,
+    ) -> Result<Result<EventChanges, SetError>, MethodError> {
+        let mut changes = Object::with_capacity(changes_.properties.len());
+        let mut pointers = Vec::new();
+        let mut calendar_ids = current
+            .as_ref()
+            .map(|event| event.inner.calendar_ids().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let mut has_event_changes = false;
+
+        for (property, value) in changes_.properties {
+            let value = match response.eval_object_references(value) {
+                Ok(value) => value,
+                Err(err) => {
+                    return Ok(Err(err));
+                }
+            };
+
+            match (property, value) {
+                (Property::CalendarIds, MaybePatchValue::Value(Value::List(ids))) => {
+                    calendar_ids.clear();
+                    for id in ids {
+                        if let Some(id) = id.try_unwrap_id() {
+                            let document_id = id.document_id();
+                            if !calendar_ids.contains(&document_id) {
+                                calendar_ids.push(document_id);
+                            }
+                        }
+                    }
+                }
+                (Property::CalendarIds, MaybePatchValue::Patch(patch)) => {
+                    let mut patch = patch.into_iter();
+                    if let Some(id) = patch.next().unwrap().try_unwrap_id() {
+                        let document_id = id.document_id();
+                        if patch.next().unwrap().try_unwrap_bool().unwrap_or_default() {
+                            if !calendar_ids.contains(&document_id) {
+                                calendar_ids.push(document_id);
+                            }
+                        } else {
+                            calendar_ids.retain(|id| *id != document_id);
+                        }
+                    }
+                }
+                (Property::Uid, MaybePatchValue::Value(Value::Text(uid))) if !uid.is_empty() => {
+                    if let Some(current) = &current {
+                        if current.inner.get(&Property::Uid).as_string() != Some(uid.as_str()) {
+                            return Ok(Err(SetError::invalid_properties()
+                                .with_property(Property::Uid)
+                                .with_description("The uid of an event cannot be changed.")));
+                        }
+                    } else {
+                        changes.append(Property::Uid, Value::Text(uid));
+                    }
+                }
+                (Property::_T(pointer), MaybePatchValue::Value(value)) if pointer.contains('/') => {
+                    pointers.push((parse_pointer(&pointer), value));
+                    has_event_changes = true;
+                }
+                (
+                    property @ (Property::Id
+                    | Property::Uid
+                    | Property::CalendarIds
+                    | Property::UtcStart
+                    | Property::UtcEnd),
+                    _,
+                ) => {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value.".to_string())));
+                }
+                (property, MaybePatchValue::Value(value)) => {
+                    if current.is_some() || value != Value::Null {
+                        changes.append(property, value);
+                        has_event_changes = true;
+                    }
+                }
+                (property, _) => {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value.".to_string())));
+                }
+            }
+        }
+
+        // Apply JSON pointer patches
+        for (path, value) in pointers {
+            let property = Property::parse(&path[0]);
+            if matches!(
+                property,
+                Property::Id
+                    | Property::Uid
+                    | Property::CalendarIds
+                    | Property::UtcStart
+                    | Property::UtcEnd
+            ) || path.len() < 2
+            {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::_T(path.join("/")))
+                    .with_description("Invalid patch.")));
+            }
+            let item = match changes.properties.get_mut(&property) {
+                Some(item) => item,
+                None => {
+                    let item = current
+                        .as_ref()
+                        .and_then(|event| event.inner.properties.get(&property))
+                        .cloned()
+                        .unwrap_or(Value::Null);
+                    changes.append(property.clone(), item);
+                    changes.properties.get_mut(&property).unwrap()
+                }
+            };
+            if !item.patch_pointer(&path[1..], value) {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::_T(path.join("/")))
+                    .with_description("Patch path does not exist.")));
+            }
+        }
+
+        // Validate calendars
+        if calendar_ids.is_empty() {
+            return Ok(Err(SetError::invalid_properties()
+                .with_property(Property::CalendarIds)
+                .with_description(
+                    "Event has to belong to at least one calendar.",
+                )));
+        } else if matches!(self.core.jmap.calendars_max_calendars_per_event, Some(max) if calendar_ids.len() > max)
+        {
+            return Ok(Err(SetError::invalid_properties()
+                .with_property(Property::CalendarIds)
+                .with_description("Event belongs to too many calendars.")));
+        }
+        let current_calendar_ids = current
+            .as_ref()
+            .map(|event| event.inner.calendar_ids().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for calendar_id in &calendar_ids {
+            if !current_calendar_ids.contains(calendar_id) {
+                if !ctx.calendar_ids.contains(*calendar_id) {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(Property::CalendarIds)
+                        .with_description(format!(
+                            "calendarId {} does not exist.",
+                            jmap_proto::types::id::Id::from(*calendar_id)
+                        ))));
+                } else if matches!(&ctx.can_add_ids, Some(ids) if !ids.contains(*calendar_id)) {
+                    return Ok(Err(SetError::forbidden().with_description(format!(
+                        "You are not allowed to add events to calendar {}.",
+                        jmap_proto::types::id::Id::from(*calendar_id)
+                    ))));
+                }
+            }
+        }
+        if let Some(can_remove_ids) = &ctx.can_remove_ids {
+            if current_calendar_ids.iter().any(|calendar_id| {
+                !calendar_ids.contains(calendar_id) && !can_remove_ids.contains(*calendar_id)
+            }) {
+                return Ok(Err(SetError::forbidden().with_description(
+                    "You are not allowed to remove events from this calendar.",
+                )));
+            }
+        }
+        if let (Some(can_modify_ids), true) = (&ctx.can_modify_ids, current.is_some()) {
+            if has_event_changes
+                && !current_calendar_ids
+                    .iter()
+                    .any(|calendar_id| can_modify_ids.contains(*calendar_id))
+            {
+                return Ok(Err(SetError::forbidden()
+                    .with_description("You are not allowed to modify this event.")));
+            }
+        }
+        if calendar_ids != current_calendar_ids {
+            changes.append(
+                Property::CalendarIds,
+                Value::List(
+                    calendar_ids
+                        .into_iter()
+                        .map(|id| Value::Id(id.into()))
+                        .collect(),
+                ),
+            );
+        }
+
+        // Add server-set properties
+        let mut server_set = Object::with_capacity(2);
+        let type_property = Property::_T("@type".to_string());
+        if current.is_none() {
+            if !changes.properties.contains_key(&Property::Uid) {
+                let uid = generate_uuid();
+                changes.append(Property::Uid, Value::Text(uid.clone()));
+                server_set.append(Property::Uid, Value::Text(uid));
+            }
+            if !changes.properties.contains_key(&type_property) {
+                changes.append(type_property.clone(), Value::Text("Event".to_string()));
+            }
+            let now = Value::Text(super::format_utc_date_time(store::write::now() as i64));
+            for property in [Property::Created, Property::Updated] {
+                if !changes.properties.contains_key(&property) {
+                    changes.append(property, now.clone());
+                }
+            }
+        } else if has_event_changes && !changes.properties.contains_key(&Property::Updated) {
+            changes.append(
+                Property::Updated,
+                Value::Text(super::format_utc_date_time(store::write::now() as i64)),
+            );
+        }
+        match changes.get(&type_property) {
+            Value::Text(value) if value == "Event" => (),
+            Value::Null if current.is_some() => (),
+            _ => {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(type_property)
+                    .with_description("Invalid JSCalendar object type.")));
+            }
+        }
+
+        // Build the resulting event
+        let mut event = if let Some(current) = &current {
+            let mut event = current.inner.clone();
+            for (property, value) in changes.properties.iter() {
+                if value != &Value::Null {
+                    event.properties.set(property.clone(), value.clone());
+                } else {
+                    event.properties.remove(property);
+                }
+            }
+            event
+        } else {
+            changes.clone()
+        };
+
+        // Validate start and participants
+        let (utc_start, utc_end) = if let Some(range) = event.utc_range() {
+            range
+        } else {
+            return Ok(Err(SetError::invalid_properties()
+                .with_property(Property::_T("start".to_string()))
+                .with_description("Event has a missing or invalid start date.")));
+        };
+        if let Some(max_participants) = self.core.jmap.calendars_max_participants_per_event {
+            if event
+                .member("participants")
+                .as_obj()
+                .is_some_and(|participants| participants.properties.len() > max_participants)
+            {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::_T("participants".to_string()))
+                    .with_description("Event has too many participants.")));
+            }
+        }
+
+        // Increment the sequence number when the organizer updates a scheduled event
+        let sequence_property = Property::_T("sequence".to_string());
+        if let Some(current) = &current {
+            if ctx.send_scheduling_messages
+                && has_event_changes
+                && !changes.properties.contains_key(&sequence_property)
+                && event
+                    .organizer_address()
+                    .is_some_and(|organizer| ctx.addresses.contains(&organizer))
+            {
+                let sequence = Value::UnsignedInt(
+                    current.inner.get(&sequence_property).as_uint().unwrap_or(0) + 1,
+                );
+                event.set(sequence_property.clone(), sequence.clone());
+                changes.append(sequence_property.clone(), sequence.clone());
+                server_set.append(sequence_property, sequence);
+            }
+        }
+
+        // Index the UTC time range
+        for (property, value) in [
+            (Property::UtcStart, utc_start.max(0) as u64),
+            (Property::UtcEnd, utc_end.max(0) as u64),
+        ] {
+            let value = Value::UnsignedInt(value);
+            if event.get(&property) != &value {
+                event.set(property.clone(), value.clone());
+                changes.append(property, value);
+            }
+        }
+
+        // Validate size
+        if (&event).serialize().len() > self.core.jmap.calendars_max_event_size {
+            return Ok(Err(SetError::too_large().with_description(format!(
+                "Calendar event exceeds maximum size of {} bytes.",
+                self.core.jmap.calendars_max_event_size
+            ))));
+        }
+
+        // Validate
+        Ok(ObjectIndexBuilder::new(SCHEMA)
+            .with_changes(changes)
+            .with_current_opt(current)
+            .validate()
+            .map(|builder| EventChanges {
+                builder,
+                event,
+                server_set,
+            }))
+    }
+
+    pub async fn calendar_event_remove_calendar(
+        &self,
+        account_id: u32,
+        calendar_id: u32,
+        event_ids: &RoaringBitmap,
+        changes: &mut ChangeLogBuilder,
+    ) -> Result<Result<(), SetError>, MethodError> {
+        // If the event is in multiple calendars, untag it from the current one,
+        // otherwise delete it.
+        for (document_id, event) in self
+            .get_properties::<HashedValue<Object<Value>>, _, _>(
+                account_id,
+                Collection::CalendarEvent,
+                event_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            let calendar_ids = event
+                .inner
+                .calendar_ids()
+                .filter(|id| *id != calendar_id)
+                .map(|id| Value::Id(id.into()))
+                .collect::<Vec<_>>();
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::CalendarEvent);
+            if !calendar_ids.is_empty() {
+                batch.update_document(document_id).custom(
+                    ObjectIndexBuilder::new(SCHEMA)
+                        .with_current(event)
+                        .with_changes(
+                            Object::with_capacity(1)
+                                .with_property(Property::CalendarIds, Value::List(calendar_ids)),
+                        ),
+                );
+                changes.log_update(Collection::CalendarEvent, document_id);
+            } else {
+                batch
+                    .delete_document(document_id)
+                    .custom(ObjectIndexBuilder::new(SCHEMA).with_current(event));
+                changes.log_delete(Collection::CalendarEvent, document_id);
+            }
+
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => (),
+                Err(store::Error::AssertValueFailed) => {
+                    return Ok(Err(SetError::forbidden().with_description(concat!(
+                        "Another process modified an event in this calendar ",
+                        "while deleting it, please try again."
+                    ))));
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "calendar_set",
+                        account_id = account_id,
+                        calendar_id = calendar_id,
+                        document_id = document_id,
+                        error = ?err,
+                        "Failed to update calendar event while deleting calendar.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+pub trait CalendarEventCalendars {
+    fn calendar_ids(&self) -> impl Iterator<Item = u32> + '_;
+}
+
+impl CalendarEventCalendars for Object<Value> {
+    fn calendar_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.get(&Property::CalendarIds)
+            .as_list()
+            .into_iter()
+            .flatten()
+            .filter_map(|id| id.as_id().map(|id| id.document_id()))
+    }
+}
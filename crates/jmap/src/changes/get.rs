@@ -62,6 +62,26 @@ impl JMAP {
 
                 Collection::EmailSubmission
             }
+            RequestArguments::AddressBook => {
+                access_token.assert_has_access(request.account_id, Collection::AddressBook)?;
+
+                Collection::AddressBook
+            }
+            RequestArguments::ContactCard => {
+                access_token.assert_has_access(request.account_id, Collection::ContactCard)?;
+
+                Collection::ContactCard
+            }
+            RequestArguments::Calendar => {
+                access_token.assert_has_access(request.account_id, Collection::Calendar)?;
+
+                Collection::Calendar
+            }
+            RequestArguments::CalendarEvent => {
+                access_token.assert_has_access(request.account_id, Collection::CalendarEvent)?;
+
+                Collection::CalendarEvent
+            }
             RequestArguments::Quota => {
                 access_token.assert_is_member(request.account_id)?;
 
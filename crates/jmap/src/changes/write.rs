@@ -93,6 +93,10 @@ impl JMAP {
             Collection::Thread,
             Collection::Identity,
             Collection::EmailSubmission,
+            Collection::AddressBook,
+            Collection::ContactCard,
+            Collection::Calendar,
+            Collection::CalendarEvent,
         ] {
             self.core
                 .storage
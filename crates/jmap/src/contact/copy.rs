@@ -0,0 +1,186 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::{method::MethodError, set::SetError},
+    method::{
+        copy::{CopyRequest, CopyResponse, RequestArguments},
+        set::{self, SetRequest},
+    },
+    object::Object,
+    request::{
+        method::{MethodFunction, MethodName, MethodObject},
+        reference::MaybeReference,
+        Call, RequestMethod,
+    },
+    types::{
+        acl::Acl,
+        collection::Collection,
+        property::Property,
+        state::{State, StateChange},
+        type_state::DataType,
+        value::{SetValue, Value},
+    },
+};
+use store::write::{log::ChangeLogBuilder, BatchBuilder};
+use utils::map::vec_map::VecMap;
+
+use crate::{auth::AccessToken, JMAP};
+
+impl JMAP {
+    pub async fn contact_card_copy(
+        &self,
+        request: CopyRequest<RequestArguments>,
+        access_token: &AccessToken,
+        next_call: &mut Option<Call<RequestMethod>>,
+    ) -> Result<CopyResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let from_account_id = request.from_account_id.document_id();
+
+        if account_id == from_account_id {
+            return Err(MethodError::InvalidArguments(
+                "From accountId is equal to fromAccountId".to_string(),
+            ));
+        }
+        let old_state = self
+            .assert_state(account_id, Collection::ContactCard, &request.if_in_state)
+            .await?;
+        let mut response = CopyResponse {
+            from_account_id: request.from_account_id,
+            account_id: request.account_id,
+            new_state: old_state.clone(),
+            old_state,
+            created: VecMap::with_capacity(request.create.len()),
+            not_created: VecMap::new(),
+            state_change: None,
+        };
+
+        let from_card_ids = self
+            .owned_or_shared_contacts(access_token, from_account_id, Acl::ReadItems)
+            .await?;
+        let ctx = self
+            .contact_card_set_context(account_id, access_token)
+            .await?;
+        let on_success_delete = request.on_success_destroy_original.unwrap_or(false);
+        let mut destroy_ids = Vec::new();
+        let mut changes = ChangeLogBuilder::new();
+
+        for (id, create) in request.create {
+            let id = id.unwrap();
+            let from_card_id = id.document_id();
+            let card = if let Some(card) = self
+                .get_property::<Object<Value>>(
+                    from_account_id,
+                    Collection::ContactCard,
+                    from_card_id,
+                    Property::Value,
+                )
+                .await?
+                .filter(|_| from_card_ids.contains(from_card_id))
+            {
+                card
+            } else {
+                response.not_created.append(
+                    id,
+                    SetError::not_found().with_description(format!(
+                        "Item {} not found not found in account {}.",
+                        id, response.from_account_id
+                    )),
+                );
+                continue;
+            };
+
+            // Overlay the requested changes on top of the original card
+            let mut object = Object {
+                properties: VecMap::with_capacity(card.properties.len() + create.properties.len()),
+            };
+            for (property, value) in card.properties {
+                if property != Property::AddressBookIds {
+                    object.properties.append(property, SetValue::Value(value));
+                }
+            }
+            for (property, value) in create.properties {
+                if property != Property::Id {
+                    object.properties.set(property, value);
+                }
+            }
+
+            match self
+                .contact_card_set_item(object, None, &ctx, &response)
+                .await?
+            {
+                Ok((builder, _)) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::ContactCard)
+                        .create_document()
+                        .custom(builder);
+                    let document_id = self.write_batch_expect_id(batch).await?;
+                    changes.log_insert(Collection::ContactCard, document_id);
+                    response.created.append(
+                        id,
+                        Object::with_capacity(1)
+                            .with_property(Property::Id, Value::Id(document_id.into())),
+                    );
+
+                    // Add to destroy list
+                    if on_success_delete {
+                        destroy_ids.push(id);
+                    }
+                }
+                Err(err) => {
+                    response.not_created.append(id, err);
+                }
+            }
+        }
+
+        // Update state
+        if !changes.is_empty() {
+            let change_id = changes.change_id;
+            response.new_state = State::Exact(self.commit_changes(account_id, changes).await?);
+            response.state_change = StateChange::new(account_id)
+                .with_change(DataType::ContactCard, change_id)
+                .into();
+        }
+
+        // Destroy ids
+        if on_success_delete && !destroy_ids.is_empty() {
+            *next_call = Call {
+                id: String::new(),
+                name: MethodName::new(MethodObject::ContactCard, MethodFunction::Set),
+                method: RequestMethod::Set(SetRequest {
+                    account_id: request.from_account_id,
+                    if_in_state: request.destroy_from_if_in_state,
+                    create: None,
+                    update: None,
+                    destroy: MaybeReference::Value(destroy_ids).into(),
+                    arguments: set::RequestArguments::ContactCard,
+                }),
+            }
+            .into();
+        }
+
+        Ok(response)
+    }
+}
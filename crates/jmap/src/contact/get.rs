@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::get::{GetRequest, GetResponse, RequestArguments},
+    object::Object,
+    types::{acl::Acl, collection::Collection, id::Id, property::Property, value::Value},
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::set::ContactCardAddressBooks;
+
+impl JMAP {
+    pub async fn contact_card_get(
+        &self,
+        mut request: GetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<GetResponse, MethodError> {
+        let ids = request.unwrap_ids(self.core.jmap.get_max_objects)?;
+        let properties = request.unwrap_properties(&[]);
+        let account_id = request.account_id.document_id();
+        let card_ids = self
+            .owned_or_shared_contacts(access_token, account_id, Acl::ReadItems)
+            .await?;
+        let ids = if let Some(ids) = ids {
+            ids
+        } else {
+            card_ids
+                .iter()
+                .take(self.core.jmap.get_max_objects)
+                .map(Into::into)
+                .collect::<Vec<_>>()
+        };
+        let mut response = GetResponse {
+            account_id: request.account_id.into(),
+            state: self
+                .get_state(account_id, Collection::ContactCard)
+                .await?
+                .into(),
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for id in ids {
+            // Obtain the card object
+            let document_id = id.document_id();
+            if !card_ids.contains(document_id) {
+                response.not_found.push(id.into());
+                continue;
+            }
+            let mut values = if let Some(values) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::ContactCard,
+                    document_id,
+                    &Property::Value,
+                )
+                .await?
+            {
+                values
+            } else {
+                response.not_found.push(id.into());
+                continue;
+            };
+
+            let mut address_book_ids = Object::with_capacity(1);
+            for address_book_id in values.address_book_ids() {
+                address_book_ids.append(Property::_T(Id::from(address_book_id).to_string()), true);
+            }
+            values.properties.remove(&Property::AddressBookIds);
+
+            // Return all properties when none are requested
+            let card = if properties.is_empty() {
+                let mut card = Object::with_capacity(values.properties.len() + 2)
+                    .with_property(Property::Id, id)
+                    .with_property(Property::AddressBookIds, address_book_ids);
+                for (property, value) in values.properties {
+                    card.append(property, value);
+                }
+                card
+            } else {
+                let mut card = Object::with_capacity(properties.len());
+                for property in &properties {
+                    let value = match property {
+                        Property::Id => Value::Id(id),
+                        Property::AddressBookIds => {
+                            Value::Object(std::mem::take(&mut address_book_ids))
+                        }
+                        property => values.remove(property),
+                    };
+                    card.append(property.clone(), value);
+                }
+                card
+            };
+
+            response.list.push(card);
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,89 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+pub mod copy;
+pub mod get;
+pub mod query;
+pub mod set;
+pub mod vcard;
+
+pub trait JSObjectPatch {
+    fn patch_pointer(&mut self, path: &[String], value: Value) -> bool;
+}
+
+pub trait JSObjectText {
+    fn contains_text(&self, text: &str) -> bool;
+}
+
+impl JSObjectPatch for Value {
+    fn patch_pointer(&mut self, path: &[String], value: Value) -> bool {
+        match (self, path) {
+            (Value::Object(obj), [member]) => {
+                let member = Property::_T(member.clone());
+                if value != Value::Null {
+                    obj.properties.set(member, value);
+                } else {
+                    obj.properties.remove(&member);
+                }
+                true
+            }
+            (Value::Object(obj), [member, path @ ..]) => obj
+                .properties
+                .get_mut(&Property::_T(member.clone()))
+                .is_some_and(|item| item.patch_pointer(path, value)),
+            _ => false,
+        }
+    }
+}
+
+impl JSObjectText for Value {
+    fn contains_text(&self, text: &str) -> bool {
+        match self {
+            Value::Text(value) => value.to_lowercase().contains(text),
+            Value::List(values) => values.iter().any(|value| value.contains_text(text)),
+            Value::Object(obj) => obj.contains_text(text),
+            _ => false,
+        }
+    }
+}
+
+impl JSObjectText for Object<Value> {
+    fn contains_text(&self, text: &str) -> bool {
+        self.properties
+            .iter()
+            .any(|(_, value)| value.contains_text(text))
+    }
+}
+
+// Splits a JSON pointer into its unescaped reference tokens (RFC 6901)
+pub fn parse_pointer(pointer: &str) -> Vec<String> {
+    pointer
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
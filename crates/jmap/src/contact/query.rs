@@ -0,0 +1,159 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::query::{
+        Comparator, Filter, QueryRequest, QueryResponse, RequestArguments, SortProperty,
+    },
+    object::Object,
+    types::{acl::Acl, collection::Collection, property::Property, value::Value},
+};
+use store::{query, roaring::RoaringBitmap};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::JSObjectText;
+
+impl JMAP {
+    pub async fn contact_card_query(
+        &self,
+        mut request: QueryRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<QueryResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut filters = Vec::with_capacity(request.filter.len());
+        let mut cards = None;
+
+        for cond in std::mem::take(&mut request.filter) {
+            match cond {
+                Filter::InAddressBook(id) => filters.push(query::Filter::eq(
+                    Property::AddressBookIds,
+                    id.document_id(),
+                )),
+                Filter::Uid(uid) => filters.push(query::Filter::eq(Property::Uid, uid)),
+                Filter::Kind(kind) => filters.push(query::Filter::eq(Property::Kind, kind)),
+                Filter::Text(_)
+                | Filter::Name(_)
+                | Filter::Email(_)
+                | Filter::Phone(_)
+                | Filter::Organization(_)
+                | Filter::HasMember(_) => {
+                    // JSContact members are not indexed, match them against the stored cards
+                    if cards.is_none() {
+                        let card_ids = self
+                            .get_document_ids(account_id, Collection::ContactCard)
+                            .await?
+                            .unwrap_or_default();
+                        cards = self
+                            .get_properties::<Object<Value>, _, _>(
+                                account_id,
+                                Collection::ContactCard,
+                                &card_ids,
+                                Property::Value,
+                            )
+                            .await?
+                            .into();
+                    }
+
+                    filters.push(query::Filter::is_in_set(
+                        cards
+                            .as_ref()
+                            .unwrap()
+                            .iter()
+                            .filter(|(_, card)| card.matches_filter(&cond))
+                            .map(|(document_id, _)| *document_id)
+                            .collect::<RoaringBitmap>(),
+                    ));
+                }
+                Filter::And | Filter::Or | Filter::Not | Filter::Close => {
+                    filters.push(cond.into());
+                }
+
+                other => return Err(MethodError::UnsupportedFilter(other.to_string())),
+            }
+        }
+
+        let mut result_set = self
+            .filter(account_id, Collection::ContactCard, filters)
+            .await?;
+        if access_token.is_shared(account_id) {
+            result_set.apply_mask(
+                self.shared_contacts(access_token, account_id, Acl::ReadItems)
+                    .await?,
+            );
+        }
+        let (response, paginate) = self.build_query_response(&result_set, &request).await?;
+
+        if let Some(paginate) = paginate {
+            // Parse sort criteria
+            let mut comparators = Vec::with_capacity(request.sort.as_ref().map_or(1, |s| s.len()));
+            for comparator in request
+                .sort
+                .and_then(|s| if !s.is_empty() { s.into() } else { None })
+                .unwrap_or_else(|| vec![Comparator::ascending(SortProperty::Created)])
+            {
+                comparators.push(match comparator.property {
+                    SortProperty::Created => {
+                        query::Comparator::field(Property::Created, comparator.is_ascending)
+                    }
+                    SortProperty::Updated => {
+                        query::Comparator::field(Property::Updated, comparator.is_ascending)
+                    }
+
+                    other => return Err(MethodError::UnsupportedSort(other.to_string())),
+                });
+            }
+
+            // Sort results
+            self.sort(result_set, comparators, paginate, response).await
+        } else {
+            Ok(response)
+        }
+    }
+}
+
+trait ContactCardFilter {
+    fn matches_filter(&self, filter: &Filter) -> bool;
+}
+
+impl ContactCardFilter for Object<Value> {
+    fn matches_filter(&self, filter: &Filter) -> bool {
+        let (property, text) = match filter {
+            Filter::Text(text) => {
+                return self.contains_text(&text.to_lowercase());
+            }
+            Filter::HasMember(uid) => {
+                return matches!(self.get(&Property::Members), Value::Object(members)
+                    if members.properties.contains_key(&Property::_T(uid.clone())));
+            }
+            Filter::Name(text) => (Property::Name, text),
+            Filter::Email(text) => (Property::_T("emails".to_string()), text),
+            Filter::Phone(text) => (Property::_T("phones".to_string()), text),
+            Filter::Organization(text) => (Property::_T("organizations".to_string()), text),
+            _ => return false,
+        };
+
+        self.get(&property).contains_text(&text.to_lowercase())
+    }
+}
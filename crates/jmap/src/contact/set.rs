@@ -0,0 +1,657 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::{method::MethodError, set::SetError},
+    method::set::{RequestArguments, SetRequest, SetResponse},
+    object::{
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{MaybePatchValue, SetValue, Value},
+    },
+};
+use store::{
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder},
+    Serialize,
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::{parse_pointer, JSObjectPatch};
+
+pub struct SetContext<'x> {
+    pub account_id: u32,
+    pub access_token: &'x AccessToken,
+    pub address_book_ids: RoaringBitmap,
+    pub can_add_ids: Option<RoaringBitmap>,
+    pub can_modify_ids: Option<RoaringBitmap>,
+    pub can_remove_ids: Option<RoaringBitmap>,
+}
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::AddressBookIds)
+        .index_as(IndexAs::IntegerList)
+        .required(),
+    IndexProperty::new(Property::Uid).index_as(IndexAs::Text {
+        tokenize: false,
+        index: true,
+    }),
+    IndexProperty::new(Property::Kind).index_as(IndexAs::Text {
+        tokenize: false,
+        index: true,
+    }),
+    IndexProperty::new(Property::Created).index_as(IndexAs::Text {
+        tokenize: false,
+        index: true,
+    }),
+    IndexProperty::new(Property::Updated).index_as(IndexAs::Text {
+        tokenize: false,
+        index: true,
+    }),
+];
+
+impl JMAP {
+    pub async fn contact_card_set(
+        &self,
+        mut request: SetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<SetResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut response = self
+            .prepare_set_response(&request, Collection::ContactCard)
+            .await?;
+        let ctx = self
+            .contact_card_set_context(account_id, access_token)
+            .await?;
+        let card_ids = self
+            .owned_or_shared_contacts(access_token, account_id, Acl::ReadItems)
+            .await?;
+        let will_destroy = request.unwrap_destroy();
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        for (id, object) in request.unwrap_create() {
+            match self
+                .contact_card_set_item(object, None, &ctx, &response)
+                .await?
+            {
+                Ok((builder, uid)) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::ContactCard)
+                        .create_document()
+                        .custom(builder);
+                    let document_id = self.write_batch_expect_id(batch).await?;
+                    changes.log_insert(Collection::ContactCard, document_id);
+                    response.created(id.clone(), document_id);
+
+                    // Return server-set properties
+                    if let (Some(uid), Some(created)) = (uid, response.created.get_mut(&id)) {
+                        created.append(Property::Uid, uid);
+                    }
+                }
+                Err(err) => {
+                    response.not_created.append(id, err);
+                }
+            }
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain card
+            let document_id = id.document_id();
+            let card = if let Some(card) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::ContactCard,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+                .filter(|_| card_ids.contains(document_id))
+            {
+                card
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+
+            match self
+                .contact_card_set_item(object, card.into(), &ctx, &response)
+                .await?
+            {
+                Ok((builder, _)) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::ContactCard)
+                        .update_document(document_id)
+                        .custom(builder);
+                    if !batch.is_empty() {
+                        match self.core.storage.data.write(batch.build()).await {
+                            Ok(_) => {
+                                changes.log_update(Collection::ContactCard, document_id);
+                            }
+                            Err(store::Error::AssertValueFailed) => {
+                                response.not_updated.append(
+                                    id,
+                                    SetError::forbidden().with_description(
+                                        "Another process modified this contact, please try again.",
+                                    ),
+                                );
+                                continue 'update;
+                            }
+                            Err(err) => {
+                                tracing::error!(
+                                    event = "error",
+                                    context = "contact_card_set",
+                                    account_id = account_id,
+                                    error = ?err,
+                                    "Failed to update contact card(s).");
+                                return Err(MethodError::ServerPartialFail);
+                            }
+                        }
+                    }
+                    response.updated.append(id, None);
+                }
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                }
+            }
+        }
+
+        // Process deletions
+        for id in will_destroy {
+            let document_id = id.document_id();
+            let card = if let Some(card) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::ContactCard,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+                .filter(|_| card_ids.contains(document_id))
+            {
+                card
+            } else {
+                response.not_destroyed.append(id, SetError::not_found());
+                continue;
+            };
+
+            // Validate ACLs
+            if let Some(can_remove_ids) = &ctx.can_remove_ids {
+                if !card
+                    .inner
+                    .address_book_ids()
+                    .all(|address_book_id| can_remove_ids.contains(address_book_id))
+                {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "You are not allowed to delete contacts from this address book.",
+                        ),
+                    );
+                    continue;
+                }
+            }
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::ContactCard)
+                .delete_document(document_id)
+                .custom(ObjectIndexBuilder::new(SCHEMA).with_current(card));
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => {
+                    changes.log_delete(Collection::ContactCard, document_id);
+                    response.destroyed.push(id);
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden().with_description(concat!(
+                            "Another process modified this contact ",
+                            "while deleting it, please try again."
+                        )),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "contact_card_set",
+                        account_id = account_id,
+                        document_id = document_id,
+                        error = ?err,
+                        "Failed to delete contact card.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            let change_id = self.commit_changes(account_id, changes).await?;
+            response.new_state = Some(change_id.into());
+            response.state_change = StateChange::new(account_id)
+                .with_change(DataType::ContactCard, change_id)
+                .into();
+        }
+
+        Ok(response)
+    }
+
+    pub async fn contact_card_set_context<'x>(
+        &self,
+        account_id: u32,
+        access_token: &'x AccessToken,
+    ) -> Result<SetContext<'x>, MethodError> {
+        let address_book_ids = self.address_book_get_or_create(account_id).await?;
+        Ok(if access_token.is_shared(account_id) {
+            SetContext {
+                account_id,
+                access_token,
+                address_book_ids,
+                can_add_ids: self
+                    .shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::AddressBook,
+                        Acl::AddItems,
+                    )
+                    .await?
+                    .into(),
+                can_modify_ids: self
+                    .shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::AddressBook,
+                        Acl::ModifyItems,
+                    )
+                    .await?
+                    .into(),
+                can_remove_ids: self
+                    .shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::AddressBook,
+                        Acl::RemoveItems,
+                    )
+                    .await?
+                    .into(),
+            }
+        } else {
+            SetContext {
+                account_id,
+                access_token,
+                address_book_ids,
+                can_add_ids: None,
+                can_modify_ids: None,
+                can_remove_ids: None,
+            }
+        })
+    }
+
+    pub async fn contact_card_set_item(
+        &self,
+        changes_: Object<SetValue>,
+        current: Option<HashedValue<Object<Value>>>,
+        ctx: &SetContext<'_>,
+        response: &impl EvalObjectReferences,
+    ) -> Result<Result<(ObjectIndexBuilder, Option<String>), SetError>, MethodError> {
+        let mut changes = Object::with_capacity(changes_.properties.len());
+        let mut pointers = Vec::new();
+        let mut address_book_ids = current
+            .as_ref()
+            .map(|card| card.inner.address_book_ids().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let mut has_card_changes = false;
+
+        for (property, value) in changes_.properties {
+            let value = match response.eval_object_references(value) {
+                Ok(value) => value,
+                Err(err) => {
+                    return Ok(Err(err));
+                }
+            };
+
+            match (property, value) {
+                (Property::AddressBookIds, MaybePatchValue::Value(Value::List(ids))) => {
+                    address_book_ids.clear();
+                    for id in ids {
+                        if let Some(id) = id.try_unwrap_id() {
+                            let document_id = id.document_id();
+                            if !address_book_ids.contains(&document_id) {
+                                address_book_ids.push(document_id);
+                            }
+                        }
+                    }
+                }
+                (Property::AddressBookIds, MaybePatchValue::Patch(patch)) => {
+                    let mut patch = patch.into_iter();
+                    if let Some(id) = patch.next().unwrap().try_unwrap_id() {
+                        let document_id = id.document_id();
+                        if patch.next().unwrap().try_unwrap_bool().unwrap_or_default() {
+                            if !address_book_ids.contains(&document_id) {
+                                address_book_ids.push(document_id);
+                            }
+                        } else {
+                            address_book_ids.retain(|id| *id != document_id);
+                        }
+                    }
+                }
+                (Property::Uid, MaybePatchValue::Value(Value::Text(uid))) if !uid.is_empty() => {
+                    if let Some(current) = &current {
+                        if current.inner.get(&Property::Uid).as_string() != Some(uid.as_str()) {
+                            return Ok(Err(SetError::invalid_properties()
+                                .with_property(Property::Uid)
+                                .with_description("The uid of a contact cannot be changed.")));
+                        }
+                    } else {
+                        changes.append(Property::Uid, Value::Text(uid));
+                    }
+                }
+                (Property::_T(pointer), MaybePatchValue::Value(value)) if pointer.contains('/') => {
+                    pointers.push((parse_pointer(&pointer), value));
+                    has_card_changes = true;
+                }
+                (property @ (Property::Id | Property::Uid | Property::AddressBookIds), _) => {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value.".to_string())));
+                }
+                (property, MaybePatchValue::Value(value)) => {
+                    if current.is_some() || value != Value::Null {
+                        changes.append(property, value);
+                        has_card_changes = true;
+                    }
+                }
+                (property, _) => {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value.".to_string())));
+                }
+            }
+        }
+
+        // Apply JSON pointer patches
+        for (path, value) in pointers {
+            let property = Property::parse(&path[0]);
+            if matches!(
+                property,
+                Property::Id | Property::Uid | Property::AddressBookIds
+            ) || path.len() < 2
+            {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::_T(path.join("/")))
+                    .with_description("Invalid patch.")));
+            }
+            let item = match changes.properties.get_mut(&property) {
+                Some(item) => item,
+                None => {
+                    let item = current
+                        .as_ref()
+                        .and_then(|card| card.inner.properties.get(&property))
+                        .cloned()
+                        .unwrap_or(Value::Null);
+                    changes.append(property.clone(), item);
+                    changes.properties.get_mut(&property).unwrap()
+                }
+            };
+            if !item.patch_pointer(&path[1..], value) {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::_T(path.join("/")))
+                    .with_description("Patch path does not exist.")));
+            }
+        }
+
+        // Validate address books
+        if address_book_ids.is_empty() {
+            return Ok(Err(SetError::invalid_properties()
+                .with_property(Property::AddressBookIds)
+                .with_description(
+                    "Contact has to belong to at least one address book.",
+                )));
+        } else if matches!(self.core.jmap.contacts_max_address_books_per_card, Some(max) if address_book_ids.len() > max)
+        {
+            return Ok(Err(SetError::invalid_properties()
+                .with_property(Property::AddressBookIds)
+                .with_description("Contact belongs to too many address books.")));
+        }
+        let current_address_book_ids = current
+            .as_ref()
+            .map(|card| card.inner.address_book_ids().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for address_book_id in &address_book_ids {
+            if !current_address_book_ids.contains(address_book_id) {
+                if !ctx.address_book_ids.contains(*address_book_id) {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(Property::AddressBookIds)
+                        .with_description(format!(
+                            "addressBookId {} does not exist.",
+                            jmap_proto::types::id::Id::from(*address_book_id)
+                        ))));
+                } else if matches!(&ctx.can_add_ids, Some(ids) if !ids.contains(*address_book_id)) {
+                    return Ok(Err(SetError::forbidden().with_description(format!(
+                        "You are not allowed to add contacts to address book {}.",
+                        jmap_proto::types::id::Id::from(*address_book_id)
+                    ))));
+                }
+            }
+        }
+        if let Some(can_remove_ids) = &ctx.can_remove_ids {
+            if current_address_book_ids.iter().any(|address_book_id| {
+                !address_book_ids.contains(address_book_id)
+                    && !can_remove_ids.contains(*address_book_id)
+            }) {
+                return Ok(Err(SetError::forbidden().with_description(
+                    "You are not allowed to remove contacts from this address book.",
+                )));
+            }
+        }
+        if let (Some(can_modify_ids), true) = (&ctx.can_modify_ids, current.is_some()) {
+            if has_card_changes
+                && !current_address_book_ids
+                    .iter()
+                    .any(|address_book_id| can_modify_ids.contains(*address_book_id))
+            {
+                return Ok(Err(SetError::forbidden()
+                    .with_description("You are not allowed to modify this contact.")));
+            }
+        }
+        if address_book_ids != current_address_book_ids {
+            changes.append(
+                Property::AddressBookIds,
+                Value::List(
+                    address_book_ids
+                        .into_iter()
+                        .map(|id| Value::Id(id.into()))
+                        .collect(),
+                ),
+            );
+        }
+
+        // Add server-set properties
+        let mut generated_uid = None;
+        if current.is_none() {
+            if !changes.properties.contains_key(&Property::Uid) {
+                let uid = format!("urn:uuid:{}", generate_uuid());
+                changes.append(Property::Uid, Value::Text(uid.clone()));
+                generated_uid = Some(uid);
+            }
+            for (property, value) in [("@type", "Card"), ("version", "1.0")] {
+                let property = Property::_T(property.to_string());
+                if !changes.properties.contains_key(&property) {
+                    changes.append(property, Value::Text(value.to_string()));
+                }
+            }
+        }
+        match changes.get(&Property::_T("@type".to_string())) {
+            Value::Text(value) if value == "Card" => (),
+            Value::Null if current.is_some() => (),
+            _ => {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::_T("@type".to_string()))
+                    .with_description("Invalid JSContact object type.")));
+            }
+        }
+
+        // Validate size
+        let size = if let Some(current) = &current {
+            let mut card = current.inner.clone();
+            for (property, value) in changes.properties.iter() {
+                card.properties.set(property.clone(), value.clone());
+            }
+            card.serialize().len()
+        } else {
+            (&changes).serialize().len()
+        };
+        if size > self.core.jmap.contacts_max_card_size {
+            return Ok(Err(SetError::too_large().with_description(format!(
+                "Contact card exceeds maximum size of {} bytes.",
+                self.core.jmap.contacts_max_card_size
+            ))));
+        }
+
+        // Validate
+        Ok(ObjectIndexBuilder::new(SCHEMA)
+            .with_changes(changes)
+            .with_current_opt(current)
+            .validate()
+            .map(|builder| (builder, generated_uid)))
+    }
+
+    pub async fn contact_card_remove_address_book(
+        &self,
+        account_id: u32,
+        address_book_id: u32,
+        card_ids: &RoaringBitmap,
+        changes: &mut ChangeLogBuilder,
+    ) -> Result<Result<(), SetError>, MethodError> {
+        // If the card is in multiple address books, untag it from the current one,
+        // otherwise delete it.
+        for (document_id, card) in self
+            .get_properties::<HashedValue<Object<Value>>, _, _>(
+                account_id,
+                Collection::ContactCard,
+                card_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            let address_book_ids = card
+                .inner
+                .address_book_ids()
+                .filter(|id| *id != address_book_id)
+                .map(|id| Value::Id(id.into()))
+                .collect::<Vec<_>>();
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::ContactCard);
+            if !address_book_ids.is_empty() {
+                batch.update_document(document_id).custom(
+                    ObjectIndexBuilder::new(SCHEMA)
+                        .with_current(card)
+                        .with_changes(Object::with_capacity(1).with_property(
+                            Property::AddressBookIds,
+                            Value::List(address_book_ids),
+                        )),
+                );
+                changes.log_update(Collection::ContactCard, document_id);
+            } else {
+                batch
+                    .delete_document(document_id)
+                    .custom(ObjectIndexBuilder::new(SCHEMA).with_current(card));
+                changes.log_delete(Collection::ContactCard, document_id);
+            }
+
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => (),
+                Err(store::Error::AssertValueFailed) => {
+                    return Ok(Err(SetError::forbidden().with_description(concat!(
+                        "Another process modified a contact in this address book ",
+                        "while deleting it, please try again."
+                    ))));
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "address_book_set",
+                        account_id = account_id,
+                        address_book_id = address_book_id,
+                        document_id = document_id,
+                        error = ?err,
+                        "Failed to update contact card while deleting address book.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+pub trait ContactCardAddressBooks {
+    fn address_book_ids(&self) -> impl Iterator<Item = u32> + '_;
+}
+
+impl ContactCardAddressBooks for Object<Value> {
+    fn address_book_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.get(&Property::AddressBookIds)
+            .as_list()
+            .into_iter()
+            .flatten()
+            .filter_map(|id| id.as_id().map(|id| id.document_id()))
+    }
+}
+
+pub(crate) fn generate_uuid() -> String {
+    let uuid = (rand::random::<u128>() & !(0xf000u128 << 64) & !(0xc000u128 << 48))
+        | (0x4000u128 << 64)
+        | (0x8000u128 << 48);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        (uuid >> 96) as u32,
+        (uuid >> 80) as u16,
+        (uuid >> 64) as u16,
+        (uuid >> 48) as u16,
+        uuid & 0xffff_ffff_ffff
+    )
+}
@@ -0,0 +1,830 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use chrono::NaiveDateTime;
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+use crate::calendar_event::ical::{
+    escape_text, parse_line, split_list, unescape_text, unfold, write_line, ContentLine,
+};
+
+pub struct VCard {
+    pub cards: Vec<Object<Value>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VCardVersion {
+    V3,
+    V4,
+}
+
+// Name components in the order of the N property
+const N_COMPONENTS: &[&str] = &["surname", "given", "given2", "title", "credential"];
+
+// Address components in the order of the ADR property
+const ADR_COMPONENTS: &[&str] = &[
+    "postOfficeBox",
+    "apartment",
+    "name",
+    "locality",
+    "region",
+    "postcode",
+    "country",
+];
+
+const PHONE_FEATURES: &[(&str, &str)] = &[
+    ("CELL", "mobile"),
+    ("VOICE", "voice"),
+    ("FAX", "fax"),
+    ("PAGER", "pager"),
+    ("TEXT", "text"),
+    ("VIDEO", "video"),
+    ("TEXTPHONE", "textphone"),
+];
+
+impl VCard {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut cards = Vec::new();
+        let mut lines: Option<Vec<ContentLine>> = None;
+
+        for line in unfold(text) {
+            let mut line =
+                parse_line(&line).ok_or_else(|| format!("Invalid content line {line:?}"))?;
+            // Drop property groups, such as "item1.EMAIL"
+            if let Some((_, name)) = line.name.rsplit_once('.') {
+                line.name = name.to_string();
+            }
+            match line.name.as_str() {
+                "BEGIN" if line.value.eq_ignore_ascii_case("VCARD") => {
+                    lines = Some(Vec::new());
+                }
+                "END" if line.value.eq_ignore_ascii_case("VCARD") => {
+                    cards.push(to_jscontact(
+                        lines
+                            .take()
+                            .ok_or_else(|| "Unexpected END:VCARD".to_string())?,
+                    ));
+                }
+                _ => {
+                    lines
+                        .as_mut()
+                        .ok_or_else(|| "Content line outside of a vCard".to_string())?
+                        .push(line);
+                }
+            }
+        }
+
+        if lines.is_some() {
+            Err("Missing END:VCARD".to_string())
+        } else if cards.is_empty() {
+            Err("Missing VCARD component".to_string())
+        } else {
+            Ok(VCard { cards })
+        }
+    }
+
+    pub fn build(card: &Object<Value>, version: VCardVersion) -> String {
+        let mut vcard = String::with_capacity(512);
+        let is_v4 = version == VCardVersion::V4;
+        write_line(&mut vcard, "BEGIN", &[], "VCARD");
+        write_line(
+            &mut vcard,
+            "VERSION",
+            &[],
+            if is_v4 { "4.0" } else { "3.0" },
+        );
+        write_line(
+            &mut vcard,
+            "PRODID",
+            &[],
+            "-//Stalwart Labs Ltd.//Stalwart Mail Server//EN",
+        );
+        if let Some(uid) = card.get(&Property::Uid).as_string() {
+            write_line(&mut vcard, "UID", &[], uid);
+        }
+        if let Some(kind) = member(card, "kind").as_string() {
+            if is_v4 {
+                write_line(&mut vcard, "KIND", &[], kind);
+            } else if kind == "group" {
+                write_line(&mut vcard, "X-ADDRESSBOOKSERVER-KIND", &[], kind);
+            }
+        }
+
+        // Names
+        let name = member(card, "name").as_obj();
+        let components = name
+            .and_then(|name| name.get(&field("components")).as_list())
+            .map(|components| components.as_slice())
+            .unwrap_or_default();
+        let full_name = name
+            .and_then(|name| name.get(&field("full")).as_string())
+            .map(|full| full.to_string())
+            .or_else(|| {
+                let parts = components
+                    .iter()
+                    .filter_map(|component| {
+                        let component = component.as_obj()?;
+                        if component.get(&field("kind")).as_string() != Some("separator") {
+                            component.get(&field("value")).as_string()
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                (!parts.is_empty()).then(|| parts.join(" "))
+            })
+            .or_else(|| {
+                entries(card, "organizations")
+                    .find_map(|(_, org)| org.get(&field("name")).as_string())
+                    .map(|name| name.to_string())
+            })
+            .or_else(|| {
+                entries(card, "emails")
+                    .find_map(|(_, email)| email.get(&field("address")).as_string())
+                    .map(|address| address.to_string())
+            })
+            .unwrap_or_default();
+        write_line(&mut vcard, "FN", &[], &escape_text(&full_name));
+        if !components.is_empty() || !is_v4 {
+            let n = N_COMPONENTS
+                .iter()
+                .map(|kind| {
+                    components
+                        .iter()
+                        .filter_map(|component| {
+                            let component = component.as_obj()?;
+                            if component.get(&field("kind")).as_string() == Some(kind) {
+                                component.get(&field("value")).as_string()
+                            } else {
+                                None
+                            }
+                        })
+                        .map(escape_text)
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect::<Vec<_>>()
+                .join(";");
+            write_line(&mut vcard, "N", &[], &n);
+        }
+        let nicknames = entries(card, "nicknames")
+            .filter_map(|(_, nickname)| nickname.get(&field("name")).as_string())
+            .map(escape_text)
+            .collect::<Vec<_>>();
+        if !nicknames.is_empty() {
+            write_line(&mut vcard, "NICKNAME", &[], &nicknames.join(","));
+        }
+
+        // Communication
+        for (_, email) in entries(card, "emails") {
+            if let Some(address) = email.get(&field("address")).as_string() {
+                let params = type_params(email, &[], version);
+                write_line(&mut vcard, "EMAIL", &params, &escape_text(address));
+            }
+        }
+        for (_, phone) in entries(card, "phones") {
+            if let Some(number) = phone.get(&field("number")).as_string() {
+                let features = phone
+                    .get(&field("features"))
+                    .as_obj()
+                    .map(|features| {
+                        PHONE_FEATURES
+                            .iter()
+                            .filter(|(_, feature)| {
+                                matches!(features.get(&field(feature)), Value::Bool(true))
+                            })
+                            .map(|(name, _)| *name)
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                let params = type_params(phone, &features, version);
+                write_line(&mut vcard, "TEL", &params, &escape_text(number));
+            }
+        }
+        for (_, address) in entries(card, "addresses") {
+            let components = address
+                .get(&field("components"))
+                .as_list()
+                .map(|components| components.as_slice())
+                .unwrap_or_default();
+            let adr = ADR_COMPONENTS
+                .iter()
+                .map(|kind| {
+                    components
+                        .iter()
+                        .filter_map(|component| {
+                            let component = component.as_obj()?;
+                            if component.get(&field("kind")).as_string() == Some(kind) {
+                                component.get(&field("value")).as_string()
+                            } else {
+                                None
+                            }
+                        })
+                        .map(escape_text)
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect::<Vec<_>>()
+                .join(";");
+            let mut params = type_params(address, &[], version);
+            if let (Some(full), true) = (address.get(&field("full")).as_string(), is_v4) {
+                params.push(("LABEL", full.replace('\n', " ")));
+            }
+            write_line(&mut vcard, "ADR", &params, &adr);
+        }
+        for (_, link) in entries(card, "links") {
+            if let Some(uri) = link.get(&field("uri")).as_string() {
+                write_line(&mut vcard, "URL", &[], uri);
+            }
+        }
+
+        // Organizational
+        for (_, organization) in entries(card, "organizations") {
+            let mut org = vec![escape_text(
+                organization
+                    .get(&field("name"))
+                    .as_string()
+                    .unwrap_or_default(),
+            )];
+            for unit in organization
+                .get(&field("units"))
+                .as_list()
+                .map(|units| units.as_slice())
+                .unwrap_or_default()
+            {
+                if let Some(name) = unit
+                    .as_obj()
+                    .and_then(|unit| unit.get(&field("name")).as_string())
+                {
+                    org.push(escape_text(name));
+                }
+            }
+            write_line(&mut vcard, "ORG", &[], &org.join(";"));
+        }
+        for (_, title) in entries(card, "titles") {
+            if let Some(name) = title.get(&field("name")).as_string() {
+                let property = if title.get(&field("kind")).as_string() == Some("role") {
+                    "ROLE"
+                } else {
+                    "TITLE"
+                };
+                write_line(&mut vcard, property, &[], &escape_text(name));
+            }
+        }
+
+        // Other
+        for (_, anniversary) in entries(card, "anniversaries") {
+            let property = match anniversary.get(&field("kind")).as_string() {
+                Some("birth") => "BDAY",
+                Some("wedding") => "ANNIVERSARY",
+                _ => continue,
+            };
+            if let Some(date) = anniversary
+                .get(&field("date"))
+                .as_obj()
+                .and_then(|date| format_partial_date(date, version))
+            {
+                write_line(&mut vcard, property, &[], &date);
+            }
+        }
+        for (_, note) in entries(card, "notes") {
+            if let Some(note) = note.get(&field("note")).as_string() {
+                write_line(&mut vcard, "NOTE", &[], &escape_text(note));
+            }
+        }
+        if let Some(keywords) = member(card, "keywords").as_obj() {
+            let keywords = keywords
+                .properties
+                .iter()
+                .filter(|(_, value)| matches!(value, Value::Bool(true)))
+                .map(|(keyword, _)| escape_text(&keyword.to_string()))
+                .collect::<Vec<_>>();
+            if !keywords.is_empty() {
+                write_line(&mut vcard, "CATEGORIES", &[], &keywords.join(","));
+            }
+        }
+        if let Some(members) = member(card, "members").as_obj() {
+            for (uid, value) in &members.properties {
+                if matches!(value, Value::Bool(true)) {
+                    write_line(
+                        &mut vcard,
+                        if is_v4 {
+                            "MEMBER"
+                        } else {
+                            "X-ADDRESSBOOKSERVER-MEMBER"
+                        },
+                        &[],
+                        &uid.to_string(),
+                    );
+                }
+            }
+        }
+        for (_, media) in entries(card, "media") {
+            if let (Some("photo"), Some(uri)) = (
+                media.get(&field("kind")).as_string(),
+                media.get(&field("uri")).as_string(),
+            ) {
+                if !is_v4 {
+                    if let Some((media_type, data)) = uri
+                        .strip_prefix("data:")
+                        .and_then(|uri| uri.split_once(";base64,"))
+                    {
+                        let media_type = media_type
+                            .rsplit('/')
+                            .next()
+                            .unwrap_or_default()
+                            .to_ascii_uppercase();
+                        write_line(
+                            &mut vcard,
+                            "PHOTO",
+                            &[("ENCODING", "b".to_string()), ("TYPE", media_type)],
+                            data,
+                        );
+                        continue;
+                    }
+                }
+                write_line(&mut vcard, "PHOTO", &[("VALUE", "uri".to_string())], uri);
+            }
+        }
+        if let Some(updated) = card
+            .get(&Property::Updated)
+            .as_string()
+            .and_then(|updated| {
+                NaiveDateTime::parse_from_str(updated.trim_end_matches('Z'), "%Y-%m-%dT%H:%M:%S")
+                    .ok()
+            })
+        {
+            write_line(
+                &mut vcard,
+                "REV",
+                &[],
+                &updated.format("%Y%m%dT%H%M%SZ").to_string(),
+            );
+        }
+
+        write_line(&mut vcard, "END", &[], "VCARD");
+        vcard
+    }
+}
+
+fn to_jscontact(lines: Vec<ContentLine>) -> Object<Value> {
+    let mut card = Object::with_capacity(lines.len() + 2);
+    card.append(property("@type"), Value::Text("Card".to_string()));
+    card.append(property("version"), Value::Text("1.0".to_string()));
+    let mut name = Object::with_capacity(2);
+    let mut maps: Vec<(&str, Object<Value>)> = Vec::new();
+    let mut keywords = Object::with_capacity(0);
+    let mut members = Object::with_capacity(0);
+
+    for line in lines {
+        let (map, prefix, entry) = match line.name.as_str() {
+            "UID" => {
+                card.set(Property::Uid, Value::Text(line.value.trim().to_string()));
+                continue;
+            }
+            "KIND" | "X-ADDRESSBOOKSERVER-KIND" => {
+                card.set(
+                    property("kind"),
+                    Value::Text(line.value.trim().to_lowercase()),
+                );
+                continue;
+            }
+            "PRODID" => {
+                card.set(property("prodId"), Value::Text(line.text()));
+                continue;
+            }
+            "FN" => {
+                let full = line.text();
+                if !full.trim().is_empty() {
+                    name.set(field("full"), Value::Text(full));
+                }
+                continue;
+            }
+            "N" => {
+                let mut components = Vec::new();
+                for (kind, values) in N_COMPONENTS.iter().zip(split_structured(&line.value)) {
+                    for value in split_list(&values) {
+                        components.push(Value::Object(
+                            Object::with_capacity(2)
+                                .with_property(field("kind"), *kind)
+                                .with_property(field("value"), value),
+                        ));
+                    }
+                }
+                if !components.is_empty() {
+                    name.set(field("components"), Value::List(components));
+                }
+                continue;
+            }
+            "NICKNAME" => {
+                for nickname in split_list(&line.value) {
+                    append_entry(
+                        &mut maps,
+                        "nicknames",
+                        "k",
+                        Object::with_capacity(2)
+                            .with_property(field("@type"), "Nickname")
+                            .with_property(field("name"), nickname),
+                    );
+                }
+                continue;
+            }
+            "EMAIL" => (
+                "emails",
+                "e",
+                with_contexts(
+                    Object::with_capacity(3)
+                        .with_property(field("@type"), "EmailAddress")
+                        .with_property(field("address"), line.text()),
+                    &line,
+                ),
+            ),
+            "TEL" => {
+                let number = line.text();
+                let mut phone = with_contexts(
+                    Object::with_capacity(3)
+                        .with_property(field("@type"), "Phone")
+                        .with_property(
+                            field("number"),
+                            number.strip_prefix("tel:").unwrap_or(&number).to_string(),
+                        ),
+                    &line,
+                );
+                let features = line_types(&line)
+                    .into_iter()
+                    .filter_map(|typ| {
+                        PHONE_FEATURES
+                            .iter()
+                            .find(|(name, _)| *name == typ)
+                            .map(|(_, feature)| *feature)
+                    })
+                    .collect::<Vec<_>>();
+                if !features.is_empty() {
+                    let mut map = Object::with_capacity(features.len());
+                    for feature in features {
+                        map.set(field(feature), Value::Bool(true));
+                    }
+                    phone.set(field("features"), Value::Object(map));
+                }
+                ("phones", "p", phone)
+            }
+            "ADR" => {
+                let mut components = Vec::new();
+                for (kind, values) in ADR_COMPONENTS.iter().zip(split_structured(&line.value)) {
+                    for value in split_list(&values) {
+                        components.push(Value::Object(
+                            Object::with_capacity(2)
+                                .with_property(field("kind"), *kind)
+                                .with_property(field("value"), value),
+                        ));
+                    }
+                }
+                let mut address = with_contexts(
+                    Object::with_capacity(3)
+                        .with_property(field("@type"), "Address")
+                        .with_property(field("components"), Value::List(components)),
+                    &line,
+                );
+                if let Some(label) = line.param("LABEL") {
+                    address.set(field("full"), Value::Text(unescape_text(label)));
+                }
+                ("addresses", "a", address)
+            }
+            "URL" => (
+                "links",
+                "l",
+                Object::with_capacity(2)
+                    .with_property(field("@type"), "Link")
+                    .with_property(field("uri"), line.value.trim().to_string()),
+            ),
+            "ORG" => {
+                let mut parts = split_structured(&line.value).into_iter();
+                let mut organization = Object::with_capacity(3)
+                    .with_property(field("@type"), "Organization")
+                    .with_property(
+                        field("name"),
+                        unescape_text(&parts.next().unwrap_or_default()),
+                    );
+                let units = parts
+                    .filter(|unit| !unit.trim().is_empty())
+                    .map(|unit| {
+                        Value::Object(
+                            Object::with_capacity(2)
+                                .with_property(field("@type"), "OrgUnit")
+                                .with_property(field("name"), unescape_text(&unit)),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                if !units.is_empty() {
+                    organization.set(field("units"), Value::List(units));
+                }
+                ("organizations", "o", organization)
+            }
+            name @ ("TITLE" | "ROLE") => (
+                "titles",
+                "t",
+                Object::with_capacity(3)
+                    .with_property(field("@type"), "Title")
+                    .with_property(field("name"), line.text())
+                    .with_property(field("kind"), if name == "ROLE" { "role" } else { "title" }),
+            ),
+            name @ ("BDAY" | "ANNIVERSARY") => {
+                if let Some(date) = parse_partial_date(&line.value) {
+                    (
+                        "anniversaries",
+                        "k",
+                        Object::with_capacity(3)
+                            .with_property(field("@type"), "Anniversary")
+                            .with_property(
+                                field("kind"),
+                                if name == "BDAY" { "birth" } else { "wedding" },
+                            )
+                            .with_property(field("date"), Value::Object(date)),
+                    )
+                } else {
+                    continue;
+                }
+            }
+            "NOTE" => (
+                "notes",
+                "n",
+                Object::with_capacity(2)
+                    .with_property(field("@type"), "Note")
+                    .with_property(field("note"), line.text()),
+            ),
+            "CATEGORIES" => {
+                for keyword in split_list(&line.value) {
+                    keywords.set(Property::_T(keyword), Value::Bool(true));
+                }
+                continue;
+            }
+            "MEMBER" | "X-ADDRESSBOOKSERVER-MEMBER" => {
+                members.set(
+                    Property::_T(line.value.trim().to_string()),
+                    Value::Bool(true),
+                );
+                continue;
+            }
+            "PHOTO" => {
+                let value = line.value.trim();
+                let uri = if line
+                    .param("ENCODING")
+                    .is_some_and(|encoding| encoding.eq_ignore_ascii_case("b"))
+                {
+                    format!(
+                        "data:image/{};base64,{value}",
+                        line.param("TYPE").unwrap_or("jpeg").to_ascii_lowercase()
+                    )
+                } else {
+                    value.to_string()
+                };
+                (
+                    "media",
+                    "m",
+                    Object::with_capacity(3)
+                        .with_property(field("@type"), "Media")
+                        .with_property(field("kind"), "photo")
+                        .with_property(field("uri"), uri),
+                )
+            }
+            "REV" => {
+                if let Some(updated) = parse_timestamp(&line.value) {
+                    card.set(Property::Updated, Value::Text(updated));
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        append_entry(&mut maps, map, prefix, entry);
+    }
+
+    if !name.properties.is_empty() {
+        name.append(field("@type"), Value::Text("Name".to_string()));
+        card.set(property("name"), Value::Object(name));
+    }
+    for (map, entries) in maps {
+        card.set(property(map), Value::Object(entries));
+    }
+    if !keywords.properties.is_empty() {
+        card.set(property("keywords"), Value::Object(keywords));
+    }
+    if !members.properties.is_empty() {
+        card.set(property("members"), Value::Object(members));
+    }
+    card
+}
+
+fn append_entry(
+    maps: &mut Vec<(&'static str, Object<Value>)>,
+    map: &'static str,
+    prefix: &str,
+    entry: Object<Value>,
+) {
+    let idx = if let Some(idx) = maps.iter().position(|(name, _)| *name == map) {
+        idx
+    } else {
+        maps.push((map, Object::with_capacity(1)));
+        maps.len() - 1
+    };
+    let entries = &mut maps[idx].1;
+    let id = format!("{prefix}{}", entries.properties.len() + 1);
+    entries.append(Property::_T(id), Value::Object(entry));
+}
+
+fn with_contexts(mut entry: Object<Value>, line: &ContentLine) -> Object<Value> {
+    let types = line_types(line);
+    let mut contexts = Object::with_capacity(1);
+    for typ in &types {
+        match typ.as_str() {
+            "WORK" => {
+                contexts.set(field("work"), Value::Bool(true));
+            }
+            "HOME" => {
+                contexts.set(field("private"), Value::Bool(true));
+            }
+            _ => (),
+        }
+    }
+    if !contexts.properties.is_empty() {
+        entry.set(field("contexts"), Value::Object(contexts));
+    }
+    let pref = line
+        .param("PREF")
+        .and_then(|pref| pref.trim().parse::<u64>().ok())
+        .or_else(|| types.iter().any(|typ| typ == "PREF").then_some(1));
+    if let Some(pref) = pref {
+        entry.set(field("pref"), Value::UnsignedInt(pref));
+    }
+    entry
+}
+
+// Returns the values of all TYPE parameters, which may be repeated or comma separated
+fn line_types(line: &ContentLine) -> Vec<String> {
+    line.params
+        .iter()
+        .filter(|(name, _)| name == "TYPE")
+        .flat_map(|(_, value)| value.split(','))
+        .map(|value| value.trim().to_ascii_uppercase())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn type_params(
+    entry: &Object<Value>,
+    extra_types: &[&str],
+    version: VCardVersion,
+) -> Vec<(&'static str, String)> {
+    let mut params = Vec::new();
+    if let Some(contexts) = entry.get(&field("contexts")).as_obj() {
+        for (context, typ) in [("work", "WORK"), ("private", "HOME")] {
+            if matches!(contexts.get(&field(context)), Value::Bool(true)) {
+                params.push(("TYPE", typ.to_string()));
+            }
+        }
+    }
+    for typ in extra_types {
+        params.push(("TYPE", typ.to_string()));
+    }
+    if let Some(pref) = entry.get(&field("pref")).as_uint() {
+        if version == VCardVersion::V4 {
+            params.push(("PREF", pref.to_string()));
+        } else if pref == 1 {
+            params.push(("TYPE", "PREF".to_string()));
+        }
+    }
+    if version == VCardVersion::V4 {
+        for (_, value) in params.iter_mut() {
+            value.make_ascii_lowercase();
+        }
+    }
+    params
+}
+
+// Splits a structured value on unescaped semicolons, keeping escapes for list splitting
+fn split_structured(value: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                part.push(ch);
+                if let Some(ch) = chars.next() {
+                    part.push(ch);
+                }
+            }
+            ';' => {
+                parts.push(std::mem::take(&mut part));
+            }
+            _ => part.push(ch),
+        }
+    }
+    parts.push(part);
+    parts
+}
+
+// Parses vCard dates such as 19850412, 1985-04-12, --0412 or --04-12
+fn parse_partial_date(value: &str) -> Option<Object<Value>> {
+    let value = value.trim();
+    let value = value.split_once('T').map_or(value, |(date, _)| date);
+    let (year, rest) = if let Some(rest) = value.strip_prefix("--") {
+        (None, rest.replace('-', ""))
+    } else {
+        let digits = value.replace('-', "");
+        if digits.len() < 4 {
+            return None;
+        }
+        (
+            Some(digits[..4].parse::<u64>().ok()?),
+            digits[4..].to_string(),
+        )
+    };
+    let month = rest.get(..2).and_then(|month| month.parse::<u64>().ok());
+    let day = rest.get(2..4).and_then(|day| day.parse::<u64>().ok());
+    if year.is_none() && month.is_none() {
+        return None;
+    }
+
+    let mut date = Object::with_capacity(4).with_property(field("@type"), "PartialDate");
+    if let Some(year) = year {
+        date.set(field("year"), Value::UnsignedInt(year));
+    }
+    if let Some(month) = month {
+        date.set(field("month"), Value::UnsignedInt(month));
+    }
+    if let Some(day) = day {
+        date.set(field("day"), Value::UnsignedInt(day));
+    }
+    Some(date)
+}
+
+fn format_partial_date(date: &Object<Value>, version: VCardVersion) -> Option<String> {
+    let year = date.get(&field("year")).as_uint();
+    let month = date.get(&field("month")).as_uint();
+    let day = date.get(&field("day")).as_uint();
+    match (year, month, day) {
+        (Some(year), Some(month), Some(day)) if version == VCardVersion::V3 => {
+            Some(format!("{year:04}-{month:02}-{day:02}"))
+        }
+        (Some(year), Some(month), Some(day)) => Some(format!("{year:04}{month:02}{day:02}")),
+        (None, Some(month), Some(day)) => Some(format!("--{month:02}{day:02}")),
+        (Some(year), None, None) if version == VCardVersion::V4 => Some(format!("{year:04}")),
+        _ => None,
+    }
+}
+
+fn parse_timestamp(value: &str) -> Option<String> {
+    let value = value
+        .trim()
+        .trim_end_matches(['Z', 'z'])
+        .replace(['-', ':'], "");
+    NaiveDateTime::parse_from_str(&value, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|date| date.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+}
+
+fn member<'x>(card: &'x Object<Value>, name: &str) -> &'x Value {
+    card.get(&property(name))
+}
+
+fn entries<'x>(
+    card: &'x Object<Value>,
+    name: &str,
+) -> impl Iterator<Item = (&'x Property, &'x Object<Value>)> + 'x {
+    member(card, name)
+        .as_obj()
+        .into_iter()
+        .flat_map(|entries| entries.properties.iter())
+        .filter_map(|(id, entry)| entry.as_obj().map(|entry| (id, entry)))
+}
+
+fn property(name: &str) -> Property {
+    Property::parse(name)
+}
+
+fn field(name: &str) -> Property {
+    Property::_T(name.to_string())
+}
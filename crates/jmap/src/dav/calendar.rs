@@ -0,0 +1,1038 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use chrono::NaiveDateTime;
+use hyper::{header, StatusCode};
+use jmap_proto::{
+    error::{method::MethodError, set::SetErrorType},
+    method::{
+        get::{GetRequest, RequestArguments},
+        set::SetRequest,
+    },
+    object::{
+        calendar::{EventSetArguments, SetArguments},
+        Object,
+    },
+    request::reference::MaybeReference,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        id::Id,
+        property::Property,
+        value::{SetValue, Value},
+    },
+};
+use store::{
+    query::{
+        log::{Change, Query},
+        Filter,
+    },
+    roaring::RoaringBitmap,
+    write::assert::HashedValue,
+};
+use utils::map::vec_map::VecMap;
+
+use crate::{
+    auth::AccessToken,
+    calendar_event::{ical::ICalendar, scheduling::into_set_object},
+    JMAP,
+};
+
+use super::{
+    dav_prop_values, dav_props, encode_segment, etag, parse_sync_token, principal_href,
+    xml::{
+        MultiStatus, PropName, PropValue, XmlElement, NS_APPLE_ICAL, NS_CALDAV, NS_CALENDARSERVER,
+        NS_DAV,
+    },
+    DavRequest, DavResponse, DavResult, Depth,
+};
+
+struct CalDavHome<'x> {
+    account_id: u32,
+    name: &'x str,
+    access_token: &'x AccessToken,
+}
+
+const HOME_PROPS: &[(&str, &str)] = &[
+    (NS_DAV, "resourcetype"),
+    (NS_DAV, "current-user-principal"),
+    (NS_DAV, "owner"),
+];
+
+const CALENDAR_PROPS: &[(&str, &str)] = &[
+    (NS_DAV, "resourcetype"),
+    (NS_DAV, "displayname"),
+    (NS_CALDAV, "calendar-description"),
+    (NS_APPLE_ICAL, "calendar-color"),
+    (NS_APPLE_ICAL, "calendar-order"),
+    (NS_CALDAV, "supported-calendar-component-set"),
+    (NS_CALENDARSERVER, "getctag"),
+    (NS_DAV, "sync-token"),
+    (NS_DAV, "current-user-privilege-set"),
+    (NS_DAV, "owner"),
+];
+
+const EVENT_PROPS: &[(&str, &str)] = &[
+    (NS_DAV, "resourcetype"),
+    (NS_DAV, "getetag"),
+    (NS_DAV, "getcontenttype"),
+];
+
+impl JMAP {
+    pub async fn handle_caldav_request(
+        &self,
+        req: &DavRequest,
+        access_token: &AccessToken,
+    ) -> DavResult {
+        let name = match req.path.get(1) {
+            Some(name) => name.as_str(),
+            None => return self.handle_dav_root(req, access_token).await,
+        };
+        let home = if let Some(account_id) = self
+            .dav_account_id(access_token, name, Collection::Calendar)
+            .await?
+        {
+            CalDavHome {
+                account_id,
+                name,
+                access_token,
+            }
+        } else {
+            return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+        };
+
+        match (req.path.get(2), req.path.get(3), req.path.len()) {
+            (None, _, _) => self.caldav_home(req, &home).await,
+            (Some(calendar), None, _) => match req.method.as_str() {
+                "MKCALENDAR" | "MKCOL" => self.caldav_mkcalendar(req, &home, calendar).await,
+                _ => {
+                    if let Some(calendar) = self.caldav_calendar(&home, calendar).await? {
+                        self.caldav_collection(req, &home, calendar).await
+                    } else {
+                        Ok(DavResponse::new(StatusCode::NOT_FOUND))
+                    }
+                }
+            },
+            (Some(calendar), Some(resource), 4) => {
+                if let Some(calendar) = self.caldav_calendar(&home, calendar).await? {
+                    self.caldav_resource(req, &home, calendar, resource).await
+                } else {
+                    Ok(DavResponse::new(StatusCode::NOT_FOUND))
+                }
+            }
+            _ => Ok(DavResponse::new(StatusCode::NOT_FOUND)),
+        }
+    }
+
+    async fn caldav_home(&self, req: &DavRequest, home: &CalDavHome<'_>) -> DavResult {
+        if req.method.as_str() != "PROPFIND" {
+            return Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let names = req.xml().map(|xml| xml.prop_names()).unwrap_or_default();
+        let owner = principal_href(home.name);
+        let mut response = MultiStatus::new();
+        let (found, not_found) = dav_prop_values(names.clone(), HOME_PROPS, &mut |prop| {
+            if prop.is(NS_DAV, "resourcetype") {
+                Some(PropValue::Xml("<d:collection/>".to_string()))
+            } else if prop.is(NS_DAV, "current-user-principal") {
+                Some(PropValue::Href(vec![principal_href(
+                    &home.access_token.name,
+                )]))
+            } else if prop.is(NS_DAV, "owner") {
+                Some(PropValue::Href(vec![owner.clone()]))
+            } else {
+                None
+            }
+        });
+        response.add_response(&home_href(home.name), found, not_found);
+
+        if req.depth == Depth::One {
+            let sync_token = self
+                .dav_sync_token(home.account_id, Collection::CalendarEvent)
+                .await?;
+            for calendar in self.caldav_calendars(home, None).await? {
+                let (found, not_found) =
+                    dav_prop_values(names.clone(), CALENDAR_PROPS, &mut |prop| {
+                        self.calendar_prop(prop, &calendar, &sync_token, &owner)
+                    });
+                response.add_response(&calendar_href(home.name, &calendar), found, not_found);
+            }
+        }
+
+        Ok(DavResponse::multi_status(response))
+    }
+
+    async fn caldav_collection(
+        &self,
+        req: &DavRequest,
+        home: &CalDavHome<'_>,
+        calendar: Object<Value>,
+    ) -> DavResult {
+        let calendar_id = calendar_document_id(&calendar);
+        let href = calendar_href(home.name, &calendar);
+
+        match req.method.as_str() {
+            "PROPFIND" => {
+                let names = req.xml().map(|xml| xml.prop_names()).unwrap_or_default();
+                let owner = principal_href(home.name);
+                let sync_token = self
+                    .dav_sync_token(home.account_id, Collection::CalendarEvent)
+                    .await?;
+                let mut response = MultiStatus::new();
+                let (found, not_found) =
+                    dav_prop_values(names.clone(), CALENDAR_PROPS, &mut |prop| {
+                        self.calendar_prop(prop, &calendar, &sync_token, &owner)
+                    });
+                response.add_response(&href, found, not_found);
+
+                if req.depth == Depth::One {
+                    let event_ids = self.caldav_event_ids(home, calendar_id, vec![]).await?;
+                    self.caldav_add_events(&mut response, home, &href, &event_ids, &names)
+                        .await?;
+                }
+
+                Ok(DavResponse::multi_status(response))
+            }
+            "REPORT" => {
+                let request = if let Some(request) = req.xml() {
+                    request
+                } else {
+                    return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+                };
+                let names = request.prop_names();
+
+                if request.is(NS_CALDAV, "calendar-multiget") {
+                    let event_ids = self.caldav_event_ids(home, calendar_id, vec![]).await?;
+                    let mut response = MultiStatus::new();
+                    for resource_href in request.children(NS_DAV, "href") {
+                        let resource = resource_href
+                            .text
+                            .trim()
+                            .trim_end_matches('/')
+                            .rsplit('/')
+                            .next()
+                            .unwrap_or_default();
+                        let resource = super::percent_decode(resource);
+                        match self
+                            .caldav_resolve(home, calendar_id, &event_ids, &resource)
+                            .await?
+                        {
+                            Some((_, event)) => {
+                                let (found, not_found) =
+                                    dav_prop_values(names.clone(), EVENT_PROPS, &mut |prop| {
+                                        event_prop(prop, &event)
+                                    });
+                                response.add_response(resource_href.text.trim(), found, not_found);
+                            }
+                            None => {
+                                response.add_status(resource_href.text.trim(), "404 Not Found");
+                            }
+                        }
+                    }
+                    Ok(DavResponse::multi_status(response))
+                } else if request.is(NS_CALDAV, "calendar-query") {
+                    let filters = if let Some(filters) = calendar_query_filters(&request) {
+                        filters
+                    } else {
+                        return Ok(DavResponse::multi_status(MultiStatus::new()));
+                    };
+                    let event_ids = self.caldav_event_ids(home, calendar_id, filters).await?;
+                    let mut response = MultiStatus::new();
+                    self.caldav_add_events(&mut response, home, &href, &event_ids, &names)
+                        .await?;
+                    Ok(DavResponse::multi_status(response))
+                } else if request.is(NS_DAV, "sync-collection") {
+                    let since = match request
+                        .child(NS_DAV, "sync-token")
+                        .map(|token| token.text.trim())
+                        .filter(|token| !token.is_empty())
+                    {
+                        Some(token) => match parse_sync_token(token) {
+                            Some(0) => None,
+                            Some(change_id) => Some(change_id - 1),
+                            None => {
+                                return Ok(DavResponse::new(StatusCode::FORBIDDEN));
+                            }
+                        },
+                        None => None,
+                    };
+                    let event_ids = self.caldav_event_ids(home, calendar_id, vec![]).await?;
+                    let mut response = MultiStatus::new().with_sync_token(
+                        self.dav_sync_token(home.account_id, Collection::CalendarEvent)
+                            .await?,
+                    );
+
+                    if let Some(change_id) = since {
+                        let mut changed_ids = RoaringBitmap::new();
+                        let mut removed_ids = RoaringBitmap::new();
+                        for change in self
+                            .changes_(
+                                home.account_id,
+                                Collection::CalendarEvent,
+                                Query::Since(change_id),
+                            )
+                            .await?
+                            .changes
+                        {
+                            let document_id = match change {
+                                Change::Insert(id)
+                                | Change::Update(id)
+                                | Change::ChildUpdate(id)
+                                | Change::Delete(id) => id as u32,
+                            };
+                            if event_ids.contains(document_id) {
+                                changed_ids.insert(document_id);
+                            } else {
+                                removed_ids.insert(document_id);
+                            }
+                        }
+                        self.caldav_add_events(&mut response, home, &href, &changed_ids, &names)
+                            .await?;
+                        for document_id in removed_ids {
+                            response.add_status(&event_href(&href, document_id), "404 Not Found");
+                        }
+                    } else {
+                        self.caldav_add_events(&mut response, home, &href, &event_ids, &names)
+                            .await?;
+                    }
+
+                    Ok(DavResponse::multi_status(response))
+                } else {
+                    Ok(DavResponse::new(StatusCode::FORBIDDEN))
+                }
+            }
+            "PROPPATCH" => {
+                let request = if let Some(request) = req.xml() {
+                    request
+                } else {
+                    return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+                };
+                let (changes, applied, unsupported) = calendar_changes(&request);
+                let mut response = MultiStatus::new();
+                if !unsupported.is_empty() {
+                    response.add_propstats(
+                        &href,
+                        vec![
+                            ("403 Forbidden", into_empty_props(unsupported)),
+                            ("424 Failed Dependency", into_empty_props(applied)),
+                        ],
+                    );
+                    return Ok(DavResponse::multi_status(response));
+                }
+
+                let id = Id::from(calendar_id);
+                let mut result = self
+                    .calendar_set(
+                        SetRequest {
+                            account_id: Id::from(home.account_id),
+                            if_in_state: None,
+                            create: None,
+                            update: Some(VecMap::from_iter([(id, changes)])),
+                            destroy: None,
+                            arguments: SetArguments::default(),
+                        },
+                        home.access_token,
+                    )
+                    .await?;
+                let status = if result.not_updated.is_empty() {
+                    "200 OK"
+                } else {
+                    "403 Forbidden"
+                };
+                if let Some(state_change) = result.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+                response.add_propstats(&href, vec![(status, into_empty_props(applied))]);
+                Ok(DavResponse::multi_status(response))
+            }
+            "DELETE" => {
+                let id = Id::from(calendar_id);
+                let mut result = self
+                    .calendar_set(
+                        SetRequest {
+                            account_id: Id::from(home.account_id),
+                            if_in_state: None,
+                            create: None,
+                            update: None,
+                            destroy: Some(MaybeReference::Value(vec![id])),
+                            arguments: SetArguments {
+                                on_destroy_remove_events: Some(true),
+                                ..Default::default()
+                            },
+                        },
+                        home.access_token,
+                    )
+                    .await?;
+                if let Some(state_change) = result.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+                Ok(DavResponse::new(if result.destroyed.contains(&id) {
+                    StatusCode::NO_CONTENT
+                } else {
+                    StatusCode::FORBIDDEN
+                }))
+            }
+            "MKCALENDAR" | "MKCOL" => Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+            _ => Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+
+    async fn caldav_mkcalendar(
+        &self,
+        req: &DavRequest,
+        home: &CalDavHome<'_>,
+        name: &str,
+    ) -> DavResult {
+        if self.caldav_calendar(home, name).await?.is_some() {
+            return Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let (mut changes, _, unsupported) = req
+            .xml()
+            .map(|request| calendar_changes(&request))
+            .unwrap_or_else(|| {
+                (
+                    Object {
+                        properties: VecMap::new(),
+                    },
+                    Vec::new(),
+                    Vec::new(),
+                )
+            });
+        if !unsupported.is_empty() {
+            return Ok(DavResponse::new(StatusCode::FORBIDDEN));
+        }
+        if !changes.properties.contains_key(&Property::Name) {
+            changes.properties.append(
+                Property::Name,
+                SetValue::Value(Value::Text(name.to_string())),
+            );
+        }
+
+        let mut result = self
+            .calendar_set(
+                SetRequest {
+                    account_id: Id::from(home.account_id),
+                    if_in_state: None,
+                    create: Some(VecMap::from_iter([("c0".to_string(), changes)])),
+                    update: None,
+                    destroy: None,
+                    arguments: SetArguments::default(),
+                },
+                home.access_token,
+            )
+            .await?;
+        if let Some(state_change) = result.state_change.take() {
+            self.broadcast_state_change(state_change).await;
+        }
+        if let Some(id) = result
+            .created
+            .get("c0")
+            .and_then(|created| created.get(&Property::Id).as_id())
+        {
+            Ok(DavResponse::new(StatusCode::CREATED)
+                .with_header(header::LOCATION, format!("{}{}/", home_href(home.name), id)))
+        } else {
+            Ok(DavResponse::new(StatusCode::FORBIDDEN))
+        }
+    }
+
+    async fn caldav_resource(
+        &self,
+        req: &DavRequest,
+        home: &CalDavHome<'_>,
+        calendar: Object<Value>,
+        resource: &str,
+    ) -> DavResult {
+        let calendar_id = calendar_document_id(&calendar);
+        let href = calendar_href(home.name, &calendar);
+        let event_ids = self.caldav_event_ids(home, calendar_id, vec![]).await?;
+        let current = self
+            .caldav_resolve(home, calendar_id, &event_ids, resource)
+            .await?;
+        let current_etag = current.as_ref().map(|(_, event)| etag(event.hash));
+        if !req.preconditions_met(current_etag.as_deref()) {
+            return Ok(DavResponse::new(StatusCode::PRECONDITION_FAILED));
+        }
+
+        match (req.method.as_str(), current) {
+            ("GET" | "HEAD", Some((_, event))) => {
+                let body = if req.method.as_str() == "GET" {
+                    ICalendar::build(&event.inner, None).into_bytes()
+                } else {
+                    Vec::new()
+                };
+                Ok(DavResponse::new(StatusCode::OK)
+                    .with_header(header::ETAG, etag(event.hash))
+                    .with_body("text/calendar; charset=utf-8", body))
+            }
+            ("PROPFIND", Some((document_id, event))) => {
+                let mut response = MultiStatus::new();
+                let (found, not_found) =
+                    dav_props(&req.body, EVENT_PROPS, |prop| event_prop(prop, &event));
+                response.add_response(&event_href(&href, document_id), found, not_found);
+                Ok(DavResponse::multi_status(response))
+            }
+            ("PUT", current) => {
+                let event = match std::str::from_utf8(&req.body)
+                    .map_err(|err| err.to_string())
+                    .and_then(ICalendar::parse)
+                {
+                    Ok(ical) if ical.events.len() == 1 => ical.events.into_iter().next().unwrap(),
+                    _ => return Ok(DavResponse::new(StatusCode::BAD_REQUEST)),
+                };
+
+                let mut create = None;
+                let mut update = None;
+                if let Some((document_id, current)) = &current {
+                    if current.inner.get(&Property::Uid) != event.get(&Property::Uid) {
+                        return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+                    }
+                    let mut changes = Object::with_capacity(event.properties.len());
+                    for (property, _) in &current.inner.properties {
+                        if !event.properties.contains_key(property)
+                            && !matches!(
+                                property,
+                                Property::CalendarIds
+                                    | Property::UtcStart
+                                    | Property::UtcEnd
+                                    | Property::Created
+                                    | Property::Updated
+                            )
+                        {
+                            changes.append(property.clone(), Value::Null);
+                        }
+                    }
+                    for (property, value) in event.properties {
+                        changes.append(property, value);
+                    }
+                    update = Some(VecMap::from_iter([(
+                        Id::from(*document_id),
+                        into_set_object(changes),
+                    )]));
+                } else {
+                    // Make sure the UID is not in use by another event
+                    if let Some(uid) = event.get(&Property::Uid).as_string() {
+                        if !self
+                            .filter(
+                                home.account_id,
+                                Collection::CalendarEvent,
+                                vec![Filter::eq(Property::Uid, uid.to_string())],
+                            )
+                            .await?
+                            .results
+                            .is_empty()
+                        {
+                            return Ok(DavResponse::new(StatusCode::FORBIDDEN));
+                        }
+                    }
+                    let mut changes = Object::with_capacity(event.properties.len() + 1);
+                    changes.append(
+                        Property::CalendarIds,
+                        Value::List(vec![Value::Id(calendar_id.into())]),
+                    );
+                    for (property, value) in event.properties {
+                        changes.append(property, value);
+                    }
+                    create = Some(VecMap::from_iter([(
+                        "c0".to_string(),
+                        into_set_object(changes),
+                    )]));
+                }
+
+                let is_create = create.is_some();
+                let mut result = self
+                    .calendar_event_set(
+                        SetRequest {
+                            account_id: Id::from(home.account_id),
+                            if_in_state: None,
+                            create,
+                            update,
+                            destroy: None,
+                            arguments: EventSetArguments {
+                                send_scheduling_messages: Some(true),
+                            },
+                        },
+                        home.access_token,
+                    )
+                    .await?;
+                if let Some(state_change) = result.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+                let document_id = if is_create {
+                    result
+                        .created
+                        .get("c0")
+                        .and_then(|created| created.get(&Property::Id).as_id())
+                        .map(|id| id.document_id())
+                } else {
+                    current
+                        .as_ref()
+                        .filter(|_| result.not_updated.is_empty())
+                        .map(|(document_id, _)| *document_id)
+                };
+                let document_id = if let Some(document_id) = document_id {
+                    document_id
+                } else {
+                    let is_forbidden = result
+                        .not_created
+                        .iter()
+                        .map(|(_, err)| err)
+                        .chain(result.not_updated.iter().map(|(_, err)| err))
+                        .any(|err| err.type_ == SetErrorType::Forbidden);
+                    return Ok(DavResponse::new(if is_forbidden {
+                        StatusCode::FORBIDDEN
+                    } else {
+                        StatusCode::BAD_REQUEST
+                    }));
+                };
+
+                let mut response = DavResponse::new(if is_create {
+                    StatusCode::CREATED
+                } else {
+                    StatusCode::NO_CONTENT
+                });
+                if let Some(event) = self
+                    .get_property::<HashedValue<Object<Value>>>(
+                        home.account_id,
+                        Collection::CalendarEvent,
+                        document_id,
+                        Property::Value,
+                    )
+                    .await?
+                {
+                    response = response.with_header(header::ETAG, etag(event.hash));
+                }
+                if is_create {
+                    response =
+                        response.with_header(header::LOCATION, event_href(&href, document_id));
+                }
+                Ok(response)
+            }
+            ("DELETE", Some((document_id, event))) => {
+                // Events in multiple calendars are only removed from this one
+                let id = Id::from(document_id);
+                let calendar_ids = event
+                    .inner
+                    .get(&Property::CalendarIds)
+                    .as_list()
+                    .map(|ids| {
+                        ids.iter()
+                            .filter(|id| id.as_id().map(|id| id.document_id()) != Some(calendar_id))
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                let (update, destroy) = if !calendar_ids.is_empty() {
+                    let mut changes = Object {
+                        properties: VecMap::with_capacity(1),
+                    };
+                    changes.properties.append(
+                        Property::CalendarIds,
+                        SetValue::Value(Value::List(calendar_ids)),
+                    );
+                    (Some(VecMap::from_iter([(id, changes)])), None)
+                } else {
+                    (None, Some(MaybeReference::Value(vec![id])))
+                };
+                let mut result = self
+                    .calendar_event_set(
+                        SetRequest {
+                            account_id: Id::from(home.account_id),
+                            if_in_state: None,
+                            create: None,
+                            update,
+                            destroy,
+                            arguments: EventSetArguments {
+                                send_scheduling_messages: Some(true),
+                            },
+                        },
+                        home.access_token,
+                    )
+                    .await?;
+                if let Some(state_change) = result.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+                Ok(DavResponse::new(
+                    if result.destroyed.contains(&id) || result.updated.contains_key(&id) {
+                        StatusCode::NO_CONTENT
+                    } else {
+                        StatusCode::FORBIDDEN
+                    },
+                ))
+            }
+            ("GET" | "HEAD" | "PROPFIND" | "DELETE", None) => {
+                Ok(DavResponse::new(StatusCode::NOT_FOUND))
+            }
+            _ => Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+
+    async fn caldav_calendars(
+        &self,
+        home: &CalDavHome<'_>,
+        ids: Option<Vec<Id>>,
+    ) -> Result<Vec<Object<Value>>, MethodError> {
+        self.calendar_get(
+            GetRequest {
+                account_id: Id::from(home.account_id),
+                ids: ids.map(|ids| {
+                    MaybeReference::Value(
+                        ids.into_iter()
+                            .map(|id| MaybeReference::Value(id.into()))
+                            .collect(),
+                    )
+                }),
+                properties: None,
+                arguments: RequestArguments::Calendar,
+            },
+            home.access_token,
+        )
+        .await
+        .map(|response| response.list)
+    }
+
+    async fn caldav_calendar(
+        &self,
+        home: &CalDavHome<'_>,
+        name: &str,
+    ) -> Result<Option<Object<Value>>, MethodError> {
+        if let Some(id) = Id::from_bytes(name.as_bytes()) {
+            self.caldav_calendars(home, Some(vec![id]))
+                .await
+                .map(|calendars| calendars.into_iter().next())
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn caldav_event_ids(
+        &self,
+        home: &CalDavHome<'_>,
+        calendar_id: u32,
+        mut filters: Vec<Filter>,
+    ) -> Result<RoaringBitmap, MethodError> {
+        filters.push(Filter::eq(Property::CalendarIds, calendar_id));
+        let mut event_ids = self
+            .filter(home.account_id, Collection::CalendarEvent, filters)
+            .await?
+            .results;
+        if home.access_token.is_shared(home.account_id) {
+            event_ids &= self
+                .owned_or_shared_calendar_events(home.access_token, home.account_id, Acl::ReadItems)
+                .await?;
+        }
+        Ok(event_ids)
+    }
+
+    // Resources are addressed by their UID or by their JMAP id
+    async fn caldav_resolve(
+        &self,
+        home: &CalDavHome<'_>,
+        calendar_id: u32,
+        event_ids: &RoaringBitmap,
+        resource: &str,
+    ) -> Result<Option<(u32, HashedValue<Object<Value>>)>, MethodError> {
+        let name = resource.strip_suffix(".ics").unwrap_or(resource);
+        let mut document_id = self
+            .filter(
+                home.account_id,
+                Collection::CalendarEvent,
+                vec![
+                    Filter::eq(Property::CalendarIds, calendar_id),
+                    Filter::eq(Property::Uid, name.to_string()),
+                ],
+            )
+            .await?
+            .results
+            .into_iter()
+            .find(|document_id| event_ids.contains(*document_id));
+        if document_id.is_none() {
+            document_id = Id::from_bytes(name.as_bytes())
+                .map(|id| id.document_id())
+                .filter(|document_id| event_ids.contains(*document_id));
+        }
+
+        if let Some(document_id) = document_id {
+            Ok(self
+                .get_property::<HashedValue<Object<Value>>>(
+                    home.account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+                .map(|event| (document_id, event)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn caldav_add_events(
+        &self,
+        response: &mut MultiStatus,
+        home: &CalDavHome<'_>,
+        calendar_href: &str,
+        event_ids: &RoaringBitmap,
+        names: &[PropName],
+    ) -> Result<(), MethodError> {
+        for (document_id, event) in self
+            .get_properties::<HashedValue<Object<Value>>, _, _>(
+                home.account_id,
+                Collection::CalendarEvent,
+                event_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            let (found, not_found) = dav_prop_values(names.to_vec(), EVENT_PROPS, &mut |prop| {
+                event_prop(prop, &event)
+            });
+            response.add_response(&event_href(calendar_href, document_id), found, not_found);
+        }
+        Ok(())
+    }
+
+    fn calendar_prop(
+        &self,
+        prop: &PropName,
+        calendar: &Object<Value>,
+        sync_token: &str,
+        owner: &str,
+    ) -> Option<PropValue> {
+        if prop.is(NS_DAV, "resourcetype") {
+            Some(PropValue::Xml("<d:collection/><c:calendar/>".to_string()))
+        } else if prop.is(NS_DAV, "displayname") {
+            calendar
+                .get(&Property::Name)
+                .as_string()
+                .map(|name| PropValue::Text(name.to_string()))
+        } else if prop.is(NS_CALDAV, "calendar-description") {
+            calendar
+                .get(&Property::Description)
+                .as_string()
+                .map(|description| PropValue::Text(description.to_string()))
+        } else if prop.is(NS_APPLE_ICAL, "calendar-color") {
+            calendar
+                .get(&Property::Color)
+                .as_string()
+                .map(|color| PropValue::Text(color.to_string()))
+        } else if prop.is(NS_APPLE_ICAL, "calendar-order") {
+            calendar
+                .get(&Property::SortOrder)
+                .as_uint()
+                .map(|order| PropValue::Text(order.to_string()))
+        } else if prop.is(NS_CALDAV, "supported-calendar-component-set") {
+            Some(PropValue::Xml("<c:comp name=\"VEVENT\"/>".to_string()))
+        } else if prop.is(NS_CALENDARSERVER, "getctag") || prop.is(NS_DAV, "sync-token") {
+            Some(PropValue::Text(sync_token.to_string()))
+        } else if prop.is(NS_DAV, "current-user-privilege-set") {
+            let rights = calendar.get(&Property::MyRights);
+            let has_right = |property: Property| {
+                rights
+                    .as_obj()
+                    .and_then(|rights| rights.get(&property).as_bool())
+                    .unwrap_or(false)
+            };
+            let mut privileges = Vec::new();
+            if has_right(Property::MayReadItems) {
+                privileges.push("d:read");
+            }
+            if has_right(Property::MayReadFreeBusy) {
+                privileges.push("c:read-free-busy");
+            }
+            if has_right(Property::MayWriteAll) {
+                privileges.extend(["d:write-content", "d:bind", "d:unbind"]);
+            }
+            if has_right(Property::MayAdmin) {
+                privileges.push("d:write-properties");
+            }
+            privileges.push("d:read-current-user-privilege-set");
+            Some(PropValue::Xml(
+                privileges
+                    .into_iter()
+                    .map(|privilege| format!("<d:privilege><{privilege}/></d:privilege>"))
+                    .collect(),
+            ))
+        } else if prop.is(NS_DAV, "owner") {
+            Some(PropValue::Href(vec![owner.to_string()]))
+        } else if prop.is(NS_DAV, "supported-report-set") {
+            Some(PropValue::Xml(concat!(
+                "<d:supported-report><d:report><c:calendar-multiget/></d:report></d:supported-report>",
+                "<d:supported-report><d:report><c:calendar-query/></d:report></d:supported-report>",
+                "<d:supported-report><d:report><d:sync-collection/></d:report></d:supported-report>"
+            ).to_string()))
+        } else if prop.is(NS_CALDAV, "max-resource-size") {
+            Some(PropValue::Text(
+                self.core.jmap.calendars_max_event_size.to_string(),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+fn event_prop(prop: &PropName, event: &HashedValue<Object<Value>>) -> Option<PropValue> {
+    if prop.is(NS_DAV, "resourcetype") {
+        Some(PropValue::Empty)
+    } else if prop.is(NS_DAV, "getetag") {
+        Some(PropValue::Text(etag(event.hash)))
+    } else if prop.is(NS_DAV, "getcontenttype") {
+        Some(PropValue::Text("text/calendar; charset=utf-8".to_string()))
+    } else if prop.is(NS_CALDAV, "calendar-data") {
+        Some(PropValue::Text(ICalendar::build(&event.inner, None)))
+    } else {
+        None
+    }
+}
+
+// Translates a calendar-query filter into index filters, returns None when
+// the query targets components other than events
+fn calendar_query_filters(request: &XmlElement) -> Option<Vec<Filter>> {
+    let mut filters = Vec::new();
+    if let Some(calendar) = request
+        .child(NS_CALDAV, "filter")
+        .and_then(|filter| filter.child(NS_CALDAV, "comp-filter"))
+    {
+        if let Some(component) = calendar.child(NS_CALDAV, "comp-filter") {
+            if !component
+                .attribute("name")
+                .is_some_and(|name| name.eq_ignore_ascii_case("VEVENT"))
+            {
+                return None;
+            }
+            if let Some(time_range) = component.child(NS_CALDAV, "time-range") {
+                if let Some(start) = time_range.attribute("start").and_then(parse_time_range) {
+                    filters.push(Filter::gt(Property::UtcEnd, start));
+                }
+                if let Some(end) = time_range.attribute("end").and_then(parse_time_range) {
+                    filters.push(Filter::lt(Property::UtcStart, end));
+                }
+            }
+        }
+    }
+    Some(filters)
+}
+
+// Maps the properties of a PROPPATCH or MKCALENDAR request to calendar changes
+fn calendar_changes(request: &XmlElement) -> (Object<SetValue>, Vec<PropName>, Vec<PropName>) {
+    let mut changes = Object {
+        properties: VecMap::new(),
+    };
+    let mut applied = Vec::new();
+    let mut unsupported = Vec::new();
+
+    for (is_set, props) in request.children.iter().filter_map(|action| {
+        if action.is(NS_DAV, "set") {
+            Some((true, action.child(NS_DAV, "prop")?))
+        } else if action.is(NS_DAV, "remove") {
+            Some((false, action.child(NS_DAV, "prop")?))
+        } else {
+            None
+        }
+    }) {
+        for prop in &props.children {
+            let name = PropName::new(&prop.ns, &prop.name);
+            let text = prop.text.trim();
+            let change = if name.is(NS_DAV, "displayname") && is_set {
+                Some((Property::Name, Value::Text(text.to_string())))
+            } else if name.is(NS_CALDAV, "calendar-description") {
+                Some((
+                    Property::Description,
+                    if is_set {
+                        Value::Text(text.to_string())
+                    } else {
+                        Value::Null
+                    },
+                ))
+            } else if name.is(NS_APPLE_ICAL, "calendar-color") {
+                Some((
+                    Property::Color,
+                    if is_set {
+                        Value::Text(text.to_string())
+                    } else {
+                        Value::Null
+                    },
+                ))
+            } else if name.is(NS_APPLE_ICAL, "calendar-order") {
+                if is_set {
+                    text.parse::<u64>()
+                        .ok()
+                        .map(|order| (Property::SortOrder, Value::UnsignedInt(order)))
+                } else {
+                    Some((Property::SortOrder, Value::UnsignedInt(0)))
+                }
+            } else if name.is(NS_DAV, "resourcetype") {
+                applied.push(name);
+                continue;
+            } else {
+                None
+            };
+
+            if let Some((property, value)) = change {
+                changes.properties.set(property, SetValue::Value(value));
+                applied.push(name);
+            } else {
+                unsupported.push(name);
+            }
+        }
+    }
+
+    (changes, applied, unsupported)
+}
+
+fn into_empty_props(names: Vec<PropName>) -> Vec<(PropName, PropValue)> {
+    names
+        .into_iter()
+        .map(|name| (name, PropValue::Empty))
+        .collect()
+}
+
+fn parse_time_range(value: &str) -> Option<u64> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|date| date.and_utc().timestamp().max(0) as u64)
+}
+
+fn calendar_document_id(calendar: &Object<Value>) -> u32 {
+    calendar
+        .get(&Property::Id)
+        .as_id()
+        .map(|id| id.document_id())
+        .unwrap_or_default()
+}
+
+fn home_href(name: &str) -> String {
+    format!("/dav/cal/{}/", encode_segment(name))
+}
+
+fn calendar_href(name: &str, calendar: &Object<Value>) -> String {
+    format!(
+        "{}{}/",
+        home_href(name),
+        Id::from(calendar_document_id(calendar))
+    )
+}
+
+fn event_href(calendar_href: &str, document_id: u32) -> String {
+    format!("{calendar_href}{}.ics", Id::from(document_id))
+}
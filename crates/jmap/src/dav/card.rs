@@ -0,0 +1,1477 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use directory::{backend::internal::manage::ManageDirectory, QueryBy, Type};
+use hyper::{header, StatusCode};
+use jmap_proto::{
+    error::{method::MethodError, set::SetErrorType},
+    method::{
+        get::{GetRequest, RequestArguments},
+        set::{self, SetRequest},
+    },
+    object::{contact::SetArguments, Object},
+    request::reference::MaybeReference,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        id::Id,
+        property::Property,
+        value::{SetValue, Value},
+    },
+};
+use store::{
+    query::{
+        log::{Change, Query},
+        Filter,
+    },
+    roaring::RoaringBitmap,
+    write::assert::HashedValue,
+};
+use utils::map::vec_map::VecMap;
+
+use crate::{
+    auth::AccessToken,
+    calendar_event::{
+        ical::{parse_line, unfold},
+        scheduling::into_set_object,
+    },
+    contact::{
+        set::ContactCardAddressBooks,
+        vcard::{VCard, VCardVersion},
+    },
+    JMAP,
+};
+
+use super::{
+    dav_prop_values, dav_props, encode_segment, etag, parse_sync_token, principal_href,
+    xml::{MultiStatus, PropName, PropValue, XmlElement, NS_CALENDARSERVER, NS_CARDDAV, NS_DAV},
+    DavRequest, DavResponse, DavResult, Depth, SYNC_TOKEN_PREFIX,
+};
+
+struct CardDavHome<'x> {
+    account_id: u32,
+    name: &'x str,
+    access_token: &'x AccessToken,
+}
+
+// Cards of the global address list, generated from the directory
+struct DirectoryCard {
+    id: Id,
+    card: Object<Value>,
+    vcard: String,
+    etag: String,
+}
+
+// Path segment of the read-only global address list
+const DIRECTORY_SEGMENT: &str = "directory";
+
+const HOME_PROPS: &[(&str, &str)] = &[
+    (NS_DAV, "resourcetype"),
+    (NS_DAV, "current-user-principal"),
+    (NS_DAV, "owner"),
+];
+
+const ADDRESS_BOOK_PROPS: &[(&str, &str)] = &[
+    (NS_DAV, "resourcetype"),
+    (NS_DAV, "displayname"),
+    (NS_CARDDAV, "addressbook-description"),
+    (NS_CARDDAV, "supported-address-data"),
+    (NS_CALENDARSERVER, "getctag"),
+    (NS_DAV, "sync-token"),
+    (NS_DAV, "current-user-privilege-set"),
+    (NS_DAV, "owner"),
+];
+
+const CARD_PROPS: &[(&str, &str)] = &[
+    (NS_DAV, "resourcetype"),
+    (NS_DAV, "getetag"),
+    (NS_DAV, "getcontenttype"),
+];
+
+impl JMAP {
+    pub async fn handle_carddav_request(
+        &self,
+        req: &DavRequest,
+        access_token: &AccessToken,
+    ) -> DavResult {
+        let name = match req.path.get(1) {
+            Some(name) => name.as_str(),
+            None => return self.handle_dav_root(req, access_token).await,
+        };
+        let home = if let Some(account_id) = self
+            .dav_account_id(access_token, name, Collection::AddressBook)
+            .await?
+        {
+            CardDavHome {
+                account_id,
+                name,
+                access_token,
+            }
+        } else {
+            return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+        };
+        let is_directory = req.path.get(2).map(|book| book.as_str()) == Some(DIRECTORY_SEGMENT)
+            && self.has_global_address_list(&home);
+
+        match (req.path.get(2), req.path.get(3), req.path.len()) {
+            (None, _, _) => self.carddav_home(req, &home).await,
+            (Some(_), None, _) if is_directory => self.carddav_directory(req, &home).await,
+            (Some(_), Some(resource), 4) if is_directory => {
+                self.carddav_directory_resource(req, &home, resource).await
+            }
+            (Some(book), None, _) => match req.method.as_str() {
+                "MKCOL" => self.carddav_mkcol(req, &home, book).await,
+                _ => {
+                    if let Some(book) = self.carddav_address_book(&home, book).await? {
+                        self.carddav_collection(req, &home, book).await
+                    } else {
+                        Ok(DavResponse::new(StatusCode::NOT_FOUND))
+                    }
+                }
+            },
+            (Some(book), Some(resource), 4) => {
+                if let Some(book) = self.carddav_address_book(&home, book).await? {
+                    self.carddav_resource(req, &home, book, resource).await
+                } else {
+                    Ok(DavResponse::new(StatusCode::NOT_FOUND))
+                }
+            }
+            _ => Ok(DavResponse::new(StatusCode::NOT_FOUND)),
+        }
+    }
+
+    async fn carddav_home(&self, req: &DavRequest, home: &CardDavHome<'_>) -> DavResult {
+        if req.method.as_str() != "PROPFIND" {
+            return Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let names = req.xml().map(|xml| xml.prop_names()).unwrap_or_default();
+        let owner = principal_href(home.name);
+        let mut response = MultiStatus::new();
+        let (found, not_found) = dav_prop_values(names.clone(), HOME_PROPS, &mut |prop| {
+            if prop.is(NS_DAV, "resourcetype") {
+                Some(PropValue::Xml("<d:collection/>".to_string()))
+            } else if prop.is(NS_DAV, "current-user-principal") {
+                Some(PropValue::Href(vec![principal_href(
+                    &home.access_token.name,
+                )]))
+            } else if prop.is(NS_DAV, "owner") {
+                Some(PropValue::Href(vec![owner.clone()]))
+            } else {
+                None
+            }
+        });
+        response.add_response(&home_href(home.name), found, not_found);
+
+        if req.depth == Depth::One {
+            let sync_token = self
+                .dav_sync_token(home.account_id, Collection::ContactCard)
+                .await?;
+            for book in self.carddav_address_books(home, None).await? {
+                let (found, not_found) =
+                    dav_prop_values(names.clone(), ADDRESS_BOOK_PROPS, &mut |prop| {
+                        self.address_book_prop(prop, &book, &sync_token, &owner)
+                    });
+                response.add_response(&address_book_href(home.name, &book), found, not_found);
+            }
+            if self.has_global_address_list(home) {
+                let cards = self.carddav_directory_cards().await?;
+                let (found, not_found) =
+                    dav_prop_values(names.clone(), ADDRESS_BOOK_PROPS, &mut |prop| {
+                        self.directory_prop(prop, &cards, &owner)
+                    });
+                response.add_response(&directory_href(home.name), found, not_found);
+            }
+        }
+
+        Ok(DavResponse::multi_status(response))
+    }
+
+    async fn carddav_collection(
+        &self,
+        req: &DavRequest,
+        home: &CardDavHome<'_>,
+        book: Object<Value>,
+    ) -> DavResult {
+        let book_id = address_book_document_id(&book);
+        let href = address_book_href(home.name, &book);
+
+        match req.method.as_str() {
+            "PROPFIND" => {
+                let names = req.xml().map(|xml| xml.prop_names()).unwrap_or_default();
+                let owner = principal_href(home.name);
+                let sync_token = self
+                    .dav_sync_token(home.account_id, Collection::ContactCard)
+                    .await?;
+                let mut response = MultiStatus::new();
+                let (found, not_found) =
+                    dav_prop_values(names.clone(), ADDRESS_BOOK_PROPS, &mut |prop| {
+                        self.address_book_prop(prop, &book, &sync_token, &owner)
+                    });
+                response.add_response(&href, found, not_found);
+
+                if req.depth == Depth::One {
+                    let card_ids = self.carddav_card_ids(home, book_id).await?;
+                    self.carddav_add_cards(
+                        &mut response,
+                        home,
+                        &href,
+                        &card_ids,
+                        &names,
+                        None,
+                        VCardVersion::V3,
+                    )
+                    .await?;
+                }
+
+                Ok(DavResponse::multi_status(response))
+            }
+            "REPORT" => {
+                let request = if let Some(request) = req.xml() {
+                    request
+                } else {
+                    return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+                };
+                let names = request.prop_names();
+                let version = address_data_version(&request);
+
+                if request.is(NS_CARDDAV, "addressbook-multiget") {
+                    let card_ids = self.carddav_card_ids(home, book_id).await?;
+                    let mut response = MultiStatus::new();
+                    for resource_href in request.children(NS_DAV, "href") {
+                        let resource = resource_href
+                            .text
+                            .trim()
+                            .trim_end_matches('/')
+                            .rsplit('/')
+                            .next()
+                            .unwrap_or_default();
+                        let resource = super::percent_decode(resource);
+                        match self
+                            .carddav_resolve(home, book_id, &card_ids, &resource)
+                            .await?
+                        {
+                            Some((_, card)) => {
+                                let (found, not_found) =
+                                    dav_prop_values(names.clone(), CARD_PROPS, &mut |prop| {
+                                        card_prop(prop, &card, version)
+                                    });
+                                response.add_response(resource_href.text.trim(), found, not_found);
+                            }
+                            None => {
+                                response.add_status(resource_href.text.trim(), "404 Not Found");
+                            }
+                        }
+                    }
+                    Ok(DavResponse::multi_status(response))
+                } else if request.is(NS_CARDDAV, "addressbook-query") {
+                    let card_ids = self.carddav_card_ids(home, book_id).await?;
+                    let mut response = MultiStatus::new();
+                    self.carddav_add_cards(
+                        &mut response,
+                        home,
+                        &href,
+                        &card_ids,
+                        &names,
+                        Some(&request),
+                        version,
+                    )
+                    .await?;
+                    Ok(DavResponse::multi_status(response))
+                } else if request.is(NS_DAV, "sync-collection") {
+                    let since = match request
+                        .child(NS_DAV, "sync-token")
+                        .map(|token| token.text.trim())
+                        .filter(|token| !token.is_empty())
+                    {
+                        Some(token) => match parse_sync_token(token) {
+                            Some(0) => None,
+                            Some(change_id) => Some(change_id - 1),
+                            None => {
+                                return Ok(DavResponse::new(StatusCode::FORBIDDEN));
+                            }
+                        },
+                        None => None,
+                    };
+                    let card_ids = self.carddav_card_ids(home, book_id).await?;
+                    let mut response = MultiStatus::new().with_sync_token(
+                        self.dav_sync_token(home.account_id, Collection::ContactCard)
+                            .await?,
+                    );
+
+                    if let Some(change_id) = since {
+                        let mut changed_ids = RoaringBitmap::new();
+                        let mut removed_ids = RoaringBitmap::new();
+                        for change in self
+                            .changes_(
+                                home.account_id,
+                                Collection::ContactCard,
+                                Query::Since(change_id),
+                            )
+                            .await?
+                            .changes
+                        {
+                            let document_id = match change {
+                                Change::Insert(id)
+                                | Change::Update(id)
+                                | Change::ChildUpdate(id)
+                                | Change::Delete(id) => id as u32,
+                            };
+                            if card_ids.contains(document_id) {
+                                changed_ids.insert(document_id);
+                            } else {
+                                removed_ids.insert(document_id);
+                            }
+                        }
+                        self.carddav_add_cards(
+                            &mut response,
+                            home,
+                            &href,
+                            &changed_ids,
+                            &names,
+                            None,
+                            version,
+                        )
+                        .await?;
+                        for document_id in removed_ids {
+                            response.add_status(&card_href(&href, document_id), "404 Not Found");
+                        }
+                    } else {
+                        self.carddav_add_cards(
+                            &mut response,
+                            home,
+                            &href,
+                            &card_ids,
+                            &names,
+                            None,
+                            version,
+                        )
+                        .await?;
+                    }
+
+                    Ok(DavResponse::multi_status(response))
+                } else {
+                    Ok(DavResponse::new(StatusCode::FORBIDDEN))
+                }
+            }
+            "PROPPATCH" => {
+                let request = if let Some(request) = req.xml() {
+                    request
+                } else {
+                    return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+                };
+                let (changes, applied, unsupported) = address_book_changes(&request);
+                let mut response = MultiStatus::new();
+                if !unsupported.is_empty() {
+                    response.add_propstats(
+                        &href,
+                        vec![
+                            ("403 Forbidden", into_empty_props(unsupported)),
+                            ("424 Failed Dependency", into_empty_props(applied)),
+                        ],
+                    );
+                    return Ok(DavResponse::multi_status(response));
+                }
+
+                let id = Id::from(book_id);
+                let mut result = self
+                    .address_book_set(
+                        SetRequest {
+                            account_id: Id::from(home.account_id),
+                            if_in_state: None,
+                            create: None,
+                            update: Some(VecMap::from_iter([(id, changes)])),
+                            destroy: None,
+                            arguments: SetArguments::default(),
+                        },
+                        home.access_token,
+                    )
+                    .await?;
+                let status = if result.not_updated.is_empty() {
+                    "200 OK"
+                } else {
+                    "403 Forbidden"
+                };
+                if let Some(state_change) = result.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+                response.add_propstats(&href, vec![(status, into_empty_props(applied))]);
+                Ok(DavResponse::multi_status(response))
+            }
+            "DELETE" => {
+                let id = Id::from(book_id);
+                let mut result = self
+                    .address_book_set(
+                        SetRequest {
+                            account_id: Id::from(home.account_id),
+                            if_in_state: None,
+                            create: None,
+                            update: None,
+                            destroy: Some(MaybeReference::Value(vec![id])),
+                            arguments: SetArguments {
+                                on_destroy_remove_contents: Some(true),
+                                ..Default::default()
+                            },
+                        },
+                        home.access_token,
+                    )
+                    .await?;
+                if let Some(state_change) = result.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+                Ok(DavResponse::new(if result.destroyed.contains(&id) {
+                    StatusCode::NO_CONTENT
+                } else {
+                    StatusCode::FORBIDDEN
+                }))
+            }
+            _ => Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+
+    async fn carddav_mkcol(
+        &self,
+        req: &DavRequest,
+        home: &CardDavHome<'_>,
+        name: &str,
+    ) -> DavResult {
+        if name == DIRECTORY_SEGMENT || self.carddav_address_book(home, name).await?.is_some() {
+            return Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let (mut changes, _, unsupported) = req
+            .xml()
+            .map(|request| address_book_changes(&request))
+            .unwrap_or_else(|| {
+                (
+                    Object {
+                        properties: VecMap::new(),
+                    },
+                    Vec::new(),
+                    Vec::new(),
+                )
+            });
+        if !unsupported.is_empty() {
+            return Ok(DavResponse::new(StatusCode::FORBIDDEN));
+        }
+        if !changes.properties.contains_key(&Property::Name) {
+            changes.properties.append(
+                Property::Name,
+                SetValue::Value(Value::Text(name.to_string())),
+            );
+        }
+
+        let mut result = self
+            .address_book_set(
+                SetRequest {
+                    account_id: Id::from(home.account_id),
+                    if_in_state: None,
+                    create: Some(VecMap::from_iter([("c0".to_string(), changes)])),
+                    update: None,
+                    destroy: None,
+                    arguments: SetArguments::default(),
+                },
+                home.access_token,
+            )
+            .await?;
+        if let Some(state_change) = result.state_change.take() {
+            self.broadcast_state_change(state_change).await;
+        }
+        if let Some(id) = result
+            .created
+            .get("c0")
+            .and_then(|created| created.get(&Property::Id).as_id())
+        {
+            Ok(DavResponse::new(StatusCode::CREATED)
+                .with_header(header::LOCATION, format!("{}{}/", home_href(home.name), id)))
+        } else {
+            Ok(DavResponse::new(StatusCode::FORBIDDEN))
+        }
+    }
+
+    async fn carddav_resource(
+        &self,
+        req: &DavRequest,
+        home: &CardDavHome<'_>,
+        book: Object<Value>,
+        resource: &str,
+    ) -> DavResult {
+        let book_id = address_book_document_id(&book);
+        let href = address_book_href(home.name, &book);
+        let card_ids = self.carddav_card_ids(home, book_id).await?;
+        let current = self
+            .carddav_resolve(home, book_id, &card_ids, resource)
+            .await?;
+        let current_etag = current.as_ref().map(|(_, card)| etag(card.hash));
+        if !req.preconditions_met(current_etag.as_deref()) {
+            return Ok(DavResponse::new(StatusCode::PRECONDITION_FAILED));
+        }
+
+        match (req.method.as_str(), current) {
+            ("GET" | "HEAD", Some((_, card))) => {
+                let body = if req.method.as_str() == "GET" {
+                    VCard::build(&card.inner, VCardVersion::V3).into_bytes()
+                } else {
+                    Vec::new()
+                };
+                Ok(DavResponse::new(StatusCode::OK)
+                    .with_header(header::ETAG, etag(card.hash))
+                    .with_body("text/vcard; charset=utf-8", body))
+            }
+            ("PROPFIND", Some((document_id, card))) => {
+                let mut response = MultiStatus::new();
+                let (found, not_found) = dav_props(&req.body, CARD_PROPS, |prop| {
+                    card_prop(prop, &card, VCardVersion::V3)
+                });
+                response.add_response(&card_href(&href, document_id), found, not_found);
+                Ok(DavResponse::multi_status(response))
+            }
+            ("PUT", current) => {
+                let card = match std::str::from_utf8(&req.body)
+                    .map_err(|err| err.to_string())
+                    .and_then(VCard::parse)
+                {
+                    Ok(vcard) if vcard.cards.len() == 1 => vcard.cards.into_iter().next().unwrap(),
+                    _ => return Ok(DavResponse::new(StatusCode::BAD_REQUEST)),
+                };
+
+                let mut create = None;
+                let mut update = None;
+                if let Some((document_id, current)) = &current {
+                    if card.get(&Property::Uid) != &Value::Null
+                        && current.inner.get(&Property::Uid) != card.get(&Property::Uid)
+                    {
+                        return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+                    }
+                    let mut changes = Object::with_capacity(card.properties.len());
+                    for (property, _) in &current.inner.properties {
+                        if !card.properties.contains_key(property)
+                            && !matches!(
+                                property,
+                                Property::AddressBookIds
+                                    | Property::Uid
+                                    | Property::Created
+                                    | Property::Updated
+                            )
+                        {
+                            changes.append(property.clone(), Value::Null);
+                        }
+                    }
+                    for (property, value) in card.properties {
+                        changes.append(property, value);
+                    }
+                    update = Some(VecMap::from_iter([(
+                        Id::from(*document_id),
+                        into_set_object(changes),
+                    )]));
+                } else {
+                    // Make sure the UID is not in use by another card
+                    if let Some(uid) = card.get(&Property::Uid).as_string() {
+                        if !self
+                            .filter(
+                                home.account_id,
+                                Collection::ContactCard,
+                                vec![
+                                    Filter::eq(Property::AddressBookIds, book_id),
+                                    Filter::eq(Property::Uid, uid.to_string()),
+                                ],
+                            )
+                            .await?
+                            .results
+                            .is_empty()
+                        {
+                            return Ok(DavResponse::new(StatusCode::FORBIDDEN));
+                        }
+                    }
+                    let mut changes = Object::with_capacity(card.properties.len() + 1);
+                    changes.append(
+                        Property::AddressBookIds,
+                        Value::List(vec![Value::Id(book_id.into())]),
+                    );
+                    for (property, value) in card.properties {
+                        changes.append(property, value);
+                    }
+                    create = Some(VecMap::from_iter([(
+                        "c0".to_string(),
+                        into_set_object(changes),
+                    )]));
+                }
+
+                let is_create = create.is_some();
+                let mut result = self
+                    .contact_card_set(
+                        SetRequest {
+                            account_id: Id::from(home.account_id),
+                            if_in_state: None,
+                            create,
+                            update,
+                            destroy: None,
+                            arguments: set::RequestArguments::ContactCard,
+                        },
+                        home.access_token,
+                    )
+                    .await?;
+                if let Some(state_change) = result.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+                let document_id = if is_create {
+                    result
+                        .created
+                        .get("c0")
+                        .and_then(|created| created.get(&Property::Id).as_id())
+                        .map(|id| id.document_id())
+                } else {
+                    current
+                        .as_ref()
+                        .filter(|_| result.not_updated.is_empty())
+                        .map(|(document_id, _)| *document_id)
+                };
+                let document_id = if let Some(document_id) = document_id {
+                    document_id
+                } else {
+                    let is_forbidden = result
+                        .not_created
+                        .iter()
+                        .map(|(_, err)| err)
+                        .chain(result.not_updated.iter().map(|(_, err)| err))
+                        .any(|err| {
+                            matches!(err.type_, SetErrorType::Forbidden | SetErrorType::TooLarge)
+                        });
+                    return Ok(DavResponse::new(if is_forbidden {
+                        StatusCode::FORBIDDEN
+                    } else {
+                        StatusCode::BAD_REQUEST
+                    }));
+                };
+
+                let mut response = DavResponse::new(if is_create {
+                    StatusCode::CREATED
+                } else {
+                    StatusCode::NO_CONTENT
+                });
+                if let Some(card) = self
+                    .get_property::<HashedValue<Object<Value>>>(
+                        home.account_id,
+                        Collection::ContactCard,
+                        document_id,
+                        Property::Value,
+                    )
+                    .await?
+                {
+                    response = response.with_header(header::ETAG, etag(card.hash));
+                }
+                if is_create {
+                    response =
+                        response.with_header(header::LOCATION, card_href(&href, document_id));
+                }
+                Ok(response)
+            }
+            ("DELETE", Some((document_id, card))) => {
+                // Cards in multiple address books are only removed from this one
+                let id = Id::from(document_id);
+                let address_book_ids = card
+                    .inner
+                    .address_book_ids()
+                    .filter(|id| *id != book_id)
+                    .map(|id| Value::Id(id.into()))
+                    .collect::<Vec<_>>();
+                let (update, destroy) = if !address_book_ids.is_empty() {
+                    let mut changes = Object {
+                        properties: VecMap::with_capacity(1),
+                    };
+                    changes.properties.append(
+                        Property::AddressBookIds,
+                        SetValue::Value(Value::List(address_book_ids)),
+                    );
+                    (Some(VecMap::from_iter([(id, changes)])), None)
+                } else {
+                    (None, Some(MaybeReference::Value(vec![id])))
+                };
+                let mut result = self
+                    .contact_card_set(
+                        SetRequest {
+                            account_id: Id::from(home.account_id),
+                            if_in_state: None,
+                            create: None,
+                            update,
+                            destroy,
+                            arguments: set::RequestArguments::ContactCard,
+                        },
+                        home.access_token,
+                    )
+                    .await?;
+                if let Some(state_change) = result.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+                Ok(DavResponse::new(
+                    if result.destroyed.contains(&id) || result.updated.contains_key(&id) {
+                        StatusCode::NO_CONTENT
+                    } else {
+                        StatusCode::FORBIDDEN
+                    },
+                ))
+            }
+            ("GET" | "HEAD" | "PROPFIND" | "DELETE", None) => {
+                Ok(DavResponse::new(StatusCode::NOT_FOUND))
+            }
+            _ => Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+
+    async fn carddav_directory(&self, req: &DavRequest, home: &CardDavHome<'_>) -> DavResult {
+        let href = directory_href(home.name);
+        let owner = principal_href(home.name);
+        let cards = self.carddav_directory_cards().await?;
+
+        match req.method.as_str() {
+            "PROPFIND" => {
+                let names = req.xml().map(|xml| xml.prop_names()).unwrap_or_default();
+                let mut response = MultiStatus::new();
+                let (found, not_found) =
+                    dav_prop_values(names.clone(), ADDRESS_BOOK_PROPS, &mut |prop| {
+                        self.directory_prop(prop, &cards, &owner)
+                    });
+                response.add_response(&href, found, not_found);
+
+                if req.depth == Depth::One {
+                    add_directory_cards(
+                        &mut response,
+                        &href,
+                        cards.iter(),
+                        &names,
+                        None,
+                        VCardVersion::V3,
+                    );
+                }
+
+                Ok(DavResponse::multi_status(response))
+            }
+            "REPORT" => {
+                let request = if let Some(request) = req.xml() {
+                    request
+                } else {
+                    return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+                };
+                let names = request.prop_names();
+                let version = address_data_version(&request);
+                let mut response = MultiStatus::new();
+
+                if request.is(NS_CARDDAV, "addressbook-multiget") {
+                    for resource_href in request.children(NS_DAV, "href") {
+                        let resource = resource_href
+                            .text
+                            .trim()
+                            .trim_end_matches('/')
+                            .rsplit('/')
+                            .next()
+                            .unwrap_or_default();
+                        let resource = super::percent_decode(resource);
+                        if let Some(card) = directory_card(&cards, &resource) {
+                            let (found, not_found) =
+                                dav_prop_values(names.clone(), CARD_PROPS, &mut |prop| {
+                                    resource_prop(prop, &card.etag, || card.build(version))
+                                });
+                            response.add_response(resource_href.text.trim(), found, not_found);
+                        } else {
+                            response.add_status(resource_href.text.trim(), "404 Not Found");
+                        }
+                    }
+                } else if request.is(NS_CARDDAV, "addressbook-query") {
+                    add_directory_cards(
+                        &mut response,
+                        &href,
+                        cards.iter(),
+                        &names,
+                        Some(&request),
+                        version,
+                    );
+                } else if request.is(NS_DAV, "sync-collection") {
+                    // Changes to the directory are not logged, clients holding
+                    // an outdated token are asked to perform a full resync
+                    let sync_token = directory_sync_token(&cards);
+                    match request
+                        .child(NS_DAV, "sync-token")
+                        .map(|token| token.text.trim())
+                        .filter(|token| !token.is_empty())
+                    {
+                        Some(token) if token == sync_token => {}
+                        Some(_) => return Ok(DavResponse::new(StatusCode::FORBIDDEN)),
+                        None => add_directory_cards(
+                            &mut response,
+                            &href,
+                            cards.iter(),
+                            &names,
+                            None,
+                            version,
+                        ),
+                    }
+                    response = response.with_sync_token(sync_token);
+                } else {
+                    return Ok(DavResponse::new(StatusCode::FORBIDDEN));
+                }
+
+                Ok(DavResponse::multi_status(response))
+            }
+            "PROPPATCH" | "DELETE" | "MKCOL" => Ok(DavResponse::new(StatusCode::FORBIDDEN)),
+            _ => Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+
+    async fn carddav_directory_resource(
+        &self,
+        req: &DavRequest,
+        home: &CardDavHome<'_>,
+        resource: &str,
+    ) -> DavResult {
+        let cards = self.carddav_directory_cards().await?;
+        let card = directory_card(&cards, resource);
+        if !req.preconditions_met(card.map(|card| card.etag.as_str())) {
+            return Ok(DavResponse::new(StatusCode::PRECONDITION_FAILED));
+        }
+
+        match (req.method.as_str(), card) {
+            ("GET" | "HEAD", Some(card)) => {
+                let body = if req.method.as_str() == "GET" {
+                    card.vcard.clone().into_bytes()
+                } else {
+                    Vec::new()
+                };
+                Ok(DavResponse::new(StatusCode::OK)
+                    .with_header(header::ETAG, card.etag.clone())
+                    .with_body("text/vcard; charset=utf-8", body))
+            }
+            ("PROPFIND", Some(card)) => {
+                let mut response = MultiStatus::new();
+                let (found, not_found) = dav_props(&req.body, CARD_PROPS, |prop| {
+                    resource_prop(prop, &card.etag, || card.vcard.clone())
+                });
+                response.add_response(
+                    &format!("{}{}.vcf", directory_href(home.name), card.id),
+                    found,
+                    not_found,
+                );
+                Ok(DavResponse::multi_status(response))
+            }
+            ("PUT" | "DELETE", _) => Ok(DavResponse::new(StatusCode::FORBIDDEN)),
+            ("GET" | "HEAD" | "PROPFIND", None) => Ok(DavResponse::new(StatusCode::NOT_FOUND)),
+            _ => Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+
+    // The global address list is generated on demand from the principals in
+    // the internal directory, it is only listed under the user's own home
+    fn has_global_address_list(&self, home: &CardDavHome<'_>) -> bool {
+        self.core.jmap.contacts_global_address_list.is_some()
+            && home.account_id == home.access_token.primary_id()
+    }
+
+    async fn carddav_directory_cards(&self) -> Result<Vec<DirectoryCard>, MethodError> {
+        let mut cards = Vec::new();
+        for name in self
+            .core
+            .storage
+            .data
+            .list_accounts(None, None)
+            .await
+            .map_err(|_| MethodError::ServerPartialFail)?
+        {
+            let principal = if let Some(principal) = self
+                .core
+                .storage
+                .directory
+                .query(QueryBy::Name(&name), false)
+                .await
+                .map_err(|_| MethodError::ServerPartialFail)?
+            {
+                principal
+            } else {
+                continue;
+            };
+            let kind = match principal.typ {
+                Type::Individual | Type::Superuser => "individual",
+                Type::Group | Type::List => "group",
+                Type::Resource => "application",
+                Type::Location => "location",
+                Type::Other => continue,
+            };
+            if principal.emails.is_empty() {
+                continue;
+            }
+
+            let id = Id::from(principal.id);
+            let mut emails = Object::with_capacity(principal.emails.len());
+            for (pos, email) in principal.emails.iter().enumerate() {
+                let mut entry = Object::with_capacity(3)
+                    .with_property(Property::_T("@type".to_string()), "EmailAddress")
+                    .with_property(Property::_T("address".to_string()), email.as_str());
+                if pos == 0 {
+                    entry.set(Property::_T("pref".to_string()), Value::UnsignedInt(1));
+                }
+                emails.append(Property::_T(format!("e{}", pos + 1)), entry);
+            }
+            let card = Object::with_capacity(6)
+                .with_property(Property::parse("@type"), "Card")
+                .with_property(Property::parse("version"), "1.0")
+                .with_property(Property::Uid, id.to_string())
+                .with_property(Property::parse("kind"), kind)
+                .with_property(
+                    Property::parse("name"),
+                    Object::with_capacity(2)
+                        .with_property(Property::_T("@type".to_string()), "Name")
+                        .with_property(
+                            Property::_T("full".to_string()),
+                            principal
+                                .description
+                                .filter(|description| !description.is_empty())
+                                .unwrap_or(principal.name),
+                        ),
+                )
+                .with_property(Property::parse("emails"), emails);
+            let vcard = VCard::build(&card, VCardVersion::V3);
+            let mut hasher = DefaultHasher::new();
+            vcard.hash(&mut hasher);
+            cards.push(DirectoryCard {
+                id,
+                card,
+                vcard,
+                etag: etag(hasher.finish()),
+            });
+        }
+        Ok(cards)
+    }
+
+    fn directory_prop(
+        &self,
+        prop: &PropName,
+        cards: &[DirectoryCard],
+        owner: &str,
+    ) -> Option<PropValue> {
+        if prop.is(NS_DAV, "resourcetype") {
+            Some(PropValue::Xml(
+                "<d:collection/><card:addressbook/>".to_string(),
+            ))
+        } else if prop.is(NS_DAV, "displayname") {
+            self.core
+                .jmap
+                .contacts_global_address_list
+                .as_ref()
+                .map(|name| PropValue::Text(name.to_string()))
+        } else if prop.is(NS_CARDDAV, "supported-address-data") {
+            Some(PropValue::Xml(SUPPORTED_ADDRESS_DATA.to_string()))
+        } else if prop.is(NS_CALENDARSERVER, "getctag") || prop.is(NS_DAV, "sync-token") {
+            Some(PropValue::Text(directory_sync_token(cards)))
+        } else if prop.is(NS_DAV, "current-user-privilege-set") {
+            Some(PropValue::Xml(
+                "<d:privilege><d:read/></d:privilege><d:privilege><d:read-current-user-privilege-set/></d:privilege>"
+                    .to_string(),
+            ))
+        } else if prop.is(NS_DAV, "owner") {
+            Some(PropValue::Href(vec![owner.to_string()]))
+        } else if prop.is(NS_DAV, "supported-report-set") {
+            Some(PropValue::Xml(SUPPORTED_REPORTS.to_string()))
+        } else {
+            None
+        }
+    }
+
+    async fn carddav_address_books(
+        &self,
+        home: &CardDavHome<'_>,
+        ids: Option<Vec<Id>>,
+    ) -> Result<Vec<Object<Value>>, MethodError> {
+        self.address_book_get(
+            GetRequest {
+                account_id: Id::from(home.account_id),
+                ids: ids.map(|ids| {
+                    MaybeReference::Value(
+                        ids.into_iter()
+                            .map(|id| MaybeReference::Value(id.into()))
+                            .collect(),
+                    )
+                }),
+                properties: None,
+                arguments: RequestArguments::AddressBook,
+            },
+            home.access_token,
+        )
+        .await
+        .map(|response| response.list)
+    }
+
+    async fn carddav_address_book(
+        &self,
+        home: &CardDavHome<'_>,
+        name: &str,
+    ) -> Result<Option<Object<Value>>, MethodError> {
+        if let Some(id) = Id::from_bytes(name.as_bytes()) {
+            self.carddav_address_books(home, Some(vec![id]))
+                .await
+                .map(|books| books.into_iter().next())
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn carddav_card_ids(
+        &self,
+        home: &CardDavHome<'_>,
+        book_id: u32,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let mut card_ids = self
+            .filter(
+                home.account_id,
+                Collection::ContactCard,
+                vec![Filter::eq(Property::AddressBookIds, book_id)],
+            )
+            .await?
+            .results;
+        if home.access_token.is_shared(home.account_id) {
+            card_ids &= self
+                .owned_or_shared_contacts(home.access_token, home.account_id, Acl::ReadItems)
+                .await?;
+        }
+        Ok(card_ids)
+    }
+
+    // Resources are addressed by their UID or by their JMAP id
+    async fn carddav_resolve(
+        &self,
+        home: &CardDavHome<'_>,
+        book_id: u32,
+        card_ids: &RoaringBitmap,
+        resource: &str,
+    ) -> Result<Option<(u32, HashedValue<Object<Value>>)>, MethodError> {
+        let name = resource.strip_suffix(".vcf").unwrap_or(resource);
+        let mut document_id = self
+            .filter(
+                home.account_id,
+                Collection::ContactCard,
+                vec![
+                    Filter::eq(Property::AddressBookIds, book_id),
+                    Filter::eq(Property::Uid, name.to_string()),
+                ],
+            )
+            .await?
+            .results
+            .into_iter()
+            .find(|document_id| card_ids.contains(*document_id));
+        if document_id.is_none() {
+            document_id = Id::from_bytes(name.as_bytes())
+                .map(|id| id.document_id())
+                .filter(|document_id| card_ids.contains(*document_id));
+        }
+
+        if let Some(document_id) = document_id {
+            Ok(self
+                .get_property::<HashedValue<Object<Value>>>(
+                    home.account_id,
+                    Collection::ContactCard,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+                .map(|card| (document_id, card)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn carddav_add_cards(
+        &self,
+        response: &mut MultiStatus,
+        home: &CardDavHome<'_>,
+        address_book_href: &str,
+        card_ids: &RoaringBitmap,
+        names: &[PropName],
+        query: Option<&XmlElement>,
+        version: VCardVersion,
+    ) -> Result<(), MethodError> {
+        let mut limit = query.and_then(query_limit).unwrap_or(usize::MAX);
+        for (document_id, card) in self
+            .get_properties::<HashedValue<Object<Value>>, _, _>(
+                home.account_id,
+                Collection::ContactCard,
+                card_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            let vcard = VCard::build(&card.inner, version);
+            if let Some(query) = query {
+                if limit == 0 {
+                    break;
+                } else if !query_matches(query, &vcard) {
+                    continue;
+                }
+                limit -= 1;
+            }
+            let etag = etag(card.hash);
+            let (found, not_found) = dav_prop_values(names.to_vec(), CARD_PROPS, &mut |prop| {
+                resource_prop(prop, &etag, || vcard.clone())
+            });
+            response.add_response(&card_href(address_book_href, document_id), found, not_found);
+        }
+        Ok(())
+    }
+
+    fn address_book_prop(
+        &self,
+        prop: &PropName,
+        book: &Object<Value>,
+        sync_token: &str,
+        owner: &str,
+    ) -> Option<PropValue> {
+        if prop.is(NS_DAV, "resourcetype") {
+            Some(PropValue::Xml(
+                "<d:collection/><card:addressbook/>".to_string(),
+            ))
+        } else if prop.is(NS_DAV, "displayname") {
+            book.get(&Property::Name)
+                .as_string()
+                .map(|name| PropValue::Text(name.to_string()))
+        } else if prop.is(NS_CARDDAV, "addressbook-description") {
+            book.get(&Property::Description)
+                .as_string()
+                .map(|description| PropValue::Text(description.to_string()))
+        } else if prop.is(NS_CARDDAV, "supported-address-data") {
+            Some(PropValue::Xml(SUPPORTED_ADDRESS_DATA.to_string()))
+        } else if prop.is(NS_CALENDARSERVER, "getctag") || prop.is(NS_DAV, "sync-token") {
+            Some(PropValue::Text(sync_token.to_string()))
+        } else if prop.is(NS_DAV, "current-user-privilege-set") {
+            let rights = book.get(&Property::MyRights);
+            let has_right = |property: Property| {
+                rights
+                    .as_obj()
+                    .and_then(|rights| rights.get(&property).as_bool())
+                    .unwrap_or(false)
+            };
+            let mut privileges = Vec::new();
+            if has_right(Property::MayRead) {
+                privileges.push("d:read");
+            }
+            if has_right(Property::MayWrite) {
+                privileges.extend(["d:write-content", "d:bind"]);
+            }
+            if has_right(Property::MayDelete) {
+                privileges.push("d:unbind");
+            }
+            if has_right(Property::MayShare) {
+                privileges.push("d:write-properties");
+            }
+            privileges.push("d:read-current-user-privilege-set");
+            Some(PropValue::Xml(
+                privileges
+                    .into_iter()
+                    .map(|privilege| format!("<d:privilege><{privilege}/></d:privilege>"))
+                    .collect(),
+            ))
+        } else if prop.is(NS_DAV, "owner") {
+            Some(PropValue::Href(vec![owner.to_string()]))
+        } else if prop.is(NS_DAV, "supported-report-set") {
+            Some(PropValue::Xml(SUPPORTED_REPORTS.to_string()))
+        } else if prop.is(NS_CARDDAV, "max-resource-size") {
+            Some(PropValue::Text(
+                self.core.jmap.contacts_max_card_size.to_string(),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+const SUPPORTED_ADDRESS_DATA: &str = concat!(
+    "<card:address-data-type content-type=\"text/vcard\" version=\"3.0\"/>",
+    "<card:address-data-type content-type=\"text/vcard\" version=\"4.0\"/>"
+);
+
+const SUPPORTED_REPORTS: &str = concat!(
+    "<d:supported-report><d:report><card:addressbook-multiget/></d:report></d:supported-report>",
+    "<d:supported-report><d:report><card:addressbook-query/></d:report></d:supported-report>",
+    "<d:supported-report><d:report><d:sync-collection/></d:report></d:supported-report>"
+);
+
+fn card_prop(
+    prop: &PropName,
+    card: &HashedValue<Object<Value>>,
+    version: VCardVersion,
+) -> Option<PropValue> {
+    resource_prop(prop, &etag(card.hash), || {
+        VCard::build(&card.inner, version)
+    })
+}
+
+fn resource_prop(prop: &PropName, etag: &str, vcard: impl FnOnce() -> String) -> Option<PropValue> {
+    if prop.is(NS_DAV, "resourcetype") {
+        Some(PropValue::Empty)
+    } else if prop.is(NS_DAV, "getetag") {
+        Some(PropValue::Text(etag.to_string()))
+    } else if prop.is(NS_DAV, "getcontenttype") {
+        Some(PropValue::Text("text/vcard; charset=utf-8".to_string()))
+    } else if prop.is(NS_CARDDAV, "address-data") {
+        Some(PropValue::Text(vcard()))
+    } else {
+        None
+    }
+}
+
+fn add_directory_cards<'x>(
+    response: &mut MultiStatus,
+    href: &str,
+    cards: impl Iterator<Item = &'x DirectoryCard>,
+    names: &[PropName],
+    query: Option<&XmlElement>,
+    version: VCardVersion,
+) {
+    let mut limit = query.and_then(query_limit).unwrap_or(usize::MAX);
+    for card in cards {
+        let vcard = card.build(version);
+        if let Some(query) = query {
+            if limit == 0 {
+                break;
+            } else if !query_matches(query, &vcard) {
+                continue;
+            }
+            limit -= 1;
+        }
+        let (found, not_found) = dav_prop_values(names.to_vec(), CARD_PROPS, &mut |prop| {
+            resource_prop(prop, &card.etag, || vcard.clone())
+        });
+        response.add_response(&format!("{href}{}.vcf", card.id), found, not_found);
+    }
+}
+
+impl DirectoryCard {
+    fn build(&self, version: VCardVersion) -> String {
+        if version == VCardVersion::V3 {
+            self.vcard.clone()
+        } else {
+            VCard::build(&self.card, version)
+        }
+    }
+}
+
+fn directory_card<'x>(cards: &'x [DirectoryCard], resource: &str) -> Option<&'x DirectoryCard> {
+    let name = resource.strip_suffix(".vcf").unwrap_or(resource);
+    cards.iter().find(|card| card.id.to_string() == name)
+}
+
+fn directory_sync_token(cards: &[DirectoryCard]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for card in cards {
+        card.etag.hash(&mut hasher);
+    }
+    format!("{SYNC_TOKEN_PREFIX}{}", hasher.finish())
+}
+
+// Returns the vCard version requested in the address-data element
+fn address_data_version(request: &XmlElement) -> VCardVersion {
+    if request
+        .child(NS_DAV, "prop")
+        .and_then(|prop| prop.child(NS_CARDDAV, "address-data"))
+        .and_then(|data| data.attribute("version"))
+        .is_some_and(|version| version.trim() == "4.0")
+    {
+        VCardVersion::V4
+    } else {
+        VCardVersion::V3
+    }
+}
+
+fn query_limit(request: &XmlElement) -> Option<usize> {
+    request
+        .child(NS_CARDDAV, "limit")?
+        .child(NS_CARDDAV, "nresults")?
+        .text
+        .trim()
+        .parse()
+        .ok()
+}
+
+// Evaluates an addressbook-query filter against the content lines of a vCard
+fn query_matches(request: &XmlElement, vcard: &str) -> bool {
+    let filter = if let Some(filter) = request.child(NS_CARDDAV, "filter") {
+        filter
+    } else {
+        return true;
+    };
+    let lines = unfold(vcard)
+        .iter()
+        .filter_map(|line| parse_line(line))
+        .collect::<Vec<_>>();
+    let is_all_of = filter.attribute("test") == Some("allof");
+    let mut prop_filters = filter.children(NS_CARDDAV, "prop-filter").peekable();
+    if prop_filters.peek().is_none() {
+        return true;
+    }
+
+    let mut matches = prop_filters.map(|prop_filter| {
+        let name = prop_filter.attribute("name").unwrap_or_default();
+        let props = lines
+            .iter()
+            .filter(|line| line.name.eq_ignore_ascii_case(name))
+            .collect::<Vec<_>>();
+        if prop_filter.child(NS_CARDDAV, "is-not-defined").is_some() {
+            return props.is_empty();
+        }
+        let is_all_of = prop_filter.attribute("test") == Some("allof");
+        let mut tests = prop_filter
+            .children
+            .iter()
+            .filter(|test| test.is(NS_CARDDAV, "text-match") || test.is(NS_CARDDAV, "param-filter"))
+            .map(|test| {
+                props.iter().any(|line| {
+                    if test.is(NS_CARDDAV, "text-match") {
+                        text_matches(test, &line.text())
+                    } else {
+                        let param = line.param(
+                            &test
+                                .attribute("name")
+                                .unwrap_or_default()
+                                .to_ascii_uppercase(),
+                        );
+                        if test.child(NS_CARDDAV, "is-not-defined").is_some() {
+                            param.is_none()
+                        } else if let Some(text_match) = test.child(NS_CARDDAV, "text-match") {
+                            param.is_some_and(|param| text_matches(text_match, param))
+                        } else {
+                            param.is_some()
+                        }
+                    }
+                })
+            })
+            .peekable();
+        if tests.peek().is_none() {
+            !props.is_empty()
+        } else if is_all_of {
+            tests.all(|result| result)
+        } else {
+            tests.any(|result| result)
+        }
+    });
+
+    if is_all_of {
+        matches.all(|result| result)
+    } else {
+        matches.any(|result| result)
+    }
+}
+
+fn text_matches(text_match: &XmlElement, value: &str) -> bool {
+    let needle = text_match.text.trim().to_lowercase();
+    let value = value.to_lowercase();
+    let result = match text_match.attribute("match-type").unwrap_or("contains") {
+        "equals" => value == needle,
+        "starts-with" => value.starts_with(&needle),
+        "ends-with" => value.ends_with(&needle),
+        _ => value.contains(&needle),
+    };
+    result != (text_match.attribute("negate-condition") == Some("yes"))
+}
+
+// Maps the properties of a PROPPATCH or extended MKCOL request to address book changes
+fn address_book_changes(request: &XmlElement) -> (Object<SetValue>, Vec<PropName>, Vec<PropName>) {
+    let mut changes = Object {
+        properties: VecMap::new(),
+    };
+    let mut applied = Vec::new();
+    let mut unsupported = Vec::new();
+
+    for (is_set, props) in request.children.iter().filter_map(|action| {
+        if action.is(NS_DAV, "set") {
+            Some((true, action.child(NS_DAV, "prop")?))
+        } else if action.is(NS_DAV, "remove") {
+            Some((false, action.child(NS_DAV, "prop")?))
+        } else {
+            None
+        }
+    }) {
+        for prop in &props.children {
+            let name = PropName::new(&prop.ns, &prop.name);
+            let text = prop.text.trim();
+            let change = if name.is(NS_DAV, "displayname") && is_set {
+                Some((Property::Name, Value::Text(text.to_string())))
+            } else if name.is(NS_CARDDAV, "addressbook-description") {
+                Some((
+                    Property::Description,
+                    if is_set {
+                        Value::Text(text.to_string())
+                    } else {
+                        Value::Null
+                    },
+                ))
+            } else if name.is(NS_DAV, "resourcetype") {
+                applied.push(name);
+                continue;
+            } else {
+                None
+            };
+
+            if let Some((property, value)) = change {
+                changes.properties.set(property, SetValue::Value(value));
+                applied.push(name);
+            } else {
+                unsupported.push(name);
+            }
+        }
+    }
+
+    (changes, applied, unsupported)
+}
+
+fn into_empty_props(names: Vec<PropName>) -> Vec<(PropName, PropValue)> {
+    names
+        .into_iter()
+        .map(|name| (name, PropValue::Empty))
+        .collect()
+}
+
+fn address_book_document_id(book: &Object<Value>) -> u32 {
+    book.get(&Property::Id)
+        .as_id()
+        .map(|id| id.document_id())
+        .unwrap_or_default()
+}
+
+fn home_href(name: &str) -> String {
+    format!("/dav/card/{}/", encode_segment(name))
+}
+
+fn address_book_href(name: &str, book: &Object<Value>) -> String {
+    format!(
+        "{}{}/",
+        home_href(name),
+        Id::from(address_book_document_id(book))
+    )
+}
+
+fn directory_href(name: &str) -> String {
+    format!("{}{DIRECTORY_SEGMENT}/", home_href(name))
+}
+
+fn card_href(address_book_href: &str, document_id: u32) -> String {
+    format!("{address_book_href}{}.vcf", Id::from(document_id))
+}
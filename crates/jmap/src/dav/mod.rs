@@ -0,0 +1,510 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::QueryBy;
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Bytes, header, Method, StatusCode};
+use jmap_proto::{
+    error::method::MethodError,
+    types::{collection::Collection, state::State},
+};
+
+use crate::{
+    api::{
+        http::{fetch_body, HttpSessionData, ToHttpResponse},
+        HttpRequest, HttpResponse,
+    },
+    auth::AccessToken,
+    JMAP,
+};
+
+use self::xml::{MultiStatus, PropName, PropValue, XmlElement, NS_CALDAV, NS_CARDDAV, NS_DAV};
+
+pub mod calendar;
+pub mod card;
+pub mod xml;
+
+pub const SYNC_TOKEN_PREFIX: &str = "http://stalw.art/ns/sync/";
+
+pub struct DavRequest {
+    pub method: Method,
+    pub path: Vec<String>,
+    pub depth: Depth,
+    pub body: Vec<u8>,
+    pub if_match: Option<String>,
+    pub if_none_match: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    Zero,
+    One,
+}
+
+pub struct DavResponse {
+    status: StatusCode,
+    headers: Vec<(header::HeaderName, String)>,
+    content_type: Option<&'static str>,
+    body: Vec<u8>,
+}
+
+pub type DavResult = Result<DavResponse, MethodError>;
+
+impl JMAP {
+    pub async fn handle_dav_request(
+        &self,
+        mut req: HttpRequest,
+        session: &HttpSessionData,
+    ) -> HttpResponse {
+        if req.method() == Method::OPTIONS {
+            return DavResponse::new(StatusCode::OK)
+                .with_header(header::HeaderName::from_static("dav"), DAV_CAPABILITIES)
+                .with_header(header::ALLOW, DAV_METHODS)
+                .into_http_response();
+        }
+
+        // Authenticate request
+        let access_token = match self.authenticate_headers(&req, session.remote_ip).await {
+            Ok(Some((_, access_token))) => access_token,
+            Ok(None) => {
+                return DavResponse::new(StatusCode::UNAUTHORIZED)
+                    .with_header(
+                        header::WWW_AUTHENTICATE,
+                        "Basic realm=\"Stalwart Mail Server\"",
+                    )
+                    .into_http_response();
+            }
+            Err(err) => return err.into_http_response(),
+        };
+
+        // Parse request
+        let path = req
+            .uri()
+            .path()
+            .split('/')
+            .skip(2)
+            .filter(|segment| !segment.is_empty())
+            .map(percent_decode)
+            .collect::<Vec<_>>();
+        let depth = match req
+            .headers()
+            .get("Depth")
+            .and_then(|value| value.to_str().ok())
+        {
+            Some("0") => Depth::Zero,
+            _ => Depth::One,
+        };
+        let if_match = header_value(&req, header::IF_MATCH);
+        let if_none_match = header_value(&req, header::IF_NONE_MATCH);
+        let body = if let Some(body) = fetch_body(
+            &mut req,
+            if !access_token.is_super_user() {
+                self.core.jmap.upload_max_size
+            } else {
+                0
+            },
+        )
+        .await
+        {
+            body
+        } else {
+            return DavResponse::new(StatusCode::PAYLOAD_TOO_LARGE).into_http_response();
+        };
+        let request = DavRequest {
+            method: req.method().clone(),
+            path,
+            depth,
+            body,
+            if_match,
+            if_none_match,
+        };
+
+        let result = match request.path.first().map(|s| s.as_str()) {
+            None => self.handle_dav_root(&request, &access_token).await,
+            Some("principal") => self.handle_dav_principal(&request, &access_token).await,
+            Some("cal") => self.handle_caldav_request(&request, &access_token).await,
+            Some("card") => self.handle_carddav_request(&request, &access_token).await,
+            _ => Ok(DavResponse::new(StatusCode::NOT_FOUND)),
+        };
+
+        match result {
+            Ok(response) => response,
+            Err(MethodError::Forbidden(_)) => DavResponse::new(StatusCode::FORBIDDEN),
+            Err(err) => {
+                tracing::debug!(
+                    context = "dav",
+                    event = "error",
+                    account_id = access_token.primary_id(),
+                    error = ?err,
+                    "DAV request failed."
+                );
+                DavResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+        .into_http_response()
+    }
+
+    async fn handle_dav_root(&self, req: &DavRequest, access_token: &AccessToken) -> DavResult {
+        if req.method.as_str() != "PROPFIND" {
+            return Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let href = format!("/dav/{}", req.path.join("/"))
+            .trim_end_matches('/')
+            .to_string()
+            + "/";
+        let mut response = MultiStatus::new();
+        let (found, not_found) = dav_props(&req.body, ROOT_PROPS, |name| {
+            if name.is(NS_DAV, "resourcetype") {
+                Some(PropValue::Xml("<d:collection/>".to_string()))
+            } else if name.is(NS_DAV, "current-user-principal") {
+                Some(PropValue::Href(vec![principal_href(&access_token.name)]))
+            } else if name.is(NS_DAV, "principal-collection-set") {
+                Some(PropValue::Href(vec!["/dav/principal/".to_string()]))
+            } else {
+                None
+            }
+        });
+        response.add_response(&href, found, not_found);
+        Ok(DavResponse::multi_status(response))
+    }
+
+    async fn handle_dav_principal(
+        &self,
+        req: &DavRequest,
+        access_token: &AccessToken,
+    ) -> DavResult {
+        if req.method.as_str() != "PROPFIND" {
+            return Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let name = match req.path.get(1) {
+            Some(name) => name,
+            None => return self.handle_dav_root(req, access_token).await,
+        };
+        let account_id = if let Some(account_id) = self
+            .dav_account_id(access_token, name, Collection::Calendar)
+            .await?
+        {
+            account_id
+        } else if let Some(account_id) = self
+            .dav_account_id(access_token, name, Collection::AddressBook)
+            .await?
+        {
+            account_id
+        } else {
+            return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+        };
+        let principal = self
+            .core
+            .storage
+            .directory
+            .query(QueryBy::Id(account_id), false)
+            .await
+            .map_err(|_| MethodError::ServerPartialFail)?
+            .unwrap_or_default();
+
+        let mut response = MultiStatus::new();
+        let (found, not_found) = dav_props(&req.body, PRINCIPAL_PROPS, |prop| {
+            if prop.is(NS_DAV, "resourcetype") {
+                Some(PropValue::Xml("<d:principal/>".to_string()))
+            } else if prop.is(NS_DAV, "displayname") {
+                Some(PropValue::Text(
+                    principal
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| name.to_string()),
+                ))
+            } else if prop.is(NS_DAV, "current-user-principal") {
+                Some(PropValue::Href(vec![principal_href(&access_token.name)]))
+            } else if prop.is(NS_DAV, "principal-URL") {
+                Some(PropValue::Href(vec![principal_href(name)]))
+            } else if prop.is(NS_DAV, "principal-collection-set") {
+                Some(PropValue::Href(vec!["/dav/principal/".to_string()]))
+            } else if prop.is(NS_CALDAV, "calendar-home-set") {
+                Some(PropValue::Href(vec![format!(
+                    "/dav/cal/{}/",
+                    encode_segment(name)
+                )]))
+            } else if prop.is(NS_CARDDAV, "addressbook-home-set") {
+                Some(PropValue::Href(vec![format!(
+                    "/dav/card/{}/",
+                    encode_segment(name)
+                )]))
+            } else if prop.is(NS_CALDAV, "calendar-user-address-set") {
+                Some(PropValue::Href(
+                    principal
+                        .emails
+                        .iter()
+                        .map(|email| format!("mailto:{email}"))
+                        .collect(),
+                ))
+            } else {
+                None
+            }
+        });
+        response.add_response(&principal_href(name), found, not_found);
+        Ok(DavResponse::multi_status(response))
+    }
+
+    // Resolves an account name, the collection has to be owned by or shared with the user
+    pub async fn dav_account_id(
+        &self,
+        access_token: &AccessToken,
+        name: &str,
+        collection: Collection,
+    ) -> Result<Option<u32>, MethodError> {
+        let account_id = if name == access_token.name {
+            access_token.primary_id()
+        } else if let Some(principal) = self
+            .core
+            .storage
+            .directory
+            .query(QueryBy::Name(name), false)
+            .await
+            .map_err(|_| MethodError::ServerPartialFail)?
+        {
+            principal.id
+        } else {
+            return Ok(None);
+        };
+
+        Ok(if access_token.has_access(account_id, collection) {
+            Some(account_id)
+        } else {
+            None
+        })
+    }
+
+    pub async fn dav_sync_token(
+        &self,
+        account_id: u32,
+        collection: impl Into<u8>,
+    ) -> Result<String, MethodError> {
+        Ok(format!(
+            "{SYNC_TOKEN_PREFIX}{}",
+            match self.get_state(account_id, collection).await? {
+                State::Exact(change_id) => change_id + 1,
+                _ => 0,
+            }
+        ))
+    }
+}
+
+impl DavRequest {
+    // Evaluates the If-Match and If-None-Match preconditions
+    pub fn preconditions_met(&self, etag: Option<&str>) -> bool {
+        if let Some(if_match) = &self.if_match {
+            match etag {
+                Some(etag) => {
+                    if if_match.trim() != "*"
+                        && !if_match.split(',').any(|value| value.trim() == etag)
+                    {
+                        return false;
+                    }
+                }
+                None => return false,
+            }
+        }
+        if let (Some(if_none_match), Some(etag)) = (&self.if_none_match, etag) {
+            if if_none_match.trim() == "*"
+                || if_none_match.split(',').any(|value| value.trim() == etag)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn xml(&self) -> Option<XmlElement> {
+        if !self.body.is_empty() {
+            XmlElement::parse(&self.body)
+        } else {
+            None
+        }
+    }
+}
+
+impl DavResponse {
+    pub fn new(status: StatusCode) -> Self {
+        DavResponse {
+            status,
+            headers: Vec::new(),
+            content_type: None,
+            body: Vec::new(),
+        }
+    }
+
+    pub fn multi_status(response: MultiStatus) -> Self {
+        DavResponse::new(StatusCode::MULTI_STATUS).with_body(
+            "application/xml; charset=utf-8",
+            response.build().into_bytes(),
+        )
+    }
+
+    pub fn with_header(mut self, name: header::HeaderName, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn with_body(mut self, content_type: &'static str, body: Vec<u8>) -> Self {
+        self.content_type = Some(content_type);
+        self.body = body;
+        self
+    }
+}
+
+impl ToHttpResponse for DavResponse {
+    fn into_http_response(self) -> HttpResponse {
+        let mut response = hyper::Response::builder().status(self.status);
+        if let Some(content_type) = self.content_type {
+            response = response.header(header::CONTENT_TYPE, content_type);
+        }
+        for (name, value) in self.headers {
+            response = response.header(name, value);
+        }
+        response
+            .body(
+                Full::new(Bytes::from(self.body))
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .unwrap()
+    }
+}
+
+const DAV_CAPABILITIES: &str =
+    "1, 3, access-control, calendar-access, calendar-auto-schedule, addressbook, extended-mkcol";
+const DAV_METHODS: &str =
+    "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, REPORT, MKCOL, MKCALENDAR";
+
+const ROOT_PROPS: &[(&str, &str)] = &[
+    (NS_DAV, "resourcetype"),
+    (NS_DAV, "current-user-principal"),
+    (NS_DAV, "principal-collection-set"),
+];
+
+const PRINCIPAL_PROPS: &[(&str, &str)] = &[
+    (NS_DAV, "resourcetype"),
+    (NS_DAV, "displayname"),
+    (NS_DAV, "current-user-principal"),
+    (NS_DAV, "principal-URL"),
+    (NS_CALDAV, "calendar-home-set"),
+    (NS_CALDAV, "calendar-user-address-set"),
+    (NS_CARDDAV, "addressbook-home-set"),
+];
+
+// Evaluates the properties requested by a PROPFIND or REPORT body, all
+// properties in the default list are returned when none are requested
+pub fn dav_props(
+    body: &[u8],
+    default_props: &[(&str, &str)],
+    mut value: impl FnMut(&PropName) -> Option<PropValue>,
+) -> (Vec<(PropName, PropValue)>, Vec<PropName>) {
+    let request = if !body.is_empty() {
+        XmlElement::parse(body)
+    } else {
+        None
+    };
+    let names = request
+        .as_ref()
+        .map(|request| request.prop_names())
+        .unwrap_or_default();
+    dav_prop_values(names, default_props, &mut value)
+}
+
+pub fn dav_prop_values(
+    names: Vec<PropName>,
+    default_props: &[(&str, &str)],
+    value: &mut impl FnMut(&PropName) -> Option<PropValue>,
+) -> (Vec<(PropName, PropValue)>, Vec<PropName>) {
+    let mut found = Vec::new();
+    let mut not_found = Vec::new();
+    if names.is_empty() {
+        for (ns, name) in default_props {
+            let name = PropName::new(ns, name);
+            if let Some(value) = value(&name) {
+                found.push((name, value));
+            }
+        }
+    } else {
+        for name in names {
+            if let Some(value) = value(&name) {
+                found.push((name, value));
+            } else {
+                not_found.push(name);
+            }
+        }
+    }
+    (found, not_found)
+}
+
+pub fn parse_sync_token(token: &str) -> Option<u64> {
+    token.trim().strip_prefix(SYNC_TOKEN_PREFIX)?.parse().ok()
+}
+
+pub fn principal_href(name: &str) -> String {
+    format!("/dav/principal/{}/", encode_segment(name))
+}
+
+pub fn etag(hash: u64) -> String {
+    format!("\"{hash:x}\"")
+}
+
+pub fn encode_segment(segment: &str) -> String {
+    let mut result = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~@+".contains(&byte) {
+            result.push(byte as char);
+        } else {
+            result.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    result
+}
+
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if bytes[pos] == b'%' {
+            if let Some(byte) = segment
+                .get(pos + 1..pos + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                result.push(byte);
+                pos += 3;
+                continue;
+            }
+        }
+        result.push(bytes[pos]);
+        pos += 1;
+    }
+    String::from_utf8(result).unwrap_or_else(|_| segment.to_string())
+}
+
+fn header_value(req: &HttpRequest, name: header::HeaderName) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
@@ -0,0 +1,314 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use quick_xml::{events::Event, name::ResolveResult, NsReader};
+
+pub const NS_DAV: &str = "DAV:";
+pub const NS_CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+pub const NS_CARDDAV: &str = "urn:ietf:params:xml:ns:carddav";
+pub const NS_CALENDARSERVER: &str = "http://calendarserver.org/ns/";
+pub const NS_APPLE_ICAL: &str = "http://apple.com/ns/ical/";
+
+#[derive(Debug, Default, Clone)]
+pub struct XmlElement {
+    pub ns: String,
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    pub fn parse(bytes: &[u8]) -> Option<XmlElement> {
+        let mut reader = NsReader::from_reader(bytes);
+        reader.trim_text(true);
+        let mut buf = Vec::new();
+        let mut stack: Vec<XmlElement> = Vec::new();
+
+        loop {
+            match reader.read_resolved_event_into(&mut buf).ok()? {
+                (ns, Event::Start(e)) => {
+                    stack.push(XmlElement::new(ns, e.local_name().as_ref(), &e));
+                }
+                (ns, Event::Empty(e)) => {
+                    let element = XmlElement::new(ns, e.local_name().as_ref(), &e);
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(element);
+                    } else {
+                        return Some(element);
+                    }
+                }
+                (_, Event::Text(text)) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push_str(&text.unescape().ok()?);
+                    }
+                }
+                (_, Event::CData(text)) => {
+                    if let Some(element) = stack.last_mut() {
+                        element
+                            .text
+                            .push_str(std::str::from_utf8(text.as_ref()).ok()?);
+                    }
+                }
+                (_, Event::End(_)) => {
+                    let element = stack.pop()?;
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(element);
+                    } else {
+                        return Some(element);
+                    }
+                }
+                (_, Event::Eof) => return None,
+                _ => (),
+            }
+            buf.clear();
+        }
+    }
+
+    fn new(ns: ResolveResult, name: &[u8], e: &quick_xml::events::BytesStart) -> Self {
+        XmlElement {
+            ns: match ns {
+                ResolveResult::Bound(ns) => String::from_utf8_lossy(ns.as_ref()).into_owned(),
+                _ => String::new(),
+            },
+            name: String::from_utf8_lossy(name).into_owned(),
+            attributes: e
+                .attributes()
+                .filter_map(|attr| {
+                    let attr = attr.ok()?;
+                    Some((
+                        String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned(),
+                        attr.unescape_value().ok()?.into_owned(),
+                    ))
+                })
+                .collect(),
+            text: String::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn is(&self, ns: &str, name: &str) -> bool {
+        self.ns == ns && self.name == name
+    }
+
+    pub fn child(&self, ns: &str, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.is(ns, name))
+    }
+
+    pub fn children<'x>(
+        &'x self,
+        ns: &'x str,
+        name: &'x str,
+    ) -> impl Iterator<Item = &'x XmlElement> + 'x {
+        self.children.iter().filter(move |child| child.is(ns, name))
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attr, _)| attr == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Returns the properties listed under a DAV:prop element
+    pub fn prop_names(&self) -> Vec<PropName> {
+        self.child(NS_DAV, "prop")
+            .map(|prop| {
+                prop.children
+                    .iter()
+                    .map(|child| PropName::new(&child.ns, &child.name))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropName {
+    pub ns: String,
+    pub name: String,
+}
+
+impl PropName {
+    pub fn new(ns: &str, name: &str) -> Self {
+        PropName {
+            ns: ns.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn is(&self, ns: &str, name: &str) -> bool {
+        self.ns == ns && self.name == name
+    }
+
+    fn tag(&self) -> String {
+        match self.ns.as_str() {
+            NS_DAV => format!("d:{}", self.name),
+            NS_CALDAV => format!("c:{}", self.name),
+            NS_CARDDAV => format!("card:{}", self.name),
+            NS_CALENDARSERVER => format!("cs:{}", self.name),
+            NS_APPLE_ICAL => format!("a:{}", self.name),
+            _ => format!("x:{}", self.name),
+        }
+    }
+
+    fn open_tag(&self) -> String {
+        match self.ns.as_str() {
+            NS_DAV | NS_CALDAV | NS_CARDDAV | NS_CALENDARSERVER | NS_APPLE_ICAL => {
+                format!("<{}>", self.tag())
+            }
+            ns => format!("<x:{} xmlns:x=\"{}\">", self.name, escape(ns)),
+        }
+    }
+}
+
+pub enum PropValue {
+    Empty,
+    Text(String),
+    Xml(String),
+    Href(Vec<String>),
+}
+
+#[derive(Default)]
+pub struct MultiStatus {
+    xml: String,
+    sync_token: Option<String>,
+}
+
+impl MultiStatus {
+    pub fn new() -> Self {
+        MultiStatus::default()
+    }
+
+    pub fn with_sync_token(mut self, sync_token: String) -> Self {
+        self.sync_token = Some(sync_token);
+        self
+    }
+
+    pub fn add_response(
+        &mut self,
+        href: &str,
+        found: Vec<(PropName, PropValue)>,
+        not_found: Vec<PropName>,
+    ) {
+        let mut propstats = Vec::with_capacity(2);
+        if !found.is_empty() || not_found.is_empty() {
+            propstats.push(("200 OK", found));
+        }
+        if !not_found.is_empty() {
+            propstats.push((
+                "404 Not Found",
+                not_found
+                    .into_iter()
+                    .map(|name| (name, PropValue::Empty))
+                    .collect(),
+            ));
+        }
+        self.add_propstats(href, propstats);
+    }
+
+    pub fn add_propstats(
+        &mut self,
+        href: &str,
+        propstats: Vec<(&str, Vec<(PropName, PropValue)>)>,
+    ) {
+        self.xml.push_str("<d:response><d:href>");
+        self.xml.push_str(&escape(href));
+        self.xml.push_str("</d:href>");
+        for (status, props) in propstats {
+            self.xml.push_str("<d:propstat><d:prop>");
+            for (name, value) in props {
+                write_prop(&mut self.xml, &name, value);
+            }
+            self.xml.push_str("</d:prop><d:status>HTTP/1.1 ");
+            self.xml.push_str(status);
+            self.xml.push_str("</d:status></d:propstat>");
+        }
+        self.xml.push_str("</d:response>");
+    }
+
+    pub fn add_status(&mut self, href: &str, status: &str) {
+        self.xml.push_str("<d:response><d:href>");
+        self.xml.push_str(&escape(href));
+        self.xml.push_str("</d:href><d:status>HTTP/1.1 ");
+        self.xml.push_str(status);
+        self.xml.push_str("</d:status></d:response>");
+    }
+
+    pub fn build(self) -> String {
+        let mut xml = String::with_capacity(self.xml.len() + 512);
+        xml.push_str(concat!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>",
+            "<d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\" ",
+            "xmlns:card=\"urn:ietf:params:xml:ns:carddav\" ",
+            "xmlns:cs=\"http://calendarserver.org/ns/\" ",
+            "xmlns:a=\"http://apple.com/ns/ical/\">"
+        ));
+        xml.push_str(&self.xml);
+        if let Some(sync_token) = self.sync_token {
+            xml.push_str("<d:sync-token>");
+            xml.push_str(&escape(&sync_token));
+            xml.push_str("</d:sync-token>");
+        }
+        xml.push_str("</d:multistatus>");
+        xml
+    }
+}
+
+fn write_prop(xml: &mut String, name: &PropName, value: PropValue) {
+    xml.push_str(&name.open_tag());
+    match value {
+        PropValue::Empty => (),
+        PropValue::Text(text) => xml.push_str(&escape(&text)),
+        PropValue::Xml(value) => xml.push_str(&value),
+        PropValue::Href(hrefs) => {
+            for href in hrefs {
+                xml.push_str("<d:href>");
+                xml.push_str(&escape(&href));
+                xml.push_str("</d:href>");
+            }
+        }
+    }
+    xml.push_str(&format!("</{}>", tag_close(name)));
+}
+
+fn tag_close(name: &PropName) -> String {
+    match name.ns.as_str() {
+        NS_DAV | NS_CALDAV | NS_CARDDAV | NS_CALENDARSERVER | NS_APPLE_ICAL => name.tag(),
+        _ => format!("x:{}", name.name),
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '&' => result.push_str("&amp;"),
+            '"' => result.push_str("&quot;"),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
@@ -50,8 +50,8 @@ use mail_parser::{parsers::fields::thread::thread_name, HeaderName, HeaderValue}
 use store::{
     write::{
         log::{Changes, LogInsert},
-        BatchBuilder, Bincode, FtsQueueClass, MaybeDynamicId, TagValue, ValueClass, F_BITMAP,
-        F_VALUE,
+        now, BatchBuilder, Bincode, FtsQueueClass, MaybeDynamicId, TagValue, ValueClass, F_BITMAP,
+        F_INDEX, F_VALUE,
     },
     BlobClass, Serialize,
 };
@@ -417,6 +417,7 @@ impl JMAP {
             .value(Property::MailboxIds, mailbox_ids, F_VALUE | F_BITMAP)
             .value(Property::Keywords, keywords, F_VALUE | F_BITMAP)
             .value(Property::Cid, change_id, F_VALUE)
+            .value(Property::SaveDate, now(), F_VALUE | F_INDEX)
             .set(
                 ValueClass::FtsQueue(FtsQueueClass {
                     seq: self.generate_snowflake_id()?,
@@ -35,7 +35,7 @@ use store::{
     roaring::RoaringBitmap,
     write::{
         log::ChangeLogBuilder, BatchBuilder, Bincode, BitmapClass, MaybeDynamicId, TagValue,
-        ValueClass, F_BITMAP, F_CLEAR, F_VALUE,
+        ValueClass, F_BITMAP, F_CLEAR, F_INDEX, F_VALUE,
     },
     BitmapKey, IterateParams, ValueKey, U32_LEN,
 };
@@ -497,6 +497,22 @@ impl JMAP {
                 );
             }
 
+            // Remove save date
+            if let Some(save_date) = self
+                .core
+                .storage
+                .data
+                .get_value::<u64>(ValueKey {
+                    account_id,
+                    collection: Collection::Email.into(),
+                    document_id,
+                    class: ValueClass::Property(Property::SaveDate.into()),
+                })
+                .await?
+            {
+                batch.value(Property::SaveDate, save_date, F_VALUE | F_INDEX | F_CLEAR);
+            }
+
             // Remove message metadata
             if let Some(metadata) = self
                 .core
@@ -141,6 +141,8 @@ impl JMAP {
                     keywords: email.keywords,
                     received_at: email.received_at.map(|r| r.into()),
                     source: IngestSource::Jmap,
+                    envelope_from: None,
+                    sender_verified: false,
                     encrypt: self.core.jmap.encrypt && self.core.jmap.encrypt_append,
                 })
                 .await
@@ -35,8 +35,8 @@ use store::{
     backend::MAX_TOKEN_LENGTH,
     fts::{index::FtsDocument, Field},
     write::{
-        BatchBuilder, Bincode, BlobOp, DirectoryClass, IntoOperations, F_BITMAP, F_CLEAR, F_INDEX,
-        F_VALUE,
+        now, BatchBuilder, Bincode, BlobOp, DirectoryClass, IntoOperations, F_BITMAP, F_CLEAR,
+        F_INDEX, F_VALUE,
     },
 };
 use utils::BlobHash;
@@ -100,6 +100,9 @@ impl IndexMessage for BatchBuilder {
         // Index receivedAt
         self.value(Property::ReceivedAt, received_at, F_INDEX);
 
+        // Index saveDate
+        self.value(Property::SaveDate, now(), F_VALUE | F_INDEX);
+
         let mut has_attachments = false;
         let mut preview = None;
         let preview_part_id = message
@@ -49,6 +49,7 @@ use store::{
 use utils::map::vec_map::VecMap;
 
 use crate::{
+    calendar_event::scheduling::imip_part,
     email::index::{IndexMessage, VisitValues, MAX_ID_LENGTH},
     mailbox::{UidMailbox, INBOX_ID, JUNK_ID},
     services::housekeeper::Event,
@@ -78,6 +79,8 @@ pub struct IngestEmail<'x> {
     pub keywords: Vec<Keyword>,
     pub received_at: Option<u64>,
     pub source: IngestSource,
+    pub envelope_from: Option<&'x str>,
+    pub sender_verified: bool,
     pub encrypt: bool,
 }
 
@@ -128,6 +131,14 @@ impl JMAP {
             }
         }
 
+        // Classify the message using the recipient's own Bayes model, if enabled
+        if params.source == IngestSource::Smtp
+            && params.mailbox_ids == [INBOX_ID]
+            && self.spam_classify(params.account_id, &message).await
+        {
+            params.mailbox_ids[0] = JUNK_ID;
+        }
+
         // Obtain message references and thread name
         let thread_id = {
             let mut references = Vec::with_capacity(5);
@@ -215,6 +226,32 @@ impl JMAP {
             }
         };
 
+        // Apply iMIP scheduling messages to the recipient's calendars, as long
+        // as the From address was authenticated and matches the envelope sender
+        if params.source == IngestSource::Smtp
+            && params.sender_verified
+            && params.mailbox_ids != [JUNK_ID]
+            && self.core.jmap.calendars_imip
+        {
+            if let Some((sender, ical)) = imip_part(&message).filter(|(sender, _)| {
+                params
+                    .envelope_from
+                    .is_some_and(|envelope_from| envelope_from.eq_ignore_ascii_case(sender))
+            }) {
+                if let Err(err) = self
+                    .calendar_imip_ingest(params.account_id, sender, ical)
+                    .await
+                {
+                    tracing::warn!(
+                        context = "email_ingest",
+                        event = "error",
+                        account_id = params.account_id,
+                        error = ?err,
+                        "Failed to process iMIP message.");
+                }
+            }
+        }
+
         // Encrypt message
         if params.encrypt && !message.is_encrypted() {
             if let Some(encrypt_params) = self
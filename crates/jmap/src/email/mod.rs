@@ -36,3 +36,5 @@ pub mod parse;
 pub mod query;
 pub mod set;
 pub mod snippet;
+pub mod spam;
+pub mod urlauth;
@@ -64,6 +64,7 @@ use crate::{auth::AccessToken, mailbox::UidMailbox, IngestError, JMAP};
 use super::{
     headers::{BuildHeader, ValueToHeader},
     ingest::{IngestEmail, IngestSource},
+    spam::SpamTrainAction,
 };
 
 impl JMAP {
@@ -738,6 +739,8 @@ impl JMAP {
                     keywords,
                     received_at,
                     source: IngestSource::Jmap,
+                    envelope_from: None,
+                    sender_verified: false,
                     encrypt: self.core.jmap.encrypt && self.core.jmap.encrypt_append,
                 })
                 .await
@@ -758,6 +761,7 @@ impl JMAP {
 
         // Process updates
         let mut changes = ChangeLogBuilder::new();
+        let mut train_ids = Vec::new();
         'update: for (id, object) in request.unwrap_update() {
             // Make sure id won't be destroyed
             if will_destroy.contains(&id) {
@@ -859,6 +863,7 @@ impl JMAP {
             // Log change
             batch.update_document(document_id);
             let mut changed_mailboxes = AHashSet::new();
+            let mut train_action = None;
             changes.log_update(Collection::Email, id);
 
             // Process keywords
@@ -884,6 +889,7 @@ impl JMAP {
                 }
 
                 // Update keywords property
+                train_action = SpamTrainAction::from_keywords(keywords.added(), keywords.removed());
                 keywords.update_batch(&mut batch, Property::Keywords);
 
                 // Update last change id
@@ -972,6 +978,14 @@ impl JMAP {
                     }
                 }
 
+                // Moving messages into or out of Junk takes precedence over keywords
+                train_action = SpamTrainAction::from_mailbox_ids(
+                    mailboxes.added().iter().map(|m| &m.mailbox_id),
+                    mailboxes.removed().iter().map(|m| &m.mailbox_id),
+                    mailboxes.current().iter().map(|m| &m.mailbox_id),
+                )
+                .or(train_action);
+
                 // Update mailboxIds property
                 mailboxes.update_batch(&mut batch, Property::MailboxIds);
             }
@@ -987,6 +1001,9 @@ impl JMAP {
                     Ok(_) => {
                         // Add to updated list
                         response.updated.append(id, None);
+                        if let Some(train_action) = train_action {
+                            train_ids.push((document_id, train_action));
+                        }
                     }
                     Err(store::Error::AssertValueFailed) => {
                         response.not_updated.append(
@@ -1089,6 +1106,11 @@ impl JMAP {
             response.new_state = new_state.into();
         }
 
+        // Train the spam classifier on messages moved into or out of Junk
+        for (document_id, train_action) in train_ids {
+            self.spam_train(account_id, document_id, train_action).await;
+        }
+
         Ok(response)
     }
 }
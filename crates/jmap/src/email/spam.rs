@@ -0,0 +1,299 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use common::{
+    config::jmap::settings::SpamTraining,
+    scripts::plugins::bayes::{bayes_classify, bayes_train},
+};
+use jmap_proto::types::{collection::Collection, keyword::Keyword, property::Property};
+use mail_parser::{parsers::fields::thread::thread_name, Message};
+use nlp::bayes::BayesClassifier;
+use store::{write::Bincode, LookupStore};
+
+use crate::{
+    mailbox::{JUNK_ID, TRASH_ID},
+    JMAP,
+};
+
+use super::{crypto::EncryptMessage, metadata::MessageMetadata};
+
+// Same threshold used by the spam filter's Bayes classification rule
+const SPAM_PROBABILITY: f64 = 0.7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpamTrainAction {
+    Spam,
+    Ham,
+    UndoSpam,
+    UndoHam,
+}
+
+impl SpamTrainAction {
+    pub fn from_mailboxes(was_junk: bool, is_junk: bool, is_trash: bool) -> Option<Self> {
+        match (was_junk, is_junk) {
+            (false, true) => Some(SpamTrainAction::Spam),
+            (true, false) if !is_trash => Some(SpamTrainAction::Ham),
+            _ => None,
+        }
+    }
+
+    pub fn from_mailbox_ids<'x>(
+        added: impl IntoIterator<Item = &'x u32>,
+        removed: impl IntoIterator<Item = &'x u32>,
+        current: impl IntoIterator<Item = &'x u32>,
+    ) -> Option<Self> {
+        let is_junk_added = added.into_iter().any(|id| *id == JUNK_ID);
+        let is_junk_removed = removed.into_iter().any(|id| *id == JUNK_ID);
+        let mut is_trash = false;
+        let mut is_junk = false;
+        for id in current {
+            is_trash |= *id == TRASH_ID;
+            is_junk |= *id == JUNK_ID;
+        }
+
+        Self::from_mailboxes(
+            is_junk_removed || (is_junk && !is_junk_added),
+            is_junk,
+            is_trash,
+        )
+    }
+
+    pub fn from_keywords<'x>(
+        added: impl IntoIterator<Item = &'x Keyword>,
+        removed: impl IntoIterator<Item = &'x Keyword>,
+    ) -> Option<Self> {
+        let mut is_junk_added = false;
+        let mut is_not_junk_added = false;
+        for keyword in added {
+            is_junk_added |= *keyword == Keyword::Junk;
+            is_not_junk_added |= *keyword == Keyword::NotJunk;
+        }
+
+        match (is_junk_added, is_not_junk_added) {
+            (true, false) => Some(SpamTrainAction::Spam),
+            (false, true) => Some(SpamTrainAction::Ham),
+            (true, true) => None,
+            (false, false) => removed.into_iter().find_map(|keyword| match keyword {
+                Keyword::Junk => Some(SpamTrainAction::UndoSpam),
+                Keyword::NotJunk => Some(SpamTrainAction::UndoHam),
+                _ => None,
+            }),
+        }
+    }
+}
+
+impl JMAP {
+    pub async fn spam_train(&self, account_id: u32, document_id: u32, action: SpamTrainAction) {
+        let (config, store) = if let Some(result) = self.spam_train_store() {
+            result
+        } else {
+            return;
+        };
+
+        // Obtain message metadata
+        let metadata = match self
+            .get_property::<Bincode<MessageMetadata>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::BodyStructure,
+            )
+            .await
+        {
+            Ok(Some(metadata)) => metadata.inner,
+            Ok(None) => return,
+            Err(_) => {
+                tracing::warn!(
+                    context = "spam_train",
+                    event = "error",
+                    account_id = account_id,
+                    document_id = document_id,
+                    "Failed to retrieve email metadata"
+                );
+                return;
+            }
+        };
+
+        // Obtain how this message was previously trained, if at all
+        let mut key = format!("spam-train:{account_id}:").into_bytes();
+        key.extend_from_slice(metadata.blob_hash.as_slice());
+        let trained = match store.key_get::<String>(key.clone()).await {
+            Ok(trained) => trained.map(|trained| trained == "spam"),
+            Err(err) => {
+                tracing::warn!(
+                    context = "spam_train",
+                    event = "error",
+                    account_id = account_id,
+                    document_id = document_id,
+                    reason = ?err,
+                    "Failed to obtain training history"
+                );
+                return;
+            }
+        };
+
+        // Never train the same message twice in the same direction
+        let (untrain, train) = match (action, trained) {
+            (SpamTrainAction::Spam, Some(true)) | (SpamTrainAction::Ham, Some(false)) => {
+                return;
+            }
+            (SpamTrainAction::Spam, trained) => (trained, Some(true)),
+            (SpamTrainAction::Ham, trained) => (trained, Some(false)),
+            (SpamTrainAction::UndoSpam, Some(true)) => (Some(true), None),
+            (SpamTrainAction::UndoHam, Some(false)) => (Some(false), None),
+            (SpamTrainAction::UndoSpam | SpamTrainAction::UndoHam, _) => return,
+        };
+
+        // Obtain message text
+        let raw_message = match self.get_blob(&metadata.blob_hash, 0..usize::MAX).await {
+            Ok(Some(raw_message)) => raw_message,
+            _ => {
+                tracing::warn!(
+                    context = "spam_train",
+                    event = "error",
+                    account_id = account_id,
+                    document_id = document_id,
+                    blob_hash = ?metadata.blob_hash,
+                    "Message blob not found"
+                );
+                return;
+            }
+        };
+        let message = metadata.contents.into_message(&raw_message);
+        if message.is_encrypted() {
+            return;
+        }
+        let text = bayes_text(&message);
+
+        // Update the per-user and global models
+        let cache = &self.smtp.inner.script_cache.bayes_cache;
+        let models = [
+            config.per_user.then_some(Some(account_id)),
+            config.global.then_some(None),
+        ];
+        for (is_spam, is_train) in untrain
+            .map(|is_spam| (is_spam, false))
+            .into_iter()
+            .chain(train.map(|is_spam| (is_spam, true)))
+        {
+            for model in models.iter().flatten() {
+                if let Err(err) =
+                    bayes_train(&self.core, store, cache, &text, is_spam, is_train, *model).await
+                {
+                    tracing::warn!(
+                        context = "spam_train",
+                        event = "error",
+                        account_id = account_id,
+                        document_id = document_id,
+                        reason = ?err,
+                        "Failed to train Bayes model"
+                    );
+                    return;
+                }
+            }
+        }
+
+        // Update training history
+        let result = if let Some(is_spam) = train {
+            store
+                .key_set(
+                    key,
+                    if is_spam { "spam" } else { "ham" }.as_bytes().to_vec(),
+                    config.history.as_secs().into(),
+                )
+                .await
+        } else {
+            store.key_delete(key).await
+        };
+        if let Err(err) = result {
+            tracing::warn!(
+                context = "spam_train",
+                event = "error",
+                account_id = account_id,
+                document_id = document_id,
+                reason = ?err,
+                "Failed to update training history"
+            );
+        }
+
+        tracing::debug!(
+            context = "spam_train",
+            event = "train",
+            account_id = account_id,
+            document_id = document_id,
+            action = ?action,
+            "Trained Bayes classifier"
+        );
+    }
+
+    pub async fn spam_classify(&self, account_id: u32, message: &Message<'_>) -> bool {
+        match self.spam_train_store() {
+            Some((config, store))
+                if config.per_user && config.classify && !message.is_encrypted() =>
+            {
+                bayes_classify(
+                    &self.core,
+                    store,
+                    &self.smtp.inner.script_cache.bayes_cache,
+                    &tracing::Span::current(),
+                    &bayes_text(message),
+                    &BayesClassifier::default(),
+                    account_id.into(),
+                )
+                .await
+                .is_some_and(|probability| probability > SPAM_PROBABILITY)
+            }
+            _ => false,
+        }
+    }
+
+    fn spam_train_store(&self) -> Option<(&SpamTraining, &LookupStore)> {
+        let config = self.core.jmap.spam_train.as_ref()?;
+        if let Some(lookup) = &config.lookup {
+            if let Some(store) = self.core.storage.lookups.get(lookup) {
+                Some((config, store))
+            } else {
+                tracing::warn!(
+                    context = "spam_train",
+                    event = "error",
+                    lookup_store = lookup,
+                    "Unknown lookup store"
+                );
+                None
+            }
+        } else {
+            Some((config, &self.core.storage.lookup))
+        }
+    }
+}
+
+fn bayes_text(message: &Message<'_>) -> String {
+    let mut text = thread_name(message.subject().unwrap_or_default()).to_string();
+    for pos in 0..message.text_body.len() {
+        if let Some(body) = message.body_text(pos) {
+            text.push(' ');
+            text.push_str(body.as_ref());
+        }
+    }
+    text
+}
@@ -0,0 +1,520 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::backend::internal::manage::ManageDirectory;
+use hmac::{Hmac, Mac};
+use jmap_proto::{
+    error::method::MethodError,
+    object::Object,
+    types::{collection::Collection, property::Property, value::Value},
+};
+use mail_parser::{DateTime, MessageParser, PartType};
+use sha2::Sha256;
+use store::{
+    rand::{distributions::Alphanumeric, thread_rng, Rng},
+    write::{assert::AssertValue, now, BatchBuilder, Bincode, F_CLEAR, F_VALUE},
+};
+
+use crate::{
+    mailbox::{UidMailbox, INBOX_ID},
+    JMAP,
+};
+
+use super::metadata::MessageMetadata;
+
+const URLAUTH_KEY_LEN: usize = 40;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImapUrl {
+    pub user: Option<String>,
+    pub mailbox: String,
+    pub uid_validity: Option<u32>,
+    pub uid: u32,
+    pub section: Option<String>,
+    pub partial: Option<(usize, Option<usize>)>,
+    pub expire: Option<i64>,
+    pub access: Option<UrlAccess>,
+    pub rump: Option<String>,
+    pub verifier: Option<UrlVerifier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlAccess {
+    Submit(String),
+    User(String),
+    AuthUser,
+    Anonymous,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlVerifier {
+    pub mechanism: String,
+    pub token: String,
+}
+
+impl ImapUrl {
+    pub fn parse(url: &str) -> Option<Self> {
+        let mut result = ImapUrl::default();
+
+        // Parse server, only the user name is relevant
+        let path = if url
+            .get(..7)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("imap://"))
+        {
+            let (server, path) = url[7..].split_once('/')?;
+            if let Some(user) = server
+                .rsplit_once('@')
+                .and_then(|(user_info, _)| user_info.split(';').next())
+                .filter(|user| !user.is_empty())
+            {
+                result.user = Some(percent_decode(user)?);
+            }
+            path
+        } else {
+            url.strip_prefix('/')?
+        };
+
+        // Parse mailbox
+        let mut segments = path.split("/;");
+        let mut mailbox = segments.next()?.split(';');
+        result.mailbox = percent_decode(mailbox.next()?)?;
+        if result.mailbox.is_empty() {
+            return None;
+        }
+        for param in mailbox {
+            let (key, value) = param.split_once('=')?;
+            if key.eq_ignore_ascii_case("UIDVALIDITY") {
+                result.uid_validity = Some(value.parse().ok().filter(|v| *v > 0)?);
+            } else {
+                return None;
+            }
+        }
+
+        // Parse message reference and authorization
+        for param in segments.flat_map(|segment| segment.split(';')) {
+            let (key, value) = param.split_once('=').filter(|_| result.access.is_none())?;
+            match key.to_ascii_uppercase().as_str() {
+                "UID" => {
+                    result.uid = value.parse().ok()?;
+                }
+                "SECTION" => {
+                    result.section = Some(percent_decode(value)?);
+                }
+                "PARTIAL" => {
+                    result.partial = Some(if let Some((offset, length)) = value.split_once('.') {
+                        (offset.parse().ok()?, Some(length.parse().ok()?))
+                    } else {
+                        (value.parse().ok()?, None)
+                    });
+                }
+                "EXPIRE" => {
+                    result.expire =
+                        Some(DateTime::parse_rfc3339(&percent_decode(value)?)?.to_timestamp());
+                }
+                "URLAUTH" => {
+                    let access = if let Some((access, verifier)) = value.split_once(':') {
+                        let (mechanism, token) = verifier.split_once(':')?;
+                        result.verifier = Some(UrlVerifier {
+                            mechanism: mechanism.to_string(),
+                            token: token.to_string(),
+                        });
+                        access
+                    } else {
+                        value
+                    };
+                    result.access = Some(UrlAccess::parse(access)?);
+                    result.rump = Some(url[..url.len() - value.len() + access.len()].to_string());
+                }
+                _ => return None,
+            }
+        }
+
+        if result.uid != 0 {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expire.is_some_and(|expire| expire < now() as i64)
+    }
+}
+
+impl UrlAccess {
+    pub fn parse(value: &str) -> Option<Self> {
+        if let Some((class, user)) = value.split_once('+') {
+            let user = percent_decode(user)?;
+            if class.eq_ignore_ascii_case("submit") {
+                Some(UrlAccess::Submit(user))
+            } else if class.eq_ignore_ascii_case("user") {
+                Some(UrlAccess::User(user))
+            } else {
+                None
+            }
+        } else if value.eq_ignore_ascii_case("authuser") {
+            Some(UrlAccess::AuthUser)
+        } else if value.eq_ignore_ascii_case("anonymous") {
+            Some(UrlAccess::Anonymous)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_allowed(&self, requester: &str, is_submit: bool) -> bool {
+        match self {
+            UrlAccess::Submit(user) => is_submit && user.eq_ignore_ascii_case(requester),
+            UrlAccess::User(user) => !is_submit && user.eq_ignore_ascii_case(requester),
+            UrlAccess::AuthUser => !requester.is_empty(),
+            UrlAccess::Anonymous => true,
+        }
+    }
+}
+
+impl JMAP {
+    pub async fn urlauth_key(
+        &self,
+        account_id: u32,
+        mailbox_id: u32,
+        create: bool,
+    ) -> Result<Option<String>, MethodError> {
+        if let Some(key) = self
+            .get_property::<String>(
+                account_id,
+                Collection::Mailbox,
+                mailbox_id,
+                Property::UrlAuthKey,
+            )
+            .await?
+        {
+            Ok(Some(key))
+        } else if create {
+            let key = thread_rng()
+                .sample_iter(Alphanumeric)
+                .take(URLAUTH_KEY_LEN)
+                .map(char::from)
+                .collect::<String>();
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Mailbox)
+                .update_document(mailbox_id)
+                .assert_value(Property::UrlAuthKey, AssertValue::None)
+                .value(Property::UrlAuthKey, key.as_str(), F_VALUE);
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => Ok(Some(key)),
+                Err(store::Error::AssertValueFailed) => {
+                    // Another session created the key concurrently
+                    self.get_property::<String>(
+                        account_id,
+                        Collection::Mailbox,
+                        mailbox_id,
+                        Property::UrlAuthKey,
+                    )
+                    .await
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "urlauth",
+                        account_id = account_id,
+                        mailbox_id = mailbox_id,
+                        error = ?err,
+                        "Failed to create URLAUTH key."
+                    );
+                    Err(MethodError::ServerPartialFail)
+                }
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn urlauth_reset_keys(
+        &self,
+        account_id: u32,
+        mailbox_ids: impl IntoIterator<Item = u32>,
+    ) -> Result<(), MethodError> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Mailbox);
+        for mailbox_id in mailbox_ids {
+            batch
+                .update_document(mailbox_id)
+                .value(Property::UrlAuthKey, (), F_VALUE | F_CLEAR);
+        }
+        if !batch.is_empty() {
+            self.write_batch(batch).await?;
+        }
+        Ok(())
+    }
+
+    pub fn urlauth_token(key: &str, rump: &str) -> String {
+        format!("{:x}", urlauth_mac(key, rump).finalize().into_bytes())
+    }
+
+    pub fn urlauth_verify(key: &str, rump: &str, token: &str) -> bool {
+        hex_decode(token).is_some_and(|token| urlauth_mac(key, rump).verify_slice(&token).is_ok())
+    }
+
+    pub async fn urlauth_fetch(
+        &self,
+        url: &str,
+        requester: &str,
+        is_submit: bool,
+    ) -> Result<Option<Vec<u8>>, MethodError> {
+        // Validate URL authorization
+        let url = match ImapUrl::parse(url) {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let (user, access, rump, verifier) =
+            match (&url.user, &url.access, &url.rump, &url.verifier) {
+                (Some(user), Some(access), Some(rump), Some(verifier))
+                    if verifier.mechanism.eq_ignore_ascii_case("INTERNAL")
+                        && access.is_allowed(requester, is_submit)
+                        && !url.is_expired() =>
+                {
+                    (user, access, rump, verifier)
+                }
+                _ => return Ok(None),
+            };
+
+        // Obtain the mailbox the URL refers to
+        let account_id = match self
+            .core
+            .storage
+            .data
+            .get_account_id(user)
+            .await
+            .map_err(|_| MethodError::ServerPartialFail)?
+        {
+            Some(account_id) => account_id,
+            None => return Ok(None),
+        };
+        let mailbox_id = if url.mailbox.eq_ignore_ascii_case("INBOX") {
+            INBOX_ID
+        } else if let Some(mailbox_id) = self.mailbox_get_by_name(account_id, &url.mailbox).await? {
+            mailbox_id
+        } else {
+            return Ok(None);
+        };
+
+        // Verify token
+        match self.urlauth_key(account_id, mailbox_id, false).await? {
+            Some(key) if JMAP::urlauth_verify(&key, rump, &verifier.token) => {
+                tracing::debug!(
+                    context = "urlauth",
+                    event = "fetch",
+                    account_id = account_id,
+                    mailbox_id = mailbox_id,
+                    uid = url.uid,
+                    access = ?access,
+                    "Fetching authorized IMAP URL."
+                );
+                self.imap_url_contents(account_id, mailbox_id, &url).await
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub async fn imap_url_contents(
+        &self,
+        account_id: u32,
+        mailbox_id: u32,
+        url: &ImapUrl,
+    ) -> Result<Option<Vec<u8>>, MethodError> {
+        // Validate UIDVALIDITY
+        if let Some(uid_validity) = url.uid_validity {
+            if self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::Mailbox,
+                    mailbox_id,
+                    &Property::Value,
+                )
+                .await?
+                .and_then(|obj| obj.get(&Property::Cid).as_uint())
+                != Some(uid_validity as u64)
+            {
+                return Ok(None);
+            }
+        }
+
+        // Find the message with the requested UID
+        let message_ids = self
+            .get_tag(
+                account_id,
+                Collection::Email,
+                Property::MailboxIds,
+                mailbox_id,
+            )
+            .await?
+            .unwrap_or_default();
+        let document_id = match self
+            .get_properties::<Vec<UidMailbox>, _, _>(
+                account_id,
+                Collection::Email,
+                &message_ids,
+                Property::MailboxIds,
+            )
+            .await?
+            .into_iter()
+            .find_map(|(document_id, mailboxes)| {
+                mailboxes
+                    .iter()
+                    .any(|m| m.mailbox_id == mailbox_id && m.uid == url.uid)
+                    .then_some(document_id)
+            }) {
+            Some(document_id) => document_id,
+            None => return Ok(None),
+        };
+
+        // Fetch raw message
+        let raw_message = if let Some(metadata) = self
+            .get_property::<Bincode<MessageMetadata>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::BodyStructure,
+            )
+            .await?
+        {
+            match self
+                .get_blob(&metadata.inner.blob_hash, 0..usize::MAX)
+                .await?
+            {
+                Some(raw_message) => raw_message,
+                None => return Ok(None),
+            }
+        } else {
+            return Ok(None);
+        };
+
+        // Extract section
+        let contents = match &url.section {
+            Some(section) if !section.is_empty() => {
+                match extract_section(&raw_message, &section.split('.').collect::<Vec<_>>()) {
+                    Some(contents) => contents,
+                    None => return Ok(None),
+                }
+            }
+            _ => raw_message,
+        };
+
+        // Apply partial range
+        Ok(Some(if let Some((offset, length)) = url.partial {
+            contents
+                .into_iter()
+                .skip(offset)
+                .take(length.unwrap_or(usize::MAX))
+                .collect()
+        } else {
+            contents
+        }))
+    }
+}
+
+fn extract_section(raw_message: &[u8], path: &[&str]) -> Option<Vec<u8>> {
+    let message = MessageParser::new().parse(raw_message)?;
+    let mut part_id = 0;
+    let mut is_root = true;
+
+    for (pos, item) in path.iter().enumerate() {
+        let part = message.parts.get(part_id)?;
+        if let Ok(num) = item.parse::<usize>() {
+            match &part.body {
+                PartType::Multipart(parts) => {
+                    part_id = *parts.get(num.checked_sub(1)?)?;
+                }
+                PartType::Message(_) if !is_root => {
+                    return extract_section(
+                        raw_message.get(part.offset_body..part.offset_end)?,
+                        &path[pos..],
+                    );
+                }
+                _ if is_root && num == 1 => {}
+                _ => return None,
+            }
+            is_root = false;
+        } else if pos == path.len() - 1 {
+            let (start, end) = match item.to_ascii_uppercase().as_str() {
+                "HEADER" if is_root => (part.offset_header, part.offset_body),
+                "TEXT" if is_root => (part.offset_body, part.offset_end),
+                "MIME" if !is_root => (part.offset_header, part.offset_body),
+                "HEADER" | "TEXT" if matches!(part.body, PartType::Message(_)) => {
+                    return extract_section(
+                        raw_message.get(part.offset_body..part.offset_end)?,
+                        &path[pos..],
+                    );
+                }
+                _ => return None,
+            };
+            return raw_message.get(start..end).map(|bytes| bytes.to_vec());
+        } else {
+            return None;
+        }
+    }
+
+    let part = message.parts.get(part_id)?;
+    if is_root {
+        Some(raw_message.to_vec())
+    } else {
+        raw_message
+            .get(part.offset_body..part.offset_end)
+            .map(|bytes| bytes.to_vec())
+    }
+}
+
+fn urlauth_mac(key: &str, rump: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(rump.as_bytes());
+    mac
+}
+
+fn hex_decode(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) || !value.bytes().all(|ch| ch.is_ascii_hexdigit()) {
+        return None;
+    }
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Some(ch) = iter.next() {
+        if ch == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(ch);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
@@ -71,10 +71,15 @@ use utils::{
     snowflake::SnowflakeIdGenerator,
 };
 
+pub mod address_book;
 pub mod api;
 pub mod auth;
 pub mod blob;
+pub mod calendar;
+pub mod calendar_event;
 pub mod changes;
+pub mod contact;
+pub mod dav;
 pub mod email;
 pub mod identity;
 pub mod mailbox;
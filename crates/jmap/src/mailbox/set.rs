@@ -501,6 +501,8 @@ impl JMAP {
                 .with_collection(Collection::Mailbox)
                 .delete_document(document_id)
                 .value(Property::EmailIds, (), F_VALUE | F_CLEAR)
+                .value(Property::Annotations, (), F_VALUE | F_CLEAR)
+                .value(Property::UrlAuthKey, (), F_VALUE | F_CLEAR)
                 .custom(ObjectIndexBuilder::new(SCHEMA).with_current(mailbox));
 
             match self.core.storage.data.write(batch.build()).await {
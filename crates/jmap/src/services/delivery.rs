@@ -35,6 +35,20 @@ pub fn spawn_delivery_manager(core: JmapInstance, mut delivery_rx: mpsc::Receive
                         .send(JMAP::from(core.clone()).deliver_message(message).await)
                         .ok();
                 }
+                DeliveryEvent::FetchUrl {
+                    url,
+                    requester,
+                    result_tx,
+                } => {
+                    result_tx
+                        .send(
+                            JMAP::from(core.clone())
+                                .urlauth_fetch(&url, &requester, true)
+                                .await
+                                .unwrap_or_default(),
+                        )
+                        .ok();
+                }
                 DeliveryEvent::Stop => break,
             }
         }
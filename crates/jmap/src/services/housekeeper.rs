@@ -66,6 +66,7 @@ enum ActionClass {
     Account,
     Store(usize),
     Acme(String),
+    QuarantineDigest,
 }
 
 #[derive(Default)]
@@ -103,6 +104,12 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                 ActionClass::Store(idx),
             );
         }
+        if let Some(digest) = &core_.smtp.queue.quarantine.digest {
+            queue.schedule(
+                Instant::now() + digest.schedule.time_to_next(),
+                ActionClass::QuarantineDigest,
+            );
+        }
 
         // Add all ACME renewals to heap
         for provider in core_.tls.acme_providers.values() {
@@ -306,6 +313,19 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                                     ActionClass::Session,
                                 );
                             }
+                            ActionClass::QuarantineDigest => {
+                                if let Some(digest) = &core_.smtp.queue.quarantine.digest {
+                                    let jmap = JMAP::from(core.clone());
+                                    tokio::spawn(async move {
+                                        tracing::debug!("Sending quarantine digests.");
+                                        jmap.smtp.send_quarantine_digest().await;
+                                    });
+                                    queue.schedule(
+                                        Instant::now() + digest.schedule.time_to_next(),
+                                        ActionClass::QuarantineDigest,
+                                    );
+                                }
+                            }
                             ActionClass::Store(idx) => {
                                 if let Some(schedule) =
                                     core_.storage.purge_schedules.get(idx).cloned()
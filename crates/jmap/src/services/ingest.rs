@@ -95,6 +95,7 @@ impl JMAP {
                     self.sieve_script_ingest(
                         &raw_message,
                         &message.sender_address,
+                        message.sender_verified,
                         rcpt,
                         *uid,
                         active_script,
@@ -128,6 +129,8 @@ impl JMAP {
                         keywords: vec![],
                         received_at: None,
                         source: IngestSource::Smtp,
+                        envelope_from: Some(&message.sender_address),
+                        sender_verified: message.sender_verified,
                         encrypt: self.core.jmap.encrypt,
                     })
                     .await
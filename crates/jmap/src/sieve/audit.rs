@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::borrow::Cow;
+
+use common::config::jmap::settings::SieveEditPolicy;
+use mail_parser::MessageParser;
+
+pub const TRACE_HEADER: &str = SieveEditPolicy::TRACE_HEADER;
+
+pub struct HeaderEdits {
+    pub trace: Vec<String>,
+    pub raw_message: Option<Vec<u8>>,
+}
+
+// Compares a message rebuilt by the Sieve runtime against the original and
+// reverts any header changes not allowed by the edit policy. Returns `None`
+// when the message was not derived from the original (i.e. vacation or
+// notification messages, or MIME changes).
+pub fn header_edits(
+    original: &[u8],
+    modified: &[u8],
+    policy: &SieveEditPolicy,
+    script_name: &str,
+) -> Option<HeaderEdits> {
+    let original = MessageParser::new().parse(original)?;
+    let modified_ = MessageParser::new().parse(modified)?;
+    let original_part = original.parts.first()?;
+    let modified_part = modified_.parts.first()?;
+    if original.raw_message.get(original_part.offset_body..)?
+        != modified.get(modified_part.offset_body..)?
+    {
+        return None;
+    }
+
+    let mut original_headers = original_part
+        .headers
+        .iter()
+        .map(|header| {
+            (
+                header.name.as_str(),
+                &original.raw_message[header.offset_field..header.offset_end],
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut headers = Vec::with_capacity(modified_part.headers.len());
+    let mut trace = Vec::new();
+    let mut has_denied = false;
+
+    for header in &modified_part.headers {
+        let line = &modified[header.offset_field..header.offset_end];
+        if let Some(pos) = original_headers.iter().position(|(_, l)| *l == line) {
+            original_headers.remove(pos);
+            headers.push(line);
+        } else if policy.can_add_header(header.name.as_str()) {
+            trace.push(trace_line(
+                "addheader",
+                header.name.as_str(),
+                script_name,
+                true,
+            ));
+            headers.push(line);
+        } else {
+            trace.push(trace_line(
+                "addheader",
+                header.name.as_str(),
+                script_name,
+                false,
+            ));
+            has_denied = true;
+        }
+    }
+
+    for (name, line) in original_headers {
+        if policy.can_delete_header(name) {
+            trace.push(trace_line("deleteheader", name, script_name, true));
+        } else {
+            trace.push(trace_line("deleteheader", name, script_name, false));
+            headers.push(line);
+            has_denied = true;
+        }
+    }
+
+    // Rebuild the message if any change had to be reverted
+    let raw_message = if has_denied {
+        let headers_end = modified_part
+            .headers
+            .last()
+            .map_or(0, |header| header.offset_end);
+        let mut raw_message = Vec::with_capacity(modified.len());
+        for line in headers {
+            raw_message.extend_from_slice(line);
+        }
+        raw_message.extend_from_slice(&modified[headers_end..]);
+        Some(raw_message)
+    } else {
+        None
+    };
+
+    Some(HeaderEdits { trace, raw_message })
+}
+
+pub fn trace_line(action: &str, target: &str, script_name: &str, allowed: bool) -> String {
+    format!(
+        "{TRACE_HEADER}: {action} {target:?}; script={script_name:?}; result={}\r\n",
+        if allowed { "applied" } else { "denied" }
+    )
+}
+
+// Removes any trace headers already present in the message, so that the
+// only trace lines delivered are the ones added by the server.
+pub fn strip_trace_headers(raw_message: &[u8]) -> Cow<'_, [u8]> {
+    let Some(message) = MessageParser::new().parse_headers(raw_message) else {
+        return Cow::Borrowed(raw_message);
+    };
+    let mut stripped = Vec::new();
+    let mut last_offset = 0;
+    for header in message.headers() {
+        if header.name.as_str().eq_ignore_ascii_case(TRACE_HEADER) {
+            stripped.extend_from_slice(&raw_message[last_offset..header.offset_field]);
+            last_offset = header.offset_end;
+        }
+    }
+
+    if last_offset > 0 {
+        stripped.extend_from_slice(&raw_message[last_offset..]);
+        Cow::Owned(stripped)
+    } else {
+        Cow::Borrowed(raw_message)
+    }
+}
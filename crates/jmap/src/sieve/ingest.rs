@@ -23,14 +23,17 @@
 
 use std::borrow::Cow;
 
-use common::listener::stream::NullIo;
+use common::{
+    listener::stream::NullIo,
+    webhooks::{WebhookNotifyImportance, WebhookPayload, WebhookType},
+};
 use directory::QueryBy;
 use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
-use mail_parser::MessageParser;
-use sieve::{Envelope, Event, Input, Mailbox, Recipient};
+use mail_parser::{HeaderName, MessageParser};
+use sieve::{Envelope, Event, Importance, Input, Mailbox, Recipient};
 use smtp::core::{Session, SessionAddress};
 use store::{
-    ahash::AHashSet,
+    ahash::{AHashMap, AHashSet},
     write::{now, BatchBuilder, Bincode, F_VALUE},
 };
 
@@ -41,12 +44,15 @@ use crate::{
     IngestError, JMAP,
 };
 
-use super::ActiveScript;
+use super::{audit, ActiveScript};
 
 struct SieveMessage<'x> {
     pub raw_message: Cow<'x, [u8]>,
     pub file_into: Vec<u32>,
     pub flags: Vec<Keyword>,
+    pub is_notification: bool,
+    pub is_derived: bool,
+    pub trace: Vec<String>,
 }
 
 impl JMAP {
@@ -55,10 +61,16 @@ impl JMAP {
         &self,
         raw_message: &[u8],
         envelope_from: &str,
+        sender_verified: bool,
         envelope_to: &str,
         account_id: u32,
         mut active_script: ActiveScript,
     ) -> Result<IngestedEmail, IngestError> {
+        // Remove trace headers supplied by the sender, so that any trace lines
+        // in the delivered message were added by the server
+        let raw_message = audit::strip_trace_headers(raw_message);
+        let raw_message = raw_message.as_ref();
+
         // Parse message
         let message = if let Some(message) = MessageParser::new().parse(raw_message) {
             message
@@ -79,7 +91,7 @@ impl JMAP {
         let mut instance = self.core.sieve.untrusted_runtime.filter_parsed(message);
 
         // Set account name and obtain quota
-        let (account_quota, mail_from) = match self
+        let (account_quota, account_emails) = match self
             .core
             .storage
             .directory
@@ -88,33 +100,48 @@ impl JMAP {
         {
             Ok(Some(p)) => {
                 instance.set_user_full_name(p.description().unwrap_or_else(|| p.name()));
-                (p.quota as i64, p.emails.into_iter().next())
+                (p.quota as i64, p.emails)
             }
-            Ok(None) => (0, None),
+            Ok(None) => (0, Vec::new()),
             Err(_) => {
                 return Err(IngestError::Temporary);
             }
         };
 
         // Set account address
-        let mail_from = mail_from.unwrap_or_else(|| envelope_to.to_string());
-        instance.set_user_address(&mail_from);
+        let mut mail_from = account_emails
+            .first()
+            .cloned()
+            .unwrap_or_else(|| envelope_to.to_string());
+        instance.set_user_address(mail_from.clone());
 
         // Set envelope
-        instance.set_envelope(Envelope::From, envelope_from);
-        instance.set_envelope(Envelope::To, envelope_to);
+        let mut envelope = [
+            (Envelope::From, envelope_from.to_string()),
+            (Envelope::To, envelope_to.to_string()),
+        ];
+        for (name, value) in &envelope {
+            instance.set_envelope(*name, value.clone());
+        }
 
+        let edit_policy = &self.core.jmap.sieve_edit_policy;
+        let script_name = active_script.script_name.clone();
+        let mut envelope_trace = Vec::new();
         let mut input = Input::script(active_script.script_name, active_script.script.clone());
 
         let mut do_discard = false;
         let mut do_deliver = false;
 
         let mut new_ids = AHashSet::new();
+        let mut lists_cache = AHashMap::new();
         let mut reject_reason = None;
         let mut messages: Vec<SieveMessage> = vec![SieveMessage {
             raw_message: raw_message.into(),
             file_into: Vec::new(),
             flags: Vec::new(),
+            is_notification: false,
+            is_derived: true,
+            trace: Vec::new(),
         }];
         let now = now();
         let mut ingested_message = IngestedEmail {
@@ -333,7 +360,13 @@ impl JMAP {
                     } => {
                         input = true.into();
                         if let Some(message) = messages.get(message_id) {
-                            if message.raw_message.len() <= self.core.jmap.mail_max_size {
+                            if message.is_notification
+                                && !self
+                                    .is_sieve_notify_allowed(account_id, envelope_from)
+                                    .await
+                            {
+                                continue;
+                            } else if message.raw_message.len() <= self.core.jmap.mail_max_size {
                                 let result = Session::<NullIo>::sieve(
                                     self.smtp.clone(),
                                     SessionAddress::new(mail_from.clone()),
@@ -376,18 +409,127 @@ impl JMAP {
                             continue;
                         }
                     }
-                    Event::ListContains { .. }
-                    | Event::Function { .. }
-                    | Event::Notify { .. }
-                    | Event::SetEnvelope { .. } => {
+                    Event::Notify {
+                        from,
+                        importance,
+                        options,
+                        message,
+                        method,
+                    } => {
+                        // Methods other than mailto are delivered through webhooks
+                        let is_valid_method = method.split_once(':').is_some_and(|(scheme, _)| {
+                            self.core
+                                .sieve
+                                .notify_methods
+                                .iter()
+                                .any(|method| method.eq_ignore_ascii_case(scheme))
+                        });
+                        if is_valid_method
+                            && self.core.has_webhook_subscribers(WebhookType::SieveNotify)
+                            && self
+                                .is_sieve_notify_allowed(account_id, envelope_from)
+                                .await
+                        {
+                            self.smtp
+                                .inner
+                                .ipc
+                                .send_webhook(
+                                    WebhookType::SieveNotify,
+                                    WebhookPayload::SieveNotify {
+                                        account_id,
+                                        method,
+                                        from,
+                                        importance: match importance {
+                                            Importance::High => WebhookNotifyImportance::High,
+                                            Importance::Normal => WebhookNotifyImportance::Normal,
+                                            Importance::Low => WebhookNotifyImportance::Low,
+                                        },
+                                        options,
+                                        message,
+                                    },
+                                )
+                                .await;
+                            input = true.into();
+                        } else {
+                            tracing::debug!(
+                                context = "sieve_script_ingest",
+                                event = "notify",
+                                account_id = account_id,
+                                method = method,
+                                "Notification not sent."
+                            );
+                            input = false.into();
+                        }
+                    }
+                    Event::ListContains {
+                        lists,
+                        values,
+                        match_as,
+                    } => {
+                        input = self
+                            .sieve_list_contains(
+                                account_id,
+                                lists,
+                                values,
+                                match_as,
+                                &mut lists_cache,
+                            )
+                            .await
+                            .into();
+                    }
+                    Event::SetEnvelope {
+                        envelope: name,
+                        value,
+                    } => {
+                        // Envelope addresses may only be changed to one of the account's addresses
+                        let is_allowed = edit_policy.envelope.contains(&name)
+                            && account_emails
+                                .iter()
+                                .any(|email| email.eq_ignore_ascii_case(&value));
+                        envelope_trace.push(audit::trace_line(
+                            "setenvelope",
+                            if name == Envelope::From { "from" } else { "to" },
+                            &script_name,
+                            is_allowed,
+                        ));
+                        if is_allowed {
+                            if name == Envelope::From {
+                                mail_from.clone_from(&value);
+                            }
+                            instance.clear_envelope();
+                            for (envelope_name, envelope_value) in &mut envelope {
+                                if *envelope_name == name {
+                                    envelope_value.clone_from(&value);
+                                }
+                                instance.set_envelope(*envelope_name, envelope_value.clone());
+                            }
+                        }
+                        input = is_allowed.into();
+                    }
+                    Event::Function { .. } => {
                         // Not allowed
                         input = false.into();
                     }
                     Event::CreatedMessage { message, .. } => {
+                        let is_notification = is_notification(&message);
+                        let (message, is_derived, trace) = match audit::header_edits(
+                            raw_message,
+                            &message,
+                            edit_policy,
+                            &script_name,
+                        ) {
+                            Some(edits) => {
+                                (edits.raw_message.unwrap_or(message), true, edits.trace)
+                            }
+                            None => (message, false, Vec::new()),
+                        };
                         messages.push(SieveMessage {
                             raw_message: message.into(),
                             file_into: Vec::new(),
                             flags: Vec::new(),
+                            is_notification,
+                            is_derived,
+                            trace,
                         });
                         input = true.into();
                     }
@@ -418,10 +560,33 @@ impl JMAP {
         // Deliver messages
         let mut last_temp_error = None;
         let mut has_delivered = false;
-        for (message_id, sieve_message) in messages.into_iter().enumerate() {
+        for (message_id, mut sieve_message) in messages.into_iter().enumerate() {
             if !sieve_message.file_into.is_empty() {
-                // Parse message if needed
-                let message = if message_id == 0 && !instance.has_message_changed() {
+                // Annotate modifications made by the script
+                let mut has_trace = false;
+                if sieve_message.is_derived
+                    && (!envelope_trace.is_empty() || !sieve_message.trace.is_empty())
+                {
+                    let mut raw_message = Vec::with_capacity(
+                        sieve_message.raw_message.len()
+                            + envelope_trace
+                                .iter()
+                                .chain(sieve_message.trace.iter())
+                                .map(|line| line.len())
+                                .sum::<usize>(),
+                    );
+                    for line in envelope_trace.iter().chain(sieve_message.trace.iter()) {
+                        raw_message.extend_from_slice(line.as_bytes());
+                    }
+                    raw_message.extend_from_slice(&sieve_message.raw_message);
+                    sieve_message.raw_message = raw_message.into();
+                    has_trace = true;
+                }
+
+                // Parse message if needed, the sender can only be trusted if the
+                // script did not modify the message
+                let is_original = message_id == 0 && !instance.has_message_changed();
+                let message = if is_original && !has_trace {
                     instance.take_message()
                 } else if let Some(message) =
                     MessageParser::new().parse(sieve_message.raw_message.as_ref())
@@ -447,6 +612,8 @@ impl JMAP {
                         keywords: sieve_message.flags,
                         received_at: None,
                         source: IngestSource::Smtp,
+                        envelope_from: Some(envelope_from),
+                        sender_verified: sender_verified && is_original,
                         encrypt: self.core.jmap.encrypt,
                     })
                     .await
@@ -491,6 +658,71 @@ impl JMAP {
             Err(last_temp_error.unwrap())
         }
     }
+
+    async fn is_sieve_notify_allowed(&self, account_id: u32, envelope_from: &str) -> bool {
+        // Do not notify on bounces to avoid mail loops
+        if envelope_from.is_empty()
+            || envelope_from
+                .split_once('@')
+                .map_or(envelope_from, |(local, _)| local)
+                .eq_ignore_ascii_case("mailer-daemon")
+        {
+            tracing::debug!(
+                context = "sieve_script_ingest",
+                event = "notify",
+                account_id = account_id,
+                "Notification suppressed for message with null or MAILER-DAEMON return path."
+            );
+            return false;
+        }
+
+        if let Some(rate) = &self.core.jmap.sieve_notify_rate {
+            match self
+                .core
+                .storage
+                .lookup
+                .is_rate_allowed(format!("sn:{account_id}").as_bytes(), rate, false)
+                .await
+            {
+                Ok(None) => true,
+                Ok(Some(_)) => {
+                    tracing::debug!(
+                        context = "sieve_script_ingest",
+                        event = "notify",
+                        account_id = account_id,
+                        "Notification rate limit exceeded."
+                    );
+                    false
+                }
+                Err(err) => {
+                    tracing::error!(
+                        context = "sieve_script_ingest",
+                        event = "error",
+                        account_id = account_id,
+                        reason = ?err,
+                        "Failed to check notification rate limit."
+                    );
+                    false
+                }
+            }
+        } else {
+            true
+        }
+    }
+}
+
+fn is_notification(raw_message: &[u8]) -> bool {
+    MessageParser::new()
+        .parse_headers(raw_message)
+        .is_some_and(|message| {
+            message.headers().iter().any(|header| {
+                matches!(&header.name, HeaderName::Other(name) if name.eq_ignore_ascii_case("Auto-Submitted"))
+                    && header
+                        .value
+                        .as_text()
+                        .is_some_and(|value| value.eq_ignore_ascii_case("auto-notified"))
+            })
+        })
 }
 
 #[inline(always)]
@@ -0,0 +1,227 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{QueryBy, Type};
+use jmap_proto::{
+    object::Object,
+    types::{collection::Collection, property::Property, value::Value},
+};
+use sieve::MatchAs;
+use store::ahash::{AHashMap, AHashSet};
+
+use crate::{contact::set::ContactCardAddressBooks, JMAP};
+
+impl JMAP {
+    pub(crate) async fn sieve_list_contains(
+        &self,
+        account_id: u32,
+        lists: Vec<String>,
+        values: Vec<String>,
+        match_as: MatchAs,
+        cache: &mut AHashMap<String, AHashSet<String>>,
+    ) -> bool {
+        for list in lists {
+            if let Some(address_book) = list.strip_prefix(":addrbook:") {
+                // Addresses from the user's address books
+                if !cache.contains_key(&list) {
+                    let addresses = self
+                        .sieve_address_book_addresses(account_id, address_book)
+                        .await;
+                    cache.insert(list.clone(), addresses);
+                }
+                let addresses = &cache[&list];
+                if values
+                    .iter()
+                    .any(|value| addresses.contains(&value.to_lowercase()))
+                {
+                    return true;
+                }
+            } else if let Some(group) = list.strip_prefix(":group:") {
+                // Members of a directory group
+                if let Some(group_id) = self.sieve_group_id(account_id, &list, group).await {
+                    for value in &values {
+                        if self.sieve_is_group_member(group_id, value).await {
+                            return true;
+                        }
+                    }
+                } else {
+                    tracing::debug!(
+                        context = "sieve_script_ingest",
+                        event = "list-not-allowed",
+                        account_id = account_id,
+                        list = list,
+                    );
+                }
+            } else if let Some(store) = self
+                .core
+                .jmap
+                .sieve_ext_lists
+                .contains(&list)
+                .then(|| self.core.storage.lookups.get(&list))
+                .flatten()
+            {
+                // Admin-defined lists
+                for value in &values {
+                    if let Ok(true) = store
+                        .key_exists(
+                            if !matches!(match_as, MatchAs::Lowercase) {
+                                value.clone()
+                            } else {
+                                value.to_lowercase()
+                            }
+                            .into_bytes(),
+                        )
+                        .await
+                    {
+                        return true;
+                    }
+                }
+            } else {
+                tracing::debug!(
+                    context = "sieve_script_ingest",
+                    event = "list-not-found",
+                    account_id = account_id,
+                    list = list,
+                );
+            }
+        }
+
+        false
+    }
+
+    async fn sieve_address_book_addresses(
+        &self,
+        account_id: u32,
+        address_book: &str,
+    ) -> AHashSet<String> {
+        let mut addresses = AHashSet::new();
+
+        // Obtain the address books to match against
+        let address_book_ids = if address_book != "personal" {
+            let ids = self
+                .get_document_ids(account_id, Collection::AddressBook)
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+            let address_books = self
+                .get_properties::<Object<Value>, _, _>(
+                    account_id,
+                    Collection::AddressBook,
+                    &ids,
+                    Property::Value,
+                )
+                .await
+                .unwrap_or_default();
+            let address_book_ids = address_books
+                .into_iter()
+                .filter(|(_, book)| {
+                    if address_book == "default" {
+                        matches!(book.get(&Property::IsDefault), Value::Bool(true))
+                    } else {
+                        book.get(&Property::Name)
+                            .as_string()
+                            .is_some_and(|name| name.eq_ignore_ascii_case(address_book))
+                    }
+                })
+                .map(|(document_id, _)| document_id)
+                .collect::<Vec<_>>();
+            if address_book_ids.is_empty() {
+                return addresses;
+            }
+            Some(address_book_ids)
+        } else {
+            None
+        };
+
+        // Collect email addresses from the matching cards
+        let card_ids = self
+            .get_document_ids(account_id, Collection::ContactCard)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        for (_, card) in self
+            .get_properties::<Object<Value>, _, _>(
+                account_id,
+                Collection::ContactCard,
+                &card_ids,
+                Property::Value,
+            )
+            .await
+            .unwrap_or_default()
+        {
+            if address_book_ids
+                .as_ref()
+                .is_none_or(|ids| card.address_book_ids().any(|id| ids.contains(&id)))
+            {
+                if let Value::Object(emails) = card.get(&Property::parse("emails")) {
+                    for email in emails.properties.values() {
+                        if let Some(address) = email.as_obj().and_then(|email| {
+                            email.get(&Property::_T("address".to_string())).as_string()
+                        }) {
+                            addresses.insert(address.trim().to_lowercase());
+                        }
+                    }
+                }
+            }
+        }
+
+        addresses
+    }
+
+    // Scripts can only look up the members of groups their owner belongs to,
+    // unless the group list is allowed by the administrator.
+    async fn sieve_group_id(&self, account_id: u32, list: &str, group: &str) -> Option<u32> {
+        let directory = &self.core.storage.directory;
+        let group_id = match directory.query(QueryBy::Name(group), false).await {
+            Ok(Some(principal)) if principal.typ == Type::Group => principal.id,
+            _ => return None,
+        };
+
+        if self.core.jmap.sieve_ext_lists.contains(list)
+            || matches!(
+                directory.query(QueryBy::Id(account_id), true).await,
+                Ok(Some(principal)) if principal.member_of.contains(&group_id)
+            )
+        {
+            Some(group_id)
+        } else {
+            None
+        }
+    }
+
+    async fn sieve_is_group_member(&self, group_id: u32, address: &str) -> bool {
+        let directory = &self.core.storage.directory;
+        for account_id in directory.email_to_ids(address).await.unwrap_or_default() {
+            if matches!(
+                directory.query(QueryBy::Id(account_id), true).await,
+                Ok(Some(principal)) if principal.member_of.contains(&group_id)
+            ) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
@@ -27,8 +27,10 @@ use serde::ser::SerializeSeq;
 use sieve::Sieve;
 use store::{ahash::AHashSet, blake3, write::now};
 
+pub mod audit;
 pub mod get;
 pub mod ingest;
+pub mod lists;
 pub mod query;
 pub mod set;
 pub mod validate;
@@ -26,7 +26,10 @@ pub mod session;
 
 use std::{borrow::Cow, net::IpAddr, sync::Arc};
 
-use common::listener::{limiter::InFlight, ServerInstance};
+use common::{
+    auth::scram::ScramServer,
+    listener::{limiter::InFlight, ServerInstance},
+};
 use imap::core::{ImapInstance, Inner};
 use imap_proto::receiver::{CommandParser, Receiver};
 use jmap::{auth::AccessToken, JMAP};
@@ -40,6 +43,7 @@ pub struct Session<T: AsyncRead + AsyncWrite> {
     pub state: State,
     pub remote_addr: IpAddr,
     pub stream: T,
+    pub scram: Option<ScramServer>,
     pub span: tracing::Span,
     pub in_flight: InFlight,
 }
@@ -48,6 +48,7 @@ impl SessionManager for ManageSieveSessionManager {
                 state: State::NotAuthenticated { auth_failures: 0 },
                 span: session.span,
                 stream: session.stream,
+                scram: None,
                 in_flight: session.in_flight,
                 remote_addr: session.remote_ip,
             };
@@ -146,6 +147,7 @@ impl<T: SessionStream> Session<T> {
         let span = self.span;
         Ok(Session {
             stream: self.instance.tls_accept(self.stream, &span).await?,
+            scram: None,
             state: self.state,
             instance: self.instance,
             in_flight: self.in_flight,
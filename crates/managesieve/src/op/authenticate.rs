@@ -21,17 +21,20 @@
  * for more details.
 */
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use common::{
+    auth::scram::{ScramResult, ScramServer},
     config::server::ServerProtocol,
     listener::{limiter::ConcurrencyLimiter, SessionStream},
     AuthResult,
 };
+use directory::core::secret::ScramAlgorithm;
 use imap::op::authenticate::{decode_challenge_oauth, decode_challenge_plain};
 use imap_proto::{
     protocol::authenticate::Mechanism,
     receiver::{self, Request},
 };
-use jmap::auth::rate_limit::ConcurrencyLimiters;
+use jmap::auth::{rate_limit::ConcurrencyLimiters, AccessToken};
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use std::sync::Arc;
@@ -72,6 +75,10 @@ impl<T: SessionStream> Session<T> {
                     return Ok(b"{0}\r\n".to_vec());
                 }
             }
+            Mechanism::ScramSha1
+            | Mechanism::ScramSha1Plus
+            | Mechanism::ScramSha256
+            | Mechanism::ScramSha256Plus => return self.handle_scram(mechanism, params).await,
             _ => {
                 return Err(StatusResponse::no(
                     "Authentication mechanism not supported.",
@@ -80,20 +87,7 @@ impl<T: SessionStream> Session<T> {
         };
 
         // Throttle authentication requests
-        if self
-            .jmap
-            .is_auth_allowed_soft(&self.remote_addr)
-            .await
-            .is_err()
-        {
-            tracing::debug!(parent: &self.span,
-                event = "disconnect",
-                "Too many authentication attempts, disconnecting.",
-            );
-            return Err(StatusResponse::bye(
-                "Too many authentication requests from this IP address.",
-            ));
-        }
+        self.is_auth_allowed().await?;
 
         // Authenticate
         let access_token = match credentials {
@@ -137,6 +131,108 @@ impl<T: SessionStream> Session<T> {
             }
         };
 
+        self.complete_authentication(access_token).await
+    }
+
+    async fn handle_scram(
+        &mut self,
+        mechanism: Mechanism,
+        mut params: Vec<String>,
+    ) -> crate::op::OpResult {
+        let mut server = if let Some(server) = self.scram.take() {
+            server
+        } else {
+            // Throttle authentication requests
+            self.is_auth_allowed().await?;
+
+            let (algorithm, is_plus) = match mechanism {
+                Mechanism::ScramSha1 => (ScramAlgorithm::Sha1, false),
+                Mechanism::ScramSha1Plus => (ScramAlgorithm::Sha1, true),
+                Mechanism::ScramSha256 => (ScramAlgorithm::Sha256, false),
+                _ => (ScramAlgorithm::Sha256, true),
+            };
+            let tls_exporter = self.stream.tls_exporter();
+            if is_plus && tls_exporter.is_none() {
+                return Err(StatusResponse::no("Channel binding is not available."));
+            }
+            let server = ScramServer::new(algorithm, is_plus, tls_exporter);
+
+            if params.is_empty() {
+                return Ok(self.scram_challenge(server, mechanism, b""));
+            }
+            server
+        };
+
+        let response = params.pop().unwrap_or_default();
+        let response = if response.is_empty() {
+            vec![]
+        } else {
+            base64_decode(response.as_bytes())
+                .ok_or_else(|| StatusResponse::no("Failed to decode challenge."))?
+        };
+
+        match self
+            .jmap
+            .authenticate_scram(
+                &mut server,
+                &response,
+                self.remote_addr,
+                ServerProtocol::ManageSieve,
+            )
+            .await
+        {
+            ScramResult::Challenge(challenge) => {
+                Ok(self.scram_challenge(server, mechanism, &challenge))
+            }
+            ScramResult::Done(AuthResult::Success(access_token)) => {
+                self.complete_authentication(Some(access_token)).await
+            }
+            ScramResult::Done(AuthResult::Failure) => self.complete_authentication(None).await,
+            ScramResult::Done(AuthResult::Banned) => Err(StatusResponse::bye(
+                "Too many authentication requests from this IP address.",
+            )),
+        }
+    }
+
+    fn scram_challenge(
+        &mut self,
+        server: ScramServer,
+        mechanism: Mechanism,
+        challenge: &[u8],
+    ) -> Vec<u8> {
+        self.scram = Some(server);
+        self.receiver.request = receiver::Request {
+            tag: String::new(),
+            command: Command::Authenticate,
+            tokens: vec![receiver::Token::Argument(mechanism.into_bytes())],
+        };
+        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
+        format!("\"{}\"\r\n", STANDARD.encode(challenge)).into_bytes()
+    }
+
+    async fn is_auth_allowed(&self) -> Result<(), StatusResponse> {
+        if self
+            .jmap
+            .is_auth_allowed_soft(&self.remote_addr)
+            .await
+            .is_err()
+        {
+            tracing::debug!(parent: &self.span,
+                event = "disconnect",
+                "Too many authentication attempts, disconnecting.",
+            );
+            Err(StatusResponse::bye(
+                "Too many authentication requests from this IP address.",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn complete_authentication(
+        &mut self,
+        access_token: Option<AccessToken>,
+    ) -> crate::op::OpResult {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = match self
@@ -35,10 +35,14 @@ impl<T: SessionStream> Session<T> {
             response.extend_from_slice(b"\"STARTTLS\"\r\n");
         }
         if self.stream.is_tls() || self.jmap.core.imap.allow_plain_auth {
-            response.extend_from_slice(b"\"SASL\" \"PLAIN OAUTHBEARER\"\r\n");
+            response.extend_from_slice(b"\"SASL\" \"PLAIN OAUTHBEARER SCRAM-SHA-256 SCRAM-SHA-1");
         } else {
-            response.extend_from_slice(b"\"SASL\" \"OAUTHBEARER\"\r\n");
+            response.extend_from_slice(b"\"SASL\" \"OAUTHBEARER SCRAM-SHA-256 SCRAM-SHA-1");
         };
+        if self.stream.tls_exporter().is_some() {
+            response.extend_from_slice(b" SCRAM-SHA-256-PLUS SCRAM-SHA-1-PLUS");
+        }
+        response.extend_from_slice(b"\"\r\n");
         if let Some(sieve) =
             self.jmap
                 .core
@@ -117,12 +117,17 @@ impl<T: SessionStream> Session<T> {
                             self.handle_rset().await?;
                         }
                         Command::Capa => {
-                            let mechanisms =
+                            let mut mechanisms =
                                 if self.stream.is_tls() || self.jmap.core.imap.allow_plain_auth {
                                     vec![Mechanism::Plain, Mechanism::OAuthBearer]
                                 } else {
                                     vec![Mechanism::OAuthBearer]
                                 };
+                            mechanisms.extend([Mechanism::ScramSha256, Mechanism::ScramSha1]);
+                            if self.stream.tls_exporter().is_some() {
+                                mechanisms
+                                    .extend([Mechanism::ScramSha256Plus, Mechanism::ScramSha1Plus]);
+                            }
 
                             self.write_bytes(
                                 Response::Capability::<u32> {
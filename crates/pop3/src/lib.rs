@@ -23,7 +23,10 @@
 
 use std::{net::IpAddr, sync::Arc};
 
-use common::listener::{limiter::InFlight, ServerInstance, SessionStream};
+use common::{
+    auth::scram::ScramServer,
+    listener::{limiter::InFlight, ServerInstance, SessionStream},
+};
 use imap::core::{ImapInstance, Inner};
 use jmap::JMAP;
 use mailbox::Mailbox;
@@ -55,6 +58,7 @@ pub struct Session<T: SessionStream> {
     pub receiver: Parser,
     pub state: State,
     pub stream: T,
+    pub scram: Option<ScramServer>,
     pub in_flight: InFlight,
     pub remote_addr: IpAddr,
     pub span: tracing::Span,
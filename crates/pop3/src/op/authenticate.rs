@@ -21,13 +21,16 @@
  * for more details.
 */
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use common::{
+    auth::scram::{ScramResult, ScramServer},
     config::server::ServerProtocol,
     listener::{limiter::ConcurrencyLimiter, SessionStream},
     AuthResult,
 };
+use directory::core::secret::ScramAlgorithm;
 use imap::op::authenticate::{decode_challenge_oauth, decode_challenge_plain};
-use jmap::auth::rate_limit::ConcurrencyLimiters;
+use jmap::auth::{rate_limit::ConcurrencyLimiters, AccessToken};
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use std::sync::Arc;
@@ -74,6 +77,10 @@ impl<T: SessionStream> Session<T> {
                     self.write_bytes("+\r\n").await
                 }
             }
+            Mechanism::ScramSha1
+            | Mechanism::ScramSha1Plus
+            | Mechanism::ScramSha256
+            | Mechanism::ScramSha256Plus => self.handle_scram(mechanism, params).await,
             _ => {
                 self.write_err("Authentication mechanism not supported.")
                     .await
@@ -81,8 +88,90 @@ impl<T: SessionStream> Session<T> {
         }
     }
 
-    pub async fn handle_auth(&mut self, credentials: Credentials<String>) -> Result<(), ()> {
-        // Throttle authentication requests
+    async fn handle_scram(
+        &mut self,
+        mechanism: Mechanism,
+        mut params: Vec<String>,
+    ) -> Result<(), ()> {
+        let mut server = if let Some(server) = self.scram.take() {
+            server
+        } else {
+            // Throttle authentication requests
+            self.is_auth_allowed().await?;
+
+            let (algorithm, is_plus) = match mechanism {
+                Mechanism::ScramSha1 => (ScramAlgorithm::Sha1, false),
+                Mechanism::ScramSha1Plus => (ScramAlgorithm::Sha1, true),
+                Mechanism::ScramSha256 => (ScramAlgorithm::Sha256, false),
+                _ => (ScramAlgorithm::Sha256, true),
+            };
+            let tls_exporter = self.stream.tls_exporter();
+            if is_plus && tls_exporter.is_none() {
+                return self.write_err("Channel binding is not available.").await;
+            }
+            let server = ScramServer::new(algorithm, is_plus, tls_exporter);
+
+            if params.is_empty() {
+                return self.scram_challenge(server, mechanism, b"").await;
+            }
+            server
+        };
+
+        let response = params.pop().unwrap_or_default();
+        let response = if response.is_empty() {
+            vec![]
+        } else if let Some(response) = base64_decode(response.as_bytes()) {
+            response
+        } else {
+            return self.write_err("Failed to decode challenge.").await;
+        };
+
+        match self
+            .jmap
+            .authenticate_scram(
+                &mut server,
+                &response,
+                self.remote_addr,
+                ServerProtocol::Pop3,
+            )
+            .await
+        {
+            ScramResult::Challenge(challenge) => {
+                self.scram_challenge(server, mechanism, &challenge).await
+            }
+            ScramResult::Done(AuthResult::Success(access_token)) => {
+                self.complete_authentication(Some(access_token)).await
+            }
+            ScramResult::Done(AuthResult::Failure) => self.complete_authentication(None).await,
+            ScramResult::Done(AuthResult::Banned) => {
+                self.write_err("Too many authentication requests from this IP address.")
+                    .await?;
+                Err(())
+            }
+        }
+    }
+
+    async fn scram_challenge(
+        &mut self,
+        server: ScramServer,
+        mechanism: Mechanism,
+        challenge: &[u8],
+    ) -> Result<(), ()> {
+        self.scram = Some(server);
+        self.receiver.state = request::State::Argument {
+            request: Command::Auth {
+                mechanism: mechanism.as_str().as_bytes().to_vec(),
+                params: vec![],
+            },
+            num: 1,
+            last_is_space: true,
+        };
+
+        self.write_bytes(format!("+ {}\r\n", STANDARD.encode(challenge)))
+            .await
+    }
+
+    async fn is_auth_allowed(&mut self) -> Result<(), ()> {
         if self
             .jmap
             .is_auth_allowed_soft(&self.remote_addr)
@@ -96,8 +185,15 @@ impl<T: SessionStream> Session<T> {
 
             self.write_err("Too many authentication requests from this IP address.")
                 .await?;
-            return Err(());
+            Err(())
+        } else {
+            Ok(())
         }
+    }
+
+    pub async fn handle_auth(&mut self, credentials: Credentials<String>) -> Result<(), ()> {
+        // Throttle authentication requests
+        self.is_auth_allowed().await?;
 
         // Authenticate
         let access_token = match credentials {
@@ -136,6 +232,13 @@ impl<T: SessionStream> Session<T> {
             }
         };
 
+        self.complete_authentication(access_token).await
+    }
+
+    async fn complete_authentication(
+        &mut self,
+        access_token: Option<AccessToken>,
+    ) -> Result<(), ()> {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = match self
@@ -51,6 +51,7 @@ impl SessionManager for Pop3SessionManager {
                     username: None,
                 },
                 stream: session.stream,
+                scram: None,
                 in_flight: session.in_flight,
                 remote_addr: session.remote_ip,
                 span: session.span,
@@ -138,6 +139,7 @@ impl<T: SessionStream> Session<T> {
             instance: self.instance,
             receiver: self.receiver,
             state: self.state,
+            scram: None,
             span: self.span,
             in_flight: self.in_flight,
             remote_addr: self.remote_addr,
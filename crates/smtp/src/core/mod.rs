@@ -52,7 +52,8 @@ use utils::snowflake::SnowflakeIdGenerator;
 
 use crate::{
     inbound::auth::SaslToken,
-    queue::{self, DomainPart, QueueId},
+    outbound::pool::IpPoolState,
+    queue::{self, adaptive::AdaptiveThrottle, DomainPart, QueueId},
     reporting,
 };
 
@@ -96,12 +97,14 @@ pub struct SMTP {
 pub struct Inner {
     pub session_throttle: DashMap<ThrottleKey, ConcurrencyLimiter, ThrottleKeyHasherBuilder>,
     pub queue_throttle: DashMap<ThrottleKey, ConcurrencyLimiter, ThrottleKeyHasherBuilder>,
+    pub adaptive_throttle: AdaptiveThrottle,
     pub queue_tx: mpsc::Sender<queue::Event>,
     pub report_tx: mpsc::Sender<reporting::Event>,
     pub snowflake_id: SnowflakeIdGenerator,
     pub connectors: TlsConnectors,
     pub ipc: Ipc,
     pub script_cache: ScriptCache,
+    pub ip_pools: IpPoolState,
 }
 
 pub struct TlsConnectors {
@@ -196,6 +199,7 @@ pub struct SessionParameters {
     pub rcpt_dsn: bool,
     pub can_expn: bool,
     pub can_vrfy: bool,
+    pub can_burl: bool,
     pub max_message_size: usize,
 
     // Mail authentication parameters
@@ -333,6 +337,7 @@ impl Session<common::listener::stream::NullIo> {
                 spf_mail_from: VerifyStrategy::Disable,
                 can_expn: false,
                 can_vrfy: false,
+                can_burl: false,
             },
             in_flight: vec![],
         }
@@ -426,6 +431,7 @@ impl Default for Inner {
         Self {
             session_throttle: Default::default(),
             queue_throttle: Default::default(),
+            adaptive_throttle: Default::default(),
             queue_tx: mpsc::channel(1).0,
             report_tx: mpsc::channel(1).0,
             snowflake_id: Default::default(),
@@ -438,6 +444,7 @@ impl Default for Inner {
                 webhook_tx: mpsc::channel(1).0,
             },
             script_cache: Default::default(),
+            ip_pools: Default::default(),
         }
     }
 }
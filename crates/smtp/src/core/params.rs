@@ -134,7 +134,7 @@ impl<T: SessionStream> Session<T> {
     }
 
     pub async fn eval_post_auth_params(&mut self) {
-        // Refresh VRFY/EXPN parameters
+        // Refresh VRFY/EXPN/BURL parameters
         let ec = &self.core.core.smtp.session.extensions;
         self.params.can_expn = self
             .core
@@ -148,6 +148,12 @@ impl<T: SessionStream> Session<T> {
             .eval_if(&ec.vrfy, self)
             .await
             .unwrap_or(false);
+        self.params.can_burl = self
+            .core
+            .core
+            .eval_if(&ec.burl, self)
+            .await
+            .unwrap_or(false);
         self.params.auth_match_sender = self
             .core
             .core
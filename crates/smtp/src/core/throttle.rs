@@ -327,5 +327,6 @@ impl SMTP {
         for throttle in [&self.inner.session_throttle, &self.inner.queue_throttle] {
             throttle.retain(|_, v| v.concurrent.load(Ordering::Relaxed) > 0);
         }
+        self.inner.ip_pools.cleanup();
     }
 }
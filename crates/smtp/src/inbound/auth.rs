@@ -21,16 +21,26 @@
  * for more details.
 */
 
-use common::{listener::SessionStream, AuthResult};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{
+    auth::scram::{ScramResult, ScramServer},
+    listener::SessionStream,
+    AuthResult,
+};
+use directory::{core::secret::ScramAlgorithm, Principal};
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
-use smtp_proto::{IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_XOAUTH2};
+use smtp_proto::{
+    IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_SCRAM_SHA_1, AUTH_SCRAM_SHA_1_PLUS,
+    AUTH_SCRAM_SHA_256, AUTH_SCRAM_SHA_256_PLUS, AUTH_XOAUTH2,
+};
 
 use crate::core::Session;
 
 pub struct SaslToken {
     mechanism: u64,
     credentials: Credentials<String>,
+    scram: Option<Box<ScramServer>>,
 }
 
 impl SaslToken {
@@ -42,6 +52,7 @@ impl SaslToken {
                     username: String::new(),
                     secret: String::new(),
                 },
+                scram: None,
             }
             .into(),
             AUTH_OAUTHBEARER => SaslToken {
@@ -49,6 +60,7 @@ impl SaslToken {
                 credentials: Credentials::OAuthBearer {
                     token: String::new(),
                 },
+                scram: None,
             }
             .into(),
             AUTH_XOAUTH2 => SaslToken {
@@ -57,6 +69,19 @@ impl SaslToken {
                     username: String::new(),
                     secret: String::new(),
                 },
+                scram: None,
+            }
+            .into(),
+            AUTH_SCRAM_SHA_1
+            | AUTH_SCRAM_SHA_1_PLUS
+            | AUTH_SCRAM_SHA_256
+            | AUTH_SCRAM_SHA_256_PLUS => SaslToken {
+                mechanism,
+                credentials: Credentials::Plain {
+                    username: String::new(),
+                    secret: String::new(),
+                },
+                scram: None,
             }
             .into(),
             _ => None,
@@ -70,6 +95,13 @@ impl<T: SessionStream> Session<T> {
         token: &mut SaslToken,
         response: &[u8],
     ) -> Result<bool, ()> {
+        if matches!(
+            token.mechanism,
+            AUTH_SCRAM_SHA_1 | AUTH_SCRAM_SHA_1_PLUS | AUTH_SCRAM_SHA_256 | AUTH_SCRAM_SHA_256_PLUS
+        ) {
+            return self.handle_scram_response(token, response).await;
+        }
+
         if response.is_empty() {
             match (token.mechanism, &token.credentials) {
                 (AUTH_PLAIN | AUTH_XOAUTH2 | AUTH_OAUTHBEARER, _) => {
@@ -173,15 +205,78 @@ impl<T: SessionStream> Session<T> {
         self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await
     }
 
-    pub async fn authenticate(&mut self, credentials: Credentials<String>) -> Result<bool, ()> {
-        if let Some(directory) = &self.params.auth_directory {
-            let authenticated_as = match &credentials {
-                Credentials::Plain { username, .. }
-                | Credentials::XOauth2 { username, .. }
-                | Credentials::OAuthBearer { token: username } => username.to_string(),
+    async fn handle_scram_response(
+        &mut self,
+        token: &mut SaslToken,
+        response: &[u8],
+    ) -> Result<bool, ()> {
+        let Some(directory) = self.params.auth_directory.clone() else {
+            return self.authenticate_result(Err(()), "").await;
+        };
+
+        if token.scram.is_none() {
+            let (algorithm, is_plus) = match token.mechanism {
+                AUTH_SCRAM_SHA_1 => (ScramAlgorithm::Sha1, false),
+                AUTH_SCRAM_SHA_1_PLUS => (ScramAlgorithm::Sha1, true),
+                AUTH_SCRAM_SHA_256 => (ScramAlgorithm::Sha256, false),
+                _ => (ScramAlgorithm::Sha256, true),
             };
-            match self
-                .core
+            let tls_exporter = self.stream.tls_exporter();
+            if is_plus && tls_exporter.is_none() {
+                return self
+                    .auth_error(b"504 5.5.4 Channel binding is not available.\r\n")
+                    .await;
+            }
+            token.scram = Box::new(ScramServer::new(algorithm, is_plus, tls_exporter)).into();
+
+            if response.is_empty() {
+                self.write(b"334 \r\n").await?;
+                return Ok(true);
+            }
+        }
+
+        let response = if !response.is_empty() {
+            if let Some(response) = base64_decode(response) {
+                response
+            } else {
+                return self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await;
+            }
+        } else {
+            vec![]
+        };
+
+        let server = token.scram.as_mut().unwrap();
+        let authenticated_as = server.username().to_string();
+        match self
+            .core
+            .core
+            .authenticate_scram(
+                &directory,
+                &self.core.inner.ipc,
+                server,
+                &response,
+                self.data.remote_ip,
+                self.instance.protocol,
+                false,
+            )
+            .await
+        {
+            Ok(ScramResult::Challenge(challenge)) => {
+                self.write(format!("334 {}\r\n", STANDARD.encode(challenge)).as_bytes())
+                    .await?;
+                Ok(true)
+            }
+            Ok(ScramResult::Done(result)) => {
+                self.authenticate_result(Ok(result), &authenticated_as)
+                    .await
+            }
+            Err(_) => self.authenticate_result(Err(()), "").await,
+        }
+    }
+
+    pub async fn authenticate(&mut self, credentials: Credentials<String>) -> Result<bool, ()> {
+        let result = if let Some(directory) = &self.params.auth_directory {
+            self.core
                 .core
                 .authenticate(
                     directory,
@@ -190,9 +285,29 @@ impl<T: SessionStream> Session<T> {
                     self.data.remote_ip,
                     self.instance.protocol,
                     false,
+                    true,
                 )
                 .await
-            {
+                .map_err(|_| ())
+        } else {
+            Err(())
+        };
+        let authenticated_as = match &credentials {
+            Credentials::Plain { username, .. }
+            | Credentials::XOauth2 { username, .. }
+            | Credentials::OAuthBearer { token: username } => username.as_str(),
+        };
+
+        self.authenticate_result(result, authenticated_as).await
+    }
+
+    async fn authenticate_result(
+        &mut self,
+        result: Result<AuthResult<Principal<u32>>, ()>,
+        authenticated_as: &str,
+    ) -> Result<bool, ()> {
+        if self.params.auth_directory.is_some() {
+            match result {
                 Ok(AuthResult::Success(principal)) => {
                     tracing::debug!(
                         parent: &self.span,
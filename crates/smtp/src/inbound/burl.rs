@@ -0,0 +1,140 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use common::{config::server::ServerProtocol, listener::SessionStream, DeliveryEvent};
+use tokio::sync::oneshot;
+
+use crate::core::Session;
+
+use super::data::count_received;
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_burl(&mut self, uri: String, is_last: bool) -> Result<(), ()> {
+        if self.data.authenticated_as.is_empty() {
+            return self.write(b"530 5.7.0 Authentication required.\r\n").await;
+        } else if !self.params.can_burl {
+            return self.write(b"502 5.5.1 BURL is disabled.\r\n").await;
+        } else if !self.can_send_data().await? {
+            return Ok(());
+        }
+
+        // Fetch the referenced message from the mail store
+        let (result_tx, result_rx) = oneshot::channel();
+        let contents = match self
+            .core
+            .inner
+            .ipc
+            .delivery_tx
+            .send(DeliveryEvent::FetchUrl {
+                url: uri.clone(),
+                requester: self.data.authenticated_as.clone(),
+                result_tx,
+            })
+            .await
+        {
+            Ok(_) => result_rx.await.ok(),
+            Err(_) => None,
+        };
+
+        match contents {
+            Some(Some(contents))
+                if contents.len() + self.data.message.len() < self.params.max_message_size =>
+            {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "burl",
+                    event = "success",
+                    url = uri,
+                    size = contents.len(),
+                );
+
+                self.data.message.extend_from_slice(&contents);
+            }
+            Some(Some(_)) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "burl",
+                    event = "too-large",
+                    url = uri,
+                    "Message is too large."
+                );
+
+                self.data.message = Vec::with_capacity(0);
+                return self
+                    .write(b"552 5.3.4 Message too big for system.\r\n")
+                    .await;
+            }
+            Some(None) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "burl",
+                    event = "failed",
+                    url = uri,
+                    "Failed to resolve URL."
+                );
+
+                self.data.message = Vec::with_capacity(0);
+                return self
+                    .write(b"554 5.6.6 IMAP URL resolution failed.\r\n")
+                    .await;
+            }
+            None => {
+                tracing::warn!(
+                    parent: &self.span,
+                    context = "burl",
+                    event = "error",
+                    url = uri,
+                    "Mail store is unavailable."
+                );
+
+                self.data.message = Vec::with_capacity(0);
+                return self
+                    .write(b"454 4.4.5 Temporary failure retrieving URL.\r\n")
+                    .await;
+            }
+        }
+
+        if is_last {
+            let num_rcpts = self.data.rcpt_to.len();
+            let message = self.queue_message().await;
+            count_received(&message);
+            if !message.is_empty() {
+                if self.instance.protocol == ServerProtocol::Smtp {
+                    self.write(message.as_ref()).await?;
+                } else {
+                    for _ in 0..num_rcpts {
+                        self.write(message.as_ref()).await?;
+                    }
+                }
+                self.reset();
+                Ok(())
+            } else {
+                // Disconnect requested
+                Err(())
+            }
+        } else {
+            self.write(b"250 2.5.0 Waiting for additional BURL or BDAT commands.\r\n")
+                .await
+        }
+    }
+}
@@ -46,12 +46,17 @@ use smtp_proto::{
 };
 use store::write::now;
 use tokio::{io::AsyncWriteExt, process::Command};
-use utils::config::Rate;
+use utils::{
+    config::Rate,
+    metrics::{SMTP_MESSAGES_ACCEPTED, SMTP_MESSAGES_DEFERRED, SMTP_MESSAGES_REJECTED},
+};
 
 use crate::{
     core::{Session, SessionAddress, State},
     inbound::milter::Modification,
-    queue::{self, Message, QueueEnvelope, Schedule},
+    queue::{
+        self, quarantine::QuarantineSource, Message, QueueEnvelope, Schedule, MAIL_SENDER_VERIFIED,
+    },
     scripts::ScriptResult,
 };
 
@@ -420,9 +425,11 @@ impl<T: SessionStream> Session<T> {
 
         // Run Milter filters
         let mut modifications = Vec::new();
+        let mut quarantine = None;
         match self.run_milters(Stage::Data, (&auth_message).into()).await {
             Ok(modifications_) => {
                 if !modifications_.is_empty() {
+                    quarantine = quarantine_reason(&modifications_, QuarantineSource::Milter);
                     tracing::debug!(
                     parent: &self.span,
                     context = "milter",
@@ -454,6 +461,9 @@ impl<T: SessionStream> Session<T> {
         {
             Ok(modifications_) => {
                 if !modifications_.is_empty() {
+                    if quarantine.is_none() {
+                        quarantine = quarantine_reason(&modifications_, QuarantineSource::MtaHook);
+                    }
                     tracing::debug!(
                             parent: &self.span,
                             context = "mta_hook",
@@ -682,6 +692,9 @@ impl<T: SessionStream> Session<T> {
                     ScriptModification::SetEnvelope { name, value } => {
                         self.data.apply_envelope_modification(name, value);
                     }
+                    ScriptModification::Quarantine { reason } => {
+                        quarantine = Some((reason, QuarantineSource::Sieve));
+                    }
                 }
             }
         }
@@ -691,6 +704,21 @@ impl<T: SessionStream> Session<T> {
         let rcpt_to = std::mem::take(&mut self.data.rcpt_to);
         let mut message = self.build_message(mail_from, rcpt_to, message_id).await;
 
+        // Flag messages whose From address passed DMARC or that the session is
+        // allowed to send as, so that local delivery can trust the sender
+        let from = auth_message.from();
+        if dmarc_result == Some(DmarcResult::Pass)
+            || (!from.is_empty()
+                && (self.data.authenticated_as == from
+                    || self
+                        .data
+                        .authenticated_emails
+                        .iter()
+                        .any(|e| e == from || (e.starts_with('@') && from.ends_with(e)))))
+        {
+            message.flags |= MAIL_SENDER_VERIFIED;
+        }
+
         // Add Return-Path
         if self
             .core
@@ -760,6 +788,35 @@ impl<T: SessionStream> Session<T> {
         // Update size
         message.size = raw_message.len() + headers.len();
 
+        // Quarantine message
+        if let Some((reason, source)) = quarantine {
+            let queue_id = message.id;
+            return if message
+                .quarantine(
+                    Some(&headers),
+                    raw_message,
+                    reason,
+                    source,
+                    self.data.remote_ip,
+                    &self.core,
+                    &self.span,
+                )
+                .await
+            {
+                self.state = State::Accepted(queue_id);
+                self.data.messages_sent += 1;
+                (b"250 2.0.0 Message queued for delivery.\r\n"[..]).into()
+            } else {
+                self.send_failure_webhook(WebhookMessageFailure::ServerFailure)
+                    .await;
+
+                (b"451 4.3.5 Unable to accept message at this time.\r\n"[..]).into()
+            };
+        }
+
+        // Build journal copies
+        let journals = self.build_journal(&message, &headers, raw_message).await;
+
         // Verify queue quota
         if self.core.has_quota(&mut message).await {
             // Prepare webhook event
@@ -800,6 +857,30 @@ impl<T: SessionStream> Session<T> {
                 .queue(Some(&headers), raw_message, &self.core, &self.span)
                 .await
             {
+                // Queue journal copies
+                for (mut journal, raw_journal) in journals {
+                    if self.core.has_quota(&mut journal).await {
+                        let journal_id = journal.id;
+                        if journal
+                            .queue(None, &raw_journal, &self.core, &self.span)
+                            .await
+                        {
+                            tracing::debug!(parent: &self.span,
+                                context = "journal",
+                                event = "queued",
+                                queue_id = queue_id,
+                                journal_id = journal_id,
+                                "Queued journal copy.");
+                        }
+                    } else {
+                        tracing::warn!(parent: &self.span,
+                            context = "journal",
+                            event = "quota-exceeded",
+                            queue_id = queue_id,
+                            "Queue quota exceeded, journal copy not queued.");
+                    }
+                }
+
                 // Send webhook event
                 if let Some(event) = webhook_event {
                     self.core
@@ -1076,3 +1157,24 @@ impl<T: SessionStream> Session<T> {
         }
     }
 }
+
+fn quarantine_reason(
+    modifications: &[Modification],
+    source: QuarantineSource,
+) -> Option<(String, QuarantineSource)> {
+    modifications
+        .iter()
+        .find_map(|modification| match modification {
+            Modification::Quarantine { reason } => Some((reason.clone(), source)),
+            _ => None,
+        })
+}
+
+pub(crate) fn count_received(response: &[u8]) {
+    match response.first() {
+        Some(b'2') => SMTP_MESSAGES_ACCEPTED.increment(),
+        Some(b'4') => SMTP_MESSAGES_DEFERRED.increment(),
+        Some(b'5') => SMTP_MESSAGES_REJECTED.increment(),
+        _ => (),
+    }
+}
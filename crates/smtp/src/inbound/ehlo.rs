@@ -221,6 +221,17 @@ impl<T: SessionStream> Session<T> {
             response.capabilities |= EXT_DSN;
         }
 
+        // BURL
+        if self
+            .core
+            .core
+            .eval_if(&ec.burl, self)
+            .await
+            .unwrap_or(false)
+        {
+            response.capabilities |= EXT_BURL;
+        }
+
         // Authentication
         if self.data.authenticated_as.is_empty() {
             response.auth_mechanisms = self
@@ -230,6 +241,10 @@ impl<T: SessionStream> Session<T> {
                 .await
                 .unwrap_or_default()
                 .into();
+            if self.stream.tls_exporter().is_none() {
+                // Channel binding requires TLS 1.3
+                response.auth_mechanisms &= !(AUTH_SCRAM_SHA_256_PLUS | AUTH_SCRAM_SHA_1_PLUS);
+            }
             if response.auth_mechanisms != 0 {
                 response.capabilities |= EXT_AUTH;
             }
@@ -130,11 +130,15 @@ impl<T: SessionStream> Session<T> {
                         Action::Discard => FilterResponse::accept(),
                         Action::Reject => FilterResponse::reject(),
                         Action::Quarantine => {
-                            modifications.push(Modification::AddHeader {
-                                name: "X-Quarantine".to_string(),
-                                value: "true".to_string(),
+                            // Handled like Milter's SMFIR_QUARANTINE: the message is held in
+                            // the quarantine store and stamped with an X-Quarantine header.
+                            modifications.push(Modification::Quarantine {
+                                reason: response
+                                    .response
+                                    .and_then(|response| response.message)
+                                    .unwrap_or_else(|| "Quarantined by MTA hook".to_string()),
                             });
-                            FilterResponse::accept()
+                            continue;
                         }
                     };
 
@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Write;
+
+use common::{
+    expr::{self, functions::ResolveVariable, *},
+    listener::SessionStream,
+};
+use mail_builder::{
+    headers::{address::Address, content_type::ContentType, HeaderType},
+    mime::{BodyPart, MimePart},
+    MessageBuilder,
+};
+use smtp_proto::{MAIL_BODY_8BITMIME, RCPT_NOTIFY_NEVER};
+
+use crate::{core::Session, queue::Message};
+
+struct JournalEnvelope<'x, T: SessionStream> {
+    session: &'x Session<T>,
+    message: &'x Message,
+    rcpt_idx: usize,
+}
+
+impl<T: SessionStream> Session<T> {
+    pub async fn build_journal(
+        &self,
+        message: &Message,
+        raw_headers: &[u8],
+        raw_message: &[u8],
+    ) -> Vec<(Message, Vec<u8>)> {
+        // Obtain the journaling addresses for each recipient
+        let mut journals: Vec<(String, Vec<&str>)> = Vec::new();
+        for (rcpt_idx, rcpt) in message.recipients.iter().enumerate() {
+            for address in self
+                .core
+                .core
+                .eval_if::<Vec<String>, _>(
+                    &self.core.core.smtp.session.data.journal,
+                    &JournalEnvelope {
+                        session: self,
+                        message,
+                        rcpt_idx,
+                    },
+                )
+                .await
+                .unwrap_or_default()
+            {
+                let address = address.trim().to_lowercase();
+                if !address.contains('@') {
+                    continue;
+                }
+                if let Some((_, rcpts)) = journals.iter_mut().find(|(a, _)| a == &address) {
+                    rcpts.push(rcpt.address.as_str());
+                } else {
+                    journals.push((address, vec![rcpt.address.as_str()]));
+                }
+            }
+        }
+        if journals.is_empty() {
+            return vec![];
+        }
+
+        // Obtain sender addresses
+        let config = &self.core.core.smtp.queue;
+        let from_name = self
+            .core
+            .core
+            .eval_if(&config.dsn.name, message)
+            .await
+            .unwrap_or_else(|| String::from("Mail Delivery Subsystem"));
+        let from_addr = self
+            .core
+            .core
+            .eval_if(&config.dsn.address, message)
+            .await
+            .unwrap_or_else(|| String::from("MAILER-DAEMON@localhost"));
+
+        // Build original message
+        let mut original = Vec::with_capacity(raw_headers.len() + raw_message.len());
+        original.extend_from_slice(raw_headers);
+        original.extend_from_slice(raw_message);
+        let is_8bit = !original.is_ascii();
+
+        let mut results = Vec::with_capacity(journals.len());
+        for (address, rcpts) in journals {
+            // Build envelope report
+            let mut report = String::with_capacity(128);
+            let _ = write!(
+                report,
+                "Sender: {}\r\n",
+                if !message.return_path.is_empty() {
+                    message.return_path.as_str()
+                } else {
+                    "<>"
+                }
+            );
+            for rcpt in rcpts {
+                let _ = write!(report, "Recipient: {rcpt}\r\n");
+            }
+            let _ = write!(
+                report,
+                "Queue-ID: {:X}\r\nRemote-IP: {}\r\nListener: {}\r\n",
+                message.id, self.data.remote_ip, self.instance.id
+            );
+            if !self.data.authenticated_as.is_empty() {
+                let _ = write!(
+                    report,
+                    "Authenticated-As: {}\r\n",
+                    self.data.authenticated_as
+                );
+            }
+
+            let raw = MessageBuilder::new()
+                .from((from_name.as_str(), from_addr.as_str()))
+                .to(Address::new_address(None::<&str>, address.as_str()))
+                .header("Auto-Submitted", HeaderType::Text("auto-generated".into()))
+                .subject(format!("Journal report for message {:X}", message.id))
+                .body(MimePart::new(
+                    ContentType::new("multipart/mixed"),
+                    BodyPart::Multipart(vec![
+                        MimePart::new(
+                            ContentType::new("text/plain").attribute("charset", "utf-8"),
+                            BodyPart::Text(report.into()),
+                        ),
+                        MimePart::new(
+                            ContentType::new("message/rfc822"),
+                            BodyPart::Binary(original.as_slice().into()),
+                        )
+                        .transfer_encoding(if is_8bit {
+                            "8bit"
+                        } else {
+                            "7bit"
+                        }),
+                    ]),
+                ))
+                .write_to_vec()
+                .unwrap_or_default();
+
+            // Journal copies never generate DSNs
+            let mut journal = self.core.new_message("", "", "");
+            if is_8bit {
+                journal.flags |= MAIL_BODY_8BITMIME;
+            }
+            journal.add_recipient(address, &self.core).await;
+            for rcpt in &mut journal.recipients {
+                rcpt.flags |= RCPT_NOTIFY_NEVER;
+            }
+            journal.size = raw.len();
+
+            results.push((journal, raw));
+        }
+
+        results
+    }
+}
+
+impl<T: SessionStream> ResolveVariable for JournalEnvelope<'_, T> {
+    fn resolve_variable(&self, variable: u32) -> expr::Variable<'_> {
+        let rcpt = &self.message.recipients[self.rcpt_idx];
+        match variable {
+            V_RECIPIENT => rcpt.address_lcase.as_str().into(),
+            V_RECIPIENT_DOMAIN => self.message.domains[rcpt.domain_idx].domain.as_str().into(),
+            V_RECIPIENTS => self
+                .message
+                .recipients
+                .iter()
+                .map(|r| Variable::String(r.address_lcase.as_str().into()))
+                .collect::<Vec<_>>()
+                .into(),
+            _ => self.session.resolve_variable(variable),
+        }
+    }
+}
@@ -30,9 +30,11 @@ use mail_auth::{
 };
 
 pub mod auth;
+pub mod burl;
 pub mod data;
 pub mod ehlo;
 pub mod hooks;
+pub mod journal;
 pub mod mail;
 pub mod milter;
 pub mod rcpt;
@@ -37,7 +37,7 @@ use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::core::{Session, State};
 
-use super::auth::SaslToken;
+use super::{auth::SaslToken, data::count_received};
 
 impl<T: SessionStream> Session<T> {
     pub async fn ingest(&mut self, bytes: &[u8]) -> Result<bool, ()> {
@@ -182,7 +182,10 @@ impl<T: SessionStream> Session<T> {
                                     self.write(b"502 5.5.1 Invalid command.\r\n").await?;
                                 }
                             }
-                            Request::Etrn { .. } | Request::Atrn { .. } | Request::Burl { .. } => {
+                            Request::Burl { uri, is_last } => {
+                                self.handle_burl(uri, is_last).await?;
+                            }
+                            Request::Etrn { .. } | Request::Atrn { .. } => {
                                 self.write(b"502 5.5.1 Command not implemented.\r\n")
                                     .await?;
                             }
@@ -235,6 +238,7 @@ impl<T: SessionStream> Session<T> {
                         if receiver.ingest(&mut iter, &mut self.data.message) {
                             let num_rcpts = self.data.rcpt_to.len();
                             let message = self.queue_message().await;
+                            count_received(&message);
                             if !message.is_empty() {
                                 if self.instance.protocol == ServerProtocol::Smtp {
                                     self.write(message.as_ref()).await?;
@@ -262,6 +266,7 @@ impl<T: SessionStream> Session<T> {
                             if receiver.is_last {
                                 let num_rcpts = self.data.rcpt_to.len();
                                 let message = self.queue_message().await;
+                                count_received(&message);
                                 if !message.is_empty() {
                                     if self.instance.protocol == ServerProtocol::Smtp {
                                         self.write(message.as_ref()).await?;
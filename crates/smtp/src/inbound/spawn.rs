@@ -28,6 +28,7 @@ use common::{
     listener::{self, SessionManager, SessionStream},
 };
 use tokio_rustls::server::TlsStream;
+use utils::metrics::{SMTP_SESSIONS, SMTP_SESSIONS_ACTIVE};
 
 use crate::{
     core::{Session, SessionData, SessionParameters, SmtpSessionManager, State},
@@ -60,6 +61,9 @@ impl SessionManager for SmtpSessionManager {
 
         // Enforce throttle
         async {
+            SMTP_SESSIONS.increment();
+            let _active = SMTP_SESSIONS_ACTIVE.guard();
+
             if session.is_allowed().await
                 && session.init_conn().await
                 && session.handle_conn().await
@@ -60,6 +60,7 @@ impl SMTP {
                 ThrottleKeyHasherBuilder::default(),
                 shard,
             ),
+            adaptive_throttle: Default::default(),
             queue_tx,
             report_tx,
             snowflake_id: config
@@ -72,6 +73,7 @@ impl SMTP {
             },
             ipc,
             script_cache: ScriptCache::parse(config),
+            ip_pools: Default::default(),
         };
         let inner = SmtpInstance::new(core, inner);
 
@@ -35,9 +35,13 @@ use mail_send::SmtpClient;
 use smtp_proto::MAIL_REQUIRETLS;
 use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use store::write::{now, BatchBuilder, QueueClass, QueueEvent, ValueClass};
+use utils::metrics::{
+    DELIVERY_COMPLETED, DELIVERY_DURATION, DELIVERY_PERM_FAIL, DELIVERY_TEMP_FAIL,
+    QUEUE_DELIVERY_ATTEMPTS,
+};
 
 use crate::{
     core::SMTP,
@@ -52,7 +56,8 @@ use super::{
     NextHop, TlsStrategy,
 };
 use crate::queue::{
-    throttle, DeliveryAttempt, Domain, Error, Event, OnHold, QueueEnvelope, Status,
+    throttle, DeliveryAttempt, Domain, Error, Event, HistoryEntry, HistoryRecipient, HistoryTls,
+    OnHold, PolicyResult, QueueEnvelope, Status, MAIL_RELAY_OVERRIDE,
 };
 
 impl DeliveryAttempt {
@@ -78,6 +83,8 @@ impl DeliveryAttempt {
                 let _ = core.core.storage.data.write(batch.build()).await;
                 return;
             };
+            let _timer = DELIVERY_DURATION.start_timer();
+            QUEUE_DELIVERY_ATTEMPTS.increment();
 
             let span = tracing::info_span!(
                 "delivery",
@@ -159,10 +166,18 @@ impl DeliveryAttempt {
                 }
             }
 
+            // Obtain relay host set through the management API, if any
+            let relay_override = if (message.flags & MAIL_RELAY_OVERRIDE) != 0 {
+                core.read_message_relay(message.id).await
+            } else {
+                None
+            };
+
             let queue_config = &core.core.smtp.queue;
             let mut on_hold = Vec::new();
             let no_ip = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
             let mut recipients = std::mem::take(&mut message.recipients);
+            let mut attempts = Vec::new();
             'next_domain: for domain_idx in 0..message.domains.len() {
                 // Only process domains due for delivery
                 let domain = &message.domains[domain_idx];
@@ -171,6 +186,23 @@ impl DeliveryAttempt {
                 {
                     continue;
                 }
+                attempts.push((
+                    domain_idx,
+                    Instant::now(),
+                    HistoryEntry {
+                        domain: domain.domain.clone(),
+                        retry_num: domain.retry.inner,
+                        attempted_at: now(),
+                        duration: 0,
+                        mx: None,
+                        tls: None,
+                        mta_sts: PolicyResult::None,
+                        dane: PolicyResult::None,
+                        status: Status::Scheduled,
+                        recipients: Vec::new(),
+                    },
+                ));
+                let attempt = &mut attempts.last_mut().unwrap().2;
 
                 // Create new span for domain
                 let span = tracing::info_span!(
@@ -196,11 +228,14 @@ impl DeliveryAttempt {
                 }
 
                 // Obtain next hop
-                let (mut remote_hosts, is_smtp) = match core
-                    .core
-                    .eval_if::<String, _>(&queue_config.next_hop, &envelope)
-                    .await
-                    .and_then(|name| core.core.get_relay_host(&name))
+                let (mut remote_hosts, is_smtp) = match if relay_override.is_some() {
+                    relay_override.clone()
+                } else {
+                    core.core
+                        .eval_if::<String, _>(&queue_config.next_hop, &envelope)
+                        .await
+                }
+                .and_then(|name| core.core.get_relay_host(&name))
                 {
                     Some(next_hop) if next_hop.protocol == ServerProtocol::Http => {
                         // Deliver message locally
@@ -349,6 +384,7 @@ impl DeliveryAttempt {
                                     "Failed to retrieve MTA-STS policy: {}",
                                     err
                                 );
+                                attempt.mta_sts = PolicyResult::Fail;
                                 let schedule = core
                                     .core
                                     .eval_if::<Vec<Duration>, _>(&queue_config.retry, &envelope)
@@ -436,8 +472,16 @@ impl DeliveryAttempt {
                 'next_host: for remote_host in &remote_hosts {
                     // Validate MTA-STS
                     envelope.mx = remote_host.hostname();
+                    attempt.mx = Some(envelope.mx.to_string());
+                    attempt.tls = None;
+                    attempt.dane = PolicyResult::None;
+                    attempt.mta_sts = PolicyResult::None;
                     if let Some(mta_sts_policy) = &mta_sts_policy {
-                        if !mta_sts_policy.verify(envelope.mx) {
+                        if mta_sts_policy.verify(envelope.mx) {
+                            attempt.mta_sts = PolicyResult::Pass;
+                        } else {
+                            attempt.mta_sts = PolicyResult::Fail;
+
                             // Report MTA-STS failed verification
                             if let Some(tls_report) = &tls_report {
                                 core.schedule_report(TlsEvent {
@@ -490,6 +534,13 @@ impl DeliveryAttempt {
                         }
                     };
 
+                    // Obtain source IP pool, if any
+                    let source_pool = core
+                        .core
+                        .eval_if::<String, _>(&queue_config.source_pool, &envelope)
+                        .await
+                        .filter(|pool_id| !pool_id.is_empty());
+
                     // Update TLS strategy
                     tls_strategy.dane = core
                         .core
@@ -548,6 +599,7 @@ impl DeliveryAttempt {
                                                     .to_string(),
                                             },
                                         ));
+                                        attempt.dane = PolicyResult::Fail;
                                         continue 'next_host;
                                     }
                                     None
@@ -585,6 +637,7 @@ impl DeliveryAttempt {
                                             entity: envelope.mx.to_string(),
                                             details: "No TLSA DNSSEC records found".to_string(),
                                         }));
+                                    attempt.dane = PolicyResult::Fail;
                                     continue 'next_host;
                                 }
                                 None
@@ -629,6 +682,7 @@ impl DeliveryAttempt {
                                         } else {
                                             err.into()
                                         };
+                                    attempt.dane = PolicyResult::Fail;
                                     continue 'next_host;
                                 }
                                 None
@@ -641,7 +695,32 @@ impl DeliveryAttempt {
                     // Try each IP address
                     'next_ip: for remote_ip in resolve_result.remote_ips {
                         // Set source IP, if any
-                        let source_ip = if remote_ip.is_ipv4() {
+                        let source_ip = if let Some(pool_id) = &source_pool {
+                            if let Some(source_ip) =
+                                core.select_pool_ip(pool_id, &domain.domain, remote_ip.is_ipv4())
+                            {
+                                Some(source_ip)
+                            } else {
+                                tracing::info!(
+                                    parent: &span,
+                                    context = "ip-pool",
+                                    event = "exhausted",
+                                    mx = envelope.mx,
+                                    pool = pool_id,
+                                    "No source IPs available in pool."
+                                );
+
+                                last_status = Status::TemporaryFailure(Error::ConnectionError(
+                                    ErrorDetails {
+                                        entity: envelope.mx.to_string(),
+                                        details: format!(
+                                            "No source IPs available in pool {pool_id:?}"
+                                        ),
+                                    },
+                                ));
+                                continue 'next_ip;
+                            }
+                        } else if remote_ip.is_ipv4() {
                             resolve_result.source_ipv4
                         } else {
                             resolve_result.source_ipv6
@@ -660,6 +739,18 @@ impl DeliveryAttempt {
                                 continue 'next_domain;
                             }
                         }
+                        if let Err(err) = core.is_adaptive_allowed(envelope.mx, &mut in_flight_host)
+                        {
+                            tracing::info!(
+                                parent: &span,
+                                context = "throttle",
+                                event = "adaptive-limit",
+                                mx = envelope.mx,
+                                "Remote host is being throttled after deferring deliveries."
+                            );
+                            message.domains[domain_idx].set_throttle_error(err, &mut on_hold);
+                            continue 'next_domain;
+                        }
 
                         // Connect
                         let conn_timeout = core
@@ -779,6 +870,22 @@ impl DeliveryAttempt {
                                     status = %status,
                                 );
 
+                                core.record_adaptive_outcome(
+                                    envelope.mx,
+                                    &status,
+                                    std::iter::empty(),
+                                );
+                                if let (Some(pool_id), Some(source_ip)) = (&source_pool, source_ip)
+                                {
+                                    core.record_pool_delivery(
+                                        pool_id,
+                                        source_ip,
+                                        &domain.domain,
+                                        &status,
+                                        std::iter::empty(),
+                                    );
+                                }
+
                                 last_status = status;
                                 continue 'next_host;
                             }
@@ -824,6 +931,9 @@ impl DeliveryAttempt {
                                             protocol = ?smtp_client.tls_connection().protocol_version(),
                                             cipher = ?smtp_client.tls_connection().negotiated_cipher_suite(),
                                         );
+                                        attempt.tls = HistoryTls::from_connection(
+                                            smtp_client.tls_connection(),
+                                        );
 
                                         // Verify DANE
                                         if let Some(dane_policy) = &dane_policy {
@@ -852,9 +962,11 @@ impl DeliveryAttempt {
                                                     .await;
                                                 }
 
+                                                attempt.dane = PolicyResult::Fail;
                                                 last_status = status;
                                                 continue 'next_host;
                                             }
+                                            attempt.dane = PolicyResult::Pass;
                                         }
 
                                         // Report TLS success
@@ -1001,7 +1113,12 @@ impl DeliveryAttempt {
                                 .unwrap_or_else(|| Duration::from_secs(3 * 60));
                             let mut smtp_client =
                                 match smtp_client.into_tls(tls_connector, envelope.mx).await {
-                                    Ok(smtp_client) => smtp_client,
+                                    Ok(smtp_client) => {
+                                        attempt.tls = HistoryTls::from_connection(
+                                            smtp_client.tls_connection(),
+                                        );
+                                        smtp_client
+                                    }
                                     Err(error) => {
                                         tracing::info!(
                                             parent: &span,
@@ -1032,6 +1149,22 @@ impl DeliveryAttempt {
                                     status = %status,
                                 );
 
+                                core.record_adaptive_outcome(
+                                    envelope.mx,
+                                    &status,
+                                    std::iter::empty(),
+                                );
+                                if let (Some(pool_id), Some(source_ip)) = (&source_pool, source_ip)
+                                {
+                                    core.record_pool_delivery(
+                                        pool_id,
+                                        source_ip,
+                                        &domain.domain,
+                                        &status,
+                                        std::iter::empty(),
+                                    );
+                                }
+
                                 last_status = status;
                                 continue 'next_host;
                             }
@@ -1046,6 +1179,22 @@ impl DeliveryAttempt {
                                 .await
                         };
 
+                        // Update adaptive throttling and source IP pool statistics
+                        core.record_adaptive_outcome(
+                            envelope.mx,
+                            &delivery_result,
+                            recipients.iter().filter(|r| r.domain_idx == domain_idx),
+                        );
+                        if let (Some(pool_id), Some(source_ip)) = (&source_pool, source_ip) {
+                            core.record_pool_delivery(
+                                pool_id,
+                                source_ip,
+                                &domain.domain,
+                                &delivery_result,
+                                recipients.iter().filter(|r| r.domain_idx == domain_idx),
+                            );
+                        }
+
                         // Update status for the current domain and continue with the next one
                         let schedule = core
                             .core
@@ -1067,6 +1216,36 @@ impl DeliveryAttempt {
             }
             message.recipients = recipients;
 
+            // Record delivery attempts
+            if !attempts.is_empty() {
+                let finished_at = Instant::now();
+                let started_at = attempts
+                    .iter()
+                    .skip(1)
+                    .map(|(_, started_at, _)| *started_at)
+                    .chain([finished_at])
+                    .collect::<Vec<_>>();
+                let history = attempts
+                    .into_iter()
+                    .zip(started_at)
+                    .map(|((domain_idx, started_at, mut entry), ended_at)| {
+                        entry.duration = ended_at.duration_since(started_at).as_millis() as u64;
+                        entry.status = message.domains[domain_idx].status.clone();
+                        entry.recipients = message
+                            .recipients
+                            .iter()
+                            .filter(|rcpt| rcpt.domain_idx == domain_idx)
+                            .map(|rcpt| HistoryRecipient {
+                                address: rcpt.address.clone(),
+                                status: rcpt.status.clone(),
+                            })
+                            .collect();
+                        entry
+                    })
+                    .collect();
+                message.append_history(&core, history).await;
+            }
+
             // Send Delivery Status Notifications
             core.send_dsn(&mut message, &span).await;
 
@@ -1187,6 +1366,12 @@ impl Message {
 impl Domain {
     pub fn set_status(&mut self, status: impl Into<Status<(), Error>>, schedule: &[Duration]) {
         self.status = status.into();
+        match &self.status {
+            Status::Completed(_) => DELIVERY_COMPLETED.increment(),
+            Status::TemporaryFailure(_) => DELIVERY_TEMP_FAIL.increment(),
+            Status::PermanentFailure(_) => DELIVERY_PERM_FAIL.increment(),
+            Status::Scheduled => (),
+        }
         if matches!(
             &self.status,
             Status::TemporaryFailure(_) | Status::Scheduled
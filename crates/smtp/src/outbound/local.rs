@@ -26,7 +26,8 @@ use smtp_proto::Response;
 use tokio::sync::{mpsc, oneshot};
 
 use crate::queue::{
-    Error, ErrorDetails, HostResponse, Message, Recipient, Status, RCPT_STATUS_CHANGED,
+    Error, ErrorDetails, HostResponse, Message, Recipient, Status, MAIL_SENDER_VERIFIED,
+    RCPT_STATUS_CHANGED,
 };
 
 impl Message {
@@ -65,6 +66,7 @@ impl Message {
                     recipients: recipient_addresses,
                     message_blob: self.blob_hash.clone(),
                     message_size: self.size,
+                    sender_verified: (self.flags & MAIL_SENDER_VERIFIED) != 0,
                 },
                 result_tx,
             })
@@ -0,0 +1,186 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::net::IpAddr;
+
+use common::config::smtp::queue::IpPool;
+use dashmap::DashMap;
+use store::write::now;
+
+use crate::{
+    core::SMTP,
+    queue::{Error, HostResponse, Recipient, Status},
+};
+
+#[derive(Default)]
+pub struct IpPoolState {
+    cursors: DashMap<String, u64>,
+    volume: DashMap<IpAddr, DailyVolume>,
+    blocked: DashMap<(IpAddr, String), u64>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct DailyVolume {
+    day: u64,
+    count: u64,
+}
+
+impl IpPoolState {
+    fn is_blocked(&self, ip: IpAddr, destination: &str, now: u64) -> bool {
+        let key = (ip, destination.to_string());
+        self.blocked.remove_if(&key, |_, until| *until <= now);
+        self.blocked.contains_key(&key)
+    }
+
+    pub fn cleanup(&self) {
+        let now = now();
+        self.blocked.retain(|_, until| *until > now);
+    }
+}
+
+impl SMTP {
+    pub fn select_pool_ip(&self, pool_id: &str, destination: &str, ipv4: bool) -> Option<IpAddr> {
+        let pool = self.core.smtp.queue.ip_pools.get(pool_id)?;
+        let state = &self.inner.ip_pools;
+        let now = now();
+        let today = now / 86400;
+
+        // Obtain addresses that are neither blocked nor over their daily limit
+        let candidates = pool
+            .addresses
+            .iter()
+            .filter(|addr| {
+                addr.ip.is_ipv4() == ipv4
+                    && !state.is_blocked(addr.ip, destination, now)
+                    && addr.daily_limit_at(now).is_none_or(|limit| {
+                        state.volume.get(&addr.ip).map_or(0, |volume| {
+                            if volume.day == today {
+                                volume.count
+                            } else {
+                                0
+                            }
+                        }) < limit
+                    })
+            })
+            .collect::<Vec<_>>();
+        let total_weight = candidates
+            .iter()
+            .map(|addr| addr.weight as u64)
+            .sum::<u64>();
+        if total_weight == 0 {
+            return None;
+        }
+
+        // Weighted round-robin
+        let mut cursor = {
+            let mut cursor = state.cursors.entry(pool_id.to_string()).or_default();
+            *cursor = cursor.wrapping_add(1);
+            *cursor % total_weight
+        };
+        for addr in &candidates {
+            if cursor < addr.weight as u64 {
+                return Some(addr.ip);
+            }
+            cursor -= addr.weight as u64;
+        }
+
+        None
+    }
+
+    pub fn record_pool_delivery<'x>(
+        &self,
+        pool_id: &str,
+        source_ip: IpAddr,
+        destination: &str,
+        status: &Status<(), Error>,
+        recipients: impl Iterator<Item = &'x Recipient>,
+    ) {
+        let Some(pool) = self.core.smtp.queue.ip_pools.get(pool_id) else {
+            return;
+        };
+
+        // Look for blocklist-style rejections
+        let mut is_blocked = match status {
+            Status::PermanentFailure(Error::UnexpectedResponse(response)) => {
+                pool.is_block_response(response)
+            }
+            _ => false,
+        };
+        let mut has_delivered = false;
+        for rcpt in recipients {
+            match &rcpt.status {
+                Status::Completed(_) => {
+                    has_delivered = true;
+                }
+                Status::PermanentFailure(response) if !is_blocked => {
+                    is_blocked = pool.is_block_response(response);
+                }
+                _ => (),
+            }
+        }
+
+        if is_blocked {
+            tracing::warn!(
+                context = "ip-pool",
+                event = "blocked",
+                pool = pool_id,
+                source_ip = %source_ip,
+                destination = destination,
+                "Removing source IP from rotation after blocklist rejection."
+            );
+
+            self.inner.ip_pools.blocked.insert(
+                (source_ip, destination.to_string()),
+                now() + pool.block_duration.as_secs(),
+            );
+        } else if has_delivered {
+            let today = now() / 86400;
+            let mut volume = self.inner.ip_pools.volume.entry(source_ip).or_default();
+            if volume.day != today {
+                *volume = DailyVolume {
+                    day: today,
+                    count: 0,
+                };
+            }
+            volume.count += 1;
+        }
+    }
+}
+
+trait IsBlockResponse {
+    fn is_block_response<T>(&self, response: &HostResponse<T>) -> bool;
+}
+
+impl IsBlockResponse for IpPool {
+    fn is_block_response<T>(&self, response: &HostResponse<T>) -> bool {
+        let response = &response.response;
+        if (500..600).contains(&response.code) {
+            let message = response.message.to_lowercase();
+            self.block_patterns
+                .iter()
+                .any(|pattern| message.contains(pattern.as_str()))
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,268 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use common::listener::limiter::{ConcurrencyLimiter, InFlight};
+use dashmap::DashMap;
+use store::write::now;
+
+use crate::core::SMTP;
+
+use super::{throttle, Error, ErrorDetails, HostResponse, Recipient, Status};
+
+#[derive(Default)]
+pub struct AdaptiveThrottle {
+    hosts: DashMap<String, HostThrottle>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HostThrottle {
+    pub concurrency: u64,
+    pub interval: u64,
+    pub next_delivery: u64,
+    pub deferral_rate: f64,
+    pub attempts: u64,
+    pub deferrals: u64,
+    pub successes: u64,
+    pub last_deferral: u64,
+    pub in_flight: Arc<AtomicU64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Deferred,
+    Accepted,
+    Other,
+}
+
+// Weight given to the most recent outcome when updating the deferral rate
+const DEFERRAL_RATE_ALPHA: f64 = 0.1;
+
+impl SMTP {
+    pub fn is_adaptive_allowed(
+        &self,
+        hostname: &str,
+        in_flight: &mut Vec<InFlight>,
+    ) -> Result<(), throttle::Error> {
+        if self.core.smtp.queue.adaptive_throttle.is_none() {
+            return Ok(());
+        }
+
+        if let Some(mut host) = self.inner.adaptive_throttle.hosts.get_mut(hostname) {
+            let now = now();
+            if host.next_delivery > now {
+                return Err(throttle::Error::Rate {
+                    retry_at: host.next_delivery,
+                });
+            }
+
+            let limiter = ConcurrencyLimiter {
+                max_concurrent: host.concurrency,
+                concurrent: host.in_flight.clone(),
+            };
+            if let Some(inflight) = limiter.is_allowed() {
+                in_flight.push(inflight);
+                host.next_delivery = now + host.interval;
+            } else {
+                return Err(throttle::Error::Concurrency { limiter });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn record_adaptive_outcome<'x>(
+        &self,
+        hostname: &str,
+        status: &Status<(), Error>,
+        recipients: impl Iterator<Item = &'x Recipient>,
+    ) {
+        let Some(config) = &self.core.smtp.queue.adaptive_throttle else {
+            return;
+        };
+        let outcome = Outcome::from_delivery(status, recipients);
+        let hosts = &self.inner.adaptive_throttle.hosts;
+
+        match outcome {
+            Outcome::Deferred => {
+                let now = now();
+                let mut host = hosts
+                    .entry(hostname.to_string())
+                    .or_insert_with(|| HostThrottle {
+                        concurrency: config.max_concurrency,
+                        interval: 0,
+                        next_delivery: 0,
+                        deferral_rate: 0.0,
+                        attempts: 0,
+                        deferrals: 0,
+                        successes: 0,
+                        last_deferral: 0,
+                        in_flight: Default::default(),
+                    });
+                host.record(outcome);
+
+                // Back off at most once per second to avoid collapsing on a burst of deferrals
+                if host.last_deferral != now {
+                    host.last_deferral = now;
+                    host.concurrency = ((host.concurrency as f64 * config.backoff_factor) as u64)
+                        .max(config.min_concurrency);
+                    host.interval = (host.interval * 2)
+                        .max(config.min_interval.as_secs())
+                        .min(config.max_interval.as_secs());
+                    host.next_delivery = now + host.interval;
+
+                    tracing::info!(
+                        context = "throttle",
+                        event = "adaptive-backoff",
+                        mx = hostname,
+                        concurrency = host.concurrency,
+                        interval = host.interval,
+                        deferral_rate = host.deferral_rate,
+                        "Remote host deferred delivery, backing off."
+                    );
+                }
+            }
+            Outcome::Accepted => {
+                let is_recovered = if let Some(mut host) = hosts.get_mut(hostname) {
+                    host.record(outcome);
+
+                    if host.successes >= config.recovery_threshold {
+                        host.successes = 0;
+                        host.concurrency = (host.concurrency + 1).min(config.max_concurrency);
+                        host.interval /= 2;
+                        if host.interval < config.min_interval.as_secs() {
+                            host.interval = 0;
+                        }
+
+                        tracing::debug!(
+                            context = "throttle",
+                            event = "adaptive-recovery",
+                            mx = hostname,
+                            concurrency = host.concurrency,
+                            interval = host.interval,
+                            deferral_rate = host.deferral_rate,
+                        );
+                    }
+
+                    host.concurrency == config.max_concurrency
+                        && host.interval == 0
+                        && host.in_flight.load(Ordering::Relaxed) == 0
+                } else {
+                    false
+                };
+
+                if is_recovered {
+                    hosts.remove(hostname);
+                }
+            }
+            Outcome::Other => {
+                if let Some(mut host) = hosts.get_mut(hostname) {
+                    host.record(outcome);
+                }
+            }
+        }
+    }
+
+    pub fn adaptive_throttle_status(&self) -> Vec<(String, HostThrottle)> {
+        let mut hosts = self
+            .inner
+            .adaptive_throttle
+            .hosts
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect::<Vec<_>>();
+        hosts.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        hosts
+    }
+}
+
+impl HostThrottle {
+    fn record(&mut self, outcome: Outcome) {
+        let is_deferred = outcome == Outcome::Deferred;
+        self.attempts += 1;
+        if is_deferred {
+            self.deferrals += 1;
+            self.successes = 0;
+        } else if outcome == Outcome::Accepted {
+            self.successes += 1;
+        }
+        self.deferral_rate = self.deferral_rate * (1.0 - DEFERRAL_RATE_ALPHA)
+            + if is_deferred {
+                DEFERRAL_RATE_ALPHA
+            } else {
+                0.0
+            };
+    }
+}
+
+impl Outcome {
+    pub fn from_delivery<'x>(
+        status: &Status<(), Error>,
+        recipients: impl Iterator<Item = &'x Recipient>,
+    ) -> Self {
+        if let Status::TemporaryFailure(Error::UnexpectedResponse(response)) = status {
+            if response.is_deferral() {
+                return Outcome::Deferred;
+            }
+        }
+
+        // Decide from all recipients together, so that a deferral for one
+        // recipient does not throttle a host that accepted the others.
+        let mut deferred = 0;
+        let mut accepted = 0;
+        for rcpt in recipients {
+            match &rcpt.status {
+                Status::TemporaryFailure(response) if response.is_deferral() => {
+                    deferred += 1;
+                }
+                Status::Completed(_) => {
+                    accepted += 1;
+                }
+                _ => (),
+            }
+        }
+
+        if deferred > accepted {
+            Outcome::Deferred
+        } else if accepted > 0 || matches!(status, Status::Completed(_)) {
+            Outcome::Accepted
+        } else {
+            Outcome::Other
+        }
+    }
+}
+
+impl HostResponse<ErrorDetails> {
+    // Only session or rate level deferrals count, such as 421 or 451 4.7.x
+    fn is_deferral(&self) -> bool {
+        match self.response.code {
+            421 => true,
+            451 => self.response.esc[0] == 4 && self.response.esc[1] == 7,
+            _ => false,
+        }
+    }
+}
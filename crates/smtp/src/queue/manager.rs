@@ -25,6 +25,7 @@ use std::{sync::atomic::Ordering, time::Duration};
 
 use store::write::now;
 use tokio::sync::mpsc;
+use utils::metrics::QUEUE_MESSAGES_ON_HOLD;
 
 use crate::core::{SmtpInstance, SMTP};
 
@@ -59,6 +60,7 @@ impl SpawnQueue for mpsc::Receiver<Event> {
                 if let Some(on_hold) = on_hold {
                     queue.on_hold(on_hold);
                 }
+                QUEUE_MESSAGES_ON_HOLD.set(queue.on_hold.len() as i64);
             }
         });
     }
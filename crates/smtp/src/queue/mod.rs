@@ -38,11 +38,14 @@ use utils::BlobHash;
 
 use self::spool::QueueEventLock;
 
+pub mod adaptive;
 pub mod dsn;
 pub mod manager;
+pub mod quarantine;
 pub mod quota;
 pub mod spool;
 pub mod throttle;
+pub mod trace;
 
 pub type QueueId = u64;
 
@@ -114,6 +117,9 @@ pub struct Recipient {
 pub const RCPT_DSN_SENT: u64 = 1 << 32;
 pub const RCPT_STATUS_CHANGED: u64 = 2 << 32;
 
+pub const MAIL_RELAY_OVERRIDE: u64 = 1 << 48;
+pub const MAIL_SENDER_VERIFIED: u64 = 2 << 48;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status<T, E> {
     #[serde(rename = "scheduled")]
@@ -151,6 +157,41 @@ pub struct ErrorDetails {
     pub details: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub domain: String,
+    pub retry_num: u32,
+    pub attempted_at: u64,
+    pub duration: u64,
+    pub mx: Option<String>,
+    pub tls: Option<HistoryTls>,
+    pub mta_sts: PolicyResult,
+    pub dane: PolicyResult,
+    pub status: Status<(), Error>,
+    pub recipients: Vec<HistoryRecipient>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryTls {
+    pub version: String,
+    pub cipher: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyResult {
+    #[default]
+    None,
+    Pass,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryRecipient {
+    pub address: String,
+    pub status: Status<HostResponse<String>, HostResponse<ErrorDetails>>,
+}
+
 pub struct DeliveryAttempt {
     pub in_flight: Vec<InFlight>,
     pub event: QueueEventLock,
@@ -348,6 +389,15 @@ pub trait InstantFromTimestamp {
     fn to_instant(&self) -> Instant;
 }
 
+impl HistoryTls {
+    pub fn from_connection(conn: &rustls::ClientConnection) -> Option<Self> {
+        Some(HistoryTls {
+            version: format!("{:?}", conn.protocol_version()?),
+            cipher: format!("{:?}", conn.negotiated_cipher_suite()?.suite()),
+        })
+    }
+}
+
 impl InstantFromTimestamp for u64 {
     fn to_instant(&self) -> Instant {
         let timestamp = *self;
@@ -0,0 +1,510 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{borrow::Cow, fmt::Write, net::IpAddr};
+
+use ahash::AHashMap;
+use mail_builder::{
+    headers::{address::Address, HeaderType},
+    MessageBuilder,
+};
+use mail_parser::{DateTime, MessageParser};
+use serde::{Deserialize, Serialize};
+use store::{
+    write::{
+        assert::AssertValue, key::DeserializeBigEndian, now, BatchBuilder, Bincode, BlobOp,
+        QueueClass, ValueClass,
+    },
+    Deserialize as _, IterateParams, Serialize as _, ValueKey, U64_LEN,
+};
+use utils::BlobHash;
+
+use crate::core::SMTP;
+
+use super::{DomainPart, Message, QueueId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuarantineSource {
+    Sieve,
+    Milter,
+    MtaHook,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantinedMessage {
+    pub message: Message,
+    pub reason: String,
+    pub source: QuarantineSource,
+    pub remote_ip: IpAddr,
+    pub subject: Option<String>,
+    pub quarantined_at: u64,
+    pub expires: u64,
+    pub digest_sent: bool,
+}
+
+impl Message {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn quarantine(
+        mut self,
+        raw_headers: Option<&[u8]>,
+        raw_message: &[u8],
+        reason: String,
+        source: QuarantineSource,
+        remote_ip: IpAddr,
+        core: &SMTP,
+        span: &tracing::Span,
+    ) -> bool {
+        // Build blob
+        let message = if let Some(raw_headers) = raw_headers {
+            let mut message = Vec::with_capacity(raw_headers.len() + raw_message.len());
+            message.extend_from_slice(raw_headers);
+            message.extend_from_slice(raw_message);
+            Cow::Owned(message)
+        } else {
+            raw_message.into()
+        };
+        self.blob_hash = BlobHash::from(message.as_ref());
+        self.size = message.len();
+
+        // The blob is reserved until the quarantine entry expires
+        let quarantined_at = now();
+        let expires = quarantined_at + core.core.smtp.queue.quarantine.retention.as_secs();
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            BlobOp::Reserve {
+                hash: self.blob_hash.clone(),
+                until: expires,
+            },
+            0u32.serialize(),
+        );
+        if let Err(err) = core.core.storage.data.write(batch.build()).await {
+            tracing::error!(
+                parent: span,
+                context = "quarantine",
+                event = "error",
+                "Failed to write to data store: {}",
+                err
+            );
+            return false;
+        }
+        if let Err(err) = core
+            .core
+            .storage
+            .blob
+            .put_blob(self.blob_hash.as_slice(), message.as_ref())
+            .await
+        {
+            tracing::error!(
+                parent: span,
+                context = "quarantine",
+                event = "error",
+                "Failed to write to blob store: {}",
+                err
+            );
+            return false;
+        }
+
+        tracing::info!(
+            parent: span,
+            context = "quarantine",
+            event = "quarantined",
+            id = self.id,
+            from = if !self.return_path.is_empty() {
+                self.return_path.as_str()
+            } else {
+                "<>"
+            },
+            nrcpts = self.recipients.len(),
+            size = self.size,
+            reason = reason,
+            "Message quarantined."
+        );
+
+        // Write quarantine entry
+        let entry = QuarantinedMessage {
+            subject: MessageParser::new()
+                .parse_headers(message.as_ref())
+                .and_then(|message| message.subject().map(|subject| subject.to_string())),
+            message: self,
+            reason,
+            source,
+            remote_ip,
+            quarantined_at,
+            expires,
+            digest_sent: false,
+        };
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::Queue(QueueClass::Quarantine {
+                id: entry.message.id,
+                expires,
+            }),
+            Bincode::new(entry).serialize(),
+        );
+        if let Err(err) = core.core.storage.data.write(batch.build()).await {
+            tracing::error!(
+                parent: span,
+                context = "quarantine",
+                event = "error",
+                "Failed to write to store: {}",
+                err
+            );
+            false
+        } else {
+            true
+        }
+    }
+}
+
+impl SMTP {
+    pub async fn list_quarantined(&self) -> Vec<QuarantinedMessage> {
+        self.iterate_quarantined(0, u64::MAX).await
+    }
+
+    pub async fn read_quarantined(&self, id: QueueId) -> Option<QuarantinedMessage> {
+        self.iterate_quarantined(id, id).await.pop()
+    }
+
+    async fn iterate_quarantined(
+        &self,
+        from_id: QueueId,
+        to_id: QueueId,
+    ) -> Vec<QuarantinedMessage> {
+        let from_key = ValueKey::from(ValueClass::Queue(QueueClass::Quarantine {
+            id: from_id,
+            expires: 0,
+        }));
+        let to_key = ValueKey::from(ValueClass::Queue(QueueClass::Quarantine {
+            id: to_id,
+            expires: u64::MAX,
+        }));
+        let now = now();
+        let mut entries = Vec::new();
+        if let Err(err) = self
+            .core
+            .storage
+            .data
+            .iterate(IterateParams::new(from_key, to_key), |key, value| {
+                if key.deserialize_be_u64(U64_LEN)? > now {
+                    entries.push(Bincode::<QuarantinedMessage>::deserialize(value)?.inner);
+                }
+                Ok(true)
+            })
+            .await
+        {
+            tracing::error!(
+                context = "quarantine",
+                event = "error",
+                "Failed to read quarantined messages from store: {}",
+                err
+            );
+        }
+
+        entries
+    }
+
+    pub async fn read_quarantined_blob(&self, entry: &QuarantinedMessage) -> Option<Vec<u8>> {
+        match self
+            .core
+            .storage
+            .blob
+            .get_blob(entry.message.blob_hash.as_slice(), 0..usize::MAX)
+            .await
+        {
+            Ok(blob) => blob,
+            Err(err) => {
+                tracing::error!(
+                    context = "quarantine",
+                    event = "error",
+                    "Failed to read quarantined message {:?} from blob store: {}",
+                    entry.message.blob_hash,
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    pub async fn delete_quarantined(&self, entry: &QuarantinedMessage) -> bool {
+        self.remove_quarantined(entry, true).await
+    }
+
+    async fn remove_quarantined(&self, entry: &QuarantinedMessage, release_blob: bool) -> bool {
+        // Assert that the entry still exists, so that a concurrent release or
+        // deletion of the same message fails instead of delivering it twice.
+        let class = ValueClass::Queue(QueueClass::Quarantine {
+            id: entry.message.id,
+            expires: entry.expires,
+        });
+        let mut batch = BatchBuilder::new();
+        batch
+            .assert_value(class.clone(), AssertValue::Some)
+            .clear(class);
+        if release_blob {
+            batch.clear(BlobOp::Reserve {
+                hash: entry.message.blob_hash.clone(),
+                until: entry.expires,
+            });
+        }
+
+        match self.core.storage.data.write(batch.build()).await {
+            Ok(_) => true,
+            Err(store::Error::AssertValueFailed) => {
+                tracing::debug!(
+                    context = "quarantine",
+                    event = "not-found",
+                    id = entry.message.id,
+                    "Quarantined message was already released or deleted."
+                );
+                false
+            }
+            Err(err) => {
+                tracing::error!(
+                    context = "quarantine",
+                    event = "error",
+                    "Failed to delete quarantined message: {}",
+                    err
+                );
+                false
+            }
+        }
+    }
+
+    pub async fn release_quarantined(
+        &self,
+        entry: QuarantinedMessage,
+        relay_host: Option<String>,
+    ) -> bool {
+        let span = tracing::info_span!(
+            "quarantine-release",
+            id = entry.message.id,
+            relay_host = relay_host.as_deref().unwrap_or_default()
+        );
+        let raw_message = if let Some(raw_message) = self.read_quarantined_blob(&entry).await {
+            raw_message
+        } else {
+            return false;
+        };
+
+        // Reschedule the message as if it had just been received
+        let mut message = entry.message.clone();
+        let delta = now().saturating_sub(message.created);
+        message.created += delta;
+        for domain in &mut message.domains {
+            domain.retry.due += delta;
+            domain.notify.due += delta;
+            domain.expires += delta;
+        }
+
+        if !self.has_quota(&mut message).await {
+            tracing::warn!(
+                parent: &span,
+                context = "quarantine",
+                event = "quota-exceeded",
+                "Queue quota exceeded, unable to release message."
+            );
+            return false;
+        }
+
+        // Remove the entry before queueing the message, the blob stays reserved
+        // until the message has been queued.
+        if !self.remove_quarantined(&entry, false).await {
+            return false;
+        }
+
+        // Apply the relay host override before queueing the message
+        let mut relay_saved = true;
+        if relay_host.is_some() {
+            let mut batch = BatchBuilder::new();
+            message.set_relay_host(&mut batch, relay_host);
+            if let Err(err) = self.core.storage.data.write(batch.build()).await {
+                tracing::error!(
+                    parent: &span,
+                    context = "quarantine",
+                    event = "error",
+                    "Failed to set message relay host: {}",
+                    err
+                );
+                relay_saved = false;
+            }
+        }
+
+        if relay_saved && message.queue(None, &raw_message, self, &span).await {
+            let mut batch = BatchBuilder::new();
+            batch.clear(BlobOp::Reserve {
+                hash: entry.message.blob_hash.clone(),
+                until: entry.expires,
+            });
+            if let Err(err) = self.core.storage.data.write(batch.build()).await {
+                tracing::error!(
+                    parent: &span,
+                    context = "quarantine",
+                    event = "error",
+                    "Failed to release blob reservation: {}",
+                    err
+                );
+            }
+            true
+        } else {
+            // Put the message back into quarantine
+            let mut batch = BatchBuilder::new();
+            batch.set(
+                ValueClass::Queue(QueueClass::Quarantine {
+                    id: entry.message.id,
+                    expires: entry.expires,
+                }),
+                Bincode::new(entry).serialize(),
+            );
+            if let Err(err) = self.core.storage.data.write(batch.build()).await {
+                tracing::error!(
+                    parent: &span,
+                    context = "quarantine",
+                    event = "error",
+                    "Failed to restore quarantined message: {}",
+                    err
+                );
+            }
+            false
+        }
+    }
+
+    pub async fn send_quarantine_digest(&self) {
+        let config = if let Some(config) = &self.core.smtp.queue.quarantine.digest {
+            config
+        } else {
+            return;
+        };
+
+        // Group pending entries by local recipient
+        let entries = self
+            .list_quarantined()
+            .await
+            .into_iter()
+            .filter(|entry| !entry.digest_sent)
+            .collect::<Vec<_>>();
+        let mut local_domains = AHashMap::new();
+        let mut digests: AHashMap<String, Vec<&QuarantinedMessage>> = AHashMap::new();
+        for entry in &entries {
+            for rcpt in &entry.message.recipients {
+                let domain = rcpt.address_lcase.domain_part();
+                let is_local = if let Some(is_local) = local_domains.get(domain) {
+                    *is_local
+                } else {
+                    let is_local = self
+                        .core
+                        .storage
+                        .directory
+                        .is_local_domain(domain)
+                        .await
+                        .unwrap_or(false);
+                    local_domains.insert(domain.to_string(), is_local);
+                    is_local
+                };
+
+                if is_local {
+                    digests
+                        .entry(rcpt.address_lcase.clone())
+                        .or_default()
+                        .push(entry);
+                }
+            }
+        }
+
+        // Send digests
+        let span = tracing::info_span!("quarantine-digest");
+        for (rcpt, entries) in digests {
+            let mut text = format!(
+                "The following {} message(s) addressed to {} have been quarantined:\r\n\r\n",
+                entries.len(),
+                rcpt
+            );
+            for entry in &entries {
+                let _ = write!(
+                    &mut text,
+                    "Id: {:X}\r\nDate: {}\r\nFrom: {}\r\nSubject: {}\r\nReason: {}\r\n\r\n",
+                    entry.message.id,
+                    DateTime::from_timestamp(entry.quarantined_at as i64).to_rfc822(),
+                    if !entry.message.return_path.is_empty() {
+                        entry.message.return_path.as_str()
+                    } else {
+                        "<>"
+                    },
+                    entry.subject.as_deref().unwrap_or_default(),
+                    entry.reason
+                );
+            }
+            let _ = write!(
+                &mut text,
+                "Quarantined messages are deleted on {}.\r\nPlease contact your administrator to release a message.\r\n",
+                entries
+                    .iter()
+                    .map(|entry| entry.expires)
+                    .min()
+                    .map(|expires| DateTime::from_timestamp(expires as i64).to_rfc822())
+                    .unwrap_or_default()
+            );
+            let digest = MessageBuilder::new()
+                .from((config.from_name.as_str(), config.from_address.as_str()))
+                .to(Address::new_address(None::<&str>, rcpt.as_str()))
+                .header("Auto-Submitted", HeaderType::Text("auto-generated".into()))
+                .subject(config.subject.as_str())
+                .text_body(text)
+                .write_to_vec()
+                .unwrap_or_default();
+
+            let from_addr_lcase = config.from_address.to_lowercase();
+            let from_addr_domain = from_addr_lcase.domain_part().to_string();
+            let mut message =
+                self.new_message(&config.from_address, from_addr_lcase, from_addr_domain);
+            message.add_recipient(rcpt.as_str(), self).await;
+            message.queue(None, &digest, self, &span).await;
+        }
+
+        // Mark entries as notified, skipping any that were released or deleted
+        // while the digest was being sent.
+        for mut entry in entries {
+            let class = ValueClass::Queue(QueueClass::Quarantine {
+                id: entry.message.id,
+                expires: entry.expires,
+            });
+            entry.digest_sent = true;
+            let mut batch = BatchBuilder::new();
+            batch
+                .assert_value(class.clone(), AssertValue::Some)
+                .set(class, Bincode::new(entry).serialize());
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) | Err(store::Error::AssertValueFailed) => (),
+                Err(err) => {
+                    tracing::error!(
+                        parent: &span,
+                        context = "quarantine",
+                        event = "error",
+                        "Failed to update quarantined message: {}",
+                        err
+                    );
+                }
+            }
+        }
+    }
+}
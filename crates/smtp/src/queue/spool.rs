@@ -27,15 +27,18 @@ use std::time::{Duration, SystemTime};
 use store::write::key::DeserializeBigEndian;
 use store::write::{now, BatchBuilder, Bincode, BlobOp, QueueClass, QueueEvent, ValueClass};
 use store::{Deserialize, IterateParams, Serialize, ValueKey, U64_LEN};
+use utils::metrics::QUEUE_MESSAGES_SCHEDULED;
 use utils::BlobHash;
 
 use crate::core::SMTP;
 
 use super::{
-    Domain, Event, Message, QueueEnvelope, QueueId, QuotaKey, Recipient, Schedule, Status,
+    Domain, Event, HistoryEntry, Message, QueueEnvelope, QueueId, QuotaKey, Recipient, Schedule,
+    Status, MAIL_RELAY_OVERRIDE,
 };
 
 pub const LOCK_EXPIRY: u64 = 300;
+pub const HISTORY_MAX_ENTRIES: usize = 50;
 
 #[derive(Debug)]
 pub struct QueueEventLock {
@@ -44,6 +47,15 @@ pub struct QueueEventLock {
     pub lock_expiry: u64,
 }
 
+pub enum LockedMessage {
+    Locked {
+        message: Box<Message>,
+        event: QueueEventLock,
+    },
+    Busy,
+    NotFound,
+}
+
 impl SMTP {
     pub fn new_message(
         &self,
@@ -161,6 +173,94 @@ impl SMTP {
         }
     }
 
+    pub async fn lock_message(&self, id: QueueId) -> LockedMessage {
+        let Some(message) = self.read_message(id).await else {
+            return LockedMessage::NotFound;
+        };
+        let due = message.next_event().unwrap_or_default();
+
+        // Obtain the current lock, if any
+        let lock_expiry = match self
+            .core
+            .storage
+            .data
+            .get_value::<u64>(ValueKey::from(ValueClass::Queue(QueueClass::MessageEvent(
+                QueueEvent { due, queue_id: id },
+            ))))
+            .await
+        {
+            Ok(Some(lock_expiry)) if lock_expiry < now() => lock_expiry,
+            Ok(_) => return LockedMessage::Busy,
+            Err(err) => {
+                tracing::error!(
+                    context = "queue",
+                    event = "error",
+                    "Failed to read queue event from store: {}",
+                    err
+                );
+                return LockedMessage::Busy;
+            }
+        };
+
+        // Lock the event and fetch the latest version of the message
+        if let Some(event) = self
+            .try_lock_event(QueueEventLock {
+                due,
+                queue_id: id,
+                lock_expiry,
+            })
+            .await
+        {
+            if let Some(message) = self.read_message(id).await {
+                LockedMessage::Locked {
+                    message: Box::new(message),
+                    event,
+                }
+            } else {
+                LockedMessage::NotFound
+            }
+        } else {
+            LockedMessage::Busy
+        }
+    }
+
+    pub async fn unlock_event(&self, event: QueueEventLock) {
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::Queue(QueueClass::MessageEvent(QueueEvent {
+                due: event.due,
+                queue_id: event.queue_id,
+            })),
+            0u64.serialize(),
+        );
+        if let Err(err) = self.core.storage.data.write(batch.build()).await {
+            tracing::error!(context = "queue", event = "error", "Unlock error: {}", err);
+        }
+    }
+
+    pub async fn read_message_relay(&self, id: QueueId) -> Option<String> {
+        match self
+            .core
+            .storage
+            .data
+            .get_value::<String>(ValueKey::from(ValueClass::Queue(QueueClass::MessageRelay(
+                id,
+            ))))
+            .await
+        {
+            Ok(relay_host) => relay_host,
+            Err(err) => {
+                tracing::error!(
+                    context = "queue",
+                    event = "error",
+                    "Failed to read message relay host from store: {}",
+                    err
+                );
+                None
+            }
+        }
+    }
+
     pub async fn read_message(&self, id: QueueId) -> Option<Message> {
         match self
             .core
@@ -184,6 +284,30 @@ impl SMTP {
             }
         }
     }
+
+    pub async fn read_message_history(&self, id: QueueId) -> Vec<HistoryEntry> {
+        match self
+            .core
+            .storage
+            .data
+            .get_value::<Bincode<Vec<HistoryEntry>>>(ValueKey::from(ValueClass::Queue(
+                QueueClass::MessageHistory(id),
+            )))
+            .await
+        {
+            Ok(Some(history)) => history.inner,
+            Ok(None) => Vec::new(),
+            Err(err) => {
+                tracing::error!(
+                    context = "queue",
+                    event = "error",
+                    "Failed to read message history from store: {}",
+                    err
+                );
+                Vec::new()
+            }
+        }
+    }
 }
 
 impl Message {
@@ -303,11 +427,12 @@ impl Message {
                     hash: self.blob_hash.clone(),
                 },
                 vec![],
-            )
-            .set(
-                ValueClass::Queue(QueueClass::Message(self.id)),
-                Bincode::new(self).serialize(),
             );
+        self.trace_received(core, message.as_ref(), &mut batch);
+        batch.set(
+            ValueClass::Queue(QueueClass::Message(self.id)),
+            Bincode::new(self).serialize(),
+        );
 
         if let Err(err) = core.core.storage.data.write(batch.build()).await {
             tracing::error!(
@@ -319,6 +444,7 @@ impl Message {
             );
             return false;
         }
+        QUEUE_MESSAGES_SCHEDULED.increment();
 
         // Queue the message
         if core.inner.queue_tx.send(Event::Reload).await.is_err() {
@@ -387,20 +513,30 @@ impl Message {
     }
 
     pub async fn save_changes(
+        self,
+        core: &SMTP,
+        prev_event: Option<u64>,
+        next_event: Option<u64>,
+    ) -> bool {
+        self.save_changes_with(core, BatchBuilder::new(), prev_event, next_event)
+            .await
+    }
+
+    /// Saves the message along with any other changes already added to `batch`,
+    /// so that they are written atomically.
+    pub async fn save_changes_with(
         mut self,
         core: &SMTP,
+        mut batch: BatchBuilder,
         prev_event: Option<u64>,
         next_event: Option<u64>,
     ) -> bool {
         debug_assert!(prev_event.is_some() == next_event.is_some());
 
-        let mut batch = BatchBuilder::new();
-
         // Release quota for completed deliveries
         self.release_quota(&mut batch);
 
         // Update message queue
-        let mut batch = BatchBuilder::new();
         if let (Some(prev_event), Some(next_event)) = (prev_event, next_event) {
             batch
                 .clear(ValueClass::Queue(QueueClass::MessageEvent(QueueEvent {
@@ -437,6 +573,9 @@ impl Message {
     pub async fn remove(self, core: &SMTP, prev_event: u64) -> bool {
         let mut batch = BatchBuilder::new();
 
+        // Record final delivery status
+        self.trace_completed(core, &mut batch).await;
+
         // Release all quotas
         for quota_key in self.quota_keys {
             match quota_key {
@@ -461,7 +600,11 @@ impl Message {
                 due: prev_event,
                 queue_id: self.id,
             })))
-            .clear(ValueClass::Queue(QueueClass::Message(self.id)));
+            .clear(ValueClass::Queue(QueueClass::Message(self.id)))
+            .clear(ValueClass::Queue(QueueClass::MessageHistory(self.id)));
+        if (self.flags & MAIL_RELAY_OVERRIDE) != 0 {
+            batch.clear(ValueClass::Queue(QueueClass::MessageRelay(self.id)));
+        }
 
         if let Err(err) = core.core.storage.data.write(batch.build()).await {
             tracing::error!(
@@ -475,4 +618,43 @@ impl Message {
             true
         }
     }
+
+    pub fn set_relay_host(&mut self, batch: &mut BatchBuilder, relay_host: Option<String>) {
+        if let Some(relay_host) = relay_host {
+            self.flags |= MAIL_RELAY_OVERRIDE;
+            batch.set(
+                ValueClass::Queue(QueueClass::MessageRelay(self.id)),
+                relay_host.into_bytes(),
+            );
+        } else {
+            self.flags &= !MAIL_RELAY_OVERRIDE;
+            batch.clear(ValueClass::Queue(QueueClass::MessageRelay(self.id)));
+        }
+    }
+
+    pub async fn append_history(&self, core: &SMTP, entries: Vec<HistoryEntry>) -> bool {
+        let mut history = core.read_message_history(self.id).await;
+        history.extend(entries);
+        if history.len() > HISTORY_MAX_ENTRIES {
+            history.drain(..history.len() - HISTORY_MAX_ENTRIES);
+        }
+
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::Queue(QueueClass::MessageHistory(self.id)),
+            Bincode::new(history).serialize(),
+        );
+
+        if let Err(err) = core.core.storage.data.write(batch.build()).await {
+            tracing::error!(
+                context = "queue",
+                event = "error",
+                "Failed to update message history: {}",
+                err
+            );
+            false
+        } else {
+            true
+        }
+    }
 }
@@ -0,0 +1,146 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use mail_parser::MessageParser;
+use serde::{Deserialize, Serialize};
+use store::{
+    write::{now, BatchBuilder, Bincode, QueueClass, ValueClass},
+    Serialize as _, ValueKey,
+};
+
+use crate::core::SMTP;
+
+use super::{ErrorDetails, HostResponse, Message, QueueId, Status};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageTrace {
+    pub id: QueueId,
+    pub received_at: u64,
+    pub completed_at: Option<u64>,
+    pub return_path: String,
+    pub message_id: Option<String>,
+    pub size: usize,
+    pub recipients: Vec<TraceRecipient>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceRecipient {
+    pub address: String,
+    pub status: Status<HostResponse<String>, HostResponse<ErrorDetails>>,
+}
+
+// Traces of queued messages expire only once the message leaves the queue,
+// so that they are never purged while the message is still in flight.
+const TRACE_IN_FLIGHT: u64 = u64::MAX;
+
+impl Message {
+    pub(super) fn trace_received(&self, core: &SMTP, raw_message: &[u8], batch: &mut BatchBuilder) {
+        if core.core.smtp.queue.trace_retention.is_some() {
+            let message_id = MessageParser::new()
+                .parse_headers(raw_message)
+                .and_then(|message| message.message_id().map(|id| id.to_string()));
+
+            batch.set(
+                ValueClass::Queue(QueueClass::MessageTrace {
+                    id: self.id,
+                    expires: TRACE_IN_FLIGHT,
+                }),
+                Bincode::new(self.build_trace(message_id, None)).serialize(),
+            );
+        }
+    }
+
+    pub(super) async fn trace_completed(&self, core: &SMTP, batch: &mut BatchBuilder) {
+        let received_key = QueueClass::MessageTrace {
+            id: self.id,
+            expires: TRACE_IN_FLIGHT,
+        };
+
+        if let Some(retention) = core.core.smtp.queue.trace_retention {
+            // Obtain the Message-ID recorded when the message was queued
+            let message_id = match core
+                .core
+                .storage
+                .data
+                .get_value::<Bincode<MessageTrace>>(ValueKey::from(ValueClass::Queue(
+                    received_key.clone(),
+                )))
+                .await
+            {
+                Ok(trace) => trace.and_then(|trace| trace.inner.message_id),
+                Err(err) => {
+                    tracing::error!(
+                        context = "queue",
+                        event = "error",
+                        "Failed to read message trace from store: {}",
+                        err
+                    );
+                    None
+                }
+            };
+
+            // Retention starts once the message leaves the queue
+            let completed_at = now();
+            batch.set(
+                ValueClass::Queue(QueueClass::MessageTrace {
+                    id: self.id,
+                    expires: completed_at + retention.as_secs(),
+                }),
+                Bincode::new(self.build_trace(message_id, completed_at.into())).serialize(),
+            );
+        }
+
+        // Always remove the in-flight trace, tracing might have been disabled
+        // since the message was queued.
+        batch.clear(ValueClass::Queue(received_key));
+    }
+
+    fn build_trace(&self, message_id: Option<String>, completed_at: Option<u64>) -> MessageTrace {
+        MessageTrace {
+            id: self.id,
+            received_at: self.created,
+            completed_at,
+            return_path: self.return_path.clone(),
+            message_id,
+            size: self.size,
+            recipients: self
+                .recipients
+                .iter()
+                .map(|rcpt| TraceRecipient {
+                    address: rcpt.address.clone(),
+                    status: rcpt.status.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MessageTrace {
+    pub fn matches(&self, address: &str) -> bool {
+        self.return_path.to_lowercase().contains(address)
+            || self
+                .recipients
+                .iter()
+                .any(|rcpt| rcpt.address.to_lowercase().contains(address))
+    }
+}
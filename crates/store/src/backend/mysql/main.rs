@@ -112,8 +112,12 @@ impl MysqlStore {
             SUBSPACE_SETTINGS,
             SUBSPACE_QUEUE_MESSAGE,
             SUBSPACE_QUEUE_EVENT,
+            SUBSPACE_QUEUE_HISTORY,
+            SUBSPACE_QUEUE_RELAY,
             SUBSPACE_REPORT_OUT,
             SUBSPACE_REPORT_IN,
+            SUBSPACE_TRACE,
+            SUBSPACE_QUARANTINE,
             SUBSPACE_FTS_INDEX,
             SUBSPACE_LOGS,
         ] {
@@ -113,8 +113,12 @@ impl SqliteStore {
             SUBSPACE_SETTINGS,
             SUBSPACE_QUEUE_MESSAGE,
             SUBSPACE_QUEUE_EVENT,
+            SUBSPACE_QUEUE_HISTORY,
+            SUBSPACE_QUEUE_RELAY,
             SUBSPACE_REPORT_OUT,
             SUBSPACE_REPORT_IN,
+            SUBSPACE_TRACE,
+            SUBSPACE_QUARANTINE,
             SUBSPACE_FTS_INDEX,
             SUBSPACE_LOGS,
             SUBSPACE_BLOBS,
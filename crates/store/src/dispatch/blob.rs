@@ -23,7 +23,10 @@
 
 use std::{borrow::Cow, ops::Range};
 
-use utils::config::utils::ParseValue;
+use utils::{
+    config::utils::ParseValue,
+    metrics::{STORE_BLOB_READ, STORE_BLOB_WRITE},
+};
 
 use crate::{BlobBackend, BlobStore, CompressionAlgo, Store};
 
@@ -33,6 +36,7 @@ impl BlobStore {
         key: &[u8],
         range: Range<usize>,
     ) -> crate::Result<Option<Vec<u8>>> {
+        let _timer = STORE_BLOB_READ.start_timer();
         let read_range = match self.compression {
             CompressionAlgo::None => range.clone(),
             CompressionAlgo::Lz4 => 0..usize::MAX,
@@ -95,6 +99,7 @@ impl BlobStore {
     }
 
     pub async fn put_blob(&self, key: &[u8], data: &[u8]) -> crate::Result<()> {
+        let _timer = STORE_BLOB_WRITE.start_timer();
         let data: Cow<[u8]> = match self.compression {
             CompressionAlgo::None => data.into(),
             CompressionAlgo::Lz4 => {
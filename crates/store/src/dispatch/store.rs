@@ -24,15 +24,16 @@
 use std::ops::{BitAndAssign, Range};
 
 use roaring::RoaringBitmap;
+use utils::metrics::{STORE_DATA_READ, STORE_DATA_WRITE};
 
 use crate::{
     write::{
         key::{DeserializeBigEndian, KeySerializer},
         now, AnyClass, AnyKey, AssignedIds, Batch, BatchBuilder, BitmapClass, BitmapHash,
-        Operation, ReportClass, ValueClass, ValueOp,
+        Operation, QueueClass, ReportClass, ValueClass, ValueOp,
     },
     BitmapKey, Deserialize, IterateParams, Key, Store, ValueKey, SUBSPACE_BITMAP_ID,
-    SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT, SUBSPACE_INDEXES, SUBSPACE_LOGS, U32_LEN,
+    SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT, SUBSPACE_INDEXES, SUBSPACE_LOGS, U32_LEN, U64_LEN,
 };
 
 use super::DocumentSet;
@@ -48,6 +49,8 @@ impl Store {
     where
         U: Deserialize + 'static,
     {
+        let _timer = STORE_DATA_READ.start_timer();
+
         match self {
             #[cfg(feature = "sqlite")]
             Self::SQLite(store) => store.get_value(key).await,
@@ -144,6 +147,8 @@ impl Store {
     }
 
     pub async fn write(&self, batch: Batch) -> crate::Result<AssignedIds> {
+        let _timer = STORE_DATA_WRITE.start_timer();
+
         #[cfg(feature = "test_mode")]
         if std::env::var("PARANOID_WRITE").map_or(false, |v| v == "1") {
             let mut account_id = u32::MAX;
@@ -274,6 +279,53 @@ impl Store {
         )
         .await?;
 
+        // Delete expired message traces
+        self.delete_range(
+            ValueKey::from(ValueClass::Queue(QueueClass::MessageTrace {
+                id: 0,
+                expires: 0,
+            })),
+            ValueKey::from(ValueClass::Queue(QueueClass::MessageTrace {
+                id: u64::MAX,
+                expires: now,
+            })),
+        )
+        .await?;
+
+        // Delete expired quarantined messages, which are keyed by id
+        let mut expired = Vec::new();
+        self.iterate(
+            IterateParams::new(
+                ValueKey::from(ValueClass::Queue(QueueClass::Quarantine {
+                    id: 0,
+                    expires: 0,
+                })),
+                ValueKey::from(ValueClass::Queue(QueueClass::Quarantine {
+                    id: u64::MAX,
+                    expires: u64::MAX,
+                })),
+            )
+            .no_values(),
+            |key, _| {
+                let expires = key.deserialize_be_u64(U64_LEN)?;
+                if expires <= now {
+                    expired.push(QueueClass::Quarantine {
+                        id: key.deserialize_be_u64(0)?,
+                        expires,
+                    });
+                }
+                Ok(true)
+            },
+        )
+        .await?;
+        if !expired.is_empty() {
+            let mut batch = BatchBuilder::new();
+            for class in expired {
+                batch.clear(ValueClass::Queue(class));
+            }
+            self.write(batch.build()).await?;
+        }
+
         match self {
             #[cfg(feature = "sqlite")]
             Self::SQLite(store) => store.purge_store().await,
@@ -508,9 +560,13 @@ impl Store {
             SUBSPACE_BLOBS,
             SUBSPACE_QUEUE_MESSAGE,
             SUBSPACE_QUEUE_EVENT,
+            SUBSPACE_QUEUE_HISTORY,
+            SUBSPACE_QUEUE_RELAY,
             SUBSPACE_QUOTA,
             SUBSPACE_REPORT_OUT,
             SUBSPACE_REPORT_IN,
+            SUBSPACE_TRACE,
+            SUBSPACE_QUARANTINE,
             SUBSPACE_FTS_INDEX,
         ] {
             self.delete_range(
@@ -680,6 +736,8 @@ impl Store {
             (SUBSPACE_SETTINGS, true),
             (SUBSPACE_QUEUE_MESSAGE, true),
             (SUBSPACE_QUEUE_EVENT, true),
+            (SUBSPACE_QUEUE_HISTORY, true),
+            (SUBSPACE_QUEUE_RELAY, true),
             (SUBSPACE_REPORT_OUT, true),
             (SUBSPACE_REPORT_IN, true),
             (SUBSPACE_FTS_INDEX, true),
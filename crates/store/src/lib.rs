@@ -185,15 +185,15 @@ pub const SUBSPACE_PROPERTY: u8 = b'p';
 pub const SUBSPACE_SETTINGS: u8 = b's';
 pub const SUBSPACE_QUEUE_MESSAGE: u8 = b'e';
 pub const SUBSPACE_QUEUE_EVENT: u8 = b'q';
+pub const SUBSPACE_QUEUE_HISTORY: u8 = b'o';
+pub const SUBSPACE_QUEUE_RELAY: u8 = b'x';
 pub const SUBSPACE_QUOTA: u8 = b'u';
 pub const SUBSPACE_REPORT_OUT: u8 = b'h';
 pub const SUBSPACE_REPORT_IN: u8 = b'r';
+pub const SUBSPACE_TRACE: u8 = b'w';
+pub const SUBSPACE_QUARANTINE: u8 = b'y';
 pub const SUBSPACE_FTS_INDEX: u8 = b'g';
 
-pub const SUBSPACE_RESERVED_1: u8 = b'o';
-pub const SUBSPACE_RESERVED_2: u8 = b'w';
-pub const SUBSPACE_RESERVED_3: u8 = b'x';
-pub const SUBSPACE_RESERVED_4: u8 = b'y';
 pub const SUBSPACE_RESERVED_5: u8 = b'z';
 
 pub struct IterateParams<T: Key> {
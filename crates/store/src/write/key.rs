@@ -29,8 +29,9 @@ use crate::{
     SUBSPACE_BITMAP_ID, SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT, SUBSPACE_BLOB_LINK,
     SUBSPACE_BLOB_RESERVE, SUBSPACE_COUNTER, SUBSPACE_DIRECTORY, SUBSPACE_FTS_INDEX,
     SUBSPACE_FTS_QUEUE, SUBSPACE_INDEXES, SUBSPACE_LOGS, SUBSPACE_LOOKUP_VALUE, SUBSPACE_PROPERTY,
-    SUBSPACE_QUEUE_EVENT, SUBSPACE_QUEUE_MESSAGE, SUBSPACE_QUOTA, SUBSPACE_REPORT_IN,
-    SUBSPACE_REPORT_OUT, SUBSPACE_SETTINGS, U32_LEN, U64_LEN, WITH_SUBSPACE,
+    SUBSPACE_QUARANTINE, SUBSPACE_QUEUE_EVENT, SUBSPACE_QUEUE_HISTORY, SUBSPACE_QUEUE_MESSAGE,
+    SUBSPACE_QUEUE_RELAY, SUBSPACE_QUOTA, SUBSPACE_REPORT_IN, SUBSPACE_REPORT_OUT,
+    SUBSPACE_SETTINGS, SUBSPACE_TRACE, U32_LEN, U64_LEN, WITH_SUBSPACE,
 };
 
 use super::{
@@ -338,10 +339,14 @@ impl<T: ResolveId> ValueClass<T> {
                     .write(has_member.resolve_id(assigned_ids)),
             },
             ValueClass::Queue(queue) => match queue {
-                QueueClass::Message(queue_id) => serializer.write(*queue_id),
+                QueueClass::Message(queue_id)
+                | QueueClass::MessageHistory(queue_id)
+                | QueueClass::MessageRelay(queue_id) => serializer.write(*queue_id),
                 QueueClass::MessageEvent(event) => {
                     serializer.write(event.due).write(event.queue_id)
                 }
+                QueueClass::MessageTrace { id, expires } => serializer.write(*expires).write(*id),
+                QueueClass::Quarantine { id, expires } => serializer.write(*id).write(*expires),
                 QueueClass::DmarcReportHeader(event) => serializer
                     .write(0u8)
                     .write(event.due)
@@ -549,8 +554,12 @@ impl<T> ValueClass<T> {
             },
             ValueClass::FtsQueue { .. } => BLOB_HASH_LEN + U64_LEN * 2,
             ValueClass::Queue(q) => match q {
-                QueueClass::Message(_) => U64_LEN,
-                QueueClass::MessageEvent(_) => U64_LEN * 2,
+                QueueClass::Message(_)
+                | QueueClass::MessageHistory(_)
+                | QueueClass::MessageRelay(_) => U64_LEN,
+                QueueClass::MessageEvent(_)
+                | QueueClass::MessageTrace { .. }
+                | QueueClass::Quarantine { .. } => U64_LEN * 2,
                 QueueClass::DmarcReportEvent(event) | QueueClass::TlsReportEvent(event) => {
                     event.domain.len() + U64_LEN * 3
                 }
@@ -594,6 +603,10 @@ impl<T> ValueClass<T> {
             ValueClass::Queue(queue) => match queue {
                 QueueClass::Message(_) => SUBSPACE_QUEUE_MESSAGE,
                 QueueClass::MessageEvent(_) => SUBSPACE_QUEUE_EVENT,
+                QueueClass::MessageHistory(_) => SUBSPACE_QUEUE_HISTORY,
+                QueueClass::MessageRelay(_) => SUBSPACE_QUEUE_RELAY,
+                QueueClass::MessageTrace { .. } => SUBSPACE_TRACE,
+                QueueClass::Quarantine { .. } => SUBSPACE_QUARANTINE,
                 QueueClass::DmarcReportHeader(_)
                 | QueueClass::TlsReportHeader(_)
                 | QueueClass::DmarcReportEvent(_)
@@ -206,6 +206,10 @@ pub enum DirectoryClass<T> {
 pub enum QueueClass {
     Message(u64),
     MessageEvent(QueueEvent),
+    MessageHistory(u64),
+    MessageRelay(u64),
+    MessageTrace { id: u64, expires: u64 },
+    Quarantine { id: u64, expires: u64 },
     DmarcReportHeader(ReportEvent),
     DmarcReportEvent(ReportEvent),
     TlsReportHeader(ReportEvent),
@@ -0,0 +1,477 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+// Latency buckets in microseconds, the last bucket is +Inf
+const LATENCY_BUCKETS: [u64; 12] = [
+    1_000, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000, 2_500_000,
+    5_000_000, 10_000_000,
+];
+const NUM_BUCKETS: usize = LATENCY_BUCKETS.len() + 1;
+
+pub type Label = Option<(&'static str, &'static str)>;
+
+pub struct AtomicCounter {
+    name: &'static str,
+    help: &'static str,
+    label: Label,
+    value: AtomicU64,
+}
+
+pub struct AtomicGauge {
+    name: &'static str,
+    help: &'static str,
+    label: Label,
+    value: AtomicI64,
+}
+
+pub struct AtomicHistogram {
+    name: &'static str,
+    help: &'static str,
+    label: Label,
+    buckets: [AtomicU64; NUM_BUCKETS],
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+pub struct HistogramTimer {
+    histogram: &'static AtomicHistogram,
+    start: Instant,
+}
+
+pub struct GaugeGuard {
+    gauge: &'static AtomicGauge,
+}
+
+pub enum Metric {
+    Counter(&'static AtomicCounter),
+    Gauge(&'static AtomicGauge),
+    Histogram(&'static AtomicHistogram),
+}
+
+// SMTP inbound
+pub static SMTP_SESSIONS: AtomicCounter = AtomicCounter::new(
+    "smtp_sessions_total",
+    "Total number of inbound SMTP sessions.",
+);
+pub static SMTP_SESSIONS_ACTIVE: AtomicGauge = AtomicGauge::new(
+    "smtp_sessions_active",
+    "Number of inbound SMTP sessions in progress.",
+);
+pub static SMTP_MESSAGES_ACCEPTED: AtomicCounter = AtomicCounter::with_label(
+    "smtp_messages_received_total",
+    "Total number of messages received over SMTP by outcome.",
+    ("result", "accepted"),
+);
+pub static SMTP_MESSAGES_DEFERRED: AtomicCounter = AtomicCounter::with_label(
+    "smtp_messages_received_total",
+    "Total number of messages received over SMTP by outcome.",
+    ("result", "deferred"),
+);
+pub static SMTP_MESSAGES_REJECTED: AtomicCounter = AtomicCounter::with_label(
+    "smtp_messages_received_total",
+    "Total number of messages received over SMTP by outcome.",
+    ("result", "rejected"),
+);
+
+// Queue
+pub static QUEUE_MESSAGES: AtomicGauge =
+    AtomicGauge::new("queue_messages", "Number of messages in the queue.");
+pub static QUEUE_MESSAGES_ON_HOLD: AtomicGauge = AtomicGauge::new(
+    "queue_messages_on_hold",
+    "Number of queued messages waiting for a concurrency limiter.",
+);
+pub static QUEUE_MESSAGES_SCHEDULED: AtomicCounter = AtomicCounter::new(
+    "queue_messages_scheduled_total",
+    "Total number of messages added to the queue.",
+);
+pub static QUEUE_DELIVERY_ATTEMPTS: AtomicCounter = AtomicCounter::new(
+    "queue_delivery_attempts_total",
+    "Total number of delivery attempts started by the queue manager.",
+);
+
+// SMTP outbound
+pub static DELIVERY_COMPLETED: AtomicCounter = AtomicCounter::with_label(
+    "smtp_delivery_total",
+    "Total number of domain delivery attempts by outcome.",
+    ("result", "completed"),
+);
+pub static DELIVERY_TEMP_FAIL: AtomicCounter = AtomicCounter::with_label(
+    "smtp_delivery_total",
+    "Total number of domain delivery attempts by outcome.",
+    ("result", "temp_fail"),
+);
+pub static DELIVERY_PERM_FAIL: AtomicCounter = AtomicCounter::with_label(
+    "smtp_delivery_total",
+    "Total number of domain delivery attempts by outcome.",
+    ("result", "perm_fail"),
+);
+pub static DELIVERY_DURATION: AtomicHistogram = AtomicHistogram::new(
+    "smtp_delivery_duration_seconds",
+    "Time spent on a queued message delivery attempt.",
+);
+
+// IMAP
+pub static IMAP_COMMANDS: AtomicCounter = AtomicCounter::new(
+    "imap_commands_total",
+    "Total number of IMAP commands received.",
+);
+pub static IMAP_COMMAND_DURATION: AtomicHistogram = AtomicHistogram::new(
+    "imap_command_duration_seconds",
+    "Time spent dispatching IMAP commands.",
+);
+
+// JMAP
+pub static JMAP_REQUESTS: AtomicCounter =
+    AtomicCounter::new("jmap_requests_total", "Total number of JMAP API requests.");
+pub static JMAP_METHOD_CALLS: AtomicCounter = AtomicCounter::new(
+    "jmap_method_calls_total",
+    "Total number of JMAP method calls.",
+);
+pub static JMAP_REQUEST_DURATION: AtomicHistogram = AtomicHistogram::new(
+    "jmap_request_duration_seconds",
+    "Time spent processing JMAP API requests.",
+);
+
+// Store
+pub static STORE_DATA_READ: AtomicHistogram = AtomicHistogram::with_label(
+    "store_operation_duration_seconds",
+    "Time spent on store operations.",
+    ("operation", "data_read"),
+);
+pub static STORE_DATA_WRITE: AtomicHistogram = AtomicHistogram::with_label(
+    "store_operation_duration_seconds",
+    "Time spent on store operations.",
+    ("operation", "data_write"),
+);
+pub static STORE_BLOB_READ: AtomicHistogram = AtomicHistogram::with_label(
+    "store_operation_duration_seconds",
+    "Time spent on store operations.",
+    ("operation", "blob_read"),
+);
+pub static STORE_BLOB_WRITE: AtomicHistogram = AtomicHistogram::with_label(
+    "store_operation_duration_seconds",
+    "Time spent on store operations.",
+    ("operation", "blob_write"),
+);
+
+// Metrics sharing a name must be listed next to each other
+pub static METRICS: &[Metric] = &[
+    Metric::Counter(&SMTP_SESSIONS),
+    Metric::Gauge(&SMTP_SESSIONS_ACTIVE),
+    Metric::Counter(&SMTP_MESSAGES_ACCEPTED),
+    Metric::Counter(&SMTP_MESSAGES_DEFERRED),
+    Metric::Counter(&SMTP_MESSAGES_REJECTED),
+    Metric::Gauge(&QUEUE_MESSAGES),
+    Metric::Gauge(&QUEUE_MESSAGES_ON_HOLD),
+    Metric::Counter(&QUEUE_MESSAGES_SCHEDULED),
+    Metric::Counter(&QUEUE_DELIVERY_ATTEMPTS),
+    Metric::Counter(&DELIVERY_COMPLETED),
+    Metric::Counter(&DELIVERY_TEMP_FAIL),
+    Metric::Counter(&DELIVERY_PERM_FAIL),
+    Metric::Histogram(&DELIVERY_DURATION),
+    Metric::Counter(&IMAP_COMMANDS),
+    Metric::Histogram(&IMAP_COMMAND_DURATION),
+    Metric::Counter(&JMAP_REQUESTS),
+    Metric::Counter(&JMAP_METHOD_CALLS),
+    Metric::Histogram(&JMAP_REQUEST_DURATION),
+    Metric::Histogram(&STORE_DATA_READ),
+    Metric::Histogram(&STORE_DATA_WRITE),
+    Metric::Histogram(&STORE_BLOB_READ),
+    Metric::Histogram(&STORE_BLOB_WRITE),
+];
+
+impl AtomicCounter {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            label: None,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub const fn with_label(
+        name: &'static str,
+        help: &'static str,
+        label: (&'static str, &'static str),
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label: Some(label),
+            value: AtomicU64::new(0),
+        }
+    }
+
+    #[inline(always)]
+    pub fn increment(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn increment_by(&self, value: u64) {
+        self.value.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+impl AtomicGauge {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            label: None,
+            value: AtomicI64::new(0),
+        }
+    }
+
+    #[inline(always)]
+    pub fn increment(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn decrement(&self) {
+        self.value.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn set(&self, value: i64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    pub fn guard(&'static self) -> GaugeGuard {
+        self.increment();
+        GaugeGuard { gauge: self }
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+
+impl AtomicHistogram {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            label: None,
+            buckets: [ZERO; NUM_BUCKETS],
+            sum: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub const fn with_label(
+        name: &'static str,
+        help: &'static str,
+        label: (&'static str, &'static str),
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label: Some(label),
+            buckets: [ZERO; NUM_BUCKETS],
+            sum: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let micros = duration.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| micros <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(micros, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn start_timer(&'static self) -> HistogramTimer {
+        HistogramTimer {
+            histogram: self,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for HistogramTimer {
+    fn drop(&mut self) {
+        self.histogram.observe(self.start.elapsed());
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.gauge.decrement();
+    }
+}
+
+impl Metric {
+    fn name(&self) -> &'static str {
+        match self {
+            Metric::Counter(m) => m.name,
+            Metric::Gauge(m) => m.name,
+            Metric::Histogram(m) => m.name,
+        }
+    }
+}
+
+/// Renders all metrics using the Prometheus text exposition format.
+pub fn export_prometheus() -> String {
+    let mut out = String::with_capacity(4096);
+    let mut last_name = "";
+
+    for metric in METRICS {
+        let name = metric.name();
+        if name != last_name {
+            let (help, typ) = match metric {
+                Metric::Counter(m) => (m.help, "counter"),
+                Metric::Gauge(m) => (m.help, "gauge"),
+                Metric::Histogram(m) => (m.help, "histogram"),
+            };
+            let _ = write!(out, "# HELP {name} {help}\n# TYPE {name} {typ}\n");
+            last_name = name;
+        }
+
+        match metric {
+            Metric::Counter(m) => {
+                let _ = writeln!(out, "{name}{} {}", Labels(m.label, None), m.get());
+            }
+            Metric::Gauge(m) => {
+                let _ = writeln!(out, "{name}{} {}", Labels(m.label, None), m.get());
+            }
+            Metric::Histogram(m) => {
+                let mut cumulative = 0;
+                for (idx, bucket) in m.buckets.iter().enumerate() {
+                    cumulative += bucket.load(Ordering::Relaxed);
+                    let le = LATENCY_BUCKETS
+                        .get(idx)
+                        .map(|bound| (*bound as f64 / 1_000_000.0).to_string())
+                        .unwrap_or_else(|| "+Inf".to_string());
+                    let _ = writeln!(
+                        out,
+                        "{name}_bucket{} {cumulative}",
+                        Labels(m.label, Some(&le))
+                    );
+                }
+                let _ = writeln!(
+                    out,
+                    "{name}_sum{} {}",
+                    Labels(m.label, None),
+                    m.sum.load(Ordering::Relaxed) as f64 / 1_000_000.0
+                );
+                let _ = writeln!(
+                    out,
+                    "{name}_count{} {}",
+                    Labels(m.label, None),
+                    m.count.load(Ordering::Relaxed)
+                );
+            }
+        }
+    }
+
+    out
+}
+
+struct Labels<'x>(Label, Option<&'x str>);
+
+impl std::fmt::Display for Labels<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.0, self.1) {
+            (Some((key, value)), Some(le)) => write!(f, "{{{key}=\"{value}\",le=\"{le}\"}}"),
+            (Some((key, value)), None) => write!(f, "{{{key}=\"{value}\"}}"),
+            (None, Some(le)) => write!(f, "{{le=\"{le}\"}}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{AtomicCounter, AtomicHistogram, Labels};
+
+    #[test]
+    fn histogram_buckets() {
+        static HISTOGRAM: AtomicHistogram = AtomicHistogram::new("test", "test");
+
+        HISTOGRAM.observe(Duration::from_micros(500));
+        HISTOGRAM.observe(Duration::from_millis(7));
+        HISTOGRAM.observe(Duration::from_secs(60));
+
+        assert_eq!(HISTOGRAM.count(), 3);
+        assert_eq!(
+            HISTOGRAM
+                .buckets
+                .iter()
+                .map(|b| b.load(std::sync::atomic::Ordering::Relaxed))
+                .collect::<Vec<_>>(),
+            vec![1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn export_format() {
+        static COUNTER: AtomicCounter =
+            AtomicCounter::with_label("test_total", "test", ("result", "ok"));
+        COUNTER.increment_by(3);
+
+        assert_eq!(
+            format!("{}", Labels(COUNTER.label, Some("0.5"))),
+            "{result=\"ok\",le=\"0.5\"}"
+        );
+        assert_eq!(COUNTER.get(), 3);
+
+        let export = super::export_prometheus();
+        assert!(export.contains("# TYPE smtp_delivery_total counter\n"));
+        assert!(export.contains("smtp_delivery_total{result=\"completed\"} "));
+        assert!(export.contains(
+            "store_operation_duration_seconds_bucket{operation=\"blob_read\",le=\"+Inf\"} "
+        ));
+        assert_eq!(
+            export
+                .matches("# HELP store_operation_duration_seconds ")
+                .count(),
+            1
+        );
+    }
+}
@@ -27,6 +27,7 @@ use directory::{
         lookup::DirectoryStore, manage::ManageDirectory, PrincipalField, PrincipalUpdate,
         PrincipalValue,
     },
+    core::secret::{recovery_code_secret, AppPassword, AppPasswordScope, TotpSecret},
     DirectoryError, ManagementError, Principal, QueryBy, Type,
 };
 use jmap_proto::types::collection::Collection;
@@ -176,7 +177,8 @@ async fn internal_directory() {
                     true
                 )
                 .await
-                .unwrap(),
+                .unwrap()
+                .map(|p| p.without_scram_secrets()),
             Some(Principal {
                 id: jane_id,
                 name: "jane".to_string(),
@@ -201,6 +203,136 @@ async fn internal_directory() {
             None
         );
 
+        // App passwords are stored alongside secrets but do not unlock the account
+        let app_password = AppPassword::new(
+            "phone".to_string(),
+            vec![AppPasswordScope::Imap],
+            None,
+            "app_secret",
+        );
+        store
+            .update_account(
+                QueryBy::Id(jane_id),
+                vec![PrincipalUpdate::add_item(
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(app_password.to_string()),
+                )],
+            )
+            .await
+            .unwrap();
+        let principal = store
+            .query(QueryBy::Id(jane_id), false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            principal.app_passwords().collect::<Vec<_>>(),
+            vec![app_password.clone()]
+        );
+        assert!(
+            app_password
+                .verify(AppPasswordScope::Imap, "app_secret")
+                .await
+        );
+        assert_eq!(
+            store
+                .query(
+                    QueryBy::Credentials(&Credentials::new(
+                        "jane".to_string(),
+                        "app_secret".to_string()
+                    )),
+                    true
+                )
+                .await
+                .unwrap(),
+            None
+        );
+        store
+            .update_account(
+                QueryBy::Id(jane_id),
+                vec![PrincipalUpdate::remove_item(
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(app_password.to_string()),
+                )],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .query(QueryBy::Id(jane_id), false)
+                .await
+                .unwrap()
+                .unwrap()
+                .app_passwords()
+                .count(),
+            0
+        );
+
+        // TOTP secrets and recovery codes do not unlock the account either
+        let totp = TotpSecret::generate().enable();
+        let recovery_code = recovery_code_secret("recovery");
+        store
+            .update_account(
+                QueryBy::Id(jane_id),
+                vec![
+                    PrincipalUpdate::add_item(
+                        PrincipalField::Secrets,
+                        PrincipalValue::String(totp.to_string()),
+                    ),
+                    PrincipalUpdate::add_item(
+                        PrincipalField::Secrets,
+                        PrincipalValue::String(recovery_code.clone()),
+                    ),
+                ],
+            )
+            .await
+            .unwrap();
+        let principal = store
+            .query(QueryBy::Id(jane_id), false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(principal.has_totp());
+        assert_eq!(principal.totp_secret(), Some(totp.clone()));
+        assert_eq!(
+            principal.verify_recovery_code("recovery").await,
+            Some(recovery_code.as_str())
+        );
+        for secret in [totp.encoded_secret(), "recovery".to_string()] {
+            assert_eq!(
+                store
+                    .query(
+                        QueryBy::Credentials(&Credentials::new("jane".to_string(), secret)),
+                        true
+                    )
+                    .await
+                    .unwrap(),
+                None
+            );
+        }
+        store
+            .update_account(
+                QueryBy::Id(jane_id),
+                vec![
+                    PrincipalUpdate::remove_item(
+                        PrincipalField::Secrets,
+                        PrincipalValue::String(totp.to_string()),
+                    ),
+                    PrincipalUpdate::remove_item(
+                        PrincipalField::Secrets,
+                        PrincipalValue::String(recovery_code),
+                    ),
+                ],
+            )
+            .await
+            .unwrap();
+        assert!(!store
+            .query(QueryBy::Id(jane_id), false)
+            .await
+            .unwrap()
+            .unwrap()
+            .has_totp());
+
         // Duplicate email address should fail
         assert_eq!(
             store
@@ -338,7 +470,8 @@ async fn internal_directory() {
                 )
                 .await
                 .unwrap()
-                .into_sorted(),
+                .into_sorted()
+                .without_scram_secrets(),
             Principal {
                 id: john_id,
                 name: "john".to_string(),
@@ -394,7 +527,8 @@ async fn internal_directory() {
                 )
                 .await
                 .unwrap()
-                .into_sorted(),
+                .into_sorted()
+                .without_scram_secrets(),
             Principal {
                 id: john_id,
                 name: "john".to_string(),
@@ -453,7 +587,8 @@ async fn internal_directory() {
                 )
                 .await
                 .unwrap()
-                .into_sorted(),
+                .into_sorted()
+                .without_scram_secrets(),
             Principal {
                 id: john_id,
                 name: "john.doe".to_string(),
@@ -694,3 +829,18 @@ async fn internal_directory() {
         );
     }
 }
+
+trait WithoutScramSecrets {
+    fn without_scram_secrets(self) -> Self;
+}
+
+impl<T> WithoutScramSecrets for Principal<T> {
+    fn without_scram_secrets(mut self) -> Self {
+        // SCRAM keys are salted at random, only check that they were generated
+        let num_secrets = self.secrets.len();
+        self.secrets
+            .retain(|secret| !secret.starts_with("{SCRAM-SHA-"));
+        assert_eq!(self.secrets.len() * 3, num_secrets, "{:?}", self.secrets);
+        self
+    }
+}
@@ -21,11 +21,15 @@
  * for more details.
 */
 
+use base64::{engine::general_purpose::STANDARD, Engine};
+use directory::core::secret::ScramAlgorithm;
 use imap::op::authenticate::decode_challenge_oauth;
 use imap_proto::ResponseType;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 
+use crate::ScramClient;
+
 use super::{AssertResult, ImapConnection, Type};
 
 pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
@@ -33,7 +37,10 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
 
     // Test CAPABILITY
     imap.send("CAPABILITY").await;
-    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("AUTH=SCRAM-SHA-256")
+        .assert_contains("AUTH=SCRAM-SHA-1");
 
     // Test NOOP
     imap.send("NOOP").await;
@@ -54,6 +61,69 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
     imap.assert_read(Type::Continuation, ResponseType::Ok).await;
     imap.send_untagged("AGJvYXR5AG1jYm9hdGZhY2U=").await;
     imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // SCRAM authentication
+    for (algorithm, mechanism, password) in [
+        (ScramAlgorithm::Sha256, "SCRAM-SHA-256", "wrong"),
+        (ScramAlgorithm::Sha1, "SCRAM-SHA-1", "secret"),
+        (ScramAlgorithm::Sha256, "SCRAM-SHA-256", "secret"),
+    ] {
+        let mut client = ScramClient::new(algorithm, "jdoe@example.com");
+        imap.send(&format!(
+            "AUTHENTICATE {mechanism} {}",
+            STANDARD.encode(client.client_first())
+        ))
+        .await;
+        let server_first = scram_challenge(imap).await;
+        imap.send_untagged(&STANDARD.encode(client.client_final(&server_first, password)))
+            .await;
+        if password == "secret" {
+            assert_eq!(scram_challenge(imap).await, client.server_final());
+            imap.send_untagged("").await;
+            imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+            imap.send("UNAUTHENTICATE").await;
+            imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+        } else {
+            imap.assert_read(Type::Tagged, ResponseType::No).await;
+        }
+    }
+
+    // Unknown users should always be given the same salt
+    let mut salts = Vec::new();
+    for _ in 0..2 {
+        let mut client = ScramClient::new(ScramAlgorithm::Sha256, "unknown@example.com");
+        imap.send(&format!(
+            "AUTHENTICATE SCRAM-SHA-256 {}",
+            STANDARD.encode(client.client_first())
+        ))
+        .await;
+        let server_first = scram_challenge(imap).await;
+        salts.push(
+            server_first
+                .split(',')
+                .find_map(|attr| attr.strip_prefix("s="))
+                .unwrap()
+                .to_string(),
+        );
+        imap.send_untagged(&STANDARD.encode(client.client_final(&server_first, "secret")))
+            .await;
+        imap.assert_read(Type::Tagged, ResponseType::No).await;
+    }
+    assert_eq!(salts[0], salts[1]);
+}
+
+async fn scram_challenge(imap: &mut ImapConnection) -> String {
+    let challenge = imap
+        .assert_read(Type::Continuation, ResponseType::Ok)
+        .await
+        .pop()
+        .unwrap();
+    String::from_utf8(
+        STANDARD
+            .decode(challenge.strip_prefix("+ ").unwrap().trim_end())
+            .unwrap(),
+    )
+    .unwrap()
 }
 
 #[test]
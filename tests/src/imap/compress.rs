@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+struct DeflateConnection {
+    stream: TcpStream,
+    compress: Option<(Compress, Decompress)>,
+    buf: Vec<u8>,
+}
+
+pub async fn test() {
+    println!("Running COMPRESS tests...");
+
+    let mut imap = DeflateConnection {
+        stream: TcpStream::connect("127.0.0.1:9991").await.unwrap(),
+        compress: None,
+        buf: Vec::new(),
+    };
+    imap.read_until("* OK").await;
+    imap.send("c1 COMPRESS DEFLATE").await;
+    imap.read_until("c1 NO").await;
+    imap.send("c2 AUTHENTICATE PLAIN {32+}\r\nAGpkb2VAZXhhbXBsZS5jb20Ac2VjcmV0")
+        .await;
+    assert!(imap.read_until("c2 OK").await.contains("COMPRESS=DEFLATE"));
+
+    // Enable compression
+    imap.send("c3 COMPRESS DEFLATE").await;
+    imap.read_until("c3 OK").await;
+    imap.compress = Some((
+        Compress::new(Compression::default(), false),
+        Decompress::new(false),
+    ));
+
+    // Commands and responses are now compressed
+    imap.send("c4 CAPABILITY").await;
+    assert!(!imap.read_until("c4 OK").await.contains("COMPRESS=DEFLATE"));
+    imap.send("c5 COMPRESS DEFLATE").await;
+    assert!(imap
+        .read_until("c5 NO")
+        .await
+        .contains("[COMPRESSIONACTIVE]"));
+    imap.send("c6 SELECT INBOX").await;
+    assert!(imap.read_until("c6 OK").await.contains("EXISTS"));
+    imap.send("c7 FETCH 1:* (BODY.PEEK[])").await;
+    imap.read_until("c7 OK").await;
+    imap.send("c8 LOGOUT").await;
+    imap.read_until("c8 OK").await;
+}
+
+impl DeflateConnection {
+    async fn send(&mut self, text: &str) {
+        let mut bytes = format!("{text}\r\n").into_bytes();
+        if let Some((compress, _)) = &mut self.compress {
+            let mut output = Vec::with_capacity(bytes.len() + 64);
+            compress
+                .compress_vec(&bytes, &mut output, FlushCompress::Sync)
+                .unwrap();
+            bytes = output;
+        }
+        self.stream.write_all(&bytes).await.unwrap();
+    }
+
+    async fn read_until(&mut self, text: &str) -> String {
+        let mut buf = vec![0u8; 4096];
+        loop {
+            let response = String::from_utf8_lossy(&self.buf).into_owned();
+            if let Some(pos) = response.find(text) {
+                if let Some(end) = response[pos..].find("\r\n") {
+                    self.buf.drain(..pos + end + 2);
+                    return response;
+                }
+            }
+
+            let bytes_read =
+                tokio::time::timeout(Duration::from_millis(1500), self.stream.read(&mut buf))
+                    .await
+                    .unwrap_or_else(|_| panic!("Timeout waiting for {text:?}: {response:?}"))
+                    .unwrap();
+            assert_ne!(bytes_read, 0, "Connection closed: {response:?}");
+            if let Some((_, decompress)) = &mut self.compress {
+                let mut input = &buf[..bytes_read];
+                while !input.is_empty() {
+                    self.buf.reserve(input.len() * 4 + 1024);
+                    let total_in = decompress.total_in();
+                    decompress
+                        .decompress_vec(input, &mut self.buf, FlushDecompress::Sync)
+                        .unwrap();
+                    input = &input[(decompress.total_in() - total_in) as usize..];
+                }
+            } else {
+                self.buf.extend_from_slice(&buf[..bytes_read]);
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
+    println!("Running METADATA tests...");
+
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("METADATA-SERVER");
+
+    // Set and retrieve mailbox metadata
+    imap.send("CREATE Annotated").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send(
+        "SETMETADATA Annotated (/private/comment \"My comment\" /shared/comment \"Shared comment\")",
+    )
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA Annotated (/private/comment /shared/comment /shared/missing)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals(concat!(
+            "* METADATA \"Annotated\" (\"/private/comment\" \"My comment\" ",
+            "\"/shared/comment\" \"Shared comment\" \"/shared/missing\" NIL)"
+        ));
+
+    // Depth and size options
+    imap.send(
+        "SETMETADATA Annotated (/private/vendor/test/a \"1\" /private/vendor/test/b/c \"12345\")",
+    )
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .send("GETMETADATA (DEPTH 1) Annotated /private/vendor/test")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* METADATA \"Annotated\" (\"/private/vendor/test/a\" \"1\")");
+    imap_check
+        .send("GETMETADATA (DEPTH infinity MAXSIZE 3) Annotated /private/vendor/test")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* METADATA \"Annotated\" (\"/private/vendor/test/a\" \"1\")")
+        .assert_response_code("METADATA LONGENTRIES 5");
+
+    // Enforce limits
+    imap.send("SETMETADATA Annotated (/private/vendor/test/d \"1\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("METADATA TOOMANY");
+    imap.send(&format!(
+        "SETMETADATA Annotated (/private/comment \"{}\")",
+        "a".repeat(101)
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("METADATA MAXSIZE 100");
+
+    // Remove entries
+    imap.send("SETMETADATA Annotated (/private/comment NIL)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA Annotated /private/comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* METADATA \"Annotated\" (\"/private/comment\" NIL)");
+
+    // Metadata is removed with the mailbox
+    imap.send("DELETE Annotated").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA Annotated /shared/comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("NONEXISTENT");
+    imap.send("CREATE Annotated").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA Annotated /shared/comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* METADATA \"Annotated\" (\"/shared/comment\" NIL)");
+    imap.send("DELETE Annotated").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Server metadata
+    imap.send("SETMETADATA \"\" (/private/vendor/vendor.dovecot/webmail-settings \"dark\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("SETMETADATA \"\" (/shared/admin \"mailto:admin@example.com\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("NOPERM");
+
+    let mut imap_admin = ImapConnection::connect(b"_a ").await;
+    imap_admin
+        .assert_read(Type::Untagged, ResponseType::Ok)
+        .await;
+    imap_admin
+        .send("AUTHENTICATE PLAIN {20+}\r\nAGFkbWluAHNlY3JldA==")
+        .await;
+    imap_admin.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_admin
+        .send("SETMETADATA \"\" (/shared/admin \"mailto:admin@example.com\")")
+        .await;
+    imap_admin.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_admin
+        .send("GETMETADATA \"\" (/private/vendor/vendor.dovecot/webmail-settings)")
+        .await;
+    imap_admin
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* METADATA \"\" (\"/private/vendor/vendor.dovecot/webmail-settings\" NIL)");
+
+    imap.send("GETMETADATA (DEPTH infinity) \"\" (/private /shared)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals(concat!(
+            "* METADATA \"\" (\"/private/vendor/vendor.dovecot/webmail-settings\" \"dark\" ",
+            "\"/shared/admin\" \"mailto:admin@example.com\")"
+        ));
+}
@@ -25,16 +25,22 @@ pub mod acl;
 pub mod append;
 pub mod basic;
 pub mod body_structure;
+pub mod compress;
 pub mod condstore;
 pub mod copy_move;
 pub mod fetch;
 pub mod idle;
 pub mod mailbox;
 pub mod managesieve;
+pub mod metadata;
+pub mod notify;
 pub mod pop;
+pub mod quota;
+pub mod replace;
 pub mod search;
 pub mod store;
 pub mod thread;
+pub mod urlauth;
 
 use std::{
     path::PathBuf,
@@ -192,6 +198,10 @@ private-key = "%{file:{PK}}%"
 [imap.protocol]
 uidplus = true
 
+[imap.metadata]
+max-size = 100
+max-entries = 4
+
 [storage]
 data = "{STORE}"
 fts = "{STORE}"
@@ -199,6 +209,11 @@ blob = "{STORE}"
 lookup = "{STORE}"
 directory = "auth"
 
+[spam.train]
+enable = true
+per-user = true
+global = false
+
 [jmap.protocol]
 set.max-objects = 100000
 
@@ -488,6 +503,12 @@ pub async fn imap_tests() {
     idle::test(&mut imap, &mut imap_check).await;
     condstore::test(&mut imap, &mut imap_check).await;
     acl::test(&mut imap, &mut imap_check).await;
+    quota::test(&handle).await;
+    metadata::test(&mut imap, &mut imap_check).await;
+    compress::test().await;
+    notify::test(&mut imap, &mut imap_check).await;
+    replace::test(&mut imap, &mut imap_check).await;
+    urlauth::test(&mut imap, &mut imap_check).await;
 
     // Logout
     for imap in [&mut imap, &mut imap_check] {
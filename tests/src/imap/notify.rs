@@ -0,0 +1,139 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
+    println!("Running NOTIFY tests...");
+
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("NOTIFY");
+
+    // Unsupported events and invalid event combinations
+    imap_check
+        .send("NOTIFY SET (personal (MailboxMetadataChange))")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[BADEVENT (MessageNew MessageExpunge");
+    imap_check.send("NOTIFY SET (personal (MessageNew))").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Bad)
+        .await;
+
+    // Enable notifications with initial status
+    for mailbox in ["Gouda", "Brie"] {
+        imap.send(&format!("CREATE {mailbox}")).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+    imap_check.send("SELECT Brie").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .send(concat!(
+            "NOTIFY SET STATUS (selected (MessageNew (UID RFC822.SIZE) MessageExpunge)) ",
+            "(mailboxes Gouda (MessageNew MessageExpunge)) ",
+            "(personal (MailboxName SubscriptionChange))"
+        ))
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("STATUS \"Gouda\" (MESSAGES 0")
+        .assert_count("STATUS \"Brie\"", 0)
+        .assert_count("STATUS \"INBOX\"", 0);
+
+    // New messages in a monitored mailbox produce STATUS responses
+    let message = "From: test@domain.com\nSubject: Test\n\nTest message\n";
+    imap.send(&format!("APPEND Gouda {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("STATUS \"Gouda\"")
+        .assert_contains("MESSAGES 1")
+        .assert_contains("UNSEEN 1");
+
+    // New messages in the selected mailbox include the requested attributes
+    imap.send(&format!("APPEND Brie {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 EXISTS");
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 FETCH (")
+        .assert_contains("UID 1")
+        .assert_contains(&format!("RFC822.SIZE {}", message.len()));
+
+    // Mailbox name and subscription changes
+    imap.send("CREATE Cheddar").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST () \"/\" \"Cheddar\"");
+    imap.send("SUBSCRIBE Cheddar").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST (\\Subscribed) \"/\" \"Cheddar\"");
+    imap.send("DELETE Cheddar").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST (\\NonExistent) \"/\" \"Cheddar\"");
+
+    // Disable notifications
+    imap_check.send("NOTIFY NONE").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send(&format!("APPEND Gouda {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check.send("UNSELECT").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("STATUS", 0);
+
+    for mailbox in ["Gouda", "Brie"] {
+        imap.send(&format!("DELETE {mailbox}")).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+}
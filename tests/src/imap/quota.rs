@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+
+use crate::directory::DirectoryStore;
+
+use super::{append::assert_append_message, AssertResult, IMAPTest, ImapConnection, Type};
+
+pub async fn test(handle: &IMAPTest) {
+    println!("Running QUOTA tests...");
+
+    // Create an account with a 10KB quota
+    let lookup = DirectoryStore {
+        store: handle
+            .jmap
+            .core
+            .storage
+            .lookups
+            .get("auth")
+            .unwrap()
+            .clone(),
+    };
+    lookup
+        .create_test_user_with_email("quota@example.com", "secret", "Quota Test")
+        .await;
+    lookup.set_test_quota("quota@example.com", 10 * 1024).await;
+
+    let mut imap = ImapConnection::connect(b"_q ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    imap.send("AUTHENTICATE PLAIN {36+}\r\nAHF1b3RhQGV4YW1wbGUuY29tAHNlY3JldA==")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("QUOTA=RES-STORAGE");
+
+    // Obtain quota roots
+    imap.send("GETQUOTAROOT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* QUOTAROOT \"INBOX\" \"\"")
+        .assert_equals("* QUOTA \"\" (STORAGE 0 10)");
+    imap.send("GETQUOTA \"\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* QUOTA \"\" (STORAGE 0 10)");
+    imap.send("GETQUOTA \"Shared Folders/nobody\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("NONEXISTENT");
+
+    // Appending messages updates the quota
+    assert_append_message(
+        &mut imap,
+        "INBOX",
+        "From: bill@example.com\r\nSubject: quota\r\n\r\ntest\r\n",
+        ResponseType::Ok,
+    )
+    .await
+    .assert_equals("* QUOTA \"\" (STORAGE 1 10)");
+    assert_append_message(
+        &mut imap,
+        "INBOX",
+        &format!(
+            "From: bill@example.com\r\nSubject: big\r\n\r\n{}\r\n",
+            "a".repeat(11 * 1024)
+        ),
+        ResponseType::No,
+    )
+    .await
+    .assert_contains("OVERQUOTA");
+
+    // Quota is sent on SELECT
+    imap.send("SELECT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* QUOTA \"\" (STORAGE 1 10)");
+
+    // Deleted storage
+    imap.send("STATUS INBOX (DELETED DELETED-STORAGE)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("(DELETED 0 DELETED-STORAGE 0)");
+    let message = "From: bill@example.com\r\nSubject: deleted\r\n\r\ntest\r\n";
+    imap.send(&format!("APPEND INBOX (\\Deleted) {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STATUS INBOX (DELETED-STORAGE)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("DELETED-STORAGE 0", 0);
+
+    // Only administrators can change quotas
+    imap.send("SETQUOTA \"\" (STORAGE 20)").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("NOPERM");
+
+    imap.send("LOGOUT").await;
+    imap.assert_read(Type::Untagged, ResponseType::Bye).await;
+}
@@ -0,0 +1,127 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    println!("Running REPLACE, SAVEDATE, APPENDLIMIT and PARTIAL tests...");
+
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("REPLACE")
+        .assert_contains("SAVEDATE")
+        .assert_contains("PARTIAL")
+        .assert_contains("APPENDLIMIT=");
+
+    // APPENDLIMIT is reported by STATUS
+    imap.send("CREATE Parmesan").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STATUS Parmesan (MESSAGES APPENDLIMIT)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 0")
+        .assert_contains("APPENDLIMIT ");
+
+    // Append a few drafts
+    for num in 1..=3 {
+        let message = format!("From: test@domain.com\nSubject: Draft {num}\n\nDraft {num}\n");
+        imap.send(&format!("APPEND Parmesan {{{}}}", message.len()))
+            .await;
+        imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+        imap.send_untagged(&message).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+    imap.send("SELECT Parmesan").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Save dates are tracked and searchable
+    imap.send("FETCH 1 (SAVEDATE)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 FETCH (SAVEDATE \"");
+    imap.send("UID SEARCH SAVEDATESUPPORTED SAVEDSINCE 1-Jan-2000")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("UID ALL 1:3");
+    imap.send("UID SEARCH SAVEDBEFORE 1-Jan-2000").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("ALL", 0);
+
+    // Paged search and fetch results
+    imap.send("SEARCH RETURN (PARTIAL 1:2) ALL").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("PARTIAL (1:2 1:2)");
+    imap.send("UID SEARCH RETURN (COUNT PARTIAL -1:-1) ALL")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("COUNT 3")
+        .assert_contains("PARTIAL (-1:-1 3)");
+    imap.send("UID FETCH 1:* (UID) (PARTIAL -1:-1)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("FETCH (", 1)
+        .assert_contains("UID 3");
+
+    // Replace a draft
+    let message = "From: test@domain.com\nSubject: Draft 1\n\nDraft 1 (edited)\n";
+    imap.send(&format!("UID REPLACE 1 Parmesan {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("[APPENDUID ")
+        .assert_contains("* VANISHED 1")
+        .assert_contains("REPLACE completed");
+    imap.send("UID SEARCH ALL").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("UID ALL 2:4");
+    imap.send("FETCH 3 (BODY.PEEK[TEXT])").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("Draft 1 (edited)");
+
+    // Replacing a missing message fails without appending
+    imap.send(&format!("REPLACE 10 Parmesan {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STATUS Parmesan (MESSAGES)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 3");
+    imap.send("DELETE Parmesan").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
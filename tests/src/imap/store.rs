@@ -21,6 +21,8 @@
  * for more details.
 */
 
+use common::scripts::plugins::bayes::bayes_learns;
+use directory::backend::internal::manage::ManageDirectory;
 use imap_proto::ResponseType;
 
 use crate::jmap::wait_for_index;
@@ -91,4 +93,38 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection, h
         .await
         .assert_count("FLAGS", 3)
         .assert_count("Answered", 0);
+
+    // Junk keywords should train the user's Bayes classifier only once per direction
+    let account_id = handle
+        .jmap
+        .core
+        .storage
+        .data
+        .get_or_create_account_id("jdoe@example.com")
+        .await
+        .unwrap();
+    for (command, expected_spam, expected_ham) in [
+        ("UID STORE 1 +FLAGS.SILENT ($Junk)", 1, 0),
+        ("UID STORE 1 +FLAGS.SILENT ($Junk)", 1, 0),
+        ("UID STORE 1 -FLAGS.SILENT ($Junk)", 0, 0),
+        ("UID STORE 1 +FLAGS.SILENT ($Junk)", 1, 0),
+        ("UID STORE 1 +FLAGS.SILENT ($NotJunk)", 0, 1),
+        ("UID STORE 1 FLAGS.SILENT ($NotJunk)", 0, 1),
+        ("UID STORE 1 -FLAGS.SILENT ($NotJunk)", 0, 0),
+    ] {
+        imap.send(command).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+        let learns = bayes_learns(
+            &handle.jmap.core.storage.lookup,
+            &handle.jmap.smtp.inner.script_cache.bayes_cache,
+            account_id.into(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            (learns.spam, learns.ham),
+            (expected_spam, expected_ham),
+            "{command}"
+        );
+    }
 }
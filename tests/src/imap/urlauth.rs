@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    println!("Running CATENATE and URLAUTH tests...");
+
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("CATENATE")
+        .assert_contains("URLAUTH");
+
+    // Append a message to be referenced
+    imap.send("CREATE Mozzarella").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    let message = "From: test@domain.com\r\nSubject: Original\r\n\r\nAttached content\r\n";
+    imap.send(&format!("APPEND Mozzarella {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STATUS Mozzarella (UIDVALIDITY)").await;
+    let uid_validity = imap
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .into_uid_validity();
+
+    // Compose a message using CATENATE
+    let header = "From: jdoe@example.com\r\nSubject: Forwarded\r\n\r\n";
+    imap.send(&format!(
+        "APPEND Mozzarella CATENATE (TEXT {{{}+}}\r\n{header} URL \"/Mozzarella;UIDVALIDITY={uid_validity}/;UID=1/;SECTION=TEXT\")",
+        header.len()
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("APPENDUID");
+    imap.send("SELECT Mozzarella").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID FETCH 2 (BODY[HEADER.FIELDS (SUBJECT)] BODY[TEXT])")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("Subject: Forwarded")
+        .assert_contains("Attached content");
+
+    // Invalid URLs are rejected
+    imap.send(&format!(
+        "APPEND Mozzarella CATENATE (URL \"/Mozzarella;UIDVALIDITY={uid_validity}/;UID=100\")"
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code(&format!(
+            "BADURL /Mozzarella;UIDVALIDITY={uid_validity}/;UID=100"
+        ));
+
+    // Generate an authorized URL
+    let rump = format!(
+        "imap://jdoe%40example.com@localhost/Mozzarella;UIDVALIDITY={uid_validity}/;UID=1;URLAUTH=user+jdoe%40example.com"
+    );
+    imap.send(&format!("GENURLAUTH \"{rump}\" INTERNAL")).await;
+    let url = imap
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .iter()
+        .find_map(|line| {
+            line.strip_prefix("* GENURLAUTH \"")
+                .and_then(|url| url.strip_suffix('"'))
+                .map(|url| url.to_string())
+        })
+        .unwrap();
+    assert!(url.starts_with(&format!("{rump}:internal:")), "{url}");
+
+    // Fetch the authorized URL
+    imap.send(&format!("URLFETCH \"{url}\"")).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("Subject: Original")
+        .assert_contains("Attached content");
+
+    // Tampered tokens are not accepted
+    imap.send(&format!("URLFETCH \"{url}0\"")).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("NIL");
+
+    // Resetting the mailbox key invalidates previously issued URLs
+    imap.send("RESETKEY Mozzarella").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send(&format!("URLFETCH \"{url}\"")).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("NIL");
+
+    // Cleanup
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE Mozzarella").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
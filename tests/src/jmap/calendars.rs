@@ -0,0 +1,723 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose, Engine};
+use directory::backend::internal::manage::ManageDirectory;
+use jmap_proto::types::id::Id;
+use reqwest::{header, Method};
+use serde_json::Value;
+
+use crate::jmap::{assert_is_empty, jmap_json_request, mailbox::destroy_all_mailboxes};
+
+use super::{contacts::Account, delivery::SmtpConnection, JMAPTest};
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Calendars tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    params
+        .directory
+        .create_test_user_with_email("jane.smith@example.com", "abcde", "Jane Smith")
+        .await;
+    let john_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jdoe@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    let jane_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jane.smith@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    let john = Account {
+        id: &john_id,
+        login: "jdoe@example.com",
+        secret: "12345",
+    };
+    let jane = Account {
+        id: &jane_id,
+        login: "jane.smith@example.com",
+        secret: "abcde",
+    };
+
+    // A default calendar is created on first access
+    let response = john
+        .request(r#"[["Calendar/get", {"accountId": "$$"}, "0"]]"#)
+        .await;
+    let default_id = response
+        .pointer("/methodResponses/0/1/list/0/id")
+        .and_then(|v| v.as_str())
+        .unwrap()
+        .to_string();
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/name"),
+        Some(&Value::from("Calendar")),
+        "{response}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/isDefault"),
+        Some(&Value::from(true)),
+        "{response}"
+    );
+
+    // Create a calendar and a recurring event
+    let response = john
+        .request(
+            r##"[["Calendar/set", {"accountId": "$$", "create": {"c1": {"name": "Work", "color": "#ff0000"}}}, "0"],
+            ["CalendarEvent/set", {"accountId": "$$", "create": {"e1": {
+                "calendarIds": {"#c1": true},
+                "title": "Weekly planning",
+                "start": "2024-03-01T10:00:00",
+                "duration": "PT1H",
+                "recurrenceRules": [{"@type": "RecurrenceRule", "frequency": "weekly", "until": "2024-03-29T10:00:00"}]
+            }}}, "1"]]"##,
+        )
+        .await;
+    let work_id = response
+        .pointer("/methodResponses/0/1/created/c1/id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+    let event_id = response
+        .pointer("/methodResponses/1/1/created/e1/id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+    assert!(
+        response
+            .pointer("/methodResponses/1/1/created/e1/uid")
+            .and_then(|v| v.as_str())
+            .is_some(),
+        "{response}"
+    );
+
+    // Fetch the event
+    let response = john
+        .request(
+            r##"[["CalendarEvent/get", {"accountId": "$$", "ids": ["#event"]}, "0"]]"##
+                .replace("#event", &event_id),
+        )
+        .await;
+    let event = response.pointer("/methodResponses/0/1/list/0").unwrap();
+    assert_eq!(event.pointer("/@type"), Some(&Value::from("Event")));
+    assert_eq!(
+        event.pointer("/title"),
+        Some(&Value::from("Weekly planning"))
+    );
+    assert_eq!(
+        event.pointer(&format!("/calendarIds/{work_id}")),
+        Some(&Value::from(true)),
+        "{response}"
+    );
+
+    // Query events by time range, title and calendar
+    for (filter, expected) in [
+        (
+            r#"{"after": "2024-03-10T00:00:00Z", "before": "2024-03-20T00:00:00Z"}"#,
+            1,
+        ),
+        (r#"{"after": "2024-04-01T00:00:00Z"}"#, 0),
+        (r#"{"before": "2024-02-01T00:00:00Z"}"#, 0),
+        (r#"{"title": "planning"}"#, 1),
+        (r#"{"title": "retrospective"}"#, 0),
+        (
+            &format!(r#"{{"inCalendars": ["{default_id}"]}}"#) as &str,
+            0,
+        ),
+        (&format!(r#"{{"inCalendars": ["{work_id}"]}}"#) as &str, 1),
+    ] {
+        let response = john
+            .request(
+                r#"[["CalendarEvent/query", {"accountId": "$$", "filter": #filter}, "0"]]"#
+                    .replace("#filter", filter),
+            )
+            .await;
+        assert_eq!(
+            response
+                .pointer("/methodResponses/0/1/ids")
+                .and_then(|v| v.as_array())
+                .map(|ids| ids.len()),
+            Some(expected),
+            "{filter}: {response}"
+        );
+    }
+
+    // Calendars with events cannot be destroyed unless requested
+    let response = john
+        .request(
+            r##"[["Calendar/set", {"accountId": "$$", "destroy": ["#work"]}, "0"]]"##
+                .replace("#work", &work_id),
+        )
+        .await;
+    assert_eq!(
+        response.pointer(&format!("/methodResponses/0/1/notDestroyed/{work_id}/type")),
+        Some(&Value::from("calendarHasEvent")),
+        "{response}"
+    );
+
+    // CalDAV requests must be authenticated
+    let (status, headers, _) =
+        dav_request(&Method::GET, "/dav/cal/jdoe@example.com/", None, &[], "").await;
+    assert_eq!(status, 401);
+    assert!(headers.contains_key(header::WWW_AUTHENTICATE));
+    let (status, _, _) = dav_request(
+        &Method::OPTIONS,
+        "/dav/cal/jdoe@example.com/",
+        None,
+        &[],
+        "",
+    )
+    .await;
+    assert_eq!(status, 200);
+
+    // Discover the calendar home from the principal
+    let (status, _, body) = dav_request(
+        &propfind(),
+        "/dav/principal/jdoe@example.com/",
+        Some(&john),
+        &[("Depth", "0")],
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+          <d:prop><c:calendar-home-set/><c:calendar-user-address-set/><d:displayname/></d:prop>
+        </d:propfind>"#,
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(
+        body.contains("<d:href>/dav/cal/jdoe@example.com/</d:href>"),
+        "{body}"
+    );
+    assert!(body.contains("mailto:jdoe@example.com"), "{body}");
+
+    // Other users cannot access the calendar home
+    let (status, _, _) = dav_request(
+        &propfind(),
+        "/dav/cal/jdoe@example.com/",
+        Some(&jane),
+        &[("Depth", "1")],
+        "",
+    )
+    .await;
+    assert_eq!(status, 404);
+
+    // List calendars
+    let (status, _, body) = dav_request(
+        &propfind(),
+        "/dav/cal/jdoe@example.com/",
+        Some(&john),
+        &[("Depth", "1")],
+        "",
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    for href in [
+        format!("/dav/cal/jdoe@example.com/{default_id}/"),
+        format!("/dav/cal/jdoe@example.com/{work_id}/"),
+    ] {
+        assert!(body.contains(&href), "{href} {body}");
+    }
+    assert!(
+        body.contains("<d:displayname>Work</d:displayname>"),
+        "{body}"
+    );
+    assert!(body.contains("<c:calendar/>"), "{body}");
+
+    // Create an event over CalDAV
+    let calendar_href = format!("/dav/cal/jdoe@example.com/{default_id}/");
+    let event_href = format!("{calendar_href}dav-event-1.ics");
+    let (status, headers, body) = dav_request(
+        &Method::PUT,
+        &event_href,
+        Some(&john),
+        &[("If-None-Match", "*")],
+        &DAV_EVENT.replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 201, "{body}");
+    let etag = headers
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .unwrap()
+        .to_string();
+    let (status, _, _) = dav_request(
+        &Method::PUT,
+        &event_href,
+        Some(&john),
+        &[("If-None-Match", "*")],
+        &DAV_EVENT.replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 412);
+
+    // Fetch the event over CalDAV and JMAP
+    let (status, headers, body) =
+        dav_request(&Method::GET, &event_href, Some(&john), &[], "").await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(
+        headers.get(header::ETAG).and_then(|v| v.to_str().ok()),
+        Some(etag.as_str())
+    );
+    assert!(body.contains("SUMMARY:Lunch"), "{body}");
+    assert!(body.contains("UID:dav-event-1"), "{body}");
+    let response = john
+        .request(r#"[["CalendarEvent/query", {"accountId": "$$", "filter": {"uid": "dav-event-1"}}, "0"]]"#)
+        .await;
+    let dav_event_id = response
+        .pointer("/methodResponses/0/1/ids/0")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+
+    // Updates require a matching ETag
+    let (status, _, _) = dav_request(
+        &Method::PUT,
+        &event_href,
+        Some(&john),
+        &[("If-Match", "\"abc\"")],
+        &DAV_EVENT
+            .replace("Lunch", "Team lunch")
+            .replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 412);
+    let (status, headers, _) = dav_request(
+        &Method::PUT,
+        &event_href,
+        Some(&john),
+        &[("If-Match", &etag)],
+        &DAV_EVENT
+            .replace("Lunch", "Team lunch")
+            .replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 204);
+    assert_ne!(
+        headers.get(header::ETAG).and_then(|v| v.to_str().ok()),
+        Some(etag.as_str())
+    );
+    let response = john
+        .request(
+            r##"[["CalendarEvent/get", {"accountId": "$$", "ids": ["#event"], "properties": ["title"]}, "0"]]"##
+                .replace("#event", &dav_event_id),
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/title"),
+        Some(&Value::from("Team lunch")),
+        "{response}"
+    );
+
+    // Query events by time range
+    for (range, expected) in [
+        (r#"start="20240305T000000Z" end="20240306T000000Z""#, true),
+        (r#"start="20240401T000000Z" end="20240402T000000Z""#, false),
+    ] {
+        let (status, _, body) = dav_request(
+            &report(),
+            &calendar_href,
+            Some(&john),
+            &[("Depth", "1")],
+            &r#"<?xml version="1.0" encoding="utf-8"?>
+            <c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+              <d:prop><d:getetag/><c:calendar-data/></d:prop>
+              <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT">
+                <c:time-range #range/>
+              </c:comp-filter></c:comp-filter></c:filter>
+            </c:calendar-query>"#
+                .replace("#range", range),
+        )
+        .await;
+        assert_eq!(status, 207, "{body}");
+        assert_eq!(body.contains("SUMMARY:Team lunch"), expected, "{body}");
+    }
+
+    // Fetch events by href
+    let (status, _, body) = dav_request(
+        &report(),
+        &calendar_href,
+        Some(&john),
+        &[],
+        &r#"<?xml version="1.0" encoding="utf-8"?>
+        <c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+          <d:prop><d:getetag/><c:calendar-data/></d:prop>
+          <d:href>#href</d:href>
+          <d:href>#calendarmissing.ics</d:href>
+        </c:calendar-multiget>"#
+            .replace("#href", &event_href)
+            .replace("#calendar", &calendar_href),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains("SUMMARY:Team lunch"), "{body}");
+    assert!(body.contains("404 Not Found"), "{body}");
+
+    // Synchronize the calendar
+    let (status, _, body) = dav_request(
+        &report(),
+        &calendar_href,
+        Some(&john),
+        &[],
+        &SYNC_COLLECTION.replace("#token", ""),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    let resource_href = format!("{calendar_href}{dav_event_id}.ics");
+    assert!(body.contains(&resource_href), "{body}");
+    let sync_token = sync_token(&body);
+
+    // Delete the event and synchronize again
+    let (status, _, _) = dav_request(&Method::DELETE, &resource_href, Some(&john), &[], "").await;
+    assert_eq!(status, 204);
+    let (status, _, body) = dav_request(&Method::GET, &event_href, Some(&john), &[], "").await;
+    assert_eq!(status, 404, "{body}");
+    let (status, _, body) = dav_request(
+        &report(),
+        &calendar_href,
+        Some(&john),
+        &[],
+        &SYNC_COLLECTION.replace("#token", &sync_token),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(
+        body.contains(&format!(
+            "<d:href>{resource_href}</d:href><d:status>HTTP/1.1 404 Not Found</d:status>"
+        )),
+        "{body}"
+    );
+    assert_ne!(sync_token, self::sync_token(&body));
+
+    // Create, rename and delete a calendar over CalDAV
+    let (status, headers, body) = dav_request(
+        &Method::from_bytes(b"MKCALENDAR").unwrap(),
+        "/dav/cal/jdoe@example.com/new-calendar/",
+        Some(&john),
+        &[],
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <c:mkcalendar xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+          <d:set><d:prop><d:displayname>Personal</d:displayname></d:prop></d:set>
+        </c:mkcalendar>"#,
+    )
+    .await;
+    assert_eq!(status, 201, "{body}");
+    let new_href = headers
+        .get(header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .unwrap()
+        .to_string();
+    let (status, _, body) = dav_request(
+        &Method::from_bytes(b"PROPPATCH").unwrap(),
+        &new_href,
+        Some(&john),
+        &[],
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <d:propertyupdate xmlns:d="DAV:" xmlns:a="http://apple.com/ns/ical/">
+          <d:set><d:prop><d:displayname>Family</d:displayname><a:calendar-color>#00ff00</a:calendar-color></d:prop></d:set>
+        </d:propertyupdate>"#,
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains("200 OK"), "{body}");
+    let (status, _, body) =
+        dav_request(&propfind(), &new_href, Some(&john), &[("Depth", "0")], "").await;
+    assert_eq!(status, 207, "{body}");
+    assert!(
+        body.contains("<d:displayname>Family</d:displayname>"),
+        "{body}"
+    );
+    assert!(
+        body.contains("<a:calendar-color>#00ff00</a:calendar-color>"),
+        "{body}"
+    );
+    let (status, _, _) = dav_request(&Method::DELETE, &new_href, Some(&john), &[], "").await;
+    assert_eq!(status, 204);
+    let (status, _, _) =
+        dav_request(&propfind(), &new_href, Some(&john), &[("Depth", "0")], "").await;
+    assert_eq!(status, 404);
+
+    // Invite Jane to an event
+    let response = john
+        .request(
+            r##"[["CalendarEvent/set", {"accountId": "$$", "create": {"e1": {
+                "calendarIds": {"#default": true},
+                "title": "Project kickoff",
+                "start": "2024-04-02T09:00:00",
+                "duration": "PT1H",
+                "replyTo": {"imip": "mailto:jdoe@example.com"},
+                "participants": {
+                    "p1": {"@type": "Participant", "name": "John Doe", "email": "jdoe@example.com",
+                           "sendTo": {"imip": "mailto:jdoe@example.com"},
+                           "roles": {"owner": true, "attendee": true}, "participationStatus": "accepted"},
+                    "p2": {"@type": "Participant", "name": "Jane Smith", "email": "jane.smith@example.com",
+                           "sendTo": {"imip": "mailto:jane.smith@example.com"},
+                           "roles": {"attendee": true}, "participationStatus": "needs-action", "expectReply": true}
+                }
+            }}, "sendSchedulingMessages": true}, "0"]]"##
+                .replace("#default", &default_id),
+        )
+        .await;
+    let invite_id = response
+        .pointer("/methodResponses/0/1/created/e1/id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+    let uid = response
+        .pointer("/methodResponses/0/1/created/e1/uid")
+        .and_then(|v| v.as_str())
+        .unwrap()
+        .to_string();
+
+    // The invitation is delivered to Jane's calendar
+    let jane_event = wait_for_event(&jane, &uid, |event| {
+        event.pointer("/title") == Some(&Value::from("Project kickoff"))
+    })
+    .await;
+    let jane_event_id = jane_event
+        .pointer("/id")
+        .and_then(|v| v.as_str())
+        .unwrap()
+        .to_string();
+    let jane_participant_id = jane_event
+        .pointer("/participants")
+        .and_then(|v| v.as_object())
+        .and_then(|participants| {
+            participants.iter().find_map(|(id, participant)| {
+                (participant.pointer("/email") == Some(&Value::from("jane.smith@example.com"))
+                    || participant.pointer("/sendTo/imip")
+                        == Some(&Value::from("mailto:jane.smith@example.com")))
+                .then(|| id.to_string())
+            })
+        })
+        .unwrap_or_else(|| panic!("{jane_event}"));
+
+    // Jane accepts the invitation
+    let response = jane
+        .request(
+            r##"[["CalendarEvent/set", {"accountId": "$$", "update": {"#event": {
+                "participants/#participant/participationStatus": "accepted"
+            }}, "sendSchedulingMessages": true}, "0"]]"##
+                .replace("#event", &jane_event_id)
+                .replace("#participant", &jane_participant_id),
+        )
+        .await;
+    assert!(
+        response
+            .pointer(&format!("/methodResponses/0/1/updated/{jane_event_id}"))
+            .is_some(),
+        "{response}"
+    );
+    wait_for_event(&john, &uid, |event| {
+        event.pointer("/participants/p2/participationStatus") == Some(&Value::from("accepted"))
+    })
+    .await;
+
+    // John cancels the event
+    let response = john
+        .request(
+            r##"[["CalendarEvent/set", {"accountId": "$$", "destroy": ["#event"],
+                "sendSchedulingMessages": true}, "0"]]"##
+                .replace("#event", &invite_id),
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::from(invite_id.as_str())),
+        "{response}"
+    );
+    wait_for_event(&jane, &uid, |event| {
+        event.pointer("/status") == Some(&Value::from("cancelled"))
+    })
+    .await;
+
+    // iMIP messages from senders that could not be authenticated are ignored
+    let mut lmtp = SmtpConnection::connect().await;
+    lmtp.ingest(
+        "jdoe@example.com",
+        &["jane.smith@example.com"],
+        concat!(
+            "From: jdoe@example.com\r\n",
+            "To: jane.smith@example.com\r\n",
+            "Subject: Invitation: Spoofed\r\n",
+            "Content-Type: text/calendar; method=REQUEST; charset=utf-8\r\n",
+            "\r\n",
+            "BEGIN:VCALENDAR\r\n",
+            "VERSION:2.0\r\n",
+            "PRODID:-//Test//EN\r\n",
+            "METHOD:REQUEST\r\n",
+            "BEGIN:VEVENT\r\n",
+            "UID:spoofed-imip@example.com\r\n",
+            "DTSTAMP:20240401T090000Z\r\n",
+            "DTSTART:20240402T090000Z\r\n",
+            "DURATION:PT1H\r\n",
+            "SUMMARY:Spoofed\r\n",
+            "ORGANIZER:mailto:jdoe@example.com\r\n",
+            "ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:jane.smith@example.com\r\n",
+            "END:VEVENT\r\n",
+            "END:VCALENDAR\r\n",
+        ),
+    )
+    .await;
+    let response = jane
+        .request(
+            r#"[["CalendarEvent/query", {"accountId": "$$",
+                "filter": {"uid": "spoofed-imip@example.com"}}, "0"]]"#,
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/ids"),
+        Some(&Value::Array(vec![])),
+        "{response}"
+    );
+
+    // Destroy test account data
+    for account_id in [&john_id, &jane_id] {
+        let response = jmap_json_request(
+            r##"[["Calendar/get", {"accountId": "$$", "properties": ["id"]}, "0"],
+            ["Calendar/set", {"accountId": "$$", "#destroy": {
+                "resultOf": "0", "name": "Calendar/get", "path": "/list/*/id"
+            }, "onDestroyRemoveEvents": true}, "1"]]"##
+                .replace("$$", account_id),
+            "admin",
+            "secret",
+        )
+        .await;
+        assert_eq!(
+            response.pointer("/methodResponses/1/1/notDestroyed"),
+            None,
+            "{response}"
+        );
+        params.client.set_default_account_id(account_id.as_str());
+        destroy_all_mailboxes(params).await;
+    }
+    assert_is_empty(server).await;
+}
+
+async fn wait_for_event(
+    account: &Account<'_>,
+    uid: &str,
+    matches: impl Fn(&Value) -> bool,
+) -> Value {
+    let mut last_response = Value::Null;
+    for _ in 0..50 {
+        let response = account
+            .request(
+                r##"[["CalendarEvent/query", {"accountId": "$$", "filter": {"uid": "#uid"}}, "0"],
+                ["CalendarEvent/get", {"accountId": "$$", "#ids": {
+                    "resultOf": "0", "name": "CalendarEvent/query", "path": "/ids"
+                }}, "1"]]"##
+                    .replace("#uid", uid),
+            )
+            .await;
+        if let Some(event) = response
+            .pointer("/methodResponses/1/1/list/0")
+            .filter(|event| matches(event))
+        {
+            return event.clone();
+        }
+        last_response = response;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Timed out waiting for event {uid}: {last_response}");
+}
+
+pub async fn dav_request(
+    method: &Method,
+    path: &str,
+    account: Option<&Account<'_>>,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (u16, header::HeaderMap, String) {
+    let mut request = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .timeout(Duration::from_millis(2000))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .request(method.clone(), format!("https://127.0.0.1:8899{path}"));
+    if let Some(account) = account {
+        request = request.header(
+            header::AUTHORIZATION,
+            format!(
+                "Basic {}",
+                general_purpose::STANDARD.encode(format!("{}:{}", account.login, account.secret))
+            ),
+        );
+    }
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = request.body(body.to_string()).send().await.unwrap();
+    let status = response.status().as_u16();
+    let headers = response.headers().clone();
+    (status, headers, response.text().await.unwrap())
+}
+
+pub fn sync_token(body: &str) -> String {
+    body.split_once("<d:sync-token>")
+        .and_then(|(_, token)| token.split_once("</d:sync-token>"))
+        .map(|(token, _)| token.to_string())
+        .unwrap_or_else(|| panic!("{body}"))
+}
+
+pub fn propfind() -> Method {
+    Method::from_bytes(b"PROPFIND").unwrap()
+}
+
+pub fn report() -> Method {
+    Method::from_bytes(b"REPORT").unwrap()
+}
+
+const DAV_EVENT: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Example Corp.//CalDAV Client//EN
+BEGIN:VEVENT
+UID:dav-event-1
+DTSTAMP:20240301T090000Z
+DTSTART:20240305T120000Z
+DTEND:20240305T130000Z
+SUMMARY:Lunch
+END:VEVENT
+END:VCALENDAR
+"#;
+
+pub const SYNC_COLLECTION: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:sync-collection xmlns:d="DAV:">
+  <d:sync-token>#token</d:sync-token>
+  <d:sync-level>1</d:sync-level>
+  <d:prop><d:getetag/></d:prop>
+</d:sync-collection>"#;
@@ -0,0 +1,538 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::backend::internal::manage::ManageDirectory;
+use jmap_proto::types::id::Id;
+use reqwest::{header, Method};
+use serde_json::Value;
+
+use crate::jmap::{assert_is_empty, jmap_json_request};
+
+use super::{
+    calendars::{dav_request, propfind, report, sync_token, SYNC_COLLECTION},
+    contacts::Account,
+    JMAPTest,
+};
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running CardDAV tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    params
+        .directory
+        .create_test_user_with_email("jane.smith@example.com", "abcde", "Jane Smith")
+        .await;
+    let john_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jdoe@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    let jane_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jane.smith@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    let john = Account {
+        id: &john_id,
+        login: "jdoe@example.com",
+        secret: "12345",
+    };
+    let jane = Account {
+        id: &jane_id,
+        login: "jane.smith@example.com",
+        secret: "abcde",
+    };
+    let response = john
+        .request(r#"[["AddressBook/get", {"accountId": "$$"}, "0"]]"#)
+        .await;
+    let default_id = response
+        .pointer("/methodResponses/0/1/list/0/id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+
+    // CardDAV requests must be authenticated
+    let (status, headers, _) =
+        dav_request(&Method::GET, "/dav/card/jdoe@example.com/", None, &[], "").await;
+    assert_eq!(status, 401);
+    assert!(headers.contains_key(header::WWW_AUTHENTICATE));
+    let (status, headers, _) =
+        dav_request(&Method::GET, "/.well-known/carddav", None, &[], "").await;
+    assert_eq!(status, 301);
+    assert_eq!(
+        headers.get(header::LOCATION).and_then(|v| v.to_str().ok()),
+        Some("/dav/card/")
+    );
+
+    // Discover the address book home from the principal
+    let (status, _, body) = dav_request(
+        &propfind(),
+        "/dav/principal/jdoe@example.com/",
+        Some(&john),
+        &[("Depth", "0")],
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <d:propfind xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
+          <d:prop><card:addressbook-home-set/></d:prop>
+        </d:propfind>"#,
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(
+        body.contains("<d:href>/dav/card/jdoe@example.com/</d:href>"),
+        "{body}"
+    );
+
+    // List address books, including the global address list
+    let (status, _, body) = dav_request(
+        &propfind(),
+        "/dav/card/jdoe@example.com/",
+        Some(&john),
+        &[("Depth", "1")],
+        "",
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    for href in [
+        format!("/dav/card/jdoe@example.com/{default_id}/"),
+        "/dav/card/jdoe@example.com/directory/".to_string(),
+    ] {
+        assert!(body.contains(&href), "{href} {body}");
+    }
+    assert!(body.contains("<card:addressbook/>"), "{body}");
+    assert!(
+        body.contains("<d:displayname>Directory</d:displayname>"),
+        "{body}"
+    );
+
+    // Other users cannot access address books that were not shared with them
+    let book_href = format!("/dav/card/jdoe@example.com/{default_id}/");
+    let (status, _, _) =
+        dav_request(&propfind(), &book_href, Some(&jane), &[("Depth", "1")], "").await;
+    assert_eq!(status, 404);
+
+    // Create a card over CardDAV
+    let card_href = format!("{book_href}dav-card-1.vcf");
+    let (status, headers, body) = dav_request(
+        &Method::PUT,
+        &card_href,
+        Some(&john),
+        &[("If-None-Match", "*")],
+        &DAV_CARD.replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 201, "{body}");
+    let etag = headers
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .unwrap()
+        .to_string();
+    let (status, _, _) = dav_request(
+        &Method::PUT,
+        &card_href,
+        Some(&john),
+        &[("If-None-Match", "*")],
+        &DAV_CARD.replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 412);
+
+    // Fetch the card over CardDAV and JMAP
+    let (status, headers, body) = dav_request(&Method::GET, &card_href, Some(&john), &[], "").await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(
+        headers.get(header::ETAG).and_then(|v| v.to_str().ok()),
+        Some(etag.as_str())
+    );
+    for line in [
+        "VERSION:3.0",
+        "UID:dav-card-1",
+        "FN:Sarah Connor",
+        "N:Connor;Sarah;;;",
+        "EMAIL;TYPE=WORK;TYPE=PREF:sarah@example.org",
+        "TEL;TYPE=HOME;TYPE=CELL:+1-555-0100",
+        "ORG:Cyberdyne;Research",
+        "BDAY:1965-05-13",
+    ] {
+        assert!(body.contains(line), "{line} {body}");
+    }
+    let response = john
+        .request(
+            r##"[["ContactCard/query", {"accountId": "$$", "filter": {"uid": "dav-card-1"}}, "0"],
+            ["ContactCard/get", {"accountId": "$$", "#ids": {
+                "resultOf": "0", "name": "ContactCard/query", "path": "/ids"
+            }}, "1"]]"##,
+        )
+        .await;
+    let card = response
+        .pointer("/methodResponses/1/1/list/0")
+        .unwrap_or_else(|| panic!("{response}"));
+    let dav_card_id = card
+        .pointer("/id")
+        .and_then(|v| v.as_str())
+        .unwrap()
+        .to_string();
+    for (pointer, expected) in [
+        ("/name/full", Value::from("Sarah Connor")),
+        ("/emails/e1/address", Value::from("sarah@example.org")),
+        ("/emails/e1/contexts/work", Value::from(true)),
+        ("/emails/e1/pref", Value::from(1)),
+        ("/phones/p1/features/mobile", Value::from(true)),
+        ("/organizations/o1/units/0/name", Value::from("Research")),
+        ("/anniversaries/k1/date/year", Value::from(1965)),
+    ] {
+        assert_eq!(card.pointer(pointer), Some(&expected), "{pointer} {card}");
+    }
+
+    // Updates require a matching ETag
+    let (status, _, _) = dav_request(
+        &Method::PUT,
+        &card_href,
+        Some(&john),
+        &[("If-Match", "\"abc\"")],
+        &DAV_CARD
+            .replace("Sarah Connor", "Sarah J. Connor")
+            .replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 412);
+    let (status, headers, _) = dav_request(
+        &Method::PUT,
+        &card_href,
+        Some(&john),
+        &[("If-Match", &etag)],
+        &DAV_CARD
+            .replace("Sarah Connor", "Sarah J. Connor")
+            .replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 204);
+    assert_ne!(
+        headers.get(header::ETAG).and_then(|v| v.to_str().ok()),
+        Some(etag.as_str())
+    );
+    let response = john
+        .request(
+            r##"[["ContactCard/get", {"accountId": "$$", "ids": ["#id"]}, "0"]]"##
+                .replace("#id", &dav_card_id),
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/name/full"),
+        Some(&Value::from("Sarah J. Connor")),
+        "{response}"
+    );
+
+    // Query cards
+    for (filter, expected) in [
+        (
+            r#"<card:prop-filter name="EMAIL"><card:text-match match-type="contains">example.org</card:text-match></card:prop-filter>"#,
+            true,
+        ),
+        (
+            r#"<card:prop-filter name="FN"><card:text-match match-type="starts-with">John</card:text-match></card:prop-filter>"#,
+            false,
+        ),
+        (
+            r#"<card:prop-filter name="NICKNAME"><card:is-not-defined/></card:prop-filter>"#,
+            true,
+        ),
+    ] {
+        let (status, _, body) = dav_request(
+            &report(),
+            &book_href,
+            Some(&john),
+            &[("Depth", "1")],
+            &format!(
+                concat!(
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?>",
+                    "<card:addressbook-query xmlns:d=\"DAV:\" xmlns:card=\"urn:ietf:params:xml:ns:carddav\">",
+                    "<d:prop><d:getetag/><card:address-data/></d:prop>",
+                    "<card:filter>{}</card:filter></card:addressbook-query>"
+                ),
+                filter
+            ),
+        )
+        .await;
+        assert_eq!(status, 207, "{body}");
+        assert_eq!(
+            body.contains(&format!("{book_href}{dav_card_id}.vcf")),
+            expected,
+            "{filter} {body}"
+        );
+    }
+
+    // Fetch the card as vCard 4.0
+    let (status, _, body) = dav_request(
+        &report(),
+        &book_href,
+        Some(&john),
+        &[("Depth", "1")],
+        &format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>",
+                "<card:addressbook-multiget xmlns:d=\"DAV:\" xmlns:card=\"urn:ietf:params:xml:ns:carddav\">",
+                "<d:prop><d:getetag/><card:address-data version=\"4.0\"/></d:prop>",
+                "<d:href>{}</d:href><d:href>{}missing.vcf</d:href>",
+                "</card:addressbook-multiget>"
+            ),
+            card_href, book_href
+        ),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains("VERSION:4.0"), "{body}");
+    assert!(
+        body.contains("EMAIL;TYPE=work;PREF=1:sarah@example.org"),
+        "{body}"
+    );
+    assert!(body.contains("404 Not Found"), "{body}");
+
+    // Synchronize changes made over JMAP
+    let (status, _, body) = dav_request(
+        &report(),
+        &book_href,
+        Some(&john),
+        &[],
+        &SYNC_COLLECTION.replace("#token", ""),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    let token = sync_token(&body);
+    let response = john
+        .request(
+            r##"[["ContactCard/set", {"accountId": "$$", "create": {"c1": {
+                "addressBookIds": {"#book": true},
+                "name": {"full": "Kyle Reese"}
+            }}}, "0"]]"##
+                .replace("#book", &default_id),
+        )
+        .await;
+    let jmap_card_id = response
+        .pointer("/methodResponses/0/1/created/c1/id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+    let (status, _, body) = dav_request(
+        &report(),
+        &book_href,
+        Some(&john),
+        &[],
+        &SYNC_COLLECTION.replace("#token", &token),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(
+        body.contains(&format!("{book_href}{jmap_card_id}.vcf")),
+        "{body}"
+    );
+    assert!(!body.contains(&dav_card_id), "{body}");
+    let token = sync_token(&body);
+    let (status, _, _) = dav_request(
+        &Method::DELETE,
+        &format!("{book_href}{jmap_card_id}.vcf"),
+        Some(&john),
+        &[],
+        "",
+    )
+    .await;
+    assert_eq!(status, 204);
+    let (status, _, body) = dav_request(
+        &report(),
+        &book_href,
+        Some(&john),
+        &[],
+        &SYNC_COLLECTION.replace("#token", &token),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains("404 Not Found"), "{body}");
+
+    // Create, rename and delete an address book
+    let (status, headers, body) = dav_request(
+        &Method::from_bytes(b"MKCOL").unwrap(),
+        "/dav/card/jdoe@example.com/friends/",
+        Some(&john),
+        &[],
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <d:mkcol xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
+          <d:set><d:prop>
+            <d:resourcetype><d:collection/><card:addressbook/></d:resourcetype>
+            <d:displayname>Friends</d:displayname>
+          </d:prop></d:set>
+        </d:mkcol>"#,
+    )
+    .await;
+    assert_eq!(status, 201, "{body}");
+    let friends_href = headers
+        .get(header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .unwrap()
+        .to_string();
+    let (status, _, body) = dav_request(
+        &Method::from_bytes(b"PROPPATCH").unwrap(),
+        &friends_href,
+        Some(&john),
+        &[],
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <d:propertyupdate xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
+          <d:set><d:prop>
+            <d:displayname>Close friends</d:displayname>
+            <card:addressbook-description>People I trust</card:addressbook-description>
+          </d:prop></d:set>
+        </d:propertyupdate>"#,
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains("200 OK"), "{body}");
+    let (_, _, body) = dav_request(
+        &propfind(),
+        &friends_href,
+        Some(&john),
+        &[("Depth", "0")],
+        "",
+    )
+    .await;
+    assert!(
+        body.contains("<d:displayname>Close friends</d:displayname>"),
+        "{body}"
+    );
+    let (status, _, _) = dav_request(&Method::DELETE, &friends_href, Some(&john), &[], "").await;
+    assert_eq!(status, 204);
+
+    // Shared address books are read-only unless write access is granted
+    let response = john
+        .request(
+            r##"[["AddressBook/set", {"accountId": "$$", "update": {"#default": {
+                "acl": {"jane.smith@example.com": ["read", "readItems"]}
+            }}}, "0"]]"##
+                .replace("#default", &default_id),
+        )
+        .await;
+    assert!(
+        response
+            .pointer(&format!("/methodResponses/0/1/updated/{default_id}"))
+            .is_some(),
+        "{response}"
+    );
+    let (status, _, body) =
+        dav_request(&propfind(), &book_href, Some(&jane), &[("Depth", "1")], "").await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains(&dav_card_id), "{body}");
+    let (status, _, _) = dav_request(
+        &Method::PUT,
+        &format!("{book_href}dav-card-2.vcf"),
+        Some(&jane),
+        &[],
+        &DAV_CARD
+            .replace("dav-card-1", "dav-card-2")
+            .replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 403);
+
+    // The global address list is generated from the directory
+    let directory_href = "/dav/card/jdoe@example.com/directory/";
+    let (status, _, body) = dav_request(
+        &report(),
+        directory_href,
+        Some(&john),
+        &[("Depth", "1")],
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>",
+            "<card:addressbook-query xmlns:d=\"DAV:\" xmlns:card=\"urn:ietf:params:xml:ns:carddav\">",
+            "<d:prop><d:getetag/><card:address-data/></d:prop>",
+            "<card:filter><card:prop-filter name=\"EMAIL\">",
+            "<card:text-match match-type=\"equals\">jane.smith@example.com</card:text-match>",
+            "</card:prop-filter></card:filter></card:addressbook-query>"
+        ),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains("FN:Jane Smith"), "{body}");
+    assert!(!body.contains("FN:John Doe"), "{body}");
+    let (status, _, body) = dav_request(
+        &Method::GET,
+        &format!("{directory_href}{jane_id}.vcf"),
+        Some(&john),
+        &[],
+        "",
+    )
+    .await;
+    assert_eq!(status, 200, "{body}");
+    assert!(body.contains("EMAIL"), "{body}");
+    let (status, _, _) = dav_request(
+        &Method::PUT,
+        &format!("{directory_href}{jane_id}.vcf"),
+        Some(&john),
+        &[],
+        &DAV_CARD.replace('\n', "\r\n"),
+    )
+    .await;
+    assert_eq!(status, 403);
+
+    // Destroy test account data
+    for account_id in [&john_id, &jane_id] {
+        let response = jmap_json_request(
+            r##"[["AddressBook/get", {"accountId": "$$", "properties": ["id"]}, "0"],
+            ["AddressBook/set", {"accountId": "$$", "#destroy": {
+                "resultOf": "0", "name": "AddressBook/get", "path": "/list/*/id"
+            }, "onDestroyRemoveContents": true}, "1"]]"##
+                .replace("$$", account_id),
+            "admin",
+            "secret",
+        )
+        .await;
+        assert_eq!(
+            response.pointer("/methodResponses/1/1/notDestroyed"),
+            None,
+            "{response}"
+        );
+    }
+    assert_is_empty(server).await;
+}
+
+const DAV_CARD: &str = r#"BEGIN:VCARD
+VERSION:3.0
+PRODID:-//Example Corp.//CardDAV Client//EN
+UID:dav-card-1
+FN:Sarah Connor
+N:Connor;Sarah;;;
+item1.EMAIL;TYPE=INTERNET,WORK,pref:sarah@example.org
+TEL;TYPE=HOME;TYPE=CELL:+1-555-0100
+ORG:Cyberdyne;Research
+BDAY:1965-05-13
+END:VCARD
+"#;
@@ -0,0 +1,421 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::backend::internal::manage::ManageDirectory;
+use jmap_proto::types::id::Id;
+use serde_json::Value;
+
+use crate::jmap::{assert_is_empty, jmap_json_request};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Contacts tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    params
+        .directory
+        .create_test_user_with_email("jane.smith@example.com", "abcde", "Jane Smith")
+        .await;
+    let john_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jdoe@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    let jane_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jane.smith@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    let john = Account {
+        id: &john_id,
+        login: "jdoe@example.com",
+        secret: "12345",
+    };
+    let jane = Account {
+        id: &jane_id,
+        login: "jane.smith@example.com",
+        secret: "abcde",
+    };
+
+    // A default address book is created on first access
+    let response = john
+        .request(r#"[["AddressBook/get", {"accountId": "$$"}, "0"]]"#)
+        .await;
+    let default_id = response
+        .pointer("/methodResponses/0/1/list/0/id")
+        .and_then(|v| v.as_str())
+        .unwrap()
+        .to_string();
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/name"),
+        Some(&Value::from("Contacts")),
+        "{response}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/isDefault"),
+        Some(&Value::from(true)),
+        "{response}"
+    );
+
+    // Create an address book and a card that belongs to both books
+    let response = john
+        .request(
+            r##"[["AddressBook/set", {"accountId": "$$", "create": {"b1": {"name": "Work"}}}, "0"],
+            ["ContactCard/set", {"accountId": "$$", "create": {"c1": {
+                "addressBookIds": {"#default": true, "#b1": true},
+                "kind": "individual",
+                "name": {"full": "Joe Bloggs"},
+                "emails": {"e1": {"address": "joe@example.org"}},
+                "phones": {"p1": {"number": "+1-555-0100"}}
+            }}}, "1"]]"##
+                .replace("#default", &default_id),
+        )
+        .await;
+    let work_id = response
+        .pointer("/methodResponses/0/1/created/b1/id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+    let card_id = response
+        .pointer("/methodResponses/1/1/created/c1/id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+    let uid = response
+        .pointer("/methodResponses/1/1/created/c1/uid")
+        .and_then(|v| v.as_str())
+        .unwrap()
+        .to_string();
+    assert!(uid.starts_with("urn:uuid:"), "{uid}");
+
+    // Fetch the card
+    let response = john
+        .request(
+            r##"[["ContactCard/get", {"accountId": "$$", "ids": ["#card"]}, "0"]]"##
+                .replace("#card", &card_id),
+        )
+        .await;
+    let card = response.pointer("/methodResponses/0/1/list/0").unwrap();
+    assert_eq!(card.pointer("/@type"), Some(&Value::from("Card")));
+    assert_eq!(card.pointer("/version"), Some(&Value::from("1.0")));
+    assert_eq!(card.pointer("/name/full"), Some(&Value::from("Joe Bloggs")));
+    assert_eq!(
+        card.pointer("/emails/e1/address"),
+        Some(&Value::from("joe@example.org"))
+    );
+    assert_eq!(
+        card.pointer(&format!("/addressBookIds/{work_id}")),
+        Some(&Value::from(true))
+    );
+
+    // Query cards
+    for (filter, expected) in [
+        (r#"{"email": "joe@example"}"#, 1),
+        (r#"{"email": "jane@example"}"#, 0),
+        (r#"{"phone": "0100"}"#, 1),
+        (r#"{"name": "bloggs"}"#, 1),
+        (r#"{"text": "JOE"}"#, 1),
+        (r#"{"kind": "individual"}"#, 1),
+        (r#"{"kind": "group"}"#, 0),
+        (r##"{"inAddressBook": "#work"}"##, 1),
+        (
+            r##"{"operator": "NOT", "conditions": [{"inAddressBook": "#work"}]}"##,
+            0,
+        ),
+    ] {
+        let response = john
+            .request(
+                r#"[["ContactCard/query", {"accountId": "$$", "filter": #filter}, "0"]]"#
+                    .replace("#filter", &filter.replace("#work", &work_id)),
+            )
+            .await;
+        assert_eq!(
+            response
+                .pointer("/methodResponses/0/1/ids")
+                .and_then(|v| v.as_array())
+                .map(|ids| ids.len()),
+            Some(expected),
+            "{filter}: {response}"
+        );
+    }
+
+    // Patch the card using JSON pointers and remove it from the work address book
+    let response = john
+        .request(
+            r##"[["ContactCard/set", {"accountId": "$$", "update": {"#card": {
+                "name/full": "Joseph Bloggs",
+                "phones": null,
+                "addressBookIds/#work": false
+            }}}, "0"],
+            ["ContactCard/set", {"accountId": "$$", "update": {"#card": {
+                "uid": "urn:uuid:changed"
+            }}}, "1"],
+            ["ContactCard/get", {"accountId": "$$", "ids": ["#card"]}, "2"]]"##
+                .replace("#card", &card_id)
+                .replace("#work", &work_id),
+        )
+        .await;
+    assert!(
+        response
+            .pointer(&format!("/methodResponses/0/1/updated/{card_id}"))
+            .is_some(),
+        "{response}"
+    );
+    assert_eq!(
+        response.pointer(&format!("/methodResponses/1/1/notUpdated/{card_id}/type")),
+        Some(&Value::from("invalidProperties")),
+        "{response}"
+    );
+    let card = response.pointer("/methodResponses/2/1/list/0").unwrap();
+    assert_eq!(
+        card.pointer("/name/full"),
+        Some(&Value::from("Joseph Bloggs"))
+    );
+    assert_eq!(card.pointer("/phones"), None);
+    assert_eq!(card.pointer(&format!("/addressBookIds/{work_id}")), None);
+    assert_eq!(card.pointer("/uid"), Some(&Value::from(uid.as_str())));
+
+    // Changes
+    let response = john
+        .request(
+            r#"[["ContactCard/changes", {"accountId": "$$", "sinceState": "n"}, "0"],
+            ["AddressBook/changes", {"accountId": "$$", "sinceState": "n"}, "1"]]"#,
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/created/0"),
+        Some(&Value::from(card_id.as_str())),
+        "{response}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/created/0"),
+        Some(&Value::from(work_id.as_str())),
+        "{response}"
+    );
+
+    // Make the work address book the default one
+    let response = john
+        .request(
+            r##"[["AddressBook/set", {"accountId": "$$", "onSuccessSetIsDefault": "#work"}, "0"],
+            ["AddressBook/get", {"accountId": "$$", "ids": ["#work", "#default"], "properties": ["isDefault"]}, "1"]]"##
+                .replace("#work", &work_id)
+                .replace("#default", &default_id),
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/list/0/isDefault"),
+        Some(&Value::from(true)),
+        "{response}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/list/1/isDefault"),
+        Some(&Value::from(false)),
+        "{response}"
+    );
+
+    // Jane has no access to John's contacts until the address book is shared
+    let response = jane
+        .request_as(
+            &john_id,
+            r#"[["ContactCard/get", {"accountId": "$$"}, "0"]]"#,
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/0"),
+        Some(&Value::from("error")),
+        "{response}"
+    );
+    let response = john
+        .request(
+            r##"[["AddressBook/set", {"accountId": "$$", "update": {"#default": {
+                "acl": {"jane.smith@example.com": ["read", "readItems"]}
+            }}}, "0"]]"##
+                .replace("#default", &default_id),
+        )
+        .await;
+    assert!(
+        response
+            .pointer(&format!("/methodResponses/0/1/updated/{default_id}"))
+            .is_some(),
+        "{response}"
+    );
+    let response = jane
+        .request_as(
+            &john_id,
+            r##"[["AddressBook/get", {"accountId": "$$"}, "0"],
+            ["ContactCard/get", {"accountId": "$$"}, "1"],
+            ["ContactCard/set", {"accountId": "$$", "update": {"#card": {"kind": "org"}}}, "2"]]"##
+                .replace("#card", &card_id),
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/id"),
+        Some(&Value::from(default_id.as_str())),
+        "{response}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/myRights/mayWrite"),
+        Some(&Value::from(false)),
+        "{response}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/list/0/id"),
+        Some(&Value::from(card_id.as_str())),
+        "{response}"
+    );
+    assert_eq!(
+        response.pointer(&format!("/methodResponses/2/1/notUpdated/{card_id}/type")),
+        Some(&Value::from("forbidden")),
+        "{response}"
+    );
+
+    // Copy the card into Jane's account
+    let jane_default_id = jane
+        .request(r#"[["AddressBook/get", {"accountId": "$$"}, "0"]]"#)
+        .await
+        .pointer("/methodResponses/0/1/list/0/id")
+        .and_then(|v| v.as_str())
+        .unwrap()
+        .to_string();
+    let response = jane
+        .request(
+            r##"[["ContactCard/copy", {"fromAccountId": "#john", "accountId": "$$", "create": {
+                "#card": {"addressBookIds": {"#default": true}}
+            }}, "0"]]"##
+                .replace("#john", &john_id)
+                .replace("#card", &card_id)
+                .replace("#default", &jane_default_id),
+        )
+        .await;
+    let jane_card_id = response
+        .pointer(&format!("/methodResponses/0/1/created/{card_id}/id"))
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+    let response = jane
+        .request(
+            r##"[["ContactCard/get", {"accountId": "$$", "ids": ["#card"]}, "0"]]"##
+                .replace("#card", &jane_card_id),
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/name/full"),
+        Some(&Value::from("Joseph Bloggs")),
+        "{response}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/uid"),
+        Some(&Value::from(uid.as_str())),
+        "{response}"
+    );
+
+    // Address books with contents cannot be destroyed unless requested
+    let response = john
+        .request(
+            r##"[["AddressBook/set", {"accountId": "$$", "destroy": ["#default"]}, "0"]]"##
+                .replace("#default", &default_id),
+        )
+        .await;
+    assert_eq!(
+        response.pointer(&format!(
+            "/methodResponses/0/1/notDestroyed/{default_id}/type"
+        )),
+        Some(&Value::from("addressBookHasContents")),
+        "{response}"
+    );
+    let response = john
+        .request(
+            r##"[["AddressBook/set", {"accountId": "$$", "destroy": ["#default"], "onDestroyRemoveContents": true}, "0"],
+            ["ContactCard/get", {"accountId": "$$"}, "1"]]"##
+                .replace("#default", &default_id),
+        )
+        .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::from(default_id.as_str())),
+        "{response}"
+    );
+    assert_eq!(
+        response
+            .pointer("/methodResponses/1/1/list")
+            .and_then(|v| v.as_array())
+            .map(|list| list.len()),
+        Some(0),
+        "{response}"
+    );
+
+    // Destroy test account data
+    for account_id in [&john_id, &jane_id] {
+        let response = jmap_json_request(
+            r##"[["AddressBook/get", {"accountId": "$$", "properties": ["id"]}, "0"],
+            ["AddressBook/set", {"accountId": "$$", "#destroy": {
+                "resultOf": "0", "name": "AddressBook/get", "path": "/list/*/id"
+            }, "onDestroyRemoveContents": true}, "1"]]"##
+                .replace("$$", account_id),
+            "admin",
+            "secret",
+        )
+        .await;
+        assert_eq!(
+            response.pointer("/methodResponses/1/1/notDestroyed"),
+            None,
+            "{response}"
+        );
+    }
+    assert_is_empty(server).await;
+}
+
+pub struct Account<'x> {
+    pub id: &'x str,
+    pub login: &'x str,
+    pub secret: &'x str,
+}
+
+impl Account<'_> {
+    pub async fn request(&self, body: impl AsRef<str>) -> Value {
+        self.request_as(self.id, body).await
+    }
+
+    pub async fn request_as(&self, account_id: &str, body: impl AsRef<str>) -> Value {
+        jmap_json_request(
+            body.as_ref().replace("$$", account_id),
+            self.login,
+            self.secret,
+        )
+        .await
+    }
+}
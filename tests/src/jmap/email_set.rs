@@ -25,7 +25,11 @@ use std::{fs, path::PathBuf};
 
 use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes};
 use ahash::AHashSet;
-use jmap::mailbox::INBOX_ID;
+use common::scripts::plugins::bayes::bayes_learns;
+use jmap::{
+    mailbox::{INBOX_ID, JUNK_ID},
+    JMAP,
+};
 use jmap_client::{
     client::Client,
     core::set::{SetError, SetErrorType},
@@ -46,6 +50,7 @@ pub async fn test(params: &mut JMAPTest) {
 
     create(&mut params.client, &mailbox_id).await;
     update(&mut params.client, &mailbox_id).await;
+    spam_train(&mut params.client, &server, &mailbox_id).await;
 
     destroy_all_mailboxes(params).await;
     assert_is_empty(server).await;
@@ -312,6 +317,62 @@ async fn update(client: &mut Client, root_mailbox_id: &str) {
         .unwrap();
 }
 
+async fn spam_train(client: &mut Client, server: &JMAP, root_mailbox_id: &str) {
+    let mailbox = client
+        .email_query(
+            email::query::Filter::in_mailbox(root_mailbox_id).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap();
+    let junk_mailbox_id = Id::from(JUNK_ID).to_string();
+    let account_id = Id::from_bytes(client.default_account_id().as_bytes())
+        .unwrap()
+        .document_id();
+
+    // Moving messages into or out of Junk should train the user's classifier once
+    for (mailbox_id, keyword, expected_spam, expected_ham) in [
+        (&junk_mailbox_id, None, 1, 0),
+        (&junk_mailbox_id, Some(("$junk", true)), 1, 0),
+        (&root_mailbox_id.to_string(), None, 0, 1),
+        (&root_mailbox_id.to_string(), Some(("$notjunk", true)), 0, 1),
+        (
+            &root_mailbox_id.to_string(),
+            Some(("$notjunk", false)),
+            0,
+            0,
+        ),
+    ] {
+        let mut request = client.build();
+        let update = request
+            .set_email()
+            .update(mailbox.id(0))
+            .mailbox_ids([mailbox_id]);
+        if let Some((keyword, set)) = keyword {
+            update.keyword(keyword, set);
+        }
+        request
+            .send_set_email()
+            .await
+            .unwrap()
+            .updated(mailbox.id(0))
+            .unwrap();
+
+        let learns = bayes_learns(
+            &server.core.storage.lookup,
+            &server.smtp.inner.script_cache.bayes_cache,
+            account_id.into(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            (learns.spam, learns.ham),
+            (expected_spam, expected_ham),
+            "{mailbox_id} {keyword:?}"
+        );
+    }
+}
+
 pub async fn assert_email_properties(
     client: &mut Client,
     message_id: &str,
@@ -59,6 +59,9 @@ pub mod auth_acl;
 pub mod auth_limits;
 pub mod auth_oauth;
 pub mod blob;
+pub mod calendars;
+pub mod carddav;
+pub mod contacts;
 pub mod crypto;
 pub mod delivery;
 pub mod email_changes;
@@ -207,6 +210,11 @@ directory = "auth"
 [spam.header]
 is-spam  = "X-Spam-Status: Yes"
 
+[spam.train]
+enable = true
+per-user = true
+global = false
+
 [jmap.protocol.get]
 max-objects = 100000
 
@@ -245,6 +253,23 @@ auto-expunge = "1s"
 [jmap.protocol.changes]
 max-history = "1s"
 
+[jmap.contacts.global-address-list]
+enable = true
+
+[sieve.untrusted]
+notification-uris = ["mailto", "https"]
+ext-lists = ["vip-senders"]
+
+[sieve.untrusted.limits]
+notify-rate = "2/1h"
+
+[sieve.untrusted.editheader]
+allow-add = ["Subject", "X-Filtered", "X-Sieve-*"]
+allow-delete = ["Subject", "X-Spam-*"]
+
+[sieve.untrusted.setenvelope]
+allow = ["from"]
+
 [store."auth"]
 type = "sqlite"
 path = "{TMP}/auth.db"
@@ -282,6 +307,9 @@ token = "1s"
 refresh-token = "3s"
 refresh-token-renew = "2s"
 
+[lookup]
+"vip-senders" = {"ceo@remote.org"}
+
 [session.extensions]
 expn = true
 vrfy = true
@@ -291,7 +319,8 @@ url = "http://127.0.0.1:8821/hook"
 events = ["auth.success", "auth.failure", "auth.banned", "auth.error", 
           "message.accepted", "message.rejected", "message.appended", 
           "account.over-quota", "dsn", "double-bounce", "report.incoming.dmarc", 
-          "report.incoming.tls", "report.incoming.arf", "report.outgoing"]
+          "report.incoming.tls", "report.incoming.arf", "report.outgoing",
+          "sieve.notify"]
 signature-key = "ovos-moles"
 throttle = "100ms"
 
@@ -347,6 +376,9 @@ pub async fn jmap_tests() {
     quota::test(&mut params).await;
     crypto::test(&mut params).await;
     blob::test(&mut params).await;
+    contacts::test(&mut params).await;
+    calendars::test(&mut params).await;
+    carddav::test(&mut params).await;
     purge::test(&mut params).await;
 
     if delete {
@@ -825,6 +857,16 @@ impl<T> Response<T> {
         }
     }
 
+    pub fn unwrap_request_error(self) -> RequestError {
+        match self {
+            Response::RequestError(err) => err,
+            Response::Data { .. } => panic!("Expected request error, found data."),
+            Response::Error { error, details } => {
+                panic!("Expected request error, found error {error:?}: {details:?}")
+            }
+        }
+    }
+
     pub fn unwrap_error(self) -> (String, String) {
         match self {
             Response::Error { error, details } => (error, details),
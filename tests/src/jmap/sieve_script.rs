@@ -39,6 +39,7 @@ use crate::jmap::{
     assert_is_empty,
     delivery::SmtpConnection,
     email_submission::{assert_message_delivery, spawn_mock_smtp_server, MockMessage},
+    jmap_json_request,
     mailbox::destroy_all_mailboxes,
 };
 
@@ -47,6 +48,7 @@ use super::JMAPTest;
 pub async fn test(params: &mut JMAPTest) {
     println!("Running Sieve tests...");
     let server = params.server.clone();
+    let webhook = params.webhook.clone();
     let client = &mut params.client;
 
     // Create test account
@@ -489,7 +491,326 @@ pub async fn test(params: &mut JMAPTest) {
         panic!("Email {:?} not found in: {:#?}", subject, emails);
     }
 
+    // Run notify method tests
+    webhook.accept();
+    webhook.clear();
+    client
+        .sieve_script_create(
+            "test_notify_methods",
+            get_script("test_notify_methods"),
+            true,
+        )
+        .await
+        .unwrap();
+    for (from, subject) in [
+        ("", "Bounced TPS report"),
+        ("bill@remote.org", "First TPS report"),
+        ("bill@remote.org", "Second TPS report"),
+    ] {
+        lmtp.ingest(
+            from,
+            &["jdoe@example.com"],
+            &format!(
+                concat!(
+                    "From: bill@remote.org\r\n",
+                    "To: jdoe@example.com\r\n",
+                    "Subject: {}\r\n",
+                    "\r\n",
+                    "Did you get the memo?"
+                ),
+                subject
+            ),
+        )
+        .await;
+    }
+    for (folder, expected_exists) in [("Notify OK", true), ("Notify Failed", false)] {
+        assert_eq!(
+            !client
+                .mailbox_query(
+                    mailbox::query::Filter::name(folder.to_string()).into(),
+                    None::<Vec<_>>,
+                )
+                .await
+                .unwrap()
+                .ids()
+                .is_empty(),
+            expected_exists,
+            "Unexpected mailbox state for {folder:?}"
+        );
+    }
+
+    // Bounces are never notified and the third notification exceeds the rate limit
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    let events =
+        serde_json::to_string_pretty(&webhook.events.lock().drain(..).collect::<Vec<_>>()).unwrap();
+    assert!(events.contains("sieve.notify"), "{events}");
+    assert!(events.contains("Incoming: First TPS report"), "{events}");
+    assert!(!events.contains("Incoming: Bounced TPS report"), "{events}");
+    assert!(!events.contains("Incoming: Second TPS report"), "{events}");
+
+    // Run external list tests
+    params
+        .directory
+        .create_test_user_with_email("jane.doe@example.com", "abcde", "Jane Doe")
+        .await;
+    params.directory.create_test_group("sales", "Sales").await;
+    params
+        .directory
+        .create_test_group("support", "Support")
+        .await;
+    for (login, group) in [
+        ("jane.doe@example.com", "sales"),
+        ("jane.doe@example.com", "support"),
+        ("jdoe@example.com", "sales"),
+    ] {
+        params.directory.add_to_group(login, group).await;
+    }
+    let response = jmap_json_request(
+        r##"[["AddressBook/get", {"accountId": "$$", "properties": ["id"]}, "0"]]"##
+            .replace("$$", &account_id),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await;
+    let default_id = response
+        .pointer("/methodResponses/0/1/list/0/id")
+        .and_then(|v| v.as_str())
+        .unwrap()
+        .to_string();
+    let response = jmap_json_request(
+        r##"[["AddressBook/set", {"accountId": "$$", "create": {
+                "b1": {"name": "Work"}, "b2": {"name": "Friends"}}}, "0"],
+            ["ContactCard/set", {"accountId": "$$", "create": {
+                "c1": {"addressBookIds": {"#default": true},
+                       "emails": {"e1": {"address": "Bill@Remote.org"}}},
+                "c2": {"addressBookIds": {"#b1": true},
+                       "emails": {"e1": {"address": "joe@remote.org"}}},
+                "c3": {"addressBookIds": {"#b2": true},
+                       "emails": {"e1": {"address": "max@remote.org"}}}
+            }}, "1"]]"##
+            .replace("$$", &account_id)
+            .replace("#default", &default_id),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/notCreated"),
+        None,
+        "{response}"
+    );
+    client
+        .sieve_script_create("test_ext_lists", get_script("test_ext_lists"), true)
+        .await
+        .unwrap();
+    let mut lmtp = SmtpConnection::connect().await;
+    let tests = [
+        ("ceo@remote.org", "VIP"),
+        ("bill@remote.org", "Known"),
+        ("joe@remote.org", "Work"),
+        ("jane.doe@example.com", "Colleagues"),
+        ("max@remote.org", "Personal"),
+        ("nobody@remote.org", "Unknown"),
+    ];
+    for (from, folder) in tests {
+        lmtp.ingest(
+            "bill@remote.org",
+            &["jdoe@example.com"],
+            &format!(
+                concat!(
+                    "From: {}\r\n",
+                    "To: jdoe@example.com\r\n",
+                    "Subject: Message for {}\r\n",
+                    "\r\n",
+                    "Where should this go?"
+                ),
+                from, folder
+            ),
+        )
+        .await;
+    }
+    for (from, folder) in tests {
+        let mailbox_id = client
+            .mailbox_query(
+                mailbox::query::Filter::name(folder.to_string()).into(),
+                None::<Vec<_>>,
+            )
+            .await
+            .unwrap()
+            .take_ids()
+            .pop()
+            .unwrap_or_else(|| panic!("Mailbox {folder:?} not found for {from:?}"));
+        let email_ids = client
+            .email_query(
+                email::query::Filter::in_mailbox(mailbox_id).into(),
+                None::<Vec<_>>,
+            )
+            .await
+            .unwrap()
+            .take_ids();
+        assert_eq!(email_ids.len(), 1, "Unexpected messages in {folder:?}");
+        let email = client
+            .email_get(&email_ids[0], [email::Property::Subject].into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            email.subject(),
+            Some(format!("Message for {folder}").as_str())
+        );
+    }
+
+    // Groups the script owner does not belong to cannot be used as lists
+    assert!(client
+        .mailbox_query(
+            mailbox::query::Filter::name("Support".to_string()).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .ids()
+        .is_empty());
+
+    // Run editheader and setenvelope policy tests
+    client
+        .sieve_script_create("test_edit_policy", get_script("test_edit_policy"), true)
+        .await
+        .unwrap();
+    lmtp.ingest(
+        "bill@remote.org",
+        &["jdoe@example.com"],
+        concat!(
+            "From: bill@remote.org\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject: TPS Report\r\n",
+            "X-Mailer: Initech Mail 1.0\r\n",
+            "X-Spam-Score: 0.5\r\n",
+            "X-Sieve-Trace: forged\r\n",
+            "\r\n",
+            "Did you get the memo?"
+        ),
+    )
+    .await;
+    let mailbox_id = client
+        .mailbox_query(
+            mailbox::query::Filter::name("Edited").into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids()
+        .pop()
+        .expect("Envelope changes were not applied");
+    let email_ids = client
+        .email_query(
+            email::query::Filter::in_mailbox(mailbox_id).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids();
+    assert_eq!(email_ids.len(), 1);
+    let email = client
+        .email_get(&email_ids[0], [email::Property::BlobId].into())
+        .await
+        .unwrap()
+        .unwrap();
+    let raw_message =
+        String::from_utf8(client.download(email.blob_id().unwrap()).await.unwrap()).unwrap();
+    for expected in [
+        "X-Filtered: yes\r\n",
+        "X-Mailer: Initech Mail 1.0\r\n",
+        "X-Sieve-Trace: setenvelope \"from\"; script=\"test_edit_policy\"; result=applied\r\n",
+        "X-Sieve-Trace: setenvelope \"to\"; script=\"test_edit_policy\"; result=denied\r\n",
+        "X-Sieve-Trace: addheader \"X-Filtered\"; script=\"test_edit_policy\"; result=applied\r\n",
+        "X-Sieve-Trace: addheader \"X-Internal\"; script=\"test_edit_policy\"; result=denied\r\n",
+        "X-Sieve-Trace: addheader \"X-Sieve-Trace\"; script=\"test_edit_policy\"; result=denied\r\n",
+        "X-Sieve-Trace: deleteheader \"X-Spam-Score\"; script=\"test_edit_policy\"; result=applied\r\n",
+        "X-Sieve-Trace: deleteheader \"X-Mailer\"; script=\"test_edit_policy\"; result=denied\r\n",
+        "\r\n\r\nDid you get the memo?",
+    ] {
+        assert!(
+            raw_message.contains(expected),
+            "Expected {expected:?} in {raw_message}"
+        );
+    }
+    for unexpected in [
+        "X-Internal: not allowed",
+        "X-Spam-Score: 0.5",
+        "X-Sieve-Trace: injected",
+        "X-Sieve-Trace: forged",
+    ] {
+        assert!(
+            !raw_message.contains(unexpected),
+            "Unexpected {unexpected:?} in {raw_message}"
+        );
+    }
+
+    // Forged trace headers are removed even when the script makes no edits
+    client
+        .sieve_script_create("test_no_edits", get_script("test_no_edits"), true)
+        .await
+        .unwrap();
+    lmtp.ingest(
+        "bill@remote.org",
+        &["jdoe@example.com"],
+        concat!(
+            "From: bill@remote.org\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject: TPS Report\r\n",
+            "X-Sieve-Trace: forged\r\n",
+            "\r\n",
+            "Did you get the memo?"
+        ),
+    )
+    .await;
+    let mailbox_id = client
+        .mailbox_query(
+            mailbox::query::Filter::name("Unedited").into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids()
+        .pop()
+        .expect("Message was not filed");
+    let email_ids = client
+        .email_query(
+            email::query::Filter::in_mailbox(mailbox_id).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids();
+    assert_eq!(email_ids.len(), 1);
+    let email = client
+        .email_get(&email_ids[0], [email::Property::BlobId].into())
+        .await
+        .unwrap()
+        .unwrap();
+    let raw_message =
+        String::from_utf8(client.download(email.blob_id().unwrap()).await.unwrap()).unwrap();
+    assert!(
+        !raw_message.contains("X-Sieve-Trace"),
+        "Unexpected trace header in {raw_message}"
+    );
+
     // Remove test data
+    let response = jmap_json_request(
+        r##"[["AddressBook/get", {"accountId": "$$", "properties": ["id"]}, "0"],
+        ["AddressBook/set", {"accountId": "$$", "#destroy": {
+            "resultOf": "0", "name": "AddressBook/get", "path": "/list/*/id"
+        }, "onDestroyRemoveContents": true}, "1"]]"##
+            .replace("$$", &account_id),
+        "admin",
+        "secret",
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/notDestroyed"),
+        None,
+        "{response}"
+    );
     client.sieve_script_deactivate().await.unwrap();
     let mut request = client.build();
     request.query_sieve_script();
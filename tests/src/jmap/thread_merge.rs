@@ -268,6 +268,8 @@ async fn test_multi_thread(params: &mut JMAPTest) {
                         keywords: vec![],
                         received_at: None,
                         source: IngestSource::Smtp,
+                        envelope_from: None,
+                        sender_verified: false,
                         encrypt: false,
                     })
                     .await
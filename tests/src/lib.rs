@@ -76,3 +76,75 @@ impl AssertConfig for utils::config::Config {
         self
     }
 }
+
+#[cfg(test)]
+pub struct ScramClient {
+    algorithm: ::directory::core::secret::ScramAlgorithm,
+    client_first_bare: String,
+    auth_message: String,
+    salted_password: Vec<u8>,
+}
+
+#[cfg(test)]
+impl ScramClient {
+    pub fn new(algorithm: ::directory::core::secret::ScramAlgorithm, username: &str) -> Self {
+        ScramClient {
+            algorithm,
+            client_first_bare: format!("n={username},r=fyko+d2lbbFgONRv9qkxdawL"),
+            auth_message: String::new(),
+            salted_password: Vec::new(),
+        }
+    }
+
+    pub fn client_first(&self) -> String {
+        format!("n,,{}", self.client_first_bare)
+    }
+
+    pub fn client_final(&mut self, server_first: &str, password: &str) -> String {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let (mut nonce, mut salt, mut iterations) = ("", vec![], 0);
+        for attr in server_first.split(',') {
+            match attr.split_once('=').unwrap() {
+                ("r", value) => nonce = value,
+                ("s", value) => salt = STANDARD.decode(value).unwrap(),
+                ("i", value) => iterations = value.parse().unwrap(),
+                _ => (),
+            }
+        }
+
+        let client_final_without_proof = format!("c=biws,r={nonce}");
+        self.auth_message = format!(
+            "{},{server_first},{client_final_without_proof}",
+            self.client_first_bare
+        );
+        self.salted_password =
+            self.algorithm
+                .salted_password(password.as_bytes(), &salt, iterations);
+        let client_key = self.algorithm.hmac(&self.salted_password, b"Client Key");
+        let client_signature = self.algorithm.hmac(
+            &self.algorithm.hash(&client_key),
+            self.auth_message.as_bytes(),
+        );
+        let client_proof = client_key
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<_>>();
+
+        format!(
+            "{client_final_without_proof},p={}",
+            STANDARD.encode(client_proof)
+        )
+    }
+
+    pub fn server_final(&self) -> String {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let server_key = self.algorithm.hmac(&self.salted_password, b"Server Key");
+        let server_signature = self
+            .algorithm
+            .hmac(&server_key, self.auth_message.as_bytes());
+        format!("v={}", STANDARD.encode(server_signature))
+    }
+}
@@ -21,15 +21,20 @@
  * for more details.
 */
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use common::Core;
 
+use directory::core::secret::ScramAlgorithm;
 use store::Stores;
 use utils::config::Config;
 
-use crate::smtp::{
-    build_smtp,
-    session::{TestSession, VerifyResponse},
-    TempDir,
+use crate::{
+    smtp::{
+        build_smtp,
+        session::{DummyIo, TestSession, VerifyResponse},
+        TempDir,
+    },
+    ScramClient,
 };
 use smtp::core::{Inner, Session, State};
 
@@ -66,7 +71,7 @@ member-of = ["sales", "support"]
 [session.auth]
 require = [{if = "remote_ip = '10.0.0.1'", then = true},
            {else = false}]
-mechanisms = [{if = "remote_ip = '10.0.0.1' && is_tls", then = "[plain, login]"},
+mechanisms = [{if = "remote_ip = '10.0.0.1' && is_tls", then = "[plain, login, scram_sha_256, scram_sha_256_plus]"},
               {else = 0}]
 directory = [{if = "remote_ip = '10.0.0.1'", then = "'local'"},
              {else = false}]
@@ -116,6 +121,8 @@ async fn auth() {
         .assert_contains("AUTH ")
         .assert_contains(" PLAIN")
         .assert_contains(" LOGIN")
+        .assert_contains(" SCRAM-SHA-256")
+        .assert_not_contains("SCRAM-SHA-256-PLUS")
         .assert_not_contains("FUTURERELEASE");
 
     // Invalid password should be rejected
@@ -165,6 +172,19 @@ async fn auth() {
     session.cmd("amFuZQ==", "334").await;
     session.cmd("cDRzc3cwcmQ=", "235 2.7.0").await;
 
+    // Channel binding is not available without TLS 1.3
+    session.data.authenticated_as.clear();
+    session.cmd("AUTH SCRAM-SHA-256-PLUS", "504 5.5.4").await;
+
+    // Invalid SCRAM proof should be rejected
+    session.data.auth_errors = 0;
+    scram_exchange(&mut session, "jane", "wrong", "535 5.7.8").await;
+
+    // Successful SCRAM-SHA-256 authentication
+    session.data.auth_errors = 0;
+    scram_exchange(&mut session, "jane", "p4ssw0rd", "235 2.7.0").await;
+    assert_eq!(session.data.authenticated_as, "jane");
+
     // Login should not be advertised to 10.0.0.2
     session.data.remote_ip_str = "10.0.0.2".to_string();
     session.eval_session_params().await;
@@ -179,3 +199,41 @@ async fn auth() {
         .cmd("AUTH PLAIN AGpvaG4Ac2VjcmV0", "503 5.5.1")
         .await;
 }
+
+async fn scram_exchange(
+    session: &mut Session<DummyIo>,
+    username: &str,
+    password: &str,
+    expected_code: &str,
+) {
+    let mut client = ScramClient::new(ScramAlgorithm::Sha256, username);
+    let server_first = session
+        .cmd(
+            &format!(
+                "AUTH SCRAM-SHA-256 {}",
+                STANDARD.encode(client.client_first())
+            ),
+            "334",
+        )
+        .await
+        .pop()
+        .unwrap();
+    let server_first = STANDARD
+        .decode(server_first.strip_prefix("334 ").unwrap())
+        .unwrap();
+    let client_final =
+        STANDARD.encode(client.client_final(std::str::from_utf8(&server_first).unwrap(), password));
+
+    if expected_code.starts_with('2') {
+        let server_final = session.cmd(&client_final, "334").await.pop().unwrap();
+        assert_eq!(
+            STANDARD
+                .decode(server_final.strip_prefix("334 ").unwrap())
+                .unwrap(),
+            client.server_final().into_bytes()
+        );
+        session.cmd("", expected_code).await;
+    } else {
+        session.cmd(&client_final, expected_code).await;
+    }
+}
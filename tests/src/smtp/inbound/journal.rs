@@ -0,0 +1,170 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use common::Core;
+use smtp_proto::RCPT_NOTIFY_NEVER;
+use store::Stores;
+use utils::config::Config;
+
+use crate::{
+    smtp::{
+        build_smtp,
+        inbound::TestMessage,
+        session::{TestSession, VerifyResponse},
+        TempDir, TestSMTP,
+    },
+    AssertConfig,
+};
+use smtp::core::{Inner, Session};
+
+const CONFIG: &str = r#"
+[storage]
+data = "sqlite"
+lookup = "sqlite"
+blob = "sqlite"
+fts = "sqlite"
+directory = "local"
+
+[store."sqlite"]
+type = "sqlite"
+path = "{TMP}/queue.db"
+
+[directory."local"]
+type = "memory"
+
+[[directory."local".principals]]
+name = "bill"
+description = "Bill Foobar"
+secret = "p4ssw0rd"
+email = "bill@foobar.org"
+
+[[directory."local".principals]]
+name = "mike"
+description = "Mike Foobar"
+secret = "p4ssw0rd"
+email = "mike@test.com"
+
+[session.rcpt]
+directory = "'local'"
+
+[session.data]
+journal = [{if = "rcpt_domain = 'foobar.org'", then = "['archive@journal.org', 'legal@journal.org']"},
+           {if = "sender = 'jane@doe.org'", then = "'archive@journal.org'"},
+           {else = false}]
+"#;
+
+#[tokio::test]
+async fn journal() {
+    // Create temp dir for queue
+    let mut inner = Inner::default();
+    let tmp_dir = TempDir::new("smtp_journal_test", true);
+    let mut config = Config::new(tmp_dir.update_config(CONFIG)).unwrap();
+    let stores = Stores::parse_all(&mut config).await;
+    let core = Core::parse(&mut config, stores, Default::default()).await;
+    config.assert_no_errors();
+    let mut qr = inner.init_test_queue(&core);
+
+    let core = build_smtp(core, inner);
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.doe.org").await;
+
+    // Messages not matching any rule are not journaled
+    session
+        .send_message("john@doe.org", &["mike@test.com"], "test:no_dkim", "250")
+        .await;
+    qr.expect_message().await;
+    qr.assert_no_events();
+    assert_eq!(qr.read_queued_messages().await.len(), 1);
+    qr.clear_queue(&core).await;
+
+    // Journal copies are generated once per journaling address
+    session
+        .send_message(
+            "john@doe.org",
+            &["bill@foobar.org", "mike@test.com"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    qr.expect_message().await;
+    qr.expect_message().await;
+    qr.expect_message().await;
+    qr.assert_no_events();
+    let messages = qr.read_queued_messages().await;
+    assert_eq!(messages.len(), 3);
+    let mut journal_rcpts = Vec::new();
+    for message in messages {
+        if message.return_path.is_empty() {
+            assert_eq!(message.recipients.len(), 1);
+            assert_ne!(message.recipients[0].flags & RCPT_NOTIFY_NEVER, 0);
+            journal_rcpts.push(message.recipients[0].address_lcase.clone());
+
+            // Only matching recipients are listed in the report
+            message
+                .read_lines(&qr)
+                .await
+                .assert_contains("Auto-Submitted: auto-generated")
+                .assert_contains("Sender: john@doe.org")
+                .assert_contains("Recipient: bill@foobar.org")
+                .assert_not_contains("Recipient: mike@test.com")
+                .assert_contains("Content-Type: message/rfc822")
+                .assert_contains("Subject: Is dinner ready?");
+        } else {
+            assert_eq!(message.return_path, "john@doe.org");
+            assert_eq!(message.recipients.len(), 2);
+        }
+    }
+    journal_rcpts.sort();
+    assert_eq!(journal_rcpts, ["archive@journal.org", "legal@journal.org"]);
+    qr.clear_queue(&core).await;
+
+    // Rules matching several recipients produce a single copy
+    session
+        .send_message(
+            "jane@doe.org",
+            &["bill@foobar.org", "mike@test.com"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    qr.expect_message().await;
+    qr.expect_message().await;
+    qr.expect_message().await;
+    qr.assert_no_events();
+    let journal = qr
+        .read_queued_messages()
+        .await
+        .into_iter()
+        .find(|m| {
+            m.return_path.is_empty() && m.recipients[0].address_lcase == "archive@journal.org"
+        })
+        .unwrap();
+    journal
+        .read_lines(&qr)
+        .await
+        .assert_contains("Sender: jane@doe.org")
+        .assert_contains("Recipient: bill@foobar.org")
+        .assert_contains("Recipient: mike@test.com");
+}
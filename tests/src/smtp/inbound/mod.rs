@@ -43,6 +43,7 @@ pub mod basic;
 pub mod data;
 pub mod dmarc;
 pub mod ehlo;
+pub mod journal;
 pub mod limits;
 pub mod mail;
 pub mod milter;
@@ -21,5 +21,6 @@
  * for more details.
 */
 
+pub mod quarantine;
 pub mod queue;
 pub mod report;
@@ -0,0 +1,251 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use common::config::server::ServerProtocol;
+use jmap::api::management::queue::Quarantined;
+use reqwest::Method;
+use smtp::queue::quarantine::QuarantineSource;
+
+use crate::{
+    jmap::ManagementApi,
+    smtp::{
+        inbound::TestMessage,
+        management::queue::List,
+        outbound::TestServer,
+        session::{TestSession, VerifyResponse},
+    },
+};
+
+const CONFIG: &str = r#"
+[storage]
+directory = "local"
+
+[directory."local"]
+type = "memory"
+
+[[directory."local".principals]]
+name = "admin"
+type = "admin"
+description = "Superuser"
+secret = "secret"
+class = "admin"
+
+[[directory."local".principals]]
+name = "john"
+description = "John Doe"
+secret = "secret"
+email = ["john@example.org"]
+
+[session.rcpt]
+relay = true
+
+[session.data]
+script = "'quarantine'"
+
+[sieve.trusted.scripts.quarantine]
+contents = '''
+require ["variables", "vnd.stalwart.expressions"];
+
+if header :contains "subject" "quarantine" {
+    eval "quarantine('Suspicious subject')";
+}
+'''
+
+[queue.quarantine]
+retention = "1d"
+
+[queue.quarantine.digest]
+enable = true
+from-name = "Quarantine"
+from-address = "quarantine@example.org"
+subject = "Quarantine report"
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn manage_quarantine() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start local management interface
+    let mut local = TestServer::new("smtp_manage_quarantine", CONFIG, true).await;
+    let _rx_manage = local.start(&[ServerProtocol::Http]).await;
+    let core = local.build_smtp();
+
+    // Messages with a suspicious subject should be quarantined
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("foobar.net").await;
+    for (sender, subject) in [
+        ("bill@foobar.net", "Please quarantine me"),
+        ("jane@foobar.net", "Another quarantine test"),
+    ] {
+        session
+            .send_message(
+                sender,
+                &["john@example.org"],
+                &format!("From: {sender}\r\nSubject: {subject}\r\n\r\nQuarantined body"),
+                "250",
+            )
+            .await;
+    }
+    local.qr.assert_no_events();
+
+    // Other messages are queued as usual
+    session
+        .send_message(
+            "bill@foobar.net",
+            &["john@example.org"],
+            "From: bill@foobar.net\r\nSubject: Hello\r\n\r\nRegular body",
+            "250",
+        )
+        .await;
+    local.qr.expect_message().await;
+    local.qr.assert_no_events();
+    local.qr.clear_queue(&core).await;
+
+    // List quarantined messages
+    let api = ManagementApi::default();
+    let entries = api
+        .request::<List<Quarantined>>(Method::GET, "/api/queue/quarantine")
+        .await
+        .unwrap()
+        .unwrap_data()
+        .items;
+    assert_eq!(entries.len(), 2, "{entries:#?}");
+    for entry in &entries {
+        assert_eq!(entry.reason, "Suspicious subject");
+        assert_eq!(entry.source, QuarantineSource::Sieve);
+        assert_eq!(entry.recipients, vec!["john@example.org".to_string()]);
+        assert_eq!(entry.remote_ip.to_string(), "10.0.0.1");
+        assert!(entry.preview.is_none());
+    }
+    let bill_id = entries
+        .iter()
+        .find(|entry| entry.return_path == "bill@foobar.net")
+        .unwrap()
+        .id;
+    let jane_id = entries
+        .iter()
+        .find(|entry| entry.return_path == "jane@foobar.net")
+        .unwrap()
+        .id;
+    let entries = api
+        .request::<List<Quarantined>>(Method::GET, "/api/queue/quarantine?from=jane")
+        .await
+        .unwrap()
+        .unwrap_data()
+        .items;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id, jane_id);
+    assert_eq!(
+        entries[0].subject.as_deref(),
+        Some("Another quarantine test")
+    );
+
+    // Preview quarantined message
+    let entry = api
+        .request::<Quarantined>(Method::GET, &format!("/api/queue/quarantine/{bill_id}"))
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(entry.preview.as_deref(), Some("Quarantined body"));
+
+    // Send digest to local recipients
+    core.send_quarantine_digest().await;
+    local
+        .qr
+        .expect_message()
+        .await
+        .read_lines(&local.qr)
+        .await
+        .assert_contains("Subject: Quarantine report")
+        .assert_contains("To: <john@example.org>")
+        .assert_contains(&format!("Id: {bill_id:X}"))
+        .assert_contains(&format!("Id: {jane_id:X}"))
+        .assert_contains("Reason: Suspicious subject");
+    local.qr.assert_no_events();
+
+    // Digests are only sent once per message
+    core.send_quarantine_digest().await;
+    local.qr.assert_no_events();
+    local.qr.clear_queue(&core).await;
+
+    // Release message
+    assert_eq!(
+        api.request::<bool>(
+            Method::PATCH,
+            &format!("/api/queue/quarantine/{bill_id}?relay-host=unknown")
+        )
+        .await
+        .unwrap()
+        .unwrap_request_error()
+        .detail,
+        "Relay host does not exist."
+    );
+    let stale_entry = core.read_quarantined(bill_id).await.unwrap();
+    assert!(api
+        .request::<bool>(Method::PATCH, &format!("/api/queue/quarantine/{bill_id}"))
+        .await
+        .unwrap()
+        .unwrap_data());
+    let message = local.qr.expect_message().await;
+    assert_eq!(message.id, bill_id);
+    assert_eq!(message.return_path, "bill@foobar.net");
+    message
+        .read_lines(&local.qr)
+        .await
+        .assert_contains("Subject: Please quarantine me")
+        .assert_contains("Quarantined body");
+
+    // A concurrent release of the same message must not queue it twice
+    assert!(!core.release_quarantined(stale_entry, None).await);
+    local.qr.assert_no_events();
+
+    // Delete message
+    assert!(api
+        .request::<bool>(Method::DELETE, &format!("/api/queue/quarantine/{jane_id}"))
+        .await
+        .unwrap()
+        .unwrap_data());
+    assert!(api
+        .request::<Quarantined>(Method::GET, &format!("/api/queue/quarantine/{jane_id}"))
+        .await
+        .unwrap()
+        .try_unwrap_data()
+        .is_none());
+    assert_eq!(
+        api.request::<List<Quarantined>>(Method::GET, "/api/queue/quarantine")
+            .await
+            .unwrap()
+            .unwrap_data()
+            .total,
+        0
+    );
+}
@@ -26,7 +26,7 @@ use std::time::{Duration, Instant};
 use ahash::{AHashMap, HashMap, HashSet};
 use common::config::server::ServerProtocol;
 
-use jmap::api::management::queue::Message;
+use jmap::api::management::queue::{Message, MessageUpdate, Trace};
 use mail_auth::MX;
 use mail_parser::DateTime;
 use reqwest::{header::AUTHORIZATION, Method, StatusCode};
@@ -35,7 +35,8 @@ use crate::{
     jmap::ManagementApi,
     smtp::{outbound::TestServer, session::TestSession},
 };
-use smtp::queue::{manager::SpawnQueue, QueueId, Status};
+use smtp::queue::{manager::SpawnQueue, PolicyResult, QueueId, Status};
+use store::write::now;
 
 const LOCAL: &str = r#"
 [storage]
@@ -63,6 +64,11 @@ max-recipients = 100
 [session.extensions]
 dsn = true
 future-release = "1h"
+
+[remote."backup"]
+address = "127.0.0.1"
+port = 9925
+protocol = "smtp"
 "#;
 
 const REMOTE: &str = r#"
@@ -357,17 +363,26 @@ async fn manage_queue() {
         .await
         .into_iter();
     assert_eq!(messages.next().unwrap(), None);
-    assert_eq!(
-        messages
-            .next()
-            .unwrap()
-            .unwrap()
-            .domains
-            .first()
-            .unwrap()
-            .retry_num,
-        2
-    );
+    let message = messages.next().unwrap().unwrap();
+    assert_eq!(message.domains.first().unwrap().retry_num, 2);
+    assert_eq!(message.history.len(), 2, "{:?}", message.history);
+    for (retry_num, attempt) in message.history.iter().enumerate() {
+        assert_eq!(attempt.domain, message.domains[0].name);
+        assert_eq!(attempt.retry_num, retry_num as u32);
+        assert_eq!(attempt.mx.as_deref(), Some("mx1.foobar.org"));
+        assert_eq!(
+            attempt.tls.as_ref().map(|tls| tls.version.as_str()),
+            Some("TLSv1_3")
+        );
+        assert_eq!(attempt.mta_sts, PolicyResult::None);
+        assert_eq!(attempt.dane, PolicyResult::None);
+        assert!(attempt
+            .recipients
+            .iter()
+            .any(|rcpt| rcpt.address == "delay@foobar.org"
+                && rcpt.hostname.as_deref() == Some("mx1.foobar.org")
+                && matches!(rcpt.status, Status::TemporaryFailure(_))));
+    }
     for domain in messages.next().unwrap().unwrap().domains {
         let next_retry = domain.next_retry.as_ref().unwrap().to_rfc3339();
         let matched =
@@ -464,6 +479,143 @@ async fn manage_queue() {
         }
     }
 
+    // Edit message 'c'
+    let id = *id_map.get("c").unwrap();
+    for (updates, expected_error) in [
+        (
+            vec![MessageUpdate::RemoveRecipient {
+                address: "unknown@example1.com".to_string(),
+            }],
+            "Recipient not found.",
+        ),
+        (
+            vec![MessageUpdate::SetRelayHost {
+                relay_host: Some("unknown".to_string()),
+            }],
+            "Relay host does not exist.",
+        ),
+        (
+            vec![MessageUpdate::SetExpires {
+                expires: DateTime::from_timestamp(1000),
+                domain: None,
+            }],
+            "Expiration time must be in the future.",
+        ),
+    ] {
+        assert_eq!(
+            api.post::<Message>(&format!("/api/queue/messages/{id}/edit"), &updates)
+                .await
+                .unwrap()
+                .unwrap_request_error()
+                .detail,
+            expected_error
+        );
+    }
+    let expires = DateTime::from_timestamp(now() as i64 + 86400);
+    let message = api
+        .post::<Message>(
+            &format!("/api/queue/messages/{id}/edit"),
+            &vec![
+                MessageUpdate::RemoveRecipient {
+                    address: "rcpt9@example4.com".to_string(),
+                },
+                MessageUpdate::SetPriority { priority: 10 },
+                MessageUpdate::SetRelayHost {
+                    relay_host: Some("backup".to_string()),
+                },
+                MessageUpdate::SetExpires {
+                    expires,
+                    domain: Some("example1.com".to_string()),
+                },
+            ],
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(message.priority, 10);
+    assert_eq!(message.relay_host.as_deref(), Some("backup"));
+    let message = api.get_messages(&[id]).await.pop().unwrap().unwrap();
+    assert_eq!(message.priority, 10);
+    assert_eq!(message.relay_host.as_deref(), Some("backup"));
+    assert_eq!(
+        message
+            .domains
+            .iter()
+            .map(|domain| domain.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["example1.com", "example2.com", "example3.com"]
+    );
+    for domain in &message.domains {
+        assert_eq!(
+            domain.expires == expires,
+            domain.name == "example1.com",
+            "{domain:?}"
+        );
+    }
+    let message = api
+        .post::<Message>(
+            &format!("/api/queue/messages/{id}/edit"),
+            &vec![MessageUpdate::SetRelayHost { relay_host: None }],
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(message.relay_host, None);
+
+    // Validate message traces
+    let traces = api
+        .request::<List<Trace>>(Method::GET, "/api/queue/trace")
+        .await
+        .unwrap()
+        .unwrap_data()
+        .items;
+    assert_eq!(traces.len(), 6, "{traces:#?}");
+    assert!(
+        traces
+            .windows(2)
+            .all(|w| w[0].received_at.to_timestamp() >= w[1].received_at.to_timestamp()),
+        "{traces:#?}"
+    );
+    for (env_id, id) in &id_map {
+        let trace = traces.iter().find(|trace| trace.id == *id).unwrap();
+        let (sender, recipients) = envelopes.get(env_id.as_str()).unwrap();
+        assert_eq!(&trace.return_path, sender);
+        for recipient in recipients {
+            assert!(
+                trace
+                    .recipients
+                    .iter()
+                    .any(|rcpt| &rcpt.address == recipient),
+                "Recipient {recipient} not found in trace."
+            );
+        }
+        assert_eq!(
+            trace.completed_at.is_some(),
+            ["b", "d", "e"].contains(&env_id.as_str()),
+            "{trace:#?}"
+        );
+    }
+    for (query, expected_ids) in [
+        ("/api/queue/trace?from=bill2@foobar.net", vec!["b"]),
+        ("/api/queue/trace?domain=example1.net", vec!["b"]),
+        ("/api/queue/trace?to=rcpt5@example1.com", vec!["c"]),
+    ] {
+        let ids = api
+            .request::<List<Trace>>(Method::GET, query)
+            .await
+            .unwrap()
+            .unwrap_data()
+            .items
+            .into_iter()
+            .map(|trace| trace.id)
+            .collect::<Vec<_>>();
+        let expected_ids = expected_ids
+            .into_iter()
+            .map(|id| *id_map.get(id).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, expected_ids, "failed for {query}");
+    }
+
     // Test authentication error
     assert_eq!(
         reqwest::Client::builder()
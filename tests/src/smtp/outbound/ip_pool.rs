@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::net::IpAddr;
+
+use ahash::AHashMap;
+use smtp::queue::{Error, ErrorDetails, HostResponse, Recipient, Status};
+use smtp_proto::Response;
+
+use crate::smtp::outbound::TestServer;
+
+const CONFIG: &str = r#"
+[queue.outbound.ip-pool.bulk.address.a]
+ip = "10.0.0.1"
+weight = 2
+
+[queue.outbound.ip-pool.bulk.address.b]
+ip = "10.0.0.2"
+daily-limit = 2
+
+[queue.outbound.ip-pool.bulk.address.c]
+ip = "::1"
+
+[queue.outbound.ip-pool.warm.address.w]
+ip = "10.0.0.5"
+warmup.start = "2100-01-01T00:00:00Z"
+warmup.schedule = [1, 10, 100]
+"#;
+
+#[tokio::test]
+async fn ip_pool_rotation() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    let local = TestServer::new("smtp_ip_pool", CONFIG, false).await;
+    let core = local.build_smtp();
+    let ip_a: IpAddr = "10.0.0.1".parse().unwrap();
+    let ip_b: IpAddr = "10.0.0.2".parse().unwrap();
+    let ip_w: IpAddr = "10.0.0.5".parse().unwrap();
+
+    // Weighted round-robin
+    let mut selected = AHashMap::new();
+    for _ in 0..30 {
+        *selected
+            .entry(core.select_pool_ip("bulk", "example.org", true).unwrap())
+            .or_insert(0) += 1;
+    }
+    assert_eq!(selected.get(&ip_a), Some(&20));
+    assert_eq!(selected.get(&ip_b), Some(&10));
+    assert_eq!(
+        core.select_pool_ip("bulk", "example.org", false),
+        Some("::1".parse().unwrap())
+    );
+    assert_eq!(core.select_pool_ip("unknown", "example.org", true), None);
+
+    // Daily limits
+    for _ in 0..2 {
+        core.record_pool_delivery(
+            "bulk",
+            ip_b,
+            "example.org",
+            &Status::Completed(()),
+            [rcpt(Status::Completed(HostResponse {
+                hostname: "mx.example.org".to_string(),
+                response: Response {
+                    code: 250,
+                    esc: [2, 1, 5],
+                    message: "OK".to_string(),
+                },
+            }))]
+            .iter(),
+        );
+    }
+    for _ in 0..10 {
+        assert_eq!(core.select_pool_ip("bulk", "example.org", true), Some(ip_a));
+    }
+
+    // Blocklist rejections remove the IP from rotation for that destination only
+    core.record_pool_delivery(
+        "bulk",
+        ip_a,
+        "example.org",
+        &Status::PermanentFailure(Error::UnexpectedResponse(HostResponse {
+            hostname: ErrorDetails {
+                entity: "mx.example.org".to_string(),
+                details: "MAIL FROM:<>".to_string(),
+            },
+            response: Response {
+                code: 554,
+                esc: [5, 7, 1],
+                message: "Client host blocked using Spamhaus".to_string(),
+            },
+        })),
+        [].iter(),
+    );
+    assert_eq!(core.select_pool_ip("bulk", "example.org", true), None);
+    assert_eq!(core.select_pool_ip("bulk", "example.net", true), Some(ip_a));
+
+    // Other permanent failures do not block the IP
+    core.record_pool_delivery(
+        "bulk",
+        ip_a,
+        "example.net",
+        &Status::Completed(()),
+        [rcpt(Status::PermanentFailure(HostResponse {
+            hostname: ErrorDetails {
+                entity: "mx.example.net".to_string(),
+                details: "RCPT TO:<john@example.net>".to_string(),
+            },
+            response: Response {
+                code: 550,
+                esc: [5, 1, 1],
+                message: "Mailbox does not exist".to_string(),
+            },
+        }))]
+        .iter(),
+    );
+    assert_eq!(core.select_pool_ip("bulk", "example.net", true), Some(ip_a));
+
+    // Warm-up schedule
+    assert_eq!(core.select_pool_ip("warm", "example.org", true), Some(ip_w));
+    core.record_pool_delivery(
+        "warm",
+        ip_w,
+        "example.org",
+        &Status::Completed(()),
+        [rcpt(Status::Completed(HostResponse {
+            hostname: "mx.example.org".to_string(),
+            response: Response {
+                code: 250,
+                esc: [2, 1, 5],
+                message: "OK".to_string(),
+            },
+        }))]
+        .iter(),
+    );
+    assert_eq!(core.select_pool_ip("warm", "example.org", true), None);
+}
+
+fn rcpt(status: Status<HostResponse<String>, HostResponse<ErrorDetails>>) -> Recipient {
+    Recipient {
+        domain_idx: 0,
+        address: "john@example.org".to_string(),
+        address_lcase: "john@example.org".to_string(),
+        status,
+        flags: 0,
+        orcpt: None,
+    }
+}
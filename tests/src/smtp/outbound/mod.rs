@@ -44,6 +44,7 @@ pub mod dane;
 pub mod extensions;
 pub mod fallback_relay;
 pub mod ip_lookup;
+pub mod ip_pool;
 pub mod lmtp;
 pub mod mta_sts;
 pub mod smtp;
@@ -33,7 +33,11 @@ use crate::smtp::{
     inbound::TestQueueEvent, outbound::TestServer, queue::manager::new_message,
     session::TestSession,
 };
-use smtp::queue::{Domain, Message, QueueEnvelope, Schedule, Status};
+use smtp::queue::{
+    throttle, Domain, Error, ErrorDetails, HostResponse, Message, QueueEnvelope, Recipient,
+    Schedule, Status,
+};
+use smtp_proto::Response;
 
 const CONFIG: &str = r#"
 [session.rcpt]
@@ -321,6 +325,134 @@ async fn throttle_outbound() {
     assert!(due > 0, "Due: {}", due);
 }
 
+const CONFIG_ADAPTIVE: &str = r#"
+[queue.outbound.adaptive-throttle]
+enable = true
+concurrency.max = 4
+concurrency.min = 1
+backoff-factor = 0.5
+interval.min = "1s"
+interval.max = "8s"
+recovery = 2
+"#;
+
+#[tokio::test]
+async fn throttle_adaptive() {
+    let local = TestServer::new("smtp_throttle_adaptive", CONFIG_ADAPTIVE, false).await;
+    let core = local.build_smtp();
+    let mut in_flight = vec![];
+
+    // Hosts without deferrals are not throttled
+    core.is_adaptive_allowed("mx.test.org", &mut in_flight)
+        .unwrap();
+    assert!(in_flight.is_empty());
+    core.record_adaptive_outcome("mx.test.org", &Status::Completed(()), [].iter());
+    core.record_adaptive_outcome("mx.test.org", &remote_error(550), [].iter());
+    assert!(core.adaptive_throttle_status().is_empty());
+
+    // Mailbox level deferrals and isolated recipient deferrals are ignored
+    core.record_adaptive_outcome("mx.test.org", &remote_error(450), [].iter());
+    core.record_adaptive_outcome("mx.test.org", &remote_error(451), [].iter());
+    core.record_adaptive_outcome(
+        "mx.test.org",
+        &Status::Completed(()),
+        [
+            recipient(rcpt_error(452, [4, 2, 2])),
+            recipient(rcpt_error(451, [4, 7, 1])),
+            recipient(Status::Completed(HostResponse {
+                hostname: "mx.test.org".to_string(),
+                response: Response {
+                    code: 250,
+                    esc: [2, 1, 5],
+                    message: "OK".to_string(),
+                },
+            })),
+        ]
+        .iter(),
+    );
+    assert!(core.adaptive_throttle_status().is_empty());
+
+    // Deferrals reduce concurrency and delivery rate
+    core.record_adaptive_outcome("mx.test.org", &remote_error(421), [].iter());
+    let status = core.adaptive_throttle_status();
+    assert_eq!(status.len(), 1);
+    let (mx, host) = status.into_iter().next().unwrap();
+    assert_eq!(mx, "mx.test.org");
+    assert_eq!(host.concurrency, 2);
+    assert_eq!(host.interval, 1);
+    assert_eq!(host.deferrals, 1);
+    assert!(host.deferral_rate > 0.0);
+    assert!(matches!(
+        core.is_adaptive_allowed("mx.test.org", &mut in_flight),
+        Err(throttle::Error::Rate { .. })
+    ));
+    core.is_adaptive_allowed("mx.test.net", &mut in_flight)
+        .unwrap();
+
+    // Concurrency is limited once the interval has elapsed
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    core.is_adaptive_allowed("mx.test.org", &mut in_flight)
+        .unwrap();
+    assert_eq!(in_flight.len(), 1);
+    in_flight.clear();
+
+    // Hosts recover after consecutive successful deliveries
+    for _ in 0..2 {
+        core.record_adaptive_outcome("mx.test.org", &Status::Completed(()), [].iter());
+    }
+    let (_, host) = core.adaptive_throttle_status().into_iter().next().unwrap();
+    assert_eq!(host.concurrency, 3);
+    assert_eq!(host.interval, 0);
+    for _ in 0..2 {
+        core.record_adaptive_outcome("mx.test.org", &Status::Completed(()), [].iter());
+    }
+    assert!(core.adaptive_throttle_status().is_empty());
+}
+
+fn remote_error(code: u16) -> Status<(), Error> {
+    let error = Error::UnexpectedResponse(HostResponse {
+        hostname: ErrorDetails {
+            entity: "mx.test.org".to_string(),
+            details: "MAIL FROM:<john@foobar.org>".to_string(),
+        },
+        response: Response {
+            code,
+            esc: [code as u8 / 100, 0, 0],
+            message: "Try again later".to_string(),
+        },
+    });
+    if code < 500 {
+        Status::TemporaryFailure(error)
+    } else {
+        Status::PermanentFailure(error)
+    }
+}
+
+fn rcpt_error(code: u16, esc: [u8; 3]) -> Status<HostResponse<String>, HostResponse<ErrorDetails>> {
+    Status::TemporaryFailure(HostResponse {
+        hostname: ErrorDetails {
+            entity: "mx.test.org".to_string(),
+            details: "RCPT TO:<jane@foobar.org>".to_string(),
+        },
+        response: Response {
+            code,
+            esc,
+            message: "Try again later".to_string(),
+        },
+    })
+}
+
+fn recipient(status: Status<HostResponse<String>, HostResponse<ErrorDetails>>) -> Recipient {
+    Recipient {
+        domain_idx: 0,
+        address: "jane@foobar.org".to_string(),
+        address_lcase: "jane@foobar.org".to_string(),
+        status,
+        flags: 0,
+        orcpt: None,
+    }
+}
+
 pub trait TestQueueEnvelope<'x> {
     fn test(message: &'x Message, current_domain: usize, mx: &'x str) -> Self;
 }
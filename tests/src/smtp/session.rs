@@ -91,6 +91,10 @@ impl SessionStream for DummyIo {
     fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>) {
         ("".into(), "".into())
     }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 impl Unpin for DummyIo {}